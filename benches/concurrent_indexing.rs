@@ -0,0 +1,126 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Indexes the same number of small documents with an increasing number of
+//! threads sharing one `IndexWriter`, to measure the contention of the
+//! indexing chain. Run with `cargo bench --bench concurrent_indexing`.
+
+#![feature(test)]
+
+extern crate rucene;
+extern crate test;
+
+use rucene::core::doc::{Field, FieldType, Word, WordTokenStream};
+use rucene::core::index::index_writer_config::IndexWriterConfig;
+use rucene::core::index::merge_policy::NoMergePolicy;
+use rucene::core::index::{Fieldable, IndexOptions, IndexWriter};
+use rucene::core::store::{FSDirectory, NativeFSLockFactory};
+use rucene::core::util::VariantValue;
+
+use std::env;
+use std::fs;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use test::Bencher;
+
+const TOTAL_DOCS: usize = 16_000;
+
+const WORDS: &[&str] = &[
+    "lucene", "rucene", "index", "search", "segment", "merge", "flush", "thread", "term",
+    "posting", "query", "score",
+];
+
+static RUN: AtomicUsize = AtomicUsize::new(0);
+
+fn document(thread: usize, i: usize) -> Vec<Box<dyn Fieldable>> {
+    let mut id_type = FieldType::default();
+    id_type.tokenized = false;
+    id_type.index_options = IndexOptions::Docs;
+    id_type.omit_norms = true;
+    let id = format!("{}-{}", thread, i);
+
+    let mut body_type = FieldType::default();
+    body_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+    let mut words = Vec::with_capacity(8);
+    let mut offset = 0;
+    for j in 0..8 {
+        let word = WORDS[(i * 7 + j * 3 + thread) % WORDS.len()];
+        words.push(Word::new(word, offset, word.len()));
+        offset += word.len() + 1;
+    }
+
+    vec![
+        Box::new(Field::new(
+            "id".into(),
+            id_type,
+            Some(VariantValue::VString(id)),
+            None,
+        )),
+        Box::new(Field::new(
+            "body".into(),
+            body_type,
+            None,
+            Some(Box::new(WordTokenStream::new(words))),
+        )),
+    ]
+}
+
+fn index_concurrently(num_threads: usize) {
+    let path = env::temp_dir().join(format!(
+        "rucene_bench_concurrent_indexing_{}",
+        RUN.fetch_add(1, Ordering::AcqRel)
+    ));
+    let _ = fs::remove_dir_all(&path);
+    let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+    let config = IndexWriterConfig::default().with_merge_policy(NoMergePolicy);
+    let writer = Arc::new(IndexWriter::new(dir, Arc::new(config)).unwrap());
+
+    let docs_per_thread = TOTAL_DOCS / num_threads;
+    let handles: Vec<_> = (0..num_threads)
+        .map(|t| {
+            let writer = Arc::clone(&writer);
+            thread::spawn(move || {
+                for i in 0..docs_per_thread {
+                    writer.add_document(document(t, i)).unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    writer.close().unwrap();
+    let _ = fs::remove_dir_all(&path);
+}
+
+#[bench]
+fn bench_index_1_thread(b: &mut Bencher) {
+    b.iter(|| index_concurrently(1));
+}
+
+#[bench]
+fn bench_index_4_threads(b: &mut Bencher) {
+    b.iter(|| index_concurrently(4));
+}
+
+#[bench]
+fn bench_index_16_threads(b: &mut Bencher) {
+    b.iter(|| index_concurrently(16));
+}
+
+#[bench]
+fn bench_index_32_threads(b: &mut Bencher) {
+    b.iter(|| index_concurrently(32));
+}
@@ -0,0 +1,135 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::TokenStream;
+use core::doc::{Word, WordTokenStream};
+
+use error::Result;
+
+/// An Analyzer builds TokenStreams, which analyze text. It thus represents a
+/// policy for extracting index terms from text.
+///
+/// The same analyzer should be used at index time and at query time, so that
+/// the terms produced from a query text match the indexed terms.
+pub trait Analyzer: Send + Sync {
+    /// Returns a `TokenStream` suitable for `field`, tokenizing the contents of `text`.
+    fn token_stream(&self, field: &str, text: &str) -> Result<Box<dyn TokenStream>>;
+}
+
+/// A token consumed from the `TokenStream` of an `Analyzer`, with the byte offsets
+/// of the token in the original text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalyzedToken {
+    pub term: String,
+    pub position: u32,
+    pub start_offset: usize,
+    pub end_offset: usize,
+}
+
+/// Consumes the whole `TokenStream` built by `analyzer` for the given text.
+pub fn analyze<A: Analyzer + ?Sized>(
+    analyzer: &A,
+    field: &str,
+    text: &str,
+) -> Result<Vec<AnalyzedToken>> {
+    let mut stream = analyzer.token_stream(field, text)?;
+    stream.reset()?;
+    let mut tokens = vec![];
+    let mut position = 0u32;
+    while stream.increment_token()? {
+        position += stream.position_attribute_mut().get_position_increment();
+        let term = String::from_utf8(
+            stream
+                .term_bytes_attribute()
+                .get_bytes_ref()
+                .bytes()
+                .to_vec(),
+        )?;
+        let offset = stream.offset_attribute();
+        tokens.push(AnalyzedToken {
+            term,
+            position: position.saturating_sub(1),
+            start_offset: offset.start_offset(),
+            end_offset: offset.end_offset(),
+        });
+    }
+    stream.end()?;
+    Ok(tokens)
+}
+
+/// An `Analyzer` that divides text at characters which are not letters or digits,
+/// and optionally lower cases the tokens.
+#[derive(Debug, Clone, Copy)]
+pub struct SimpleAnalyzer {
+    lower_case: bool,
+}
+
+impl Default for SimpleAnalyzer {
+    fn default() -> Self {
+        SimpleAnalyzer { lower_case: true }
+    }
+}
+
+impl SimpleAnalyzer {
+    pub fn new(lower_case: bool) -> SimpleAnalyzer {
+        SimpleAnalyzer { lower_case }
+    }
+
+    fn words(&self, text: &str) -> Vec<Word> {
+        let mut words = vec![];
+        let mut start = None;
+        for (i, c) in text.char_indices() {
+            if c.is_alphanumeric() {
+                if start.is_none() {
+                    start = Some(i);
+                }
+            } else if let Some(s) = start.take() {
+                words.push(self.word(text, s, i));
+            }
+        }
+        if let Some(s) = start {
+            words.push(self.word(text, s, text.len()));
+        }
+        words
+    }
+
+    fn word(&self, text: &str, start: usize, end: usize) -> Word {
+        let value = &text[start..end];
+        if self.lower_case {
+            Word::new(&value.to_lowercase(), start, end - start)
+        } else {
+            Word::new(value, start, end - start)
+        }
+    }
+}
+
+impl Analyzer for SimpleAnalyzer {
+    fn token_stream(&self, _field: &str, text: &str) -> Result<Box<dyn TokenStream>> {
+        Ok(Box::new(WordTokenStream::new(self.words(text))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simple_analyzer() {
+        let tokens = analyze(&SimpleAnalyzer::default(), "f", "Hello, Rucene-Search!").unwrap();
+        let terms: Vec<&str> = tokens.iter().map(|t| t.term.as_str()).collect();
+        assert_eq!(terms, vec!["hello", "rucene", "search"]);
+        assert_eq!(tokens[1].position, 1);
+        assert_eq!(tokens[1].start_offset, 7);
+        assert_eq!(tokens[1].end_offset, 13);
+    }
+}
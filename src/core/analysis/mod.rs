@@ -14,5 +14,11 @@
 mod token_stream;
 pub use self::token_stream::TokenStream;
 
+mod analyzer;
+pub use self::analyzer::*;
+
+mod synonym_filter;
+pub use self::synonym_filter::*;
+
 mod char_buffer;
 pub mod whitespace_tokenizer;
@@ -0,0 +1,219 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::{Analyzer, TokenStream};
+use core::attribute::{
+    CharTermAttribute, OffsetAttribute, PositionIncrementAttribute, TermToBytesRefAttribute,
+};
+
+use error::Result;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A map from a single word to the words it is synonymous with.
+#[derive(Debug, Clone, Default)]
+pub struct SynonymMap {
+    synonyms: HashMap<String, Vec<String>>,
+}
+
+impl SynonymMap {
+    pub fn new() -> SynonymMap {
+        SynonymMap::default()
+    }
+
+    /// Adds `synonym` as a synonym of `word`, and `word` as a synonym of
+    /// `synonym` too if `bidirectional` is true.
+    pub fn add(&mut self, word: &str, synonym: &str, bidirectional: bool) {
+        Self::insert(&mut self.synonyms, word, synonym);
+        if bidirectional {
+            Self::insert(&mut self.synonyms, synonym, word);
+        }
+    }
+
+    /// Makes every word of `words` a synonym of all the others.
+    pub fn add_equivalents(&mut self, words: &[&str]) {
+        for word in words {
+            for synonym in words {
+                if word != synonym {
+                    Self::insert(&mut self.synonyms, word, synonym);
+                }
+            }
+        }
+    }
+
+    pub fn synonyms(&self, word: &str) -> &[String] {
+        self.synonyms.get(word).map_or(&[], |s| s.as_slice())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.synonyms.is_empty()
+    }
+
+    fn insert(synonyms: &mut HashMap<String, Vec<String>>, word: &str, synonym: &str) {
+        let entry = synonyms.entry(word.to_string()).or_insert_with(Vec::new);
+        if !entry.iter().any(|s| s == synonym) {
+            entry.push(synonym.to_string());
+        }
+    }
+}
+
+/// Injects the synonyms of each token of the input stream right after it. The
+/// synonyms are stacked on the original token: they share its offsets and have
+/// a position increment of 0.
+#[derive(Debug)]
+pub struct SynonymFilter {
+    input: Box<dyn TokenStream>,
+    synonyms: Arc<SynonymMap>,
+    term_attr: CharTermAttribute,
+    offset_attr: OffsetAttribute,
+    position_attr: PositionIncrementAttribute,
+    // synonyms of the current token not emitted yet, in reverse order
+    pending: Vec<String>,
+}
+
+impl SynonymFilter {
+    pub fn new(input: Box<dyn TokenStream>, synonyms: Arc<SynonymMap>) -> SynonymFilter {
+        SynonymFilter {
+            input,
+            synonyms,
+            term_attr: CharTermAttribute::new(),
+            offset_attr: OffsetAttribute::new(),
+            position_attr: PositionIncrementAttribute::new(),
+            pending: vec![],
+        }
+    }
+}
+
+impl TokenStream for SynonymFilter {
+    fn increment_token(&mut self) -> Result<bool> {
+        if let Some(synonym) = self.pending.pop() {
+            self.term_attr.copy_buffer(synonym.as_bytes());
+            self.position_attr.set_position_increment(0);
+            return Ok(true);
+        }
+
+        if !self.input.increment_token()? {
+            return Ok(false);
+        }
+        self.clear_attributes();
+        let position_increment = self.input.position_attribute_mut().get_position_increment();
+        self.position_attr
+            .set_position_increment(position_increment);
+        let (start, end) = {
+            let offset = self.input.offset_attribute();
+            (offset.start_offset(), offset.end_offset())
+        };
+        self.offset_attr.set_offset(start, end)?;
+
+        let term = self.input.term_bytes_attribute().get_bytes_ref();
+        self.term_attr.copy_buffer(term.bytes());
+        if let Ok(word) = ::std::str::from_utf8(term.bytes()) {
+            self.pending = self.synonyms.synonyms(word).iter().rev().cloned().collect();
+        }
+        Ok(true)
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.input.end()?;
+        self.end_attributes();
+        let final_offset = self.input.offset_attribute().end_offset();
+        self.offset_attr.set_offset(final_offset, final_offset)
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.pending.clear();
+        self.input.reset()
+    }
+
+    fn offset_attribute_mut(&mut self) -> &mut OffsetAttribute {
+        &mut self.offset_attr
+    }
+
+    fn offset_attribute(&self) -> &OffsetAttribute {
+        &self.offset_attr
+    }
+
+    fn position_attribute_mut(&mut self) -> &mut PositionIncrementAttribute {
+        &mut self.position_attr
+    }
+
+    fn term_bytes_attribute_mut(&mut self) -> &mut dyn TermToBytesRefAttribute {
+        &mut self.term_attr
+    }
+
+    fn term_bytes_attribute(&self) -> &dyn TermToBytesRefAttribute {
+        &self.term_attr
+    }
+}
+
+/// Wraps an `Analyzer` so that the tokens it produces are followed by their
+/// synonyms, see `SynonymFilter`.
+pub struct SynonymAnalyzer<A: Analyzer> {
+    analyzer: A,
+    synonyms: Arc<SynonymMap>,
+}
+
+impl<A: Analyzer> SynonymAnalyzer<A> {
+    pub fn new(analyzer: A, synonyms: SynonymMap) -> SynonymAnalyzer<A> {
+        SynonymAnalyzer {
+            analyzer,
+            synonyms: Arc::new(synonyms),
+        }
+    }
+
+    pub fn synonyms(&self) -> &SynonymMap {
+        &self.synonyms
+    }
+}
+
+impl<A: Analyzer> Analyzer for SynonymAnalyzer<A> {
+    fn token_stream(&self, field: &str, text: &str) -> Result<Box<dyn TokenStream>> {
+        Ok(Box::new(SynonymFilter::new(
+            self.analyzer.token_stream(field, text)?,
+            Arc::clone(&self.synonyms),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::{analyze, SimpleAnalyzer};
+
+    #[test]
+    fn test_synonym_analyzer() {
+        let mut synonyms = SynonymMap::new();
+        synonyms.add_equivalents(&["car", "automobile"]);
+        synonyms.add("fast", "quick", false);
+        let analyzer = SynonymAnalyzer::new(SimpleAnalyzer::default(), synonyms);
+
+        let tokens = analyze(&analyzer, "f", "Fast car").unwrap();
+        let terms: Vec<(&str, u32, usize)> = tokens
+            .iter()
+            .map(|t| (t.term.as_str(), t.position, t.start_offset))
+            .collect();
+        assert_eq!(
+            terms,
+            vec![
+                ("fast", 0, 0),
+                ("quick", 0, 0),
+                ("car", 1, 5),
+                ("automobile", 1, 5),
+            ]
+        );
+
+        let tokens = analyze(&analyzer, "f", "quick").unwrap();
+        assert_eq!(tokens.len(), 1);
+    }
+}
@@ -0,0 +1,104 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+import java.nio.file.Paths;
+
+import org.apache.lucene.analysis.Analyzer;
+import org.apache.lucene.document.BinaryDocValuesField;
+import org.apache.lucene.document.Document;
+import org.apache.lucene.document.Field;
+import org.apache.lucene.document.FieldType;
+import org.apache.lucene.document.IntPoint;
+import org.apache.lucene.document.NumericDocValuesField;
+import org.apache.lucene.document.SortedDocValuesField;
+import org.apache.lucene.document.SortedNumericDocValuesField;
+import org.apache.lucene.document.SortedSetDocValuesField;
+import org.apache.lucene.document.StringField;
+import org.apache.lucene.index.IndexOptions;
+import org.apache.lucene.index.IndexWriter;
+import org.apache.lucene.index.IndexWriterConfig;
+import org.apache.lucene.index.NoMergePolicy;
+import org.apache.lucene.index.Term;
+import org.apache.lucene.store.FSDirectory;
+import org.apache.lucene.util.BytesRef;
+
+/**
+ * Writes the Lucene indexes read by the back-compat tests of the codecs, in
+ * this directory, with the lucene-core jar of the Lucene version to test:
+ *
+ * <pre>
+ * javac -cp lucene-core-7.7.3.jar GenerateBackCompatIndex.java
+ * java -cp lucene-core-7.7.3.jar:. GenerateBackCompatIndex 7.7.3-cfs true
+ * java -cp lucene-core-7.7.3.jar:. GenerateBackCompatIndex 7.7.3-nocfs false
+ * </pre>
+ *
+ * The tests expect the 6.1.0, 6.6.6 and 7.7.3 indexes, each with and without
+ * compound files. See `core::codec::tests::check_back_compat_index` for what
+ * is read back.
+ */
+public class GenerateBackCompatIndex {
+  static final int NUM_DOCS = 50;
+
+  public static void main(String[] args) throws Exception {
+    String name = args[0];
+    boolean compound = Boolean.parseBoolean(args[1]);
+
+    // only untokenized fields are indexed, so the analyzer is never used
+    Analyzer analyzer = new Analyzer() {
+      @Override
+      protected TokenStreamComponents createComponents(String fieldName) {
+        throw new UnsupportedOperationException();
+      }
+    };
+    IndexWriterConfig config = new IndexWriterConfig(analyzer);
+    config.setUseCompoundFile(compound);
+    config.setMergePolicy(NoMergePolicy.INSTANCE);
+
+    // every instance of the field is one token, so the freqs and the norms
+    // follow the number of instances
+    FieldType bodyType = new FieldType();
+    bodyType.setTokenized(false);
+    bodyType.setIndexOptions(IndexOptions.DOCS_AND_FREQS_AND_POSITIONS);
+    bodyType.freeze();
+
+    try (IndexWriter writer = new IndexWriter(FSDirectory.open(Paths.get(name)), config)) {
+      for (int i = 0; i < NUM_DOCS; i++) {
+        Document doc = new Document();
+        doc.add(new StringField("id", Integer.toString(i), Field.Store.YES));
+        for (int j = 0; j <= i % 3; j++) {
+          doc.add(new Field("body", "common", bodyType));
+        }
+        doc.add(new Field("body", "t" + (i % 10), bodyType));
+        // sparse doc values
+        if (i % 2 == 0) {
+          doc.add(new NumericDocValuesField("num", i * 3L));
+        }
+        doc.add(new NumericDocValuesField("dense_num", i - 25L));
+        doc.add(new BinaryDocValuesField("binary", new BytesRef("binary" + i)));
+        doc.add(new SortedDocValuesField("sorted", new BytesRef("s" + (i % 5))));
+        doc.add(new SortedSetDocValuesField("sorted_set", new BytesRef("a" + (i % 3))));
+        doc.add(new SortedSetDocValuesField("sorted_set", new BytesRef("b" + (i % 4))));
+        doc.add(new SortedNumericDocValuesField("sorted_num", i));
+        doc.add(new SortedNumericDocValuesField("sorted_num", -i));
+        doc.add(new IntPoint("point", i));
+        doc.add(new IntPoint("point2d", i, -i));
+        writer.addDocument(doc);
+        if (i == NUM_DOCS / 2 - 1) {
+          writer.commit();
+        }
+      }
+      writer.deleteDocuments(new Term("id", "7"));
+      writer.commit();
+    }
+  }
+}
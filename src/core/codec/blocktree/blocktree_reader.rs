@@ -32,12 +32,14 @@ use core::index::segment_file_name;
 use core::index::{FieldInfo, FieldInfoRef, Fields};
 use core::index::{IndexOptions, SegmentReadState};
 use core::index::{SeekStatus, TermIterator, Terms};
+use core::search::posting_iterator::ImpactsEnum;
 use core::store::{ByteArrayDataInput, DataInput, Directory, IndexInput};
 use core::util::bit_util::UnsignedShift;
 use core::util::fst::{
     Arc as FSTArc, ByteSequenceOutput, ByteSequenceOutputFactory, DirectionalBytesReader,
     FSTBytesReader, OutputFactory, FST,
 };
+use core::util::{vec_ram_bytes, Accountable, RamUsage};
 use error::{
     ErrorKind::{CorruptIndex, IllegalState, UnsupportedOperation},
     Result,
@@ -369,6 +371,19 @@ impl FieldsProducer for BlockTreeTermsReader {
     }
 }
 
+impl Accountable for BlockTreeTermsReader {
+    fn ram_bytes_used(&self) -> usize {
+        self.fields.values().map(|f| f.ram_bytes_used()).sum()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.fields
+            .iter()
+            .map(|(name, f)| RamUsage::of(format!("field '{}'", name), f))
+            .collect()
+    }
+}
+
 impl Fields for BlockTreeTermsReader {
     type Terms = FieldReaderRef;
     fn fields(&self) -> Vec<String> {
@@ -477,6 +492,31 @@ impl FieldReader {
     pub fn index(&self) -> &FSTRef {
         self.index.as_ref().unwrap()
     }
+
+    /// Walks all the blocks of the terms dictionary of this field to gather
+    /// their statistics, which helps tuning the block sizes of the format.
+    pub fn block_stats(&self) -> Result<Stats> {
+        let field_info = self.field_info.clone();
+        debug_assert!(self.index.is_some());
+        let postings_reader = self.postings_reader.clone();
+        let terms_in = self.terms_in.clone();
+        let mut iter = SegmentTermIteratorInner::new(self, terms_in, postings_reader, field_info);
+        iter.compute_block_stats()
+    }
+}
+
+impl Accountable for FieldReader {
+    /// The terms index FST and the bounds of the field, the terms blocks are
+    /// read from the terms dictionary file.
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.root_code)
+            + vec_ram_bytes(&self.min_term)
+            + vec_ram_bytes(&self.max_term)
+            + self
+                .index
+                .as_ref()
+                .map_or(0, |index| index.ram_bytes_used())
+    }
 }
 
 impl<'a> Terms for FieldReader {
@@ -537,14 +577,7 @@ impl<'a> Terms for FieldReader {
     }
 
     fn stats(&self) -> Result<String> {
-        let field_info = self.field_info.clone();
-        debug_assert!(self.index.is_some());
-        let postings_reader = self.postings_reader.clone();
-        let terms_in = self.terms_in.clone();
-        let mut iter = SegmentTermIteratorInner::new(self, terms_in, postings_reader, field_info);
-        let stats = iter.compute_block_stats()?;
-
-        stats.to_string()
+        self.block_stats()?.to_string()
     }
 }
 
@@ -698,6 +731,7 @@ impl Stats {
             frame.state.term_block_ord
         };
         let sub_block_count = frame.ent_count - term_count;
+        self.total_term_count += i64::from(term_count);
         match (term_count, sub_block_count) {
             (0, x) if x > 0 => self.sub_blocks_only_block_count += 1,
             (x, 0) if x > 0 => self.terms_only_block_count += 1,
@@ -820,39 +854,49 @@ impl Stats {
 
     pub fn to_string(&self) -> Result<String> {
         let mut string = String::with_capacity(1024);
-        string.write_fmt(format_args!("  index FST:"))?;
-        string.write_fmt(format_args!("    {} bytes", self.index_num_bytes))?;
-        string.write_fmt(format_args!("  terms:"))?;
-        string.write_fmt(format_args!("    {} terms", self.total_term_count))?;
+        writeln!(
+            string,
+            "BlockTree stats for segment={} field={}:",
+            self.segment, self.field
+        )?;
+        writeln!(string, "  index FST:")?;
+        writeln!(string, "    {} bytes", self.index_num_bytes)?;
+        writeln!(string, "  terms:")?;
+        writeln!(string, "    {} terms", self.total_term_count)?;
         let bps = if self.total_term_count != 0 {
             self.total_term_bytes as f64 / self.total_term_count as f64
         } else {
             0.0
         };
-        string.write_fmt(format_args!(
+        writeln!(
+            string,
             "    {} bytes {} (bytes/term)",
             self.total_term_bytes, bps
-        ))?;
-        string.write_fmt(format_args!("  blocks:"))?;
-        string.write_fmt(format_args!("    {} blocks", self.total_block_count))?;
-        string.write_fmt(format_args!(
+        )?;
+        writeln!(string, "  blocks:")?;
+        writeln!(string, "    {} blocks", self.total_block_count)?;
+        writeln!(
+            string,
             "    {} terms-only blocks",
             self.terms_only_block_count
-        ))?;
-        string.write_fmt(format_args!(
+        )?;
+        writeln!(
+            string,
             "    {} sub-block-only blocks",
             self.sub_blocks_only_block_count,
-        ))?;
-        string.write_fmt(format_args!("    {} mixed blocks", self.mixed_block_count))?;
-        string.write_fmt(format_args!("    {} floor blocks", self.floor_block_count))?;
-        string.write_fmt(format_args!(
+        )?;
+        writeln!(string, "    {} mixed blocks", self.mixed_block_count)?;
+        writeln!(string, "    {} floor blocks", self.floor_block_count)?;
+        writeln!(
+            string,
             "    {} non-floor blocks",
             (self.total_block_count - self.floor_sub_block_count),
-        ))?;
-        string.write_fmt(format_args!(
+        )?;
+        writeln!(
+            string,
             "    {} floor sub-blocks",
             self.floor_sub_block_count
-        ))?;
+        )?;
         let (bsubps, bstbps, bobps) = if self.total_block_count != 0 {
             let total_block_count = f64::from(self.total_block_count);
             (
@@ -863,26 +907,29 @@ impl Stats {
         } else {
             (0.0, 0.0, 0.0)
         };
-        string.write_fmt(format_args!(
+        writeln!(
+            string,
             "    {} term suffix bytes {} (suffix-bytes/block)",
             self.total_block_suffix_bytes, bsubps
-        ))?;
-        string.write_fmt(format_args!(
+        )?;
+        writeln!(
+            string,
             "    {} term stats bytes {} (stats-bytes/block)",
             self.total_block_stats_bytes, bstbps
-        ))?;
-        string.write_fmt(format_args!(
+        )?;
+        writeln!(
+            string,
             "    {} other bytes {} (other-bytes/block)",
             self.total_block_other_bytes, bobps
-        ))?;
+        )?;
         if self.total_block_count != 0 {
-            string.write_fmt(format_args!("    by prefix length:"))?;
+            writeln!(string, "    by prefix length:")?;
             let mut total = 0;
             for prefix in 0..self.block_count_by_prefix_len.len() {
                 let block_count = self.block_count_by_prefix_len[prefix];
                 total += block_count;
                 if block_count != 0 {
-                    string.write_fmt(format_args!("      {}: {}", prefix, block_count))?;
+                    writeln!(string, "      {}: {}", prefix, block_count)?;
                 }
             }
             debug_assert!(self.total_block_count == total);
@@ -978,6 +1025,11 @@ impl TermIterator for SegmentTermIterator {
         self.iter.postings_with_flags(flags)
     }
 
+    #[inline]
+    fn impacts(&mut self, flags: u16) -> Result<Box<dyn ImpactsEnum>> {
+        self.iter.impacts(flags)
+    }
+
     #[inline]
     fn term_state(&mut self) -> Result<Self::TermState> {
         self.iter.term_state()
@@ -1088,8 +1140,8 @@ impl SegmentTermIteratorInner {
             &self.field_reader().parent.segment,
             &self.field_reader().field_info.name,
         );
-        if self.field_reader().index.is_some() {
-            // stats.index_num_bytes = self.fr.index.unwrap().
+        if let Some(ref index) = self.field_reader().index {
+            stats.index_num_bytes = index.ram_bytes_used() as i64;
         }
         self.current_frame_ord = -1;
 
@@ -1112,9 +1164,8 @@ impl SegmentTermIteratorInner {
         }
 
         'all_term: loop {
-            let next_ent = self.current_frame().next_ent;
-            let ent_count = self.current_frame().ent_count;
-            while next_ent == ent_count {
+            // pop finished blocks
+            while self.current_frame().next_ent == self.current_frame().ent_count {
                 stats.end_block(self.current_frame())?;
                 if !self.current_frame().is_last_in_floor {
                     self.current_frame().load_next_floor_block()?;
@@ -1136,6 +1187,7 @@ impl SegmentTermIteratorInner {
                     let term_len = self.term_len;
                     self.current_frame_ord =
                         self.push_frame_by_fp(None, last_sub_fp, term_len)? as isize;
+                    self.current_frame().fp_orig = self.current_frame().fp;
                     self.current_frame().load_block()?;
                     let frame = self.current_frame();
                     stats.start_block(frame, !frame.is_last_in_floor);
@@ -1805,6 +1857,18 @@ impl TermIterator for SegmentTermIteratorInner {
         }
     }
 
+    fn impacts(&mut self, flags: u16) -> Result<Box<dyn ImpactsEnum>> {
+        debug_assert!(!self.eof);
+        self.current_frame().decode_metadata()?;
+        let state = if self.current_frame_ord < 0 {
+            &self.static_frame.state
+        } else {
+            &self.stack[self.current_frame_ord as usize].state
+        };
+        self.postings_reader
+            .impacts(self.field_info.as_ref(), state, flags)
+    }
+
     fn term_state(&mut self) -> Result<Self::TermState> {
         self.current_frame().decode_metadata()?;
         Ok(self.current_frame().state.clone())
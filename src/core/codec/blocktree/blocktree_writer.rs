@@ -15,10 +15,10 @@ use core::codec::blocktree::*;
 use core::codec::codec_util::{write_footer, write_index_header};
 use core::codec::consumer::FieldsConsumer;
 use core::codec::writer::PostingsWriterBase;
-use core::codec::{BlockTermState, Codec};
-use core::index::TermIterator;
+use core::codec::{BlockTermState, Codec, NormsProducer};
 use core::index::{segment_file_name, SegmentWriteState};
 use core::index::{FieldInfo, FieldInfos, Fields, IndexOptions, Terms};
+use core::index::{NumericDocValues, TermIterator};
 use core::store::{DataOutput, IndexOutput};
 use core::store::{Directory, RAMOutputStream};
 use core::util::bit_set::{FixedBitSet, ImmutableBitSet};
@@ -121,6 +121,31 @@ use std::mem;
 ///     byte of each sub-block, and its file pointer.
 ///
 /// @see BlockTreeTermsReader
+/// Checks that terms blocks of `min_items_in_block` to `max_items_in_block`
+/// entries can always be formed.
+pub fn validate_block_sizes(min_items_in_block: usize, max_items_in_block: usize) -> Result<()> {
+    if min_items_in_block <= 1 {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "min_items_in_block must be >= 2; got {}",
+            min_items_in_block
+        )));
+    }
+
+    if min_items_in_block > max_items_in_block {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "min_items_in_block '{}' >= max_items_in_block '{}'",
+            min_items_in_block, max_items_in_block
+        )));
+    }
+    if 2 * (min_items_in_block - 1) > max_items_in_block {
+        bail!(ErrorKind::IllegalArgument(format!(
+            "2 * (min_items_in_block '{}' - 1) >= max_items_in_block '{}'",
+            min_items_in_block, max_items_in_block
+        )));
+    }
+    Ok(())
+}
+
 pub struct BlockTreeTermsWriter<T: PostingsWriterBase, O: IndexOutput> {
     terms_out: O,
     index_out: O,
@@ -142,7 +167,7 @@ impl<T: PostingsWriterBase, O: IndexOutput> BlockTreeTermsWriter<T, O> {
         min_items_in_block: usize,
         max_items_in_block: usize,
     ) -> Result<BlockTreeTermsWriter<T, O>> {
-        Self::validate_settings(min_items_in_block, max_items_in_block)?;
+        validate_block_sizes(min_items_in_block, max_items_in_block)?;
 
         let max_doc = state.segment_info.max_doc;
 
@@ -200,29 +225,6 @@ impl<T: PostingsWriterBase, O: IndexOutput> BlockTreeTermsWriter<T, O> {
         out.write_long(dir_start)
     }
 
-    fn validate_settings(min_items_in_block: usize, max_items_in_block: usize) -> Result<()> {
-        if min_items_in_block <= 1 {
-            bail!(ErrorKind::IllegalArgument(format!(
-                "min_items_in_block must be >= 2; got {}",
-                min_items_in_block
-            )));
-        }
-
-        if min_items_in_block > max_items_in_block {
-            bail!(ErrorKind::IllegalArgument(format!(
-                "min_items_in_block '{}' >= max_items_in_block '{}'",
-                min_items_in_block, max_items_in_block
-            )));
-        }
-        if 2 * (min_items_in_block - 1) > max_items_in_block {
-            bail!(ErrorKind::IllegalArgument(format!(
-                "2 * (min_items_in_block '{}' - 1) >= max_items_in_block '{}'",
-                min_items_in_block, max_items_in_block
-            )));
-        }
-        Ok(())
-    }
-
     fn close(&mut self) -> Result<()> {
         if self.closed {
             return Ok(());
@@ -270,7 +272,7 @@ impl<T: PostingsWriterBase, O: IndexOutput> BlockTreeTermsWriter<T, O> {
 }
 
 impl<T: PostingsWriterBase, O: IndexOutput> FieldsConsumer for BlockTreeTermsWriter<T, O> {
-    fn write(&mut self, fields: &impl Fields) -> Result<()> {
+    fn write(&mut self, fields: &impl Fields, norms: Option<&dyn NormsProducer>) -> Result<()> {
         let mut last_field = String::new();
         for field in fields.fields() {
             debug_assert!(last_field < field);
@@ -278,11 +280,19 @@ impl<T: PostingsWriterBase, O: IndexOutput> FieldsConsumer for BlockTreeTermsWri
             if let Some(terms) = fields.terms(&field)? {
                 let mut terms_iter = terms.iterator()?;
                 let field_info = self.field_infos.field_info_by_name(&field).unwrap().clone();
+                let field_norms = match norms {
+                    Some(norms) if field_info.has_norms() => Some(norms.norms(&field_info)?),
+                    _ => None,
+                };
                 let mut terms_writer = TermsWriter::new(field_info, self);
 
                 loop {
                     if let Some(term) = terms_iter.next()? {
-                        terms_writer.write(&term, &mut terms_iter)?;
+                        terms_writer.write(
+                            &term,
+                            &mut terms_iter,
+                            field_norms.as_ref().map(|n| n.as_ref()),
+                        )?;
                     } else {
                         break;
                     }
@@ -700,11 +710,17 @@ impl<'a, T: PostingsWriterBase, O: IndexOutput> TermsWriter<'a, T, O> {
     }
 
     // Writes one term's worth of postings.
-    pub fn write(&mut self, text: &[u8], terms_iter: &mut impl TermIterator) -> Result<()> {
+    pub fn write(
+        &mut self,
+        text: &[u8],
+        terms_iter: &mut impl TermIterator,
+        norms: Option<&dyn NumericDocValues>,
+    ) -> Result<()> {
         if let Some(state) = self.block_tree_writer.postings_writer.write_term(
             text,
             terms_iter,
             &mut self.docs_seen,
+            norms,
         )? {
             assert_ne!(state.doc_freq, 0);
             assert!(
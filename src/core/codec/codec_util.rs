@@ -36,8 +36,8 @@ pub fn write_header<T: DataOutput + ?Sized>(out: &mut T, codec: &str, version: i
     out.write_int(version)
 }
 
-pub fn write_index_header(
-    out: &mut impl DataOutput,
+pub fn write_index_header<T: DataOutput + ?Sized>(
+    out: &mut T,
     codec: &str,
     version: i32,
     id: &[u8],
@@ -60,7 +60,7 @@ pub fn write_index_header(
     out.write_bytes(&suffix.as_bytes(), 0, slen)
 }
 
-pub fn write_footer(output: &mut impl IndexOutput) -> Result<()> {
+pub fn write_footer<T: IndexOutput + ?Sized>(output: &mut T) -> Result<()> {
     output.write_int(FOOTER_MAGIC)?;
     output.write_int(0)?;
     write_crc(output)
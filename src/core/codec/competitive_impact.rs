@@ -0,0 +1,113 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use core::search::posting_iterator::Impact;
+
+/// Accumulates the (freq, norm) pairs of the docs of a block of postings and
+/// keeps the competitive ones, ie. the pairs that are not dominated by a pair
+/// with a greater or equal freq and a lower or equal norm.
+///
+/// Norms are compared as unsigned longs, so that lower norms are always more
+/// competitive than greater ones.
+#[derive(Default, Clone)]
+pub struct CompetitiveImpactAccumulator {
+    // the max freq seen for each norm, keyed by the unsigned norm
+    max_freqs: BTreeMap<u64, i32>,
+}
+
+impl CompetitiveImpactAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reset to the same state it was in after creation.
+    pub fn clear(&mut self) {
+        self.max_freqs.clear();
+    }
+
+    /// Accumulate a (freq, norm) pair, updating this structure if there is no
+    /// equivalent or more competitive entry already.
+    pub fn add(&mut self, freq: i32, norm: i64) {
+        let max_freq = self.max_freqs.entry(norm as u64).or_insert(freq);
+        if freq > *max_freq {
+            *max_freq = freq;
+        }
+    }
+
+    /// Merge `other` into this accumulator.
+    pub fn add_all(&mut self, other: &CompetitiveImpactAccumulator) {
+        for (&norm, &freq) in &other.max_freqs {
+            self.add(freq, norm as i64);
+        }
+    }
+
+    /// Get the set of competitive freq and norm pairs, ordered by increasing
+    /// freq and norm.
+    pub fn competitive_freq_norm_pairs(&self) -> Vec<Impact> {
+        let mut impacts = Vec::new();
+        let mut max_freq_for_lower_norms = 0;
+        for (&norm, &freq) in &self.max_freqs {
+            if freq > max_freq_for_lower_norms {
+                impacts.push(Impact::new(freq, norm as i64));
+                max_freq_for_lower_norms = freq;
+            }
+        }
+        impacts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_freqs.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_competitive_freq_norm_pairs() {
+        let mut acc = CompetitiveImpactAccumulator::new();
+        acc.add(3, 10);
+        acc.add(5, 12);
+        // dominated by (5, 12)
+        acc.add(4, 12);
+        acc.add(2, 14);
+        acc.add(7, 20);
+        assert_eq!(
+            acc.competitive_freq_norm_pairs(),
+            vec![Impact::new(3, 10), Impact::new(5, 12), Impact::new(7, 20)]
+        );
+
+        // negative norms are greater than positive ones as unsigned values
+        let mut other = CompetitiveImpactAccumulator::new();
+        other.add(100, -1);
+        other.add(1, 1);
+        acc.add_all(&other);
+        assert_eq!(
+            acc.competitive_freq_norm_pairs(),
+            vec![
+                Impact::new(1, 1),
+                Impact::new(3, 10),
+                Impact::new(5, 12),
+                Impact::new(7, 20),
+                Impact::new(100, -1),
+            ]
+        );
+
+        acc.clear();
+        assert!(acc.is_empty());
+        assert!(acc.competitive_freq_norm_pairs().is_empty());
+    }
+}
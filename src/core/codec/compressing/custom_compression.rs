@@ -0,0 +1,271 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use core::codec::compressing::{Compress, CompressionMode, Decompress};
+use core::store::{DataInput, DataOutput};
+use core::util::packed_misc::OffsetAndLength;
+use error::{
+    ErrorKind::{CorruptIndex, IllegalArgument},
+    Result,
+};
+
+/// A compression algorithm provided by users of this crate, e.g. FSST for
+/// short strings.
+///
+/// Once registered with `register_compression`, it can be used wherever a
+/// `CompressionMode` is accepted: stored fields, term vectors and block
+/// compressed binary doc values. Segments only record its name, so the
+/// compression must be registered again before they are read.
+pub trait CustomCompression: Send + Sync {
+    /// The name recorded in the index, it must not clash with the built-in
+    /// compression modes nor with other registered compressions.
+    fn name(&self) -> &'static str;
+
+    /// Compress `bytes` and append the compressed data to `out`.
+    fn compress(&self, bytes: &[u8], out: &mut Vec<u8>) -> Result<()>;
+
+    /// Decompress `compressed`, which decompresses to `original_length` bytes,
+    /// and append the decompressed data to `out`.
+    fn decompress(
+        &self,
+        compressed: &[u8],
+        original_length: usize,
+        out: &mut Vec<u8>,
+    ) -> Result<()>;
+}
+
+lazy_static! {
+    static ref CUSTOM_COMPRESSIONS: RwLock<HashMap<&'static str, Arc<dyn CustomCompression>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Register a custom compression and return the `CompressionMode` that uses it.
+///
+/// Registering the same instance twice is a no-op, while registering another
+/// compression under a name that is already taken fails.
+pub fn register_compression(compression: Arc<dyn CustomCompression>) -> Result<CompressionMode> {
+    let name = compression.name();
+    let mut compressions = CUSTOM_COMPRESSIONS.write().unwrap();
+    if let Some(registered) = compressions.get(name) {
+        if Arc::ptr_eq(registered, &compression) {
+            return Ok(CompressionMode::Custom(name));
+        }
+        bail!(IllegalArgument(format!(
+            "compression {} is already registered",
+            name
+        )));
+    }
+    if CompressionMode::builtin_for_name(name).is_some() {
+        bail!(IllegalArgument(format!(
+            "compression {} clashes with a built-in compression mode",
+            name
+        )));
+    }
+    compressions.insert(name, compression);
+    Ok(CompressionMode::Custom(name))
+}
+
+/// Return the custom compression registered under the given name.
+pub fn custom_compression(name: &str) -> Option<Arc<dyn CustomCompression>> {
+    CUSTOM_COMPRESSIONS.read().unwrap().get(name).cloned()
+}
+
+fn registered(name: &str) -> Result<Arc<dyn CustomCompression>> {
+    match custom_compression(name) {
+        Some(compression) => Ok(compression),
+        None => bail!(IllegalArgument(format!(
+            "compression {} is not registered",
+            name
+        ))),
+    }
+}
+
+/// Adapts a `CustomCompression` to `Compress`, the compressed data is
+/// prefixed with its length.
+pub struct CustomCompressor {
+    compression: Arc<dyn CustomCompression>,
+    compressed: Vec<u8>,
+}
+
+impl CustomCompressor {
+    pub fn new(name: &str) -> Result<CustomCompressor> {
+        Ok(CustomCompressor {
+            compression: registered(name)?,
+            compressed: Vec::new(),
+        })
+    }
+}
+
+impl Compress for CustomCompressor {
+    fn compress(
+        &mut self,
+        bytes: &[u8],
+        off: usize,
+        len: usize,
+        out: &mut impl DataOutput,
+    ) -> Result<()> {
+        self.compressed.clear();
+        self.compression
+            .compress(&bytes[off..off + len], &mut self.compressed)?;
+        out.write_vint(self.compressed.len() as i32)?;
+        out.write_bytes(&self.compressed, 0, self.compressed.len())
+    }
+}
+
+/// Adapts a `CustomCompression` to `Decompress`.
+#[derive(Clone)]
+pub struct CustomDecompressor {
+    compression: Arc<dyn CustomCompression>,
+}
+
+impl CustomDecompressor {
+    pub fn new(name: &str) -> Result<CustomDecompressor> {
+        Ok(CustomDecompressor {
+            compression: registered(name)?,
+        })
+    }
+}
+
+impl Decompress for CustomDecompressor {
+    fn decompress<R: DataInput + ?Sized>(
+        &self,
+        input: &mut R,
+        original_length: usize,
+        offset: usize,
+        length: usize,
+        bytes: &mut Vec<u8>,
+        bytes_position: &mut OffsetAndLength,
+    ) -> Result<()> {
+        debug_assert!(offset + length <= original_length);
+        if length == 0 {
+            bytes_position.1 = 0;
+            return Ok(());
+        }
+
+        let compressed_length = input.read_vint()? as usize;
+        let mut compressed = vec![0u8; compressed_length];
+        input.read_bytes(&mut compressed, 0, compressed_length)?;
+
+        bytes.clear();
+        self.compression
+            .decompress(&compressed, original_length, bytes)?;
+        if bytes.len() != original_length {
+            bail!(CorruptIndex(format!(
+                "{} lengths mismatch: {} != {}",
+                self.compression.name(),
+                bytes.len(),
+                original_length
+            )));
+        }
+        bytes_position.0 = offset;
+        bytes_position.1 = length;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::ByteArrayDataInput;
+
+    /// Run length encoding, as (run length, byte) pairs.
+    struct RunLength;
+
+    impl CustomCompression for RunLength {
+        fn name(&self) -> &'static str {
+            "TEST_RUN_LENGTH"
+        }
+
+        fn compress(&self, bytes: &[u8], out: &mut Vec<u8>) -> Result<()> {
+            let mut i = 0;
+            while i < bytes.len() {
+                let mut run = 1;
+                while i + run < bytes.len() && run < 255 && bytes[i + run] == bytes[i] {
+                    run += 1;
+                }
+                out.push(run as u8);
+                out.push(bytes[i]);
+                i += run;
+            }
+            Ok(())
+        }
+
+        fn decompress(&self, compressed: &[u8], _len: usize, out: &mut Vec<u8>) -> Result<()> {
+            for pair in compressed.chunks(2) {
+                out.extend((0..pair[0]).map(|_| pair[1]));
+            }
+            Ok(())
+        }
+    }
+
+    struct Fast;
+
+    impl CustomCompression for Fast {
+        fn name(&self) -> &'static str {
+            "FAST"
+        }
+
+        fn compress(&self, _bytes: &[u8], _out: &mut Vec<u8>) -> Result<()> {
+            unreachable!()
+        }
+
+        fn decompress(&self, _compressed: &[u8], _len: usize, _out: &mut Vec<u8>) -> Result<()> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn test_custom_compression() {
+        assert!(CompressionMode::for_name("TEST_RUN_LENGTH").is_none());
+        let compression: Arc<dyn CustomCompression> = Arc::new(RunLength);
+        let mode = register_compression(Arc::clone(&compression)).unwrap();
+        assert_eq!(mode, CompressionMode::Custom("TEST_RUN_LENGTH"));
+        assert!(register_compression(compression).is_ok());
+        assert!(register_compression(Arc::new(RunLength)).is_err());
+        assert!(register_compression(Arc::new(Fast)).is_err());
+        assert_eq!(CompressionMode::for_name(mode.name()), Some(mode));
+
+        let bytes: Vec<u8> = (0..10_000).map(|i| (i / 100) as u8).collect();
+        let mut compressed = Vec::new();
+        mode.new_compressor()
+            .unwrap()
+            .compress(&bytes, 0, bytes.len(), &mut compressed)
+            .unwrap();
+        assert!(compressed.len() < bytes.len());
+
+        let mut input = ByteArrayDataInput::new(compressed);
+        let mut decompressed = Vec::new();
+        let mut position = OffsetAndLength(0, 0);
+        mode.new_decompressor()
+            .unwrap()
+            .decompress(
+                &mut input,
+                bytes.len(),
+                300,
+                1000,
+                &mut decompressed,
+                &mut position,
+            )
+            .unwrap();
+        assert_eq!(
+            &decompressed[position.0..position.0 + position.1],
+            &bytes[300..1300]
+        );
+        assert!(CompressionMode::Custom("UNREGISTERED")
+            .new_decompressor()
+            .is_err());
+    }
+}
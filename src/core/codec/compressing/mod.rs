@@ -29,13 +29,24 @@ pub use self::stored_fields_writer::*;
 
 mod matching_reader;
 
+mod custom_compression;
+
+pub use self::custom_compression::*;
+
+#[cfg(feature = "zstd")]
+mod zstd_compressor;
+
+#[cfg(feature = "zstd")]
+pub use self::zstd_compressor::*;
+
 use error::Result;
 
-use std;
 use std::cmp::min;
 use std::io::{Read, Write};
+use std::mem;
 
-use flate2::read::{DeflateDecoder, DeflateEncoder};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
 use flate2::Compression;
 
 use core::store::{DataInput, DataOutput};
@@ -443,94 +454,15 @@ impl Compress for LZ4FastCompressor {
     }
 }
 
-// use vector as a read write buf
-struct VecReadWriteBuf {
-    buf: Vec<u8>,
-    offset: usize,
-    length: usize,
-}
-
-impl VecReadWriteBuf {
-    pub fn new(size: usize) -> VecReadWriteBuf {
-        VecReadWriteBuf {
-            buf: Vec::with_capacity(size),
-            offset: 0,
-            length: 0,
-        }
-    }
-}
-
-impl Read for VecReadWriteBuf {
-    fn read(&mut self, buf: &mut [u8]) -> std::result::Result<usize, std::io::Error> {
-        let read_size = min(buf.len(), self.length);
-        if self.offset + read_size <= self.buf.len() {
-            buf[0..read_size]
-                .as_mut()
-                .copy_from_slice(&self.buf[self.offset..self.offset + read_size]);
-        } else {
-            let ahead = self.buf.len() - self.offset;
-            buf[0..ahead]
-                .as_mut()
-                .copy_from_slice(&self.buf[self.offset..self.buf.len()]);
-            buf[ahead..read_size]
-                .as_mut()
-                .copy_from_slice(&self.buf[0..read_size - ahead]);
-        }
-        self.offset = (self.offset + read_size) % self.buf.capacity();
-        self.length -= read_size;
-        Ok(read_size)
-    }
-}
-
-impl Write for VecReadWriteBuf {
-    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
-        if buf.is_empty() {
-            return Ok(0);
-        }
-        let total_size = min(self.buf.capacity() - self.length, buf.len());
-        let mut written_size = 0usize;
-
-        if self.buf.len() > self.offset + self.length {
-            let write_len = min(self.buf.len() - self.offset - self.length, total_size);
-            self.buf[self.offset + self.length..self.offset + self.length + write_len]
-                .copy_from_slice(&buf[0..write_len]);
-            written_size += write_len;
-        }
-        if written_size < total_size && self.buf.len() < self.buf.capacity() {
-            let write_len = min(
-                self.buf.capacity() - self.buf.len(),
-                total_size - written_size,
-            );
-            for _ in 0..write_len {
-                self.buf.push(buf[written_size]);
-                written_size += 1;
-            }
-        }
-        if written_size < total_size {
-            self.buf[0..total_size - written_size]
-                .copy_from_slice(&buf[total_size - written_size..total_size]);
-        }
-        self.length += total_size;
-        Ok(total_size)
-    }
-
-    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
-        unimplemented!()
-    }
-}
-
 pub struct DeflateCompressor {
-    compressor: DeflateEncoder<VecReadWriteBuf>,
+    level: Compression,
     compressed: Vec<u8>,
 }
 
 impl DeflateCompressor {
     pub fn new(level: i32) -> DeflateCompressor {
         DeflateCompressor {
-            compressor: DeflateEncoder::new(
-                VecReadWriteBuf::new(1024),
-                Compression::new(level as u32),
-            ),
+            level: Compression::new(level as u32),
             compressed: Vec::with_capacity(64usize),
         }
     }
@@ -544,26 +476,13 @@ impl Compress for DeflateCompressor {
         len: usize,
         out: &mut impl DataOutput,
     ) -> Result<()> {
-        let mut total = 0usize;
-        let mut finish = false;
-        let mut compressed_bytes = 0usize;
         self.compressed.clear();
-        loop {
-            if total < len {
-                let size = self.compressor.write(&bytes[off + total..off + len])?;
-                total += size;
-                debug_assert!(total <= len);
-            } else {
-                finish = true;
-            }
-
-            compressed_bytes += self.compressor.read_to_end(&mut self.compressed)?;
-            if finish {
-                break;
-            }
-        }
-        out.write_vint(compressed_bytes as i32)?;
-        out.write_bytes(&self.compressed, 0, compressed_bytes)
+        let mut encoder =
+            DeflateEncoder::new(mem::replace(&mut self.compressed, vec![]), self.level);
+        encoder.write_all(&bytes[off..off + len])?;
+        self.compressed = encoder.finish()?;
+        out.write_vint(self.compressed.len() as i32)?;
+        out.write_bytes(&self.compressed, 0, self.compressed.len())
     }
 }
 
@@ -673,6 +592,9 @@ impl Decompress for DeflateDecompressor {
 pub enum Decompressor {
     LZ4(LZ4Decompressor),
     Deflate(DeflateDecompressor),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdDecompressor),
+    Custom(CustomDecompressor),
 }
 
 impl Decompress for Decompressor {
@@ -702,6 +624,23 @@ impl Decompress for Decompressor {
                 bytes,
                 bytes_position,
             ),
+            #[cfg(feature = "zstd")]
+            Decompressor::Zstd(ref d) => d.decompress(
+                input,
+                original_length,
+                offset,
+                length,
+                bytes,
+                bytes_position,
+            ),
+            Decompressor::Custom(ref d) => d.decompress(
+                input,
+                original_length,
+                offset,
+                length,
+                bytes,
+                bytes_position,
+            ),
         }
     }
 }
@@ -711,20 +650,33 @@ impl Clone for Decompressor {
         match *self {
             Decompressor::LZ4(ref d) => Decompressor::LZ4(d.clone()),
             Decompressor::Deflate(ref d) => Decompressor::Deflate(d.clone()),
+            #[cfg(feature = "zstd")]
+            Decompressor::Zstd(ref d) => Decompressor::Zstd(d.clone()),
+            Decompressor::Custom(ref d) => Decompressor::Custom(d.clone()),
         }
     }
 }
 
-#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Serialize, Debug)]
 pub enum CompressionMode {
     FAST,
     HighCompression,
+    /// zstd with the given compression level, only available with the `zstd`
+    /// feature. It trades slower writes for smaller chunks than
+    /// `HighCompression`, which suits read-mostly archival indexes.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// A `CustomCompression` registered under this name.
+    Custom(&'static str),
     // FastDecompression,  // 暂时没有看到使用，故先不实现
 }
 
 pub enum Compressor {
     LZ4Fast(LZ4FastCompressor),
     Deflate(DeflateCompressor),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdCompressor),
+    Custom(CustomCompressor),
 }
 
 impl Compress for Compressor {
@@ -738,27 +690,188 @@ impl Compress for Compressor {
         match *self {
             Compressor::LZ4Fast(ref mut c) => c.compress(bytes, off, len, out),
             Compressor::Deflate(ref mut c) => c.compress(bytes, off, len, out),
+            #[cfg(feature = "zstd")]
+            Compressor::Zstd(ref mut c) => c.compress(bytes, off, len, out),
+            Compressor::Custom(ref mut c) => c.compress(bytes, off, len, out),
         }
     }
 }
 
 impl CompressionMode {
-    pub fn new_compressor(&self) -> Compressor {
+    pub fn name(&self) -> &'static str {
         match *self {
+            CompressionMode::FAST => "FAST",
+            CompressionMode::HighCompression => "HIGH_COMPRESSION",
+            #[cfg(feature = "zstd")]
+            CompressionMode::Zstd(_) => "ZSTD",
+            CompressionMode::Custom(name) => name,
+        }
+    }
+
+    /// Resolve a built-in mode or a registered `CustomCompression` by name.
+    pub fn for_name(name: &str) -> Option<CompressionMode> {
+        CompressionMode::builtin_for_name(name)
+            .or_else(|| custom_compression(name).map(|c| CompressionMode::Custom(c.name())))
+    }
+
+    fn builtin_for_name(name: &str) -> Option<CompressionMode> {
+        match name {
+            "FAST" => Some(CompressionMode::FAST),
+            "HIGH_COMPRESSION" => Some(CompressionMode::HighCompression),
+            // the level is only needed to compress
+            #[cfg(feature = "zstd")]
+            "ZSTD" => Some(CompressionMode::Zstd(ZSTD_DEFAULT_LEVEL)),
+            _ => None,
+        }
+    }
+
+    /// Fails if this is a custom mode whose compression is not registered.
+    pub fn new_compressor(&self) -> Result<Compressor> {
+        Ok(match *self {
             CompressionMode::FAST => Compressor::LZ4Fast(LZ4FastCompressor::default()),
             // notes:
             // 3 is the highest level that doesn't have lazy match evaluation
             // 6 is the default, higher than that is just a waste of cpu
             CompressionMode::HighCompression => Compressor::Deflate(DeflateCompressor::new(6)),
-        }
+            #[cfg(feature = "zstd")]
+            CompressionMode::Zstd(level) => Compressor::Zstd(ZstdCompressor::new(level)),
+            CompressionMode::Custom(name) => Compressor::Custom(CustomCompressor::new(name)?),
+        })
     }
 
-    pub fn new_decompressor(&self) -> Decompressor {
-        match *self {
+    /// Fails if this is a custom mode whose compression is not registered.
+    pub fn new_decompressor(&self) -> Result<Decompressor> {
+        Ok(match *self {
             CompressionMode::FAST => Decompressor::LZ4(LZ4Decompressor {}),
             CompressionMode::HighCompression => {
                 Decompressor::Deflate(DeflateDecompressor::default())
             }
+            #[cfg(feature = "zstd")]
+            CompressionMode::Zstd(_) => Decompressor::Zstd(ZstdDecompressor::default()),
+            CompressionMode::Custom(name) => Decompressor::Custom(CustomDecompressor::new(name)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::ByteArrayDataInput;
+
+    fn modes() -> Vec<CompressionMode> {
+        #[allow(unused_mut)]
+        let mut modes = vec![CompressionMode::FAST, CompressionMode::HighCompression];
+        #[cfg(feature = "zstd")]
+        modes.push(CompressionMode::Zstd(ZSTD_DEFAULT_LEVEL));
+        modes
+    }
+
+    #[test]
+    fn test_compression_round_trip() {
+        let bytes: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        for &mode in &modes() {
+            let mut compressed = Vec::new();
+            mode.new_compressor()
+                .unwrap()
+                .compress(&bytes, 0, bytes.len(), &mut compressed)
+                .unwrap();
+            assert!(compressed.len() < bytes.len());
+
+            let mut input = ByteArrayDataInput::new(compressed);
+            let mut decompressed = Vec::new();
+            let mut position = OffsetAndLength(0, 0);
+            mode.new_decompressor()
+                .unwrap()
+                .decompress(
+                    &mut input,
+                    bytes.len(),
+                    0,
+                    bytes.len(),
+                    &mut decompressed,
+                    &mut position,
+                )
+                .unwrap();
+            assert_eq!(
+                &decompressed[position.0..position.0 + position.1],
+                &bytes[..]
+            );
+        }
+    }
+
+    #[test]
+    fn test_decompress_slice() {
+        let bytes: Vec<u8> = (0..20_000).map(|i| ((i * 7) % 253) as u8).collect();
+        for &mode in &modes() {
+            let mut compressed = Vec::new();
+            mode.new_compressor()
+                .unwrap()
+                .compress(&bytes, 0, bytes.len(), &mut compressed)
+                .unwrap();
+
+            for &(offset, length) in &[(0, 10), (5, 4000), (12_345, 6_000), (19_990, 10)] {
+                let mut input = ByteArrayDataInput::new(compressed.clone());
+                let mut decompressed = Vec::new();
+                let mut position = OffsetAndLength(0, 0);
+                mode.new_decompressor()
+                    .unwrap()
+                    .decompress(
+                        &mut input,
+                        bytes.len(),
+                        offset,
+                        length,
+                        &mut decompressed,
+                        &mut position,
+                    )
+                    .unwrap();
+                assert_eq!(position.1, length);
+                assert_eq!(
+                    &decompressed[position.0..position.0 + position.1],
+                    &bytes[offset..offset + length]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_compressor_reuse() {
+        // every call must produce a complete stream that decompresses on its
+        // own, whatever was compressed before with the same compressor
+        let first: Vec<u8> = (0..5_000).map(|i| (i % 13) as u8).collect();
+        let second: Vec<u8> = (0..3_000).map(|i| ((i * 31) % 199) as u8).collect();
+        for &mode in &modes() {
+            let mut compressor = mode.new_compressor().unwrap();
+            let mut compressed = Vec::new();
+            compressor
+                .compress(&first, 0, first.len(), &mut compressed)
+                .unwrap();
+            let first_len = compressed.len();
+            compressor
+                .compress(&second, 0, second.len(), &mut compressed)
+                .unwrap();
+
+            for &(bytes, start, end) in &[
+                (&first, 0, first_len),
+                (&second, first_len, compressed.len()),
+            ] {
+                let mut input = ByteArrayDataInput::new(compressed[start..end].to_vec());
+                let mut decompressed = Vec::new();
+                let mut position = OffsetAndLength(0, 0);
+                mode.new_decompressor()
+                    .unwrap()
+                    .decompress(
+                        &mut input,
+                        bytes.len(),
+                        0,
+                        bytes.len(),
+                        &mut decompressed,
+                        &mut position,
+                    )
+                    .unwrap();
+                assert_eq!(
+                    &decompressed[position.0..position.0 + position.1],
+                    &bytes[..]
+                );
+            }
         }
     }
 }
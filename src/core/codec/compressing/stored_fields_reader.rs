@@ -397,7 +397,7 @@ impl CompressingStoredFieldsReader {
 
         retrieve_checksum(fields_stream.as_mut())?;
 
-        let decompressor = compression_mode.new_decompressor();
+        let decompressor = compression_mode.new_decompressor()?;
         Ok(CompressingStoredFieldsReader {
             version,
             field_infos,
@@ -530,6 +530,9 @@ impl CompressingStoredFieldsReader {
         if b == 0xff {
             // negative value
             Ok(f64::from_bits(input.read_long()? as u64))
+        } else if b == 0xfe {
+            // float
+            Ok(f64::from(f32::from_bits(input.read_int()? as u32)))
         } else if (b & 0x80) != 0 {
             // small integer [-1..125]
             Ok(f64::from((b & 0x7f) - 1))
@@ -714,8 +717,9 @@ impl CompressingStoredFieldsReader {
                     )?;
                     self.bytes
                         .resize(self.bytes_position.1 + self.spare_position.1, 0);
+                    // append the slice after the ones decompressed so far
                     self.bytes
-                        [self.bytes_position.0..self.bytes_position.0 + self.spare_position.1]
+                        [self.bytes_position.1..self.bytes_position.1 + self.spare_position.1]
                         .copy_from_slice(
                             &self.spare[self.spare_position.0
                                 ..self.spare_position.0 + self.spare_position.1],
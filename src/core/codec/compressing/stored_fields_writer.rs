@@ -276,7 +276,7 @@ impl<O: IndexOutput + 'static> CompressingStoredFieldsWriter<O> {
             index_writer,
             fields_stream,
             compress_mode,
-            compressor: compress_mode.new_compressor(),
+            compressor: compress_mode.new_compressor()?,
             chunk_size,
             max_docs_per_chunk,
             buffered_docs: GrowableByteArrayDataOutput::new(chunk_size),
@@ -235,7 +235,7 @@ impl CompressingTermVectorsReader {
         vectors_stream.seek(pos)?;
         let packed_ints_version = vectors_stream.read_vint()?;
         let chunk_size = vectors_stream.read_vint()?;
-        let decompressor = compression_mode.new_decompressor();
+        let decompressor = compression_mode.new_decompressor()?;
         let reader = BlockPackedReaderIterator::new(
             vectors_stream.as_mut(),
             packed_ints_version,
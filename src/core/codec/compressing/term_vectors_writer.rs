@@ -330,7 +330,7 @@ impl<O: IndexOutput> CompressingTermVectorsWriter<O> {
             index_writer,
             vectors_stream,
             compress_mode,
-            compressor: compress_mode.new_compressor(),
+            compressor: compress_mode.new_compressor()?,
             chunk_size,
             // number of compressed blocks written
             num_chunks: 0,
@@ -0,0 +1,188 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::min;
+
+use zstd::block::{self, Compressor as BlockCompressor, Decompressor as BlockDecompressor};
+
+use core::codec::compressing::{Compress, Decompress};
+use core::store::{DataInput, DataOutput};
+use core::util::packed_misc::OffsetAndLength;
+use error::{ErrorKind::CorruptIndex, Result};
+
+/// The level used by `CompressionMode::Zstd` when none is configured.
+pub const ZSTD_DEFAULT_LEVEL: i32 = 3;
+
+// each chunk is split into this many sub blocks, which are compressed
+// independently so that reading a document only decompresses its sub blocks
+const NUM_SUB_BLOCKS: usize = 10;
+// the dictionary of a chunk is 1/DICT_SIZE_FACTOR of the size of a sub block
+const DICT_SIZE_FACTOR: usize = 6;
+
+fn num_sub_blocks(len: usize, dict_length: usize, block_length: usize) -> usize {
+    if block_length == 0 {
+        0
+    } else {
+        (len - dict_length + block_length - 1) / block_length
+    }
+}
+
+/// Compress each chunk with zstd, using the head of the chunk as a preset
+/// dictionary for the rest of it.
+///
+/// The dictionary is compressed on its own, then the remaining bytes are
+/// split into sub blocks that are each compressed with the dictionary, so
+/// documents of a chunk still share their redundancy while a reader only
+/// needs to decompress the dictionary and the sub blocks it is interested in.
+pub struct ZstdCompressor {
+    level: i32,
+}
+
+impl ZstdCompressor {
+    pub fn new(level: i32) -> ZstdCompressor {
+        ZstdCompressor { level }
+    }
+}
+
+impl Compress for ZstdCompressor {
+    fn compress(
+        &mut self,
+        bytes: &[u8],
+        off: usize,
+        len: usize,
+        out: &mut impl DataOutput,
+    ) -> Result<()> {
+        let dict_length = len / (NUM_SUB_BLOCKS * DICT_SIZE_FACTOR);
+        let block_length = (len - dict_length + NUM_SUB_BLOCKS - 1) / NUM_SUB_BLOCKS;
+        out.write_vint(dict_length as i32)?;
+        out.write_vint(block_length as i32)?;
+
+        let dict = &bytes[off..off + dict_length];
+        let mut compressed = Vec::with_capacity(NUM_SUB_BLOCKS + 1);
+        if dict_length > 0 {
+            compressed.push(block::compress(dict, self.level)?);
+        } else {
+            compressed.push(Vec::new());
+        }
+        let mut compressor = BlockCompressor::with_dict(dict.to_vec());
+        let mut start = off + dict_length;
+        while start < off + len {
+            let end = min(start + block_length, off + len);
+            compressed.push(compressor.compress(&bytes[start..end], self.level)?);
+            start = end;
+        }
+
+        // write all the lengths first so that readers can skip sub blocks
+        for c in &compressed {
+            out.write_vint(c.len() as i32)?;
+        }
+        for c in &compressed {
+            out.write_bytes(c, 0, c.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Decompressor for the chunks written by `ZstdCompressor`.
+#[derive(Clone)]
+pub struct ZstdDecompressor;
+
+impl Default for ZstdDecompressor {
+    fn default() -> ZstdDecompressor {
+        ZstdDecompressor {}
+    }
+}
+
+impl Decompress for ZstdDecompressor {
+    fn decompress<R: DataInput + ?Sized>(
+        &self,
+        input: &mut R,
+        original_length: usize,
+        offset: usize,
+        length: usize,
+        bytes: &mut Vec<u8>,
+        bytes_position: &mut OffsetAndLength,
+    ) -> Result<()> {
+        debug_assert!(offset + length <= original_length);
+        if length == 0 {
+            bytes_position.0 = 0;
+            bytes_position.1 = 0;
+            return Ok(());
+        }
+
+        let dict_length = input.read_vint()? as usize;
+        let block_length = input.read_vint()? as usize;
+        if dict_length > original_length {
+            bail!(CorruptIndex(format!(
+                "zstd dictionary length {} > {}",
+                dict_length, original_length
+            )));
+        }
+        let num_blocks = num_sub_blocks(original_length, dict_length, block_length);
+        let dict_compressed_length = input.read_vint()? as usize;
+        let mut compressed_lengths = Vec::with_capacity(num_blocks);
+        for _ in 0..num_blocks {
+            compressed_lengths.push(input.read_vint()? as usize);
+        }
+
+        let mut compressed = vec![0u8; dict_compressed_length];
+        input.read_bytes(&mut compressed, 0, dict_compressed_length)?;
+        bytes.clear();
+        if dict_length > 0 {
+            bytes.extend(block::decompress(&compressed, dict_length)?);
+            if bytes.len() != dict_length {
+                bail!(CorruptIndex(format!(
+                    "zstd dictionary lengths mismatch: {} != {}",
+                    bytes.len(),
+                    dict_length
+                )));
+            }
+        }
+
+        let mut decompressor = BlockDecompressor::with_dict(bytes.clone());
+        // start of the first decompressed sub block in the original stream
+        let mut first_block_start = None;
+        for (i, &compressed_length) in compressed_lengths.iter().enumerate() {
+            let start = dict_length + i * block_length;
+            let end = min(start + block_length, original_length);
+            if start >= offset + length {
+                break;
+            }
+            if end <= offset {
+                input.skip_bytes(compressed_length)?;
+                continue;
+            }
+            compressed.resize(compressed_length, 0u8);
+            input.read_bytes(&mut compressed, 0, compressed_length)?;
+            let block = decompressor.decompress(&compressed, end - start)?;
+            if block.len() != end - start {
+                bail!(CorruptIndex(format!(
+                    "zstd block lengths mismatch: {} != {}",
+                    block.len(),
+                    end - start
+                )));
+            }
+            bytes.extend(block);
+            if first_block_start.is_none() {
+                first_block_start = Some(start);
+            }
+        }
+
+        bytes_position.0 = match first_block_start {
+            Some(start) if offset >= dict_length => dict_length + offset - start,
+            _ => offset,
+        };
+        bytes_position.1 = length;
+        Ok(())
+    }
+}
@@ -12,8 +12,8 @@
 // limitations under the License.
 
 use core::codec::{
-    BlockTreeTermsWriter, Codec, FieldsProducer, Lucene50PostingsWriter, NormsProducer,
-    PerFieldFieldsWriter,
+    BlockTreeTermsWriter, Codec, FieldsProducer, Lucene50PostingsWriter, MemoryFieldsConsumer,
+    NormsProducer, PerFieldFieldsWriter,
 };
 use core::index::doc_id_merger::{
     doc_id_merger_of, DocIdMerger, DocIdMergerEnum, DocIdMergerSub, DocIdMergerSubBase,
@@ -27,9 +27,11 @@ use core::index::{
     SeekStatus, SortedDocValues, SortedDocValuesRef, SortedNumericDocValuesContext,
     SortedNumericDocValuesRef, SortedSetDocValuesRef, TermIterator, NO_MORE_ORDS,
 };
+use core::search::bm25_similarity::NormsEncoding;
 use core::search::posting_iterator::EmptyPostingIterator;
 use core::search::NO_MORE_DOCS;
 use core::store::Directory;
+use core::suggest::document::CompletionFieldsConsumer;
 use core::util::bkd::LongBitSet;
 use core::util::numeric::Numeric;
 use core::util::packed_misc::COMPACT;
@@ -38,7 +40,6 @@ use core::util::{BitsRef, BytesRef, DocId, LongValues, MatchNoBits, ReusableIter
 use error::Result;
 
 use std::ptr;
-use std::rc::Rc;
 use std::sync::Arc;
 
 /// Abstract API that consumes terms, doc, freq, prox, offset and
@@ -68,14 +69,21 @@ pub trait FieldsConsumer {
     ///
     /// - The provided Fields instance is limited: you cannot call any methods that return
     ///   statistics/counts; you cannot pass a non-null live docs when pulling docs/positions enums.
-    fn write(&mut self, fields: &impl Fields) -> Result<()>;
+    ///
+    /// `norms` gives access to the norms of the segment being written, if it has any, so that
+    /// the impacts of the postings can be recorded.
+    fn write(&mut self, fields: &impl Fields, norms: Option<&dyn NormsProducer>) -> Result<()>;
 
     /// Merges in the fields from the readers in
     /// <code>mergeState</code>. The default implementation skips
     /// and maps around deleted documents, and calls {@link #write(Fields)}.
     /// Implementations can override this method for more sophisticated
     /// merging (bulk-byte copying, etc).
-    fn merge<D: Directory, C: Codec>(&mut self, merge_state: &mut MergeState<D, C>) -> Result<()> {
+    fn merge<D: Directory, C: Codec>(
+        &mut self,
+        merge_state: &mut MergeState<D, C>,
+        norms: Option<&dyn NormsProducer>,
+    ) -> Result<()> {
         let mut fields = vec![];
         let mut slices = vec![];
 
@@ -93,30 +101,37 @@ pub trait FieldsConsumer {
 
         let fields = MultiFields::new(fields, slices);
         let merged_fields = MappedMultiFields::new(merge_state, fields);
-        self.write(&merged_fields)
+        self.write(&merged_fields, norms)
     }
 }
 
 pub enum FieldsConsumerEnum<D: Directory, DW: Directory, C: Codec> {
     Lucene50(BlockTreeTermsWriter<Lucene50PostingsWriter<DW::IndexOutput>, DW::IndexOutput>),
     PerField(PerFieldFieldsWriter<D, DW, C>),
+    Completion(CompletionFieldsConsumer<DW::IndexOutput>),
+    Memory(MemoryFieldsConsumer<DW::IndexOutput>),
 }
 
 impl<D: Directory, DW: Directory, C: Codec> FieldsConsumer for FieldsConsumerEnum<D, DW, C> {
-    fn write(&mut self, fields: &impl Fields) -> Result<()> {
+    fn write(&mut self, fields: &impl Fields, norms: Option<&dyn NormsProducer>) -> Result<()> {
         match self {
-            FieldsConsumerEnum::Lucene50(w) => w.write(fields),
-            FieldsConsumerEnum::PerField(w) => w.write(fields),
+            FieldsConsumerEnum::Lucene50(w) => w.write(fields, norms),
+            FieldsConsumerEnum::PerField(w) => w.write(fields, norms),
+            FieldsConsumerEnum::Completion(w) => w.write(fields, norms),
+            FieldsConsumerEnum::Memory(w) => w.write(fields, norms),
         }
     }
 
     fn merge<D1: Directory, C1: Codec>(
         &mut self,
         merge_state: &mut MergeState<D1, C1>,
+        norms: Option<&dyn NormsProducer>,
     ) -> Result<()> {
         match self {
-            FieldsConsumerEnum::Lucene50(w) => w.merge(merge_state),
-            FieldsConsumerEnum::PerField(w) => w.merge(merge_state),
+            FieldsConsumerEnum::Lucene50(w) => w.merge(merge_state, norms),
+            FieldsConsumerEnum::PerField(w) => w.merge(merge_state, norms),
+            FieldsConsumerEnum::Completion(w) => w.merge(merge_state, norms),
+            FieldsConsumerEnum::Memory(w) => w.merge(merge_state, norms),
         }
     }
 }
@@ -727,7 +742,7 @@ impl<'a, D: Directory + 'static, C: Codec> BinaryDocValuesMergeIter<'a, D, C> {
         if let Some(sub) = self.doc_id_merger.next()? {
             self.next_is_set = true;
             if sub.docs_with_field.get(sub.doc_id as usize)? {
-                self.next_value = sub.values.get(sub.doc_id)?;
+                sub.values.get_into(sub.doc_id, &mut self.next_value)?;
                 self.next_ref = BytesRef::new(&self.next_value);
             } else {
                 self.next_value.clear();
@@ -958,7 +973,7 @@ struct SortedDocValuesSub {
     values: SortedDocValuesRef,
     doc_id: DocId,
     max_doc: i32,
-    map: Rc<dyn LongValues>,
+    map: Arc<dyn LongValues>,
     base: DocIdMergerSubBase,
 }
 
@@ -967,7 +982,7 @@ impl SortedDocValuesSub {
         doc_map: Arc<LiveDocsDocMap>,
         values: SortedDocValuesRef,
         max_doc: i32,
-        map: Rc<dyn LongValues>,
+        map: Arc<dyn LongValues>,
     ) -> Self {
         let base = DocIdMergerSubBase::new(doc_map);
         SortedDocValuesSub {
@@ -1249,7 +1264,7 @@ struct SortedSetDocValuesSub {
     values: SortedSetDocValuesRef,
     doc_id: DocId,
     max_doc: i32,
-    map: Rc<dyn LongValues>,
+    map: Arc<dyn LongValues>,
     base: DocIdMergerSubBase,
 }
 
@@ -1258,7 +1273,7 @@ impl SortedSetDocValuesSub {
         doc_map: Arc<LiveDocsDocMap>,
         values: SortedSetDocValuesRef,
         max_doc: i32,
-        map: Rc<dyn LongValues>,
+        map: Arc<dyn LongValues>,
     ) -> Self {
         let base = DocIdMergerSubBase::new(doc_map);
         SortedSetDocValuesSub {
@@ -1553,6 +1568,9 @@ pub trait NormsConsumer {
             .values()
         {
             if merge_field_info.has_norms() {
+                // the segments may have been indexed with different encodings,
+                // their norms are re-encoded with the one of the merged field
+                let norms_encoding = NormsEncoding::of(Some(merge_field_info))?;
                 let mut to_merge = vec![];
                 for i in 0..merge_state.norms_producers.len() {
                     let mut norms: NumericDocValuesRef = Arc::new(EmptyNumericDocValues::default());
@@ -1561,7 +1579,10 @@ pub trait NormsConsumer {
                             merge_state.fields_infos[i].field_info_by_name(&merge_field_info.name)
                         {
                             if field_info.has_norms() {
-                                norms = Arc::from(norm_producer.norms(field_info)?);
+                                norms = NormsEncoding::of(Some(field_info))?.reencode(
+                                    Arc::from(norm_producer.norms(field_info)?),
+                                    norms_encoding,
+                                );
                             }
                         }
                     }
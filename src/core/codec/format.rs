@@ -17,7 +17,10 @@ use core::index::{
     SegmentCommitInfo, SegmentInfo, SegmentReadState, SegmentWriteState, SortedDocValuesRef,
     SortedNumericDocValuesRef, SortedSetDocValuesRef,
 };
-use core::store::{Directory, IOContext, IndexOutput};
+use core::store::{
+    Directory, DirectoryOutputs, FilesDirectory, FilesOutputs, IOContext, IndexOutput,
+};
+use core::suggest::document::{CompletionPostingsFormat, COMPLETION_POSTINGS_FORMAT_NAME};
 use core::util::bit_set::FixedBitSet;
 use core::util::string_util::ID_LENGTH;
 use core::util::{Bits, BitsRef, BytesRef, Numeric, ReusableIterator};
@@ -92,6 +95,8 @@ pub trait PostingsFormat {
 
 pub enum PostingsFormatEnum {
     Lucene50(Lucene50PostingsFormat),
+    Completion(CompletionPostingsFormat),
+    Memory(MemoryPostingsFormat),
 }
 
 impl PostingsFormat for PostingsFormatEnum {
@@ -105,6 +110,12 @@ impl PostingsFormat for PostingsFormatEnum {
             PostingsFormatEnum::Lucene50(f) => {
                 Ok(FieldsProducerEnum::Lucene50(f.fields_producer(state)?))
             }
+            PostingsFormatEnum::Completion(f) => {
+                Ok(FieldsProducerEnum::Completion(f.fields_producer(state)?))
+            }
+            PostingsFormatEnum::Memory(f) => {
+                Ok(FieldsProducerEnum::Memory(f.fields_producer(state)?))
+            }
         }
     }
 
@@ -114,28 +125,45 @@ impl PostingsFormat for PostingsFormatEnum {
     ) -> Result<FieldsConsumerEnum<D, DW, C>> {
         match self {
             PostingsFormatEnum::Lucene50(f) => f.fields_consumer(state),
+            PostingsFormatEnum::Completion(f) => f.fields_consumer(state),
+            PostingsFormatEnum::Memory(f) => f.fields_consumer(state),
         }
     }
 
     fn name(&self) -> &str {
         match self {
             PostingsFormatEnum::Lucene50(f) => f.name(),
+            PostingsFormatEnum::Completion(f) => f.name(),
+            PostingsFormatEnum::Memory(f) => f.name(),
         }
     }
 }
 
 pub fn postings_format_for_name(name: &str) -> Result<PostingsFormatEnum> {
-    match name {
-        "Lucene50" => Ok(PostingsFormatEnum::Lucene50(
-            Lucene50PostingsFormat::default(),
-        )),
-        _ => bail!(IllegalArgument(format!(
+    match builtin_postings_format(name).or_else(|| registered_postings_format(name)) {
+        Some(format) => Ok(format),
+        None => bail!(IllegalArgument(format!(
             "Invalid postings format: {}",
             name
         ))),
     }
 }
 
+pub(crate) fn builtin_postings_format(name: &str) -> Option<PostingsFormatEnum> {
+    match name {
+        "Lucene50" => Some(PostingsFormatEnum::Lucene50(
+            Lucene50PostingsFormat::default(),
+        )),
+        COMPLETION_POSTINGS_FORMAT_NAME => Some(PostingsFormatEnum::Completion(
+            CompletionPostingsFormat::default(),
+        )),
+        MEMORY_POSTINGS_FORMAT_NAME => {
+            Some(PostingsFormatEnum::Memory(MemoryPostingsFormat::default()))
+        }
+        _ => None,
+    }
+}
+
 /// Controls the format of stored fields
 pub trait StoredFieldsFormat {
     type Reader: StoredFieldsReader;
@@ -250,8 +278,57 @@ pub trait SegmentInfoFormat {
     ) -> Result<()>;
 }
 
+#[derive(Copy, Clone)]
+pub enum SegmentInfoFormatEnum {
+    Lucene50(Lucene50SegmentInfoFormat),
+    Lucene62(Lucene62SegmentInfoFormat),
+    Lucene70(Lucene70SegmentInfoFormat),
+}
+
+impl SegmentInfoFormat for SegmentInfoFormatEnum {
+    fn read<D: Directory, C: Codec>(
+        &self,
+        directory: &Arc<D>,
+        segment_name: &str,
+        segment_id: [u8; ID_LENGTH],
+        context: &IOContext,
+    ) -> Result<SegmentInfo<D, C>> {
+        match self {
+            SegmentInfoFormatEnum::Lucene50(f) => {
+                f.read(directory, segment_name, segment_id, context)
+            }
+            SegmentInfoFormatEnum::Lucene62(f) => {
+                f.read(directory, segment_name, segment_id, context)
+            }
+            SegmentInfoFormatEnum::Lucene70(f) => {
+                f.read(directory, segment_name, segment_id, context)
+            }
+        }
+    }
+
+    fn write<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        dir: &Arc<DW>,
+        info: &mut SegmentInfo<D, C>,
+        io_context: &IOContext,
+    ) -> Result<()> {
+        match self {
+            SegmentInfoFormatEnum::Lucene50(f) => f.write(dir, info, io_context),
+            SegmentInfoFormatEnum::Lucene62(f) => f.write(dir, info, io_context),
+            SegmentInfoFormatEnum::Lucene70(f) => f.write(dir, info, io_context),
+        }
+    }
+}
+
 pub trait DocValuesFormat {
     fn name(&self) -> &str;
+
+    /// Returns true if this format can only read the segments written with it,
+    /// the fields it read are then written with the default format.
+    fn read_only(&self) -> bool {
+        false
+    }
+
     // TODO need GAT to remove the Box
     fn fields_producer<'a, D: Directory, DW: Directory, C: Codec>(
         &self,
@@ -267,14 +344,31 @@ pub trait DocValuesFormat {
 
 pub enum DocValuesFormatEnum {
     Lucene54(Lucene54DocValuesFormat),
+    Lucene70(Lucene70DocValuesFormat),
+    Rucene54(Rucene54DocValuesFormat),
     PerField(PerFieldDocValuesFormat),
+    /// A format implemented outside of this crate, see `register_doc_values_format`.
+    Custom(Box<dyn CustomDocValuesFormat>),
 }
 
 impl DocValuesFormat for DocValuesFormatEnum {
     fn name(&self) -> &str {
         match self {
             DocValuesFormatEnum::Lucene54(d) => d.name(),
+            DocValuesFormatEnum::Lucene70(d) => d.name(),
+            DocValuesFormatEnum::Rucene54(d) => d.name(),
             DocValuesFormatEnum::PerField(d) => d.name(),
+            DocValuesFormatEnum::Custom(d) => d.name(),
+        }
+    }
+
+    fn read_only(&self) -> bool {
+        match self {
+            DocValuesFormatEnum::Lucene54(d) => d.read_only(),
+            DocValuesFormatEnum::Lucene70(d) => d.read_only(),
+            DocValuesFormatEnum::Rucene54(d) => d.read_only(),
+            DocValuesFormatEnum::PerField(d) => d.read_only(),
+            DocValuesFormatEnum::Custom(_) => false,
         }
     }
 
@@ -284,7 +378,19 @@ impl DocValuesFormat for DocValuesFormatEnum {
     ) -> Result<Box<dyn DocValuesProducer>> {
         match self {
             DocValuesFormatEnum::Lucene54(d) => d.fields_producer(state),
+            DocValuesFormatEnum::Lucene70(d) => d.fields_producer(state),
+            DocValuesFormatEnum::Rucene54(d) => d.fields_producer(state),
             DocValuesFormatEnum::PerField(d) => d.fields_producer(state),
+            DocValuesFormatEnum::Custom(d) => d.fields_producer(
+                state.directory.as_ref(),
+                &SegmentFiles {
+                    segment_name: &state.segment_info.name,
+                    segment_id: state.segment_info.get_id(),
+                    max_doc: state.segment_info.max_doc(),
+                    field_infos: &state.field_infos,
+                    segment_suffix: &state.segment_suffix,
+                },
+            ),
         }
     }
 
@@ -294,7 +400,183 @@ impl DocValuesFormat for DocValuesFormatEnum {
     ) -> Result<DocValuesConsumerEnum<D, DW, C>> {
         match self {
             DocValuesFormatEnum::Lucene54(d) => d.fields_consumer(state),
+            DocValuesFormatEnum::Lucene70(d) => d.fields_consumer(state),
+            DocValuesFormatEnum::Rucene54(d) => d.fields_consumer(state),
             DocValuesFormatEnum::PerField(d) => d.fields_consumer(state),
+            DocValuesFormatEnum::Custom(d) => {
+                let mut outputs =
+                    DirectoryOutputs::new(Arc::clone(&state.directory), state.context);
+                let consumer = d.fields_consumer(
+                    &mut outputs,
+                    &SegmentFiles {
+                        segment_name: &state.segment_info.name,
+                        segment_id: state.segment_info.get_id(),
+                        max_doc: state.segment_info.max_doc(),
+                        field_infos: &state.field_infos,
+                        segment_suffix: &state.segment_suffix,
+                    },
+                )?;
+                Ok(DocValuesConsumerEnum::Custom(CustomDocValuesWriter {
+                    consumer,
+                    outputs,
+                }))
+            }
+        }
+    }
+}
+
+/// The segment a `CustomDocValuesFormat` reads or writes.
+pub struct SegmentFiles<'a> {
+    pub segment_name: &'a str,
+    pub segment_id: &'a [u8],
+    pub max_doc: i32,
+    pub field_infos: &'a FieldInfos,
+    /// The suffix of the file names, set by the per field format to
+    /// tell apart the files of each format.
+    pub segment_suffix: &'a str,
+}
+
+/// A doc values format implemented as a trait object, so that a format
+/// defined outside of this crate can be registered under its name with
+/// `register_doc_values_format` and wrapped in `DocValuesFormatEnum::Custom`.
+pub trait CustomDocValuesFormat: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn fields_producer(
+        &self,
+        directory: &dyn FilesDirectory,
+        state: &SegmentFiles<'_>,
+    ) -> Result<Box<dyn DocValuesProducer>>;
+
+    /// Creates the files of the consumer in `outputs`, the returned
+    /// consumer is given back `outputs` to write them.
+    fn fields_consumer(
+        &self,
+        outputs: &mut dyn FilesOutputs,
+        state: &SegmentFiles<'_>,
+    ) -> Result<Box<dyn CustomDocValuesConsumer>>;
+}
+
+/// The object safe counterpart of `DocValuesConsumer` for the consumers of
+/// a `CustomDocValuesFormat`, merges go through the default `DocValuesConsumer`
+/// merging which adds the merged values with these methods.
+pub trait CustomDocValuesConsumer {
+    fn add_numeric_field(
+        &mut self,
+        outputs: &mut dyn FilesOutputs,
+        field_info: &FieldInfo,
+        values: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+    ) -> Result<()>;
+
+    fn add_binary_field(
+        &mut self,
+        outputs: &mut dyn FilesOutputs,
+        field_info: &FieldInfo,
+        values: &mut dyn ReusableIterator<Item = Result<BytesRef>>,
+    ) -> Result<()>;
+
+    fn add_sorted_field(
+        &mut self,
+        outputs: &mut dyn FilesOutputs,
+        field_info: &FieldInfo,
+        values: &mut dyn ReusableIterator<Item = Result<BytesRef>>,
+        doc_to_ord: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+    ) -> Result<()>;
+
+    fn add_sorted_numeric_field(
+        &mut self,
+        outputs: &mut dyn FilesOutputs,
+        field_info: &FieldInfo,
+        values: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+        doc_to_value_count: &mut dyn ReusableIterator<Item = Result<u32>>,
+    ) -> Result<()>;
+
+    fn add_sorted_set_field(
+        &mut self,
+        outputs: &mut dyn FilesOutputs,
+        field_info: &FieldInfo,
+        values: &mut dyn ReusableIterator<Item = Result<BytesRef>>,
+        doc_to_ord_count: &mut dyn ReusableIterator<Item = Result<u32>>,
+        ords: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+    ) -> Result<()>;
+
+    /// Called once all the fields are added, writes the footers of the files.
+    fn finish(&mut self, _outputs: &mut dyn FilesOutputs) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Owns the outputs of a `CustomDocValuesConsumer` and finishes it on drop
+/// like the other consumers close their files.
+pub struct CustomDocValuesWriter<D: Directory> {
+    consumer: Box<dyn CustomDocValuesConsumer>,
+    outputs: DirectoryOutputs<D>,
+}
+
+impl<D: Directory> DocValuesConsumer for CustomDocValuesWriter<D> {
+    fn add_numeric_field(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<Numeric>>,
+    ) -> Result<()> {
+        self.consumer
+            .add_numeric_field(&mut self.outputs, field_info, values)
+    }
+
+    fn add_binary_field(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+    ) -> Result<()> {
+        self.consumer
+            .add_binary_field(&mut self.outputs, field_info, values)
+    }
+
+    fn add_sorted_field(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+        doc_to_ord: &mut impl ReusableIterator<Item = Result<Numeric>>,
+    ) -> Result<()> {
+        self.consumer
+            .add_sorted_field(&mut self.outputs, field_info, values, doc_to_ord)
+    }
+
+    fn add_sorted_numeric_field(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<Numeric>>,
+        doc_to_value_count: &mut impl ReusableIterator<Item = Result<u32>>,
+    ) -> Result<()> {
+        self.consumer.add_sorted_numeric_field(
+            &mut self.outputs,
+            field_info,
+            values,
+            doc_to_value_count,
+        )
+    }
+
+    fn add_sorted_set_field(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+        doc_to_ord_count: &mut impl ReusableIterator<Item = Result<u32>>,
+        ords: &mut impl ReusableIterator<Item = Result<Numeric>>,
+    ) -> Result<()> {
+        self.consumer.add_sorted_set_field(
+            &mut self.outputs,
+            field_info,
+            values,
+            doc_to_ord_count,
+            ords,
+        )
+    }
+}
+
+impl<D: Directory> Drop for CustomDocValuesWriter<D> {
+    fn drop(&mut self) {
+        if let Err(e) = self.consumer.finish(&mut self.outputs) {
+            error!("finish custom doc values consumer failed: {:?}", e);
         }
     }
 }
@@ -302,6 +584,7 @@ impl DocValuesFormat for DocValuesFormatEnum {
 pub enum DocValuesConsumerEnum<D: Directory, DW: Directory, C: Codec> {
     Lucene54(Lucene54DocValuesConsumer<DW::IndexOutput>),
     PerField(DocValuesFieldsWriter<D, DW, C>),
+    Custom(CustomDocValuesWriter<DW>),
 }
 
 impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsumerEnum<D, DW, C> {
@@ -313,6 +596,7 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
         match self {
             DocValuesConsumerEnum::Lucene54(d) => d.add_numeric_field(field_info, values),
             DocValuesConsumerEnum::PerField(d) => d.add_numeric_field(field_info, values),
+            DocValuesConsumerEnum::Custom(d) => d.add_numeric_field(field_info, values),
         }
     }
 
@@ -324,6 +608,7 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
         match self {
             DocValuesConsumerEnum::Lucene54(d) => d.add_binary_field(field_info, values),
             DocValuesConsumerEnum::PerField(d) => d.add_binary_field(field_info, values),
+            DocValuesConsumerEnum::Custom(d) => d.add_binary_field(field_info, values),
         }
     }
 
@@ -340,6 +625,7 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.add_sorted_field(field_info, values, doc_to_ord)
             }
+            DocValuesConsumerEnum::Custom(d) => d.add_sorted_field(field_info, values, doc_to_ord),
         }
     }
 
@@ -356,6 +642,9 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.add_sorted_numeric_field(field_info, values, doc_to_value_count)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.add_sorted_numeric_field(field_info, values, doc_to_value_count)
+            }
         }
     }
 
@@ -373,6 +662,9 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.add_sorted_set_field(field_info, values, doc_to_ord_count, ords)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.add_sorted_set_field(field_info, values, doc_to_ord_count, ords)
+            }
         }
     }
 
@@ -383,6 +675,7 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
         match self {
             DocValuesConsumerEnum::Lucene54(d) => d.merge(merge_state),
             DocValuesConsumerEnum::PerField(d) => d.merge(merge_state),
+            DocValuesConsumerEnum::Custom(d) => d.merge(merge_state),
         }
     }
 
@@ -400,6 +693,9 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.merge_numeric_field(field_info, merge_state, to_merge, docs_with_field)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.merge_numeric_field(field_info, merge_state, to_merge, docs_with_field)
+            }
         }
     }
 
@@ -417,6 +713,9 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.merge_binary_field(field_info, merge_state, to_merge, docs_with_field)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.merge_binary_field(field_info, merge_state, to_merge, docs_with_field)
+            }
         }
     }
 
@@ -433,6 +732,9 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.merge_sorted_field(field_info, merge_state, to_merge)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.merge_sorted_field(field_info, merge_state, to_merge)
+            }
         }
     }
 
@@ -449,6 +751,9 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.merge_sorted_set_field(field_info, merge_state, to_merge)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.merge_sorted_set_field(field_info, merge_state, to_merge)
+            }
         }
     }
 
@@ -465,16 +770,35 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesConsumer for DocValuesConsu
             DocValuesConsumerEnum::PerField(d) => {
                 d.merge_sorted_numeric_field(field_info, merge_state, to_merge)
             }
+            DocValuesConsumerEnum::Custom(d) => {
+                d.merge_sorted_numeric_field(field_info, merge_state, to_merge)
+            }
         }
     }
 }
 
 pub fn doc_values_format_for_name(format: &str) -> Result<DocValuesFormatEnum> {
+    match builtin_doc_values_format(format).or_else(|| registered_doc_values_format(format)) {
+        Some(format) => Ok(format),
+        None => bail!(IllegalArgument(format!(
+            "Invalid doc values format: {}",
+            format
+        ))),
+    }
+}
+
+pub(crate) fn builtin_doc_values_format(format: &str) -> Option<DocValuesFormatEnum> {
     match format {
-        "Lucene54" => Ok(DocValuesFormatEnum::Lucene54(
+        "Lucene54" => Some(DocValuesFormatEnum::Lucene54(
             Lucene54DocValuesFormat::default(),
         )),
-        _ => unimplemented!(),
+        "Lucene70" => Some(DocValuesFormatEnum::Lucene70(
+            Lucene70DocValuesFormat::default(),
+        )),
+        "Rucene54" => Some(DocValuesFormatEnum::Rucene54(
+            Rucene54DocValuesFormat::default(),
+        )),
+        _ => None,
     }
 }
 
@@ -500,6 +824,61 @@ pub trait NormsFormat {
     ) -> Result<NormsConsumerEnum<DW::IndexOutput>>;
 }
 
+#[derive(Copy, Clone)]
+pub enum NormsFormatEnum {
+    Lucene53(Lucene53NormsFormat),
+    Lucene70(Lucene70NormsFormat),
+}
+
+impl NormsFormat for NormsFormatEnum {
+    type NormsProducer = NormsProducerEnum;
+
+    fn norms_producer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::NormsProducer> {
+        match self {
+            NormsFormatEnum::Lucene53(f) => {
+                Ok(NormsProducerEnum::Lucene53(f.norms_producer(state)?))
+            }
+            NormsFormatEnum::Lucene70(f) => {
+                Ok(NormsProducerEnum::Lucene70(f.norms_producer(state)?))
+            }
+        }
+    }
+
+    fn norms_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<NormsConsumerEnum<DW::IndexOutput>> {
+        match self {
+            NormsFormatEnum::Lucene53(f) => f.norms_consumer(state),
+            NormsFormatEnum::Lucene70(f) => f.norms_consumer(state),
+        }
+    }
+}
+
+pub enum NormsProducerEnum {
+    Lucene53(Lucene53NormsProducer),
+    Lucene70(Lucene70NormsProducer),
+}
+
+impl NormsProducer for NormsProducerEnum {
+    fn norms(&self, field: &FieldInfo) -> Result<Box<dyn NumericDocValues>> {
+        match self {
+            NormsProducerEnum::Lucene53(p) => p.norms(field),
+            NormsProducerEnum::Lucene70(p) => p.norms(field),
+        }
+    }
+
+    fn check_integrity(&self) -> Result<()> {
+        match self {
+            NormsProducerEnum::Lucene53(p) => p.check_integrity(),
+            NormsProducerEnum::Lucene70(p) => p.check_integrity(),
+        }
+    }
+}
+
 pub enum NormsConsumerEnum<O: IndexOutput> {
     Lucene53(Lucene53NormsConsumer<O>),
 }
@@ -600,3 +979,22 @@ pub trait PointsFormat {
         state: &SegmentReadState<'_, D, DW, C>,
     ) -> Result<Self::Reader>;
 }
+
+/// Encodes/decodes per-document float vectors.
+pub trait VectorFormat {
+    type Reader: VectorReader + 'static;
+
+    /// Writes a new segment
+    // TODO we need GAT to make this interface possible
+    fn fields_writer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<Lucene90VectorWriter<DW::IndexOutput>>;
+
+    /// Reads a segment, all the files it needs must be opened before the call
+    /// returns.
+    fn fields_reader<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::Reader>;
+}
@@ -31,6 +31,10 @@ mod live_docs;
 
 pub use self::live_docs::*;
 
+mod segment_info;
+
+pub use self::segment_info::*;
+
 pub mod skip_reader;
 pub mod skip_writer;
 pub mod util;
@@ -34,9 +34,6 @@ pub struct Lucene50PostingsFormat {
 // NOTE: must be multiple of 64 because of PackedInts long-aligned encoding/decoding
 pub const BLOCK_SIZE: i32 = 128;
 
-const DEFAULT_MIN_BLOCK_SIZE: usize = 25;
-const DEFAULT_MAX_BLOCK_SIZE: usize = 48;
-
 impl fmt::Display for Lucene50PostingsFormat {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}(blocksize={})", self.name, BLOCK_SIZE)
@@ -45,11 +42,16 @@ impl fmt::Display for Lucene50PostingsFormat {
 
 impl Default for Lucene50PostingsFormat {
     fn default() -> Lucene50PostingsFormat {
-        Self::with_block_size(DEFAULT_MIN_BLOCK_SIZE, DEFAULT_MAX_BLOCK_SIZE)
+        Self::with_block_size(Self::DEFAULT_MIN_BLOCK_SIZE, Self::DEFAULT_MAX_BLOCK_SIZE)
     }
 }
 
 impl Lucene50PostingsFormat {
+    /// Default minimum number of terms in a block of the terms dictionary.
+    pub const DEFAULT_MIN_BLOCK_SIZE: usize = 25;
+    /// Default maximum number of terms in a block of the terms dictionary.
+    pub const DEFAULT_MAX_BLOCK_SIZE: usize = 48;
+
     pub fn with_block_size(
         min_term_block_size: usize,
         max_term_block_size: usize,
@@ -60,6 +62,22 @@ impl Lucene50PostingsFormat {
             max_term_block_size,
         }
     }
+
+    /// Creates the block tree terms writer backing the fields consumer of this
+    /// format, for formats which wrap it.
+    pub fn terms_writer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<BlockTreeTermsWriter<Lucene50PostingsWriter<DW::IndexOutput>, DW::IndexOutput>>
+    {
+        let postings_writer = Lucene50PostingsWriter::new(state)?;
+        BlockTreeTermsWriter::new(
+            state,
+            postings_writer,
+            self.min_term_block_size,
+            self.max_term_block_size,
+        )
+    }
 }
 
 impl PostingsFormat for Lucene50PostingsFormat {
@@ -76,13 +94,7 @@ impl PostingsFormat for Lucene50PostingsFormat {
         &self,
         state: &SegmentWriteState<D, DW, C>,
     ) -> Result<FieldsConsumerEnum<D, DW, C>> {
-        let postings_writer = Lucene50PostingsWriter::new(state)?;
-        Ok(FieldsConsumerEnum::Lucene50(BlockTreeTermsWriter::new(
-            state,
-            postings_writer,
-            self.min_term_block_size,
-            self.max_term_block_size,
-        )?))
+        Ok(FieldsConsumerEnum::Lucene50(self.terms_writer(state)?))
     }
 
     fn name(&self) -> &str {
@@ -51,7 +51,9 @@ pub const PAY_CODEC: &str = "Lucene50PostingsWriterPay";
 
 // Increment version to change it
 const VERSION_START: i32 = 0;
-pub const VERSION_CURRENT: i32 = VERSION_START;
+/// Version that records the competitive impacts of the docs in the skip data.
+pub const VERSION_IMPACT_SKIP_DATA: i32 = 1;
+pub const VERSION_CURRENT: i32 = VERSION_IMPACT_SKIP_DATA;
 
 fn clone_option_index_input(input: &Option<Box<dyn IndexInput>>) -> Result<Box<dyn IndexInput>> {
     debug_assert!(input.is_some());
@@ -194,6 +196,7 @@ impl Lucene50PostingsReader {
                     state,
                     flags,
                     self.for_util.clone(),
+                    self.version,
                 )?)
             } else if (!index_has_offsets
                 || !PostingIteratorFlags::feature_requested(flags, PostingIteratorFlags::OFFSETS))
@@ -210,6 +213,7 @@ impl Lucene50PostingsReader {
                     state,
                     flags,
                     self.for_util.clone(),
+                    self.version,
                 )?)
             } else {
                 debug_assert!(self.pos_in.is_some());
@@ -222,11 +226,49 @@ impl Lucene50PostingsReader {
                     state,
                     flags,
                     self.for_util.clone(),
+                    self.version,
                 )?)
             },
         )
     }
 
+    /// Returns the postings of a term along with the competitive impacts recorded
+    /// in its skip data. Terms without skip data, or written before impacts were
+    /// recorded, can't skip anything and fall back to `SlowImpactsEnum`.
+    pub fn impacts(
+        &self,
+        field_info: &FieldInfo,
+        state: &BlockTermState,
+        flags: u16,
+    ) -> Result<Box<dyn ImpactsEnum>> {
+        let postings = self.postings(field_info, state, flags)?;
+        if state.doc_freq <= BLOCK_SIZE || self.version < VERSION_IMPACT_SKIP_DATA {
+            return Ok(Box::new(SlowImpactsEnum::new(postings)));
+        }
+
+        let options = &field_info.index_options;
+        let mut skipper = Lucene50SkipReader::new(
+            self.doc_in.clone()?,
+            MAX_SKIP_LEVELS,
+            options.has_positions(),
+            options.has_offsets(),
+            field_info.has_store_payloads,
+            true,
+        );
+        skipper.init(
+            state.doc_start_fp + state.skip_offset,
+            state.doc_start_fp,
+            state.pos_start_fp,
+            state.pay_start_fp,
+            state.doc_freq,
+        )?;
+        Ok(Box::new(BlockImpactsEnum {
+            postings,
+            skipper,
+            next_skip_doc: -1,
+        }))
+    }
+
     pub fn check_integrity(&self) -> Result<()> {
         //        codec_util::checksum_entire_file(self.doc_in.as_ref())?;
         //
@@ -365,6 +407,7 @@ pub struct BlockDocIterator {
     singleton_doc_id: DocId,
 
     for_util: ForUtil,
+    version: i32,
 }
 
 impl BlockDocIterator {
@@ -374,6 +417,7 @@ impl BlockDocIterator {
         term_state: &BlockTermState,
         flags: u16,
         for_util: ForUtil,
+        version: i32,
     ) -> Result<BlockDocIterator> {
         let options = &field_info.index_options;
         let mut iterator = BlockDocIterator {
@@ -401,6 +445,7 @@ impl BlockDocIterator {
             index_has_offsets: options.has_offsets(),
             index_has_payloads: field_info.has_store_payloads,
             for_util,
+            version,
         };
         iterator.reset(term_state, flags)?;
         Ok(iterator)
@@ -542,6 +587,7 @@ impl DocIterator for BlockDocIterator {
                     self.index_has_pos,
                     self.index_has_offsets,
                     self.index_has_payloads,
+                    self.version >= VERSION_IMPACT_SKIP_DATA,
                 ));
             }
 
@@ -573,7 +619,9 @@ impl DocIterator for BlockDocIterator {
                 // Force to read next block
                 self.doc_buffer_upto = BLOCK_SIZE;
                 self.accum = skipper.doc(); // actually, this is just lastSkipEntry
-                self.doc_in.as_mut().unwrap().seek(skipper.doc_pointer())?; // now point to the block we want to search
+                self.doc_in.as_mut().unwrap().seek(skipper.doc_pointer())?; // now point to the
+                                                                            // block we want to
+                                                                            // search
             }
             // next time we call advance, this is used to
             // foresee whether skipper is necessary.
@@ -685,6 +733,7 @@ pub struct BlockPostingIterator {
     singleton_doc_id: i32,
 
     for_util: ForUtil,
+    version: i32,
 }
 
 impl BlockPostingIterator {
@@ -695,6 +744,7 @@ impl BlockPostingIterator {
         term_state: &BlockTermState,
         _flags: u16,
         for_util: ForUtil,
+        version: i32,
     ) -> Result<BlockPostingIterator> {
         let options = &field_info.index_options;
         let mut iterator = BlockPostingIterator {
@@ -729,6 +779,7 @@ impl BlockPostingIterator {
             index_has_offsets: options.has_offsets(),
             index_has_payloads: field_info.has_store_payloads,
             for_util,
+            version,
         };
         iterator.reset(term_state)?;
         Ok(iterator)
@@ -951,6 +1002,7 @@ impl DocIterator for BlockPostingIterator {
                     self.index_has_pos,
                     self.index_has_offsets,
                     self.index_has_payloads,
+                    self.version >= VERSION_IMPACT_SKIP_DATA,
                 ));
             }
 
@@ -1117,6 +1169,7 @@ pub struct EverythingIterator {
     singleton_doc_id: i32,
     // docid when there is a single pulsed posting, otherwise -1
     for_util: ForUtil,
+    version: i32,
 }
 
 impl<'a> EverythingIterator {
@@ -1129,6 +1182,7 @@ impl<'a> EverythingIterator {
         term_state: &BlockTermState,
         flags: u16,
         for_util: ForUtil,
+        version: i32,
     ) -> Result<EverythingIterator> {
         let encoded = vec![0 as u8; MAX_ENCODED_SIZE];
         let index_has_offsets = field_info.index_options.has_offsets();
@@ -1205,6 +1259,7 @@ impl<'a> EverythingIterator {
             skipped: false,
             total_term_freq: 0,
             for_util,
+            version,
         };
 
         iterator.reset(term_state, flags)?;
@@ -1713,6 +1768,7 @@ impl DocIterator for EverythingIterator {
                     true,
                     self.index_has_offsets,
                     self.index_has_payloads,
+                    self.version >= VERSION_IMPACT_SKIP_DATA,
                 ));
             }
 
@@ -1906,3 +1962,69 @@ impl DocIterator for Lucene50PostingIterEnum {
         }
     }
 }
+
+/// `ImpactsEnum` over the postings of a term that has skip data. The impacts
+/// are read with a skipper of its own, so that shallow advancing doesn't
+/// move the postings.
+pub struct BlockImpactsEnum {
+    postings: Lucene50PostingIterEnum,
+    skipper: Lucene50SkipReader,
+    /// the last doc of the block whose impacts were loaded
+    next_skip_doc: DocId,
+}
+
+impl ImpactsSource for BlockImpactsEnum {
+    fn advance_shallow(&mut self, target: DocId) -> Result<()> {
+        if target > self.next_skip_doc {
+            self.skipper.skip_to(target)?;
+            self.next_skip_doc = self.skipper.next_skip_doc();
+        }
+        Ok(())
+    }
+
+    fn impacts(&mut self) -> Result<Impacts> {
+        let doc = self.postings.doc_id();
+        self.advance_shallow(doc)?;
+        self.skipper.impacts()
+    }
+}
+
+impl PostingIterator for BlockImpactsEnum {
+    fn freq(&self) -> Result<i32> {
+        self.postings.freq()
+    }
+
+    fn next_position(&mut self) -> Result<i32> {
+        self.postings.next_position()
+    }
+
+    fn start_offset(&self) -> Result<i32> {
+        self.postings.start_offset()
+    }
+
+    fn end_offset(&self) -> Result<i32> {
+        self.postings.end_offset()
+    }
+
+    fn payload(&self) -> Result<Payload> {
+        self.postings.payload()
+    }
+}
+
+impl DocIterator for BlockImpactsEnum {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.postings.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.postings.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+}
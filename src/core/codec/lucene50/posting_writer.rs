@@ -17,11 +17,11 @@ use core::codec::lucene50::posting_reader::*;
 use core::codec::lucene50::skip_writer::Lucene50SkipWriter;
 use core::codec::lucene50::util::*;
 use core::codec::writer::PostingsWriterBase;
-use core::codec::{BlockTermState, Codec};
-use core::index::TermIterator;
+use core::codec::{BlockTermState, Codec, CompetitiveImpactAccumulator};
 use core::index::INDEX_MAX_POSITION;
 use core::index::{segment_file_name, SegmentWriteState};
 use core::index::{FieldInfo, IndexOptions};
+use core::index::{NumericDocValues, TermIterator};
 use core::search::posting_iterator::*;
 use core::search::{DocIterator, NO_MORE_DOCS};
 use core::store::{DataOutput, Directory, IndexOutput};
@@ -68,6 +68,8 @@ pub struct Lucene50PostingsWriter<O: IndexOutput> {
     last_start_offset: i32,
     doc_count: i32,
 
+    competitive_freq_norm_accumulator: CompetitiveImpactAccumulator,
+
     encoded: Vec<u8>,
     for_util: ForUtil,
     skip_writer: Lucene50SkipWriter,
@@ -203,6 +205,8 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
             last_start_offset: 0,
             doc_count: 0,
 
+            competitive_freq_norm_accumulator: CompetitiveImpactAccumulator::new(),
+
             encoded: vec![0u8; MAX_ENCODED_SIZE],
             for_util,
             skip_writer,
@@ -262,20 +266,24 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
         }
         self.last_doc_id = 0;
         self.last_block_doc_id = -1;
+        self.competitive_freq_norm_accumulator.clear();
+        // the pos and pay files only exist if a field of the segment has
+        // positions, resp. offsets or payloads
         self.skip_writer.reset_skip(
             self.doc_out.file_pointer(),
-            self.pos_out.as_ref().unwrap().file_pointer(),
-            self.pay_out.as_ref().unwrap().file_pointer(),
+            self.pos_out.as_ref().map_or(0, |out| out.file_pointer()),
+            self.pay_out.as_ref().map_or(0, |out| out.file_pointer()),
         );
     }
 
-    pub fn start_doc(&mut self, doc_id: DocId, term_doc_freq: i32) -> Result<()> {
+    pub fn start_doc(&mut self, doc_id: DocId, term_doc_freq: i32, norm: i64) -> Result<()> {
         // Have collected a block of docs, and get a new doc.
         // Should write skip data as well as postings list for
         // current block.
         if self.last_block_doc_id != -1 && self.doc_buffer_upto == 0 {
             self.skip_writer.buffer_skip(
                 self.last_block_doc_id,
+                &self.competitive_freq_norm_accumulator,
                 self.doc_count as u32,
                 self.last_block_pos_fp,
                 self.last_block_pay_fp,
@@ -283,6 +291,7 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
                 self.last_block_payload_byte_upto,
                 self.doc_out.file_pointer(),
             )?;
+            self.competitive_freq_norm_accumulator.clear();
         }
 
         let doc_delta = doc_id - self.last_doc_id;
@@ -299,6 +308,9 @@ impl<O: IndexOutput> Lucene50PostingsWriter<O> {
         self.doc_buffer_upto += 1;
         self.doc_count += 1;
 
+        let freq = if self.write_freqs { term_doc_freq } else { 1 };
+        self.competitive_freq_norm_accumulator.add(freq, norm);
+
         if self.doc_buffer_upto == BLOCK_SIZE as usize {
             self.for_util.write_block(
                 &self.doc_delta_buffer,
@@ -577,6 +589,7 @@ impl<O: IndexOutput> PostingsWriterBase for Lucene50PostingsWriter<O> {
         _term: &[u8],
         terms: &mut impl TermIterator,
         docs_seen: &mut FixedBitSet,
+        norms: Option<&dyn NumericDocValues>,
     ) -> Result<Option<BlockTermState>> {
         self.start_term();
         let mut postings_enum = terms.postings_with_flags(self.enum_flags)?;
@@ -598,7 +611,12 @@ impl<O: IndexOutput> PostingsWriterBase for Lucene50PostingsWriter<O> {
                 -1
             };
 
-            self.start_doc(doc_id, freq)?;
+            // fields that omit norms are scored as if all docs had the same norm
+            let norm = match norms {
+                Some(norms) => norms.get(doc_id)?,
+                None => 1,
+            };
+            self.start_doc(doc_id, freq, norm)?;
 
             if self.write_positions {
                 for _ in 0..freq {
@@ -13,8 +13,11 @@
 
 use std::io;
 use std::io::Read;
+use std::mem;
 
 use core::codec::lucene50::posting_format::BLOCK_SIZE;
+use core::search::posting_iterator::{Impact, Impacts};
+use core::search::NO_MORE_DOCS;
 use core::store::DataInput;
 use core::store::IndexInput;
 use core::store::RandomAccessInput;
@@ -177,6 +180,11 @@ pub struct Lucene50SkipReader {
     last_payload_byte_upto: i32,
     last_doc_pointer: i64,
     last_pos_buffer_upto: i32,
+
+    /// whether the skip entries record the competitive impacts of their docs
+    has_impacts: bool,
+    /// encoded competitive impacts of current skip entry per level.
+    impact_data: Vec<Vec<u8>>,
 }
 
 impl Lucene50SkipReader {
@@ -218,6 +226,9 @@ impl Lucene50SkipReader {
             last_payload_byte_upto: self.last_payload_byte_upto,
             last_doc_pointer: self.last_doc_pointer,
             last_pos_buffer_upto: self.last_pos_buffer_upto,
+
+            has_impacts: self.has_impacts,
+            impact_data: self.impact_data.clone(),
         })
     }
 
@@ -227,6 +238,7 @@ impl Lucene50SkipReader {
         has_pos: bool,
         has_offsets: bool,
         has_payloads: bool,
+        has_impacts: bool,
     ) -> Lucene50SkipReader {
         // fields for MultiLevelSkipReader part
         let max_number_of_skip_levels = max_skip_levels;
@@ -297,6 +309,9 @@ impl Lucene50SkipReader {
             last_payload_byte_upto: 0,
             last_doc_pointer: 0,
             last_pos_buffer_upto: 0,
+
+            has_impacts,
+            impact_data: vec![Vec::new(); max_skip_levels],
         }
     }
 
@@ -328,6 +343,9 @@ impl Lucene50SkipReader {
         self.skip_doc.iter_mut().map(|x| *x = 0).count();
         self.num_skipped.iter_mut().map(|x| *x = 0).count();
         self.child_pointer.iter_mut().map(|x| *x = 0).count();
+        for data in &mut self.impact_data {
+            data.clear();
+        }
 
         for i in 1..self.number_of_skip_levels as usize {
             self.skip_stream[i] = None;
@@ -383,6 +401,45 @@ impl Lucene50SkipReader {
         self.skip_doc[0]
     }
 
+    /// Returns the competitive impacts of the skip entries the last call of
+    /// `skip_to` has loaded, one level per skip level. Once the skip data is
+    /// exhausted, returns impacts that match anything up to `NO_MORE_DOCS`.
+    pub fn impacts(&self) -> Result<Impacts> {
+        if !self.has_impacts || self.number_of_skip_levels <= 0 {
+            return Ok(Impacts::dummy());
+        }
+        let mut levels = Vec::with_capacity(self.number_of_skip_levels as usize);
+        for level in 0..self.number_of_skip_levels as usize {
+            let impacts = if self.impact_data[level].is_empty() {
+                vec![Impact::new(i32::max_value(), 1)]
+            } else {
+                Self::read_impacts(&self.impact_data[level])?
+            };
+            levels.push((self.skip_doc[level], impacts));
+        }
+        if levels[levels.len() - 1].0 != NO_MORE_DOCS {
+            // the docs after the last skip entry have no impacts
+            levels.push((NO_MORE_DOCS, vec![Impact::new(i32::max_value(), 1)]));
+        }
+        Ok(Impacts::new(levels))
+    }
+
+    fn read_impacts(mut data: &[u8]) -> Result<Vec<Impact>> {
+        let mut impacts = Vec::new();
+        let mut freq = 0;
+        let mut norm = 0i64;
+        while !data.is_empty() {
+            let freq_delta = data.read_vint()?;
+            freq += 1 + (freq_delta as u32 >> 1) as i32;
+            norm = norm.wrapping_add(1);
+            if (freq_delta & 0x01) != 0 {
+                norm = norm.wrapping_add(data.read_zlong()?);
+            }
+            impacts.push(Impact::new(freq, norm));
+        }
+        Ok(impacts)
+    }
+
     /// Seeks the skip entry on the given level
     pub fn seek_child(&mut self, level: i32) -> Result<()> {
         let ulevel = level as usize;
@@ -451,6 +508,14 @@ impl Lucene50SkipReader {
                 self.pay_pointer.as_mut().unwrap()[level] += pointer;
             }
         }
+
+        if self.has_impacts {
+            let length = self.stream(level)?.read_vint()? as usize;
+            let mut data = mem::replace(&mut self.impact_data[level], Vec::new());
+            data.resize(length, 0);
+            self.stream(level)?.read_exact(&mut data)?;
+            self.impact_data[level] = data;
+        }
         Ok(delta)
     }
 
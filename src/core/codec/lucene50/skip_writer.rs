@@ -13,6 +13,8 @@
 
 use std::cmp::min;
 
+use core::codec::CompetitiveImpactAccumulator;
+use core::search::posting_iterator::Impact;
 use core::store::{DataOutput, IndexOutput, RAMOutputStream};
 use core::util::fill_slice;
 use core::util::math;
@@ -38,6 +40,7 @@ const SKIP_MULTIPLIER: u32 = 8;
 /// 2. its related file points(position, payload),
 /// 3. related numbers or uptos(position, payload).
 /// 4. start offset.
+/// 5. the competitive (freq, norm) pairs of the docs up to this skip point.
 pub struct Lucene50SkipWriter {
     last_skip_doc: Vec<i32>,
     last_skip_doc_pointer: Vec<i64>,
@@ -53,6 +56,8 @@ pub struct Lucene50SkipWriter {
     cur_pay_pointer: i64,
     cur_pos_buffer_upto: usize,
     cur_payload_byte_upto: usize,
+    cur_competitive_freq_norms: Vec<CompetitiveImpactAccumulator>,
+    freq_norm_out: RAMOutputStream,
     field_has_positions: bool,
     field_has_offsets: bool,
     field_has_payloads: bool,
@@ -113,6 +118,8 @@ impl Lucene50SkipWriter {
             cur_pay_pointer: 0,
             cur_pos_buffer_upto: 0,
             cur_payload_byte_upto: 0,
+            cur_competitive_freq_norms: vec![CompetitiveImpactAccumulator::new(); max_skip_levels],
+            freq_norm_out: RAMOutputStream::new(false),
             field_has_positions: false,
             field_has_offsets: false,
             field_has_payloads: false,
@@ -160,6 +167,9 @@ impl Lucene50SkipWriter {
                     fill_slice(&mut self.last_skip_pay_pointer, self.last_pay_fp);
                 }
             }
+            for acc in &mut self.cur_competitive_freq_norms {
+                acc.clear();
+            }
             self.initialized = true;
         }
     }
@@ -187,6 +197,7 @@ impl Lucene50SkipWriter {
     pub fn buffer_skip(
         &mut self,
         doc: DocId,
+        competitive_freq_norms: &CompetitiveImpactAccumulator,
         num_docs: u32,
         pos_fp: i64,
         pay_fp: i64,
@@ -201,6 +212,7 @@ impl Lucene50SkipWriter {
         self.cur_pay_pointer = pay_fp;
         self.cur_pos_buffer_upto = pos_buffer_upto;
         self.cur_payload_byte_upto = payload_byte_upto;
+        self.cur_competitive_freq_norms[0].add_all(competitive_freq_norms);
         self.buffer_skip_levels(num_docs)
     }
 
@@ -285,6 +297,41 @@ impl Lucene50SkipWriter {
             }
         }
 
+        debug_assert!(!self.cur_competitive_freq_norms[level].is_empty());
+        if level + 1 < self.number_of_skip_levels {
+            let (lower, upper) = self.cur_competitive_freq_norms.split_at_mut(level + 1);
+            upper[0].add_all(&lower[level]);
+        }
+        Self::write_impacts(
+            &self.cur_competitive_freq_norms[level],
+            &mut self.freq_norm_out,
+        )?;
+        self.skip_buffer[level].write_vint(self.freq_norm_out.file_pointer() as i32)?;
+        self.freq_norm_out.write_to(&mut self.skip_buffer[level])?;
+        self.freq_norm_out.reset();
+        self.cur_competitive_freq_norms[level].clear();
+
+        Ok(())
+    }
+
+    /// Writes the competitive (freq, norm) pairs, both delta-coded against the
+    /// previous pair minus one since they are usually strictly increasing.
+    fn write_impacts(acc: &CompetitiveImpactAccumulator, out: &mut impl DataOutput) -> Result<()> {
+        let mut previous = Impact::new(0, 0);
+        for impact in acc.competitive_freq_norm_pairs() {
+            debug_assert!(impact.freq > previous.freq);
+            let freq_delta = impact.freq - previous.freq - 1;
+            let norm_delta = impact.norm.wrapping_sub(previous.norm).wrapping_sub(1);
+            if norm_delta == 0 {
+                // most of time, norm only increases by 1, so we can fold everything in a single
+                // byte
+                out.write_vint(freq_delta << 1)?;
+            } else {
+                out.write_vint((freq_delta << 1) | 1)?;
+                out.write_zlong(norm_delta)?;
+            }
+            previous = impact;
+        }
         Ok(())
     }
 }
@@ -14,6 +14,8 @@
 use std::str::FromStr;
 use std::sync::Arc;
 
+#[cfg(feature = "zstd")]
+use core::codec::compressing::ZSTD_DEFAULT_LEVEL;
 use core::codec::compressing::{
     CompressingStoredFieldsFormat, CompressingStoredFieldsReader, CompressionMode,
 };
@@ -31,15 +33,44 @@ const MODE_KEY: &str = "Lucene50StoredFieldsFormat.mode";
 pub enum StoredFieldCompressMode {
     BestSpeed,
     BestCompression,
+    /// zstd with the given level and a preset dictionary per chunk, for
+    /// read-mostly archival indexes.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+    /// The `CustomCompression` registered under this name, with the chunks of
+    /// `BestCompression`.
+    Custom(&'static str),
+}
+
+impl StoredFieldCompressMode {
+    /// The name recorded in the segment attributes, the level of zstd is
+    /// not needed to read the segment back.
+    pub fn name(&self) -> &'static str {
+        match self {
+            StoredFieldCompressMode::BestSpeed => "BEST_SPEED",
+            StoredFieldCompressMode::BestCompression => "BEST_COMPRESSION",
+            #[cfg(feature = "zstd")]
+            StoredFieldCompressMode::Zstd(_) => "ZSTD",
+            StoredFieldCompressMode::Custom(name) => name,
+        }
+    }
 }
 
 impl FromStr for StoredFieldCompressMode {
     type Err = CoreError;
     fn from_str(v: &str) -> Result<Self> {
-        let r = if v == "BEST_SPEED" {
-            StoredFieldCompressMode::BestSpeed
-        } else {
-            StoredFieldCompressMode::BestCompression
+        let r = match v {
+            "BEST_SPEED" => StoredFieldCompressMode::BestSpeed,
+            "BEST_COMPRESSION" => StoredFieldCompressMode::BestCompression,
+            #[cfg(feature = "zstd")]
+            "ZSTD" => StoredFieldCompressMode::Zstd(ZSTD_DEFAULT_LEVEL),
+            _ => match CompressionMode::for_name(v) {
+                Some(CompressionMode::Custom(name)) => StoredFieldCompressMode::Custom(name),
+                _ => bail!(IllegalState(format!(
+                    "unknown stored fields compression mode: {}",
+                    v
+                ))),
+            },
         };
         Ok(r)
     }
@@ -47,7 +78,6 @@ impl FromStr for StoredFieldCompressMode {
 
 #[derive(Copy, Clone)]
 pub struct Lucene50StoredFieldsFormat {
-    #[allow(dead_code)]
     mode: StoredFieldCompressMode,
 }
 
@@ -80,6 +110,23 @@ impl Lucene50StoredFieldsFormat {
                 512,
                 1024,
             ),
+            #[cfg(feature = "zstd")]
+            StoredFieldCompressMode::Zstd(level) => CompressingStoredFieldsFormat::new(
+                "Lucene50StoredFieldsZstd",
+                "",
+                CompressionMode::Zstd(*level),
+                61440,
+                512,
+                1024,
+            ),
+            StoredFieldCompressMode::Custom(name) => CompressingStoredFieldsFormat::new(
+                "Lucene50StoredFieldsCustom",
+                "",
+                CompressionMode::Custom(name),
+                61440,
+                512,
+                1024,
+            ),
         }
     }
 }
@@ -126,7 +173,7 @@ impl StoredFieldsFormat for Lucene50StoredFieldsFormat {
         }
 
         si.attributes
-            .insert(MODE_KEY.to_string(), "BEST_SPEED".to_string());
+            .insert(MODE_KEY.to_string(), self.mode.name().to_string());
         self.format(&self.mode).fields_writer(directory, si, ioctx)
     }
 }
@@ -115,6 +115,7 @@ impl DocValuesFormat for Lucene54DocValuesFormat {
             Self::DATA_EXTENSION,
             Self::META_CODEC,
             Self::META_EXTENSION,
+            false,
         )?;
         Ok(Box::new(boxed))
     }
@@ -130,6 +131,7 @@ impl DocValuesFormat for Lucene54DocValuesFormat {
                 Self::DATA_EXTENSION,
                 Self::META_CODEC,
                 Self::META_EXTENSION,
+                false,
             )?,
         ))
     }
@@ -14,8 +14,12 @@
 use core::codec::codec_util;
 use core::codec::consumer::{is_single_valued, singleton_view};
 use core::codec::lucene54::{Lucene54DocValuesFormat, NumberType};
-use core::codec::{Codec, DocValuesConsumer};
-use core::index::{segment_file_name, FieldInfo, SegmentWriteState};
+use core::codec::lucene70::IndexedDISI;
+use core::codec::rucene54::{
+    Rucene54DocValuesFormat, BINARY_AUTO_COMPRESSION, BINARY_COMPRESSION_KEY,
+};
+use core::codec::{Codec, Compress, CompressionMode, Compressor, DocValuesConsumer};
+use core::index::{segment_file_name, DocValuesType, FieldInfo, SegmentWriteState};
 use core::store::{DataOutput, IndexOutput};
 use core::store::{Directory, RAMOutputStream};
 use core::util::math;
@@ -24,9 +28,9 @@ use core::util::packed::{AbstractBlockPackedWriter, MonotonicBlockPackedWriter};
 use core::util::packed::{DirectMonotonicWriter, DirectWriter};
 use core::util::packed_misc::VERSION_CURRENT as PACKED_VERSION_CURRENT;
 use core::util::string_util::{bytes_difference, sort_key_length};
-use core::util::{BytesRef, PagedBytes, ReusableIterator};
+use core::util::{BytesRef, DocId, PagedBytes, ReusableIterator};
 
-use error::Result;
+use error::{ErrorKind::IllegalArgument, Result};
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::hash::{Hash, Hasher};
@@ -35,6 +39,8 @@ pub struct Lucene54DocValuesConsumer<O: IndexOutput> {
     data: O,
     meta: O,
     max_doc: i32,
+    // whether the entry formats of `Rucene54DocValuesFormat` may be written
+    extended: bool,
 }
 
 impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
@@ -44,7 +50,13 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         data_extension: &str,
         meta_codec: &str,
         meta_extension: &str,
+        extended: bool,
     ) -> Result<Self> {
+        let version = if extended {
+            Rucene54DocValuesFormat::VERSION_CURRENT
+        } else {
+            Lucene54DocValuesFormat::VERSION_CURRENT
+        };
         let data_name = segment_file_name(
             &state.segment_info.name,
             &state.segment_suffix,
@@ -54,7 +66,7 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         codec_util::write_index_header(
             &mut data,
             data_codec,
-            Lucene54DocValuesFormat::VERSION_CURRENT,
+            version,
             state.segment_info.get_id(),
             &state.segment_suffix,
         )?;
@@ -68,7 +80,7 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         codec_util::write_index_header(
             &mut meta,
             meta_codec,
-            Lucene54DocValuesFormat::VERSION_CURRENT,
+            version,
             state.segment_info.get_id(),
             &state.segment_suffix,
         )?;
@@ -79,6 +91,7 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
             data,
             meta,
             max_doc,
+            extended,
         })
     }
 }
@@ -183,6 +196,11 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
             NumberType::ORDINAL => (missing_ord_count as f64 / count as f64) >= 0.99,
         };
 
+        let num_docs_with_value: i64 = match number_type {
+            NumberType::VALUE => count - missing_count,
+            NumberType::ORDINAL => count - missing_ord_count,
+        };
+
         let format = if unique_values.is_some()
             && count as i32 <= i32::max_value()
             && (unique_values.as_ref().unwrap().len() == 1
@@ -195,6 +213,8 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         } else if sparse && count >= 1024 {
             // require at least 1024 docs to avoid flipping back and forth when doing NRT search
             Lucene54DocValuesFormat::SPARSE_COMPRESSED
+        } else if self.is_sparse(count, num_docs_with_value) {
+            Rucene54DocValuesFormat::DISI_COMPRESSED
         } else if unique_values.is_some() && table_bits_required < delta_bits_required {
             Lucene54DocValuesFormat::TABLE_COMPRESSED
         } else if gcd != 0 && gcd != 1 {
@@ -215,14 +235,28 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         self.meta.write_vint(format)?;
         if format == Lucene54DocValuesFormat::SPARSE_COMPRESSED {
             self.meta.write_long(self.data.file_pointer())?;
-            let num_docs_with_value: i64 = match number_type {
-                NumberType::VALUE => count - missing_count,
-                NumberType::ORDINAL => count - missing_ord_count,
-            };
             values.reset();
             let max_doc =
                 self.write_sparse_missing_bitset(values, number_type, num_docs_with_value)?;
             debug_assert!(max_doc == count);
+        } else if format == Rucene54DocValuesFormat::DISI_COMPRESSED {
+            self.meta.write_long(self.data.file_pointer())?;
+            self.meta.write_vlong(num_docs_with_value)?;
+            values.reset();
+            let mut docs = Vec::with_capacity(num_docs_with_value as usize);
+            let mut doc: DocId = 0;
+            for nv in &mut *values {
+                let nv = nv?;
+                let has_value = match number_type {
+                    NumberType::VALUE => !nv.is_null(),
+                    NumberType::ORDINAL => nv.long_value() != -1,
+                };
+                if has_value {
+                    docs.push(doc);
+                }
+                doc += 1;
+            }
+            IndexedDISI::write_docs(docs.into_iter(), &mut self.data)?;
         } else if missing_count == 0 {
             self.meta
                 .write_long(Lucene54DocValuesFormat::ALL_LIVE as i64)?;
@@ -301,7 +335,8 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
                     ords_writer.finish()?;
                 }
             }
-            Lucene54DocValuesFormat::SPARSE_COMPRESSED => {
+            Lucene54DocValuesFormat::SPARSE_COMPRESSED
+            | Rucene54DocValuesFormat::DISI_COMPRESSED => {
                 match number_type {
                     NumberType::VALUE => {
                         self.meta.write_byte(0)?;
@@ -330,6 +365,15 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         self.meta.write_long(self.data.file_pointer())
     }
 
+    // whether to only store the values of the docs that have one, along with
+    // the set of these docs
+    fn is_sparse(&self, count: i64, num_docs_with_value: i64) -> bool {
+        self.extended
+            && count >= Rucene54DocValuesFormat::SPARSE_MIN_DOCS
+            && num_docs_with_value > 0
+            && num_docs_with_value * 2 <= count
+    }
+
     // TODO: in some cases representing missing with minValue-1 wouldn't take up additional space
     // and so on, but this is very simple, and algorithms only check this for values of 0
     // anyway (doesnt slow down normal decode)
@@ -626,6 +670,326 @@ impl<O: IndexOutput> Lucene54DocValuesConsumer<O> {
         Ok(())
     }
 
+    /// Writes the values of every doc, a doc without a value having an empty
+    /// one.
+    fn add_dense_binary(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+    ) -> Result<()> {
+        // the terms of sorted fields are also written here, they are never
+        // compressed by blocks
+        if field_info.doc_values_type == DocValuesType::Binary {
+            let compression = field_info.attribute(BINARY_COMPRESSION_KEY);
+            if compression.is_some() && !self.extended {
+                bail!(IllegalArgument(format!(
+                    "binary doc values of field {} can only be compressed by the {} format",
+                    field_info.name,
+                    Rucene54DocValuesFormat::NAME
+                )));
+            }
+            let mode = match compression {
+                Some(ref name) if name == BINARY_AUTO_COMPRESSION => {
+                    let mode = Self::auto_binary_compression(values)?;
+                    values.reset();
+                    mode
+                }
+                Some(name) => Some(CompressionMode::for_name(&name).ok_or_else(|| {
+                    IllegalArgument(format!(
+                        "unknown compression mode {} for field {}",
+                        name, field_info.name
+                    ))
+                })?),
+                None => None,
+            };
+            if let Some(mode) = mode {
+                return self.add_block_compressed_binary(field_info, values, mode);
+            }
+        }
+        // write the byte[] data
+        self.meta.write_vint(field_info.number as i32)?;
+        self.meta.write_byte(Lucene54DocValuesFormat::BINARY)?;
+        let mut min_length = i32::max_value();
+        let mut max_length = i32::min_value();
+        let start_fp = self.data.file_pointer();
+        let mut count = 0i64;
+        let mut missing_count = 0i64;
+
+        loop {
+            let v = match values.next() {
+                None => {
+                    break;
+                }
+                Some(r) => r?,
+            };
+            let length = v.len() as i32;
+            if length == 0 {
+                missing_count += 1;
+            }
+
+            min_length = length.min(min_length);
+            max_length = length.max(max_length);
+            if length > 0 {
+                self.data.write_bytes(v.bytes(), 0, length as usize)?;
+            }
+
+            count += 1;
+        }
+
+        let v = if min_length == max_length {
+            Lucene54DocValuesFormat::BINARY_FIXED_UNCOMPRESSED
+        } else {
+            Lucene54DocValuesFormat::BINARY_VARIABLE_UNCOMPRESSED
+        };
+        self.meta.write_vint(v)?;
+        if missing_count == 0 {
+            self.meta
+                .write_long(Lucene54DocValuesFormat::ALL_LIVE as i64)?;
+        } else if missing_count == count {
+            self.meta
+                .write_long(Lucene54DocValuesFormat::ALL_MISSING as i64)?;
+        } else {
+            self.meta.write_long(self.data.file_pointer())?;
+            values.reset();
+            self.write_missing_bitset_bytes(values)?;
+        }
+
+        self.meta.write_vint(min_length)?;
+        self.meta.write_vint(max_length)?;
+        self.meta.write_vlong(count)?;
+        self.meta.write_long(start_fp)?;
+
+        // if minLength == maxLength, it's a fixed-length byte[], we are done (the addresses are
+        // implicit) otherwise, we need to record the length fields...
+
+        if min_length != max_length {
+            self.meta.write_long(self.data.file_pointer())?;
+            self.meta
+                .write_vint(Lucene54DocValuesFormat::DIRECT_MONOTONIC_BLOCK_SHIFT)?;
+
+            {
+                let mut writer = DirectMonotonicWriter::get_instance(
+                    &mut self.meta,
+                    &mut self.data,
+                    count + 1,
+                    Lucene54DocValuesFormat::DIRECT_MONOTONIC_BLOCK_SHIFT,
+                )?;
+                let mut addr = 0;
+                writer.add(addr)?;
+                values.reset();
+                for v in values {
+                    let v = v?;
+                    if v.len() > 0 {
+                        addr += v.len() as i64;
+                    }
+                    writer.add(addr)?;
+                }
+                writer.finish()?;
+            }
+            self.meta.write_long(self.data.file_pointer())?;
+        }
+        Ok(())
+    }
+
+    /// Writes the docs that have a value in an `IndexedDISI`, followed by the
+    /// values of these docs only as a dense binary entry.
+    fn add_sparse_binary(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+        num_docs_with_value: i64,
+    ) -> Result<()> {
+        self.meta.write_vint(field_info.number as i32)?;
+        self.meta.write_byte(Lucene54DocValuesFormat::BINARY)?;
+        self.meta
+            .write_vint(Rucene54DocValuesFormat::BINARY_SPARSE)?;
+        self.meta.write_long(self.data.file_pointer())?;
+        self.meta.write_vlong(num_docs_with_value)?;
+        let mut docs = Vec::with_capacity(num_docs_with_value as usize);
+        let mut doc: DocId = 0;
+        for v in &mut *values {
+            if v?.len() > 0 {
+                docs.push(doc);
+            }
+            doc += 1;
+        }
+        IndexedDISI::write_docs(docs.into_iter(), &mut self.data)?;
+        self.meta.write_long(self.data.file_pointer())?;
+
+        values.reset();
+        let predicate = |v: &Result<BytesRef>| match v {
+            Ok(v) => v.len() > 0,
+            Err(_) => true,
+        };
+        self.add_dense_binary(field_info, &mut ReusableIterFilter::new(values, predicate))
+    }
+
+    /// Writes the values by blocks of `2^BINARY_BLOCK_SHIFT` values, each
+    /// block holds the number and the lengths of its values followed by their
+    /// compressed bytes, and the start of each block is addressed with a
+    /// `DirectMonotonicWriter`, so a value is read by decompressing its block.
+    fn add_block_compressed_binary(
+        &mut self,
+        field_info: &FieldInfo,
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+        mode: CompressionMode,
+    ) -> Result<()> {
+        self.meta.write_vint(field_info.number as i32)?;
+        self.meta.write_byte(Lucene54DocValuesFormat::BINARY)?;
+        self.meta
+            .write_vint(Rucene54DocValuesFormat::BINARY_BLOCK_COMPRESSED)?;
+
+        let mut min_length = i32::max_value();
+        let mut max_length = i32::min_value();
+        let mut count = 0i64;
+        let mut missing_count = 0i64;
+        for v in &mut *values {
+            let length = v?.len() as i32;
+            if length == 0 {
+                missing_count += 1;
+            }
+            min_length = length.min(min_length);
+            max_length = length.max(max_length);
+            count += 1;
+        }
+
+        if missing_count == 0 {
+            self.meta
+                .write_long(Lucene54DocValuesFormat::ALL_LIVE as i64)?;
+        } else if missing_count == count {
+            self.meta
+                .write_long(Lucene54DocValuesFormat::ALL_MISSING as i64)?;
+        } else {
+            self.meta.write_long(self.data.file_pointer())?;
+            values.reset();
+            self.write_missing_bitset_bytes(values)?;
+        }
+        self.meta.write_vint(min_length)?;
+        self.meta.write_vint(max_length)?;
+        self.meta.write_vlong(count)?;
+        let start_fp = self.data.file_pointer();
+        self.meta.write_long(start_fp)?;
+
+        let block_size = 1usize << Rucene54DocValuesFormat::BINARY_BLOCK_SHIFT;
+        let mut compressor = mode.new_compressor()?;
+        let mut block_addresses = Vec::with_capacity(count as usize / block_size + 2);
+        let mut lengths = Vec::with_capacity(block_size);
+        let mut buffer = Vec::new();
+        values.reset();
+        for v in &mut *values {
+            let v = v?;
+            lengths.push(v.len() as i32);
+            buffer.extend_from_slice(v.bytes());
+            if lengths.len() == block_size {
+                block_addresses.push(self.data.file_pointer() - start_fp);
+                self.write_binary_block(&mut compressor, &mut lengths, &mut buffer)?;
+            }
+        }
+        if !lengths.is_empty() {
+            block_addresses.push(self.data.file_pointer() - start_fp);
+            self.write_binary_block(&mut compressor, &mut lengths, &mut buffer)?;
+        }
+        block_addresses.push(self.data.file_pointer() - start_fp);
+
+        self.meta.write_byte(match mode {
+            CompressionMode::FAST => 0,
+            CompressionMode::HighCompression => 1,
+            #[cfg(feature = "zstd")]
+            CompressionMode::Zstd(_) => 2,
+            CompressionMode::Custom(_) => 3,
+        })?;
+        if let CompressionMode::Custom(name) = mode {
+            self.meta.write_string(name)?;
+        }
+        self.meta
+            .write_vint(Rucene54DocValuesFormat::BINARY_BLOCK_SHIFT)?;
+        self.meta.write_long(self.data.file_pointer())?;
+        self.meta
+            .write_vint(Lucene54DocValuesFormat::DIRECT_MONOTONIC_BLOCK_SHIFT)?;
+        {
+            let mut writer = DirectMonotonicWriter::get_instance(
+                &mut self.meta,
+                &mut self.data,
+                block_addresses.len() as i64,
+                Lucene54DocValuesFormat::DIRECT_MONOTONIC_BLOCK_SHIFT,
+            )?;
+            for address in block_addresses {
+                writer.add(address)?;
+            }
+            writer.finish()?;
+        }
+        self.meta.write_long(self.data.file_pointer())?;
+        Ok(())
+    }
+
+    /// Chooses to compress the binary values of a field with auto compression
+    /// by blocks with LZ4 if they are large and repetitive, e.g. JSON
+    /// documents, by compressing the first blocks of values like
+    /// `add_block_compressed_binary` would. Only the values of the sample
+    /// are read.
+    fn auto_binary_compression(
+        values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
+    ) -> Result<Option<CompressionMode>> {
+        let block_size = 1usize << Rucene54DocValuesFormat::BINARY_BLOCK_SHIFT;
+        let mut count = 0i64;
+        let mut total_length = 0i64;
+        let mut compressor = CompressionMode::FAST.new_compressor()?;
+        let mut num_values = 0;
+        let mut buffer = Vec::new();
+        let mut sample_length = 0usize;
+        let mut compressed = Vec::new();
+        for v in &mut *values {
+            let v = v?;
+            if !v.is_empty() {
+                count += 1;
+                total_length += v.len() as i64;
+            }
+            buffer.extend_from_slice(v.bytes());
+            num_values += 1;
+            if num_values == block_size {
+                sample_length += buffer.len();
+                compressor.compress(&buffer, 0, buffer.len(), &mut compressed)?;
+                num_values = 0;
+                buffer.clear();
+                if sample_length >= Rucene54DocValuesFormat::BINARY_AUTO_COMPRESSION_SAMPLE_SIZE {
+                    break;
+                }
+            }
+        }
+        if !buffer.is_empty() {
+            sample_length += buffer.len();
+            compressor.compress(&buffer, 0, buffer.len(), &mut compressed)?;
+        }
+
+        if count == 0
+            || total_length / count < Rucene54DocValuesFormat::BINARY_AUTO_COMPRESSION_MIN_LENGTH
+            || compressed.len() * 4 > sample_length * 3
+        {
+            Ok(None)
+        } else {
+            Ok(Some(CompressionMode::FAST))
+        }
+    }
+
+    fn write_binary_block(
+        &mut self,
+        compressor: &mut Compressor,
+        lengths: &mut Vec<i32>,
+        buffer: &mut Vec<u8>,
+    ) -> Result<()> {
+        self.data.write_vint(lengths.len() as i32)?;
+        for &length in lengths.iter() {
+            self.data.write_vint(length)?;
+        }
+        // blocks of missing values have no bytes to compress
+        if !buffer.is_empty() {
+            compressor.compress(buffer, 0, buffer.len(), &mut self.data)?;
+        }
+        lengths.clear();
+        buffer.clear();
+        Ok(())
+    }
+
     /// writes reverse term index: used for binary searching a term into a range of 64 blocks
     /// for every 64 blocks (1024 terms) we store a term, trimming any suffix unnecessary for
     /// comparison terms are written as a contiguous byte[], but never spanning 2^15 byte
@@ -804,89 +1168,22 @@ impl<O: IndexOutput> DocValuesConsumer for Lucene54DocValuesConsumer<O> {
         field_info: &FieldInfo,
         values: &mut impl ReusableIterator<Item = Result<BytesRef>>,
     ) -> Result<()> {
-        // write the byte[] data
-        self.meta.write_vint(field_info.number as i32)?;
-        self.meta.write_byte(Lucene54DocValuesFormat::BINARY)?;
-        let mut min_length = i32::max_value();
-        let mut max_length = i32::min_value();
-        let start_fp = self.data.file_pointer();
-        let mut count = 0i64;
-        let mut missing_count = 0i64;
-
-        loop {
-            let v = match values.next() {
-                None => {
-                    break;
+        // the terms of sorted fields are also written here, they are never sparse
+        if field_info.doc_values_type == DocValuesType::Binary {
+            let mut count = 0i64;
+            let mut num_docs_with_value = 0i64;
+            for v in &mut *values {
+                if v?.len() > 0 {
+                    num_docs_with_value += 1;
                 }
-                Some(r) => r?,
-            };
-            let length = v.len() as i32;
-            if length == 0 {
-                missing_count += 1;
-            }
-
-            min_length = length.min(min_length);
-            max_length = length.max(max_length);
-            if length > 0 {
-                self.data.write_bytes(v.bytes(), 0, length as usize)?;
+                count += 1;
             }
-
-            count += 1;
-        }
-
-        let v = if min_length == max_length {
-            Lucene54DocValuesFormat::BINARY_FIXED_UNCOMPRESSED
-        } else {
-            Lucene54DocValuesFormat::BINARY_VARIABLE_UNCOMPRESSED
-        };
-        self.meta.write_vint(v)?;
-        if missing_count == 0 {
-            self.meta
-                .write_long(Lucene54DocValuesFormat::ALL_LIVE as i64)?;
-        } else if missing_count == count {
-            self.meta
-                .write_long(Lucene54DocValuesFormat::ALL_MISSING as i64)?;
-        } else {
-            self.meta.write_long(self.data.file_pointer())?;
             values.reset();
-            self.write_missing_bitset_bytes(values)?;
-        }
-
-        self.meta.write_vint(min_length)?;
-        self.meta.write_vint(max_length)?;
-        self.meta.write_vlong(count)?;
-        self.meta.write_long(start_fp)?;
-
-        // if minLength == maxLength, it's a fixed-length byte[], we are done (the addresses are
-        // implicit) otherwise, we need to record the length fields...
-
-        if min_length != max_length {
-            self.meta.write_long(self.data.file_pointer())?;
-            self.meta
-                .write_vint(Lucene54DocValuesFormat::DIRECT_MONOTONIC_BLOCK_SHIFT)?;
-
-            {
-                let mut writer = DirectMonotonicWriter::get_instance(
-                    &mut self.meta,
-                    &mut self.data,
-                    count + 1,
-                    Lucene54DocValuesFormat::DIRECT_MONOTONIC_BLOCK_SHIFT,
-                )?;
-                let mut addr = 0;
-                writer.add(addr)?;
-                values.reset();
-                for v in values {
-                    let v = v?;
-                    if v.len() > 0 {
-                        addr += v.len() as i64;
-                    }
-                    writer.add(addr)?;
-                }
-                writer.finish()?;
+            if self.is_sparse(count, num_docs_with_value) {
+                return self.add_sparse_binary(field_info, values, num_docs_with_value);
             }
-            self.meta.write_long(self.data.file_pointer())?;
         }
-        Ok(())
+        self.add_dense_binary(field_info, values)
     }
 
     fn add_sorted_field(
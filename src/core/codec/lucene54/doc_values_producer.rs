@@ -11,12 +11,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::{codec_util, Codec, DocValuesProducer, Lucene54DocValuesFormat, NumberType};
+#[cfg(feature = "zstd")]
+use core::codec::ZSTD_DEFAULT_LEVEL;
+use core::codec::{
+    codec_util, Codec, CompressionMode, DocValuesProducer, IndexedDISI, Lucene54DocValuesFormat,
+    NumberType, SparseDISIBinaryValues, SparseDISIValues,
+};
 use core::index::{
     segment_file_name, AddressedRandomAccessOrds, AddressedSortedNumericDocValues, BinaryDocValues,
-    CompressedBinaryDocValues, DocValues, DocValuesType, FieldInfo, FieldInfos,
-    FixedBinaryDocValues, NumericDocValues, SegmentInfo, SegmentReadState, SortedDocValues,
-    SortedNumericDocValues, SortedSetDocValues, TabledRandomAccessOrds,
+    BlockCompressedBinaryDocValues, CompressedBinaryDocValues, DocValues, DocValuesType, FieldInfo,
+    FieldInfos, FixedBinaryDocValues, NumericDocValues, SegmentInfo, SegmentReadState,
+    SortedDocValues, SortedNumericDocValues, SortedSetDocValues, TabledRandomAccessOrds,
     TabledSortedNumericDocValues, TailoredSortedDocValues, VariableBinaryDocValues,
 };
 use core::store::{BufferedChecksumIndexInput, Directory, IndexInput};
@@ -24,8 +29,9 @@ use core::util::{
     packed::{
         DirectMonotonicMeta, DirectMonotonicReader, DirectReader, MonotonicBlockPackedReader,
     },
-    BitsRef, DeltaLongValues, GcdLongValues, LiveBits, LiveLongValues, LongValues, MatchAllBits,
-    MatchNoBits, PagedBytes, PagedBytesReader, SparseBits, SparseLongValues, TableLongValues,
+    Accountable, BitsRef, DeltaLongValues, GcdLongValues, LiveBits, LiveLongValues, LongValues,
+    MatchAllBits, MatchNoBits, PagedBytes, PagedBytesReader, RamUsage, SparseBits,
+    SparseLongValues, TableLongValues,
 };
 
 use error::ErrorKind::{CorruptIndex, IllegalArgument};
@@ -106,6 +112,14 @@ pub struct BinaryEntry {
 
     format: i32,
     addresses_meta: Option<Arc<DirectMonotonicMeta>>,
+
+    // compression and values per block of the block compressed format
+    compression: Option<CompressionMode>,
+    binary_block_shift: i32,
+
+    // the docs with a value and their values of the sparse format
+    num_docs_with_value: i64,
+    non_missing_values: Option<Arc<BinaryEntry>>,
 }
 
 impl Default for BinaryEntry {
@@ -123,6 +137,10 @@ impl Default for BinaryEntry {
             block_size: 0,
             format: 0,
             addresses_meta: None,
+            compression: None,
+            binary_block_shift: 0,
+            num_docs_with_value: 0,
+            non_missing_values: None,
         }
     }
 }
@@ -158,6 +176,7 @@ pub struct Lucene54DocValuesProducer {
     ord_indexes: HashMap<String, NumericEntryLink>,
     address_instances: RwLock<HashMap<String, Arc<MonotonicBlockPackedReader>>>,
     reverse_index_instances: RwLock<HashMap<String, Arc<ReverseTermsIndex>>>,
+    disi_instances: RwLock<HashMap<String, Arc<IndexedDISI>>>,
 }
 
 impl Lucene54DocValuesProducer {
@@ -167,7 +186,19 @@ impl Lucene54DocValuesProducer {
         data_ext: &str,
         meta_codec: &str,
         meta_ext: &str,
+        extended: bool,
     ) -> Result<Lucene54DocValuesProducer> {
+        let (version_start, version_current) = if extended {
+            (
+                Rucene54DocValuesFormat::VERSION_START,
+                Rucene54DocValuesFormat::VERSION_CURRENT,
+            )
+        } else {
+            (
+                Lucene54DocValuesFormat::VERSION_START,
+                Lucene54DocValuesFormat::VERSION_CURRENT,
+            )
+        };
         let meta_name =
             segment_file_name(&state.segment_info.name, &state.segment_suffix, meta_ext);
         // read in the entries from the metadata file
@@ -177,8 +208,8 @@ impl Lucene54DocValuesProducer {
         let version = codec_util::check_index_header(
             &mut checksum_input,
             meta_codec,
-            Lucene54DocValuesFormat::VERSION_START,
-            Lucene54DocValuesFormat::VERSION_CURRENT,
+            version_start,
+            version_current,
             state.segment_info.get_id(),
             &state.segment_suffix,
         )?;
@@ -192,6 +223,7 @@ impl Lucene54DocValuesProducer {
 
         let num_fields: i32 = Lucene54DocValuesProducer::read_fields(
             &mut checksum_input,
+            extended,
             &state.field_infos,
             &state.segment_info,
             &mut numerics,
@@ -209,8 +241,8 @@ impl Lucene54DocValuesProducer {
         let version2 = codec_util::check_index_header(
             data.as_mut(),
             data_codec,
-            Lucene54DocValuesFormat::VERSION_START,
-            Lucene54DocValuesFormat::VERSION_CURRENT,
+            version_start,
+            version_current,
             state.segment_info.get_id(),
             &state.segment_suffix,
         )?;
@@ -238,6 +270,7 @@ impl Lucene54DocValuesProducer {
             ord_indexes,
             address_instances,
             reverse_index_instances,
+            disi_instances: RwLock::new(HashMap::new()),
         })
     }
 
@@ -257,12 +290,14 @@ impl Lucene54DocValuesProducer {
             ord_indexes: producer.ord_indexes.clone(),
             address_instances,
             reverse_index_instances,
+            disi_instances: RwLock::new(producer.disi_instances.read()?.clone()),
         })
     }
 
     #[allow(too_many_arguments)]
     fn read_fields<D: Directory, C: Codec>(
         meta: &mut dyn IndexInput,
+        extended: bool,
         infos: &FieldInfos,
         segment_info: &SegmentInfo<D, C>,
         numerics: &mut HashMap<String, NumericEntryLink>,
@@ -284,8 +319,12 @@ impl Lucene54DocValuesProducer {
             let dv_type = meta.read_byte()?;
             match dv_type {
                 Lucene54DocValuesFormat::NUMERIC => {
-                    let entry =
-                        Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta)?;
+                    let entry = Lucene54DocValuesProducer::read_numeric_entry(
+                        info,
+                        segment_info,
+                        meta,
+                        extended,
+                    )?;
                     match entry {
                         Some(n) => numerics.insert(info.name.clone(), n),
                         _ => unreachable!(),
@@ -293,7 +332,7 @@ impl Lucene54DocValuesProducer {
                 }
 
                 Lucene54DocValuesFormat::BINARY => {
-                    let b = Lucene54DocValuesProducer::read_binary_entry(info, meta)?;
+                    let b = Lucene54DocValuesProducer::read_binary_entry(info, meta, extended)?;
                     binaries.insert(info.name.clone(), b);
                 }
 
@@ -302,6 +341,7 @@ impl Lucene54DocValuesProducer {
                         info,
                         segment_info,
                         meta,
+                        extended,
                         binaries,
                         ords,
                     )?;
@@ -317,6 +357,7 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                                 binaries,
                                 ords,
                                 ord_indexes,
@@ -328,6 +369,7 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                                 binaries,
                                 ords,
                             )?;
@@ -352,6 +394,7 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                                 binaries,
                                 ords,
                             )?;
@@ -385,10 +428,11 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                             )?;
 
                             match entry {
-                                Some(n) => ords.insert(info.name.clone(), n),
+                                Some(n) => numerics.insert(info.name.clone(), n),
                                 _ => unreachable!(),
                             };
 
@@ -410,6 +454,7 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                             )?;
                             match entry {
                                 Some(n) => ord_indexes.insert(info.name.clone(), n),
@@ -435,6 +480,7 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                             )?;
                             match entry {
                                 Some(n) => ords.insert(info.name.clone(), n),
@@ -459,6 +505,7 @@ impl Lucene54DocValuesProducer {
                                 info,
                                 segment_info,
                                 meta,
+                                extended,
                             )? {
                                 numerics.insert(info.name.clone(), n);
                             } else {
@@ -489,11 +536,20 @@ impl Lucene54DocValuesProducer {
         info: &FieldInfo,
         segment_info: &SegmentInfo<D, C>,
         meta: &mut dyn IndexInput,
+        extended: bool,
     ) -> Result<Option<NumericEntryLink>> {
         let mut entry = NumericEntry::new();
         entry.format = meta.read_vint()?;
+        if entry.format == Rucene54DocValuesFormat::DISI_COMPRESSED && !extended {
+            bail!(CorruptIndex(format!(
+                "DISI compressed numeric field {} in a Lucene54 segment",
+                info.name
+            )));
+        }
         entry.missing_offset = meta.read_long()?;
-        if entry.format == Lucene54DocValuesFormat::SPARSE_COMPRESSED {
+        if entry.format == Rucene54DocValuesFormat::DISI_COMPRESSED {
+            entry.num_docs_with_value = meta.read_vlong()?;
+        } else if entry.format == Lucene54DocValuesFormat::SPARSE_COMPRESSED {
             entry.num_docs_with_value = meta.read_vlong()?;
             let block_shift = meta.read_vint()?;
             let monotonic_meta = Arc::new(DirectMonotonicReader::load_meta(
@@ -548,7 +604,8 @@ impl Lucene54DocValuesProducer {
                 )?);
                 entry.monotonic_meta = Some(monotonic_meta);
             }
-            Lucene54DocValuesFormat::SPARSE_COMPRESSED => {
+            Lucene54DocValuesFormat::SPARSE_COMPRESSED
+            | Rucene54DocValuesFormat::DISI_COMPRESSED => {
                 let number_type = meta.read_byte()?;
                 match number_type {
                     0 => {
@@ -580,8 +637,12 @@ impl Lucene54DocValuesProducer {
                     )));
                 }
                 // NOTE: Better way to handle the list?
-                entry.non_missing_values =
-                    Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta)?;
+                entry.non_missing_values = Lucene54DocValuesProducer::read_numeric_entry(
+                    info,
+                    segment_info,
+                    meta,
+                    extended,
+                )?;
             }
             _ => {
                 bail!(CorruptIndex(format!("unknown format: {}", entry.format)));
@@ -591,10 +652,50 @@ impl Lucene54DocValuesProducer {
         Ok(Some(Arc::new(entry)))
     }
 
-    fn read_binary_entry(_info: &FieldInfo, meta: &mut dyn IndexInput) -> Result<BinaryEntry> {
+    fn read_binary_entry(
+        info: &FieldInfo,
+        meta: &mut dyn IndexInput,
+        extended: bool,
+    ) -> Result<BinaryEntry> {
         let mut entry = BinaryEntry::default();
         entry.format = meta.read_vint()?;
         entry.missing_offset = meta.read_long()?;
+        if entry.format == Rucene54DocValuesFormat::BINARY_SPARSE {
+            if !extended {
+                bail!(CorruptIndex(format!(
+                    "sparse binary field {} in a Lucene54 segment",
+                    info.name
+                )));
+            }
+            entry.num_docs_with_value = meta.read_vlong()?;
+            entry.offset = meta.read_long()?;
+            let field_number = meta.read_vint()?;
+            if field_number != info.number as i32 {
+                bail!(CorruptIndex(format!(
+                    "Field number mismatch: {} != {}",
+                    field_number, info.number
+                )));
+            }
+            let dv_format = meta.read_byte()?;
+            if dv_format != Lucene54DocValuesFormat::BINARY {
+                bail!(CorruptIndex(format!(
+                    "Format mismatch: {} != {}",
+                    dv_format,
+                    Lucene54DocValuesFormat::BINARY
+                )));
+            }
+            let values = Lucene54DocValuesProducer::read_binary_entry(info, meta, extended)?;
+            if values.count != entry.num_docs_with_value {
+                bail!(CorruptIndex(format!(
+                    "sparse binary field {} has {} docs with a value but {} values",
+                    info.name, entry.num_docs_with_value, values.count
+                )));
+            }
+            entry.min_length = values.min_length;
+            entry.max_length = values.max_length;
+            entry.non_missing_values = Some(Arc::new(values));
+            return Ok(entry);
+        }
         entry.min_length = meta.read_vint()?;
         entry.max_length = meta.read_vint()?;
         entry.count = meta.read_vlong()?;
@@ -618,6 +719,42 @@ impl Lucene54DocValuesProducer {
                 entry.addresses_meta = Some(Arc::clone(&addresses_meta));
                 entry.addresses_end_offset = meta.read_long()?;
             }
+            Rucene54DocValuesFormat::BINARY_BLOCK_COMPRESSED => {
+                if !extended {
+                    bail!(CorruptIndex(format!(
+                        "block compressed binary field {} in a Lucene54 segment",
+                        info.name
+                    )));
+                }
+                entry.compression = match meta.read_byte()? {
+                    0 => Some(CompressionMode::FAST),
+                    1 => Some(CompressionMode::HighCompression),
+                    #[cfg(feature = "zstd")]
+                    2 => Some(CompressionMode::Zstd(ZSTD_DEFAULT_LEVEL)),
+                    3 => {
+                        let name = meta.read_string()?;
+                        match CompressionMode::for_name(&name) {
+                            Some(mode) => Some(mode),
+                            None => bail!(CorruptIndex(format!(
+                                "compression {} of field {} is not registered",
+                                name, info.name
+                            ))),
+                        }
+                    }
+                    mode => bail!(CorruptIndex(format!("unknown compression mode: {}", mode))),
+                };
+                entry.binary_block_shift = meta.read_vint()?;
+                entry.addresses_offset = meta.read_long()?;
+                let block_shift = meta.read_vint()?;
+                let num_blocks =
+                    (entry.count + (1 << entry.binary_block_shift) - 1) >> entry.binary_block_shift;
+                entry.addresses_meta = Some(Arc::new(DirectMonotonicReader::load_meta(
+                    meta,
+                    num_blocks + 1,
+                    block_shift,
+                )?));
+                entry.addresses_end_offset = meta.read_long()?;
+            }
             _ => {
                 bail!(CorruptIndex(format!("unknown format: {}", entry.format)));
             }
@@ -676,6 +813,7 @@ impl Lucene54DocValuesProducer {
         info: &FieldInfo,
         segment_info: &SegmentInfo<D, C>,
         meta: &mut dyn IndexInput,
+        extended: bool,
         binaries: &mut HashMap<String, BinaryEntry>,
         ords: &mut HashMap<String, NumericEntryLink>,
     ) -> Result<()> {
@@ -694,7 +832,7 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        let b = Lucene54DocValuesProducer::read_binary_entry(info, meta)?;
+        let b = Lucene54DocValuesProducer::read_binary_entry(info, meta, extended)?;
         binaries.insert(info.name.clone(), b);
         if meta.read_vint()? != info.number as i32 {
             bail!(CorruptIndex(format!(
@@ -710,7 +848,9 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        if let Some(n) = Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta)? {
+        if let Some(n) =
+            Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta, extended)?
+        {
             ords.insert(info.name.clone(), n);
         } else {
             unreachable!();
@@ -722,6 +862,7 @@ impl Lucene54DocValuesProducer {
         info: &FieldInfo,
         segment_info: &SegmentInfo<D, C>,
         meta: &mut dyn IndexInput,
+        extended: bool,
         binaries: &mut HashMap<String, BinaryEntry>,
         ords: &mut HashMap<String, NumericEntryLink>,
         ord_indexes: &mut HashMap<String, NumericEntryLink>,
@@ -741,7 +882,7 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        let b = Lucene54DocValuesProducer::read_binary_entry(info, meta)?;
+        let b = Lucene54DocValuesProducer::read_binary_entry(info, meta, extended)?;
         binaries.insert(info.name.clone(), b);
 
         if meta.read_vint()? != info.number as i32 {
@@ -758,7 +899,9 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        if let Some(n1) = Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta)? {
+        if let Some(n1) =
+            Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta, extended)?
+        {
             ords.insert(info.name.clone(), n1);
         } else {
             unreachable!();
@@ -778,7 +921,9 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        if let Some(n2) = Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta)? {
+        if let Some(n2) =
+            Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta, extended)?
+        {
             ord_indexes.insert(info.name.clone(), n2);
         } else {
             unreachable!();
@@ -790,6 +935,7 @@ impl Lucene54DocValuesProducer {
         info: &FieldInfo,
         segment_info: &SegmentInfo<D, C>,
         meta: &mut dyn IndexInput,
+        extended: bool,
         binaries: &mut HashMap<String, BinaryEntry>,
         ords: &mut HashMap<String, NumericEntryLink>,
     ) -> Result<()> {
@@ -807,7 +953,7 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        let b = Lucene54DocValuesProducer::read_binary_entry(info, meta)?;
+        let b = Lucene54DocValuesProducer::read_binary_entry(info, meta, extended)?;
         binaries.insert(info.name.clone(), b);
 
         if meta.read_vint()? != info.number as i32 {
@@ -824,7 +970,9 @@ impl Lucene54DocValuesProducer {
             )));
         }
 
-        if let Some(n) = Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta)? {
+        if let Some(n) =
+            Lucene54DocValuesProducer::read_numeric_entry(info, segment_info, meta, extended)?
+        {
             ords.insert(info.name.clone(), n);
         } else {
             unreachable!();
@@ -885,6 +1033,7 @@ impl Lucene54DocValuesProducer {
 
     fn get_numeric_sparse_compressed(
         &self,
+        field: &FieldInfo,
         entry: &NumericEntryLink,
     ) -> Result<SparseLongValues<MixinMonotonicLongValues>> {
         let docs_with_field = Arc::new(self.get_sparse_live_bits_by_entry(&entry)?);
@@ -892,7 +1041,7 @@ impl Lucene54DocValuesProducer {
         debug_assert!(!entry.non_missing_values.is_none());
 
         let non_missing_values = entry.non_missing_values.as_ref().unwrap();
-        let values = self.get_numeric_by_entry(non_missing_values)?;
+        let values = self.get_numeric_by_entry(field, non_missing_values)?;
         let missing_value = match entry.number_type {
             NumberType::ORDINAL => -1_i64,
             NumberType::VALUE => 0_i64,
@@ -904,8 +1053,34 @@ impl Lucene54DocValuesProducer {
         ))
     }
 
+    fn get_numeric_disi_compressed(
+        &self,
+        field: &FieldInfo,
+        entry: &NumericEntryLink,
+    ) -> Result<SparseDISIValues> {
+        let docs_with_field = self.get_disi(
+            field,
+            entry.missing_offset,
+            entry.offset - entry.missing_offset,
+            entry.num_docs_with_value,
+        )?;
+        debug_assert!(entry.non_missing_values.is_some());
+        let non_missing_values = entry.non_missing_values.as_ref().unwrap();
+        let values = self.get_numeric_by_entry(field, non_missing_values)?;
+        let missing_value = match entry.number_type {
+            NumberType::ORDINAL => -1_i64,
+            NumberType::VALUE => 0_i64,
+        };
+        Ok(SparseDISIValues::new(
+            docs_with_field,
+            values,
+            missing_value,
+        ))
+    }
+
     fn get_numeric_by_entry_outbound(
         &self,
+        field: &FieldInfo,
         entry: &NumericEntryLink,
     ) -> Result<Box<dyn NumericDocValues>> {
         let fmt = entry.format;
@@ -927,9 +1102,13 @@ impl Lucene54DocValuesProducer {
                 Ok(Box::new(table_lv))
             }
             Lucene54DocValuesFormat::SPARSE_COMPRESSED => {
-                let sparse_lv = self.get_numeric_sparse_compressed(entry)?;
+                let sparse_lv = self.get_numeric_sparse_compressed(field, entry)?;
                 Ok(Box::new(sparse_lv))
             }
+            Rucene54DocValuesFormat::DISI_COMPRESSED => {
+                let disi_lv = self.get_numeric_disi_compressed(field, entry)?;
+                Ok(Box::new(disi_lv))
+            }
             _ => bail!(IllegalArgument(format!(
                 "Unknown numeric entry format: {}",
                 fmt
@@ -937,7 +1116,11 @@ impl Lucene54DocValuesProducer {
         }
     }
 
-    fn get_numeric_by_entry(&self, entry: &NumericEntryLink) -> Result<Box<dyn LongValues>> {
+    fn get_numeric_by_entry(
+        &self,
+        field: &FieldInfo,
+        entry: &NumericEntryLink,
+    ) -> Result<Box<dyn LongValues>> {
         let fmt = entry.format;
         match fmt {
             Lucene54DocValuesFormat::CONST_COMPRESSED => {
@@ -957,9 +1140,13 @@ impl Lucene54DocValuesProducer {
                 Ok(Box::new(table_lv))
             }
             Lucene54DocValuesFormat::SPARSE_COMPRESSED => {
-                let sparse_lv = self.get_numeric_sparse_compressed(entry)?;
+                let sparse_lv = self.get_numeric_sparse_compressed(field, entry)?;
                 Ok(Box::new(sparse_lv))
             }
+            Rucene54DocValuesFormat::DISI_COMPRESSED => {
+                let disi_lv = self.get_numeric_disi_compressed(field, entry)?;
+                Ok(Box::new(disi_lv))
+            }
             _ => bail!(IllegalArgument(format!(
                 "Unknown numeric entry format: {}",
                 fmt
@@ -967,6 +1154,31 @@ impl Lucene54DocValuesProducer {
         }
     }
 
+    fn get_disi(
+        &self,
+        field: &FieldInfo,
+        offset: i64,
+        length: i64,
+        cost: i64,
+    ) -> Result<Arc<IndexedDISI>> {
+        if let Some(disi) = self.disi_instances.read()?.get(&field.name) {
+            return Ok(Arc::clone(disi));
+        }
+        let disi = Arc::new(IndexedDISI::new(
+            self.data.as_ref(),
+            offset,
+            length,
+            cost,
+            self.max_doc,
+        )?);
+        if !self.merging {
+            self.disi_instances
+                .write()?
+                .insert(field.name.clone(), Arc::clone(&disi));
+        }
+        Ok(disi)
+    }
+
     fn get_sparse_live_bits_by_entry(
         &self,
         entry: &NumericEntry,
@@ -1026,6 +1238,80 @@ impl Lucene54DocValuesProducer {
         Ok(variable_binary)
     }
 
+    fn get_block_compressed_binary(
+        &self,
+        bytes: &BinaryEntry,
+    ) -> Result<BlockCompressedBinaryDocValues<MixinMonotonicLongValues>> {
+        let addresses_length = bytes.addresses_end_offset - bytes.addresses_offset;
+        let meta = bytes
+            .addresses_meta
+            .as_ref()
+            .ok_or_else(|| IllegalArgument("addresses_meta None???".to_owned()))?;
+        let addresses_data = Arc::from(
+            self.data
+                .random_access_slice(bytes.addresses_offset, addresses_length)?,
+        );
+        let addresses = DirectMonotonicReader::get_instance(meta.as_ref(), &addresses_data)?;
+        let data_length = bytes.addresses_offset - bytes.offset;
+        let data = self
+            .data
+            .slice("block-compressed-binary", bytes.offset, data_length)?;
+        Ok(BlockCompressedBinaryDocValues::new(
+            addresses,
+            data,
+            bytes.binary_block_shift,
+            bytes.compression.unwrap().new_decompressor()?,
+        ))
+    }
+
+    fn get_binary_by_entry(
+        &self,
+        field: &FieldInfo,
+        bytes: &BinaryEntry,
+    ) -> Result<Arc<dyn BinaryDocValues>> {
+        match bytes.format {
+            Lucene54DocValuesFormat::BINARY_FIXED_UNCOMPRESSED => {
+                let boxed = self.get_fixed_binary(field, bytes)?;
+                Ok(Arc::new(boxed))
+            }
+            Lucene54DocValuesFormat::BINARY_VARIABLE_UNCOMPRESSED => {
+                let boxed = self.get_variable_binary(field, bytes)?;
+                Ok(Arc::new(boxed))
+            }
+            Lucene54DocValuesFormat::BINARY_PREFIX_COMPRESSED => {
+                let boxed = self.get_compressed_binary(field, bytes)?;
+                Ok(Arc::new(boxed))
+            }
+            Rucene54DocValuesFormat::BINARY_BLOCK_COMPRESSED => {
+                let boxed = self.get_block_compressed_binary(bytes)?;
+                Ok(Arc::new(boxed))
+            }
+            Rucene54DocValuesFormat::BINARY_SPARSE => {
+                let disi = self.get_sparse_binary_disi(field, bytes)?;
+                let values = bytes.non_missing_values.as_ref().unwrap();
+                let values = self.get_binary_by_entry(field, values)?;
+                Ok(Arc::new(SparseDISIBinaryValues::new(disi, values)))
+            }
+            _ => bail!(IllegalArgument(format!(
+                "unknown binary_entry format: {}",
+                bytes.format,
+            ))),
+        }
+    }
+
+    fn get_sparse_binary_disi(
+        &self,
+        field: &FieldInfo,
+        bytes: &BinaryEntry,
+    ) -> Result<Arc<IndexedDISI>> {
+        self.get_disi(
+            field,
+            bytes.missing_offset,
+            bytes.offset - bytes.missing_offset,
+            bytes.num_docs_with_value,
+        )
+    }
+
     fn get_interval_instance(
         &self,
         field: &FieldInfo,
@@ -1137,14 +1423,14 @@ impl Lucene54DocValuesProducer {
                 .get(&field.name)
                 .ok_or_else(|| IllegalArgument(format!("No ords field named {}", &field.name)))?;
 
-            self.get_numeric_by_entry(ord_entry)?
+            self.get_numeric_by_entry(field, ord_entry)?
         };
 
         let ord_index = {
             let ord_index_entry = self.ord_indexes.get(&field.name).ok_or_else(|| {
                 IllegalArgument(format!("No OrdIndex field named {}", field.name))
             })?;
-            self.get_ord_index_instance(ord_index_entry)?
+            Box::new(self.get_ord_index_instance(ord_index_entry)?)
         };
 
         let bytes = self
@@ -1210,7 +1496,7 @@ impl Lucene54DocValuesProducer {
                 .ords
                 .get(&field.name)
                 .ok_or_else(|| IllegalArgument(format!("No ords field named {}", &field.name)))?;
-            ordinals = self.get_numeric_by_entry(ord_entry)?;
+            ordinals = self.get_numeric_by_entry(field, ord_entry)?;
         }
 
         let bytes = self
@@ -1261,12 +1547,70 @@ impl Lucene54DocValuesProducer {
     }
 }
 
+impl Accountable for ReverseTermsIndex {
+    fn ram_bytes_used(&self) -> usize {
+        self.term_addresses.ram_bytes_used() + self.terms.ram_bytes_used()
+    }
+}
+
+impl Accountable for Lucene54DocValuesProducer {
+    /// The addresses and reverse terms indexes loaded so far, the values
+    /// themselves are read from the data file.
+    fn ram_bytes_used(&self) -> usize {
+        let addresses: usize = self
+            .address_instances
+            .read()
+            .unwrap()
+            .values()
+            .map(|a| a.ram_bytes_used())
+            .sum();
+        let reverse_indexes: usize = self
+            .reverse_index_instances
+            .read()
+            .unwrap()
+            .values()
+            .map(|r| r.ram_bytes_used())
+            .sum();
+        let disis: usize = self
+            .disi_instances
+            .read()
+            .unwrap()
+            .values()
+            .map(|d| d.ram_bytes_used())
+            .sum();
+        addresses + reverse_indexes + disis
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        let mut resources = vec![];
+        for (field, addresses) in self.address_instances.read().unwrap().iter() {
+            resources.push(RamUsage::of(
+                format!("addresses field '{}'", field),
+                addresses,
+            ));
+        }
+        for (field, index) in self.reverse_index_instances.read().unwrap().iter() {
+            resources.push(RamUsage::of(
+                format!("terms index field '{}'", field),
+                index,
+            ));
+        }
+        for (field, disi) in self.disi_instances.read().unwrap().iter() {
+            resources.push(RamUsage::of(
+                format!("docs with field '{}'", field),
+                disi.as_ref(),
+            ));
+        }
+        resources
+    }
+}
+
 impl DocValuesProducer for Lucene54DocValuesProducer {
     fn get_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
         let link = self.numerics.get(&field.name).ok_or_else(|| {
             IllegalArgument(format!("No numeric field named {} found", field.name))
         })?;
-        let boxed = self.get_numeric_by_entry_outbound(link)?;
+        let boxed = self.get_numeric_by_entry_outbound(field, link)?;
         Ok(Arc::from(boxed))
     }
 
@@ -1274,29 +1618,8 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
         let bytes = self
             .binaries
             .get(&field.name)
-            .ok_or_else(|| IllegalArgument(format!("No binary field named {}", field.name)))?
-            .clone();
-        let myformat = bytes.format;
-
-        match myformat {
-            Lucene54DocValuesFormat::BINARY_FIXED_UNCOMPRESSED => {
-                let boxed = self.get_fixed_binary(field, &bytes)?;
-                Ok(Arc::new(boxed))
-            }
-            Lucene54DocValuesFormat::BINARY_VARIABLE_UNCOMPRESSED => {
-                let boxed = self.get_variable_binary(field, &bytes)?;
-                Ok(Arc::new(boxed))
-            }
-
-            Lucene54DocValuesFormat::BINARY_PREFIX_COMPRESSED => {
-                let boxed = self.get_compressed_binary(field, &bytes)?;
-                Ok(Arc::new(boxed))
-            }
-            _ => bail!(IllegalArgument(format!(
-                "unknown binary_entry format: {}",
-                myformat,
-            ))),
-        }
+            .ok_or_else(|| IllegalArgument(format!("No binary field named {}", field.name)))?;
+        self.get_binary_by_entry(field, bytes)
     }
 
     fn get_sorted(&self, field: &FieldInfo) -> Result<Arc<dyn SortedDocValues>> {
@@ -1311,7 +1634,7 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
                 .ords
                 .get(&field.name)
                 .ok_or_else(|| IllegalArgument(format!("No ords field named {}", &field.name)))?;
-            ordinals = self.get_numeric_by_entry(entry)?;
+            ordinals = self.get_numeric_by_entry(field, entry)?;
         }
 
         let bytes = self
@@ -1354,17 +1677,29 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
                     IllegalArgument(format!("No Numerics field named {}", field.name))
                 })?;
                 if numeric_entry.format == Lucene54DocValuesFormat::SPARSE_COMPRESSED {
-                    let values = self.get_numeric_sparse_compressed(numeric_entry)?;
+                    let values = self.get_numeric_sparse_compressed(field, numeric_entry)?;
                     let docs_with_field = values.docs_with_field_clone();
                     Ok(Arc::new(DocValues::singleton_sorted_numeric_doc_values(
                         Box::new(values),
                         docs_with_field,
                     )))
+                } else if numeric_entry.format == Rucene54DocValuesFormat::DISI_COMPRESSED {
+                    let values = self.get_numeric_disi_compressed(field, numeric_entry)?;
+                    let docs_with_field = self.get_disi(
+                        field,
+                        numeric_entry.missing_offset,
+                        numeric_entry.offset - numeric_entry.missing_offset,
+                        numeric_entry.num_docs_with_value,
+                    )?;
+                    Ok(Arc::new(DocValues::singleton_sorted_numeric_doc_values(
+                        Box::new(values),
+                        docs_with_field,
+                    )))
                 } else {
                     let offset = numeric_entry.missing_offset;
                     let count = self.max_doc as usize;
 
-                    let values = self.get_numeric_by_entry_outbound(numeric_entry)?;
+                    let values = self.get_numeric_by_entry_outbound(field, numeric_entry)?;
 
                     match offset as i32 {
                         Lucene54DocValuesFormat::ALL_MISSING => {
@@ -1396,7 +1731,7 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
                 let numeric_entry = self.numerics.get(&field.name).ok_or_else(|| {
                     IllegalArgument(format!("No Numerics field named {}", field.name))
                 })?;
-                let values = self.get_numeric_by_entry(numeric_entry)?;
+                let values = self.get_numeric_by_entry(field, numeric_entry)?;
                 let ord_entry = self.ord_indexes.get(&field.name).ok_or_else(|| {
                     IllegalArgument(format!("no field named {} in ord_indexes", field.name))
                 })?;
@@ -1409,7 +1744,7 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
                 let numeric_entry = self.ords.get(&field.name).ok_or_else(|| {
                     IllegalArgument(format!("No Ords field named {}", field.name))
                 })?;
-                let ordinals = self.get_numeric_by_entry(numeric_entry)?;
+                let ordinals = self.get_numeric_by_entry(field, numeric_entry)?;
                 Ok(Arc::new(TabledSortedNumericDocValues::new(
                     ordinals,
                     &ss.table,
@@ -1468,9 +1803,13 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
                 let be = self.binaries.get(&field.name).ok_or_else(|| {
                     IllegalArgument(format!("No binary field named {}", field.name))
                 })?;
-                Ok(self
-                    .get_live_bits(be.missing_offset, self.max_doc as usize)?
-                    .into_bits())
+                if be.format == Rucene54DocValuesFormat::BINARY_SPARSE {
+                    Ok(self.get_sparse_binary_disi(field, be)?)
+                } else {
+                    Ok(self
+                        .get_live_bits(be.missing_offset, self.max_doc as usize)?
+                        .into_bits())
+                }
             }
             DocValuesType::Numeric => {
                 let ne = self.numerics.get(&field.name).ok_or_else(|| {
@@ -1478,6 +1817,13 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
                 })?;
                 if ne.format == Lucene54DocValuesFormat::SPARSE_COMPRESSED {
                     Ok(Arc::new(self.get_sparse_live_bits_by_entry(&ne)?))
+                } else if ne.format == Rucene54DocValuesFormat::DISI_COMPRESSED {
+                    Ok(self.get_disi(
+                        field,
+                        ne.missing_offset,
+                        ne.offset - ne.missing_offset,
+                        ne.num_docs_with_value,
+                    )?)
                 } else {
                     Ok(self
                         .get_live_bits(ne.missing_offset, self.max_doc as usize)?
@@ -1500,6 +1846,13 @@ impl DocValuesProducer for Lucene54DocValuesProducer {
     fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>> {
         Ok(Box::new(Lucene54DocValuesProducer::copy_from(self)?))
     }
+
+    fn evict(&self, field: &FieldInfo) -> Result<()> {
+        self.address_instances.write()?.remove(&field.name);
+        self.reverse_index_instances.write()?.remove(&field.name);
+        self.disi_instances.write()?.remove(&field.name);
+        Ok(())
+    }
 }
 
 #[derive(Clone)]
@@ -1546,3 +1899,365 @@ impl Bits for LiveBitsEnum {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{
+        BinaryDocValuesField, Field, BINARY_DOC_VALUES_FIELD_TYPE, NUMERIC_DOC_VALUES_FIELD_TYPE,
+    };
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{DocValuesType, Fieldable, IndexOptions, IndexReader, IndexWriter};
+    use core::store::{DataOutput, FSDirectory, IOContext, NativeFSLockFactory};
+    use core::util::string_util::ID_LENGTH;
+    use core::util::{VariantValue, VERSION_LATEST};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn test_directory(name: &str) -> (PathBuf, Arc<FSDirectory<NativeFSLockFactory>>) {
+        let path = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let dir = FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap();
+        (path, Arc::new(dir))
+    }
+
+    fn field_info(
+        name: &str,
+        doc_values_type: DocValuesType,
+        attributes: HashMap<String, String>,
+    ) -> FieldInfo {
+        FieldInfo::new(
+            name.into(),
+            0,
+            false,
+            true,
+            false,
+            IndexOptions::Null,
+            doc_values_type,
+            -1,
+            attributes,
+            0,
+            0,
+        )
+        .unwrap()
+    }
+
+    /// Returns the length of the doc values data of the directory.
+    fn data_length(dir: &FSDirectory<NativeFSLockFactory>) -> i64 {
+        let mut data_length = 0;
+        for file in dir.list_all().unwrap() {
+            if file.ends_with(".dvd") {
+                data_length += dir.file_length(&file).unwrap();
+            }
+        }
+        data_length
+    }
+
+    /// Writes `docs` documents, the documents for which `value` returns
+    /// `None` having no value for `field`, checks the values read back and
+    /// returns the length of the doc values data.
+    fn check_binary_round_trip<F: Fieldable + 'static>(
+        name: &str,
+        docs: i32,
+        value: impl Fn(i32) -> Option<Vec<u8>>,
+        field: impl Fn(&[u8]) -> F,
+    ) -> i64 {
+        let (path, dir) = test_directory(name);
+        let mut config = IndexWriterConfig::default();
+        config.use_compound_file = false;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        for doc in 0..docs {
+            let fields: Vec<Box<dyn Fieldable>> = match value(doc) {
+                Some(v) => vec![Box::new(field(&v))],
+                None => vec![],
+            };
+            writer.add_document(fields).unwrap();
+        }
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let values = leaves[0].reader.get_binary_doc_values("field").unwrap();
+        for doc in 0..docs {
+            assert_eq!(
+                values.get(doc).unwrap(),
+                value(doc).unwrap_or_default(),
+                "doc {}",
+                doc
+            );
+        }
+        let data_length = data_length(&dir);
+
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        data_length
+    }
+
+    fn json_value(doc: i32) -> Vec<u8> {
+        format!(
+            r#"{{"id": {}, "name": "document {}", "tags": ["a", "b", "c"]}}"#,
+            doc,
+            doc % 17
+        )
+        .into_bytes()
+    }
+
+    fn with_missing(doc: i32, value: Vec<u8>) -> Option<Vec<u8>> {
+        if doc % 7 == 3 {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Writes the entry of a block compressed field without values.
+    fn write_block_compressed_entry(dir: &FSDirectory<NativeFSLockFactory>) {
+        let mut out = dir.create_output("meta", &IOContext::Default).unwrap();
+        out.write_vint(Rucene54DocValuesFormat::BINARY_BLOCK_COMPRESSED)
+            .unwrap();
+        out.write_long(-1).unwrap();
+        out.write_vint(0).unwrap();
+        out.write_vint(0).unwrap();
+        out.write_vlong(0).unwrap();
+        out.write_long(0).unwrap();
+        out.write_byte(0).unwrap();
+        out.write_vint(Rucene54DocValuesFormat::BINARY_BLOCK_SHIFT)
+            .unwrap();
+        out.write_long(0).unwrap();
+        out.write_vint(16).unwrap();
+        // the monotonic meta of the single address
+        out.write_long(0).unwrap();
+        out.write_int(0).unwrap();
+        out.write_long(0).unwrap();
+        out.write_byte(0).unwrap();
+        out.write_long(0).unwrap();
+    }
+
+    fn read_binary_entry(
+        dir: &FSDirectory<NativeFSLockFactory>,
+        info: &FieldInfo,
+        extended: bool,
+    ) -> Result<BinaryEntry> {
+        let mut input = dir.open_input("meta", &IOContext::Default).unwrap();
+        Lucene54DocValuesProducer::read_binary_entry(info, input.as_mut(), extended)
+    }
+
+    #[test]
+    fn test_block_compressed_binary() {
+        #[allow(unused_mut)]
+        let mut modes = vec![
+            ("rucene_dv_block_compressed_fast", CompressionMode::FAST),
+            (
+                "rucene_dv_block_compressed_high",
+                CompressionMode::HighCompression,
+            ),
+        ];
+        #[cfg(feature = "zstd")]
+        modes.push((
+            "rucene_dv_block_compressed_zstd",
+            CompressionMode::Zstd(ZSTD_DEFAULT_LEVEL),
+        ));
+        for (name, mode) in modes {
+            // several blocks, the last one partial, with some docs missing
+            check_binary_round_trip(
+                name,
+                1000,
+                |doc| with_missing(doc, json_value(doc)),
+                |v| BinaryDocValuesField::with_compression("field", v, mode),
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_compressed_binary_requires_rucene54() {
+        let (path, dir) = test_directory("rucene_dv_block_compressed_lucene54");
+        write_block_compressed_entry(&dir);
+        let info = field_info("field", DocValuesType::Binary, HashMap::new());
+        assert!(read_binary_entry(&dir, &info, true).is_ok());
+        assert!(read_binary_entry(&dir, &info, false).is_err());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_auto_block_compressed_binary() {
+        let docs = 1000;
+        let raw_length: usize = (0..docs)
+            .filter_map(|doc| with_missing(doc, json_value(doc)))
+            .map(|v| v.len())
+            .sum();
+        let data_length = check_binary_round_trip(
+            "rucene_dv_auto_compressed",
+            docs,
+            |doc| with_missing(doc, json_value(doc)),
+            |v| BinaryDocValuesField::with_auto_compression("field", v),
+        );
+        assert!(data_length * 2 < raw_length as i64);
+
+        // the fields without auto compression are never compressed
+        let data_length = check_binary_round_trip(
+            "rucene_dv_not_auto_compressed",
+            docs,
+            |doc| with_missing(doc, json_value(doc)),
+            |v| BinaryDocValuesField::new("field", v),
+        );
+        assert!(data_length >= raw_length as i64);
+    }
+
+    #[test]
+    fn test_auto_block_compression_skips_short_values() {
+        let docs = 1000;
+        let value = |doc: i32| with_missing(doc, format!("{:08}", doc * 7919).into_bytes());
+        let raw_length: usize = (0..docs).filter_map(value).map(|v| v.len()).sum();
+        let data_length =
+            check_binary_round_trip("rucene_dv_auto_compressed_short", docs, value, |v| {
+                BinaryDocValuesField::with_auto_compression("field", v)
+            });
+        assert!(data_length >= raw_length as i64);
+    }
+
+    fn sparse_value(doc: i32, value: Vec<u8>) -> Option<Vec<u8>> {
+        if doc % 3 == 1 {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a binary doc values field written with `Rucene54DocValuesFormat`.
+    fn rucene54_binary(value: &[u8]) -> Field {
+        let mut field_type = BINARY_DOC_VALUES_FIELD_TYPE;
+        field_type.set_doc_values_format(Rucene54DocValuesFormat::NAME);
+        Field::new(
+            "field".into(),
+            field_type,
+            Some(VariantValue::from(value)),
+            None,
+        )
+    }
+
+    /// Writes the values of a third of the docs with `format`, checks the
+    /// values read back and returns the length of the doc values data.
+    fn check_sparse_numeric(name: &str, format: &'static str) -> i64 {
+        let (path, dir) = test_directory(name);
+        let mut config = IndexWriterConfig::default();
+        config.use_compound_file = false;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        let docs = 3000;
+        let value = |doc: i32| i64::from(doc) * 1_000_003 - 1_500_000_000;
+        let mut field_type = NUMERIC_DOC_VALUES_FIELD_TYPE;
+        field_type.set_doc_values_format(format);
+        for doc in 0..docs {
+            let fields: Vec<Box<dyn Fieldable>> = if doc % 3 == 1 {
+                vec![Box::new(Field::new(
+                    "field".into(),
+                    field_type.clone(),
+                    Some(VariantValue::Long(value(doc))),
+                    None,
+                ))]
+            } else {
+                vec![]
+            };
+            writer.add_document(fields).unwrap();
+        }
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let values = leaves[0].reader.get_numeric_doc_values("field").unwrap();
+        let docs_with_field = leaves[0].reader.get_docs_with_field("field").unwrap();
+        for doc in 0..docs {
+            let has_value = doc % 3 == 1;
+            assert_eq!(docs_with_field.get(doc as usize).unwrap(), has_value);
+            let expected = if has_value { value(doc) } else { 0 };
+            assert_eq!(values.get(doc).unwrap(), expected, "doc {}", doc);
+        }
+        let data_length = data_length(&dir);
+
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        data_length
+    }
+
+    #[test]
+    fn test_sparse_numeric() {
+        // only the values of the docs that have one are stored by Rucene54,
+        // Lucene54 stores a value for every doc
+        let dense_length = check_sparse_numeric("rucene_dv_sparse_numeric_lucene54", "Lucene54");
+        let sparse_length =
+            check_sparse_numeric("rucene_dv_sparse_numeric", Rucene54DocValuesFormat::NAME);
+        assert!(sparse_length < dense_length);
+    }
+
+    #[test]
+    fn test_sparse_binary() {
+        // the values of the docs that have one are written uncompressed or
+        // compressed by blocks like dense values are, the fields compressed
+        // by blocks being written with Rucene54
+        let value = |doc: i32| sparse_value(doc, format!("{:08}", doc * 7919).into_bytes());
+        let dense_length =
+            check_binary_round_trip("rucene_dv_sparse_binary_lucene54", 3000, value, |v| {
+                BinaryDocValuesField::new("field", v)
+            });
+        let sparse_length =
+            check_binary_round_trip("rucene_dv_sparse_binary", 3000, value, rucene54_binary);
+        assert!(sparse_length < dense_length);
+        check_binary_round_trip(
+            "rucene_dv_sparse_binary_compressed",
+            3000,
+            |doc| sparse_value(doc, json_value(doc)),
+            |v| BinaryDocValuesField::with_auto_compression("field", v),
+        );
+    }
+
+    #[test]
+    fn test_sparse_entries_require_rucene54() {
+        let (path, dir) = test_directory("rucene_dv_sparse_lucene54");
+        let info = field_info("field", DocValuesType::Numeric, HashMap::new());
+        let is_format_error = |result: Result<()>| match result {
+            Err(e) => format!("{}", e).contains("in a Lucene54 segment"),
+            Ok(()) => false,
+        };
+        {
+            let mut out = dir.create_output("meta", &IOContext::Default).unwrap();
+            out.write_vint(Rucene54DocValuesFormat::DISI_COMPRESSED)
+                .unwrap();
+        }
+        let mut input = dir.open_input("meta", &IOContext::Default).unwrap();
+        let segment_info: SegmentInfo<_, CodecEnum> = SegmentInfo::new(
+            VERSION_LATEST,
+            "_0",
+            3000,
+            Arc::clone(&dir),
+            false,
+            None,
+            HashMap::new(),
+            [0u8; ID_LENGTH],
+            HashMap::new(),
+            None,
+        )
+        .unwrap();
+        let result = Lucene54DocValuesProducer::read_numeric_entry(
+            &info,
+            &segment_info,
+            input.as_mut(),
+            false,
+        );
+        assert!(is_format_error(result.map(|_| ())));
+
+        dir.delete_file("meta").unwrap();
+        {
+            let mut out = dir.create_output("meta", &IOContext::Default).unwrap();
+            out.write_vint(Rucene54DocValuesFormat::BINARY_SPARSE)
+                .unwrap();
+            out.write_long(0).unwrap();
+        }
+        let result = read_binary_entry(&dir, &info, false);
+        assert!(is_format_error(result.map(|_| ())));
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
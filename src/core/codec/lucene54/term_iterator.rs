@@ -149,7 +149,7 @@ impl CompressedBinaryTermIterator {
     fn binary_search_index(&self, text: &[u8]) -> Result<i64> {
         let mut low = 0_i64;
         let mut high = self.num_reverse_index_values - 1;
-        while low < high {
+        while low <= high {
             let mid = low + (high - low) / 2;
             let start = self.reverse_index.term_addresses.get64(mid)?;
             let scratch = self.reverse_index.terms.fill(start);
@@ -274,3 +274,76 @@ impl TermIterator for CompressedBinaryTermIterator {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::doc::SortedSetDocValuesField;
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexReader, IndexWriter, SeekStatus, TermIterator};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_seek_beyond_first_reverse_index_block() {
+        let path = ::std::env::temp_dir().join("rucene_lucene54_term_iterator");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // more than two blocks of 1024 terms of the reverse index, the terms
+        // have different lengths so they are prefix compressed
+        let mut terms: Vec<Vec<u8>> = (0..2600)
+            .map(|i| format!("term{}", i).into_bytes())
+            .collect();
+        for term in &terms {
+            let fields: Vec<Box<dyn Fieldable>> =
+                vec![Box::new(SortedSetDocValuesField::new("terms", term))];
+            writer.add_document(fields).unwrap();
+        }
+        writer.commit().unwrap();
+        terms.sort();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let values = leaves[0].reader.get_sorted_set_doc_values("terms").unwrap();
+        assert_eq!(values.get_value_count(), terms.len());
+
+        // the first and last terms of every reverse index block, some in
+        // between, and the last term
+        let ords = [
+            0, 1, 500, 1023, 1024, 1025, 1500, 2047, 2048, 2049, 2300, 2598, 2599,
+        ];
+        for &ord in &ords {
+            let term = &terms[ord];
+            assert_eq!(values.lookup_term(term).unwrap(), ord as i64);
+
+            let mut iter = values.term_iterator().unwrap();
+            assert_eq!(iter.seek_ceil(term).unwrap(), SeekStatus::Found);
+            assert_eq!(iter.ord().unwrap(), ord as i64);
+            assert_eq!(iter.term().unwrap(), term.as_slice());
+
+            // a term sorting right after `term` is not found
+            let mut after = term.clone();
+            after.push(0);
+            let mut iter = values.term_iterator().unwrap();
+            if ord + 1 < terms.len() {
+                assert_eq!(iter.seek_ceil(&after).unwrap(), SeekStatus::NotFound);
+                assert_eq!(iter.ord().unwrap(), ord as i64 + 1);
+                assert_eq!(iter.term().unwrap(), terms[ord + 1].as_slice());
+                assert_eq!(values.lookup_term(&after).unwrap(), -(ord as i64 + 1) - 1);
+            } else {
+                assert_eq!(iter.seek_ceil(&after).unwrap(), SeekStatus::End);
+            }
+        }
+
+        let mut iter = values.term_iterator().unwrap();
+        assert_eq!(iter.seek_ceil(b"a").unwrap(), SeekStatus::NotFound);
+        assert_eq!(iter.ord().unwrap(), 0);
+        assert_eq!(iter.seek_ceil(b"z").unwrap(), SeekStatus::End);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -13,7 +13,9 @@
 
 use core::codec::format::FieldInfosFormat;
 use core::codec::{codec_util, Codec};
-use core::index::{segment_file_name, DocValuesType, IndexOptions, SegmentInfo};
+use core::index::{
+    segment_file_name, DocValuesType, IndexOptions, SegmentInfo, VectorSimilarityFunction,
+};
 use core::index::{FieldInfo, FieldInfos};
 use core::store::Directory;
 use core::store::{
@@ -27,6 +29,12 @@ const EXTENSION: &str = "fnm";
 // Codec header
 const CODEC_NAME: &str = "Lucene60FieldInfos";
 const FORMAT_START: i32 = 0;
+// written by Lucene 7.4+, which records the soft deletes field in the flags
+const FORMAT_SOFT_DELETES: i32 = 1;
+// records the vector dimension and similarity function of the fields, only
+// used when some field has vectors, so that the other segments keep a format
+// Lucene can read
+const FORMAT_VECTOR_VALUES: i32 = 2;
 const FORMAT_CURRENT: i32 = FORMAT_START;
 
 // Field flags
@@ -40,11 +48,11 @@ fn read_field_infos_from_index<T: IndexInput + ?Sized, D: Directory, C: Codec>(
     suffix: &str,
 ) -> Result<Vec<FieldInfo>> {
     let mut infos: Vec<FieldInfo> = Vec::new();
-    codec_util::check_index_header(
+    let version = codec_util::check_index_header(
         input,
         CODEC_NAME,
         FORMAT_START,
-        FORMAT_CURRENT,
+        FORMAT_VECTOR_VALUES,
         &segment_info.id,
         suffix,
     )?;
@@ -63,6 +71,7 @@ fn read_field_infos_from_index<T: IndexInput + ?Sized, D: Directory, C: Codec>(
         let store_term_vector = (bits & STORE_TERM_VECTOR) != 0;
         let omit_norms = (bits & OMIT_NORMS) != 0;
         let store_payloads = (bits & STORE_PAYLOADS) != 0;
+        // the soft deletes field flag (0x8) is ignored, soft deletes aren't supported
         let index_options = read_index_options(input)?;
         let doc_values_type = read_doc_values_type(input)?;
         let dv_gen = input.read_long()?;
@@ -73,8 +82,15 @@ fn read_field_infos_from_index<T: IndexInput + ?Sized, D: Directory, C: Codec>(
         } else {
             0
         };
+        let (vector_dimension, vector_similarity_function) = if version >= FORMAT_VECTOR_VALUES {
+            let dimension = input.read_vint()?;
+            let function = VectorSimilarityFunction::from_ord(input.read_byte()?)?;
+            (dimension, function)
+        } else {
+            (0, VectorSimilarityFunction::default())
+        };
 
-        let info = FieldInfo::new(
+        let mut info = FieldInfo::new(
             name,
             field_number as u32,
             store_term_vector,
@@ -87,6 +103,19 @@ fn read_field_infos_from_index<T: IndexInput + ?Sized, D: Directory, C: Codec>(
             point_dimension_count as u32,
             point_num_bytes as u32,
         )?;
+        if vector_dimension < 0 {
+            bail!(CorruptIndex(format!(
+                "invalid vector dimension for field: {}, dimension={}",
+                info.name, vector_dimension
+            )));
+        }
+        if vector_dimension > 0 {
+            info.set_vector_dimension_and_similarity_function(
+                vector_dimension as u32,
+                vector_similarity_function,
+            )?;
+            info.check_consistency()?;
+        }
         infos.push(info);
     }
     Ok(infos)
@@ -195,10 +224,15 @@ impl FieldInfosFormat for Lucene60FieldInfosFormat {
     ) -> Result<()> {
         let file_name = segment_file_name(&segment_info.name, segment_suffix, EXTENSION);
         let mut output = directory.create_output(&file_name, context)?;
+        let format = if infos.has_vector_values {
+            FORMAT_VECTOR_VALUES
+        } else {
+            FORMAT_CURRENT
+        };
         codec_util::write_index_header(
             &mut output,
             CODEC_NAME,
-            FORMAT_CURRENT,
+            format,
             segment_info.get_id(),
             segment_suffix,
         )?;
@@ -234,6 +268,10 @@ impl FieldInfosFormat for Lucene60FieldInfosFormat {
             if point_dimension_count > 0 {
                 output.write_vint(fi.point_num_bytes as i32)?;
             }
+            if format >= FORMAT_VECTOR_VALUES {
+                output.write_vint(fi.vector_dimension as i32)?;
+                output.write_byte(fi.vector_similarity_function.ord())?;
+            }
         }
 
         codec_util::write_footer(&mut output)
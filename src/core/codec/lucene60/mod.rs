@@ -11,6 +11,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod codec;
+
+pub use self::codec::*;
+
 mod field_infos;
 
 pub use self::field_infos::*;
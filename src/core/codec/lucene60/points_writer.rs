@@ -48,7 +48,7 @@ impl<D: Directory, DW: Directory, C: Codec> Lucene60PointsWriter<D, DW, C> {
         write_state: &SegmentWriteState<D, DW, C>,
     ) -> Result<Lucene60PointsWriter<D, DW, C>> {
         let write_state = write_state.clone();
-        debug_assert!(write_state.field_infos.has_doc_values);
+        debug_assert!(write_state.field_infos.has_point_values);
         let data_file_name = segment_file_name(
             &write_state.segment_info.name,
             &write_state.segment_suffix,
@@ -21,6 +21,7 @@ use core::codec::lucene53::Lucene53NormsFormat;
 use core::codec::lucene60::Lucene60FieldInfosFormat;
 use core::codec::lucene60::Lucene60PointsFormat;
 use core::codec::lucene62::Lucene62SegmentInfoFormat;
+use core::codec::lucene90::Lucene90VectorFormat;
 use core::codec::Codec;
 use core::codec::{PerFieldDocValuesFormat, PerFieldPostingsFormat};
 
@@ -41,6 +42,7 @@ pub struct Lucene62Codec {
     stored_fields_format: Lucene50StoredFieldsFormat,
     norms_format: Lucene53NormsFormat,
     points_format: Lucene60PointsFormat,
+    vector_format: Lucene90VectorFormat,
 }
 
 impl Default for Lucene62Codec {
@@ -58,10 +60,30 @@ impl Default for Lucene62Codec {
             doc_values_format: PerFieldDocValuesFormat::default(),
             norms_format: Lucene53NormsFormat::default(),
             points_format: Lucene60PointsFormat {},
+            vector_format: Lucene90VectorFormat {},
         }
     }
 }
 
+impl Lucene62Codec {
+    /// Writes the stored fields with the given mode, the mode is recorded per
+    /// segment so the default codec can read them back.
+    pub fn with_stored_fields_mode(mut self, mode: StoredFieldCompressMode) -> Lucene62Codec {
+        self.stored_fields_format = Lucene50StoredFieldsFormat::new(Some(mode));
+        self
+    }
+
+    /// Writes the postings with the given format, e.g. one created by
+    /// `PerFieldPostingsFormat::with_term_block_size`.
+    pub fn with_postings_format(
+        mut self,
+        postings_format: PerFieldPostingsFormat,
+    ) -> Lucene62Codec {
+        self.postings_format = postings_format;
+        self
+    }
+}
+
 impl Codec for Lucene62Codec {
     type FieldsProducer = Arc<PerFieldFieldsReader>;
     type PostingFmt = PerFieldPostingsFormat;
@@ -74,6 +96,7 @@ impl Codec for Lucene62Codec {
     type LiveDocFmt = Lucene50LiveDocsFormat;
     type CompoundFmt = Lucene50CompoundFormat;
     type PointFmt = Lucene60PointsFormat;
+    type VectorFmt = Lucene90VectorFormat;
 
     fn name(&self) -> &str {
         "Lucene62"
@@ -118,6 +141,10 @@ impl Codec for Lucene62Codec {
     fn points_format(&self) -> Self::PointFmt {
         self.points_format
     }
+
+    fn vector_format(&self) -> Self::VectorFmt {
+        self.vector_format
+    }
 }
 
 impl TryFrom<String> for Lucene62Codec {
@@ -134,3 +161,17 @@ impl TryFrom<String> for Lucene62Codec {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::codec::tests::check_back_compat_index;
+
+    // the indexes are written by Lucene 6.2 to 6.6, see
+    // src/core/codec/back_compat/GenerateBackCompatIndex.java
+    #[test]
+    #[ignore]
+    fn test_read_lucene6_6_index() {
+        check_back_compat_index("6.6.6-cfs", "Lucene62");
+        check_back_compat_index("6.6.6-nocfs", "Lucene62");
+    }
+}
@@ -35,27 +35,9 @@ const VERSION_START: i32 = 0;
 const VERSION_MULTI_VALUED_SORT: i32 = 1;
 const VERSION_CURRENT: i32 = VERSION_MULTI_VALUED_SORT;
 
-fn read_segment_info_from_index<D: Directory, C: Codec>(
-    input: &mut dyn IndexInput,
-    dir: &Arc<D>,
-    segment: &str,
-    id: [u8; ID_LENGTH],
-) -> Result<SegmentInfo<D, C>> {
-    codec_util::check_index_header(input, CODEC_NAME, VERSION_START, VERSION_CURRENT, &id, "")?;
-    let major = input.read_int()?;
-    let minor = input.read_int()?;
-    let bugfix = input.read_int()?;
-    let version = Version::new(major, minor, bugfix)?;
-    let doc_count = input.read_int()?;
-    if doc_count < 0 {
-        bail!(CorruptIndex(format!("invalid docCount: {}", doc_count)));
-    }
-    let is_compound_file = input.read_byte()? == SEGMENT_USE_COMPOUND_YES;
-
-    let diagnostics = input.read_map_of_strings()?;
-    let files = input.read_set_of_strings()?;
-    let attributes = input.read_map_of_strings()?;
-
+/// Reads the index sort of a segment, which is written the same way by the
+/// segment infos of Lucene 6.2+ and 7.x.
+pub(crate) fn read_index_sort(input: &mut dyn IndexInput) -> Result<Option<Sort>> {
     let num_sort_fields = input.read_vint()?;
     let mut index_sort = None;
     if num_sort_fields > 0 {
@@ -210,6 +192,32 @@ fn read_segment_info_from_index<D: Directory, C: Codec>(
         )));
     }
 
+    Ok(index_sort)
+}
+
+fn read_segment_info_from_index<D: Directory, C: Codec>(
+    input: &mut dyn IndexInput,
+    dir: &Arc<D>,
+    segment: &str,
+    id: [u8; ID_LENGTH],
+) -> Result<SegmentInfo<D, C>> {
+    codec_util::check_index_header(input, CODEC_NAME, VERSION_START, VERSION_CURRENT, &id, "")?;
+    let major = input.read_int()?;
+    let minor = input.read_int()?;
+    let bugfix = input.read_int()?;
+    let version = Version::new(major, minor, bugfix)?;
+    let doc_count = input.read_int()?;
+    if doc_count < 0 {
+        bail!(CorruptIndex(format!("invalid docCount: {}", doc_count)));
+    }
+    let is_compound_file = input.read_byte()? == SEGMENT_USE_COMPOUND_YES;
+
+    let diagnostics = input.read_map_of_strings()?;
+    let files = input.read_set_of_strings()?;
+    let attributes = input.read_map_of_strings()?;
+
+    let index_sort = read_index_sort(input)?;
+
     let mut si = SegmentInfo::new(
         version,
         segment,
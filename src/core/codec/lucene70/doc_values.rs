@@ -0,0 +1,74 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::format::{DocValuesConsumerEnum, DocValuesFormat};
+use core::codec::lucene70::Lucene70DocValuesProducer;
+use core::codec::{Codec, DocValuesProducer};
+use core::index::{SegmentReadState, SegmentWriteState};
+use core::store::Directory;
+use error::{ErrorKind::UnsupportedOperation, Result};
+
+/// Lucene 7.0 doc values format, only used to read the segments of Lucene 7.
+///
+/// Unlike `Lucene54DocValuesFormat`, the docs that have a value are recorded
+/// in an `IndexedDISI` and only their values are stored, in doc id order, so
+/// sparse fields don't take space for the docs without a value.
+#[derive(Copy, Clone, Default)]
+pub struct Lucene70DocValuesFormat;
+
+impl Lucene70DocValuesFormat {
+    pub(crate) const DATA_CODEC: &'static str = "Lucene70DocValuesData";
+    pub(crate) const DATA_EXTENSION: &'static str = "dvd";
+    pub(crate) const META_CODEC: &'static str = "Lucene70DocValuesMetadata";
+    pub(crate) const META_EXTENSION: &'static str = "dvm";
+    pub(crate) const VERSION_START: i32 = 0;
+    pub(crate) const VERSION_CURRENT: i32 = Self::VERSION_START;
+
+    // indicates docvalues type
+    pub(crate) const NUMERIC: u8 = 0;
+    pub(crate) const BINARY: u8 = 1;
+    pub(crate) const SORTED: u8 = 2;
+    pub(crate) const SORTED_SET: u8 = 3;
+    pub(crate) const SORTED_NUMERIC: u8 = 4;
+
+    // docs with field offset of fields without any value
+    pub(crate) const ALL_MISSING: i64 = -2;
+    // docs with field offset of fields with a value for every doc
+    pub(crate) const ALL_LIVE: i64 = -1;
+}
+
+impl DocValuesFormat for Lucene70DocValuesFormat {
+    fn name(&self) -> &str {
+        "Lucene70"
+    }
+
+    fn read_only(&self) -> bool {
+        true
+    }
+
+    fn fields_producer<'a, D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'a, D, DW, C>,
+    ) -> Result<Box<dyn DocValuesProducer>> {
+        Ok(Box::new(Lucene70DocValuesProducer::new(state)?))
+    }
+
+    fn fields_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<DocValuesConsumerEnum<D, DW, C>> {
+        bail!(UnsupportedOperation(
+            "Lucene70DocValuesFormat can only be used for reading".into()
+        ))
+    }
+}
@@ -0,0 +1,1404 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::lucene70::{
+    IndexedDISI, Lucene70DocValuesFormat, SparseDISIAddresses, SparseDISIValues,
+};
+use core::codec::{codec_util, Codec, DocValuesProducer, LiveBitsEnum};
+use core::index::{
+    segment_file_name, AddressedRandomAccessOrds, AddressedSortedNumericDocValues, BinaryDocValues,
+    DocValues, DocValuesType, EmptyBinaryDocValues, EmptyNumericDocValues, EmptySortedDocValues,
+    EmptySortedSetDocValues, FieldInfo, FieldInfos, FixedBinaryDocValues, LongBinaryDocValues,
+    NumericDocValues, NumericDocValuesContext, SegmentReadState, SortedDocValues,
+    SortedNumericDocValues, SortedSetDocValues, TailoredSortedDocValues, VariableBinaryDocValues,
+};
+use core::store::{BufferedChecksumIndexInput, Directory, IndexInput, RandomAccessInput};
+use core::util::packed::{
+    DirectMonotonicMeta, DirectMonotonicReader, DirectPackedReader, DirectReader,
+    MixinMonotonicLongValues,
+};
+use core::util::{
+    Accountable, BitsRef, DocId, EmptyLongValues, GcdLongValues, LiveLongValues, LongValues,
+    LongValuesContext, MatchAllBits, MatchNoBits, RamUsage, TableLongValues,
+};
+use error::ErrorKind::{CorruptIndex, IllegalArgument};
+use error::Result;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// metadata entry for a numeric docvalues field
+struct NumericEntry {
+    docs_with_field_offset: i64,
+    docs_with_field_length: i64,
+    num_values: i64,
+    table: Option<Vec<i64>>,
+    // values are split in blocks of `1 << block_shift` values with their own
+    // bits per value if not -1
+    block_shift: i32,
+    bits_per_value: i32,
+    min_value: i64,
+    gcd: i64,
+    values_offset: i64,
+    values_length: i64,
+}
+
+/// metadata entry for a binary docvalues field
+struct BinaryEntry {
+    data_offset: i64,
+    data_length: i64,
+    docs_with_field_offset: i64,
+    docs_with_field_length: i64,
+    num_docs_with_field: i32,
+    min_length: i32,
+    max_length: i32,
+    addresses_offset: i64,
+    addresses_length: i64,
+    addresses_meta: Option<DirectMonotonicMeta>,
+}
+
+/// metadata of the terms of a sorted or sorted set field, the terms are
+/// prefix compressed by blocks of `1 << block_shift` terms
+struct TermsDictEntry {
+    size: i64,
+    block_shift: i32,
+    addresses_meta: DirectMonotonicMeta,
+    data_offset: i64,
+    data_length: i64,
+    addresses_offset: i64,
+    addresses_length: i64,
+}
+
+/// metadata entry for a sorted docvalues field
+struct SortedEntry {
+    docs_with_field_offset: i64,
+    docs_with_field_length: i64,
+    num_docs_with_field: i32,
+    bits_per_value: i32,
+    ords_offset: i64,
+    ords_length: i64,
+    terms_dict: TermsDictEntry,
+}
+
+/// metadata entry for a sorted set docvalues field with several values per doc
+struct SortedSetEntry {
+    docs_with_field_offset: i64,
+    docs_with_field_length: i64,
+    bits_per_value: i32,
+    ords_offset: i64,
+    ords_length: i64,
+    num_docs_with_field: i32,
+    addresses_offset: i64,
+    addresses_length: i64,
+    addresses_meta: DirectMonotonicMeta,
+    terms_dict: TermsDictEntry,
+}
+
+/// metadata entry for a sorted numeric docvalues field, the addresses are
+/// only written if some doc has several values
+struct SortedNumericEntry {
+    numeric: NumericEntry,
+    num_docs_with_field: i32,
+    addresses_offset: i64,
+    addresses_length: i64,
+    addresses_meta: Option<DirectMonotonicMeta>,
+}
+
+#[derive(Default)]
+struct Entries {
+    numerics: HashMap<String, NumericEntry>,
+    binaries: HashMap<String, BinaryEntry>,
+    sorted: HashMap<String, SortedEntry>,
+    // the single valued sorted sets are written as sorted fields
+    sorted_sets: HashMap<String, SortedSetEntry>,
+    sorted_numerics: HashMap<String, SortedNumericEntry>,
+}
+
+/// Reader for the doc values of `Lucene70DocValuesFormat`.
+pub struct Lucene70DocValuesProducer {
+    max_doc: DocId,
+    data: Box<dyn IndexInput>,
+    merging: bool,
+    entries: Arc<Entries>,
+    disi_instances: RwLock<HashMap<String, Arc<IndexedDISI>>>,
+}
+
+impl Lucene70DocValuesProducer {
+    pub fn new<D: Directory, DW: Directory, C: Codec>(
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Lucene70DocValuesProducer> {
+        let meta_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            Lucene70DocValuesFormat::META_EXTENSION,
+        );
+        let input = state.directory.open_input(&meta_name, &state.context)?;
+        let mut checksum_input = BufferedChecksumIndexInput::new(input);
+        let version = codec_util::check_index_header(
+            &mut checksum_input,
+            Lucene70DocValuesFormat::META_CODEC,
+            Lucene70DocValuesFormat::VERSION_START,
+            Lucene70DocValuesFormat::VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        let entries = Self::read_fields(&mut checksum_input, &state.field_infos)?;
+        codec_util::check_footer(&mut checksum_input)?;
+
+        let data_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            Lucene70DocValuesFormat::DATA_EXTENSION,
+        );
+        let mut data = state.directory.open_input(&data_name, &state.context)?;
+        let version2 = codec_util::check_index_header(
+            data.as_mut(),
+            Lucene70DocValuesFormat::DATA_CODEC,
+            Lucene70DocValuesFormat::VERSION_START,
+            Lucene70DocValuesFormat::VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        if version != version2 {
+            bail!(CorruptIndex(format!(
+                "Format versions mismatch: meta={}, data={}",
+                version, version2
+            )));
+        }
+        codec_util::retrieve_checksum(data.as_mut())?;
+
+        Ok(Lucene70DocValuesProducer {
+            max_doc: state.segment_info.max_doc(),
+            data,
+            merging: false,
+            entries: Arc::new(entries),
+            disi_instances: RwLock::new(HashMap::new()),
+        })
+    }
+
+    fn copy_from(producer: &Lucene70DocValuesProducer) -> Result<Lucene70DocValuesProducer> {
+        Ok(Lucene70DocValuesProducer {
+            max_doc: producer.max_doc,
+            data: producer.data.clone()?,
+            merging: true,
+            entries: Arc::clone(&producer.entries),
+            disi_instances: RwLock::new(producer.disi_instances.read()?.clone()),
+        })
+    }
+
+    fn read_fields(meta: &mut dyn IndexInput, infos: &FieldInfos) -> Result<Entries> {
+        let mut entries = Entries::default();
+        loop {
+            let field_number = meta.read_int()?;
+            if field_number == -1 {
+                break;
+            }
+            let info = infos
+                .field_info_by_number(field_number as u32)
+                .ok_or_else(|| {
+                    IllegalArgument(format!("invalid field number: {}", field_number))
+                })?;
+            let name = info.name.clone();
+            match meta.read_byte()? {
+                Lucene70DocValuesFormat::NUMERIC => {
+                    entries.numerics.insert(name, Self::read_numeric(meta)?);
+                }
+                Lucene70DocValuesFormat::BINARY => {
+                    entries.binaries.insert(name, Self::read_binary(meta)?);
+                }
+                Lucene70DocValuesFormat::SORTED => {
+                    entries.sorted.insert(name, Self::read_sorted(meta)?);
+                }
+                Lucene70DocValuesFormat::SORTED_SET => match meta.read_byte()? {
+                    0 => {
+                        entries.sorted.insert(name, Self::read_sorted(meta)?);
+                    }
+                    1 => {
+                        entries
+                            .sorted_sets
+                            .insert(name, Self::read_sorted_set(meta)?);
+                    }
+                    b => bail!(CorruptIndex(format!("invalid multiValued flag: {}", b))),
+                },
+                Lucene70DocValuesFormat::SORTED_NUMERIC => {
+                    entries
+                        .sorted_numerics
+                        .insert(name, Self::read_sorted_numeric(meta)?);
+                }
+                t => bail!(CorruptIndex(format!(
+                    "invalid type {} of field {}",
+                    t, info.name
+                ))),
+            }
+        }
+        Ok(entries)
+    }
+
+    fn read_numeric(meta: &mut dyn IndexInput) -> Result<NumericEntry> {
+        let docs_with_field_offset = meta.read_long()?;
+        let docs_with_field_length = meta.read_long()?;
+        let num_values = meta.read_long()?;
+        let table_size = meta.read_int()?;
+        if table_size > 256 {
+            bail!(CorruptIndex(format!("invalid table size: {}", table_size)));
+        }
+        let table = if table_size >= 0 {
+            let mut table = Vec::with_capacity(table_size as usize);
+            for _ in 0..table_size {
+                table.push(meta.read_long()?);
+            }
+            Some(table)
+        } else {
+            None
+        };
+        let block_shift = if table_size < -1 { -2 - table_size } else { -1 };
+        Ok(NumericEntry {
+            docs_with_field_offset,
+            docs_with_field_length,
+            num_values,
+            table,
+            block_shift,
+            bits_per_value: i32::from(meta.read_byte()?),
+            min_value: meta.read_long()?,
+            gcd: meta.read_long()?,
+            values_offset: meta.read_long()?,
+            values_length: meta.read_long()?,
+        })
+    }
+
+    fn read_binary(meta: &mut dyn IndexInput) -> Result<BinaryEntry> {
+        let data_offset = meta.read_long()?;
+        let data_length = meta.read_long()?;
+        let docs_with_field_offset = meta.read_long()?;
+        let docs_with_field_length = meta.read_long()?;
+        let num_docs_with_field = meta.read_int()?;
+        let min_length = meta.read_int()?;
+        let max_length = meta.read_int()?;
+        let mut entry = BinaryEntry {
+            data_offset,
+            data_length,
+            docs_with_field_offset,
+            docs_with_field_length,
+            num_docs_with_field,
+            min_length,
+            max_length,
+            addresses_offset: 0,
+            addresses_length: 0,
+            addresses_meta: None,
+        };
+        if min_length < max_length {
+            entry.addresses_offset = meta.read_long()?;
+            let block_shift = meta.read_vint()?;
+            entry.addresses_meta = Some(DirectMonotonicReader::load_meta(
+                meta,
+                i64::from(num_docs_with_field) + 1,
+                block_shift,
+            )?);
+            entry.addresses_length = meta.read_long()?;
+        }
+        Ok(entry)
+    }
+
+    fn read_sorted(meta: &mut dyn IndexInput) -> Result<SortedEntry> {
+        Ok(SortedEntry {
+            docs_with_field_offset: meta.read_long()?,
+            docs_with_field_length: meta.read_long()?,
+            num_docs_with_field: meta.read_int()?,
+            bits_per_value: i32::from(meta.read_byte()?),
+            ords_offset: meta.read_long()?,
+            ords_length: meta.read_long()?,
+            terms_dict: Self::read_terms_dict(meta)?,
+        })
+    }
+
+    fn read_sorted_set(meta: &mut dyn IndexInput) -> Result<SortedSetEntry> {
+        let docs_with_field_offset = meta.read_long()?;
+        let docs_with_field_length = meta.read_long()?;
+        let bits_per_value = i32::from(meta.read_byte()?);
+        let ords_offset = meta.read_long()?;
+        let ords_length = meta.read_long()?;
+        let num_docs_with_field = meta.read_int()?;
+        let addresses_offset = meta.read_long()?;
+        let block_shift = meta.read_vint()?;
+        let addresses_meta = DirectMonotonicReader::load_meta(
+            meta,
+            i64::from(num_docs_with_field) + 1,
+            block_shift,
+        )?;
+        let addresses_length = meta.read_long()?;
+        Ok(SortedSetEntry {
+            docs_with_field_offset,
+            docs_with_field_length,
+            bits_per_value,
+            ords_offset,
+            ords_length,
+            num_docs_with_field,
+            addresses_offset,
+            addresses_length,
+            addresses_meta,
+            terms_dict: Self::read_terms_dict(meta)?,
+        })
+    }
+
+    fn read_sorted_numeric(meta: &mut dyn IndexInput) -> Result<SortedNumericEntry> {
+        let numeric = Self::read_numeric(meta)?;
+        let num_docs_with_field = meta.read_int()?;
+        let mut entry = SortedNumericEntry {
+            numeric,
+            num_docs_with_field,
+            addresses_offset: 0,
+            addresses_length: 0,
+            addresses_meta: None,
+        };
+        if i64::from(num_docs_with_field) != entry.numeric.num_values {
+            entry.addresses_offset = meta.read_long()?;
+            let block_shift = meta.read_vint()?;
+            entry.addresses_meta = Some(DirectMonotonicReader::load_meta(
+                meta,
+                i64::from(num_docs_with_field) + 1,
+                block_shift,
+            )?);
+            entry.addresses_length = meta.read_long()?;
+        }
+        Ok(entry)
+    }
+
+    fn read_terms_dict(meta: &mut dyn IndexInput) -> Result<TermsDictEntry> {
+        let size = meta.read_vlong()?;
+        let block_shift = meta.read_int()?;
+        let addresses_block_shift = meta.read_int()?;
+        let num_blocks = (size + (1i64 << block_shift) - 1) >> block_shift;
+        let addresses_meta =
+            DirectMonotonicReader::load_meta(meta, num_blocks, addresses_block_shift)?;
+        let _max_term_length = meta.read_int()?;
+        let data_offset = meta.read_long()?;
+        let data_length = meta.read_long()?;
+        let addresses_offset = meta.read_long()?;
+        let addresses_length = meta.read_long()?;
+        // the reverse index of every 1024th term only speeds up seeking, the
+        // terms are looked up with a binary search over the blocks instead
+        let index_shift = meta.read_int()?;
+        let index_size = (size + (1i64 << index_shift) - 1) >> index_shift;
+        DirectMonotonicReader::load_meta(meta, 1 + index_size, addresses_block_shift)?;
+        for _ in 0..4 {
+            meta.read_long()?;
+        }
+        Ok(TermsDictEntry {
+            size,
+            block_shift,
+            addresses_meta,
+            data_offset,
+            data_length,
+            addresses_offset,
+            addresses_length,
+        })
+    }
+}
+
+impl Lucene70DocValuesProducer {
+    fn get_disi(
+        &self,
+        field: &str,
+        offset: i64,
+        length: i64,
+        cost: i64,
+    ) -> Result<Arc<IndexedDISI>> {
+        if let Some(disi) = self.disi_instances.read()?.get(field) {
+            return Ok(Arc::clone(disi));
+        }
+        let disi = Arc::new(IndexedDISI::new(
+            self.data.as_ref(),
+            offset,
+            length,
+            cost,
+            self.max_doc,
+        )?);
+        if !self.merging {
+            self.disi_instances
+                .write()?
+                .insert(field.to_string(), Arc::clone(&disi));
+        }
+        Ok(disi)
+    }
+
+    fn docs_with_field(&self, field: &str, offset: i64, length: i64, cost: i64) -> Result<BitsRef> {
+        match offset {
+            Lucene70DocValuesFormat::ALL_MISSING => {
+                Ok(Arc::new(MatchNoBits::new(self.max_doc as usize)))
+            }
+            Lucene70DocValuesFormat::ALL_LIVE => {
+                Ok(Arc::new(MatchAllBits::new(self.max_doc as usize)))
+            }
+            _ => Ok(self.get_disi(field, offset, length, cost)?),
+        }
+    }
+
+    fn random_access_slice(&self, offset: i64, length: i64) -> Result<Arc<dyn RandomAccessInput>> {
+        Ok(Arc::from(self.data.random_access_slice(offset, length)?))
+    }
+
+    fn monotonic_values(
+        &self,
+        meta: &DirectMonotonicMeta,
+        offset: i64,
+        length: i64,
+    ) -> Result<MixinMonotonicLongValues> {
+        DirectMonotonicReader::get_instance(meta, &self.random_access_slice(offset, length)?)
+    }
+
+    /// The values of a numeric entry, in order of the docs that have a value.
+    fn numeric_values(&self, entry: &NumericEntry) -> Result<NumericValues> {
+        if entry.bits_per_value == 0 {
+            let all = LiveBitsEnum::All(MatchAllBits::new(entry.num_values as usize));
+            return Ok(NumericValues::Constant(LiveLongValues::new(
+                all,
+                entry.min_value,
+            )));
+        }
+        let slice = self.random_access_slice(entry.values_offset, entry.values_length)?;
+        if entry.block_shift >= 0 {
+            return Ok(NumericValues::Blocks(BlockLongValues::new(
+                slice,
+                entry.num_values,
+                entry.block_shift,
+                entry.gcd,
+            )?));
+        }
+        let values = DirectReader::get_instance(slice, entry.bits_per_value, 0)?;
+        Ok(match entry.table {
+            Some(ref table) => NumericValues::Table(TableLongValues::new(values, table.clone())),
+            None => NumericValues::Gcd(GcdLongValues::new(values, entry.min_value, entry.gcd)),
+        })
+    }
+
+    fn get_numeric_entry(
+        &self,
+        field: &str,
+        entry: &NumericEntry,
+    ) -> Result<Box<dyn NumericDocValues>> {
+        match entry.docs_with_field_offset {
+            Lucene70DocValuesFormat::ALL_MISSING => Ok(Box::new(EmptyNumericDocValues {})),
+            Lucene70DocValuesFormat::ALL_LIVE => Ok(Box::new(self.numeric_values(entry)?)),
+            _ => {
+                let disi = self.get_disi(
+                    field,
+                    entry.docs_with_field_offset,
+                    entry.docs_with_field_length,
+                    entry.num_values,
+                )?;
+                let values = Box::new(self.numeric_values(entry)?);
+                Ok(Box::new(SparseDISIValues::new(disi, values, 0)))
+            }
+        }
+    }
+
+    /// The ordinals of a sorted or sorted set entry, in order of their values.
+    fn ords_values(
+        &self,
+        bits_per_value: i32,
+        offset: i64,
+        length: i64,
+    ) -> Result<Box<dyn LongValues>> {
+        if bits_per_value == 0 {
+            Ok(Box::new(EmptyLongValues {}))
+        } else {
+            let slice = self.random_access_slice(offset, length)?;
+            Ok(Box::new(DirectReader::get_instance(
+                slice,
+                bits_per_value,
+                0,
+            )?))
+        }
+    }
+
+    fn terms_dict(&self, entry: &TermsDictEntry) -> Result<TermsDict> {
+        let addresses = self.monotonic_values(
+            &entry.addresses_meta,
+            entry.addresses_offset,
+            entry.addresses_length,
+        )?;
+        let bytes = self
+            .data
+            .slice("terms", entry.data_offset, entry.data_length)?;
+        Ok(TermsDict {
+            size: entry.size,
+            block_shift: entry.block_shift,
+            addresses,
+            bytes: Mutex::new(bytes),
+        })
+    }
+
+    fn get_sorted_entry(
+        &self,
+        field: &str,
+        entry: &SortedEntry,
+    ) -> Result<Arc<dyn SortedDocValues>> {
+        if entry.docs_with_field_offset == Lucene70DocValuesFormat::ALL_MISSING {
+            return Ok(Arc::new(EmptySortedDocValues {}));
+        }
+        let mut ords =
+            self.ords_values(entry.bits_per_value, entry.ords_offset, entry.ords_length)?;
+        if entry.docs_with_field_offset != Lucene70DocValuesFormat::ALL_LIVE {
+            let disi = self.get_disi(
+                field,
+                entry.docs_with_field_offset,
+                entry.docs_with_field_length,
+                i64::from(entry.num_docs_with_field),
+            )?;
+            ords = Box::new(SparseDISIValues::new(disi, ords, -1));
+        }
+        let terms = self.terms_dict(&entry.terms_dict)?;
+        Ok(Arc::new(TailoredSortedDocValues::new(
+            ords,
+            Box::new(terms),
+            entry.terms_dict.size as usize,
+        )))
+    }
+}
+
+impl Accountable for Lucene70DocValuesProducer {
+    /// The docs with field loaded so far, the values themselves are read from
+    /// the data file.
+    fn ram_bytes_used(&self) -> usize {
+        self.disi_instances
+            .read()
+            .unwrap()
+            .values()
+            .map(|d| d.ram_bytes_used())
+            .sum()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.disi_instances
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(field, disi)| RamUsage::of(format!("docs with field '{}'", field), disi))
+            .collect()
+    }
+}
+
+impl DocValuesProducer for Lucene70DocValuesProducer {
+    fn get_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
+        let entry = self.entries.numerics.get(&field.name).ok_or_else(|| {
+            IllegalArgument(format!("No numeric field named {} found", field.name))
+        })?;
+        Ok(Arc::from(self.get_numeric_entry(&field.name, entry)?))
+    }
+
+    fn get_binary(&self, field: &FieldInfo) -> Result<Arc<dyn BinaryDocValues>> {
+        let entry = self
+            .entries
+            .binaries
+            .get(&field.name)
+            .ok_or_else(|| IllegalArgument(format!("No binary field named {}", field.name)))?;
+        if entry.docs_with_field_offset == Lucene70DocValuesFormat::ALL_MISSING {
+            return Ok(Arc::new(EmptyBinaryDocValues {}));
+        }
+        let data = self
+            .data
+            .slice("binary", entry.data_offset, entry.data_length)?;
+        let values: Box<dyn LongBinaryDocValues> = match entry.addresses_meta {
+            None => Box::new(FixedBinaryDocValues::new(data, entry.max_length as usize)),
+            Some(ref meta) => {
+                let addresses =
+                    self.monotonic_values(meta, entry.addresses_offset, entry.addresses_length)?;
+                Box::new(VariableBinaryDocValues::new(
+                    addresses,
+                    data,
+                    entry.max_length as usize,
+                ))
+            }
+        };
+        debug_assert!(entry.min_length <= entry.max_length);
+        let disi = if entry.docs_with_field_offset == Lucene70DocValuesFormat::ALL_LIVE {
+            None
+        } else {
+            Some(self.get_disi(
+                &field.name,
+                entry.docs_with_field_offset,
+                entry.docs_with_field_length,
+                i64::from(entry.num_docs_with_field),
+            )?)
+        };
+        Ok(Arc::new(Lucene70BinaryDocValues { disi, values }))
+    }
+
+    fn get_sorted(&self, field: &FieldInfo) -> Result<Arc<dyn SortedDocValues>> {
+        let entry = self
+            .entries
+            .sorted
+            .get(&field.name)
+            .ok_or_else(|| IllegalArgument(format!("No sorted field named {}", field.name)))?;
+        self.get_sorted_entry(&field.name, entry)
+    }
+
+    fn get_sorted_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn SortedNumericDocValues>> {
+        let entry = self
+            .entries
+            .sorted_numerics
+            .get(&field.name)
+            .ok_or_else(|| {
+                IllegalArgument(format!("No SortedNumeric field named {}", field.name))
+            })?;
+        let numeric = &entry.numeric;
+        let addresses_meta = match entry.addresses_meta {
+            None => {
+                let values = self.get_numeric_entry(&field.name, numeric)?;
+                let docs_with_field = self.docs_with_field(
+                    &field.name,
+                    numeric.docs_with_field_offset,
+                    numeric.docs_with_field_length,
+                    numeric.num_values,
+                )?;
+                return Ok(Arc::new(DocValues::singleton_sorted_numeric_doc_values(
+                    values,
+                    docs_with_field,
+                )));
+            }
+            Some(ref meta) => meta,
+        };
+        let values = Box::new(self.numeric_values(numeric)?);
+        let addresses = self.monotonic_values(
+            addresses_meta,
+            entry.addresses_offset,
+            entry.addresses_length,
+        )?;
+        if numeric.docs_with_field_offset == Lucene70DocValuesFormat::ALL_LIVE {
+            Ok(Arc::new(AddressedSortedNumericDocValues::new(
+                values, addresses,
+            )))
+        } else {
+            let disi = self.get_disi(
+                &field.name,
+                numeric.docs_with_field_offset,
+                numeric.docs_with_field_length,
+                i64::from(entry.num_docs_with_field),
+            )?;
+            Ok(Arc::new(AddressedSortedNumericDocValues::new(
+                values,
+                SparseDISIAddresses::new(disi, addresses),
+            )))
+        }
+    }
+
+    fn get_sorted_set(&self, field: &FieldInfo) -> Result<Arc<dyn SortedSetDocValues>> {
+        if let Some(entry) = self.entries.sorted.get(&field.name) {
+            let values = self.get_sorted_entry(&field.name, entry)?;
+            return Ok(Arc::new(DocValues::singleton_sorted_doc_values(values)));
+        }
+        let entry =
+            self.entries.sorted_sets.get(&field.name).ok_or_else(|| {
+                IllegalArgument(format!("No SortedSet field named {}", field.name))
+            })?;
+        if entry.docs_with_field_offset == Lucene70DocValuesFormat::ALL_MISSING {
+            return Ok(Arc::new(EmptySortedSetDocValues {}));
+        }
+        let ords = self.ords_values(entry.bits_per_value, entry.ords_offset, entry.ords_length)?;
+        let addresses = self.monotonic_values(
+            &entry.addresses_meta,
+            entry.addresses_offset,
+            entry.addresses_length,
+        )?;
+        let ord_index: Box<dyn LongValues> =
+            if entry.docs_with_field_offset == Lucene70DocValuesFormat::ALL_LIVE {
+                Box::new(addresses)
+            } else {
+                let disi = self.get_disi(
+                    &field.name,
+                    entry.docs_with_field_offset,
+                    entry.docs_with_field_length,
+                    i64::from(entry.num_docs_with_field),
+                )?;
+                Box::new(SparseDISIAddresses::new(disi, addresses))
+            };
+        let terms = self.terms_dict(&entry.terms_dict)?;
+        Ok(Arc::new(AddressedRandomAccessOrds::new(
+            Box::new(terms),
+            ords,
+            ord_index,
+            entry.terms_dict.size as usize,
+        )))
+    }
+
+    fn get_docs_with_field(&self, field: &FieldInfo) -> Result<BitsRef> {
+        let name = &field.name;
+        let (offset, length, cost) =
+            match field.doc_values_type {
+                DocValuesType::Numeric => {
+                    let e = self.entries.numerics.get(name).ok_or_else(|| {
+                        IllegalArgument(format!("No numeric field named {} found", name))
+                    })?;
+                    (
+                        e.docs_with_field_offset,
+                        e.docs_with_field_length,
+                        e.num_values,
+                    )
+                }
+                DocValuesType::Binary => {
+                    let e = self.entries.binaries.get(name).ok_or_else(|| {
+                        IllegalArgument(format!("No binary field named {}", name))
+                    })?;
+                    let cost = i64::from(e.num_docs_with_field);
+                    (e.docs_with_field_offset, e.docs_with_field_length, cost)
+                }
+                DocValuesType::Sorted | DocValuesType::SortedSet
+                    if self.entries.sorted.contains_key(name) =>
+                {
+                    let e = &self.entries.sorted[name];
+                    let cost = i64::from(e.num_docs_with_field);
+                    (e.docs_with_field_offset, e.docs_with_field_length, cost)
+                }
+                DocValuesType::SortedSet => {
+                    let e = self.entries.sorted_sets.get(name).ok_or_else(|| {
+                        IllegalArgument(format!("No SortedSet field named {}", name))
+                    })?;
+                    let cost = i64::from(e.num_docs_with_field);
+                    (e.docs_with_field_offset, e.docs_with_field_length, cost)
+                }
+                DocValuesType::SortedNumeric => {
+                    let e = self.entries.sorted_numerics.get(name).ok_or_else(|| {
+                        IllegalArgument(format!("No SortedNumeric field named {}", name))
+                    })?;
+                    let cost = i64::from(e.num_docs_with_field);
+                    (
+                        e.numeric.docs_with_field_offset,
+                        e.numeric.docs_with_field_length,
+                        cost,
+                    )
+                }
+                _ => bail!(IllegalArgument(format!(
+                    "Unknown DocValuesType {:?} for field {}",
+                    field.doc_values_type, name
+                ))),
+            };
+        self.docs_with_field(name, offset, length, cost)
+    }
+
+    fn check_integrity(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>> {
+        Ok(Box::new(Lucene70DocValuesProducer::copy_from(self)?))
+    }
+
+    fn evict(&self, field: &FieldInfo) -> Result<()> {
+        self.disi_instances.write()?.remove(&field.name);
+        Ok(())
+    }
+}
+
+/// Numeric values split in blocks of `1 << shift` values, each block having
+/// its own bits per value and delta, the values being multiplied by `gcd`.
+struct BlockLongValues {
+    blocks: Vec<(Option<DirectPackedReader>, i64)>,
+    shift: i32,
+    gcd: i64,
+}
+
+impl BlockLongValues {
+    fn new(
+        slice: Arc<dyn RandomAccessInput>,
+        num_values: i64,
+        shift: i32,
+        gcd: i64,
+    ) -> Result<BlockLongValues> {
+        let num_blocks = (num_values + (1i64 << shift) - 1) >> shift;
+        let mut blocks = Vec::with_capacity(num_blocks as usize);
+        let mut offset = 0i64;
+        for _ in 0..num_blocks {
+            let bits_per_value = i32::from(slice.read_byte(offset)?);
+            let delta = slice.read_long(offset + 1)?;
+            offset += 9;
+            if bits_per_value == 0 {
+                blocks.push((None, delta));
+            } else {
+                let length = slice.read_int(offset)?;
+                offset += 4;
+                let values =
+                    DirectReader::get_instance(Arc::clone(&slice), bits_per_value, offset)?;
+                blocks.push((Some(values), delta));
+                offset += i64::from(length);
+            }
+        }
+        Ok(BlockLongValues { blocks, shift, gcd })
+    }
+}
+
+impl LongValues for BlockLongValues {
+    fn get64_with_ctx(
+        &self,
+        ctx: LongValuesContext,
+        index: i64,
+    ) -> Result<(i64, LongValuesContext)> {
+        let (ref values, delta) = self.blocks[(index >> self.shift) as usize];
+        let (value, ctx) = match values {
+            Some(values) => values.get64_with_ctx(ctx, index & ((1i64 << self.shift) - 1))?,
+            None => (0, ctx),
+        };
+        Ok((self.gcd * value + delta, ctx))
+    }
+}
+
+impl NumericDocValues for BlockLongValues {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        self.get64_with_ctx(ctx, i64::from(doc_id))
+    }
+}
+
+enum NumericValues {
+    Constant(LiveLongValues),
+    Blocks(BlockLongValues),
+    Table(TableLongValues),
+    Gcd(GcdLongValues),
+}
+
+impl LongValues for NumericValues {
+    fn get64_with_ctx(
+        &self,
+        ctx: LongValuesContext,
+        index: i64,
+    ) -> Result<(i64, LongValuesContext)> {
+        match self {
+            NumericValues::Constant(v) => v.get64_with_ctx(ctx, index),
+            NumericValues::Blocks(v) => v.get64_with_ctx(ctx, index),
+            NumericValues::Table(v) => v.get64_with_ctx(ctx, index),
+            NumericValues::Gcd(v) => v.get64_with_ctx(ctx, index),
+        }
+    }
+}
+
+impl NumericDocValues for NumericValues {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        self.get64_with_ctx(ctx, i64::from(doc_id))
+    }
+}
+
+/// Binary values of the docs that have one, in doc id order.
+struct Lucene70BinaryDocValues {
+    disi: Option<Arc<IndexedDISI>>,
+    values: Box<dyn LongBinaryDocValues>,
+}
+
+impl BinaryDocValues for Lucene70BinaryDocValues {
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        match self.disi {
+            None => self.values.get64_into(i64::from(doc_id), value),
+            Some(ref disi) => match disi.rank(doc_id)? {
+                (true, index) => self.values.get64_into(index, value),
+                (false, _) => {
+                    value.clear();
+                    Ok(())
+                }
+            },
+        }
+    }
+}
+
+/// The terms of a sorted or sorted set field, prefix compressed by blocks.
+///
+/// The first term of a block is written as is, the following ones as a
+/// token holding the lengths of the prefix they share with the previous
+/// term and of their suffix, followed by the suffix.
+struct TermsDict {
+    size: i64,
+    block_shift: i32,
+    addresses: MixinMonotonicLongValues,
+    bytes: Mutex<Box<dyn IndexInput>>,
+}
+
+impl LongBinaryDocValues for TermsDict {
+    fn get64_into(&self, ord: i64, value: &mut Vec<u8>) -> Result<()> {
+        if ord < 0 || ord >= self.size {
+            bail!(IllegalArgument(format!(
+                "ord {} out of bounds [0, {})",
+                ord, self.size
+            )));
+        }
+        let mut bytes = self.bytes.lock()?;
+        bytes.seek(self.addresses.get64(ord >> self.block_shift)?)?;
+        let length = bytes.read_vint()? as usize;
+        value.resize(length, 0u8);
+        bytes.read_bytes(value, 0, length)?;
+        for _ in 0..(ord & ((1i64 << self.block_shift) - 1)) {
+            let token = bytes.read_byte()?;
+            let mut prefix_length = (token & 0x0F) as usize;
+            let mut suffix_length = 1 + (token >> 4) as usize;
+            if prefix_length == 15 {
+                prefix_length += bytes.read_vint()? as usize;
+            }
+            if suffix_length == 16 {
+                suffix_length += bytes.read_vint()? as usize;
+            }
+            if prefix_length > value.len() {
+                bail!(CorruptIndex(format!(
+                    "invalid prefix length {} of term {}",
+                    prefix_length, ord
+                )));
+            }
+            value.resize(prefix_length + suffix_length, 0u8);
+            bytes.read_bytes(value, prefix_length, suffix_length)?;
+        }
+        Ok(())
+    }
+}
+
+impl BinaryDocValues for TermsDict {
+    fn get_into(&self, ord: DocId, value: &mut Vec<u8>) -> Result<()> {
+        self.get64_into(i64::from(ord), value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::index::{IndexOptions, SegmentInfo, NO_MORE_ORDS};
+    use core::store::{
+        DataOutput, FSDirectory, FSIndexOutput, IOContext, IndexOutput, NativeFSLockFactory,
+    };
+    use core::util::packed::{DirectMonotonicWriter, DirectWriter};
+    use core::util::string_util::ID_LENGTH;
+    use core::util::VERSION_LATEST;
+    use std::fs;
+
+    const MAX_DOC: DocId = 100;
+    const SEGMENT_ID: [u8; ID_LENGTH] = [7u8; ID_LENGTH];
+
+    /// Writes the doc values of a segment the way Lucene 7 does, so that
+    /// every entry type can be read back without a Lucene 7 index.
+    struct EntriesWriter {
+        meta: FSIndexOutput,
+        data: FSIndexOutput,
+    }
+
+    impl EntriesWriter {
+        fn new(dir: &FSDirectory<NativeFSLockFactory>) -> EntriesWriter {
+            let mut meta = dir.create_output("_0.dvm", &IOContext::Default).unwrap();
+            let mut data = dir.create_output("_0.dvd", &IOContext::Default).unwrap();
+            let version = Lucene70DocValuesFormat::VERSION_CURRENT;
+            let codec = Lucene70DocValuesFormat::META_CODEC;
+            codec_util::write_index_header(&mut meta, codec, version, &SEGMENT_ID, "").unwrap();
+            let codec = Lucene70DocValuesFormat::DATA_CODEC;
+            codec_util::write_index_header(&mut data, codec, version, &SEGMENT_ID, "").unwrap();
+            EntriesWriter { meta, data }
+        }
+
+        fn finish(mut self) {
+            self.meta.write_int(-1).unwrap();
+            codec_util::write_footer(&mut self.meta).unwrap();
+            codec_util::write_footer(&mut self.data).unwrap();
+        }
+
+        fn write_docs_with_field(&mut self, docs: &[DocId]) {
+            let (offset, length) = if docs.is_empty() {
+                (Lucene70DocValuesFormat::ALL_MISSING, 0)
+            } else if docs.len() == MAX_DOC as usize {
+                (Lucene70DocValuesFormat::ALL_LIVE, 0)
+            } else {
+                let offset = self.data.file_pointer();
+                IndexedDISI::write_docs(docs.iter().cloned(), &mut self.data).unwrap();
+                (offset, self.data.file_pointer() - offset)
+            };
+            self.meta.write_long(offset).unwrap();
+            self.meta.write_long(length).unwrap();
+        }
+
+        /// Writes the bits per value, offset and length of packed `values`.
+        fn write_packed(&mut self, values: &[i64]) {
+            let max = values.iter().cloned().max().unwrap_or(0);
+            if max == 0 {
+                self.meta.write_byte(0).unwrap();
+                self.meta.write_long(0).unwrap();
+                self.meta.write_long(0).unwrap();
+                return;
+            }
+            let bits_per_value = DirectWriter::<FSIndexOutput>::unsigned_bits_required(max);
+            let offset = self.data.file_pointer();
+            {
+                let mut writer =
+                    DirectWriter::new(&mut self.data, values.len() as i64, bits_per_value);
+                for &v in values {
+                    writer.add(v).unwrap();
+                }
+                writer.finish().unwrap();
+            }
+            self.meta.write_byte(bits_per_value as u8).unwrap();
+            self.meta.write_long(offset).unwrap();
+            self.meta
+                .write_long(self.data.file_pointer() - offset)
+                .unwrap();
+        }
+
+        /// Writes the block shift and the meta of monotonic `values`,
+        /// between the offset and the length of their data if `offsets`.
+        fn write_monotonic(&mut self, values: &[i64], offsets: bool) {
+            let offset = self.data.file_pointer();
+            if offsets {
+                self.meta.write_long(offset).unwrap();
+                self.meta.write_vint(4).unwrap();
+            }
+            {
+                let mut writer = DirectMonotonicWriter::new(
+                    &mut self.meta,
+                    &mut self.data,
+                    values.len() as i64,
+                    4,
+                )
+                .unwrap();
+                for &v in values {
+                    writer.add(v).unwrap();
+                }
+                writer.finish().unwrap();
+            }
+            if offsets {
+                self.meta
+                    .write_long(self.data.file_pointer() - offset)
+                    .unwrap();
+            }
+        }
+
+        fn write_numeric(&mut self, field: i32, docs: &[DocId], values: &[i64]) {
+            self.meta.write_int(field).unwrap();
+            self.meta
+                .write_byte(Lucene70DocValuesFormat::NUMERIC)
+                .unwrap();
+            self.write_numeric_entry(docs, values);
+        }
+
+        fn write_numeric_entry(&mut self, docs: &[DocId], values: &[i64]) {
+            self.write_docs_with_field(docs);
+            self.meta.write_long(values.len() as i64).unwrap();
+            // no table
+            self.meta.write_int(-1).unwrap();
+            let min = values.iter().cloned().min().unwrap_or(0);
+            let deltas: Vec<i64> = values.iter().map(|v| v - min).collect();
+            let max_delta = deltas.iter().cloned().max().unwrap_or(0);
+            let bits_per_value = if max_delta == 0 {
+                0
+            } else {
+                DirectWriter::<FSIndexOutput>::unsigned_bits_required(max_delta)
+            };
+            self.meta.write_byte(bits_per_value as u8).unwrap();
+            self.meta.write_long(min).unwrap();
+            // gcd
+            self.meta.write_long(1).unwrap();
+            let offset = self.data.file_pointer();
+            if bits_per_value > 0 {
+                let mut writer =
+                    DirectWriter::new(&mut self.data, deltas.len() as i64, bits_per_value);
+                for &v in &deltas {
+                    writer.add(v).unwrap();
+                }
+                writer.finish().unwrap();
+            }
+            self.meta.write_long(offset).unwrap();
+            self.meta
+                .write_long(self.data.file_pointer() - offset)
+                .unwrap();
+        }
+
+        /// Writes the sorted numeric `values` of the docs having some.
+        fn write_sorted_numeric(&mut self, field: i32, values: &[(DocId, Vec<i64>)]) {
+            self.meta.write_int(field).unwrap();
+            self.meta
+                .write_byte(Lucene70DocValuesFormat::SORTED_NUMERIC)
+                .unwrap();
+            let docs: Vec<DocId> = values.iter().map(|v| v.0).collect();
+            let flat: Vec<i64> = values.iter().flat_map(|v| v.1.clone()).collect();
+            self.write_numeric_entry(&docs, &flat);
+            self.meta.write_int(docs.len() as i32).unwrap();
+            if flat.len() != docs.len() {
+                self.write_monotonic(&addresses(values), true);
+            }
+        }
+
+        /// Writes the sorted set `values` of the docs having some, as a
+        /// sorted entry if every doc has a single value.
+        fn write_sorted_set(&mut self, field: i32, values: &[(DocId, Vec<&str>)]) {
+            let mut terms: Vec<&str> = values.iter().flat_map(|v| v.1.clone()).collect();
+            terms.sort();
+            terms.dedup();
+            let docs: Vec<DocId> = values.iter().map(|v| v.0).collect();
+            let ords: Vec<i64> = values
+                .iter()
+                .flat_map(|v| v.1.iter().map(|t| terms.binary_search(t).unwrap() as i64))
+                .collect();
+
+            self.meta.write_int(field).unwrap();
+            self.meta
+                .write_byte(Lucene70DocValuesFormat::SORTED_SET)
+                .unwrap();
+            if ords.len() == docs.len() {
+                self.meta.write_byte(0).unwrap();
+                self.write_docs_with_field(&docs);
+                self.meta.write_int(docs.len() as i32).unwrap();
+                self.write_packed(&ords);
+            } else {
+                self.meta.write_byte(1).unwrap();
+                self.write_docs_with_field(&docs);
+                self.write_packed(&ords);
+                self.meta.write_int(docs.len() as i32).unwrap();
+                self.write_monotonic(&addresses(values), true);
+            }
+            self.write_terms_dict(&terms);
+        }
+
+        /// Writes `terms` prefix compressed by blocks of 4 terms.
+        fn write_terms_dict(&mut self, terms: &[&str]) {
+            const BLOCK_SHIFT: i32 = 2;
+            let data_offset = self.data.file_pointer();
+            let mut block_addresses = vec![];
+            let mut previous: &[u8] = &[];
+            for (ord, term) in terms.iter().enumerate() {
+                let term = term.as_bytes();
+                if ord & ((1 << BLOCK_SHIFT) - 1) == 0 {
+                    block_addresses.push(self.data.file_pointer() - data_offset);
+                    self.data.write_vint(term.len() as i32).unwrap();
+                    self.data.write_bytes(term, 0, term.len()).unwrap();
+                } else {
+                    let prefix = previous
+                        .iter()
+                        .zip(term)
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    let suffix = term.len() - prefix;
+                    assert!(prefix < 15 && suffix <= 15);
+                    self.data
+                        .write_byte((prefix | ((suffix - 1) << 4)) as u8)
+                        .unwrap();
+                    self.data.write_bytes(term, prefix, suffix).unwrap();
+                }
+                previous = term;
+            }
+            let data_length = self.data.file_pointer() - data_offset;
+
+            self.meta.write_vlong(terms.len() as i64).unwrap();
+            self.meta.write_int(BLOCK_SHIFT).unwrap();
+            self.meta.write_int(4).unwrap();
+            let addresses_offset = self.data.file_pointer();
+            self.write_monotonic(&block_addresses, false);
+            let max_term_length = terms.iter().map(|t| t.len()).max().unwrap_or(0);
+            self.meta.write_int(max_term_length as i32).unwrap();
+            self.meta.write_long(data_offset).unwrap();
+            self.meta.write_long(data_length).unwrap();
+            self.meta.write_long(addresses_offset).unwrap();
+            self.meta
+                .write_long(self.data.file_pointer() - addresses_offset)
+                .unwrap();
+            // the reverse index, which isn't read
+            self.meta.write_int(10).unwrap();
+            self.write_monotonic(&[0, 0], false);
+            for _ in 0..4 {
+                self.meta.write_long(0).unwrap();
+            }
+        }
+    }
+
+    /// The start of the values of each doc, followed by the number of values.
+    fn addresses<T>(values: &[(DocId, Vec<T>)]) -> Vec<i64> {
+        let mut addresses = vec![0];
+        for v in values {
+            let last = addresses[addresses.len() - 1];
+            addresses.push(last + v.1.len() as i64);
+        }
+        addresses
+    }
+
+    fn field_info(name: &str, number: u32, doc_values_type: DocValuesType) -> FieldInfo {
+        FieldInfo::new(
+            name.into(),
+            number,
+            false,
+            true,
+            false,
+            IndexOptions::Null,
+            doc_values_type,
+            -1,
+            HashMap::new(),
+            0,
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_read_entries() {
+        let path = ::std::env::temp_dir().join("rucene_lucene70_doc_values");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+
+        let dense: Vec<i64> = (0..MAX_DOC).map(|doc| i64::from(doc) * 3 - 50).collect();
+        let sparse: Vec<DocId> = (0..MAX_DOC).filter(|doc| doc % 7 == 0).collect();
+        let single: Vec<(DocId, Vec<&str>)> = (0..MAX_DOC)
+            .filter(|doc| doc % 2 == 0)
+            .map(|doc| {
+                (
+                    doc,
+                    vec![["a", "ab", "abc", "b", "bcd"][(doc % 5) as usize]],
+                )
+            })
+            .collect();
+        let multi: Vec<(DocId, Vec<&str>)> = (0..MAX_DOC)
+            .filter(|doc| doc % 3 != 0)
+            .map(|doc| {
+                let first = ["m0", "m1", "m2", "m3"][(doc % 4) as usize];
+                if doc % 2 == 0 {
+                    (doc, vec![first, "z"])
+                } else {
+                    (doc, vec![first])
+                }
+            })
+            .collect();
+        let numbers: Vec<(DocId, Vec<i64>)> = (0..MAX_DOC)
+            .filter(|doc| doc % 4 != 3)
+            .map(|doc| {
+                let doc = i64::from(doc);
+                if doc % 2 == 0 {
+                    (doc as DocId, vec![-doc, doc + 1000])
+                } else {
+                    (doc as DocId, vec![doc])
+                }
+            })
+            .collect();
+        let single_numbers: Vec<(DocId, Vec<i64>)> = (0..MAX_DOC)
+            .filter(|doc| doc % 5 == 0)
+            .map(|doc| (doc, vec![-i64::from(doc)]))
+            .collect();
+
+        let mut writer = EntriesWriter::new(&dir);
+        let all: Vec<DocId> = (0..MAX_DOC).collect();
+        writer.write_numeric(0, &all, &dense);
+        let sparse_values: Vec<i64> = sparse.iter().map(|&doc| i64::from(doc) * 1000).collect();
+        writer.write_numeric(1, &sparse, &sparse_values);
+        writer.write_sorted_set(2, &single);
+        writer.write_sorted_set(3, &multi);
+        writer.write_sorted_numeric(4, &numbers);
+        writer.write_sorted_numeric(5, &single_numbers);
+        writer.finish();
+
+        let infos = vec![
+            field_info("dense", 0, DocValuesType::Numeric),
+            field_info("sparse", 1, DocValuesType::Numeric),
+            field_info("single", 2, DocValuesType::SortedSet),
+            field_info("multi", 3, DocValuesType::SortedSet),
+            field_info("numbers", 4, DocValuesType::SortedNumeric),
+            field_info("single_numbers", 5, DocValuesType::SortedNumeric),
+        ];
+        let field_infos = Arc::new(FieldInfos::new(infos.clone()).unwrap());
+        let segment_info: SegmentInfo<_, CodecEnum> = SegmentInfo::new(
+            VERSION_LATEST,
+            "_0",
+            MAX_DOC,
+            Arc::clone(&dir),
+            false,
+            None,
+            HashMap::new(),
+            SEGMENT_ID,
+            HashMap::new(),
+            None,
+        )
+        .unwrap();
+        let context = IOContext::Default;
+        let state = SegmentReadState::new(
+            Arc::clone(&dir),
+            &segment_info,
+            field_infos,
+            &context,
+            String::new(),
+        );
+        let producer = Lucene70DocValuesProducer::new(&state).unwrap();
+
+        // dense numeric
+        let values = producer.get_numeric(&infos[0]).unwrap();
+        let docs_with_field = producer.get_docs_with_field(&infos[0]).unwrap();
+        for doc in 0..MAX_DOC {
+            assert_eq!(values.get(doc).unwrap(), dense[doc as usize]);
+            assert!(docs_with_field.get(doc as usize).unwrap());
+        }
+
+        // sparse numeric
+        let values = producer.get_numeric(&infos[1]).unwrap();
+        let docs_with_field = producer.get_docs_with_field(&infos[1]).unwrap();
+        for doc in 0..MAX_DOC {
+            let exists = doc % 7 == 0;
+            assert_eq!(docs_with_field.get(doc as usize).unwrap(), exists);
+            let expected = if exists { i64::from(doc) * 1000 } else { 0 };
+            assert_eq!(values.get(doc).unwrap(), expected, "doc {}", doc);
+        }
+
+        // single and multi valued sorted sets
+        for &(info, expected) in &[(&infos[2], &single), (&infos[3], &multi)] {
+            let values = producer.get_sorted_set(info).unwrap();
+            let docs_with_field = producer.get_docs_with_field(info).unwrap();
+            let mut expected = expected.iter().peekable();
+            for doc in 0..MAX_DOC {
+                let mut terms = vec![];
+                let mut ctx = values.set_document(doc).unwrap();
+                loop {
+                    let ord = values.next_ord(&mut ctx).unwrap();
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    terms.push(String::from_utf8(values.lookup_ord(ord).unwrap()).unwrap());
+                }
+                let exists = expected.peek().map_or(false, |v| v.0 == doc);
+                assert_eq!(docs_with_field.get(doc as usize).unwrap(), exists);
+                if exists {
+                    assert_eq!(terms, expected.next().unwrap().1, "doc {}", doc);
+                } else {
+                    assert!(terms.is_empty(), "doc {}", doc);
+                }
+            }
+        }
+        assert_eq!(
+            producer
+                .get_sorted_set(&infos[3])
+                .unwrap()
+                .get_value_count(),
+            5
+        );
+
+        // multi and single valued sorted numerics
+        for &(info, expected) in &[(&infos[4], &numbers), (&infos[5], &single_numbers)] {
+            let values = producer.get_sorted_numeric(info).unwrap();
+            let docs_with_field = producer.get_docs_with_field(info).unwrap();
+            let mut expected = expected.iter().peekable();
+            for doc in 0..MAX_DOC {
+                let ctx = values.set_document(None, doc).unwrap();
+                let count = values.count(&ctx);
+                let doc_values: Vec<i64> = (0..count)
+                    .map(|i| values.value_at(&ctx, i).unwrap())
+                    .collect();
+                let exists = expected.peek().map_or(false, |v| v.0 == doc);
+                assert_eq!(docs_with_field.get(doc as usize).unwrap(), exists);
+                if exists {
+                    assert_eq!(doc_values, expected.next().unwrap().1, "doc {}", doc);
+                } else {
+                    assert!(doc_values.is_empty(), "doc {}", doc);
+                }
+            }
+        }
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
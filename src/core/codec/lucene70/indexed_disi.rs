@@ -0,0 +1,427 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::index::{BinaryDocValues, NumericDocValues, NumericDocValuesContext};
+use core::store::{DataOutput, IndexInput, RandomAccessInput};
+use core::util::{Accountable, Bits, BitsContext, DocId, LongValues, LongValuesContext};
+use error::{ErrorKind::CorruptIndex, Result};
+
+use std::mem;
+use std::sync::Arc;
+
+/// Blocks with at most this many docs store them as an array of shorts.
+pub const MAX_ARRAY_LENGTH: u32 = (1 << 12) - 1;
+/// Number of docs covered by a block.
+pub const BLOCK_SIZE: u32 = 1 << 16;
+/// Number of longs of the bit set of a dense block.
+const DENSE_WORDS: usize = (BLOCK_SIZE / 64) as usize;
+// the block of the doc written at the end of the set
+const NO_MORE_DOCS_BLOCK: u32 = 0x7FFF;
+// the rank of a dense block is recorded every this many words
+const RANK_WORDS_SHIFT: usize = 3;
+
+#[derive(Debug)]
+enum BlockKind {
+    /// the low 16 bits of the docs, as sorted shorts
+    Sparse(u32),
+    /// a bit set, with the number of docs before every 8th word
+    Dense(Vec<u16>),
+    /// every doc of the block has a value
+    All,
+}
+
+#[derive(Debug)]
+struct Block {
+    id: u32,
+    /// the number of docs in the previous blocks
+    index: i64,
+    /// the offset of the block data in the slice
+    offset: i64,
+    kind: BlockKind,
+}
+
+/// The set of docs having a value of a sparse doc values field, as written
+/// by the doc values and norms formats of Lucene 7 and by the sparse formats
+/// of `Lucene54DocValuesFormat`.
+///
+/// Docs are split into blocks of 65536 docs, each block storing its docs as
+/// an array of shorts if it has at most 4095 docs, as a bit set otherwise and
+/// nothing if all of its docs are set. The blocks are read once when the
+/// field is opened so that looking up a doc is a binary search over them,
+/// the values of the docs being stored in order of their index in the set.
+pub struct IndexedDISI {
+    slice: Box<dyn RandomAccessInput>,
+    blocks: Vec<Block>,
+    cost: i64,
+    max_doc: DocId,
+}
+
+impl IndexedDISI {
+    pub fn new(
+        data: &dyn IndexInput,
+        offset: i64,
+        length: i64,
+        cost: i64,
+        max_doc: DocId,
+    ) -> Result<IndexedDISI> {
+        let slice = data.random_access_slice(offset, length)?;
+        let mut blocks = Vec::new();
+        let mut index = 0i64;
+        let mut pos = 0i64;
+        while index < cost {
+            if pos + 4 > length {
+                bail!(CorruptIndex(format!(
+                    "docs with field truncated after {} of {} docs",
+                    index, cost
+                )));
+            }
+            let id = u32::from(slice.read_short(pos)? as u16);
+            let cardinality = u32::from(slice.read_short(pos + 2)? as u16) + 1;
+            pos += 4;
+            if id == NO_MORE_DOCS_BLOCK {
+                bail!(CorruptIndex(format!(
+                    "docs with field ends after {} of {} docs",
+                    index, cost
+                )));
+            }
+            if let Some(last) = blocks.last() {
+                let last: &Block = last;
+                if id <= last.id {
+                    bail!(CorruptIndex(format!(
+                        "docs with field blocks out of order: {} after {}",
+                        id, last.id
+                    )));
+                }
+            }
+            let block_offset = pos;
+            let kind = if cardinality <= MAX_ARRAY_LENGTH {
+                pos += i64::from(cardinality) * 2;
+                BlockKind::Sparse(cardinality)
+            } else if cardinality == BLOCK_SIZE {
+                BlockKind::All
+            } else {
+                let mut ranks = Vec::with_capacity(DENSE_WORDS >> RANK_WORDS_SHIFT);
+                let mut rank = 0u32;
+                for i in 0..DENSE_WORDS {
+                    if i & ((1 << RANK_WORDS_SHIFT) - 1) == 0 {
+                        ranks.push(rank as u16);
+                    }
+                    rank += slice.read_long(pos + i as i64 * 8)?.count_ones();
+                }
+                if rank != cardinality {
+                    bail!(CorruptIndex(format!(
+                        "dense block {} has {} docs, expected {}",
+                        id, rank, cardinality
+                    )));
+                }
+                pos += DENSE_WORDS as i64 * 8;
+                BlockKind::Dense(ranks)
+            };
+            blocks.push(Block {
+                id,
+                index,
+                offset: block_offset,
+                kind,
+            });
+            index += i64::from(cardinality);
+        }
+        if index != cost {
+            bail!(CorruptIndex(format!(
+                "docs with field has {} docs, expected {}",
+                index, cost
+            )));
+        }
+        Ok(IndexedDISI {
+            slice,
+            blocks,
+            cost,
+            max_doc,
+        })
+    }
+
+    /// Writes `docs`, which must be in increasing order, in the format read by
+    /// `IndexedDISI::new` and returns the number of docs written.
+    pub fn write_docs<O: DataOutput + ?Sized>(
+        docs: impl Iterator<Item = DocId>,
+        out: &mut O,
+    ) -> Result<i64> {
+        let mut cardinality = 0i64;
+        let mut block = 0u32;
+        let mut block_docs: Vec<u16> = Vec::new();
+        let mut last_doc = -1;
+        for doc in docs {
+            debug_assert!(doc > last_doc);
+            last_doc = doc;
+            let doc_block = (doc as u32) >> 16;
+            if doc_block != block && !block_docs.is_empty() {
+                Self::flush_block(block, &block_docs, out)?;
+                block_docs.clear();
+            }
+            block = doc_block;
+            block_docs.push(doc as u16);
+            cardinality += 1;
+        }
+        if !block_docs.is_empty() {
+            Self::flush_block(block, &block_docs, out)?;
+        }
+        // the set ends with a block holding only NO_MORE_DOCS
+        Self::flush_block(NO_MORE_DOCS_BLOCK, &[0xFFFF], out)?;
+        Ok(cardinality)
+    }
+
+    fn flush_block<O: DataOutput + ?Sized>(block: u32, docs: &[u16], out: &mut O) -> Result<()> {
+        debug_assert!(!docs.is_empty() && docs.len() as u32 <= BLOCK_SIZE);
+        out.write_short(block as i16)?;
+        out.write_short((docs.len() - 1) as i16)?;
+        if docs.len() as u32 <= MAX_ARRAY_LENGTH {
+            for &doc in docs {
+                out.write_short(doc as i16)?;
+            }
+        } else if docs.len() as u32 != BLOCK_SIZE {
+            let mut words = vec![0u64; DENSE_WORDS];
+            for &doc in docs {
+                words[(doc >> 6) as usize] |= 1u64 << (doc & 63);
+            }
+            for word in words {
+                out.write_long(word as i64)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of docs in the set.
+    pub fn cost(&self) -> i64 {
+        self.cost
+    }
+
+    /// Returns whether `doc` is in the set, and the number of docs of the
+    /// set that are less than `doc`, which is the index of its value if any.
+    pub fn rank(&self, doc: DocId) -> Result<(bool, i64)> {
+        debug_assert!(doc >= 0);
+        let id = (doc as u32) >> 16;
+        let block = match self.blocks.binary_search_by_key(&id, |b| b.id) {
+            Ok(i) => &self.blocks[i],
+            Err(i) => {
+                let index = self.blocks.get(i).map_or(self.cost, |b| b.index);
+                return Ok((false, index));
+            }
+        };
+        let target = (doc as u32) & 0xFFFF;
+        match block.kind {
+            BlockKind::All => Ok((true, block.index + i64::from(target))),
+            BlockKind::Sparse(cardinality) => {
+                let (mut low, mut high) = (0i64, i64::from(cardinality) - 1);
+                while low <= high {
+                    let mid = (low + high) >> 1;
+                    let v = u32::from(self.slice.read_short(block.offset + mid * 2)? as u16);
+                    if v < target {
+                        low = mid + 1;
+                    } else if v > target {
+                        high = mid - 1;
+                    } else {
+                        return Ok((true, block.index + mid));
+                    }
+                }
+                Ok((false, block.index + low))
+            }
+            BlockKind::Dense(ref ranks) => {
+                let word_index = (target >> 6) as usize;
+                let first_word = word_index & !((1 << RANK_WORDS_SHIFT) - 1);
+                let mut rank = u32::from(ranks[word_index >> RANK_WORDS_SHIFT]);
+                for i in first_word..word_index {
+                    rank += self
+                        .slice
+                        .read_long(block.offset + i as i64 * 8)?
+                        .count_ones();
+                }
+                let word = self.slice.read_long(block.offset + word_index as i64 * 8)? as u64;
+                let bit = 1u64 << (target & 63);
+                rank += (word & (bit - 1)).count_ones();
+                Ok((word & bit != 0, block.index + i64::from(rank)))
+            }
+        }
+    }
+}
+
+impl Accountable for IndexedDISI {
+    fn ram_bytes_used(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|b| match b.kind {
+                BlockKind::Dense(ref ranks) => mem::size_of::<Block>() + ranks.len() * 2,
+                _ => mem::size_of::<Block>(),
+            })
+            .sum()
+    }
+}
+
+impl Bits for IndexedDISI {
+    fn get_with_ctx(&self, ctx: BitsContext, index: usize) -> Result<(bool, BitsContext)> {
+        Ok((self.rank(index as DocId)?.0, ctx))
+    }
+
+    fn len(&self) -> usize {
+        self.max_doc as usize
+    }
+}
+
+/// The values of the docs of an `IndexedDISI`, stored by index in the set,
+/// returning `missing_value` for the other docs.
+pub struct SparseDISIValues {
+    disi: Arc<IndexedDISI>,
+    values: Box<dyn LongValues>,
+    missing_value: i64,
+}
+
+impl SparseDISIValues {
+    pub fn new(disi: Arc<IndexedDISI>, values: Box<dyn LongValues>, missing_value: i64) -> Self {
+        SparseDISIValues {
+            disi,
+            values,
+            missing_value,
+        }
+    }
+}
+
+impl LongValues for SparseDISIValues {
+    fn get64_with_ctx(
+        &self,
+        ctx: LongValuesContext,
+        index: i64,
+    ) -> Result<(i64, LongValuesContext)> {
+        match self.disi.rank(index as DocId)? {
+            (true, rank) => self.values.get64_with_ctx(ctx, rank),
+            (false, _) => Ok((self.missing_value, ctx)),
+        }
+    }
+}
+
+impl NumericDocValues for SparseDISIValues {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        self.get64_with_ctx(ctx, i64::from(doc_id))
+    }
+}
+
+/// The binary values of the docs of an `IndexedDISI`, stored by index in the
+/// set, the other docs having an empty value.
+pub struct SparseDISIBinaryValues {
+    disi: Arc<IndexedDISI>,
+    values: Arc<dyn BinaryDocValues>,
+}
+
+impl SparseDISIBinaryValues {
+    pub fn new(disi: Arc<IndexedDISI>, values: Arc<dyn BinaryDocValues>) -> Self {
+        SparseDISIBinaryValues { disi, values }
+    }
+}
+
+impl BinaryDocValues for SparseDISIBinaryValues {
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        match self.disi.rank(doc_id)? {
+            (true, index) => self.values.get_into(index as DocId, value),
+            (false, _) => {
+                value.clear();
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Maps a doc to the start of its values, given the addresses of the values
+/// of the docs of an `IndexedDISI`, so that the values of a doc are between
+/// its address and the one of the next doc like for dense fields.
+pub struct SparseDISIAddresses<T: LongValues> {
+    disi: Arc<IndexedDISI>,
+    addresses: T,
+}
+
+impl<T: LongValues> SparseDISIAddresses<T> {
+    pub fn new(disi: Arc<IndexedDISI>, addresses: T) -> Self {
+        SparseDISIAddresses { disi, addresses }
+    }
+}
+
+impl<T: LongValues> LongValues for SparseDISIAddresses<T> {
+    fn get64_with_ctx(
+        &self,
+        ctx: LongValuesContext,
+        index: i64,
+    ) -> Result<(i64, LongValuesContext)> {
+        // docs past the last one of the set share the end address
+        let rank = if index >= i64::from(self.disi.max_doc) {
+            self.disi.cost
+        } else {
+            self.disi.rank(index as DocId)?.1
+        };
+        self.addresses.get64_with_ctx(ctx, rank)
+    }
+}
+
+impl<T: LongValues> NumericDocValues for SparseDISIAddresses<T> {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        self.get64_with_ctx(ctx, i64::from(doc_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::{FSIndexOutput, IndexOutput, MmapIndexInput};
+    use std::fs;
+
+    #[test]
+    fn test_rank() {
+        let max_doc = 5 * BLOCK_SIZE;
+        // sparse, dense, all and an empty block
+        let docs: Vec<u32> = (0..100)
+            .map(|i| i * 7)
+            .chain((BLOCK_SIZE..2 * BLOCK_SIZE).filter(|d| d % 3 != 0))
+            .chain(2 * BLOCK_SIZE..3 * BLOCK_SIZE)
+            .chain(vec![4 * BLOCK_SIZE + 1, max_doc - 1])
+            .collect();
+        let mut bytes = Vec::new();
+        let count = IndexedDISI::write_docs(docs.iter().map(|&d| d as DocId), &mut bytes).unwrap();
+        assert_eq!(count, docs.len() as i64);
+        let len = bytes.len() as i64;
+        let path = ::std::env::temp_dir().join("rucene_test_indexed_disi");
+        let mut output = FSIndexOutput::new(&path).unwrap();
+        output.write_bytes(&bytes, 0, bytes.len()).unwrap();
+        drop(output);
+        let input = MmapIndexInput::new(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let disi = IndexedDISI::new(&input, 0, len, docs.len() as i64, max_doc as DocId).unwrap();
+
+        let mut next = 0;
+        for doc in 0..max_doc {
+            let present = next < docs.len() && docs[next] == doc;
+            assert_eq!(
+                disi.rank(doc as DocId).unwrap(),
+                (present, next as i64),
+                "doc {}",
+                doc
+            );
+            if present {
+                next += 1;
+            }
+        }
+        assert!(IndexedDISI::new(&input, 0, len, docs.len() as i64 + 1, max_doc as DocId).is_err());
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod codec;
+
+pub use self::codec::*;
+
+mod doc_values;
+
+pub use self::doc_values::*;
+
+mod doc_values_producer;
+
+pub use self::doc_values_producer::*;
+
+mod indexed_disi;
+
+pub use self::indexed_disi::{
+    IndexedDISI, SparseDISIAddresses, SparseDISIBinaryValues, SparseDISIValues,
+};
+
+mod norms;
+
+pub use self::norms::Lucene70NormsFormat;
+
+mod norms_producer;
+
+pub use self::norms_producer::*;
+
+mod segment_info;
+
+pub use self::segment_info::*;
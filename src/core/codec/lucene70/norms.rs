@@ -0,0 +1,54 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, Lucene70NormsProducer, NormsConsumerEnum, NormsFormat};
+use core::index::{SegmentReadState, SegmentWriteState};
+use core::store::Directory;
+
+use error::{ErrorKind::UnsupportedOperation, Result};
+
+pub const DATA_CODEC: &str = "Lucene70NormsData";
+pub const DATA_EXTENSION: &str = "nvd";
+pub const METADATA_CODEC: &str = "Lucene70NormsMetadata";
+pub const METADATA_EXTENSION: &str = "nvm";
+pub const VERSION_START: i32 = 0;
+pub const VERSION_CURRENT: i32 = VERSION_START;
+
+/// Lucene 7.0 norms format, only used to read the segments of Lucene 7.
+///
+/// The norms of the docs that have one are stored with the fewest bytes that
+/// fit all of them, in doc id order, the docs having a norm being recorded
+/// in an `IndexedDISI` unless all docs do. Note that Lucene 7 encodes the
+/// field length of a doc into its norm, so the fields with norms are marked
+/// as `NormsEncoding::Length` when read.
+#[derive(Copy, Clone, Default)]
+pub struct Lucene70NormsFormat;
+
+impl NormsFormat for Lucene70NormsFormat {
+    type NormsProducer = Lucene70NormsProducer;
+    fn norms_producer<'a, D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'a, D, DW, C>,
+    ) -> Result<Self::NormsProducer> {
+        Lucene70NormsProducer::new(state)
+    }
+
+    fn norms_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<NormsConsumerEnum<DW::IndexOutput>> {
+        bail!(UnsupportedOperation(
+            "Lucene70NormsFormat can only be used for reading".into()
+        ))
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::lucene70::norms::*;
+use core::codec::lucene70::{IndexedDISI, SparseDISIValues};
+use core::codec::{codec_util, Codec, NormsProducer};
+use core::index::{segment_file_name, FieldInfo, FieldInfos, SegmentReadState};
+use core::index::{EmptyNumericDocValues, NumericDocValues, NumericDocValuesContext};
+use core::search::bm25_similarity::NormsEncoding;
+use core::store::{Directory, IndexInput, RandomAccessInput};
+use core::util::{DocId, LongValues, LongValuesContext};
+use error::ErrorKind::{CorruptIndex, IllegalArgument};
+use error::Result;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[derive(Debug)]
+struct NormsEntry {
+    // -2 if no doc has a norm, -1 if all docs have one
+    docs_with_field_offset: i64,
+    docs_with_field_length: i64,
+    num_docs_with_field: i32,
+    bytes_per_norm: u8,
+    // the norm of all docs if `bytes_per_norm` is 0
+    norms_offset: i64,
+}
+
+/// Reader for the norms of `Lucene70NormsFormat`.
+pub struct Lucene70NormsProducer {
+    max_doc: DocId,
+    data: Box<dyn IndexInput>,
+    entries: HashMap<u32, NormsEntry>,
+}
+
+impl Lucene70NormsProducer {
+    pub fn new<D: Directory, DW: Directory, C: Codec>(
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Lucene70NormsProducer> {
+        let max_doc = state.segment_info.max_doc();
+        let meta_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            METADATA_EXTENSION,
+        );
+        let mut checksum_input = state
+            .directory
+            .open_checksum_input(&meta_name, &state.context)?;
+        let meta_version = codec_util::check_index_header(
+            &mut checksum_input,
+            METADATA_CODEC,
+            VERSION_START,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        let entries = Self::read_fields(&mut checksum_input, &state.field_infos)?;
+        codec_util::check_footer(&mut checksum_input)?;
+
+        let data_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            DATA_EXTENSION,
+        );
+        let mut data = state.directory.open_input(&data_name, &state.context)?;
+        let data_version = codec_util::check_index_header(
+            data.as_mut(),
+            DATA_CODEC,
+            VERSION_START,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        if data_version != meta_version {
+            bail!(CorruptIndex(format!(
+                "Format versions mismatch: meta={}, data={}",
+                meta_version, data_version
+            )))
+        }
+        codec_util::retrieve_checksum(data.as_mut())?;
+
+        Ok(Lucene70NormsProducer {
+            max_doc,
+            data,
+            entries,
+        })
+    }
+
+    fn read_fields<T: IndexInput + ?Sized>(
+        input: &mut T,
+        infos: &FieldInfos,
+    ) -> Result<HashMap<u32, NormsEntry>> {
+        let mut entries = HashMap::new();
+        loop {
+            let field_num = input.read_int()?;
+            if field_num == -1 {
+                break;
+            }
+            let field_info = infos
+                .field_info_by_number(field_num as u32)
+                .ok_or_else(|| IllegalArgument(format!("Invalid field number: {}", field_num)))?;
+            if !field_info.has_norms() {
+                bail!(CorruptIndex(format!("Invalid field: {}", field_info.name)))
+            }
+            // Lucene 7 doesn't record the encoding of the norms, which is
+            // always the length of the field
+            if field_info.attribute(NormsEncoding::ATTRIBUTE_KEY).is_none() {
+                NormsEncoding::Length.put_attribute(field_info);
+            }
+            let docs_with_field_offset = input.read_long()?;
+            let docs_with_field_length = input.read_long()?;
+            let num_docs_with_field = input.read_int()?;
+            let bytes_per_norm = input.read_byte()?;
+            match bytes_per_norm {
+                0 | 1 | 2 | 4 | 8 => {}
+                _ => {
+                    bail!(CorruptIndex(format!(
+                        "Invalid bytesPerValue: {}, field: {}",
+                        bytes_per_norm, field_info.name
+                    )));
+                }
+            }
+            let norms_offset = input.read_long()?;
+            entries.insert(
+                field_info.number,
+                NormsEntry {
+                    docs_with_field_offset,
+                    docs_with_field_length,
+                    num_docs_with_field,
+                    bytes_per_norm,
+                    norms_offset,
+                },
+            );
+        }
+        Ok(entries)
+    }
+
+    fn norms_values(&self, entry: &NormsEntry, count: i64) -> Result<NormValues> {
+        if entry.bytes_per_norm == 0 {
+            return Ok(NormValues::Constant(entry.norms_offset));
+        }
+        let slice = self
+            .data
+            .random_access_slice(entry.norms_offset, count * i64::from(entry.bytes_per_norm))?;
+        Ok(NormValues::Bytes(slice, entry.bytes_per_norm))
+    }
+}
+
+impl NormsProducer for Lucene70NormsProducer {
+    fn norms(&self, field: &FieldInfo) -> Result<Box<dyn NumericDocValues>> {
+        let entry = self
+            .entries
+            .get(&field.number)
+            .ok_or_else(|| IllegalArgument(format!("No norms for field {}", field.name)))?;
+        match entry.docs_with_field_offset {
+            -2 => Ok(Box::new(EmptyNumericDocValues {})),
+            -1 => Ok(Box::new(self.norms_values(entry, i64::from(self.max_doc))?)),
+            _ => {
+                let disi = IndexedDISI::new(
+                    self.data.as_ref(),
+                    entry.docs_with_field_offset,
+                    entry.docs_with_field_length,
+                    i64::from(entry.num_docs_with_field),
+                    self.max_doc,
+                )?;
+                let values = self.norms_values(entry, i64::from(entry.num_docs_with_field))?;
+                Ok(Box::new(SparseDISIValues::new(
+                    Arc::new(disi),
+                    Box::new(values),
+                    0,
+                )))
+            }
+        }
+    }
+}
+
+enum NormValues {
+    Constant(i64),
+    Bytes(Box<dyn RandomAccessInput>, u8),
+}
+
+impl LongValues for NormValues {
+    fn get64_with_ctx(
+        &self,
+        ctx: LongValuesContext,
+        index: i64,
+    ) -> Result<(i64, LongValuesContext)> {
+        let value = match self {
+            NormValues::Constant(v) => *v,
+            NormValues::Bytes(slice, 1) => i64::from(slice.read_byte(index)? as i8),
+            NormValues::Bytes(slice, 2) => i64::from(slice.read_short(index << 1)?),
+            NormValues::Bytes(slice, 4) => i64::from(slice.read_int(index << 2)?),
+            NormValues::Bytes(slice, _) => slice.read_long(index << 3)?,
+        };
+        Ok((value, ctx))
+    }
+}
+
+impl NumericDocValues for NormValues {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        self.get64_with_ctx(ctx, i64::from(doc_id))
+    }
+}
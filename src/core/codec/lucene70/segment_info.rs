@@ -0,0 +1,116 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use core::codec::format::SegmentInfoFormat;
+use core::codec::lucene62::read_index_sort;
+use core::codec::{codec_util, Codec};
+use core::index::{segment_file_name, SegmentInfo, SEGMENT_USE_COMPOUND_YES};
+use core::store::Directory;
+use core::store::{BufferedChecksumIndexInput, ChecksumIndexInput, IOContext, IndexInput};
+use core::util::string_util::ID_LENGTH;
+use core::util::Version;
+use error::ErrorKind::{CorruptIndex, UnsupportedOperation};
+use error::Result;
+
+const SI_EXTENSION: &str = "si";
+const CODEC_NAME: &str = "Lucene70SegmentInfo";
+const VERSION_START: i32 = 0;
+const VERSION_CURRENT: i32 = VERSION_START;
+
+/// Lucene 7.0 segment info format, only used to read the segments of Lucene 7.
+///
+/// It adds the minimum Lucene version of the segments merged into a segment
+/// to `Lucene62SegmentInfoFormat`, which is not kept.
+#[derive(Copy, Clone, Default)]
+pub struct Lucene70SegmentInfoFormat;
+
+fn read_segment_info_from_index<D: Directory, C: Codec>(
+    input: &mut dyn IndexInput,
+    dir: &Arc<D>,
+    segment: &str,
+    id: [u8; ID_LENGTH],
+) -> Result<SegmentInfo<D, C>> {
+    codec_util::check_index_header(input, CODEC_NAME, VERSION_START, VERSION_CURRENT, &id, "")?;
+    let major = input.read_int()?;
+    let minor = input.read_int()?;
+    let bugfix = input.read_int()?;
+    let version = Version::new(major, minor, bugfix)?;
+    match input.read_byte()? {
+        0 => {}
+        1 => {
+            // the min version of the merged segments
+            for _ in 0..3 {
+                input.read_int()?;
+            }
+        }
+        b => bail!(CorruptIndex(format!("illegal boolean value {}", b))),
+    }
+    let doc_count = input.read_int()?;
+    if doc_count < 0 {
+        bail!(CorruptIndex(format!("invalid docCount: {}", doc_count)));
+    }
+    let is_compound_file = input.read_byte()? == SEGMENT_USE_COMPOUND_YES;
+
+    let diagnostics = input.read_map_of_strings()?;
+    let files = input.read_set_of_strings()?;
+    let attributes = input.read_map_of_strings()?;
+    let index_sort = read_index_sort(input)?;
+
+    let mut si = SegmentInfo::new(
+        version,
+        segment,
+        doc_count,
+        Arc::clone(dir),
+        is_compound_file,
+        None,
+        diagnostics,
+        id,
+        attributes,
+        index_sort,
+    )?;
+    si.set_files(&files)?;
+    Ok(si)
+}
+
+impl SegmentInfoFormat for Lucene70SegmentInfoFormat {
+    fn read<D: Directory, C: Codec>(
+        &self,
+        directory: &Arc<D>,
+        segment_name: &str,
+        segment_id: [u8; ID_LENGTH],
+        context: &IOContext,
+    ) -> Result<SegmentInfo<D, C>> {
+        let file_name = segment_file_name(segment_name, "", SI_EXTENSION);
+        let original_input = directory.open_input(&file_name, context)?;
+        let mut checksum = BufferedChecksumIndexInput::new(original_input);
+        let segment_info =
+            read_segment_info_from_index(&mut checksum, directory, segment_name, segment_id)?;
+        codec_util::validate_footer(&mut checksum)?;
+        let digest = checksum.checksum();
+        codec_util::check_checksum(&mut checksum, digest)?;
+        Ok(segment_info)
+    }
+
+    fn write<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        _dir: &Arc<DW>,
+        _info: &mut SegmentInfo<D, C>,
+        _io_context: &IOContext,
+    ) -> Result<()> {
+        bail!(UnsupportedOperation(
+            "Lucene70SegmentInfoFormat can only be used for reading".into()
+        ))
+    }
+}
@@ -0,0 +1,388 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::format::VectorFormat;
+use core::codec::lucene90::Lucene90VectorWriter;
+use core::codec::{codec_util, Codec, VectorReader};
+use core::index::{
+    segment_file_name, FieldInfos, SegmentReadState, SegmentWriteState, VectorSimilarityFunction,
+    VectorValues,
+};
+use core::store::{DataInput, Directory, IndexInput, RandomAccessInput};
+use core::util::DocId;
+
+use error::{ErrorKind::CorruptIndex, Result};
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+pub const VECTOR_DATA_CODEC_NAME: &str = "Lucene90VectorsFormatData";
+pub const VECTOR_META_CODEC_NAME: &str = "Lucene90VectorsFormatMeta";
+
+// Filename extension for the vectors
+pub const VECTOR_DATA_EXTENSION: &str = "vec";
+// Filename extension for the metadata of the fields
+pub const VECTOR_META_EXTENSION: &str = "vem";
+
+pub const VECTOR_VERSION_START: i32 = 0;
+pub const VECTOR_VERSION_CURRENT: i32 = VECTOR_VERSION_START;
+
+/// Lucene 9.0 vector format.
+///
+/// Each segment has two files:
+/// - the vector data file (.vec) holds the vectors of all the fields, one field after the other,
+///   each vector being `dimension` floats written as their int bits, so any vector can be read from
+///   its ord;
+/// - the vector metadata file (.vem) holds, for each field, the field number, the similarity
+///   function, the position and length of its vectors in the data file, the dimension, the number
+///   of vectors and the doc ids of the vectors when not every document of the segment has one. The
+///   list of fields ends with -1.
+#[derive(Copy, Clone)]
+pub struct Lucene90VectorFormat;
+
+impl VectorFormat for Lucene90VectorFormat {
+    type Reader = Lucene90VectorReader;
+
+    fn fields_writer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<Lucene90VectorWriter<DW::IndexOutput>> {
+        Lucene90VectorWriter::new(state)
+    }
+
+    fn fields_reader<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::Reader> {
+        Lucene90VectorReader::new(state)
+    }
+}
+
+struct FieldEntry {
+    similarity_function: VectorSimilarityFunction,
+    data_offset: i64,
+    data_length: i64,
+    dimension: usize,
+    size: usize,
+    // doc id of each ord, None when every document has a vector
+    docs: Option<Vec<DocId>>,
+}
+
+pub struct Lucene90VectorReader {
+    field_infos: Arc<FieldInfos>,
+    fields: HashMap<u32, Arc<FieldEntry>>,
+    data: Box<dyn IndexInput>,
+}
+
+impl Lucene90VectorReader {
+    pub fn new<D: Directory, DW: Directory, C: Codec>(
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Lucene90VectorReader> {
+        let meta_file_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            VECTOR_META_EXTENSION,
+        );
+        let mut meta_in = state
+            .directory
+            .open_checksum_input(&meta_file_name, state.context)?;
+        let version = codec_util::check_index_header(
+            &mut meta_in,
+            VECTOR_META_CODEC_NAME,
+            VECTOR_VERSION_START,
+            VECTOR_VERSION_CURRENT,
+            &state.segment_info.id,
+            &state.segment_suffix,
+        )?;
+        let max_doc = state.segment_info.max_doc();
+        let mut fields = HashMap::new();
+        loop {
+            let field_number = meta_in.read_vint()?;
+            if field_number == -1 {
+                break;
+            }
+            let info = match state.field_infos.field_info_by_number(field_number as u32) {
+                Some(info) => info,
+                None => bail!(CorruptIndex(format!(
+                    "invalid field number: {}",
+                    field_number
+                ))),
+            };
+            let similarity_function = VectorSimilarityFunction::from_ord(meta_in.read_byte()?)?;
+            let data_offset = meta_in.read_vlong()?;
+            let data_length = meta_in.read_vlong()?;
+            let dimension = meta_in.read_vint()?;
+            let size = meta_in.read_vint()?;
+            if dimension as u32 != info.vector_dimension
+                || similarity_function != info.vector_similarity_function
+            {
+                bail!(CorruptIndex(format!(
+                    "inconsistent vector properties for field '{}': dimension={}, \
+                     similarity_function={:?}",
+                    info.name, dimension, similarity_function
+                )));
+            }
+            if size < 0
+                || size > max_doc
+                || data_length != i64::from(size) * i64::from(dimension) * 4
+            {
+                bail!(CorruptIndex(format!(
+                    "invalid vector data for field '{}': size={}, data_length={}",
+                    info.name, size, data_length
+                )));
+            }
+            let docs = if meta_in.read_byte()? == 0 {
+                if size != max_doc {
+                    bail!(CorruptIndex(format!(
+                        "field '{}' is dense but has {} vectors for {} docs",
+                        info.name, size, max_doc
+                    )));
+                }
+                None
+            } else {
+                let mut docs = Vec::with_capacity(size as usize);
+                let mut doc = 0;
+                for _ in 0..size {
+                    doc += meta_in.read_vint()?;
+                    docs.push(doc);
+                }
+                Some(docs)
+            };
+            fields.insert(
+                field_number as u32,
+                Arc::new(FieldEntry {
+                    similarity_function,
+                    data_offset,
+                    data_length,
+                    dimension: dimension as usize,
+                    size: size as usize,
+                    docs,
+                }),
+            );
+        }
+        codec_util::check_footer(&mut meta_in)?;
+
+        let data_file_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            VECTOR_DATA_EXTENSION,
+        );
+        let mut data = state.directory.open_input(&data_file_name, state.context)?;
+        let data_version = codec_util::check_index_header(
+            data.as_mut(),
+            VECTOR_DATA_CODEC_NAME,
+            VECTOR_VERSION_START,
+            VECTOR_VERSION_CURRENT,
+            &state.segment_info.id,
+            &state.segment_suffix,
+        )?;
+        if version != data_version {
+            bail!(CorruptIndex(format!(
+                "format versions mismatch: meta={}, data={}",
+                version, data_version
+            )));
+        }
+        // NOTE: data file is too costly to verify checksum against all the bytes on open,
+        // but for now we at least verify proper structure of the checksum footer
+        codec_util::retrieve_checksum(data.as_mut())?;
+
+        Ok(Lucene90VectorReader {
+            field_infos: Arc::clone(&state.field_infos),
+            fields,
+            data,
+        })
+    }
+}
+
+impl VectorReader for Lucene90VectorReader {
+    fn check_integrity(&self) -> Result<()> {
+        codec_util::checksum_entire_file(self.data.as_ref())?;
+        Ok(())
+    }
+
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        let entry = match self.field_infos.field_info_by_name(field) {
+            Some(info) => match self.fields.get(&info.number) {
+                Some(entry) => Arc::clone(entry),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let data = self
+            .data
+            .random_access_slice(entry.data_offset, entry.data_length)?;
+        Ok(Some(Box::new(Lucene90VectorValues { entry, data })))
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+struct Lucene90VectorValues {
+    entry: Arc<FieldEntry>,
+    data: Box<dyn RandomAccessInput>,
+}
+
+impl VectorValues for Lucene90VectorValues {
+    fn dimension(&self) -> usize {
+        self.entry.dimension
+    }
+
+    fn size(&self) -> usize {
+        self.entry.size
+    }
+
+    fn similarity_function(&self) -> VectorSimilarityFunction {
+        self.entry.similarity_function
+    }
+
+    fn ord_to_doc(&self, ord: usize) -> DocId {
+        match self.entry.docs {
+            Some(ref docs) => docs[ord],
+            None => ord as DocId,
+        }
+    }
+
+    fn doc_to_ord(&self, doc: DocId) -> Option<usize> {
+        match self.entry.docs {
+            Some(ref docs) => docs.binary_search(&doc).ok(),
+            None if doc >= 0 && (doc as usize) < self.entry.size => Some(doc as usize),
+            None => None,
+        }
+    }
+
+    fn vector_value(&self, ord: usize, vector: &mut [f32]) -> Result<()> {
+        debug_assert_eq!(vector.len(), self.entry.dimension);
+        let mut pos = (ord * self.entry.dimension * 4) as i64;
+        for v in vector {
+            *v = f32::from_bits(self.data.read_int(pos)? as u32);
+            pos += 4;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::KnnVectorField;
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexReader, IndexWriter};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+
+    fn dense_vector(doc: i32) -> Vec<f32> {
+        vec![doc as f32, doc as f32 + 0.5, -(doc as f32)]
+    }
+
+    // every third document has no sparse vector
+    fn sparse_vector(doc: i32) -> Option<Vec<f32>> {
+        if doc % 3 == 1 {
+            None
+        } else {
+            Some(vec![doc as f32 * 2.0, 1.0])
+        }
+    }
+
+    #[test]
+    fn test_vectors_round_trip() {
+        let path = ::std::env::temp_dir().join("rucene_lucene90_vectors");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let mut config = IndexWriterConfig::default();
+        config.use_compound_file = false;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        let docs = 10;
+        for doc in 0..docs {
+            let mut fields: Vec<Box<dyn Fieldable>> = vec![Box::new(
+                KnnVectorField::new(
+                    "dense",
+                    &dense_vector(doc),
+                    VectorSimilarityFunction::Euclidean,
+                )
+                .unwrap(),
+            )];
+            if let Some(vector) = sparse_vector(doc) {
+                fields.push(Box::new(
+                    KnnVectorField::new("sparse", &vector, VectorSimilarityFunction::Cosine)
+                        .unwrap(),
+                ));
+            }
+            writer.add_document(fields).unwrap();
+        }
+        writer.commit().unwrap();
+
+        let files = dir.list_all().unwrap();
+        assert!(files.iter().any(|f| f.ends_with(VECTOR_DATA_EXTENSION)));
+        assert!(files.iter().any(|f| f.ends_with(VECTOR_META_EXTENSION)));
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = leaves[0].reader;
+
+        let dense = leaf.vector_values("dense").unwrap().unwrap();
+        assert_eq!(dense.dimension(), 3);
+        assert_eq!(dense.size(), docs as usize);
+        assert_eq!(
+            dense.similarity_function(),
+            VectorSimilarityFunction::Euclidean
+        );
+        let mut vector = vec![0f32; 3];
+        for doc in 0..docs {
+            let ord = dense.doc_to_ord(doc).unwrap();
+            assert_eq!(dense.ord_to_doc(ord), doc);
+            dense.vector_value(ord, &mut vector).unwrap();
+            assert_eq!(vector, dense_vector(doc));
+        }
+        assert!(dense.doc_to_ord(docs).is_none());
+
+        let sparse = leaf.vector_values("sparse").unwrap().unwrap();
+        assert_eq!(sparse.dimension(), 2);
+        assert_eq!(
+            sparse.size(),
+            (0..docs).filter(|d| sparse_vector(*d).is_some()).count()
+        );
+        assert_eq!(
+            sparse.similarity_function(),
+            VectorSimilarityFunction::Cosine
+        );
+        let mut vector = vec![0f32; 2];
+        let mut ord = 0;
+        for doc in 0..docs {
+            match sparse_vector(doc) {
+                Some(expected) => {
+                    assert_eq!(sparse.doc_to_ord(doc), Some(ord));
+                    assert_eq!(sparse.ord_to_doc(ord), doc);
+                    sparse.vector_value(ord, &mut vector).unwrap();
+                    assert_eq!(vector, expected);
+                    ord += 1;
+                }
+                None => assert!(sparse.doc_to_ord(doc).is_none(), "doc {}", doc),
+            }
+        }
+
+        assert!(leaf.vector_values("missing").unwrap().is_none());
+        leaf.vector_reader()
+            .unwrap()
+            .unwrap()
+            .check_integrity()
+            .unwrap();
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::lucene90::vector_reader::{
+    VECTOR_DATA_CODEC_NAME, VECTOR_DATA_EXTENSION, VECTOR_META_CODEC_NAME, VECTOR_META_EXTENSION,
+    VECTOR_VERSION_CURRENT,
+};
+use core::codec::{write_footer, write_index_header, Codec, VectorWriter};
+use core::index::{segment_file_name, FieldInfo, SegmentWriteState, VectorValues};
+use core::store::{DataOutput, Directory, IndexOutput};
+use core::util::DocId;
+
+use error::{ErrorKind::IllegalState, Result};
+
+/// Writes vectors in the `Lucene90VectorFormat`.
+pub struct Lucene90VectorWriter<O: IndexOutput> {
+    meta: O,
+    data: O,
+    max_doc: DocId,
+    finished: bool,
+}
+
+impl<O: IndexOutput> Lucene90VectorWriter<O> {
+    pub fn new<D: Directory, DW: Directory<IndexOutput = O>, C: Codec>(
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<Lucene90VectorWriter<O>> {
+        let meta_file_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            VECTOR_META_EXTENSION,
+        );
+        let data_file_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            VECTOR_DATA_EXTENSION,
+        );
+        let mut meta = state
+            .directory
+            .create_output(&meta_file_name, &state.context)?;
+        let mut data = state
+            .directory
+            .create_output(&data_file_name, &state.context)?;
+        write_index_header(
+            &mut meta,
+            VECTOR_META_CODEC_NAME,
+            VECTOR_VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        write_index_header(
+            &mut data,
+            VECTOR_DATA_CODEC_NAME,
+            VECTOR_VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        Ok(Lucene90VectorWriter {
+            meta,
+            data,
+            max_doc: state.segment_info.max_doc(),
+            finished: false,
+        })
+    }
+}
+
+impl<O: IndexOutput> VectorWriter for Lucene90VectorWriter<O> {
+    fn write_field(&mut self, field_info: &FieldInfo, values: &dyn VectorValues) -> Result<()> {
+        let dimension = values.dimension();
+        let size = values.size();
+        let data_offset = self.data.file_pointer();
+        let mut vector = vec![0f32; dimension];
+        for ord in 0..size {
+            values.vector_value(ord, &mut vector)?;
+            for v in &vector {
+                self.data.write_int(v.to_bits() as i32)?;
+            }
+        }
+        let data_length = self.data.file_pointer() - data_offset;
+
+        self.meta.write_vint(field_info.number as i32)?;
+        self.meta.write_byte(values.similarity_function().ord())?;
+        self.meta.write_vlong(data_offset)?;
+        self.meta.write_vlong(data_length)?;
+        self.meta.write_vint(dimension as i32)?;
+        self.meta.write_vint(size as i32)?;
+        if size == self.max_doc as usize {
+            // dense: the ord of a vector is its doc id
+            self.meta.write_byte(0)?;
+        } else {
+            self.meta.write_byte(1)?;
+            let mut last_doc = 0;
+            for ord in 0..size {
+                let doc = values.ord_to_doc(ord);
+                debug_assert!(doc >= last_doc);
+                self.meta.write_vint(doc - last_doc)?;
+                last_doc = doc;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            bail!(IllegalState("already finished".into()));
+        }
+        self.finished = true;
+        self.meta.write_vint(-1)?;
+        write_footer(&mut self.meta)?;
+        write_footer(&mut self.data)
+    }
+}
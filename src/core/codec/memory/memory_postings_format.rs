@@ -0,0 +1,392 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{
+    codec_util, BlockTreeTermsReader, BlockTreeTermsWriter, Codec, FieldReaderRef, FieldsConsumer,
+    FieldsConsumerEnum, FieldsProducer, Lucene50PostingsFormat, Lucene50PostingsWriter,
+    NormsProducer, PostingsFormat,
+};
+use core::index::{
+    segment_file_name, FieldInfos, Fields, SegmentReadState, SegmentWriteState, TermIterator, Terms,
+};
+use core::search::posting_iterator::PostingIteratorFlags;
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::store::{DataInput, DataOutput, Directory, IndexOutput};
+use core::util::fst::fst_builder::FstBuilder;
+use core::util::fst::{ByteSequenceOutput, ByteSequenceOutputFactory, InputType, FST};
+use core::util::ints_ref::{to_ints_ref, IntsRefBuilder};
+use core::util::{Accountable, DocId, RamUsage};
+
+use error::{ErrorKind::CorruptIndex, Result};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Name of the memory postings format.
+pub const MEMORY_POSTINGS_FORMAT_NAME: &str = "Memory";
+
+const CODEC_NAME: &str = "MemoryPostings";
+const VERSION_START: i32 = 0;
+const VERSION_CURRENT: i32 = VERSION_START;
+
+/// Extension of the file holding the per field terms dictionaries.
+const EXTENSION: &str = "ram";
+
+/// A postings format which, besides the regular postings written by the
+/// `Lucene50PostingsFormat`, keeps the whole terms dictionary of every field
+/// indexed with it in a FST in memory, the output of each term being the
+/// docs having it.
+///
+/// It is meant for primary key fields: a lookup by `MemoryTermsDict::docs`
+/// costs O(term length) and never touches the disk, while the field can
+/// still be searched like any other. Since the postings of every term are
+/// held in memory, it is a poor fit for fields with frequent terms.
+/// Select it with `FieldType::set_postings_format`.
+#[derive(Default)]
+pub struct MemoryPostingsFormat {
+    delegate: Lucene50PostingsFormat,
+}
+
+impl PostingsFormat for MemoryPostingsFormat {
+    type FieldsProducer = MemoryFieldsProducer;
+
+    fn fields_producer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::FieldsProducer> {
+        let delegate = self.delegate.fields_producer(state)?;
+        MemoryFieldsProducer::new(delegate, state)
+    }
+
+    fn fields_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<FieldsConsumerEnum<D, DW, C>> {
+        let delegate = self.delegate.terms_writer(state)?;
+        Ok(FieldsConsumerEnum::Memory(MemoryFieldsConsumer::new(
+            delegate, state,
+        )?))
+    }
+
+    fn name(&self) -> &str {
+        MEMORY_POSTINGS_FORMAT_NAME
+    }
+}
+
+pub struct MemoryFieldsConsumer<O: IndexOutput> {
+    delegate: BlockTreeTermsWriter<Lucene50PostingsWriter<O>, O>,
+    field_infos: FieldInfos,
+    out: O,
+    closed: bool,
+}
+
+impl<O: IndexOutput> MemoryFieldsConsumer<O> {
+    fn new<D: Directory, DW: Directory<IndexOutput = O>, C: Codec>(
+        delegate: BlockTreeTermsWriter<Lucene50PostingsWriter<O>, O>,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<Self> {
+        let name = segment_file_name(&state.segment_info.name, &state.segment_suffix, EXTENSION);
+        let mut out = state.directory.create_output(&name, &state.context)?;
+        codec_util::write_index_header(
+            &mut out,
+            CODEC_NAME,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+
+        Ok(MemoryFieldsConsumer {
+            delegate,
+            field_infos: state.field_infos.clone(),
+            out,
+            closed: false,
+        })
+    }
+
+    fn write_terms_dict(&mut self, field: &str, terms: &impl Terms) -> Result<()> {
+        let mut builder = MemoryTermsDictBuilder::default();
+        let mut docs = vec![];
+        let mut terms_iter = terms.iterator()?;
+        while let Some(term) = terms_iter.next()? {
+            let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+            loop {
+                let doc = postings.next()?;
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                docs.push(doc);
+            }
+            builder.add_term(&term, &docs)?;
+            docs.clear();
+        }
+
+        let number = match self.field_infos.field_info_by_name(field) {
+            Some(info) => info.number,
+            None => bail!(CorruptIndex(format!("unknown field: {}", field))),
+        };
+        // field numbers are shifted by one, 0 marks the end of the fields
+        self.out.write_vint(number as i32 + 1)?;
+        builder.store(&mut self.out)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+
+        self.out.write_vint(0)?;
+        codec_util::write_footer(&mut self.out)
+    }
+}
+
+impl<O: IndexOutput> FieldsConsumer for MemoryFieldsConsumer<O> {
+    fn write(&mut self, fields: &impl Fields, norms: Option<&dyn NormsProducer>) -> Result<()> {
+        self.delegate.write(fields, norms)?;
+
+        for field in fields.fields() {
+            if let Some(terms) = fields.terms(&field)? {
+                self.write_terms_dict(&field, &terms)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<O: IndexOutput> Drop for MemoryFieldsConsumer<O> {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            error!("drop MemoryFieldsConsumer failed by '{:?}'", e);
+        }
+    }
+}
+
+/// Reads the postings of the fields written by `MemoryPostingsFormat`, and
+/// keeps their `MemoryTermsDict` in memory.
+pub struct MemoryFieldsProducer {
+    delegate: BlockTreeTermsReader,
+    terms_dicts: HashMap<String, Arc<MemoryTermsDict>>,
+}
+
+impl MemoryFieldsProducer {
+    fn new<D: Directory, DW: Directory, C: Codec>(
+        delegate: BlockTreeTermsReader,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<MemoryFieldsProducer> {
+        let name = segment_file_name(&state.segment_info.name, &state.segment_suffix, EXTENSION);
+        let mut input = state.directory.open_checksum_input(&name, &state.context)?;
+        codec_util::check_index_header(
+            &mut input,
+            CODEC_NAME,
+            VERSION_START,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+
+        let mut terms_dicts = HashMap::new();
+        loop {
+            let number = input.read_vint()?;
+            if number == 0 {
+                break;
+            }
+            let field = match state.field_infos.field_info_by_number((number - 1) as u32) {
+                Some(info) => info.name.clone(),
+                None => bail!(CorruptIndex(format!(
+                    "invalid field number: {}",
+                    number - 1
+                ))),
+            };
+            let terms_dict = MemoryTermsDict::load(&mut input)?;
+            terms_dicts.insert(field, Arc::new(terms_dict));
+        }
+        codec_util::check_footer(&mut input)?;
+
+        Ok(MemoryFieldsProducer {
+            delegate,
+            terms_dicts,
+        })
+    }
+}
+
+impl FieldsProducer for MemoryFieldsProducer {
+    fn check_integrity(&self) -> Result<()> {
+        self.delegate.check_integrity()
+    }
+
+    fn memory_terms_dict(&self, field: &str) -> Option<Arc<MemoryTermsDict>> {
+        self.terms_dicts.get(field).cloned()
+    }
+}
+
+impl Accountable for MemoryFieldsProducer {
+    fn ram_bytes_used(&self) -> usize {
+        self.delegate.ram_bytes_used()
+            + self
+                .terms_dicts
+                .values()
+                .map(|d| d.ram_bytes_used())
+                .sum::<usize>()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        let mut resources = vec![RamUsage::of("delegate", &self.delegate)];
+        for (field, terms_dict) in &self.terms_dicts {
+            resources.push(RamUsage::of(format!("terms dict '{}'", field), terms_dict));
+        }
+        resources
+    }
+}
+
+impl Fields for MemoryFieldsProducer {
+    type Terms = FieldReaderRef;
+    fn fields(&self) -> Vec<String> {
+        self.delegate.fields()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<Self::Terms>> {
+        self.delegate.terms(field)
+    }
+
+    fn size(&self) -> usize {
+        self.delegate.size()
+    }
+
+    fn terms_freq(&self, field: &str) -> usize {
+        self.delegate.terms_freq(field)
+    }
+}
+
+/// Builds the FST of a `MemoryTermsDict`, terms must be added in sorted order.
+#[derive(Default)]
+pub struct MemoryTermsDictBuilder {
+    // the FST builder keeps pointers to itself, so it can't be stored here and
+    // the encoded terms are only added to it in `store`
+    terms: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl MemoryTermsDictBuilder {
+    /// Adds `term` with the sorted segment local `docs` having it.
+    pub fn add_term(&mut self, term: &[u8], docs: &[DocId]) -> Result<()> {
+        debug_assert!(self.terms.last().map_or(true, |t| t.0.as_slice() < term));
+        let mut output = Vec::with_capacity(docs.len() + 1);
+        output.write_vint(docs.len() as i32)?;
+        let mut last_doc = 0;
+        for &doc in docs {
+            output.write_vint(doc - last_doc)?;
+            last_doc = doc;
+        }
+        self.terms.push((term.to_vec(), output));
+        Ok(())
+    }
+
+    /// Writes the built FST, a field without any term gets an empty one.
+    pub fn store(&mut self, out: &mut impl DataOutput) -> Result<()> {
+        let mut builder = FstBuilder::new(InputType::Byte1, ByteSequenceOutputFactory::new());
+        builder.init();
+        let mut scratch_ints = IntsRefBuilder::new();
+        for (term, output) in self.terms.drain(..) {
+            let input = to_ints_ref(&term, &mut scratch_ints);
+            builder.add(input, ByteSequenceOutput::new(output))?;
+        }
+        match builder.finish()? {
+            Some(fst) => {
+                out.write_byte(1)?;
+                fst.save(out)
+            }
+            None => out.write_byte(0),
+        }
+    }
+}
+
+/// The whole terms dictionary of a field for one segment held in a FST,
+/// loaded by `MemoryFieldsProducer`.
+pub struct MemoryTermsDict {
+    fst: Option<FST<ByteSequenceOutputFactory>>,
+}
+
+impl Accountable for MemoryTermsDict {
+    fn ram_bytes_used(&self) -> usize {
+        self.fst.as_ref().map_or(0, |fst| fst.ram_bytes_used())
+    }
+}
+
+impl MemoryTermsDict {
+    pub fn load<I: DataInput + ?Sized>(input: &mut I) -> Result<MemoryTermsDict> {
+        let fst = if input.read_byte()? != 0 {
+            Some(FST::from_input(input, ByteSequenceOutputFactory::new())?)
+        } else {
+            None
+        };
+        Ok(MemoryTermsDict { fst })
+    }
+
+    /// Returns the segment local docs having `term` in increasing order, or
+    /// `None` if no doc has it.
+    ///
+    /// Deleted docs are not filtered out, check them against the live docs of
+    /// the segment.
+    pub fn docs(&self, term: &[u8]) -> Result<Option<Vec<DocId>>> {
+        let output = match self.fst {
+            Some(ref fst) => fst.get(term)?,
+            None => None,
+        };
+        match output {
+            Some(output) => {
+                let mut input = output.inner();
+                let count = input.read_vint()?;
+                let mut docs = Vec::with_capacity(count.max(0) as usize);
+                let mut doc = 0;
+                for _ in 0..count {
+                    doc += input.read_vint()?;
+                    docs.push(doc);
+                }
+                Ok(Some(docs))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_terms_dict_lookup() {
+        let mut builder = MemoryTermsDictBuilder::default();
+        builder.add_term(b"id-1", &[3]).unwrap();
+        builder.add_term(b"id-10", &[0, 7]).unwrap();
+        builder.add_term(b"id-2", &[5]).unwrap();
+        let mut out = vec![];
+        builder.store(&mut out).unwrap();
+
+        let mut input = out.as_slice();
+        let terms_dict = MemoryTermsDict::load(&mut input).unwrap();
+        assert_eq!(terms_dict.docs(b"id-1").unwrap(), Some(vec![3]));
+        assert_eq!(terms_dict.docs(b"id-10").unwrap(), Some(vec![0, 7]));
+        assert_eq!(terms_dict.docs(b"id-2").unwrap(), Some(vec![5]));
+        assert_eq!(terms_dict.docs(b"id-").unwrap(), None);
+        assert_eq!(terms_dict.docs(b"id-3").unwrap(), None);
+    }
+
+    #[test]
+    fn test_empty_terms_dict() {
+        let mut out = vec![];
+        MemoryTermsDictBuilder::default().store(&mut out).unwrap();
+
+        let mut input = out.as_slice();
+        let terms_dict = MemoryTermsDict::load(&mut input).unwrap();
+        assert_eq!(terms_dict.docs(b"id-1").unwrap(), None);
+    }
+}
@@ -27,6 +27,10 @@ mod compressing;
 
 pub use self::compressing::*;
 
+mod competitive_impact;
+
+pub use self::competitive_impact::*;
+
 mod format;
 
 pub use self::format::*;
@@ -51,10 +55,30 @@ mod lucene62;
 
 pub use self::lucene62::*;
 
+mod lucene70;
+
+pub use self::lucene70::*;
+
+mod lucene90;
+
+pub use self::lucene90::*;
+
+mod memory;
+
+pub use self::memory::*;
+
 mod reader;
 
 pub use self::reader::*;
 
+mod registry;
+
+pub use self::registry::*;
+
+mod rucene54;
+
+pub use self::rucene54::*;
+
 mod writer;
 
 pub use self::writer::*;
@@ -279,6 +303,7 @@ pub trait Codec: TryFrom<String, Error = Error> + 'static {
     type LiveDocFmt: LiveDocsFormat;
     type CompoundFmt: CompoundFormat;
     type PointFmt: PointsFormat;
+    type VectorFmt: VectorFormat;
 
     fn name(&self) -> &str;
     fn postings_format(&self) -> Self::PostingFmt;
@@ -291,6 +316,7 @@ pub trait Codec: TryFrom<String, Error = Error> + 'static {
     fn live_docs_format(&self) -> Self::LiveDocFmt;
     fn compound_format(&self) -> Self::CompoundFmt;
     fn points_format(&self) -> Self::PointFmt;
+    fn vector_format(&self) -> Self::VectorFmt;
 }
 
 pub type CodecFieldsProducer<C> = <<C as Codec>::PostingFmt as PostingsFormat>::FieldsProducer;
@@ -307,7 +333,12 @@ pub type CodecNormsProducer<C> = <<C as Codec>::NormFmt as NormsFormat>::NormsPr
 pub type CodecPointsReader<C> = <<C as Codec>::PointFmt as PointsFormat>::Reader;
 
 pub enum CodecEnum {
+    Lucene60(Lucene60Codec),
     Lucene62(Lucene62Codec),
+    Lucene70(Lucene70Codec),
+    /// A codec registered with `register_codec`, its name is recorded in the
+    /// segments in place of the name of the wrapped codec.
+    Named(&'static str, Box<CodecEnum>),
 }
 
 impl Codec for CodecEnum {
@@ -317,67 +348,111 @@ impl Codec for CodecEnum {
     type StoredFmt = Lucene50StoredFieldsFormat;
     type TVFmt = CompressingTermVectorsFormat;
     type FieldFmt = Lucene60FieldInfosFormat;
-    type SegmentFmt = Lucene62SegmentInfoFormat;
-    type NormFmt = Lucene53NormsFormat;
+    type SegmentFmt = SegmentInfoFormatEnum;
+    type NormFmt = NormsFormatEnum;
     type LiveDocFmt = Lucene50LiveDocsFormat;
     type CompoundFmt = Lucene50CompoundFormat;
     type PointFmt = Lucene60PointsFormat;
+    type VectorFmt = Lucene90VectorFormat;
 
     fn name(&self) -> &str {
         match self {
+            CodecEnum::Lucene60(c) => c.name(),
             CodecEnum::Lucene62(c) => c.name(),
+            CodecEnum::Lucene70(c) => c.name(),
+            CodecEnum::Named(name, _) => name,
         }
     }
     fn postings_format(&self) -> Self::PostingFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.postings_format(),
             CodecEnum::Lucene62(c) => c.postings_format(),
+            CodecEnum::Lucene70(c) => c.postings_format(),
+            CodecEnum::Named(_, c) => c.postings_format(),
         }
     }
     fn doc_values_format(&self) -> Self::DVFmt {
         match self {
+            CodecEnum::Lucene60(c) => DocValuesFormatEnum::PerField(c.doc_values_format()),
             CodecEnum::Lucene62(c) => DocValuesFormatEnum::PerField(c.doc_values_format()),
+            CodecEnum::Lucene70(c) => DocValuesFormatEnum::PerField(c.doc_values_format()),
+            CodecEnum::Named(_, c) => c.doc_values_format(),
         }
     }
     fn stored_fields_format(&self) -> Self::StoredFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.stored_fields_format(),
             CodecEnum::Lucene62(c) => c.stored_fields_format(),
+            CodecEnum::Lucene70(c) => c.stored_fields_format(),
+            CodecEnum::Named(_, c) => c.stored_fields_format(),
         }
     }
     fn term_vectors_format(&self) -> Self::TVFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.term_vectors_format(),
             CodecEnum::Lucene62(c) => c.term_vectors_format(),
+            CodecEnum::Lucene70(c) => c.term_vectors_format(),
+            CodecEnum::Named(_, c) => c.term_vectors_format(),
         }
     }
     fn field_infos_format(&self) -> Self::FieldFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.field_infos_format(),
             CodecEnum::Lucene62(c) => c.field_infos_format(),
+            CodecEnum::Lucene70(c) => c.field_infos_format(),
+            CodecEnum::Named(_, c) => c.field_infos_format(),
         }
     }
     fn segment_info_format(&self) -> Self::SegmentFmt {
         match self {
-            CodecEnum::Lucene62(c) => c.segment_info_format(),
+            CodecEnum::Lucene60(c) => SegmentInfoFormatEnum::Lucene50(c.segment_info_format()),
+            CodecEnum::Lucene62(c) => SegmentInfoFormatEnum::Lucene62(c.segment_info_format()),
+            CodecEnum::Lucene70(c) => SegmentInfoFormatEnum::Lucene70(c.segment_info_format()),
+            CodecEnum::Named(_, c) => c.segment_info_format(),
         }
     }
     fn norms_format(&self) -> Self::NormFmt {
         match self {
-            CodecEnum::Lucene62(c) => c.norms_format(),
+            CodecEnum::Lucene60(c) => NormsFormatEnum::Lucene53(c.norms_format()),
+            CodecEnum::Lucene62(c) => NormsFormatEnum::Lucene53(c.norms_format()),
+            CodecEnum::Lucene70(c) => NormsFormatEnum::Lucene70(c.norms_format()),
+            CodecEnum::Named(_, c) => c.norms_format(),
         }
     }
     fn live_docs_format(&self) -> Self::LiveDocFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.live_docs_format(),
             CodecEnum::Lucene62(c) => c.live_docs_format(),
+            CodecEnum::Lucene70(c) => c.live_docs_format(),
+            CodecEnum::Named(_, c) => c.live_docs_format(),
         }
     }
     fn compound_format(&self) -> Self::CompoundFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.compound_format(),
             CodecEnum::Lucene62(c) => c.compound_format(),
+            CodecEnum::Lucene70(c) => c.compound_format(),
+            CodecEnum::Named(_, c) => c.compound_format(),
         }
     }
 
     /// Encodes/decodes points index
     fn points_format(&self) -> Self::PointFmt {
         match self {
+            CodecEnum::Lucene60(c) => c.points_format(),
             CodecEnum::Lucene62(c) => c.points_format(),
+            CodecEnum::Lucene70(c) => c.points_format(),
+            CodecEnum::Named(_, c) => c.points_format(),
+        }
+    }
+
+    /// Encodes/decodes vectors
+    fn vector_format(&self) -> Self::VectorFmt {
+        match self {
+            CodecEnum::Lucene60(c) => c.vector_format(),
+            CodecEnum::Lucene62(c) => c.vector_format(),
+            CodecEnum::Lucene70(c) => c.vector_format(),
+            CodecEnum::Named(_, c) => c.vector_format(),
         }
     }
 }
@@ -386,27 +461,45 @@ impl TryFrom<String> for CodecEnum {
     type Error = Error;
 
     fn try_from(value: String) -> Result<Self> {
-        match value.as_str() {
-            "Lucene62" => Ok(CodecEnum::Lucene62(lucene62::Lucene62Codec::try_from(
-                value,
-            )?)),
-            _ => bail!(IllegalArgument(format!("Invalid codec name: {}", value))),
-        }
+        codec_for_name(&value)
     }
 }
 
+/// Returns the codec with the given name, either a built-in codec or one
+/// registered with `register_codec`.
 pub fn codec_for_name(name: &str) -> Result<CodecEnum> {
+    match builtin_codec(name).or_else(|| registered_codec(name)) {
+        Some(codec) => Ok(codec),
+        None => bail!(IllegalArgument(format!("Invalid codec name: {}", name))),
+    }
+}
+
+pub(crate) fn builtin_codec(name: &str) -> Option<CodecEnum> {
     match name {
-        "Lucene62" => Ok(CodecEnum::Lucene62(lucene62::Lucene62Codec::try_from(
-            name.to_string(),
-        )?)),
-        _ => bail!(IllegalArgument(format!("Invalid codec name: {}", name))),
+        "Lucene60" => Some(CodecEnum::Lucene60(Lucene60Codec::default())),
+        "Lucene62" => Some(CodecEnum::Lucene62(lucene62::Lucene62Codec::default())),
+        "Lucene70" => Some(CodecEnum::Lucene70(Lucene70Codec::default())),
+        _ => None,
     }
 }
 
 #[cfg(test)]
 pub mod tests {
-    use core::codec::{CodecEnum, Lucene62Codec};
+    use core::codec::{Codec, CodecEnum, Lucene62Codec};
+    use core::doc::IntPoint;
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::{
+        IndexReader, LeafReaderContext, StandardDirectoryReader, Term, NO_MORE_ORDS,
+    };
+    use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::search::{DocIterator, NO_MORE_DOCS};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::DocId;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::sync::Arc;
 
     pub type TestCodec = CodecEnum;
 
@@ -415,4 +508,150 @@ pub mod tests {
             CodecEnum::Lucene62(Lucene62Codec::default())
         }
     }
+
+    const BACK_COMPAT_DOCS: i32 = 50;
+    const BACK_COMPAT_DELETED_DOC: i32 = 7;
+
+    fn postings(leaf: &LeafReaderContext<CodecEnum>, field: &str, term: &str) -> Vec<(DocId, i32)> {
+        let term = Term::new(field.into(), term.as_bytes().to_vec());
+        let flags = i32::from(PostingIteratorFlags::FREQS);
+        let mut result = vec![];
+        if let Some(mut postings) = leaf.reader.postings(&term, flags).unwrap() {
+            loop {
+                let doc = postings.next().unwrap();
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                result.push((leaf.doc_base + doc, postings.freq().unwrap()));
+            }
+        }
+        result
+    }
+
+    /// Opens the index `name` written by Lucene with
+    /// `src/core/codec/back_compat/GenerateBackCompatIndex.java`, and checks
+    /// its postings, norms, doc values, points and stored fields.
+    pub fn check_back_compat_index(name: &str, codec_name: &str) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("src/core/codec/back_compat")
+            .join(name);
+        assert!(path.is_dir(), "missing back-compat index {:?}", path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let reader: StandardDirectoryReader<_, CodecEnum, SerialMergeScheduler, TieredMergePolicy> =
+            StandardDirectoryReader::open(dir).unwrap();
+        assert_eq!(reader.max_doc(), BACK_COMPAT_DOCS);
+        assert_eq!(reader.num_docs(), BACK_COMPAT_DOCS - 1);
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 2);
+
+        let mut common_docs = vec![];
+        for leaf in &leaves {
+            let leaf_reader = leaf.reader;
+            assert_eq!(leaf_reader.codec().name(), codec_name);
+            let live_docs = leaf_reader.live_docs();
+
+            // postings, the deleted doc is still there
+            for (doc, freq) in postings(leaf, "body", "common") {
+                assert_eq!(freq, doc % 3 + 1);
+                common_docs.push(doc);
+            }
+            for (doc, freq) in postings(leaf, "body", "t3") {
+                assert_eq!(doc % 10, 3);
+                assert_eq!(freq, 1);
+            }
+            for doc in 0..leaf_reader.max_doc() {
+                let id = leaf.doc_base + doc;
+                assert_eq!(postings(leaf, "id", &id.to_string()), vec![(id, 1)]);
+                assert_eq!(
+                    live_docs.get(doc as usize).unwrap(),
+                    id != BACK_COMPAT_DELETED_DOC
+                );
+            }
+
+            // the norms only depend on the number of tokens
+            let norms = leaf_reader.norm_values("body").unwrap().unwrap();
+            let mut norms_by_length = HashMap::new();
+            for doc in 0..leaf_reader.max_doc() {
+                let length = (leaf.doc_base + doc) % 3 + 2;
+                let norm = norms.get(doc).unwrap();
+                assert_eq!(*norms_by_length.entry(length).or_insert(norm), norm);
+            }
+            assert_eq!(norms_by_length.len(), 3);
+            assert_ne!(norms_by_length[&2], norms_by_length[&4]);
+
+            // doc values
+            let num = leaf_reader.get_numeric_doc_values("num").unwrap();
+            let docs_with_num = leaf_reader.get_docs_with_field("num").unwrap();
+            let dense_num = leaf_reader.get_numeric_doc_values("dense_num").unwrap();
+            let binary = leaf_reader.get_binary_doc_values("binary").unwrap();
+            let sorted = leaf_reader.get_sorted_doc_values("sorted").unwrap();
+            let sorted_set = leaf_reader.get_sorted_set_doc_values("sorted_set").unwrap();
+            let sorted_num = leaf_reader
+                .get_sorted_numeric_doc_values("sorted_num")
+                .unwrap();
+            for doc in 0..leaf_reader.max_doc() {
+                let id = leaf.doc_base + doc;
+                let has_num = id % 2 == 0;
+                assert_eq!(docs_with_num.get(doc as usize).unwrap(), has_num);
+                if has_num {
+                    assert_eq!(num.get(doc).unwrap(), i64::from(id) * 3);
+                }
+                assert_eq!(dense_num.get(doc).unwrap(), i64::from(id) - 25);
+                assert_eq!(
+                    binary.get(doc).unwrap(),
+                    format!("binary{}", id).into_bytes()
+                );
+                let ord = sorted.get_ord(doc).unwrap();
+                assert_eq!(
+                    sorted.lookup_ord(ord).unwrap(),
+                    format!("s{}", id % 5).into_bytes()
+                );
+
+                let mut ctx = sorted_set.set_document(doc).unwrap();
+                let mut values = vec![];
+                loop {
+                    let ord = sorted_set.next_ord(&mut ctx).unwrap();
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    values.push(sorted_set.lookup_ord(ord).unwrap());
+                }
+                assert_eq!(
+                    values,
+                    vec![
+                        format!("a{}", id % 3).into_bytes(),
+                        format!("b{}", id % 4).into_bytes(),
+                    ]
+                );
+
+                let ctx = sorted_num.set_document(None, doc).unwrap();
+                assert_eq!(sorted_num.count(&ctx), 2);
+                assert_eq!(sorted_num.value_at(&ctx, 0).unwrap(), -i64::from(id));
+                assert_eq!(sorted_num.value_at(&ctx, 1).unwrap(), i64::from(id));
+            }
+        }
+        common_docs.sort();
+        assert_eq!(common_docs, (0..BACK_COMPAT_DOCS).collect::<Vec<_>>());
+
+        // stored fields
+        for id in 0..BACK_COMPAT_DOCS {
+            let document = reader.document(id, &["id".to_string()]).unwrap();
+            assert_eq!(
+                document.fields[0].field.string_value(),
+                Some(id.to_string().as_str())
+            );
+        }
+
+        // points, the ranges span both segments
+        let searcher = DefaultIndexSearcher::new(&reader);
+        let query = IntPoint::new_range_query("point".into(), 5, 30).unwrap();
+        // doc 7 is deleted
+        assert_eq!(searcher.count(query.as_ref()).unwrap(), 25);
+        let query =
+            IntPoint::new_multi_range_query("point2d".into(), &[20, -40], &[40, -20]).unwrap();
+        assert_eq!(searcher.count(query.as_ref()).unwrap(), 21);
+        let query =
+            IntPoint::new_multi_range_query("point2d".into(), &[20, -10], &[40, 0]).unwrap();
+        assert_eq!(searcher.count(query.as_ref()).unwrap(), 0);
+    }
 }
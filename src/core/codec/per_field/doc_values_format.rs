@@ -31,7 +31,7 @@ use core::index::{SegmentReadState, SegmentWriteState};
 use core::store::Directory;
 use core::util::numeric::Numeric;
 use core::util::BytesRef;
-use core::util::{BitsRef, ReusableIterator};
+use core::util::{Accountable, BitsRef, RamUsage, ReusableIterator};
 
 use error::ErrorKind::{IllegalArgument, IllegalState};
 use error::Result;
@@ -56,6 +56,11 @@ fn get_full_segment_suffix(outer_segment_suffix: &str, segment_suffix: String) -
     }
 }
 
+/// Writes the doc values of each field with the format named by its
+/// `FieldType::doc_values_format`, or `Lucene54` by default (`Rucene54` for
+/// the binary fields compressed by blocks), the format of a field being
+/// recorded in its `FieldInfo` attributes so it's read, updated and merged
+/// with the same one.
 #[derive(Default, Clone, Copy)]
 pub struct PerFieldDocValuesFormat;
 
@@ -144,6 +149,38 @@ impl DocValuesFieldsReader {
     }
 }
 
+impl DocValuesFieldsReader {
+    // the fields of a format share the same producer
+    fn producers(&self) -> Vec<(&str, &DocValuesProducerRef)> {
+        let mut seen: Vec<*const u8> = Vec::new();
+        let mut producers = Vec::new();
+        for (field, producer) in &self.fields {
+            let ptr = producer.as_ref() as *const dyn DocValuesProducer as *const u8;
+            if !seen.contains(&ptr) {
+                seen.push(ptr);
+                producers.push((field.as_str(), producer));
+            }
+        }
+        producers
+    }
+}
+
+impl Accountable for DocValuesFieldsReader {
+    fn ram_bytes_used(&self) -> usize {
+        self.producers()
+            .iter()
+            .map(|(_, producer)| producer.ram_bytes_used())
+            .sum()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.producers()
+            .into_iter()
+            .map(|(field, producer)| RamUsage::of(format!("format of field '{}'", field), producer))
+            .collect()
+    }
+}
+
 impl DocValuesProducer for DocValuesFieldsReader {
     fn get_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
         match self.fields.get(&field.name) {
@@ -215,6 +252,13 @@ impl DocValuesProducer for DocValuesFieldsReader {
     fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>> {
         Ok(Box::new(DocValuesFieldsReader::copy_for_merge(self)?))
     }
+
+    fn evict(&self, field: &FieldInfo) -> Result<()> {
+        match self.fields.get(&field.name) {
+            Some(producer) => producer.evict(field),
+            None => Ok(()),
+        }
+    }
 }
 
 struct ConsumerAndSuffix<D: Directory, DW: Directory, C: Codec> {
@@ -238,32 +282,30 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesFieldsWriter<D, DW, C> {
     }
 
     fn get_instance(&mut self, field: &FieldInfo) -> Result<&mut DocValuesConsumerEnum<D, DW, C>> {
-        let mut format: Option<DocValuesFormatEnum> = None;
-        if field.dv_gen != -1 {
-            // this means the field never existed in that segment, yet is applied updates
-            if let Some(format_name) = field.attribute(PER_FIELD_VALUE_FORMAT_KEY) {
-                format = Some(doc_values_format_for_name(&format_name)?);
+        // the format is the one chosen by the field type, kept across merges,
+        // or the one the field was written with when applying updates, unless
+        // it can only read, e.g. for the fields merged from older segments
+        // a registered format keeps its own name, the reader resolves it again
+        let format = match field.attribute(PER_FIELD_VALUE_FORMAT_KEY) {
+            Some(format_name) => {
+                let format = doc_values_format_for_name(&format_name)?;
+                if format.read_only() {
+                    None
+                } else {
+                    Some((format, format_name))
+                }
             }
-        }
-        if format.is_none() {
-            // TODO hard code for `PerFieldDocValuesFormat.getDocValuesFormatForField`
-            format = Some(DocValuesFormatEnum::Lucene54(
-                Lucene54DocValuesFormat::default(),
-            ));
-        }
-
-        let format = format.unwrap();
-        let format_name = format.name().to_string();
-        let prev = field.put_attribute(PER_FIELD_VALUE_FORMAT_KEY.to_string(), format_name.clone());
-        if field.dv_gen == -1 && prev.is_some() {
-            bail!(IllegalState(format!(
-                "found existing value for {}, field={}, old={}, new={}",
-                PER_FIELD_VALUE_FORMAT_KEY,
-                field.name,
-                prev.unwrap(),
-                &format_name
-            )));
-        }
+            None => None,
+        };
+        let (format, format_name) = match format {
+            Some(format) => format,
+            None => {
+                let format = DocValuesFormatEnum::Lucene54(Lucene54DocValuesFormat::default());
+                let format_name = format.name().to_string();
+                (format, format_name)
+            }
+        };
+        field.put_attribute(PER_FIELD_VALUE_FORMAT_KEY.to_string(), format_name.clone());
 
         let mut suffix: Option<i32> = None;
         if self.formats.contains_key(&format_name) {
@@ -306,15 +348,16 @@ impl<D: Directory, DW: Directory, C: Codec> DocValuesFieldsWriter<D, DW, C> {
             self.segment_write_state.segment_suffix = old_suffix;
         }
 
-        if let Some(p) = field.put_attribute(
+        let prev = field.put_attribute(
             PER_FIELD_VALUE_SUFFIX_KEY.to_string(),
             format!("{}", suffix.unwrap()),
-        ) {
+        );
+        if field.dv_gen == -1 && prev.is_some() {
             bail!(IllegalState(format!(
                 "found existing value for {}, field={}, old={}, new={}",
                 PER_FIELD_VALUE_SUFFIX_KEY,
                 field.name,
-                p,
+                prev.unwrap(),
                 suffix.unwrap()
             )));
         }
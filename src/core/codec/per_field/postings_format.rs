@@ -17,16 +17,19 @@ use std::fmt;
 use std::mem;
 use std::sync::Arc;
 
-use core::codec::blocktree::FieldReaderRef;
+use core::codec::blocktree::{validate_block_sizes, FieldReaderRef};
 use core::codec::consumer::FieldsConsumerEnum;
-use core::codec::format::{postings_format_for_name, PostingsFormat};
-use core::codec::lucene50::Lucene50PostingsFormat;
+use core::codec::format::{postings_format_for_name, PostingsFormat, PostingsFormatEnum};
 use core::codec::producer::FieldsProducerEnum;
-use core::codec::{Codec, FieldsConsumer, FieldsProducer};
+use core::codec::{
+    Codec, FieldsConsumer, FieldsProducer, Lucene50PostingsFormat, MemoryTermsDict, NormsProducer,
+};
 use core::index::Fields;
 use core::index::{IndexOptions, SegmentReadState, SegmentWriteState};
 use core::store::Directory;
-use error::Result;
+use core::suggest::document::NRTSuggester;
+use core::util::{Accountable, RamUsage};
+use error::{ErrorKind::IllegalState, Result};
 
 /// Name of this {@link PostingsFormat}. */
 // const PER_FIELD_NAME: &str = "PerField40";
@@ -42,24 +45,48 @@ fn get_suffix(format: &str, suffix: &str) -> String {
 }
 
 /// Enables per field postings support.
-/// <p>
-/// Note, when extending this class, the name ({@link #getName}) is
-/// written into the index. In order for the field to be read, the
-/// name must resolve to your implementation via {@link #forName(String)}.
-/// This method uses Java's
-/// {@link ServiceLoader Service Provider Interface} to resolve format names.
-/// <p>
+///
+/// Writes the postings of each field with the format named by its
+/// `FieldType::postings_format`, or `Lucene50` by default, e.g. the
+/// `MemoryPostingsFormat` for an ID field while the text fields use the
+/// default block tree format. The format of a field is recorded in its
+/// `FieldInfo` attributes and resolved by `postings_format_for_name` when the
+/// segment is read.
+///
 /// Files written by each posting format have an additional suffix containing the
-/// format name. For example, in a per-field configuration instead of <tt>_1.prx</tt>
-/// filenames would look like <tt>_1_Lucene40_0.prx</tt>.
-/// @see ServiceLoader
-/// @lucene.experimental
+/// format name. For example, in a per-field configuration instead of <tt>_1.tim</tt>
+/// filenames would look like <tt>_1_Lucene50_0.tim</tt>.
 #[derive(Copy, Clone)]
-pub struct PerFieldPostingsFormat;
+pub struct PerFieldPostingsFormat {
+    min_term_block_size: usize,
+    max_term_block_size: usize,
+}
 
 impl Default for PerFieldPostingsFormat {
     fn default() -> PerFieldPostingsFormat {
-        PerFieldPostingsFormat {}
+        PerFieldPostingsFormat {
+            min_term_block_size: Lucene50PostingsFormat::DEFAULT_MIN_BLOCK_SIZE,
+            max_term_block_size: Lucene50PostingsFormat::DEFAULT_MAX_BLOCK_SIZE,
+        }
+    }
+}
+
+impl PerFieldPostingsFormat {
+    /// Writes the block tree terms dictionary of the `Lucene50` fields with
+    /// blocks of `min_term_block_size` to `max_term_block_size` terms.
+    ///
+    /// Smaller blocks make the terms index larger and seeks faster, larger
+    /// blocks do the opposite. The block sizes are only needed to write, so
+    /// segments written with other sizes are still read by the default format.
+    pub fn with_term_block_size(
+        min_term_block_size: usize,
+        max_term_block_size: usize,
+    ) -> Result<PerFieldPostingsFormat> {
+        validate_block_sizes(min_term_block_size, max_term_block_size)?;
+        Ok(PerFieldPostingsFormat {
+            min_term_block_size,
+            max_term_block_size,
+        })
     }
 }
 
@@ -78,6 +105,8 @@ impl PostingsFormat for PerFieldPostingsFormat {
     ) -> Result<FieldsConsumerEnum<D, DW, C>> {
         Ok(FieldsConsumerEnum::PerField(PerFieldFieldsWriter::new(
             state,
+            self.min_term_block_size,
+            self.max_term_block_size,
         )))
     }
 
@@ -113,11 +142,11 @@ impl PerFieldFieldsReader {
                     .unwrap()
                     .get(PER_FIELD_POSTING_SUFFIX_KEY)
                 {
-                    if !formats.contains_key(suffix) {
+                    let suffix = get_suffix(format, suffix);
+                    if !formats.contains_key(&suffix) {
                         formats.insert(suffix.clone(), postings_format_for_name(format)?);
                     }
-                    let postings_format = formats.get(suffix).unwrap();
-                    let suffix = get_suffix(format, suffix);
+                    let postings_format = &formats[&suffix];
                     let state = SegmentReadState::with_suffix(state, &suffix);
                     fields.insert(name.clone(), postings_format.fields_producer(&state)?);
                 } else {
@@ -151,6 +180,19 @@ impl fmt::Display for PerFieldFieldsReader {
     }
 }
 
+impl Accountable for PerFieldFieldsReader {
+    fn ram_bytes_used(&self) -> usize {
+        self.fields.values().map(Accountable::ram_bytes_used).sum()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.fields
+            .iter()
+            .map(|(name, producer)| RamUsage::of(format!("format of field '{}'", name), producer))
+            .collect()
+    }
+}
+
 impl FieldsProducer for PerFieldFieldsReader {
     fn check_integrity(&self) -> Result<()> {
         for producer in self.fields.values() {
@@ -158,6 +200,18 @@ impl FieldsProducer for PerFieldFieldsReader {
         }
         Ok(())
     }
+
+    fn suggester(&self, field: &str) -> Option<Arc<NRTSuggester>> {
+        self.fields
+            .get(field)
+            .and_then(|producer| producer.suggester(field))
+    }
+
+    fn memory_terms_dict(&self, field: &str) -> Option<Arc<MemoryTermsDict>> {
+        self.fields
+            .get(field)
+            .and_then(|producer| producer.memory_terms_dict(field))
+    }
 }
 
 impl Fields for PerFieldFieldsReader {
@@ -173,21 +227,22 @@ impl Fields for PerFieldFieldsReader {
     }
 }
 
-#[allow(dead_code)]
-struct FieldsGroup<D: Directory, DW: Directory, C: Codec> {
-    fields: BTreeSet<String>,
-    suffix: usize,
-    state: SegmentWriteState<D, DW, C>,
-}
-
 pub struct PerFieldFieldsWriter<D: Directory, DW: Directory, C: Codec> {
     write_state: SegmentWriteState<D, DW, C>,
+    min_term_block_size: usize,
+    max_term_block_size: usize,
 }
 
 impl<D: Directory, DW: Directory, C: Codec> PerFieldFieldsWriter<D, DW, C> {
-    pub fn new(write_state: &SegmentWriteState<D, DW, C>) -> Self {
+    pub fn new(
+        write_state: &SegmentWriteState<D, DW, C>,
+        min_term_block_size: usize,
+        max_term_block_size: usize,
+    ) -> Self {
         PerFieldFieldsWriter {
             write_state: write_state.clone(),
+            min_term_block_size,
+            max_term_block_size,
         }
     }
 
@@ -198,24 +253,98 @@ impl<D: Directory, DW: Directory, C: Codec> PerFieldFieldsWriter<D, DW, C> {
 }
 
 impl<D: Directory, DW: Directory, C: Codec> FieldsConsumer for PerFieldFieldsWriter<D, DW, C> {
-    // TODO this is only one postings format currently (lucene50).
-    // And we assume that we always use one format all the time.
-    // so we won't implement this like lucene
-    // when the format changes, it's easy to change the hard code then.
-    fn write(&mut self, fields: &impl Fields) -> Result<()> {
-        // this is only one format, so suffix is always "0"
-        let segment_suffix =
-            self.get_full_segment_suffix(&self.write_state.segment_suffix, "Lucene50_0".into());
-        // always use lucene50, so just hard code it.
-        let format = Lucene50PostingsFormat::default();
+    fn write(&mut self, fields: &impl Fields, norms: Option<&dyn NormsProducer>) -> Result<()> {
+        // group the fields by the postings format they are written with, fields
+        // without the format attribute use the default lucene50 format.
+        let mut groups: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        groups.insert("Lucene50".into(), BTreeSet::new());
+        // the reader opens every format named by the field infos, so their files
+        // must be written even if none of their fields got a term in this segment
+        for info in self.write_state.field_infos.by_number.values() {
+            if info.index_options == IndexOptions::Null {
+                continue;
+            }
+            if let Some(format) = info.attribute(PER_FIELD_POSTING_FORMAT_KEY) {
+                groups.entry(format).or_insert_with(BTreeSet::new);
+            }
+        }
+        for field in fields.fields() {
+            let format = self
+                .write_state
+                .field_infos
+                .field_info_by_name(&field)
+                .and_then(|fi| fi.attribute(PER_FIELD_POSTING_FORMAT_KEY))
+                .unwrap_or_else(|| "Lucene50".into());
+            groups
+                .entry(format)
+                .or_insert_with(BTreeSet::new)
+                .insert(field);
+        }
+
+        for (format_name, group) in groups {
+            // each format has only one instance, so suffix is always "0"
+            let segment_suffix = self.get_full_segment_suffix(
+                &self.write_state.segment_suffix,
+                get_suffix(&format_name, "0"),
+            );
+            let format = if format_name == "Lucene50" {
+                PostingsFormatEnum::Lucene50(Lucene50PostingsFormat::with_block_size(
+                    self.min_term_block_size,
+                    self.max_term_block_size,
+                ))
+            } else {
+                postings_format_for_name(&format_name)?
+            };
 
-        let old_suffix = mem::replace(&mut self.write_state.segment_suffix, segment_suffix);
+            let old_suffix = mem::replace(&mut self.write_state.segment_suffix, segment_suffix);
 
-        let mut consumer = format.fields_consumer(&self.write_state)?;
-        consumer.write(fields)?;
+            let group = FieldsGroup {
+                fields,
+                names: group,
+            };
+            // dispatch on the concrete consumers, calling back into the enum with the
+            // wrapped fields would recurse endlessly when monomorphizing.
+            match format.fields_consumer(&self.write_state)? {
+                FieldsConsumerEnum::Lucene50(mut w) => w.write(&group, norms)?,
+                FieldsConsumerEnum::Completion(mut w) => w.write(&group, norms)?,
+                FieldsConsumerEnum::Memory(mut w) => w.write(&group, norms)?,
+                FieldsConsumerEnum::PerField(_) => {
+                    bail!(IllegalState("nested per field postings format".into()));
+                }
+            }
 
-        self.write_state.segment_suffix = old_suffix;
+            self.write_state.segment_suffix = old_suffix;
+        }
 
         Ok(())
     }
 }
+
+/// A view of the given `Fields` that only exposes the fields written by one format.
+struct FieldsGroup<'a, T: Fields> {
+    fields: &'a T,
+    names: BTreeSet<String>,
+}
+
+impl<'a, T: Fields> Fields for FieldsGroup<'a, T> {
+    type Terms = T::Terms;
+    fn fields(&self) -> Vec<String> {
+        self.names.iter().cloned().collect()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<Self::Terms>> {
+        if self.names.contains(field) {
+            self.fields.terms(field)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.names.len()
+    }
+
+    fn terms_freq(&self, field: &str) -> usize {
+        self.fields.terms_freq(field)
+    }
+}
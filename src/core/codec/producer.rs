@@ -17,20 +17,35 @@ use core::index::SortedDocValues;
 use core::index::SortedNumericDocValues;
 use core::index::SortedSetDocValues;
 use core::index::{FieldInfo, Fields};
-use core::util::BitsRef;
+use core::util::{Accountable, BitsRef, RamUsage};
 use error::Result;
 
 use core::codec::blocktree::BlockTreeTermsReader;
 use core::codec::per_field::PerFieldFieldsReader;
-use core::codec::FieldReaderRef;
+use core::codec::{FieldReaderRef, MemoryFieldsProducer, MemoryTermsDict};
+use core::suggest::document::{CompletionFieldsProducer, NRTSuggester};
 use std::sync::Arc;
 
-pub trait FieldsProducer: Fields {
+/// The terms dictionary and postings of a segment, its heap usage is
+/// reported through `Accountable`, e.g. the terms index FSTs.
+pub trait FieldsProducer: Fields + Accountable {
     /// Checks consistency of this reader.
     /// Note that this may be costly in terms of I/O, e.g.
     /// may involve computing a checksum value against large data files.
     fn check_integrity(&self) -> Result<()>;
 
+    /// Returns the in memory suggester of `field` if it's indexed with a
+    /// completion postings format.
+    fn suggester(&self, _field: &str) -> Option<Arc<NRTSuggester>> {
+        None
+    }
+
+    /// Returns the in memory terms dictionary of `field` if it's indexed with
+    /// a memory postings format.
+    fn memory_terms_dict(&self, _field: &str) -> Option<Arc<MemoryTermsDict>> {
+        None
+    }
+
     // Returns an instance optimized for merging.
     // fn get_merge_instance(&self) -> Result<FieldsProducerRef>;
 }
@@ -42,6 +57,14 @@ impl<T: FieldsProducer> FieldsProducer for Arc<T> {
     fn check_integrity(&self) -> Result<()> {
         (**self).check_integrity()
     }
+
+    fn suggester(&self, field: &str) -> Option<Arc<NRTSuggester>> {
+        (**self).suggester(field)
+    }
+
+    fn memory_terms_dict(&self, field: &str) -> Option<Arc<MemoryTermsDict>> {
+        (**self).memory_terms_dict(field)
+    }
 }
 
 impl<T: FieldsProducer> Fields for Arc<T> {
@@ -65,12 +88,50 @@ impl<T: FieldsProducer> Fields for Arc<T> {
 
 pub enum FieldsProducerEnum {
     Lucene50(BlockTreeTermsReader),
+    Completion(CompletionFieldsProducer),
+    Memory(MemoryFieldsProducer),
 }
 
 impl FieldsProducer for FieldsProducerEnum {
     fn check_integrity(&self) -> Result<()> {
         match self {
             FieldsProducerEnum::Lucene50(f) => f.check_integrity(),
+            FieldsProducerEnum::Completion(f) => f.check_integrity(),
+            FieldsProducerEnum::Memory(f) => f.check_integrity(),
+        }
+    }
+
+    fn suggester(&self, field: &str) -> Option<Arc<NRTSuggester>> {
+        match self {
+            FieldsProducerEnum::Lucene50(f) => f.suggester(field),
+            FieldsProducerEnum::Completion(f) => f.suggester(field),
+            FieldsProducerEnum::Memory(f) => f.suggester(field),
+        }
+    }
+
+    fn memory_terms_dict(&self, field: &str) -> Option<Arc<MemoryTermsDict>> {
+        match self {
+            FieldsProducerEnum::Lucene50(f) => f.memory_terms_dict(field),
+            FieldsProducerEnum::Completion(f) => f.memory_terms_dict(field),
+            FieldsProducerEnum::Memory(f) => f.memory_terms_dict(field),
+        }
+    }
+}
+
+impl Accountable for FieldsProducerEnum {
+    fn ram_bytes_used(&self) -> usize {
+        match self {
+            FieldsProducerEnum::Lucene50(f) => f.ram_bytes_used(),
+            FieldsProducerEnum::Completion(f) => f.ram_bytes_used(),
+            FieldsProducerEnum::Memory(f) => f.ram_bytes_used(),
+        }
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        match self {
+            FieldsProducerEnum::Lucene50(f) => f.child_resources(),
+            FieldsProducerEnum::Completion(f) => f.child_resources(),
+            FieldsProducerEnum::Memory(f) => f.child_resources(),
         }
     }
 }
@@ -80,29 +141,39 @@ impl Fields for FieldsProducerEnum {
     fn fields(&self) -> Vec<String> {
         match self {
             FieldsProducerEnum::Lucene50(f) => f.fields(),
+            FieldsProducerEnum::Completion(f) => f.fields(),
+            FieldsProducerEnum::Memory(f) => f.fields(),
         }
     }
 
     fn terms(&self, field: &str) -> Result<Option<Self::Terms>> {
         match self {
             FieldsProducerEnum::Lucene50(f) => f.terms(field),
+            FieldsProducerEnum::Completion(f) => f.terms(field),
+            FieldsProducerEnum::Memory(f) => f.terms(field),
         }
     }
 
     fn size(&self) -> usize {
         match self {
             FieldsProducerEnum::Lucene50(f) => f.size(),
+            FieldsProducerEnum::Completion(f) => f.size(),
+            FieldsProducerEnum::Memory(f) => f.size(),
         }
     }
 
     fn terms_freq(&self, field: &str) -> usize {
         match self {
             FieldsProducerEnum::Lucene50(f) => f.terms_freq(field),
+            FieldsProducerEnum::Completion(f) => f.terms_freq(field),
+            FieldsProducerEnum::Memory(f) => f.terms_freq(field),
         }
     }
 }
 
-pub trait DocValuesProducer: Send + Sync {
+/// The doc values of a segment, its heap usage is reported through
+/// `Accountable`, e.g. the lazily loaded addresses of binary values.
+pub trait DocValuesProducer: Send + Sync + Accountable {
     fn get_numeric(&self, field_info: &FieldInfo) -> Result<Arc<dyn NumericDocValues>>;
     fn get_binary(&self, field_info: &FieldInfo) -> Result<Arc<dyn BinaryDocValues>>;
     fn get_sorted(&self, field: &FieldInfo) -> Result<Arc<dyn SortedDocValues>>;
@@ -118,6 +189,12 @@ pub trait DocValuesProducer: Send + Sync {
     fn check_integrity(&self) -> Result<()>;
 
     fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>>;
+
+    /// Drops what this producer keeps in memory for `field`, e.g. its
+    /// addresses, which is loaded again on the next access to the field.
+    fn evict(&self, _field: &FieldInfo) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub type DocValuesProducerRef = Arc<dyn DocValuesProducer>;
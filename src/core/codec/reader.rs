@@ -14,6 +14,7 @@
 use core::index::field_info::Fields;
 use core::index::PointValues;
 use core::index::StoredFieldVisitor;
+use core::index::VectorValues;
 use core::util::DocId;
 
 use error::Result;
@@ -84,6 +85,18 @@ pub trait PointsReader: PointValues {
     fn as_any(&self) -> &Any;
 }
 
+/// Reads the vectors of a segment.
+pub trait VectorReader: Send + Sync {
+    fn check_integrity(&self) -> Result<()>;
+
+    /// Returns the vectors of the field, or None if the field has no vector.
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>>;
+
+    fn as_any(&self) -> &Any;
+}
+
+pub type VectorReaderRef = Arc<dyn VectorReader>;
+
 pub trait MutablePointsReader: PointsReader {
     fn value(&self, i: i32, packed_value: &mut Vec<u8>);
     fn byte_at(&self, i: i32, k: i32) -> u8;
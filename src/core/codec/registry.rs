@@ -0,0 +1,440 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use core::codec::{
+    builtin_codec, builtin_doc_values_format, builtin_postings_format, CodecEnum,
+    DocValuesFormatEnum, PostingsFormatEnum,
+};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+/// Creates the codec registered under a name.
+pub type CodecFactory = Arc<dyn Fn() -> CodecEnum + Send + Sync>;
+/// Creates the postings format registered under a name.
+pub type PostingsFormatFactory = Arc<dyn Fn() -> PostingsFormatEnum + Send + Sync>;
+/// Creates the doc values format registered under a name.
+pub type DocValuesFormatFactory = Arc<dyn Fn() -> DocValuesFormatEnum + Send + Sync>;
+
+/// Maps the names recorded in the index to the factories of the formats that
+/// read and write them, like the service loader of Lucene.
+struct Registry<T: ?Sized> {
+    kind: &'static str,
+    // the name is kept along with the factory to hand out the `'static` one
+    factories: RwLock<HashMap<&'static str, (&'static str, Arc<T>)>>,
+}
+
+impl<T: ?Sized> Registry<T> {
+    fn new(kind: &'static str) -> Registry<T> {
+        Registry {
+            kind,
+            factories: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn register(&self, name: &'static str, factory: Arc<T>, builtin: bool) -> Result<()> {
+        if builtin {
+            bail!(IllegalArgument(format!(
+                "{} {} clashes with a built-in {}",
+                self.kind, name, self.kind
+            )));
+        }
+        let mut factories = self.factories.write().unwrap();
+        if let Some((_, registered)) = factories.get(name) {
+            if Arc::ptr_eq(registered, &factory) {
+                return Ok(());
+            }
+            bail!(IllegalArgument(format!(
+                "{} {} is already registered",
+                self.kind, name
+            )));
+        }
+        factories.insert(name, (name, factory));
+        Ok(())
+    }
+
+    fn get(&self, name: &str) -> Option<(&'static str, Arc<T>)> {
+        self.factories
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|(name, factory)| (*name, Arc::clone(factory)))
+    }
+}
+
+lazy_static! {
+    static ref CODECS: Registry<dyn Fn() -> CodecEnum + Send + Sync> = Registry::new("codec");
+    static ref POSTINGS_FORMATS: Registry<dyn Fn() -> PostingsFormatEnum + Send + Sync> =
+        Registry::new("postings format");
+    static ref DOC_VALUES_FORMATS: Registry<dyn Fn() -> DocValuesFormatEnum + Send + Sync> =
+        Registry::new("doc values format");
+}
+
+/// Register a codec under `name`, which is recorded in the segments written
+/// with the codec returned by `codec_for_name(name)`.
+///
+/// Segments only record the name, so the codec must be registered again
+/// before they are read. Registering the same factory twice is a no-op,
+/// while registering another factory under a name that is already taken
+/// fails.
+pub fn register_codec(name: &'static str, factory: CodecFactory) -> Result<()> {
+    CODECS.register(name, factory, builtin_codec(name).is_some())
+}
+
+/// Register a postings format under `name`, fields whose
+/// `FieldType::postings_format` is `name` are written and read with it.
+pub fn register_postings_format(name: &'static str, factory: PostingsFormatFactory) -> Result<()> {
+    POSTINGS_FORMATS.register(name, factory, builtin_postings_format(name).is_some())
+}
+
+/// Register a doc values format under `name`, fields whose
+/// `FieldType::doc_values_format` is `name` are written and read with it.
+pub fn register_doc_values_format(
+    name: &'static str,
+    factory: DocValuesFormatFactory,
+) -> Result<()> {
+    DOC_VALUES_FORMATS.register(name, factory, builtin_doc_values_format(name).is_some())
+}
+
+pub(crate) fn registered_codec(name: &str) -> Option<CodecEnum> {
+    CODECS
+        .get(name)
+        .map(|(name, factory)| CodecEnum::Named(name, Box::new(factory())))
+}
+
+pub(crate) fn registered_postings_format(name: &str) -> Option<PostingsFormatEnum> {
+    POSTINGS_FORMATS.get(name).map(|(_, factory)| factory())
+}
+
+pub(crate) fn registered_doc_values_format(name: &str) -> Option<DocValuesFormatEnum> {
+    DOC_VALUES_FORMATS.get(name).map(|(_, factory)| factory())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::{
+        codec_for_name, doc_values_format_for_name, postings_format_for_name, Codec,
+        DocValuesFormat, Lucene50PostingsFormat, Lucene54DocValuesFormat, Lucene62Codec,
+        PostingsFormat,
+    };
+    use core::codec::{
+        codec_util, CustomDocValuesConsumer, CustomDocValuesFormat, DocValuesProducer, SegmentFiles,
+    };
+    use core::doc::{Field, NUMERIC_DOC_VALUES_FIELD_TYPE};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{
+        segment_file_name, BinaryDocValues, FieldInfo, Fieldable, IndexReader, IndexWriter,
+        NumericDocValues, NumericDocValuesContext, SortedDocValues, SortedNumericDocValues,
+        SortedSetDocValues,
+    };
+    use core::store::{FSDirectory, FilesDirectory, FilesOutputs, IOContext, NativeFSLockFactory};
+    use core::util::numeric::Numeric;
+    use core::util::{
+        Accountable, BitsRef, BytesRef, DocId, MatchAllBits, ReusableIterator, VariantValue,
+    };
+    use std::convert::TryFrom;
+    use std::fs;
+
+    #[test]
+    fn test_register_codec() {
+        assert!(codec_for_name("RegistryTestCodec").is_err());
+
+        let factory: CodecFactory = Arc::new(|| CodecEnum::Lucene62(Lucene62Codec::default()));
+        register_codec("RegistryTestCodec", Arc::clone(&factory)).unwrap();
+        register_codec("RegistryTestCodec", factory).unwrap();
+        assert!(register_codec(
+            "RegistryTestCodec",
+            Arc::new(|| CodecEnum::Lucene62(Lucene62Codec::default()))
+        )
+        .is_err());
+        assert!(register_codec(
+            "Lucene62",
+            Arc::new(|| CodecEnum::Lucene62(Lucene62Codec::default()))
+        )
+        .is_err());
+
+        let codec = codec_for_name("RegistryTestCodec").unwrap();
+        assert_eq!(codec.name(), "RegistryTestCodec");
+        assert_eq!(codec.postings_format().name(), "PerField40");
+        let codec = CodecEnum::try_from("RegistryTestCodec".to_string()).unwrap();
+        assert_eq!(codec.name(), "RegistryTestCodec");
+    }
+
+    #[test]
+    fn test_register_formats() {
+        assert!(postings_format_for_name("RegistryTestPostings").is_err());
+        register_postings_format(
+            "RegistryTestPostings",
+            Arc::new(|| {
+                PostingsFormatEnum::Lucene50(Lucene50PostingsFormat::with_block_size(10, 20))
+            }),
+        )
+        .unwrap();
+        let format = postings_format_for_name("RegistryTestPostings").unwrap();
+        assert_eq!(format.name(), "Lucene50");
+        assert!(register_postings_format(
+            "Lucene50",
+            Arc::new(|| PostingsFormatEnum::Lucene50(Lucene50PostingsFormat::default()))
+        )
+        .is_err());
+
+        assert!(doc_values_format_for_name("RegistryTestDocValues").is_err());
+        register_doc_values_format(
+            "RegistryTestDocValues",
+            Arc::new(|| DocValuesFormatEnum::Lucene54(Lucene54DocValuesFormat::default())),
+        )
+        .unwrap();
+        let format = doc_values_format_for_name("RegistryTestDocValues").unwrap();
+        assert_eq!(format.name(), "Lucene54");
+    }
+
+    const TEST_CODEC: &str = "RegistryTestCustom";
+    const TEST_EXTENSION: &str = "tdv";
+
+    /// Writes the numeric fields as a list of zlongs, one per document.
+    struct TestDocValuesFormat;
+
+    impl CustomDocValuesFormat for TestDocValuesFormat {
+        fn name(&self) -> &str {
+            "RegistryTestCustom"
+        }
+
+        fn fields_producer(
+            &self,
+            directory: &dyn FilesDirectory,
+            state: &SegmentFiles<'_>,
+        ) -> Result<Box<dyn DocValuesProducer>> {
+            let name = segment_file_name(state.segment_name, state.segment_suffix, TEST_EXTENSION);
+            let mut input = directory.open_file(&name, &IOContext::READ)?;
+            codec_util::check_index_header(
+                input.as_mut(),
+                TEST_CODEC,
+                0,
+                0,
+                state.segment_id,
+                state.segment_suffix,
+            )?;
+            let mut fields = HashMap::new();
+            loop {
+                let number = input.read_vint()?;
+                if number == -1 {
+                    break;
+                }
+                let count = input.read_vint()? as usize;
+                let mut values = Vec::with_capacity(count);
+                for _ in 0..count {
+                    values.push(input.read_zlong()?);
+                }
+                fields.insert(number as u32, Arc::new(TestNumericDocValues(values)));
+            }
+            Ok(Box::new(TestDocValuesProducer {
+                fields,
+                max_doc: state.max_doc,
+            }))
+        }
+
+        fn fields_consumer(
+            &self,
+            outputs: &mut dyn FilesOutputs,
+            state: &SegmentFiles<'_>,
+        ) -> Result<Box<dyn CustomDocValuesConsumer>> {
+            let name = segment_file_name(state.segment_name, state.segment_suffix, TEST_EXTENSION);
+            let data = outputs.create_file(&name)?;
+            codec_util::write_index_header(
+                outputs.file(data),
+                TEST_CODEC,
+                0,
+                state.segment_id,
+                state.segment_suffix,
+            )?;
+            Ok(Box::new(TestDocValuesConsumer { data }))
+        }
+    }
+
+    struct TestDocValuesConsumer {
+        data: usize,
+    }
+
+    impl CustomDocValuesConsumer for TestDocValuesConsumer {
+        fn add_numeric_field(
+            &mut self,
+            outputs: &mut dyn FilesOutputs,
+            field_info: &FieldInfo,
+            values: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+        ) -> Result<()> {
+            let mut longs = vec![];
+            for value in values {
+                let value = value?;
+                longs.push(if value.is_null() {
+                    0
+                } else {
+                    value.long_value()
+                });
+            }
+            let data = outputs.file(self.data);
+            data.write_vint(field_info.number as i32)?;
+            data.write_vint(longs.len() as i32)?;
+            for long in longs {
+                data.write_zlong(long)?;
+            }
+            Ok(())
+        }
+
+        fn add_binary_field(
+            &mut self,
+            _outputs: &mut dyn FilesOutputs,
+            _field_info: &FieldInfo,
+            _values: &mut dyn ReusableIterator<Item = Result<BytesRef>>,
+        ) -> Result<()> {
+            unreachable!()
+        }
+
+        fn add_sorted_field(
+            &mut self,
+            _outputs: &mut dyn FilesOutputs,
+            _field_info: &FieldInfo,
+            _values: &mut dyn ReusableIterator<Item = Result<BytesRef>>,
+            _doc_to_ord: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+        ) -> Result<()> {
+            unreachable!()
+        }
+
+        fn add_sorted_numeric_field(
+            &mut self,
+            _outputs: &mut dyn FilesOutputs,
+            _field_info: &FieldInfo,
+            _values: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+            _doc_to_value_count: &mut dyn ReusableIterator<Item = Result<u32>>,
+        ) -> Result<()> {
+            unreachable!()
+        }
+
+        fn add_sorted_set_field(
+            &mut self,
+            _outputs: &mut dyn FilesOutputs,
+            _field_info: &FieldInfo,
+            _values: &mut dyn ReusableIterator<Item = Result<BytesRef>>,
+            _doc_to_ord_count: &mut dyn ReusableIterator<Item = Result<u32>>,
+            _ords: &mut dyn ReusableIterator<Item = Result<Numeric>>,
+        ) -> Result<()> {
+            unreachable!()
+        }
+
+        fn finish(&mut self, outputs: &mut dyn FilesOutputs) -> Result<()> {
+            let data = outputs.file(self.data);
+            data.write_vint(-1)?;
+            codec_util::write_footer(data)
+        }
+    }
+
+    struct TestNumericDocValues(Vec<i64>);
+
+    impl NumericDocValues for TestNumericDocValues {
+        fn get_with_ctx(
+            &self,
+            ctx: NumericDocValuesContext,
+            doc_id: DocId,
+        ) -> Result<(i64, NumericDocValuesContext)> {
+            Ok((self.0[doc_id as usize], ctx))
+        }
+    }
+
+    #[derive(Clone)]
+    struct TestDocValuesProducer {
+        fields: HashMap<u32, Arc<TestNumericDocValues>>,
+        max_doc: i32,
+    }
+
+    impl DocValuesProducer for TestDocValuesProducer {
+        fn get_numeric(&self, field_info: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
+            Ok(Arc::clone(&self.fields[&field_info.number]) as Arc<dyn NumericDocValues>)
+        }
+
+        fn get_binary(&self, _field: &FieldInfo) -> Result<Arc<dyn BinaryDocValues>> {
+            unreachable!()
+        }
+
+        fn get_sorted(&self, _field: &FieldInfo) -> Result<Arc<dyn SortedDocValues>> {
+            unreachable!()
+        }
+
+        fn get_sorted_numeric(
+            &self,
+            _field: &FieldInfo,
+        ) -> Result<Arc<dyn SortedNumericDocValues>> {
+            unreachable!()
+        }
+
+        fn get_sorted_set(&self, _field: &FieldInfo) -> Result<Arc<dyn SortedSetDocValues>> {
+            unreachable!()
+        }
+
+        fn get_docs_with_field(&self, _field: &FieldInfo) -> Result<BitsRef> {
+            Ok(Arc::new(MatchAllBits::new(self.max_doc as usize)))
+        }
+
+        fn check_integrity(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>> {
+            Ok(Box::new(self.clone()))
+        }
+    }
+
+    impl Accountable for TestDocValuesProducer {
+        fn ram_bytes_used(&self) -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_register_custom_doc_values_format() {
+        register_doc_values_format(
+            "RegistryTestCustom",
+            Arc::new(|| DocValuesFormatEnum::Custom(Box::new(TestDocValuesFormat))),
+        )
+        .unwrap();
+
+        let path = ::std::env::temp_dir().join("rucene_registry_custom_doc_values");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+
+        let mut field_type = NUMERIC_DOC_VALUES_FIELD_TYPE;
+        field_type.doc_values_format = Some("RegistryTestCustom");
+        for i in 0..10i64 {
+            let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+                "weight".into(),
+                field_type.clone(),
+                Some(VariantValue::Long(i * 3)),
+                None,
+            ))];
+            writer.add_document(doc).unwrap();
+        }
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let values = leaves[0].reader.get_numeric_doc_values("weight").unwrap();
+        for doc in 0..10 {
+            assert_eq!(values.get(doc).unwrap(), i64::from(doc) * 3);
+        }
+
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::format::{DocValuesConsumerEnum, DocValuesFormat};
+use core::codec::lucene54::{Lucene54DocValuesConsumer, Lucene54DocValuesProducer};
+use core::codec::{Codec, DocValuesProducer};
+use core::index::{SegmentReadState, SegmentWriteState};
+use core::store::Directory;
+use error::Result;
+
+/// `FieldInfo` attribute name used to store the `CompressionMode` of the
+/// binary doc values of a field, see `FieldType::binary_doc_values_compression`.
+pub const BINARY_COMPRESSION_KEY: &str = "Rucene54DocValuesFormat.binaryCompression";
+/// Value of `BINARY_COMPRESSION_KEY` for the fields whose binary doc values
+/// are compressed only if a sample of them compresses well, see
+/// `FieldType::binary_doc_values_auto_compression`.
+pub const BINARY_AUTO_COMPRESSION: &str = "AUTO";
+
+/// Doc values format of rucene extending `Lucene54DocValuesFormat`, which
+/// stays readable by Lucene, with entry formats Lucene doesn't have:
+///
+/// - binary values compressed by blocks, per field with a `CompressionMode`
+/// or automatically when a sample of them compresses well;
+/// - sparse numeric and binary values, the docs that have a value being
+/// recorded in an `IndexedDISI` followed by the values of these docs only.
+///
+/// The other entries are written the way `Lucene54DocValuesFormat` writes
+/// them, with the same reader and writer.
+#[derive(Copy, Clone, Default)]
+pub struct Rucene54DocValuesFormat;
+
+impl Rucene54DocValuesFormat {
+    pub(crate) const NAME: &'static str = "Rucene54";
+    const DATA_CODEC: &'static str = "Rucene54DocValuesData";
+    const DATA_EXTENSION: &'static str = "dvd";
+    const META_CODEC: &'static str = "Rucene54DocValuesMetadata";
+    const META_EXTENSION: &'static str = "dvm";
+    pub(crate) const VERSION_START: i32 = 0;
+    pub(crate) const VERSION_CURRENT: i32 = Self::VERSION_START;
+
+    // Compressed with the docs that have a value in an IndexedDISI, followed
+    // by the values of these docs only
+    pub(crate) const DISI_COMPRESSED: i32 = 6;
+
+    // Binary compressed by blocks of values, with the lengths of the values
+    // of a block before its compressed bytes
+    pub(crate) const BINARY_BLOCK_COMPRESSED: i32 = 3;
+    // Binary with the docs that have a value in an IndexedDISI, followed by
+    // the values of these docs only, written as another binary entry
+    pub(crate) const BINARY_SPARSE: i32 = 4;
+
+    // fields of at least this many docs that have a value for at most half of
+    // them only store the values of the docs that have one
+    pub(crate) const SPARSE_MIN_DOCS: i64 = 1024;
+
+    // binary values are compressed by blocks of 32 values
+    pub(crate) const BINARY_BLOCK_SHIFT: i32 = 5;
+    // binary values of the fields with auto compression are compressed by
+    // blocks with LZ4 if the values of a sample of up to 64KB are at least 32
+    // bytes long on average and its blocks compress to at most 3/4 of its size
+    pub(crate) const BINARY_AUTO_COMPRESSION_MIN_LENGTH: i64 = 32;
+    pub(crate) const BINARY_AUTO_COMPRESSION_SAMPLE_SIZE: usize = 1 << 16;
+}
+
+impl DocValuesFormat for Rucene54DocValuesFormat {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn fields_producer<'a, D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'a, D, DW, C>,
+    ) -> Result<Box<dyn DocValuesProducer>> {
+        let boxed = Lucene54DocValuesProducer::new(
+            state,
+            Self::DATA_CODEC,
+            Self::DATA_EXTENSION,
+            Self::META_CODEC,
+            Self::META_EXTENSION,
+            true,
+        )?;
+        Ok(Box::new(boxed))
+    }
+
+    fn fields_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<DocValuesConsumerEnum<D, DW, C>> {
+        Ok(DocValuesConsumerEnum::Lucene54(
+            Lucene54DocValuesConsumer::new(
+                state,
+                Self::DATA_CODEC,
+                Self::DATA_EXTENSION,
+                Self::META_CODEC,
+                Self::META_EXTENSION,
+                true,
+            )?,
+        ))
+    }
+}
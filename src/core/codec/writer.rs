@@ -23,8 +23,8 @@ use core::index::doc_id_merger::{DocIdMerger, DocIdMergerSub, DocIdMergerSubBase
 use core::index::{DocMap, LiveDocsDocMap, MergeState};
 use core::index::{FieldInfo, FieldInfos, Fieldable, Fields, SegmentWriteState, Terms};
 use core::index::{IntersectVisitor, PointValues, Relation};
-use core::index::{MergePointValuesEnum, TempMutablePointsReader, TermIterator};
-use core::index::{Status, StoredFieldVisitor};
+use core::index::{MergePointValuesEnum, NumericDocValues, TempMutablePointsReader, TermIterator};
+use core::index::{Status, StoredFieldVisitor, VectorSimilarityFunction, VectorValues};
 use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
 use core::search::{DocIterator, NO_MORE_DOCS};
 use core::store::{DataInput, DataOutput, Directory, IndexOutput};
@@ -71,6 +71,17 @@ impl<P: PointsReader, MP: MutablePointsReader> PointValues for PointsReaderEnum<
         }
     }
 
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        visitor: &mut impl IntersectVisitor,
+    ) -> Result<i64> {
+        match self {
+            PointsReaderEnum::Simple(s) => s.estimate_point_count(field_name, visitor),
+            PointsReaderEnum::Mutable(m) => m.estimate_point_count(field_name, visitor),
+        }
+    }
+
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
         match self {
             PointsReaderEnum::Simple(s) => s.min_packed_value(field_name),
@@ -194,6 +205,110 @@ pub fn merge_point_values<D: Directory, C: Codec, P: PointsWriter>(
     writer.finish()
 }
 
+/// Writes the vectors of a segment.
+pub trait VectorWriter {
+    /// Writes all the vectors of a field.
+    fn write_field(&mut self, field_info: &FieldInfo, values: &dyn VectorValues) -> Result<()>;
+
+    /// Merges the vectors of the segments being merged, dropping the deleted
+    /// documents, and finishes the writer.
+    fn merge<D: Directory, C: Codec>(&mut self, merge_state: &MergeState<D, C>) -> Result<()> {
+        for field_info in merge_state
+            .merge_field_infos
+            .as_ref()
+            .unwrap()
+            .by_number
+            .values()
+        {
+            if field_info.has_vector_values() {
+                let values = MergedVectorValues::new(merge_state, field_info)?;
+                self.write_field(field_info, &values)?;
+            }
+        }
+        self.finish()
+    }
+
+    /// Called once after all the fields have been written.
+    fn finish(&mut self) -> Result<()>;
+}
+
+/// Vectors of a field of the merged segment, ordered by their new doc ids.
+struct MergedVectorValues {
+    dimension: usize,
+    similarity_function: VectorSimilarityFunction,
+    subs: Vec<Box<dyn VectorValues>>,
+    // (new doc id, index of the sub, ord in the sub), sorted by doc id
+    docs: Vec<(DocId, usize, usize)>,
+}
+
+impl MergedVectorValues {
+    fn new<D: Directory, C: Codec>(
+        merge_state: &MergeState<D, C>,
+        field_info: &FieldInfo,
+    ) -> Result<MergedVectorValues> {
+        let mut subs = Vec::with_capacity(merge_state.vector_readers.len());
+        let mut docs = vec![];
+        for (i, reader) in merge_state.vector_readers.iter().enumerate() {
+            let values = match reader {
+                Some(reader) => reader.vector_values(&field_info.name)?,
+                None => None,
+            };
+            if let Some(values) = values {
+                if values.dimension() != field_info.vector_dimension as usize {
+                    bail!(IllegalState(format!(
+                        "field '{}': segment has vectors of dimension {}, expected {}",
+                        field_info.name,
+                        values.dimension(),
+                        field_info.vector_dimension
+                    )));
+                }
+                let doc_map = &merge_state.doc_maps[i];
+                for ord in 0..values.size() {
+                    let new_doc = doc_map.get(values.ord_to_doc(ord))?;
+                    if new_doc != -1 {
+                        docs.push((new_doc, subs.len(), ord));
+                    }
+                }
+                subs.push(values);
+            }
+        }
+        docs.sort_unstable();
+        Ok(MergedVectorValues {
+            dimension: field_info.vector_dimension as usize,
+            similarity_function: field_info.vector_similarity_function,
+            subs,
+            docs,
+        })
+    }
+}
+
+impl VectorValues for MergedVectorValues {
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn size(&self) -> usize {
+        self.docs.len()
+    }
+
+    fn similarity_function(&self) -> VectorSimilarityFunction {
+        self.similarity_function
+    }
+
+    fn ord_to_doc(&self, ord: usize) -> DocId {
+        self.docs[ord].0
+    }
+
+    fn doc_to_ord(&self, doc: DocId) -> Option<usize> {
+        self.docs.binary_search_by_key(&doc, |d| d.0).ok()
+    }
+
+    fn vector_value(&self, ord: usize, vector: &mut [f32]) -> Result<()> {
+        let (_, sub, sub_ord) = self.docs[ord];
+        self.subs[sub].vector_value(sub_ord, vector)
+    }
+}
+
 pub struct MergePointsReader<C: Codec> {
     field_info: FieldInfo,
     points_readers: Vec<Option<MergePointValuesEnum<Arc<CodecPointsReader<C>>>>>,
@@ -338,11 +453,15 @@ pub trait PostingsWriterBase {
     /// FixedBitSet} for every docID written.  If no docs
     /// were written, this method should return null, and the
     /// terms dict will skip the term.
+    ///
+    /// `norms` are the norms of the field if it has any, they are used to
+    /// record the competitive impacts of the postings.
     fn write_term(
         &mut self,
         term: &[u8],
         terms: &mut impl TermIterator,
         docs_seen: &mut FixedBitSet,
+        norms: Option<&dyn NumericDocValues>,
     ) -> Result<Option<BlockTermState>>;
 
     /// Encode metadata as long[] and byte[]. {@code absolute} controls whether
@@ -14,6 +14,7 @@
 use std::ops::Deref;
 
 use core::analysis::TokenStream;
+use core::codec::CompressionMode;
 use core::doc::{BinaryTokenStream, Field, FieldType, BINARY_DOC_VALUES_FIELD_TYPE};
 use core::index::Fieldable;
 use core::util::{BytesRef, Numeric, VariantValue};
@@ -35,6 +36,41 @@ impl BinaryDocValuesField {
             ),
         }
     }
+    /// Creates a field whose values are compressed by blocks with `mode`,
+    /// which is recorded per field so all the values of the field in a
+    /// segment are compressed the same way.
+    pub fn with_compression(
+        name: &str,
+        value: &[u8],
+        mode: CompressionMode,
+    ) -> BinaryDocValuesField {
+        let mut field_type = BINARY_DOC_VALUES_FIELD_TYPE;
+        field_type.set_binary_doc_values_compression(mode);
+        BinaryDocValuesField {
+            field: Field::new(
+                String::from(name),
+                field_type,
+                Some(VariantValue::from(value)),
+                None,
+            ),
+        }
+    }
+
+    /// Creates a field whose values are compressed by blocks with LZ4 when
+    /// they are large and compress well, which is checked on a sample of the
+    /// values of the field each time a segment is written.
+    pub fn with_auto_compression(name: &str, value: &[u8]) -> BinaryDocValuesField {
+        let mut field_type = BINARY_DOC_VALUES_FIELD_TYPE;
+        field_type.set_binary_doc_values_auto_compression();
+        BinaryDocValuesField {
+            field: Field::new(
+                String::from(name),
+                field_type,
+                Some(VariantValue::from(value)),
+                None,
+            ),
+        }
+    }
 }
 
 impl Fieldable for BinaryDocValuesField {
@@ -0,0 +1,401 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::Analyzer;
+use core::doc::{Document, Field, FieldType, NumericDocValuesField, SortedSetDocValuesField};
+use core::index::{DocValuesType, Fieldable, IndexOptions, IndexReader};
+use core::util::{DocId, VariantValue};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::sync::Arc;
+
+type Getter<T> = Box<dyn Fn(&T) -> VariantValue + Send + Sync>;
+type Setter<T> = Box<dyn Fn(&mut T, VariantValue) -> Result<()> + Send + Sync>;
+
+enum MappedFieldKind {
+    Text(Arc<dyn Analyzer>),
+    Keyword,
+    Long,
+    Double,
+    Binary,
+}
+
+struct MappedField<T> {
+    name: String,
+    kind: MappedFieldKind,
+    stored: bool,
+    doc_values: bool,
+    get: Getter<T>,
+    set: Setter<T>,
+}
+
+impl<T> MappedField<T> {
+    fn field_type(&self) -> FieldType {
+        let mut field_type = FieldType::default();
+        field_type.stored = self.stored;
+        match self.kind {
+            MappedFieldKind::Text(_) => {
+                field_type.tokenized = true;
+                field_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+            }
+            MappedFieldKind::Keyword => {
+                field_type.tokenized = false;
+                field_type.omit_norms = true;
+                field_type.index_options = IndexOptions::Docs;
+            }
+            MappedFieldKind::Long | MappedFieldKind::Double | MappedFieldKind::Binary => {
+                field_type.tokenized = false;
+                field_type.index_options = IndexOptions::Null;
+            }
+        }
+        field_type.doc_values_type = DocValuesType::Null;
+        field_type
+    }
+
+    fn fields(&self, value: &T, fields: &mut Vec<Box<dyn Fieldable>>) -> Result<()> {
+        let data = (self.get)(value);
+        if let MappedFieldKind::Text(_) | MappedFieldKind::Keyword = self.kind {
+            if self.doc_values {
+                let bytes = data.get_string().unwrap().as_bytes();
+                fields.push(Box::new(SortedSetDocValuesField::new(&self.name, bytes)));
+            }
+        } else if self.doc_values {
+            match data {
+                VariantValue::Long(v) => {
+                    fields.push(Box::new(NumericDocValuesField::new(&self.name, v)))
+                }
+                // the raw bits of the doubles, as read by the double sort fields
+                VariantValue::Double(v) => fields.push(Box::new(NumericDocValuesField::new(
+                    &self.name,
+                    v.to_bits() as i64,
+                ))),
+                _ => {}
+            }
+        }
+
+        let field_type = self.field_type();
+        if !field_type.stored && field_type.index_options == IndexOptions::Null {
+            return Ok(());
+        }
+        let token_stream = match self.kind {
+            MappedFieldKind::Text(ref analyzer) => {
+                Some(analyzer.token_stream(&self.name, data.get_string().unwrap())?)
+            }
+            _ => None,
+        };
+        fields.push(Box::new(Field::new(
+            self.name.clone(),
+            field_type,
+            Some(data),
+            token_stream,
+        )));
+        Ok(())
+    }
+}
+
+/// Maps the properties of a struct to the fields of a document, so that
+/// values of the struct are indexed with `to_document` and read back from
+/// the stored fields with `load` or `from_document`, without handling the
+/// field names and `VariantValue`s by hand.
+///
+/// Each property is mapped by a getter and a setter:
+///
+/// - `text`: tokenized by an analyzer for full text search, stored.
+/// - `keyword`: indexed as a single term, stored.
+/// - `long` and `double`: stored, with numeric doc values for sorting and
+/// aggregations.
+/// - `binary`: stored only.
+///
+/// The defaults are changed by `stored` and `doc_values`, which apply to the
+/// property mapped last. Only the stored properties are read back, the other
+/// ones keep their `Default` value.
+///
+/// ```ignore
+/// let mapping = DocumentMapping::new()
+///     .keyword("id", |b: &Book| &b.id, |b, v| b.id = v)
+///     .text("title", analyzer, |b: &Book| &b.title, |b, v| b.title = v)
+///     .long("year", |b: &Book| b.year, |b, v| b.year = v)
+///     .doc_values(false);
+/// writer.add_document(mapping.to_document(&book)?)?;
+/// let book = mapping.load(&reader, doc_id)?;
+/// ```
+pub struct DocumentMapping<T> {
+    fields: Vec<MappedField<T>>,
+}
+
+impl<T> Default for DocumentMapping<T> {
+    fn default() -> Self {
+        DocumentMapping { fields: vec![] }
+    }
+}
+
+impl<T> DocumentMapping<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(
+        mut self,
+        name: &str,
+        kind: MappedFieldKind,
+        doc_values: bool,
+        get: Getter<T>,
+        set: Setter<T>,
+    ) -> Self {
+        self.fields.push(MappedField {
+            name: name.to_string(),
+            kind,
+            stored: true,
+            doc_values,
+            get,
+            set,
+        });
+        self
+    }
+
+    /// Maps a property to a full text field tokenized by `analyzer`.
+    pub fn text<G, S>(self, name: &str, analyzer: Arc<dyn Analyzer>, get: G, set: S) -> Self
+    where
+        G: Fn(&T) -> &str + Send + Sync + 'static,
+        S: Fn(&mut T, String) + Send + Sync + 'static,
+    {
+        self.add(
+            name,
+            MappedFieldKind::Text(analyzer),
+            false,
+            Box::new(move |v| VariantValue::VString(get(v).to_string())),
+            Box::new(move |v, data| match data {
+                VariantValue::VString(s) => {
+                    set(v, s);
+                    Ok(())
+                }
+                data => bail!(IllegalArgument(format!("expect a string, got {:?}", data))),
+            }),
+        )
+    }
+
+    /// Maps a property to a field indexed as a single term.
+    pub fn keyword<G, S>(self, name: &str, get: G, set: S) -> Self
+    where
+        G: Fn(&T) -> &str + Send + Sync + 'static,
+        S: Fn(&mut T, String) + Send + Sync + 'static,
+    {
+        self.add(
+            name,
+            MappedFieldKind::Keyword,
+            false,
+            Box::new(move |v| VariantValue::VString(get(v).to_string())),
+            Box::new(move |v, data| match data {
+                VariantValue::VString(s) => {
+                    set(v, s);
+                    Ok(())
+                }
+                data => bail!(IllegalArgument(format!("expect a string, got {:?}", data))),
+            }),
+        )
+    }
+
+    /// Maps a property to a long field.
+    pub fn long<G, S>(self, name: &str, get: G, set: S) -> Self
+    where
+        G: Fn(&T) -> i64 + Send + Sync + 'static,
+        S: Fn(&mut T, i64) + Send + Sync + 'static,
+    {
+        self.add(
+            name,
+            MappedFieldKind::Long,
+            true,
+            Box::new(move |v| VariantValue::Long(get(v))),
+            Box::new(move |v, data| match data {
+                VariantValue::Long(l) => {
+                    set(v, l);
+                    Ok(())
+                }
+                VariantValue::Int(i) => {
+                    set(v, i64::from(i));
+                    Ok(())
+                }
+                data => bail!(IllegalArgument(format!("expect a long, got {:?}", data))),
+            }),
+        )
+    }
+
+    /// Maps a property to a double field.
+    pub fn double<G, S>(self, name: &str, get: G, set: S) -> Self
+    where
+        G: Fn(&T) -> f64 + Send + Sync + 'static,
+        S: Fn(&mut T, f64) + Send + Sync + 'static,
+    {
+        self.add(
+            name,
+            MappedFieldKind::Double,
+            true,
+            Box::new(move |v| VariantValue::Double(get(v))),
+            Box::new(move |v, data| match data {
+                VariantValue::Double(d) => {
+                    set(v, d);
+                    Ok(())
+                }
+                VariantValue::Float(f) => {
+                    set(v, f64::from(f));
+                    Ok(())
+                }
+                data => bail!(IllegalArgument(format!("expect a double, got {:?}", data))),
+            }),
+        )
+    }
+
+    /// Maps a property to a stored binary field.
+    pub fn binary<G, S>(self, name: &str, get: G, set: S) -> Self
+    where
+        G: Fn(&T) -> &[u8] + Send + Sync + 'static,
+        S: Fn(&mut T, Vec<u8>) + Send + Sync + 'static,
+    {
+        self.add(
+            name,
+            MappedFieldKind::Binary,
+            false,
+            Box::new(move |v| VariantValue::Binary(get(v).to_vec())),
+            Box::new(move |v, data| match data {
+                VariantValue::Binary(b) => {
+                    set(v, b);
+                    Ok(())
+                }
+                data => bail!(IllegalArgument(format!("expect bytes, got {:?}", data))),
+            }),
+        )
+    }
+
+    /// Sets whether the property mapped last is stored.
+    pub fn stored(mut self, stored: bool) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.stored = stored;
+        }
+        self
+    }
+
+    /// Sets whether the property mapped last has doc values, sorted set doc
+    /// values for `text` and `keyword`, numeric doc values for `long` and
+    /// `double`. `binary` properties have no doc values.
+    pub fn doc_values(mut self, doc_values: bool) -> Self {
+        if let Some(field) = self.fields.last_mut() {
+            field.doc_values = doc_values;
+        }
+        self
+    }
+
+    /// The names of the stored fields, which are loaded by `load`.
+    pub fn stored_fields(&self) -> Vec<String> {
+        self.fields
+            .iter()
+            .filter(|f| f.stored)
+            .map(|f| f.name.clone())
+            .collect()
+    }
+
+    /// Converts `value` to the fields of a document to be indexed.
+    pub fn to_document(&self, value: &T) -> Result<Vec<Box<dyn Fieldable>>> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for field in &self.fields {
+            field.fields(value, &mut fields)?;
+        }
+        Ok(fields)
+    }
+}
+
+impl<T: Default> DocumentMapping<T> {
+    /// Converts the stored fields of `doc` back to a value. The missing
+    /// properties keep their default value, the unmapped fields are ignored.
+    pub fn from_document(&self, doc: &Document) -> Result<T> {
+        let mut value = T::default();
+        for stored in &doc.fields {
+            let name = stored.field.name();
+            if let Some(field) = self.fields.iter().find(|f| f.stored && f.name == name) {
+                if let Some(data) = stored.field.fields_data() {
+                    (field.set)(&mut value, data.clone())?;
+                }
+            }
+        }
+        Ok(value)
+    }
+
+    /// Loads the stored fields of doc `doc_id` of `reader` as a value.
+    pub fn load<R: IndexReader + ?Sized>(&self, reader: &R, doc_id: DocId) -> Result<T> {
+        let doc = reader.document(doc_id, &self.stored_fields())?;
+        self.from_document(&doc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::StoredField;
+
+    #[derive(Default, Debug, PartialEq)]
+    struct Book {
+        id: String,
+        year: i64,
+        price: f64,
+        cover: Vec<u8>,
+    }
+
+    fn mapping() -> DocumentMapping<Book> {
+        DocumentMapping::new()
+            .keyword("id", |b: &Book| &b.id, |b, v| b.id = v)
+            .long("year", |b: &Book| b.year, |b, v| b.year = v)
+            .double("price", |b: &Book| b.price, |b, v| b.price = v)
+            .stored(false)
+            .binary("cover", |b: &Book| &b.cover, |b, v| b.cover = v)
+    }
+
+    #[test]
+    fn test_document_mapping() {
+        let mapping = mapping();
+        let book = Book {
+            id: "b1".to_string(),
+            year: 1999,
+            price: 12.5,
+            cover: vec![1, 2, 3],
+        };
+        let fields = mapping.to_document(&book).unwrap();
+        let names: Vec<&str> = fields.iter().map(|f| f.name()).collect();
+        assert_eq!(names, vec!["id", "year", "year", "price", "cover"]);
+        assert_eq!(fields[2].field_type().doc_values_type, DocValuesType::Null);
+        assert_eq!(
+            fields[3].field_type().doc_values_type,
+            DocValuesType::Numeric
+        );
+        assert_eq!(mapping.stored_fields(), vec!["id", "year", "cover"]);
+
+        let doc = Document::new(
+            fields
+                .iter()
+                .filter(|f| f.field_type().stored)
+                .map(|f| StoredField::new(f.name(), None, f.fields_data().unwrap().clone()))
+                .collect(),
+        );
+        let loaded = mapping.from_document(&doc).unwrap();
+        assert_eq!(loaded.id, "b1");
+        assert_eq!(loaded.year, 1999);
+        assert_eq!(loaded.cover, vec![1, 2, 3]);
+        assert!(loaded.price.abs() < ::std::f64::EPSILON);
+
+        let bad = Document::new(vec![StoredField::new(
+            "year",
+            None,
+            VariantValue::VString("x".into()),
+        )]);
+        assert!(mapping.from_document(&bad).is_err());
+    }
+}
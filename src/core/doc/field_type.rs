@@ -13,7 +13,11 @@
 
 use std::fmt;
 
-use core::index::{DocValuesType, IndexOptions, MAX_DIMENSIONS, MAX_NUM_BYTES};
+use core::codec::CompressionMode;
+use core::index::{
+    DocValuesType, IndexOptions, VectorSimilarityFunction, MAX_DIMENSIONS, MAX_NUM_BYTES,
+    MAX_VECTOR_DIMENSIONS,
+};
 
 use error::{ErrorKind::IllegalArgument, Result};
 
@@ -30,6 +34,32 @@ pub struct FieldType {
     pub doc_values_type: DocValuesType,
     pub dimension_count: u32,
     pub dimension_num_bytes: u32,
+    /// Name of the postings format the field is indexed with, `None` means
+    /// the default format of the codec.
+    pub postings_format: Option<&'static str>,
+    /// Name of the doc values format the values of the field are written
+    /// with, `None` means the default format of the codec.
+    pub doc_values_format: Option<&'static str>,
+    /// Whether the number of tokens of the field is indexed as the numeric
+    /// doc values of `token_count_field(name)` when it is inverted.
+    pub index_token_count: bool,
+    /// How the binary doc values of the field are compressed by blocks of
+    /// values, `None` means they are not compressed unless
+    /// `binary_doc_values_auto_compression` is set. The values of a field
+    /// compressed either way are written with `Rucene54DocValuesFormat`
+    /// unless `doc_values_format` is set.
+    pub binary_doc_values_compression: Option<CompressionMode>,
+    /// Whether the binary doc values of a field without compression mode are
+    /// compressed with LZ4 when a sample of them is large and compresses well.
+    pub binary_doc_values_auto_compression: bool,
+    /// Number of positions skipped between the values of a multi-valued
+    /// tokenized field, so that phrase queries don't match across values.
+    pub position_increment_gap: u32,
+    /// Number of dimensions of the vectors of the field, 0 if the field has
+    /// no vector.
+    pub vector_dimension: u32,
+    /// How the vectors of the field are compared.
+    pub vector_similarity_function: VectorSimilarityFunction,
 }
 
 impl Default for FieldType {
@@ -46,6 +76,14 @@ impl Default for FieldType {
             doc_values_type: DocValuesType::Null,
             dimension_count: 0,
             dimension_num_bytes: 0,
+            postings_format: None,
+            doc_values_format: None,
+            index_token_count: false,
+            binary_doc_values_compression: None,
+            binary_doc_values_auto_compression: false,
+            position_increment_gap: 0,
+            vector_dimension: 0,
+            vector_similarity_function: VectorSimilarityFunction::Euclidean,
         }
     }
 }
@@ -77,6 +115,14 @@ impl FieldType {
             doc_values_type,
             dimension_count,
             dimension_num_bytes,
+            postings_format: None,
+            doc_values_format: None,
+            index_token_count: false,
+            binary_doc_values_compression: None,
+            binary_doc_values_auto_compression: false,
+            position_increment_gap: 0,
+            vector_dimension: 0,
+            vector_similarity_function: VectorSimilarityFunction::Euclidean,
         }
     }
 
@@ -145,6 +191,54 @@ impl FieldType {
         self.tokenized
     }
 
+    pub fn postings_format(&self) -> Option<&'static str> {
+        self.postings_format
+    }
+
+    pub fn set_postings_format(&mut self, format: &'static str) {
+        self.postings_format = Some(format);
+    }
+
+    pub fn doc_values_format(&self) -> Option<&'static str> {
+        self.doc_values_format
+    }
+
+    pub fn set_doc_values_format(&mut self, format: &'static str) {
+        self.doc_values_format = Some(format);
+    }
+
+    pub fn binary_doc_values_compression(&self) -> Option<CompressionMode> {
+        self.binary_doc_values_compression
+    }
+
+    pub fn set_binary_doc_values_compression(&mut self, mode: CompressionMode) {
+        self.binary_doc_values_compression = Some(mode);
+    }
+
+    pub fn binary_doc_values_auto_compression(&self) -> bool {
+        self.binary_doc_values_auto_compression
+    }
+
+    pub fn set_binary_doc_values_auto_compression(&mut self) {
+        self.binary_doc_values_auto_compression = true;
+    }
+
+    pub fn index_token_count(&self) -> bool {
+        self.index_token_count
+    }
+
+    pub fn set_index_token_count(&mut self, v: bool) {
+        self.index_token_count = v;
+    }
+
+    pub fn position_increment_gap(&self) -> u32 {
+        self.position_increment_gap
+    }
+
+    pub fn set_position_increment_gap(&mut self, gap: u32) {
+        self.position_increment_gap = gap;
+    }
+
     pub fn set_dimensions(&mut self, dimension_count: u32, dimension_num_bytes: u32) -> Result<()> {
         if dimension_count > MAX_DIMENSIONS {
             bail!(IllegalArgument(format!(
@@ -177,6 +271,42 @@ impl FieldType {
 
         Ok(())
     }
+
+    pub fn vector_dimension(&self) -> u32 {
+        self.vector_dimension
+    }
+
+    pub fn vector_similarity_function(&self) -> VectorSimilarityFunction {
+        self.vector_similarity_function
+    }
+
+    pub fn set_vector_dimension_and_similarity_function(
+        &mut self,
+        dimension: u32,
+        similarity_function: VectorSimilarityFunction,
+    ) -> Result<()> {
+        if dimension == 0 || dimension > MAX_VECTOR_DIMENSIONS {
+            bail!(IllegalArgument(format!(
+                "vector dimension must be > 0 and <= {}, got {}",
+                MAX_VECTOR_DIMENSIONS, dimension
+            )));
+        }
+
+        self.vector_dimension = dimension;
+        self.vector_similarity_function = similarity_function;
+
+        Ok(())
+    }
+}
+
+/// Suffix of the name of the doc values field holding the token counts of an
+/// indexed field, see `FieldType::index_token_count`.
+pub const TOKEN_COUNT_FIELD_SUFFIX: &str = "$token_count";
+
+/// Returns the name of the doc values field holding the token counts of
+/// `field`.
+pub fn token_count_field(field: &str) -> String {
+    format!("{}{}", field, TOKEN_COUNT_FIELD_SUFFIX)
 }
 
 impl fmt::Display for FieldType {
@@ -201,6 +331,14 @@ pub const NUMERIC_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::Numeric,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    postings_format: None,
+    doc_values_format: None,
+    index_token_count: false,
+    binary_doc_values_compression: None,
+    binary_doc_values_auto_compression: false,
+    position_increment_gap: 0,
+    vector_dimension: 0,
+    vector_similarity_function: VectorSimilarityFunction::Euclidean,
 };
 
 pub const SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -215,6 +353,14 @@ pub const SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::SortedNumeric,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    postings_format: None,
+    doc_values_format: None,
+    index_token_count: false,
+    binary_doc_values_compression: None,
+    binary_doc_values_auto_compression: false,
+    position_increment_gap: 0,
+    vector_dimension: 0,
+    vector_similarity_function: VectorSimilarityFunction::Euclidean,
 };
 
 pub const BINARY_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -229,6 +375,14 @@ pub const BINARY_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::Binary,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    postings_format: None,
+    doc_values_format: None,
+    index_token_count: false,
+    binary_doc_values_compression: None,
+    binary_doc_values_auto_compression: false,
+    position_increment_gap: 0,
+    vector_dimension: 0,
+    vector_similarity_function: VectorSimilarityFunction::Euclidean,
 };
 
 pub const SORTED_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -243,6 +397,14 @@ pub const SORTED_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::Sorted,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    postings_format: None,
+    doc_values_format: None,
+    index_token_count: false,
+    binary_doc_values_compression: None,
+    binary_doc_values_auto_compression: false,
+    position_increment_gap: 0,
+    vector_dimension: 0,
+    vector_similarity_function: VectorSimilarityFunction::Euclidean,
 };
 
 pub const SORTED_SET_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
@@ -257,4 +419,12 @@ pub const SORTED_SET_DOC_VALUES_FIELD_TYPE: FieldType = FieldType {
     doc_values_type: DocValuesType::SortedSet,
     dimension_count: 0,
     dimension_num_bytes: 0,
+    postings_format: None,
+    doc_values_format: None,
+    index_token_count: false,
+    binary_doc_values_compression: None,
+    binary_doc_values_auto_compression: false,
+    position_increment_gap: 0,
+    vector_dimension: 0,
+    vector_similarity_function: VectorSimilarityFunction::Euclidean,
 };
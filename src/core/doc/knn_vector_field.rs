@@ -0,0 +1,138 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use core::analysis::TokenStream;
+use core::doc::{Field, FieldType};
+use core::index::{Fieldable, VectorSimilarityFunction};
+use core::util::{Numeric, VariantValue};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+/// A field holding a float vector per document, the vectors are read back
+/// with `LeafReader::vector_values`. All the vectors of a field must have the
+/// same dimension and similarity function.
+pub struct KnnVectorField {
+    field: Field,
+}
+
+impl KnnVectorField {
+    pub fn new(
+        name: &str,
+        vector: &[f32],
+        similarity_function: VectorSimilarityFunction,
+    ) -> Result<KnnVectorField> {
+        if vector.is_empty() {
+            bail!(IllegalArgument(format!(
+                "field '{}': vector must not be empty",
+                name
+            )));
+        }
+        let mut field_type = FieldType::default();
+        field_type.tokenized = false;
+        field_type.set_vector_dimension_and_similarity_function(
+            vector.len() as u32,
+            similarity_function,
+        )?;
+        Ok(KnnVectorField {
+            field: Field::new(
+                String::from(name),
+                field_type,
+                Some(VariantValue::Binary(Self::pack(vector))),
+                None,
+            ),
+        })
+    }
+
+    /// Encodes a vector as the binary value of the field.
+    pub fn pack(vector: &[f32]) -> Vec<u8> {
+        let mut bytes = vec![0u8; vector.len() * 4];
+        LittleEndian::write_f32_into(vector, &mut bytes);
+        bytes
+    }
+
+    /// Decodes the vector of a binary value into `vector`.
+    pub fn unpack(bytes: &[u8], vector: &mut [f32]) {
+        LittleEndian::read_f32_into(bytes, vector);
+    }
+}
+
+impl Fieldable for KnnVectorField {
+    fn name(&self) -> &str {
+        self.field.name()
+    }
+
+    fn field_type(&self) -> &FieldType {
+        self.field.field_type()
+    }
+
+    fn boost(&self) -> f32 {
+        self.field.boost()
+    }
+
+    fn fields_data(&self) -> Option<&VariantValue> {
+        self.field.fields_data()
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        unreachable!()
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        self.field.binary_value()
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        None
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        None
+    }
+}
+
+impl Deref for KnnVectorField {
+    type Target = Field;
+    fn deref(&self) -> &Field {
+        &self.field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knn_vector_field_new_test() {
+        let field = KnnVectorField::new(
+            "v",
+            &[1.0, -2.5, 0.25],
+            VectorSimilarityFunction::DotProduct,
+        )
+        .unwrap();
+        assert_eq!(field.name(), "v");
+        assert_eq!(field.field_type().vector_dimension(), 3);
+        assert_eq!(
+            field.field_type().vector_similarity_function(),
+            VectorSimilarityFunction::DotProduct
+        );
+        let mut vector = [0f32; 3];
+        KnnVectorField::unpack(field.binary_value().unwrap(), &mut vector);
+        assert_eq!(vector, [1.0, -2.5, 0.25]);
+
+        assert!(KnnVectorField::new("v", &[], VectorSimilarityFunction::Euclidean).is_err());
+    }
+}
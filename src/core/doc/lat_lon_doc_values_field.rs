@@ -0,0 +1,107 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Deref;
+
+use core::analysis::TokenStream;
+use core::doc::SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE;
+use core::doc::{Field, FieldType};
+use core::index::Fieldable;
+use core::search::sort_field::{GeoDistanceSortField, SortField};
+use core::util::geo;
+use core::util::{Numeric, VariantValue};
+
+use error::Result;
+
+/// A geo point stored as sorted numeric doc values, see `geo::encode_point`,
+/// which can be used to sort hits by their distance to a location, see
+/// `new_distance_sort`, or to bucket them by ranges of distances, see
+/// `GeoDistanceCollector`.
+///
+/// A document may have several points, the closest one is used for sorting.
+pub struct LatLonDocValuesField {
+    field: Field,
+}
+
+impl LatLonDocValuesField {
+    pub fn new(name: &str, latitude: f64, longitude: f64) -> Result<LatLonDocValuesField> {
+        let value = geo::encode_point(latitude, longitude)?;
+        Ok(LatLonDocValuesField {
+            field: Field::new(
+                String::from(name),
+                SORTED_NUMERIC_DOC_VALUES_FIELD_TYPE,
+                Some(VariantValue::Long(value)),
+                None,
+            ),
+        })
+    }
+
+    /// The quantized `(latitude, longitude)` of the point.
+    pub fn point(&self) -> (f64, f64) {
+        match self.field.fields_data().unwrap() {
+            VariantValue::Long(v) => geo::decode_point(*v),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Creates a `SortField` sorting hits by their distance in meters to
+    /// `(latitude, longitude)`, closest first. Documents without a point in
+    /// `field` sort last.
+    pub fn new_distance_sort(field: &str, latitude: f64, longitude: f64) -> Result<SortField> {
+        Ok(SortField::GeoDistance(GeoDistanceSortField::new(
+            field, latitude, longitude,
+        )?))
+    }
+}
+
+impl Fieldable for LatLonDocValuesField {
+    fn name(&self) -> &str {
+        self.field.name()
+    }
+
+    fn field_type(&self) -> &FieldType {
+        self.field.field_type()
+    }
+
+    fn boost(&self) -> f32 {
+        self.field.boost()
+    }
+
+    fn fields_data(&self) -> Option<&VariantValue> {
+        self.field.fields_data()
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        self.field.token_stream()
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        None
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        self.field.numeric_value()
+    }
+}
+
+impl Deref for LatLonDocValuesField {
+    type Target = Field;
+
+    fn deref(&self) -> &Field {
+        &self.field
+    }
+}
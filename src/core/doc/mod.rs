@@ -29,18 +29,27 @@ pub use self::sorted_set_doc_values_field::*;
 mod binary_doc_values_field;
 pub use self::binary_doc_values_field::*;
 
+mod knn_vector_field;
+pub use self::knn_vector_field::*;
+
 mod double_doc_values_field;
 pub use self::double_doc_values_field::*;
 
 mod float_doc_values_field;
 pub use self::float_doc_values_field::*;
 
+mod lat_lon_doc_values_field;
+pub use self::lat_lon_doc_values_field::*;
+
 mod numeric_field;
 pub use self::numeric_field::*;
 
 mod document;
 pub use self::document::*;
 
+mod document_mapping;
+pub use self::document_mapping::*;
+
 mod document_stored_field_visitor;
 pub use self::document_stored_field_visitor::*;
 
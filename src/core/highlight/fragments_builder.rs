@@ -31,6 +31,9 @@ pub struct BaseFragmentsBuilder {
     post_tags: Vec<String>,
     multi_valued_separator: char,
     boundary_scanner: Box<dyn BoundaryScanner>,
+    /// Whether the fragments of a multi-valued field are split at the
+    /// boundaries of the values, so that a fragment never spans several
+    /// values. Enabled by default.
     pub discrete_multi_value_highlighting: bool,
 }
 
@@ -46,7 +49,7 @@ impl BaseFragmentsBuilder {
             multi_valued_separator: ' ',
             boundary_scanner: boundary_scanner
                 .unwrap_or_else(|| Box::new(SimpleBoundaryScanner::new(None, None))),
-            discrete_multi_value_highlighting: false,
+            discrete_multi_value_highlighting: true,
         }
     }
 
@@ -11,6 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::analysis::Analyzer;
 use core::codec::Codec;
 use core::highlight::frag_list_builder::SimpleFragListBuilder;
 use core::highlight::fragments_builder::BaseFragmentsBuilder;
@@ -25,6 +26,7 @@ use core::util::DocId;
 use error::Result;
 
 use std::i32;
+use std::sync::Arc;
 
 const DEFAULT_PHRASE_HIGHLIGHT: bool = true;
 const DEFAULT_FIELD_MATCH: bool = true;
@@ -36,6 +38,7 @@ pub struct FastVectorHighlighter {
     frag_list_builder: Box<dyn FragListBuilder>,
     fragments_builder: BaseFragmentsBuilder,
     pub phrase_limit: i32,
+    query_analyzer: Option<Arc<dyn Analyzer>>,
 }
 
 impl FastVectorHighlighter {
@@ -67,9 +70,16 @@ impl FastVectorHighlighter {
                 Some(x) => x,
                 None => DEFAULT_PHRASE_LIMIT,
             },
+            query_analyzer: None,
         }
     }
 
+    /// Sets the analyzer used at query time, the terms of the query are expanded
+    /// through it (e.g. to their synonyms) when building the `FieldQuery`.
+    pub fn set_query_analyzer(&mut self, analyzer: Arc<dyn Analyzer>) {
+        self.query_analyzer = Some(analyzer);
+    }
+
     #[allow(too_many_arguments)]
     pub fn get_best_fragments<C: Codec>(
         &mut self,
@@ -162,7 +172,13 @@ impl FastVectorHighlighter {
         query: &dyn Query<C>,
         reader: Option<&IndexReader<Codec = C>>,
     ) -> Result<FieldQuery> {
-        FieldQuery::new(query, reader, self.phrase_highlight, self.field_match)
+        FieldQuery::with_analyzer(
+            query,
+            reader,
+            self.phrase_highlight,
+            self.field_match,
+            self.query_analyzer.as_ref().map(|a| a.as_ref()),
+        )
     }
 
     fn get_field_frag_list<C: Codec>(
@@ -11,6 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::analysis::{analyze, Analyzer};
 use core::codec::Codec;
 use core::index::{Fields, IndexReader, LeafReaderContext, Term, TermIterator, Terms};
 use core::search::{
@@ -508,10 +509,24 @@ pub struct FieldQuery {
 
 impl FieldQuery {
     pub fn new<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+        query: &dyn Query<C>,
+        reader: Option<&IR>,
+        phrase_highlight: bool,
+        field_match: bool,
+    ) -> Result<FieldQuery> {
+        Self::with_analyzer(query, reader, phrase_highlight, field_match, None)
+    }
+
+    /// Like `new`, but each query term is also run through `analyzer`, the analysis
+    /// chain used at query time. The extra terms it produces, e.g. the synonyms
+    /// injected by a `SynonymAnalyzer`, are highlighted like the query term itself,
+    /// so documents matched through a synonym still get highlighted passages.
+    pub fn with_analyzer<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
         query: &dyn Query<C>,
         reader: Option<&IR>,
         _phrase_highlight: bool,
         field_match: bool,
+        analyzer: Option<&dyn Analyzer>,
     ) -> Result<FieldQuery> {
         let mut flat_queries: Vec<TermQuery> = vec![];
         let mut field_query = FieldQuery {
@@ -529,6 +544,15 @@ impl FieldQuery {
         for flat_query in &expand_queries {
             let term_or_phrase_number = field_query.next_term_or_phrase_number();
             field_query.add_root_map_by_query(flat_query, reader, term_or_phrase_number)?;
+
+            if let Some(analyzer) = analyzer {
+                // expanded terms share the number of the query term, so they get
+                // the same tag when colored tags are used.
+                for expanded in field_query.analyze_term(flat_query, analyzer)? {
+                    field_query.add_term_set_by_query(&expanded, expanded.term.text()?);
+                    field_query.add_root_map_by_query(&expanded, reader, term_or_phrase_number)?;
+                }
+            }
         }
 
         Ok(field_query)
@@ -579,6 +603,23 @@ impl FieldQuery {
         Ok(())
     }
 
+    // Returns the terms other than the query term itself that `analyzer` produces
+    // from the text of the query term.
+    fn analyze_term(&self, query: &TermQuery, analyzer: &dyn Analyzer) -> Result<Vec<TermQuery>> {
+        let text = query.term.text()?;
+        let mut expanded: Vec<TermQuery> = vec![];
+        for token in analyze(analyzer, query.term.field(), &text)? {
+            if token.term == text {
+                continue;
+            }
+            let term = Term::new(query.term.field.clone(), token.term.into_bytes());
+            if expanded.iter().all(|q| q.term != term) {
+                expanded.push(TermQuery::new(term, query.boost, None));
+            }
+        }
+        Ok(expanded)
+    }
+
     // Return 'key' string. 'key' is the field name of the Query.
     // If not fieldMatch, 'key' will be null.
     //
@@ -599,7 +640,10 @@ impl FieldQuery {
         }
 
         debug_assert!(self.term_set_map.contains_key(&key));
-        self.term_set_map.get_mut(&key).unwrap().push(value)
+        let term_set = self.term_set_map.get_mut(&key).unwrap();
+        if !term_set.contains(&value) {
+            term_set.push(value)
+        }
     }
 
     pub fn get_term_set_by_field(&self, field: &str) -> Option<&Vec<String>> {
@@ -12,19 +12,28 @@
 // limitations under the License.
 
 use core::codec::CompressedBinaryTermIterator;
-use core::codec::{BinaryEntry, ReverseTermsIndexRef};
+use core::codec::{BinaryEntry, Decompress, Decompressor, ReverseTermsIndexRef};
 use core::index::SeekStatus;
 use core::index::TermIterator;
 use core::store::IndexInput;
 use core::util::packed::MonotonicBlockPackedReaderRef;
+use core::util::packed_misc::OffsetAndLength;
 use core::util::DocId;
 use core::util::LongValues;
 use error::Result;
 
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 pub trait BinaryDocValues: Send + Sync {
-    fn get(&self, doc_id: DocId) -> Result<Vec<u8>>;
+    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
+        let mut value = Vec::new();
+        self.get_into(doc_id, &mut value)?;
+        Ok(value)
+    }
+
+    /// Reads the value of `doc_id` into `value`, replacing its content, so
+    /// reading the values of many docs reuses the same buffer.
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()>;
 }
 
 pub type BinaryDocValuesRef = Arc<dyn BinaryDocValues>;
@@ -35,67 +44,165 @@ impl BinaryDocValues for EmptyBinaryDocValues {
     fn get(&self, _doc_id: DocId) -> Result<Vec<u8>> {
         Ok(Vec::with_capacity(0))
     }
+
+    fn get_into(&self, _doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        value.clear();
+        Ok(())
+    }
 }
 
 pub trait LongBinaryDocValues: BinaryDocValues {
-    fn get64(&self, doc_id: i64) -> Result<Vec<u8>>;
+    fn get64(&self, doc_id: i64) -> Result<Vec<u8>> {
+        let mut value = Vec::new();
+        self.get64_into(doc_id, &mut value)?;
+        Ok(value)
+    }
+
+    fn get64_into(&self, doc_id: i64, value: &mut Vec<u8>) -> Result<()>;
 }
 
+/// Binary doc values of the same length. The values are read from an input
+/// owned by the instance, which the segment readers keep per thread and
+/// field, so reading a value doesn't clone the input.
 pub struct FixedBinaryDocValues {
-    data: Box<dyn IndexInput>,
+    data: Mutex<Box<dyn IndexInput>>,
     buffer_len: usize,
 }
 
 impl FixedBinaryDocValues {
     pub fn new(data: Box<dyn IndexInput>, buffer_len: usize) -> Self {
-        FixedBinaryDocValues { data, buffer_len }
+        FixedBinaryDocValues {
+            data: Mutex::new(data),
+            buffer_len,
+        }
     }
 }
 
 impl LongBinaryDocValues for FixedBinaryDocValues {
-    fn get64(&self, id: i64) -> Result<Vec<u8>> {
+    fn get64_into(&self, id: i64, value: &mut Vec<u8>) -> Result<()> {
         let length = self.buffer_len;
-        let mut data = self.data.as_ref().clone()?;
+        let mut data = self.data.lock()?;
         data.seek(id * length as i64)?;
-        let mut buffer = vec![0u8; length];
-        data.read_bytes(&mut buffer, 0, length)?;
-        Ok(buffer)
+        value.resize(length, 0u8);
+        data.read_bytes(value, 0, length)
     }
 }
 
 impl BinaryDocValues for FixedBinaryDocValues {
-    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
-        FixedBinaryDocValues::get64(self, i64::from(doc_id))
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        self.get64_into(i64::from(doc_id), value)
     }
 }
 
+/// Binary doc values of variable length, addressed by `addresses`. Like
+/// `FixedBinaryDocValues` the input is owned by the instance.
 pub struct VariableBinaryDocValues<T: LongValues> {
     addresses: T,
-    data: Box<dyn IndexInput>,
+    data: Mutex<Box<dyn IndexInput>>,
 }
 
 impl<T: LongValues> VariableBinaryDocValues<T> {
     pub fn new(addresses: T, data: Box<dyn IndexInput>, _length: usize) -> Self {
-        VariableBinaryDocValues { addresses, data }
+        VariableBinaryDocValues {
+            addresses,
+            data: Mutex::new(data),
+        }
     }
 }
 
 impl<T: LongValues> LongBinaryDocValues for VariableBinaryDocValues<T> {
-    fn get64(&self, id: i64) -> Result<Vec<u8>> {
+    fn get64_into(&self, id: i64, value: &mut Vec<u8>) -> Result<()> {
         let start_address = self.addresses.get64(id)?;
         let end_address = self.addresses.get64(id + 1)?;
         let length = (end_address - start_address) as usize;
-        let mut data = self.data.as_ref().clone()?;
+        let mut data = self.data.lock()?;
         data.seek(start_address)?;
-        let mut buffer = vec![0u8; length];
-        data.read_bytes(&mut buffer, 0, length)?;
-        Ok(buffer)
+        value.resize(length, 0u8);
+        data.read_bytes(value, 0, length)
     }
 }
 
 impl<T: LongValues> BinaryDocValues for VariableBinaryDocValues<T> {
-    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
-        VariableBinaryDocValues::get64(self, i64::from(doc_id))
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        self.get64_into(i64::from(doc_id), value)
+    }
+}
+
+/// Binary doc values compressed by blocks of `2^block_shift` values. The last
+/// decompressed block is kept, as the values are mostly read in doc id order.
+pub struct BlockCompressedBinaryDocValues<T: LongValues> {
+    addresses: T,
+    data: Box<dyn IndexInput>,
+    block_shift: i32,
+    decompressor: Decompressor,
+    block: Mutex<DecompressedBlock>,
+}
+
+struct DecompressedBlock {
+    id: i64,
+    // the offsets of the values in `bytes`, followed by the end of the last one
+    offsets: Vec<usize>,
+    bytes: Vec<u8>,
+}
+
+impl<T: LongValues> BlockCompressedBinaryDocValues<T> {
+    pub fn new(
+        addresses: T,
+        data: Box<dyn IndexInput>,
+        block_shift: i32,
+        decompressor: Decompressor,
+    ) -> Self {
+        BlockCompressedBinaryDocValues {
+            addresses,
+            data,
+            block_shift,
+            decompressor,
+            block: Mutex::new(DecompressedBlock {
+                id: -1,
+                offsets: vec![],
+                bytes: vec![],
+            }),
+        }
+    }
+
+    fn decompress_block(&self, id: i64, block: &mut DecompressedBlock) -> Result<()> {
+        let mut data = self.data.as_ref().clone()?;
+        data.seek(self.addresses.get64(id)?)?;
+        let num_values = data.read_vint()? as usize;
+        block.offsets.clear();
+        block.offsets.push(0);
+        let mut end = 0;
+        for _ in 0..num_values {
+            end += data.read_vint()? as usize;
+            block.offsets.push(end);
+        }
+        if end > 0 {
+            let mut position = OffsetAndLength(0, 0);
+            self.decompressor.decompress(
+                data.as_mut(),
+                end,
+                0,
+                end,
+                &mut block.bytes,
+                &mut position,
+            )?;
+        }
+        block.id = id;
+        Ok(())
+    }
+}
+
+impl<T: LongValues> BinaryDocValues for BlockCompressedBinaryDocValues<T> {
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        let id = i64::from(doc_id) >> self.block_shift;
+        let mut block = self.block.lock()?;
+        if block.id != id {
+            self.decompress_block(id, &mut block)?;
+        }
+        let index = (doc_id & ((1 << self.block_shift) - 1)) as usize;
+        value.clear();
+        value.extend_from_slice(&block.bytes[block.offsets[index]..block.offsets[index + 1]]);
+        Ok(())
     }
 }
 
@@ -107,6 +214,9 @@ pub struct CompressedBinaryDocValues {
     data: Box<dyn IndexInput>,
     reverse_index: ReverseTermsIndexRef,
     addresses: MonotonicBlockPackedReaderRef,
+    // reused by `get64`, so the header of the current block is only decoded
+    // again when the ordinal is in another block
+    lookup_iterator: Mutex<CompressedBinaryTermIterator>,
 }
 
 impl CompressedBinaryDocValues {
@@ -121,6 +231,15 @@ impl CompressedBinaryDocValues {
         let num_values = bytes.count;
         let num_index_values = addresses.size() as i64;
 
+        let lookup_iterator = CompressedBinaryTermIterator::new(
+            IndexInput::clone(data.as_ref())?,
+            max_term_length as usize,
+            num_reverse_index_values,
+            Arc::clone(&reverse_index),
+            Arc::clone(&addresses),
+            num_values,
+            num_index_values,
+        )?;
         let dv = CompressedBinaryDocValues {
             num_values,
             num_index_values,
@@ -129,6 +248,7 @@ impl CompressedBinaryDocValues {
             data,
             reverse_index,
             addresses,
+            lookup_iterator: Mutex::new(lookup_iterator),
         };
         Ok(dv)
     }
@@ -160,17 +280,18 @@ impl CompressedBinaryDocValues {
 }
 
 impl LongBinaryDocValues for CompressedBinaryDocValues {
-    fn get64(&self, id: i64) -> Result<Vec<u8>> {
-        let mut term_iterator = self.get_term_iterator()?;
+    fn get64_into(&self, id: i64, value: &mut Vec<u8>) -> Result<()> {
+        let mut term_iterator = self.lookup_iterator.lock()?;
         term_iterator.seek_exact_ord(id)?;
-        let term = term_iterator.term()?;
-        Ok(term.to_vec())
+        value.clear();
+        value.extend_from_slice(term_iterator.term()?);
+        Ok(())
     }
 }
 
 impl BinaryDocValues for CompressedBinaryDocValues {
-    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
-        CompressedBinaryDocValues::get64(self, i64::from(doc_id))
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        self.get64_into(i64::from(doc_id), value)
     }
 }
 
@@ -12,6 +12,7 @@
 // limitations under the License.
 
 use core::codec::{Codec, CodecPostingIterator, CodecTermIterator};
+use core::index::doc_values_writer::{DocValuesFieldUpdates, DocValuesUpdate};
 use core::index::index_writer::{ReaderPool, ReadersAndUpdates};
 use core::index::merge_policy::MergePolicy;
 use core::index::prefix_code_terms::{FieldTermIter, FieldTermIterator};
@@ -25,7 +26,7 @@ use core::search::query_cache::{NoCacheQueryCache, QueryCache};
 use core::search::searcher::{DefaultIndexSearcher, IndexSearcher, SearchPlanBuilder};
 use core::search::{DocIterator, Query, NO_MORE_DOCS};
 use core::store::{Directory, IOContext};
-use core::util::DocId;
+use core::util::{Accountable, DocId};
 
 use std::cmp::{min, Ordering as CmpOrdering};
 use std::collections::{BinaryHeap, HashMap};
@@ -53,6 +54,9 @@ pub const BYTES_PER_DEL_TERM: usize = 6 * mem::size_of::<usize>();
 /// entry costs.
 pub const BYTES_PER_DEL_QUERY_IN_HASH: usize = 4 * mem::size_of::<usize>() + 28;
 
+/// Rough logic: a doc values update is kept in a Vec, plus the update itself
+pub const BYTES_PER_DV_UPDATE: usize = 2 * mem::size_of::<usize>();
+
 /// Holds buffered deletes and updates, by docID, term or query for a
 /// single segment. This is used to hold buffered pending
 /// deletes and updates against the to-be-flushed segment.  Once the
@@ -70,23 +74,9 @@ pub struct BufferedUpdates<C: Codec> {
     // the key is string represent of query, query is share by multi-thread
     pub deleted_queries: HashMap<String, (Arc<dyn Query<C>>, DocId)>,
     pub deleted_doc_ids: Vec<i32>,
-    // Map<dvField,Map<updateTerm,NumericUpdate>>
-    // For each field we keep an ordered list of NumericUpdates, key'd by the
-    // update Term. LinkedHashMap guarantees we will later traverse the map in
-    // insertion order (so that if two terms affect the same document, the last
-    // one that came in wins), and helps us detect faster if the same Term is
-    // used to update the same field multiple times (so we later traverse it
-    // only once).
-    // numeric_updates: HashMap<String, HashMap<Term, NumericDocValuesUpdate>>,
-    //
-    // Map<dvField,Map<updateTerm,BinaryUpdate>>
-    // For each field we keep an ordered list of BinaryUpdates, key'd by the
-    // update Term. LinkedHashMap guarantees we will later traverse the map in
-    // insertion order (so that if two terms affect the same document, the last
-    // one that came in wins), and helps us detect faster if the same Term is
-    // used to update the same field multiple times (so we later traverse it
-    // only once).
-    // binary_update: HashMap<String, HashMap<Term, BinaryDocValuesUpdate>>,
+    // The doc values updates, kept in insertion order so that if two terms
+    // affect the same document, the last one that came in wins.
+    pub dv_updates: Vec<DocValuesUpdate>,
     pub bytes_used: AtomicUsize,
     // gen: i64,
     pub segment_name: String,
@@ -99,6 +89,7 @@ impl<C: Codec> BufferedUpdates<C> {
             deleted_terms: HashMap::new(),
             deleted_queries: HashMap::new(),
             deleted_doc_ids: vec![],
+            dv_updates: vec![],
             bytes_used: AtomicUsize::new(0),
             segment_name: name,
         }
@@ -152,10 +143,18 @@ impl<C: Codec> BufferedUpdates<C> {
         }
     }
 
+    pub fn add_doc_values_update(&mut self, mut update: DocValuesUpdate, doc_id_upto: DocId) {
+        update.doc_id_up_to = doc_id_upto;
+        let cost = BYTES_PER_DV_UPDATE + update.size_in_bytes();
+        self.dv_updates.push(update);
+        self.bytes_used.fetch_add(cost, Ordering::AcqRel);
+    }
+
     pub fn clear(&mut self) {
         self.deleted_terms.clear();
         self.deleted_queries.clear();
         self.deleted_doc_ids.clear();
+        self.dv_updates.clear();
         self.num_term_deletes.store(0, Ordering::Release);
         self.bytes_used.store(0, Ordering::Release);
     }
@@ -164,6 +163,7 @@ impl<C: Codec> BufferedUpdates<C> {
         !self.deleted_terms.is_empty()
             || !self.deleted_doc_ids.is_empty()
             || !self.deleted_queries.is_empty()
+            || !self.dv_updates.is_empty()
     }
 }
 
@@ -178,12 +178,14 @@ pub struct FrozenBufferedUpdates<C: Codec> {
     terms: Arc<PrefixCodedTerms>,
     // Parallel array of deleted query, and the doc_id_upto for each
     query_and_limits: Vec<(Arc<dyn Query<C>>, DocId)>,
+    // doc values updates in insertion order, with the doc_id_upto of each
+    dv_updates: Arc<Vec<DocValuesUpdate>>,
     pub bytes_used: usize,
     pub num_term_deletes: usize,
     pub gen: u64,
     // assigned by BufferedUpdatesStream once pushed
     // set to true iff this frozen packet represents a segment private delete.
-    // in that case is should only have queries
+    // in that case is should only have queries and doc values updates
     is_segment_private: bool,
 }
 
@@ -199,6 +201,9 @@ impl<C: Codec> fmt::Display for FrozenBufferedUpdates<C> {
         if !self.query_and_limits.is_empty() {
             write!(f, " {} deleted queries", self.query_and_limits.len())?;
         }
+        if !self.dv_updates.is_empty() {
+            write!(f, " {} doc values updates", self.dv_updates.len())?;
+        }
         if self.bytes_used > 0 {
             write!(f, " bytes_used={}", self.bytes_used)?;
         }
@@ -232,10 +237,18 @@ impl<C: Codec> FrozenBufferedUpdates<C> {
         // so that it maps to all fields it affects, sorted by their docUpto, and traverse
         // that Term only once, applying the update to all fields that still need to be
         // updated.
-        let bytes_used = terms.ram_bytes_used() + query_and_limits.len() * BYTES_PER_DEL_QUERY;
+        let dv_updates: Vec<DocValuesUpdate> = deletes.dv_updates.drain(..).collect();
+        let dv_bytes_used: usize = dv_updates
+            .iter()
+            .map(|u| BYTES_PER_DV_UPDATE + u.size_in_bytes())
+            .sum();
+
+        let bytes_used =
+            terms.ram_bytes_used() + query_and_limits.len() * BYTES_PER_DEL_QUERY + dv_bytes_used;
         FrozenBufferedUpdates {
             terms: Arc::new(terms),
             query_and_limits,
+            dv_updates: Arc::new(dv_updates),
             bytes_used,
             num_term_deletes: deletes.num_term_deletes.load(Ordering::Acquire),
             gen: u64::max_value(),
@@ -251,7 +264,7 @@ impl<C: Codec> FrozenBufferedUpdates<C> {
     }
 
     pub fn any(&self) -> bool {
-        self.terms.size > 0 || self.query_and_limits.len() > 0
+        self.terms.size > 0 || self.query_and_limits.len() > 0 || !self.dv_updates.is_empty()
     }
 }
 
@@ -295,6 +308,14 @@ impl<C: Codec> Default for BufferedUpdatesStream<C> {
     }
 }
 
+/// The frozen packets of deletes and updates not yet applied to the
+/// segments.
+impl<C: Codec> Accountable for BufferedUpdatesStream<C> {
+    fn ram_bytes_used(&self) -> usize {
+        self.bytes_used.load(Ordering::Acquire)
+    }
+}
+
 impl<C: Codec> BufferedUpdatesStream<C> {
     // Append a new packet of buffered deletes to the stream:
     // setting its generation:
@@ -336,10 +357,6 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         self.num_terms.load(Ordering::Acquire)
     }
 
-    pub fn ram_bytes_used(&self) -> usize {
-        self.bytes_used.load(Ordering::Acquire)
-    }
-
     pub fn apply_deletes_and_updates<D, MS, MP>(
         &self,
         pool: &ReaderPool<D, C, MS, MP>,
@@ -460,6 +477,11 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                         updates[del_idx - 1].query_and_limits.iter(),
                         seg_state,
                     )?;
+                    Self::apply_doc_values_updates(
+                        updates[del_idx - 1].dv_updates.iter(),
+                        seg_state,
+                        true,
+                    )?;
 
                     // ... then coalesced deletes/updates, so that if there is an update
                     // that appears in both, the coalesced updates (carried from
@@ -470,6 +492,13 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                             seg_state,
                         )?;
                     }
+                    if coalesce_updates.has_dv_updates() {
+                        Self::apply_doc_values_updates(
+                            coalesce_updates.dv_updates(),
+                            seg_state,
+                            false,
+                        )?;
+                    }
 
                     total_del_count += del_count;
 
@@ -494,6 +523,13 @@ impl<C: Codec> BufferedUpdatesStream<C> {
                                 seg_state,
                             )?;
                         }
+                        if coalesce_updates.has_dv_updates() {
+                            Self::apply_doc_values_updates(
+                                coalesce_updates.dv_updates(),
+                                seg_state,
+                                false,
+                            )?;
+                        }
 
                         total_del_count += del_count;
                     }
@@ -568,6 +604,57 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         Ok(del_count)
     }
 
+    /// Resolves the update terms to the documents of the segment, the updates
+    /// are written on close of the segment states. The limit of each update only
+    /// applies to the segment private updates, coalesced updates are all newer
+    /// than the segment.
+    fn apply_doc_values_updates<'a, D, MS, MP>(
+        updates: impl Iterator<Item = &'a DocValuesUpdate>,
+        seg_state: &mut SegmentState<D, C, MS, MP>,
+        segment_private: bool,
+    ) -> Result<()>
+    where
+        D: Directory + Send + Sync + 'static,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let rld = seg_state.rld.inner.lock()?;
+        let reader = rld.reader();
+        let fields = reader.fields()?;
+        for update in updates {
+            let limit = if segment_private {
+                update.doc_id_up_to
+            } else {
+                NO_MORE_DOCS
+            };
+            let mut terms_iterator = match fields.terms(&update.term.field)? {
+                Some(terms) => terms.iterator()?,
+                None => continue,
+            };
+            if !terms_iterator.seek_exact(&update.term.bytes)? {
+                continue;
+            }
+            let mut postings = terms_iterator.postings_with_flags(PostingIteratorFlags::NONE)?;
+            let field_updates = seg_state
+                .dv_updates
+                .entry(update.field.clone())
+                .or_insert_with(|| DocValuesFieldUpdates::new(update.doc_values_type));
+            loop {
+                let doc = postings.next()?;
+                if doc >= limit {
+                    break;
+                }
+                // deleted documents are updated too, they may be live in a
+                // pending reader, and their values are dropped on merge anyway.
+                field_updates.add(doc, update.value.clone());
+            }
+        }
+        for field_updates in seg_state.dv_updates.values_mut() {
+            field_updates.finish();
+        }
+        Ok(())
+    }
+
     /// Merge sorts the deleted terms and all segments to resolve terms to doc_ids for deletion.
     fn apply_term_deletes<D, MS, MP>(
         &mut self,
@@ -778,7 +865,20 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         let mut total_del_count = 0;
         let mut all_deleted = vec![];
 
+        let mut any_updates = false;
         for seg_state in seg_states {
+            if success && !seg_state.dv_updates.is_empty() {
+                let info = &seg_state.rld.info;
+                let res = seg_state.rld.inner.lock()?.write_field_updates(
+                    info,
+                    &info.info.directory,
+                    &seg_state.dv_updates,
+                );
+                if let Err(e) = res {
+                    first_err = Err(e);
+                }
+                any_updates = true;
+            }
             if success {
                 total_del_count +=
                     seg_state.rld.pending_delete_count() - seg_state.start_del_count as u32;
@@ -806,7 +906,7 @@ impl<C: Codec> BufferedUpdatesStream<C> {
         );
 
         Ok(ApplyDeletesResult::new(
-            total_del_count > 0,
+            total_del_count > 0 || any_updates,
             gen,
             all_deleted,
         ))
@@ -917,6 +1017,8 @@ struct SegmentState<
     postings: Option<CodecPostingIterator<C>>,
     term: Option<Vec<u8>>,
     any: bool,
+    // the doc values updates of the segment, by field
+    dv_updates: HashMap<String, DocValuesFieldUpdates>,
 }
 
 impl<D, C, MS, MP> SegmentState<D, C, MS, MP>
@@ -940,6 +1042,7 @@ where
             postings: None,
             term: None,
             any: false,
+            dv_updates: HashMap::new(),
         })
     }
 
@@ -1035,6 +1138,8 @@ struct CoalescedUpdates<C: Codec> {
     queries: HashMap<String, (Arc<dyn Query<C>>, DocId)>,
     terms: Vec<Arc<PrefixCodedTerms>>,
     total_term_count: usize,
+    // the packets are coalesced from the newest to the oldest
+    dv_updates: Vec<Arc<Vec<DocValuesUpdate>>>,
 }
 
 impl<C: Codec> Default for CoalescedUpdates<C> {
@@ -1043,6 +1148,7 @@ impl<C: Codec> Default for CoalescedUpdates<C> {
             queries: HashMap::new(),
             terms: vec![],
             total_term_count: 0,
+            dv_updates: vec![],
         }
     }
 }
@@ -1056,6 +1162,9 @@ impl<C: Codec> CoalescedUpdates<C> {
             self.queries
                 .insert(query.to_string(), (Arc::clone(query), i32::max_value()));
         }
+        if !up.dv_updates.is_empty() {
+            self.dv_updates.push(Arc::clone(&up.dv_updates));
+        }
     }
 
     fn term_iterator(&self) -> Result<FieldTermIterator> {
@@ -1071,7 +1180,20 @@ impl<C: Codec> CoalescedUpdates<C> {
         !self.queries.is_empty()
     }
 
+    /// Returns the doc values updates from the oldest to the newest, so
+    /// that the last update of a document wins.
+    pub fn dv_updates(&self) -> impl Iterator<Item = &DocValuesUpdate> {
+        self.dv_updates
+            .iter()
+            .rev()
+            .flat_map(|updates| updates.iter())
+    }
+
+    pub fn has_dv_updates(&self) -> bool {
+        !self.dv_updates.is_empty()
+    }
+
     pub fn any(&self) -> bool {
-        self.queries.len() > 0 || self.terms.len() > 0
+        self.queries.len() > 0 || self.terms.len() > 0 || !self.dv_updates.is_empty()
     }
 }
@@ -11,9 +11,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::index::index_commit::IndexCommit;
-use core::store::Directory;
-use error::Result;
+use core::index::index_commit::IndexCommitPoint;
+use error::{
+    ErrorKind::{IllegalArgument, IllegalState},
+    Result,
+};
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 /// Expert: policy for deletion of stale `IndexCommit index commits`.
 ///
@@ -44,7 +50,7 @@ use error::Result;
 /// Implementers of sub-classes should make sure that `#clone()`
 /// returns an independent instance able to work with any other `IndexWriter`
 /// or `Directory` instance.
-pub trait IndexDeletionPolicy {
+pub trait IndexDeletionPolicy: Send + Sync {
     /// This is called once when a writer is first
     /// instantiated to give the policy a chance to remove old
     /// commit points.
@@ -65,7 +71,7 @@ pub trait IndexDeletionPolicy {
     ///  sorted by age (the 0th one is the oldest commit).
     ///  Note that for a new index this method is invoked with
     ///  an empty list.
-    fn on_init<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()>;
+    fn on_init(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()>;
 
     /// This is called each time the writer completed a commit.
     /// This gives the policy a chance to remove old commit points
@@ -86,18 +92,18 @@ pub trait IndexDeletionPolicy {
     ///  
     /// @param commits List of `IndexCommit`,
     ///  sorted by age (the 0th one is the oldest commit).
-    fn on_commit<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()>;
+    fn on_commit(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()>;
 }
 
 #[derive(Default)]
 pub struct KeepOnlyLastCommitDeletionPolicy;
 
 impl IndexDeletionPolicy for KeepOnlyLastCommitDeletionPolicy {
-    fn on_init<D: Directory>(&self, commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+    fn on_init(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
         self.on_commit(commits)
     }
 
-    fn on_commit<D: Directory>(&self, mut commits: Vec<&mut IndexCommit<D>>) -> Result<()> {
+    fn on_commit(&self, mut commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
         commits.pop();
         for commit in commits {
             commit.delete()?;
@@ -105,3 +111,455 @@ impl IndexDeletionPolicy for KeepOnlyLastCommitDeletionPolicy {
         Ok(())
     }
 }
+
+/// An `IndexDeletionPolicy` keeping the last `n` commits, e.g. to let the
+/// readers on a shared filesystem finish their searches on an older commit,
+/// or to be able to roll the index back a few commits.
+#[derive(Debug)]
+pub struct KeepLastCommitsDeletionPolicy {
+    num_commits: usize,
+}
+
+impl KeepLastCommitsDeletionPolicy {
+    pub fn new(num_commits: usize) -> Result<Self> {
+        if num_commits == 0 {
+            bail!(IllegalArgument(
+                "at least the last commit must be kept".into()
+            ));
+        }
+        Ok(KeepLastCommitsDeletionPolicy { num_commits })
+    }
+
+    pub fn num_commits(&self) -> usize {
+        self.num_commits
+    }
+}
+
+impl IndexDeletionPolicy for KeepLastCommitsDeletionPolicy {
+    fn on_init(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
+        self.on_commit(commits)
+    }
+
+    fn on_commit(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
+        let num_deleted = commits.len().saturating_sub(self.num_commits);
+        for commit in commits.into_iter().take(num_deleted) {
+            commit.delete()?;
+        }
+        Ok(())
+    }
+}
+
+/// An `IndexDeletionPolicy` keeping the commits younger than `max_age`, the
+/// last commit is always kept.
+///
+/// The age of a commit is counted from the time the policy first saw it, the
+/// commits found in the index when the writer is opened are thus kept for
+/// `max_age` after the opening, rather than deleted too early. The expired
+/// commits are deleted on the next commit, or with
+/// `IndexWriter::delete_unused_files()` if the writer is idle.
+#[derive(Debug)]
+pub struct KeepYoungCommitsDeletionPolicy {
+    max_age: Duration,
+    // when each commit generation was first seen
+    seen: Mutex<HashMap<i64, Instant>>,
+}
+
+impl KeepYoungCommitsDeletionPolicy {
+    pub fn new(max_age: Duration) -> Self {
+        KeepYoungCommitsDeletionPolicy {
+            max_age,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    fn delete_expired(&self, commits: Vec<&mut dyn IndexCommitPoint>, now: Instant) -> Result<()> {
+        let mut seen = self.seen.lock()?;
+        let last = commits.len().saturating_sub(1);
+        for (i, commit) in commits.into_iter().enumerate() {
+            let generation = commit.generation();
+            let first_seen = *seen.entry(generation).or_insert(now);
+            if i < last && now.duration_since(first_seen) > self.max_age {
+                commit.delete()?;
+                seen.remove(&generation);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IndexDeletionPolicy for KeepYoungCommitsDeletionPolicy {
+    fn on_init(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
+        self.delete_expired(commits, Instant::now())
+    }
+
+    fn on_commit(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
+        self.delete_expired(commits, Instant::now())
+    }
+}
+
+/// A snapshot of an index commit taken by `SnapshotDeletionPolicy`, its
+/// files stay in the index directory until it's released.
+#[derive(Debug, Clone)]
+pub struct SnapshotCommit {
+    segments_file_name: String,
+    files: HashSet<String>,
+    generation: i64,
+    segment_count: usize,
+    user_data: HashMap<String, String>,
+}
+
+impl SnapshotCommit {
+    fn new(commit: &dyn IndexCommitPoint) -> Result<SnapshotCommit> {
+        Ok(SnapshotCommit {
+            segments_file_name: commit.segments_file_name().to_string(),
+            files: commit.file_names()?.clone(),
+            generation: commit.generation(),
+            segment_count: commit.segment_count(),
+            user_data: commit.user_data().clone(),
+        })
+    }
+
+    /// The segments file (`segments_N`) of the commit.
+    pub fn segments_file_name(&self) -> &str {
+        &self.segments_file_name
+    }
+
+    /// All the index files of the commit, the segments file included.
+    pub fn file_names(&self) -> &HashSet<String> {
+        &self.files
+    }
+
+    pub fn generation(&self) -> i64 {
+        self.generation
+    }
+
+    pub fn segment_count(&self) -> usize {
+        self.segment_count
+    }
+
+    pub fn user_data(&self) -> &HashMap<String, String> {
+        &self.user_data
+    }
+}
+
+#[derive(Default)]
+struct SnapshotState {
+    // snapshot count per commit generation
+    ref_counts: HashMap<i64, u32>,
+    snapshots: HashMap<i64, SnapshotCommit>,
+    last_commit: Option<SnapshotCommit>,
+}
+
+/// An `IndexDeletionPolicy` wrapping another one to keep the commits that
+/// have been snapshotted, whatever the wrapped policy decides, e.g. to copy
+/// the files of a commit for a backup while the writer goes on indexing.
+///
+/// `snapshot()` pins the last commit, and `release()` lets it go again once
+/// it's not needed anymore. The files of a released commit are deleted on
+/// the next commit of the writer, or right away with
+/// `IndexWriter::delete_unused_files()`.
+///
+/// The snapshots are held in memory only, so they are lost when the writer
+/// is closed. Set the policy on the `IndexWriterConfig` and keep a handle to
+/// take the snapshots:
+///
+/// ```ignore
+/// let policy = Arc::new(SnapshotDeletionPolicy::default());
+/// config.index_deletion_policy = Arc::clone(&policy) as Arc<dyn IndexDeletionPolicy>;
+/// ```
+pub struct SnapshotDeletionPolicy<P: IndexDeletionPolicy = KeepOnlyLastCommitDeletionPolicy> {
+    primary: P,
+    state: Mutex<SnapshotState>,
+}
+
+impl Default for SnapshotDeletionPolicy {
+    fn default() -> Self {
+        SnapshotDeletionPolicy::new(KeepOnlyLastCommitDeletionPolicy)
+    }
+}
+
+impl<P: IndexDeletionPolicy> SnapshotDeletionPolicy<P> {
+    pub fn new(primary: P) -> Self {
+        SnapshotDeletionPolicy {
+            primary,
+            state: Mutex::new(SnapshotState::default()),
+        }
+    }
+
+    /// Snapshots the last commit of the index, its files won't be deleted
+    /// until the snapshot is released with `release()`. A commit may be
+    /// snapshotted several times, it's kept until all its snapshots are
+    /// released.
+    ///
+    /// Fails if the writer has not opened any commit yet.
+    pub fn snapshot(&self) -> Result<SnapshotCommit> {
+        let mut state = self.state.lock()?;
+        let commit = match state.last_commit {
+            Some(ref commit) => commit.clone(),
+            None => bail!(IllegalState("no index commit to snapshot".into())),
+        };
+        *state.ref_counts.entry(commit.generation).or_insert(0) += 1;
+        state
+            .snapshots
+            .entry(commit.generation)
+            .or_insert_with(|| commit.clone());
+        Ok(commit)
+    }
+
+    /// Releases a snapshot taken with `snapshot()`.
+    pub fn release(&self, commit: &SnapshotCommit) -> Result<()> {
+        self.release_generation(commit.generation)
+    }
+
+    /// Releases a snapshot of the commit of the given generation.
+    pub fn release_generation(&self, generation: i64) -> Result<()> {
+        let mut state = self.state.lock()?;
+        let released = match state.ref_counts.get_mut(&generation) {
+            Some(count) => {
+                *count -= 1;
+                *count == 0
+            }
+            None => bail!(IllegalArgument(format!(
+                "commit gen={} is not currently snapshotted",
+                generation
+            ))),
+        };
+        if released {
+            state.ref_counts.remove(&generation);
+            state.snapshots.remove(&generation);
+        }
+        Ok(())
+    }
+
+    /// The commits currently snapshotted, oldest first.
+    pub fn snapshots(&self) -> Vec<SnapshotCommit> {
+        let state = self.state.lock().unwrap();
+        let mut snapshots: Vec<SnapshotCommit> = state.snapshots.values().cloned().collect();
+        snapshots.sort_by_key(|c| c.generation);
+        snapshots
+    }
+
+    /// The number of snapshots not released yet, a commit snapshotted twice
+    /// counts twice.
+    pub fn snapshot_count(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.ref_counts.values().map(|&c| c as usize).sum()
+    }
+
+    fn on_changed(&self, commits: Vec<&mut dyn IndexCommitPoint>, init: bool) -> Result<()> {
+        let mut state = self.state.lock()?;
+        let last_commit = match commits.last() {
+            Some(commit) => Some(SnapshotCommit::new(&**commit)?),
+            None => None,
+        };
+        let mut wrapped: Vec<SnapshotCommitPoint> = commits
+            .into_iter()
+            .map(|commit| SnapshotCommitPoint {
+                snapshotted: state.ref_counts.contains_key(&commit.generation()),
+                commit,
+            })
+            .collect();
+        {
+            let commits: Vec<&mut dyn IndexCommitPoint> = wrapped
+                .iter_mut()
+                .map(|c| c as &mut dyn IndexCommitPoint)
+                .collect();
+            if init {
+                self.primary.on_init(commits)?;
+            } else {
+                self.primary.on_commit(commits)?;
+            }
+        }
+        state.last_commit = last_commit;
+        Ok(())
+    }
+}
+
+impl<P: IndexDeletionPolicy> IndexDeletionPolicy for SnapshotDeletionPolicy<P> {
+    fn on_init(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
+        self.on_changed(commits, true)
+    }
+
+    fn on_commit(&self, commits: Vec<&mut dyn IndexCommitPoint>) -> Result<()> {
+        self.on_changed(commits, false)
+    }
+}
+
+/// A commit as seen by the primary policy of a `SnapshotDeletionPolicy`,
+/// ignoring its deletion while it's snapshotted.
+struct SnapshotCommitPoint<'a> {
+    commit: &'a mut dyn IndexCommitPoint,
+    snapshotted: bool,
+}
+
+impl<'a> IndexCommitPoint for SnapshotCommitPoint<'a> {
+    fn segments_file_name(&self) -> &str {
+        self.commit.segments_file_name()
+    }
+
+    fn file_names(&self) -> Result<&HashSet<String>> {
+        self.commit.file_names()
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        if !self.snapshotted {
+            self.commit.delete()?;
+        }
+        Ok(())
+    }
+
+    fn is_deleted(&self) -> bool {
+        self.commit.is_deleted()
+    }
+
+    fn segment_count(&self) -> usize {
+        self.commit.segment_count()
+    }
+
+    fn generation(&self) -> i64 {
+        self.commit.generation()
+    }
+
+    fn user_data(&self) -> &HashMap<String, String> {
+        self.commit.user_data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockCommit {
+        name: String,
+        files: HashSet<String>,
+        generation: i64,
+        deleted: bool,
+        user_data: HashMap<String, String>,
+    }
+
+    impl MockCommit {
+        fn new(generation: i64) -> MockCommit {
+            let name = format!("segments_{}", generation);
+            let mut files = HashSet::new();
+            files.insert(name.clone());
+            MockCommit {
+                name,
+                files,
+                generation,
+                deleted: false,
+                user_data: HashMap::new(),
+            }
+        }
+    }
+
+    impl IndexCommitPoint for MockCommit {
+        fn segments_file_name(&self) -> &str {
+            &self.name
+        }
+
+        fn file_names(&self) -> Result<&HashSet<String>> {
+            Ok(&self.files)
+        }
+
+        fn delete(&mut self) -> Result<()> {
+            self.deleted = true;
+            Ok(())
+        }
+
+        fn is_deleted(&self) -> bool {
+            self.deleted
+        }
+
+        fn segment_count(&self) -> usize {
+            1
+        }
+
+        fn generation(&self) -> i64 {
+            self.generation
+        }
+
+        fn user_data(&self) -> &HashMap<String, String> {
+            &self.user_data
+        }
+    }
+
+    fn as_points(commits: &mut [MockCommit]) -> Vec<&mut dyn IndexCommitPoint> {
+        commits
+            .iter_mut()
+            .map(|c| c as &mut dyn IndexCommitPoint)
+            .collect()
+    }
+
+    fn on_commit(policy: &dyn IndexDeletionPolicy, commits: &mut [MockCommit]) {
+        policy.on_commit(as_points(commits)).unwrap();
+    }
+
+    #[test]
+    fn test_keep_last_commits_deletion_policy() {
+        assert!(KeepLastCommitsDeletionPolicy::new(0).is_err());
+        let policy = KeepLastCommitsDeletionPolicy::new(2).unwrap();
+        let mut commits: Vec<MockCommit> = (1..5).map(MockCommit::new).collect();
+        on_commit(&policy, &mut commits[..1]);
+        assert!(!commits[0].is_deleted());
+        on_commit(&policy, &mut commits);
+        let deleted: Vec<bool> = commits.iter().map(|c| c.is_deleted()).collect();
+        assert_eq!(deleted, vec![true, true, false, false]);
+    }
+
+    #[test]
+    fn test_keep_young_commits_deletion_policy() {
+        let policy = KeepYoungCommitsDeletionPolicy::new(Duration::from_secs(60));
+        let start = Instant::now();
+        let mut commits: Vec<MockCommit> = (1..3).map(MockCommit::new).collect();
+        policy
+            .delete_expired(as_points(&mut commits), start)
+            .unwrap();
+        commits.push(MockCommit::new(3));
+        policy
+            .delete_expired(as_points(&mut commits), start + Duration::from_secs(30))
+            .unwrap();
+        assert!(commits.iter().all(|c| !c.is_deleted()));
+
+        policy
+            .delete_expired(as_points(&mut commits), start + Duration::from_secs(61))
+            .unwrap();
+        let deleted: Vec<bool> = commits.iter().map(|c| c.is_deleted()).collect();
+        assert_eq!(deleted, vec![true, true, false]);
+
+        // the last commit is kept whatever its age
+        let mut commits: Vec<MockCommit> = vec![MockCommit::new(3)];
+        policy
+            .delete_expired(as_points(&mut commits), start + Duration::from_secs(600))
+            .unwrap();
+        assert!(!commits[0].is_deleted());
+    }
+
+    #[test]
+    fn test_snapshot_deletion_policy() {
+        let policy = SnapshotDeletionPolicy::default();
+        assert!(policy.snapshot().is_err());
+
+        let mut commits = vec![MockCommit::new(1)];
+        on_commit(&policy, &mut commits);
+        let snapshot = policy.snapshot().unwrap();
+        assert_eq!(snapshot.generation(), 1);
+        assert_eq!(snapshot.segments_file_name(), "segments_1");
+        assert_eq!(policy.snapshot_count(), 1);
+
+        commits.push(MockCommit::new(2));
+        on_commit(&policy, &mut commits);
+        assert!(!commits[0].is_deleted());
+        assert!(!commits[1].is_deleted());
+
+        policy.release(&snapshot).unwrap();
+        assert!(policy.release(&snapshot).is_err());
+        assert_eq!(policy.snapshot_count(), 0);
+        on_commit(&policy, &mut commits);
+        assert!(commits[0].is_deleted());
+        assert!(!commits[1].is_deleted());
+    }
+}
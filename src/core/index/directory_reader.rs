@@ -100,6 +100,21 @@ where
         ))
     }
 
+    /// Opens a near real-time reader on the index of `writer`, seeing all the
+    /// changes made by the writer so far without needing a commit, the
+    /// flushed segments included. If `apply_all_deletes` is false, the
+    /// buffered deletes may not be visible yet, which makes opening cheaper.
+    ///
+    /// The segment readers are shared with the writer's reader pool, so
+    /// reopening the reader with `open_if_changed` only opens the new
+    /// segments.
+    pub fn open_from_writer(
+        writer: &IndexWriter<D, C, MS, MP>,
+        apply_all_deletes: bool,
+    ) -> Result<Self> {
+        writer.get_reader(apply_all_deletes, false)
+    }
+
     /// Used by near real-time searcher
     pub fn open_by_writer(
         writer: IndexWriter<D, C, MS, MP>,
@@ -252,17 +267,51 @@ where
         self.segment_infos.version
     }
 
+    pub fn directory(&self) -> &Arc<D> {
+        &self.directory
+    }
+
+    /// The readers of the segments of this reader, in order.
+    pub fn segment_readers(&self) -> &[Arc<SegmentReader<D, C>>] {
+        &self.readers
+    }
+
+    /// Opens a new reader if the index changed since this reader was opened,
+    /// or returns None if it didn't. A reader opened from a writer is
+    /// reopened from the writer, seeing its uncommitted changes, otherwise
+    /// the new reader is opened on `commit` or the last commit. The readers
+    /// of the unchanged segments are shared with this reader.
     pub fn open_if_changed(&self, commit: Option<&IndexCommit<D>>) -> Result<Option<Self>> {
         // If we were obtained by writer.getReader(), re-ask the
         // writer to get a new reader.
         if self.writer.is_some() {
-            self.open_from_writer(commit)
+            self.reopen_from_writer(commit)
         } else {
             self.do_open_no_writer(commit)
         }
     }
 
-    fn open_from_writer(&self, commit: Option<&IndexCommit<D>>) -> Result<Option<Self>> {
+    /// Opens a near real-time reader on the writer if the index changed since
+    /// this reader was opened, or None if it didn't.
+    ///
+    /// If this reader was opened from the same writer with the same
+    /// `apply_all_deletes`, this is `open_if_changed(None)`. Otherwise, e.g.
+    /// for a reader opened from a commit, a new reader is always opened from
+    /// the writer.
+    pub fn open_if_changed_from_writer(
+        &self,
+        writer: &IndexWriter<D, C, MS, MP>,
+        apply_all_deletes: bool,
+    ) -> Result<Option<Self>> {
+        match self.writer {
+            Some(ref w) if w.same_writer(writer) && self.apply_all_deletes == apply_all_deletes => {
+                self.reopen_from_writer(None)
+            }
+            _ => Ok(Some(Self::open_from_writer(writer, apply_all_deletes)?)),
+        }
+    }
+
+    fn reopen_from_writer(&self, commit: Option<&IndexCommit<D>>) -> Result<Option<Self>> {
         if commit.is_some() {
             Ok(Some(self.open_from_commit(commit)?))
         } else {
@@ -14,9 +14,11 @@
 use core::analysis::TokenStream;
 use core::codec::{
     Codec, DocValuesFormat, FieldInfosFormat, NormsFormat, PointsFormat, PointsWriter,
-    StoredFieldsFormat, StoredFieldsWriter, StoredFieldsWriterEnum,
+    Rucene54DocValuesFormat, StoredFieldsFormat, StoredFieldsWriter, StoredFieldsWriterEnum,
+    VectorFormat, VectorWriter, BINARY_AUTO_COMPRESSION, BINARY_COMPRESSION_KEY,
+    PER_FIELD_POSTING_FORMAT_KEY, PER_FIELD_POSTING_SUFFIX_KEY, PER_FIELD_VALUE_FORMAT_KEY,
 };
-use core::doc::FieldType;
+use core::doc::{token_count_field, FieldType, KnnVectorField, NumericDocValuesField};
 use core::index::doc_values_type::DocValuesType;
 use core::index::doc_values_writer::BinaryDocValuesWriter;
 use core::index::doc_values_writer::NumericDocValuesWriter;
@@ -32,6 +34,7 @@ use core::index::term_vector::TermVectorsConsumer;
 use core::index::terms_hash::{FreqProxTermsWriter, TermsHash};
 use core::index::terms_hash_per_field::{FreqProxTermsWriterPerField, TermsHashPerField};
 use core::index::thread_doc_writer::{DocState, DocumentsWriterPerThread};
+use core::index::vector_values_writer::VectorValuesWriter;
 use core::index::{
     FieldInfo, FieldInfosBuilder, FieldInvertState, FieldNumbersRef, Fieldable, IndexOptions,
     SegmentWriteState,
@@ -39,8 +42,6 @@ use core::index::{
 use core::store::{Directory, IOContext};
 use core::util::{BytesRef, Counter, DocId, VariantValue};
 
-use core::search::bm25_similarity::BM25Similarity;
-
 use error::{
     ErrorKind::{IllegalArgument, UnsupportedOperation},
     Result,
@@ -54,6 +55,9 @@ use std::ptr;
 
 const MAX_FIELD_COUNT: usize = 65536;
 
+/// The offset gap between the values of a multi-valued tokenized field.
+const MULTI_VALUED_OFFSET_GAP: usize = 1;
+
 pub trait DocConsumer<D: Directory, C: Codec> {
     fn process_document<F: Fieldable>(
         &mut self,
@@ -195,6 +199,34 @@ where
         Ok(())
     }
 
+    /// Writes all buffered vectors.
+    fn write_vectors<DW: Directory>(&mut self, state: &SegmentWriteState<D, DW, C>) -> Result<()> {
+        let mut vector_writer = None;
+        for per_field in &mut self.field_hash {
+            if let Some(ref writer) = per_field.vector_values_writer {
+                debug_assert!(per_field.field_info().has_vector_values());
+                if vector_writer.is_none() {
+                    // lazy init
+                    vector_writer = Some(
+                        state
+                            .segment_info
+                            .codec()
+                            .vector_format()
+                            .fields_writer(state)?,
+                    );
+                }
+                writer.flush(state, vector_writer.as_mut().unwrap())?;
+            } else {
+                debug_assert!(!per_field.field_info().has_vector_values());
+            }
+            per_field.vector_values_writer = None;
+        }
+        if let Some(ref mut writer) = vector_writer {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+
     /// Writes all buffered doc values (called from {@link #flush}).
     fn write_doc_values<DW: Directory>(
         &mut self,
@@ -293,6 +325,7 @@ where
         if state.field_infos.has_norms {
             let norms_format = state.segment_info.codec().norms_format();
             let mut norms_consumer = norms_format.norms_consumer(state)?;
+            let norms_encoding = self.doc_writer().doc_state.norms_encoding;
 
             for pf in &mut self.field_hash {
                 let name = pf.name.as_str();
@@ -300,6 +333,9 @@ where
                     if fi.omit_norms == false && fi.index_options != IndexOptions::Null {
                         debug_assert!(pf.norms.is_some());
                         if pf.norms.is_some() {
+                            if let Some(fi) = state.field_infos.field_info_by_name(name) {
+                                norms_encoding.put_attribute(fi);
+                            }
                             pf.norms.as_mut().unwrap().finish(max_doc);
                             pf.norms
                                 .as_mut()
@@ -381,6 +417,13 @@ where
             self.index_point(per_field.unwrap(), field, doc_state)?;
         }
 
+        if field.field_type().vector_dimension > 0 {
+            if per_field.is_none() {
+                per_field = Some(self.get_or_add_field(field.name(), field.field_type(), false)?);
+            }
+            self.index_vector(per_field.unwrap(), field, doc_state)?;
+        }
+
         Ok(field_count)
     }
 
@@ -444,6 +487,29 @@ where
             // also must set it in PerField.invert to allow for later downgrading of
             // the index options:
             fi.set_index_options(field_type.index_options);
+            if let Some(format) = field_type.postings_format {
+                fi.put_attribute(PER_FIELD_POSTING_FORMAT_KEY.to_string(), format.to_string());
+                fi.put_attribute(PER_FIELD_POSTING_SUFFIX_KEY.to_string(), "0".to_string());
+            }
+            // binary doc values are only compressed by `Rucene54DocValuesFormat`
+            let compressed = field_type.binary_doc_values_compression.is_some()
+                || field_type.binary_doc_values_auto_compression;
+            if let Some(format) = field_type.doc_values_format {
+                fi.put_attribute(PER_FIELD_VALUE_FORMAT_KEY.to_string(), format.to_string());
+            } else if compressed {
+                fi.put_attribute(
+                    PER_FIELD_VALUE_FORMAT_KEY.to_string(),
+                    Rucene54DocValuesFormat::NAME.to_string(),
+                );
+            }
+            if let Some(mode) = field_type.binary_doc_values_compression {
+                fi.put_attribute(BINARY_COMPRESSION_KEY.to_string(), mode.name().to_string());
+            } else if field_type.binary_doc_values_auto_compression {
+                fi.put_attribute(
+                    BINARY_COMPRESSION_KEY.to_string(),
+                    BINARY_AUTO_COMPRESSION.to_string(),
+                );
+            }
 
             let fp = PerField::new(&mut fi, invert, &mut self.terms_hash);
             self.field_hash.push(fp);
@@ -577,6 +643,16 @@ where
         Ok(())
     }
 
+    /// Indexes the number of tokens of an inverted field as the numeric doc
+    /// values of its token count field.
+    fn index_token_count(&mut self, field_idx: usize, doc_state: &DocState) -> Result<()> {
+        let name = token_count_field(&self.field_hash[field_idx].name);
+        let count = self.field_hash[field_idx].invert_state.length;
+        let field = NumericDocValuesField::new(&name, i64::from(count));
+        let idx = self.get_or_add_field(&name, field.field_type(), false)?;
+        self.index_doc_value(idx, DocValuesType::Numeric, &field, doc_state)
+    }
+
     /// Called from process_document to index one field's point
     fn index_point(
         &mut self,
@@ -620,6 +696,55 @@ where
                 &BytesRef::new(field.binary_value().unwrap()),
             )
     }
+
+    /// Called from process_document to index one field's vector
+    fn index_vector(
+        &mut self,
+        field_idx: usize,
+        field: &impl Fieldable,
+        doc_state: &DocState,
+    ) -> Result<()> {
+        let per_field = &mut self.field_hash[field_idx];
+        let dimension = field.field_type().vector_dimension;
+        let similarity_function = field.field_type().vector_similarity_function;
+
+        // Record the vector properties of this field; this setter will throw
+        // IllegalArgExc if they were already set to something different:
+        if !per_field.field_info().has_vector_values() {
+            self.field_infos
+                .global_field_numbers
+                .as_ref()
+                .set_vector_dimension_and_similarity_function(
+                    per_field.field_info().number,
+                    &per_field.field_info().name,
+                    dimension,
+                    similarity_function,
+                )?;
+            self.field_infos
+                .get_or_add(&per_field.field_info().name)?
+                .set_vector_dimension_and_similarity_function(dimension, similarity_function)?;
+        }
+
+        let bytes = match field.binary_value() {
+            Some(bytes) if bytes.len() == dimension as usize * 4 => bytes,
+            _ => bail!(IllegalArgument(format!(
+                "field '{}': vector value must be {} packed floats",
+                field.name(),
+                dimension
+            ))),
+        };
+        let mut vector = vec![0f32; dimension as usize];
+        KnnVectorField::unpack(bytes, &mut vector);
+
+        if per_field.vector_values_writer.is_none() {
+            per_field.vector_values_writer = Some(VectorValuesWriter::new(per_field.field_info()));
+        }
+        per_field
+            .vector_values_writer
+            .as_mut()
+            .unwrap()
+            .add_value(doc_state.doc_id, &vector)
+    }
 }
 
 impl<D, C, MS, MP> DocConsumer<D, C> for DefaultIndexingChain<D, C, MS, MP>
@@ -660,6 +785,12 @@ where
             let idx = self.fields[i];
             self.field_hash[idx].finish(doc_state)?;
         }
+        for i in 0..field_count {
+            let idx = self.fields[i];
+            if self.field_hash[idx].index_token_count {
+                self.index_token_count(idx, doc_state)?;
+            }
+        }
 
         self.finished_stored_fields()?;
 
@@ -682,6 +813,7 @@ where
 
         self.write_doc_values(state)?;
         self.write_points(state)?;
+        self.write_vectors(state)?;
 
         // it's possible all docs hit non-aborting exceptions...
         self.init_stored_fields_writer()?;
@@ -744,11 +876,15 @@ pub struct PerField<T: TermsHashPerField> {
     doc_values_writer: Option<DocValuesWriterEnum>,
     // Non-null if this field ever had points in this segment:
     point_values_writer: Option<PointValuesWriter>,
+    // Non-null if this field ever had vectors in this segment:
+    vector_values_writer: Option<VectorValuesWriter>,
     /// We use this to know when a PerField is seen for the
     /// first time in the current document
     field_gen: i64,
     norms: Option<NormValuesWriter>,
     invert: bool,
+    // whether the token count of the field is indexed for the current document
+    index_token_count: bool,
 }
 
 impl<T: TermsHashPerField> PerField<T> {
@@ -766,9 +902,11 @@ impl<T: TermsHashPerField> PerField<T> {
             term_hash_per_field,
             doc_values_writer: None,
             point_values_writer: None,
+            vector_values_writer: None,
             field_gen: -1,
             norms: None,
             invert,
+            index_token_count: false,
         };
 
         if invert {
@@ -811,7 +949,7 @@ impl<T: TermsHashPerField> PerField<T> {
             self.norms
                 .as_mut()
                 .unwrap()
-                .add_value(doc_id, BM25Similarity::compute_norm(&self.invert_state));
+                .add_value(doc_id, doc_state.norms_encoding.encode(&self.invert_state));
         }
 
         self.term_hash_per_field
@@ -849,7 +987,9 @@ impl<T: TermsHashPerField> PerField<T> {
             // First time we're seeing this field (indexed) in
             // this document:
             self.invert_state.reset();
+            self.index_token_count = false;
         }
+        self.index_token_count |= field.field_type().index_token_count;
 
         let index_options = field.field_type().index_options;
         index_chain
@@ -965,10 +1105,12 @@ impl<T: TermsHashPerField> PerField<T> {
             .get_position_increment() as i32;
         self.invert_state.offset += token_stream.offset_attribute_mut().end_offset();
 
-        //        if analyzed {
-        //            self.invert_state.position += doc_state.analyzer.get_position_increment_gap();
-        //            self.invert_state.offset += doc_state.analyzer.get_offset_gap();
-        //        }
+        // leave a gap between the values of a multi-valued field, the offset gap
+        // matches the separator the highlighter joins the stored values with
+        if field.field_type().tokenized {
+            self.invert_state.position += field.field_type().position_increment_gap as i32;
+            self.invert_state.offset += MULTI_VALUED_OFFSET_GAP;
+        }
 
         self.invert_state.boost *= field.boost();
 
@@ -245,7 +245,10 @@ impl<T: DocIdMergerSub> DocIdMerger for SortedDocIdMerger<T> {
                 self.queue.pop();
                 break;
             }
-            let top = self.queue.peek_mut().unwrap();
+            let mut top = self.queue.peek_mut().unwrap();
+            // deref the top mutably so that it's sifted down on drop, the
+            // mapped doc id is updated through the raw pointer
+            let top = &mut *top;
             let mapped_doc_id = top.sub().base().doc_map.get(doc_id)?;
             if mapped_doc_id == -1 {
                 // doc was deleted
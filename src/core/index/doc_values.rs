@@ -30,7 +30,6 @@ use core::util::{
 
 use error::Result;
 
-use std::rc::Rc;
 use std::sync::Arc;
 
 pub struct DocValues;
@@ -162,6 +161,13 @@ impl Bits for SortedNumericDocValuesBits {
 }
 
 /// maps per-segment ordinals to/from global ordinal space
+///
+/// The global ordinals are the ordinals of the union of the terms of the
+/// segments, so the values of different segments can be compared, counted
+/// or joined by ordinal instead of by term. Per segment the global ordinals
+/// are stored as packed deltas to the segment ordinals, or not at all when
+/// they are the same, which is likely for the largest segment since the
+/// segments are ordered by decreasing weight.
 // TODO: we could also have a utility method to merge Terms[] and use size() as a weight when we
 // need it TODO: use more efficient packed ints structures?
 // TODO: pull this out? it's pretty generic (maps between N ord()-enabled TermsEnums)
@@ -172,12 +178,39 @@ pub struct OrdinalMap {
     // globalOrd -> first segment container
     first_segments: PackedLongValues,
     // for every segment, segmentOrd -> globalOrd
-    segment_to_global_ords: Vec<Rc<LongValues>>,
+    segment_to_global_ords: Vec<Arc<dyn LongValues>>,
     // the map from/to segment ids
     segment_map: SegmentMap,
 }
 
 impl OrdinalMap {
+    /// Creates the map of the sorted doc values of the segments, in the order
+    /// of the leaves they are read from.
+    pub fn from_sorted_values(values: &[SortedDocValuesRef]) -> Result<Self> {
+        let mut terms = Vec::with_capacity(values.len());
+        let mut weights = Vec::with_capacity(values.len());
+        for v in values {
+            terms.push(Some(v.term_iterator()?));
+            weights.push(v.get_value_count());
+        }
+        Self::build(terms, weights, COMPACT)
+    }
+
+    /// Creates the map of the sorted set doc values of the segments, in the
+    /// order of the leaves they are read from.
+    pub fn from_sorted_set_values(values: &[SortedSetDocValuesRef]) -> Result<Self> {
+        let mut terms = Vec::with_capacity(values.len());
+        let mut weights = Vec::with_capacity(values.len());
+        for v in values {
+            terms.push(Some(v.term_iterator()?));
+            weights.push(v.get_value_count());
+        }
+        Self::build(terms, weights, COMPACT)
+    }
+
+    /// Creates the map of the terms of `subs`, the sub with the largest
+    /// weight, e.g. its number of terms, is the one whose ordinals are the
+    /// most likely to be the global ones.
     pub fn build<T: TermIterator>(
         subs: Vec<Option<T>>,
         weights: Vec<usize>,
@@ -262,14 +295,14 @@ impl OrdinalMap {
         let first_segments = first_segments_builder.build();
         let global_ord_deltas = global_ord_deltas_builder.build();
 
-        let mut segment_to_global_ords: Vec<Rc<LongValues>> = Vec::with_capacity(subs.len());
+        let mut segment_to_global_ords: Vec<Arc<dyn LongValues>> = Vec::with_capacity(subs.len());
         let mut i = 0;
         for mut d in ord_deltas {
             let deltas = d.build();
             if ord_delta_bits[i] == 0 {
                 // segment ords perfectly match global ordinals
                 // likely in case of low cardinalities and large segments
-                segment_to_global_ords.push(Rc::new(IdentityLongValues {}));
+                segment_to_global_ords.push(Arc::new(IdentityLongValues {}));
             } else {
                 let bits_required = if ord_delta_bits[i] < 0 {
                     64
@@ -296,12 +329,12 @@ impl OrdinalMap {
                         cnt += 1;
                     }
                     debug_assert_eq!(cnt as i64, size);
-                    segment_to_global_ords.push(Rc::new(MutableAsLongValues {
+                    segment_to_global_ords.push(Arc::new(MutableAsLongValues {
                         mutable: new_deltas,
                     }));
                 } else {
                     segment_to_global_ords
-                        .push(Rc::new(PackedLongValuesWrapper { values: deltas }));
+                        .push(Arc::new(PackedLongValuesWrapper { values: deltas }));
                 }
             }
             i += 1;
@@ -314,23 +347,28 @@ impl OrdinalMap {
         })
     }
 
+    /// The number of distinct terms over all the segments.
     pub fn value_count(&self) -> i64 {
         self.global_ord_deltas.size()
     }
 
+    /// The first segment having the term of `global_ord`.
     pub fn first_segment_number(&self, global_ord: i64) -> i32 {
         let new = self.first_segments.get64(global_ord).unwrap() as i32;
         let res = self.segment_map.new_to_old(new);
         res
     }
 
+    /// The ordinal of the term of `global_ord` in its first segment, see
+    /// `first_segment_number`, e.g. to look the term up.
     pub fn first_segment_ord(&self, global_ord: i64) -> i64 {
         global_ord - self.global_ord_deltas.get64(global_ord).unwrap()
     }
 
-    pub fn get_global_ords(&self, index: usize) -> Rc<LongValues> {
+    /// The global ordinals of the ordinals of the segment `index`.
+    pub fn get_global_ords(&self, index: usize) -> Arc<dyn LongValues> {
         let i = self.segment_map.old_to_new(index as i32) as usize;
-        Rc::clone(&self.segment_to_global_ords[i])
+        Arc::clone(&self.segment_to_global_ords[i])
     }
 }
 
@@ -16,11 +16,17 @@ use core::index::sorted_doc_values::TailoredSortedDocValues;
 use core::index::sorted_doc_values_term_iterator::SortedDocValuesTermIterator;
 use core::index::sorted_set_doc_values::{AddressedRandomAccessOrds, TabledRandomAccessOrds};
 use core::index::sorted_set_doc_values_term_iterator::SortedSetDocValuesTermIterator;
+use core::index::MultiSortedDocValues;
 use core::index::{EmptyTermIterator, OrdTermState, SeekStatus, TermIterator};
 use core::search::posting_iterator::EmptyPostingIterator;
 
 use error::Result;
 
+/// The terms dictionary of a sorted or sorted set doc values field, returned
+/// by their `term_iterator`.
+///
+/// The terms have no postings or statistics, `postings`, `doc_freq` and
+/// `total_term_freq` are unsupported.
 pub struct DocValuesTermIterator(DocValuesTermIteratorEnum);
 
 impl DocValuesTermIterator {
@@ -36,6 +42,9 @@ impl DocValuesTermIterator {
     pub fn sorted_set_table(d: SortedSetDocValuesTermIterator<TabledRandomAccessOrds>) -> Self {
         DocValuesTermIterator(DocValuesTermIteratorEnum::SortedSetTable(d))
     }
+    pub fn sorted_multi(d: SortedDocValuesTermIterator<MultiSortedDocValues>) -> Self {
+        DocValuesTermIterator(DocValuesTermIteratorEnum::SortedMulti(d))
+    }
     pub fn empty() -> Self {
         DocValuesTermIterator(DocValuesTermIteratorEnum::Empty(EmptyTermIterator {}))
     }
@@ -46,6 +55,7 @@ enum DocValuesTermIteratorEnum {
     Sorted(SortedDocValuesTermIterator<TailoredSortedDocValues>),
     SortedSetAddr(SortedSetDocValuesTermIterator<AddressedRandomAccessOrds>),
     SortedSetTable(SortedSetDocValuesTermIterator<TabledRandomAccessOrds>),
+    SortedMulti(SortedDocValuesTermIterator<MultiSortedDocValues>),
     Empty(EmptyTermIterator),
 }
 
@@ -58,6 +68,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.next(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.next(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.next(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.next(),
             DocValuesTermIteratorEnum::Empty(t) => t.next(),
         }
     }
@@ -68,6 +79,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_exact(text),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.seek_exact(text),
             DocValuesTermIteratorEnum::Empty(t) => t.seek_exact(text),
         }
     }
@@ -78,6 +90,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_ceil(text),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.seek_ceil(text),
             DocValuesTermIteratorEnum::Empty(t) => t.seek_ceil(text),
         }
     }
@@ -88,6 +101,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_exact_ord(ord),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.seek_exact_ord(ord),
             DocValuesTermIteratorEnum::Empty(t) => t.seek_exact_ord(ord),
         }
     }
@@ -98,6 +112,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.seek_exact_state(text, state),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.seek_exact_state(text, state),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.seek_exact_state(text, state),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.seek_exact_state(text, state),
             DocValuesTermIteratorEnum::Empty(_) => unreachable!(),
         }
     }
@@ -108,6 +123,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.term(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.term(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.term(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.term(),
             DocValuesTermIteratorEnum::Empty(t) => t.term(),
         }
     }
@@ -118,6 +134,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.ord(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.ord(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.ord(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.ord(),
             DocValuesTermIteratorEnum::Empty(t) => t.ord(),
         }
     }
@@ -128,6 +145,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.doc_freq(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.doc_freq(),
             DocValuesTermIteratorEnum::Empty(t) => t.doc_freq(),
         }
     }
@@ -138,6 +156,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.total_term_freq(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.total_term_freq(),
             DocValuesTermIteratorEnum::Empty(t) => t.total_term_freq(),
         }
     }
@@ -148,6 +167,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.postings(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.postings(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.postings(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.postings(),
             DocValuesTermIteratorEnum::Empty(t) => t.postings(),
         }
     }
@@ -158,6 +178,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.postings_with_flags(flags),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.postings_with_flags(flags),
             DocValuesTermIteratorEnum::Empty(t) => t.postings_with_flags(flags),
         }
     }
@@ -168,6 +189,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.term_state(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.term_state(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.term_state(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.term_state(),
             DocValuesTermIteratorEnum::Empty(_) => unimplemented!(),
         }
     }
@@ -178,6 +200,7 @@ impl TermIterator for DocValuesTermIterator {
             DocValuesTermIteratorEnum::Sorted(t) => t.is_empty(),
             DocValuesTermIteratorEnum::SortedSetAddr(t) => t.is_empty(),
             DocValuesTermIteratorEnum::SortedSetTable(t) => t.is_empty(),
+            DocValuesTermIteratorEnum::SortedMulti(t) => t.is_empty(),
             DocValuesTermIteratorEnum::Empty(t) => t.is_empty(),
         }
     }
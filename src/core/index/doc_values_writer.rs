@@ -15,32 +15,26 @@ use core::codec::{Codec, DocValuesConsumer, INT_BYTES, LONG_BYTES};
 use core::index::{
     numeric_doc_values::NumericDocValues, DocValuesType, FieldInfo, SegmentWriteState, Term,
 };
-use core::search::NO_MORE_DOCS;
 use core::store::{DataInput, DataOutput, Directory};
 use core::util::bit_set::{BitSet, FixedBitSet};
-use core::util::bit_util::BitsRequired;
 use core::util::bits::Bits;
 use core::util::byte_block_pool::{ByteBlockPool, DirectTrackingAllocator};
 use core::util::bytes_ref_hash::{self, BytesRefHash, DirectByteStartArray};
 use core::util::packed::{
     LongValuesIterator, PackedLongValues, PackedLongValuesBuilder, PackedLongValuesBuilderType,
-    PagedGrowableWriter, PagedMutableHugeWriter, PagedMutableWriter, DEFAULT_PAGE_SIZE,
+    PagedMutableWriter, DEFAULT_PAGE_SIZE,
 };
-use core::util::packed_misc::{COMPACT, FAST};
-use core::util::sorter::{Sorter, BINARY_SORT_THRESHOLD};
+use core::util::packed_misc::COMPACT;
 use core::util::BytesRef;
 use core::util::{
-    Counter, DocId, Numeric, PagedBytes, PagedBytesDataInput, ReusableIterator, VariantValue,
+    Accountable, Counter, DocId, Numeric, PagedBytes, PagedBytesDataInput, ReusableIterator,
+    VariantValue,
 };
 
-use error::{
-    ErrorKind::{IllegalArgument, IllegalState},
-    Result,
-};
+use error::{ErrorKind::IllegalArgument, Result};
 
-use std::cmp::Ordering;
-use std::collections::HashMap;
 use std::fmt;
+use std::mem;
 
 pub trait DocValuesWriter {
     //    fn as_numeric(&mut self) -> &mut NumericDocValuesWriter;
@@ -540,8 +534,8 @@ impl SortedDocValuesWriter {
             term_id = -term_id - 1;
         } else {
             // reserve additional space for each unique value:
-            // 1. when indexing, when hash is 50% full, rehash() suddenly needs 2*size ints.
-            //    TODO: can this same OOM happen in THPF?
+            // 1. when indexing, when hash is 50% full, rehash() suddenly needs 2*size ints. TODO:
+            //    can this same OOM happen in THPF?
             // 2. when flushing, we need 1 int per value (slot in the ordMap).
         }
 
@@ -772,8 +766,8 @@ impl SortedSetDocValuesWriter {
             term_id = -term_id - 1;
         } else {
             // reserve additional space for each unique value:
-            // 1. when indexing, when hash is 50% full, rehash() suddenly needs 2*size ints.
-            //    TODO: can this same OOM happen in THPF?
+            // 1. when indexing, when hash is 50% full, rehash() suddenly needs 2*size ints. TODO:
+            //    can this same OOM happen in THPF?
             // 2. when flushing, we need 1 int per value (slot in the ordMap).
         }
 
@@ -947,13 +941,8 @@ pub fn align_object_size(size: i32) -> i32 {
     size - size % NUM_BYTES_OBJECT_ALIGNMENT
 }
 
-pub trait SizeInBytesCalc {
-    fn value_size_in_bytes(&self) -> usize;
-
-    fn size_in_bytes(&self) -> usize;
-}
-
 /// An in-place update to a DocValues field.
+#[derive(Clone)]
 pub struct DocValuesUpdate {
     pub doc_values_type: DocValuesType,
     pub term: Term,
@@ -988,6 +977,16 @@ impl DocValuesUpdate {
 
         size_in_bytes
     }
+
+    pub fn size_in_bytes(&self) -> usize {
+        let value_size_in_bytes = match self.value {
+            VariantValue::Binary(ref b) => {
+                align_object_size(NUM_BYTES_OBJECT_HEADER + INT_BYTES) as usize + b.len()
+            }
+            _ => LONG_BYTES as usize,
+        };
+        self.base_size_in_bytes() + value_size_in_bytes
+    }
 }
 
 impl fmt::Display for DocValuesUpdate {
@@ -1000,680 +999,233 @@ impl fmt::Display for DocValuesUpdate {
     }
 }
 
-pub struct BinaryDocValuesUpdate {
-    update: DocValuesUpdate,
-}
-
-impl BinaryDocValuesUpdate {
-    pub fn new(term: Term, field: String, value: VariantValue) -> BinaryDocValuesUpdate {
-        BinaryDocValuesUpdate {
-            update: DocValuesUpdate::new(DocValuesType::Binary, term, field, value),
-        }
-    }
-}
-
-impl SizeInBytesCalc for BinaryDocValuesUpdate {
-    fn value_size_in_bytes(&self) -> usize {
-        debug_assert!(self.update.value.get_binary().is_some());
-        let raw_value_size_in_bytes = align_object_size(NUM_BYTES_OBJECT_HEADER + INT_BYTES) * 2
-            + 2 * INT_BYTES
-            + NUM_BYTES_OBJECT_REF;
-
-        raw_value_size_in_bytes as usize + self.update.value.get_binary().as_ref().unwrap().len()
-    }
-
-    fn size_in_bytes(&self) -> usize {
-        self.update.base_size_in_bytes() + self.value_size_in_bytes()
-    }
-}
-
-pub struct NumericDocValuesUpdate {
-    update: DocValuesUpdate,
-}
-
-impl NumericDocValuesUpdate {
-    pub fn new(term: Term, field: String, value: VariantValue) -> NumericDocValuesUpdate {
-        NumericDocValuesUpdate {
-            update: DocValuesUpdate::new(DocValuesType::Numeric, term, field, value),
-        }
-    }
-}
-
-impl SizeInBytesCalc for NumericDocValuesUpdate {
-    fn value_size_in_bytes(&self) -> usize {
-        LONG_BYTES as usize
-    }
-
-    fn size_in_bytes(&self) -> usize {
-        self.update.base_size_in_bytes() + self.value_size_in_bytes()
-    }
-}
-
-pub struct DocValuesFieldUpdatesValue {
-    doc_values_type: DocValuesType,
-    numeric_value: Option<Numeric>,
-    binary_value: Option<BytesRef>,
-}
-
-/// An iterator over documents and their updated values. Only documents with
-/// updates are returned by this iterator, and the documents are returned in
-/// increasing order.
-pub trait DocValuesFieldUpdatesIterator {
-    /// Returns the next document which has an update, or
-    /// {@link DocIdSetIterator#NO_MORE_DOCS} if there are no more documents to return.
-    fn next_doc(&mut self) -> Result<DocId>;
-
-    /// Returns the current document this iterator is on.
-    fn doc(&self) -> DocId;
-
-    /// Returns the value of the document returned from {@link #nextDoc()}. A
-    /// {@code null} value means that it was unset for this document.
-    fn value(&self) -> DocValuesFieldUpdatesValue;
-
-    /// Reset the iterator's state. Should be called before {@link #nextDoc()} and {@link #value()}.
-    fn reset(&mut self);
-}
-
-pub const PAGE_SIZE: usize = 1024;
-
-/// Returns the estimated capacity of a {@link PagedGrowableWriter} given the actual
-/// number of stored elements.
-pub fn estimate_capacity(size: usize) -> usize {
-    let extra = if size % PAGE_SIZE == 0 { 0 } else { 1 };
-
-    (size / PAGE_SIZE + extra as usize) * PAGE_SIZE
-}
-
-pub struct Container {
-    numeric_dv_updates: HashMap<String, NumericDocValuesFieldUpdates>,
-    binary_dv_updates: HashMap<String, BinaryDocValuesFieldUpdates>,
-}
-
-impl Container {
-    pub fn new() -> Container {
-        Container {
-            numeric_dv_updates: HashMap::new(),
-            binary_dv_updates: HashMap::new(),
+/// The updates of a DocValues field resolved to the documents of a segment.
+///
+/// The updates are appended to arrays, the numeric values as longs and the
+/// binary values concatenated, and `finish` sorts them by doc, keeping the
+/// last update of each document.
+pub struct DocValuesFieldUpdates {
+    pub doc_values_type: DocValuesType,
+    docs: Vec<DocId>,
+    values: UpdatedValues,
+    // whether `docs` is strictly increasing
+    finished: bool,
+}
+
+enum UpdatedValues {
+    Numeric(Vec<i64>),
+    // the end offset of each value in `bytes`
+    Binary { ends: Vec<usize>, bytes: Vec<u8> },
+}
+
+impl DocValuesFieldUpdates {
+    pub fn new(doc_values_type: DocValuesType) -> DocValuesFieldUpdates {
+        let values = match doc_values_type {
+            DocValuesType::Binary => UpdatedValues::Binary {
+                ends: vec![],
+                bytes: vec![],
+            },
+            _ => UpdatedValues::Numeric(vec![]),
+        };
+        DocValuesFieldUpdates {
+            doc_values_type,
+            docs: vec![],
+            values,
+            finished: true,
         }
     }
 
-    pub fn any(&self) -> bool {
-        for updates in self.numeric_dv_updates.values() {
-            if updates.any() {
-                return true;
+    /// Add an update to a document, replacing any previous update of it once
+    /// `finish` is called. The value must be a long for numeric updates and
+    /// bytes for binary ones.
+    pub fn add(&mut self, doc: DocId, value: VariantValue) {
+        match (&mut self.values, value) {
+            (UpdatedValues::Numeric(values), VariantValue::Long(v)) => values.push(v),
+            (UpdatedValues::Binary { ends, bytes }, VariantValue::Binary(v)) => {
+                bytes.extend_from_slice(&v);
+                ends.push(bytes.len());
             }
-        }
-
-        for updates in self.binary_dv_updates.values() {
-            if updates.any() {
-                return true;
+            (_, value) => {
+                debug_assert!(false, "invalid update {:?}", value);
+                return;
             }
         }
-
-        false
+        if self.docs.last().map_or(false, |&last| last >= doc) {
+            self.finished = false;
+        }
+        self.docs.push(doc);
     }
 
-    pub fn size(&self) -> usize {
-        self.numeric_dv_updates.len() + self.binary_dv_updates.len()
-    }
-
-    pub fn get_updates(
-        &self,
-        field: &str,
-        doc_values_type: &DocValuesType,
-    ) -> Result<&DocValuesFieldUpdates> {
-        match doc_values_type {
-            DocValuesType::Numeric => Ok(self
-                .numeric_dv_updates
-                .get(field)
-                .as_ref()
-                .unwrap()
-                .as_base()),
-            DocValuesType::Binary => Ok(self
-                .binary_dv_updates
-                .get(field)
-                .as_ref()
-                .unwrap()
-                .as_base()),
-            _ => bail!(IllegalArgument(format!(
-                "unsupported type: {:?}",
-                doc_values_type
-            ))),
+    /// Sorts the updates by doc, only keeping the last update of each doc.
+    pub fn finish(&mut self) {
+        if self.finished {
+            return;
         }
-    }
+        // the sort is stable, so the last update of a doc comes last
+        let mut order: Vec<usize> = (0..self.docs.len()).collect();
+        order.sort_by_key(|&i| self.docs[i]);
+        order.dedup_by(|next, last| {
+            let dup = self.docs[*next] == self.docs[*last];
+            if dup {
+                *last = *next;
+            }
+            dup
+        });
 
-    pub fn new_updates(
-        &mut self,
-        field: &str,
-        doc_values_type: &DocValuesType,
-        max_doc: i32,
-    ) -> Result<&DocValuesFieldUpdates> {
-        match doc_values_type {
-            DocValuesType::Numeric => {
-                debug_assert!(self.numeric_dv_updates.get(field).is_none());
-                let numeric_updates = NumericDocValuesFieldUpdates::new(field, max_doc);
-                self.numeric_dv_updates
-                    .insert(field.to_string(), numeric_updates);
-                Ok(self
-                    .numeric_dv_updates
-                    .get(field)
-                    .as_ref()
-                    .unwrap()
-                    .as_base())
+        let docs = order.iter().map(|&i| self.docs[i]).collect();
+        let values = match self.values {
+            UpdatedValues::Numeric(ref values) => {
+                UpdatedValues::Numeric(order.iter().map(|&i| values[i]).collect())
             }
-            DocValuesType::Binary => {
-                debug_assert!(self.binary_dv_updates.get(field).is_none());
-                let binary_updates = BinaryDocValuesFieldUpdates::new(field, max_doc);
-                self.binary_dv_updates
-                    .insert(field.to_string(), binary_updates);
-                Ok(self
-                    .binary_dv_updates
-                    .get(field)
-                    .as_ref()
-                    .unwrap()
-                    .as_base())
+            UpdatedValues::Binary {
+                ref ends,
+                ref bytes,
+            } => {
+                let mut sorted_ends = Vec::with_capacity(order.len());
+                let mut sorted_bytes = Vec::with_capacity(bytes.len());
+                for &i in &order {
+                    let start = if i == 0 { 0 } else { ends[i - 1] };
+                    sorted_bytes.extend_from_slice(&bytes[start..ends[i]]);
+                    sorted_ends.push(sorted_bytes.len());
+                }
+                sorted_bytes.shrink_to_fit();
+                UpdatedValues::Binary {
+                    ends: sorted_ends,
+                    bytes: sorted_bytes,
+                }
+            }
+        };
+        self.docs = docs;
+        self.values = values;
+        self.finished = true;
+    }
+
+    fn value(&self, index: usize) -> VariantValue {
+        match self.values {
+            UpdatedValues::Numeric(ref values) => VariantValue::Long(values[index]),
+            UpdatedValues::Binary {
+                ref ends,
+                ref bytes,
+            } => {
+                let start = if index == 0 { 0 } else { ends[index - 1] };
+                VariantValue::Binary(bytes[start..ends[index]].to_vec())
             }
-            _ => bail!(IllegalArgument(format!(
-                "unsupported type: {:?}",
-                doc_values_type
-            ))),
-        }
-    }
-}
-
-pub trait DocValuesFieldUpdates {
-    /// Add an update to a document. For unsetting a value you should pass
-    /// {@code null}.
-    fn add(&mut self, doc: DocId, value: &DocValuesFieldUpdatesValue) -> Result<()>;
-
-    /// Returns an {@link Iterator} over the updated documents and their values.
-    fn iterator(&mut self) -> Result<Box<dyn DocValuesFieldUpdatesIterator>>;
-
-    /// Returns true if this instance contains any updates.
-    fn any(&self) -> bool;
-
-    /// Merge with another {@link DocValuesFieldUpdates}. This is called for a
-    /// segment which received updates while it was being merged. The given updates
-    /// should override whatever updates are in that instance.
-    fn merge(&mut self, other: &DocValuesFieldUpdates) -> Result<()>;
-
-    fn doc_values_type(&self) -> &DocValuesType;
-
-    fn as_numeric(&self) -> &NumericDocValuesFieldUpdates {
-        unimplemented!()
-    }
-
-    fn as_binary(&self) -> &BinaryDocValuesFieldUpdates {
-        unimplemented!()
-    }
-
-    fn as_base(&self) -> &DocValuesFieldUpdates;
-}
-
-pub struct NumericDocValuesFieldUpdatesIterator {
-    updates: *const NumericDocValuesFieldUpdates,
-    // long so we don't overflow if size == Integer.MAX_VALUE
-    idx: DocId,
-    doc: DocId,
-    value: Option<Numeric>,
-}
-
-impl NumericDocValuesFieldUpdatesIterator {
-    pub fn new(updates: &NumericDocValuesFieldUpdates) -> NumericDocValuesFieldUpdatesIterator {
-        NumericDocValuesFieldUpdatesIterator {
-            updates,
-            idx: 0,
-            doc: -1,
-            value: None,
-        }
-    }
-}
-
-impl DocValuesFieldUpdatesIterator for NumericDocValuesFieldUpdatesIterator {
-    fn next_doc(&mut self) -> Result<DocId> {
-        let updates = unsafe { &(*self.updates) };
-        if self.idx > updates.size as DocId {
-            self.value = None;
-            self.doc = NO_MORE_DOCS;
-            return Ok(self.doc);
-        }
-
-        self.doc = updates.docs.get(self.idx)? as DocId;
-        self.idx += 1;
-        while self.idx < updates.size as DocId && updates.docs.get(self.idx)? == self.doc as i64 {
-            self.idx += 1;
         }
-
-        // idx points to the "next" element
-        self.value = Some(Numeric::Long(updates.values.get(self.idx - 1)?));
-        Ok(self.doc)
     }
 
-    fn doc(&self) -> DocId {
-        self.doc
+    /// Returns the value of the last update of `doc`, if any.
+    pub fn get(&self, doc: DocId) -> Option<VariantValue> {
+        debug_assert!(self.finished);
+        self.docs
+            .binary_search(&doc)
+            .ok()
+            .map(|index| self.value(index))
     }
 
-    fn value(&self) -> DocValuesFieldUpdatesValue {
-        DocValuesFieldUpdatesValue {
-            doc_values_type: DocValuesType::Numeric,
-            numeric_value: self.value.clone(),
-            binary_value: None,
-        }
+    /// Returns the number of updated documents.
+    pub fn size(&self) -> usize {
+        debug_assert!(self.finished);
+        self.docs.len()
     }
 
-    fn reset(&mut self) {
-        self.idx = 0;
-        self.doc = -1;
-        self.value = None;
+    /// Returns true if this instance contains any updates.
+    pub fn any(&self) -> bool {
+        !self.docs.is_empty()
     }
-}
 
-pub struct NumericDocValuesInPlaceMergeSorter {
-    updates: *mut NumericDocValuesFieldUpdates,
-    pivot_index: i32,
-}
-
-impl NumericDocValuesInPlaceMergeSorter {
-    pub fn new(updates: &mut NumericDocValuesFieldUpdates) -> NumericDocValuesInPlaceMergeSorter {
-        NumericDocValuesInPlaceMergeSorter {
-            updates,
-            pivot_index: 0,
-        }
+    /// Returns the updated documents and their values, by increasing doc id.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (DocId, VariantValue)> + 'a {
+        debug_assert!(self.finished);
+        self.docs
+            .iter()
+            .enumerate()
+            .map(move |(index, &doc)| (doc, self.value(index)))
     }
 
-    fn merge_sort(&mut self, from: i32, to: i32) {
-        if to - from < BINARY_SORT_THRESHOLD as i32 {
-            self.binary_sort(from, to);
-        } else {
-            let mid = (from + to) / 2;
-            self.merge_sort(from, mid);
-            self.merge_sort(mid, to);
-            self.merge_in_place(from, mid, to);
+    /// Merge with another `DocValuesFieldUpdates`, the updates of `other`
+    /// override the ones of this instance.
+    pub fn merge(&mut self, other: &DocValuesFieldUpdates) {
+        debug_assert_eq!(self.doc_values_type, other.doc_values_type);
+        for (doc, value) in other.iter() {
+            self.add(doc, value);
         }
+        self.finish();
     }
 }
 
-impl Sorter for NumericDocValuesInPlaceMergeSorter {
-    fn compare(&mut self, i: i32, j: i32) -> Ordering {
-        let updates = unsafe { &(*self.updates) };
-        let x = updates.docs.get(i).unwrap();
-        let y = updates.docs.get(j).unwrap();
-        x.cmp(&y)
-    }
-
-    fn swap(&mut self, i: i32, j: i32) {
-        let updates = unsafe { &mut (*self.updates) };
-        let temp_doci = updates.docs.get(i).unwrap();
-        let temp_docj = updates.docs.get(j).unwrap();
-        updates.docs.set(i as usize, temp_docj);
-        updates.docs.set(j as usize, temp_doci);
-
-        let temp_valuei = updates.values.get(i).unwrap();
-        let temp_valuej = updates.values.get(j).unwrap();
-        updates.values.set(i as usize, temp_valuej);
-        updates.values.set(j as usize, temp_valuei);
-    }
-
-    fn sort(&mut self, from: i32, to: i32) {
-        assert!(from <= to);
-        self.merge_sort(from, to);
-    }
-
-    fn set_pivot(&mut self, i: i32) {
-        self.pivot_index = i;
-    }
-
-    fn compare_pivot(&mut self, j: i32) -> Ordering {
-        let pivot_index = self.pivot_index;
-        self.compare(pivot_index, j)
+impl Accountable for DocValuesFieldUpdates {
+    fn ram_bytes_used(&self) -> usize {
+        let values = match self.values {
+            UpdatedValues::Numeric(ref values) => values.capacity() * LONG_BYTES as usize,
+            UpdatedValues::Binary {
+                ref ends,
+                ref bytes,
+            } => ends.capacity() * mem::size_of::<usize>() + bytes.capacity(),
+        };
+        mem::size_of::<Self>() + self.docs.capacity() * INT_BYTES as usize + values
     }
 }
 
-pub struct NumericDocValuesFieldUpdates {
-    pub field: String,
-    pub doc_values_type: DocValuesType,
-    pub bits_per_value: i32,
-    pub size: usize,
-    pub docs: PagedMutableHugeWriter,
-    pub values: PagedGrowableWriter,
-}
-
-impl NumericDocValuesFieldUpdates {
-    pub fn new(field: &str, max_doc: i32) -> NumericDocValuesFieldUpdates {
-        let bits_per_value = (max_doc - 1).bits_required() as i32;
-        let docs = PagedMutableHugeWriter::new(1, PAGE_SIZE, bits_per_value, COMPACT);
-        let values = PagedGrowableWriter::new(1, PAGE_SIZE, 1, FAST);
-
-        NumericDocValuesFieldUpdates {
-            field: field.to_string(),
-            doc_values_type: DocValuesType::Numeric,
-            bits_per_value,
-            size: 0,
-            docs,
-            values,
-        }
-    }
+/// Iterates over the values of a field rewritten with its updates applied.
+pub struct UpdatedValuesIter<'a, T: Clone> {
+    values: &'a [T],
+    upto: usize,
 }
 
-impl DocValuesFieldUpdates for NumericDocValuesFieldUpdates {
-    fn add(&mut self, doc: DocId, value: &DocValuesFieldUpdatesValue) -> Result<()> {
-        // TODO: if the Sorter interface changes to take long indexes, we can remove that limitation
-        if self.size == i32::max_value() as usize {
-            bail!(IllegalState(
-                "cannot support more than Integer.MAX_VALUE doc/value entries".into()
-            ))
-        }
-
-        debug_assert!(
-            value.doc_values_type == DocValuesType::Numeric && value.numeric_value.is_some()
-        );
-        let val = value.numeric_value.as_ref().unwrap();
-
-        // grow the structures to have room for more elements
-        if self.docs.size() == self.size {
-            self.docs = self.docs.grow_by_size(self.size + 1);
-            self.values = self.values.grow_by_size(self.size + 1);
-        }
-
-        self.docs.set(self.size, doc as i64);
-        self.values.set(self.size, val.long_value());
-        self.size += 1;
-
-        Ok(())
-    }
-
-    fn iterator(&mut self) -> Result<Box<dyn DocValuesFieldUpdatesIterator>> {
-        let mut sorter = NumericDocValuesInPlaceMergeSorter::new(self);
-        sorter.sort(0, self.size as i32);
-
-        Ok(Box::new(NumericDocValuesFieldUpdatesIterator::new(self)))
-    }
-
-    fn any(&self) -> bool {
-        self.size > 0
-    }
-
-    fn merge(&mut self, other: &DocValuesFieldUpdates) -> Result<()> {
-        debug_assert!(other.doc_values_type() == self.doc_values_type());
-        let other_updates = other.as_numeric();
-        if other_updates.size + self.size > i32::max_value() as usize {
-            bail!(IllegalState(format!(
-                "cannot support more than Integer.MAX_VALUE doc/value entries; size={} \
-                 other.size={}",
-                self.size, other_updates.size
-            )));
-        }
-        self.docs = self.docs.grow_by_size(self.size + other_updates.size);
-        self.values = self.values.grow_by_size(self.size + other_updates.size);
-        for i in 0..other_updates.size {
-            self.docs
-                .set(self.size, other_updates.docs.get(i as DocId)?);
-            self.values
-                .set(self.size, other_updates.values.get(i as DocId)?);
-            self.size += 1;
-        }
-
-        Ok(())
-    }
-
-    fn doc_values_type(&self) -> &DocValuesType {
-        &self.doc_values_type
-    }
-
-    fn as_numeric(&self) -> &NumericDocValuesFieldUpdates {
-        self
-    }
-
-    fn as_base(&self) -> &DocValuesFieldUpdates {
-        self
+impl<'a, T: Clone> UpdatedValuesIter<'a, T> {
+    pub fn new(values: &'a [T]) -> Self {
+        UpdatedValuesIter { values, upto: 0 }
     }
 }
 
-pub struct BinaryDocValuesFieldUpdatesIterator {
-    updates: *mut BinaryDocValuesFieldUpdates,
-    // long so we don't overflow if size == Integer.MAX_VALUE
-    idx: DocId,
-    doc: DocId,
-    offset: i64,
-    length: i64,
-    value: BytesRef,
-}
+impl<'a, T: Clone> Iterator for UpdatedValuesIter<'a, T> {
+    type Item = Result<T>;
 
-impl BinaryDocValuesFieldUpdatesIterator {
-    pub fn new(updates: &mut BinaryDocValuesFieldUpdates) -> BinaryDocValuesFieldUpdatesIterator {
-        BinaryDocValuesFieldUpdatesIterator {
-            updates,
-            idx: 0,
-            doc: -1,
-            offset: -1,
-            length: 0,
-            value: BytesRef::new(&mut updates.values),
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.upto < self.values.len() {
+            self.upto += 1;
+            Some(Ok(self.values[self.upto - 1].clone()))
+        } else {
+            None
         }
     }
 }
 
-impl DocValuesFieldUpdatesIterator for BinaryDocValuesFieldUpdatesIterator {
-    fn next_doc(&mut self) -> Result<DocId> {
-        let updates = unsafe { &mut (*self.updates) };
-        if self.idx > updates.size as DocId {
-            self.offset = -1;
-            self.doc = NO_MORE_DOCS;
-            return Ok(self.doc);
-        }
-
-        self.doc = updates.docs.get(self.idx)? as DocId;
-        self.idx += 1;
-        while self.idx < updates.size as DocId && updates.docs.get(self.idx)? == self.doc as i64 {
-            self.idx += 1;
-        }
-
-        // idx points to the "next" element
-        let prev_idx = self.idx - 1;
-        // cannot change 'value' here because nextDoc is called before the
-        // value is used, and it's a waste to clone the BytesRef when we
-        // obtain the value
-        self.offset = updates.offsets.get(prev_idx)?;
-        self.length = updates.lengths.get(prev_idx)?;
-        Ok(self.doc)
-    }
-
-    fn doc(&self) -> DocId {
-        self.doc
-    }
-
-    fn value(&self) -> DocValuesFieldUpdatesValue {
-        DocValuesFieldUpdatesValue {
-            doc_values_type: DocValuesType::Binary,
-            numeric_value: None,
-            binary_value: Some(self.value),
-        }
-    }
-
+impl<'a, T: Clone> ReusableIterator for UpdatedValuesIter<'a, T> {
     fn reset(&mut self) {
-        self.idx = 0;
-        self.doc = -1;
-        self.offset = -1;
+        self.upto = 0;
     }
 }
 
-pub struct BinaryDocValuesInPlaceMergeSorter {
-    updates: *mut BinaryDocValuesFieldUpdates,
-    pivot_index: i32,
+/// Iterates over the binary values of a field rewritten with its updates
+/// applied, an empty value stands for a document without value.
+pub struct UpdatedBytesIter<'a> {
+    values: &'a [Vec<u8>],
+    upto: usize,
 }
 
-impl BinaryDocValuesInPlaceMergeSorter {
-    pub fn new(updates: &mut BinaryDocValuesFieldUpdates) -> BinaryDocValuesInPlaceMergeSorter {
-        BinaryDocValuesInPlaceMergeSorter {
-            updates,
-            pivot_index: 0,
-        }
-    }
-
-    fn merge_sort(&mut self, from: i32, to: i32) {
-        if to - from < BINARY_SORT_THRESHOLD as i32 {
-            self.binary_sort(from, to);
-        } else {
-            let mid = (from + to) / 2;
-            self.merge_sort(from, mid);
-            self.merge_sort(mid, to);
-            self.merge_in_place(from, mid, to);
-        }
+impl<'a> UpdatedBytesIter<'a> {
+    pub fn new(values: &'a [Vec<u8>]) -> Self {
+        UpdatedBytesIter { values, upto: 0 }
     }
 }
 
-impl Sorter for BinaryDocValuesInPlaceMergeSorter {
-    fn compare(&mut self, i: i32, j: i32) -> Ordering {
-        let updates = unsafe { &(*self.updates) };
-        let x = updates.docs.get(i).unwrap();
-        let y = updates.docs.get(j).unwrap();
-        x.cmp(&y)
-    }
-
-    fn swap(&mut self, i: i32, j: i32) {
-        let updates = unsafe { &mut (*self.updates) };
-        let temp_doci = updates.docs.get(i).unwrap();
-        let temp_docj = updates.docs.get(j).unwrap();
-        updates.docs.set(i as usize, temp_docj);
-        updates.docs.set(j as usize, temp_doci);
-
-        let temp_offseti = updates.offsets.get(i).unwrap();
-        let temp_offsetj = updates.offsets.get(j).unwrap();
-        updates.offsets.set(i as usize, temp_offsetj);
-        updates.offsets.set(j as usize, temp_offseti);
-
-        let temp_lengthi = updates.lengths.get(i).unwrap();
-        let temp_lengthj = updates.lengths.get(j).unwrap();
-        updates.lengths.set(i as usize, temp_lengthj);
-        updates.lengths.set(j as usize, temp_lengthi);
-    }
-
-    fn sort(&mut self, from: i32, to: i32) {
-        assert!(from <= to);
-        self.merge_sort(from, to);
-    }
-
-    fn set_pivot(&mut self, i: i32) {
-        self.pivot_index = i;
-    }
-
-    fn compare_pivot(&mut self, j: i32) -> Ordering {
-        let pivot_index = self.pivot_index;
-        self.compare(pivot_index, j)
-    }
-}
+impl<'a> Iterator for UpdatedBytesIter<'a> {
+    type Item = Result<BytesRef>;
 
-pub struct BinaryDocValuesFieldUpdates {
-    pub field: String,
-    pub doc_values_type: DocValuesType,
-    pub bits_per_value: i32,
-    pub size: usize,
-    pub docs: PagedMutableHugeWriter,
-    pub offsets: PagedGrowableWriter,
-    pub lengths: PagedGrowableWriter,
-    pub values: Vec<u8>,
-}
-
-impl BinaryDocValuesFieldUpdates {
-    pub fn new(field: &str, max_doc: i32) -> BinaryDocValuesFieldUpdates {
-        let bits_per_value = (max_doc - 1).bits_required() as i32;
-        let docs = PagedMutableHugeWriter::new(1, PAGE_SIZE, bits_per_value, COMPACT);
-        let offsets = PagedGrowableWriter::new(1, PAGE_SIZE, 1, FAST);
-        let lengths = PagedGrowableWriter::new(1, PAGE_SIZE, 1, FAST);
-
-        BinaryDocValuesFieldUpdates {
-            field: field.to_string(),
-            doc_values_type: DocValuesType::Binary,
-            bits_per_value,
-            size: 0,
-            docs,
-            offsets,
-            lengths,
-            values: vec![],
+    fn next(&mut self) -> Option<Result<BytesRef>> {
+        if self.upto < self.values.len() {
+            self.upto += 1;
+            Some(Ok(BytesRef::new(&self.values[self.upto - 1])))
+        } else {
+            None
         }
     }
 }
 
-impl DocValuesFieldUpdates for BinaryDocValuesFieldUpdates {
-    fn add(&mut self, doc: DocId, value: &DocValuesFieldUpdatesValue) -> Result<()> {
-        // TODO: if the Sorter interface changes to take long indexes, we can remove that limitation
-        if self.size == i32::max_value() as usize {
-            bail!(IllegalState(
-                "cannot support more than Integer.MAX_VALUE doc/value entries".into()
-            ));
-        }
-        debug_assert!(
-            value.doc_values_type == DocValuesType::Binary && value.binary_value.is_some()
-        );
-        let val = value.binary_value.as_ref().unwrap();
-
-        // grow the structures to have room for more elements
-        if self.docs.size() == self.size {
-            self.docs = self.docs.grow_by_size(self.size + 1);
-            self.offsets = self.offsets.grow_by_size(self.size + 1);
-            self.lengths = self.lengths.grow_by_size(self.size + 1);
-        }
-
-        self.docs.set(self.size, doc as i64);
-        self.offsets.set(self.size, self.values.len() as i64);
-        self.lengths.set(self.size, val.len() as i64);
-        self.values.extend_from_slice(val.bytes());
-        self.size += 1;
-
-        Ok(())
-    }
-
-    fn iterator(&mut self) -> Result<Box<dyn DocValuesFieldUpdatesIterator>> {
-        let mut sorter = BinaryDocValuesInPlaceMergeSorter::new(self);
-        sorter.sort(0, self.size as i32);
-
-        Ok(Box::new(BinaryDocValuesFieldUpdatesIterator::new(self)))
-    }
-
-    fn any(&self) -> bool {
-        self.size > 0
-    }
-
-    fn merge(&mut self, other: &DocValuesFieldUpdates) -> Result<()> {
-        debug_assert!(other.doc_values_type() == self.doc_values_type());
-        let other_updates = other.as_binary();
-        if other_updates.size + self.size > i32::max_value() as usize {
-            bail!(IllegalState(format!(
-                "cannot support more than Integer.MAX_VALUE doc/value entries; size={} \
-                 other.size={}",
-                self.size, other_updates.size
-            )));
-        }
-
-        let new_size = self.size + other_updates.size;
-        self.docs = self.docs.grow_by_size(new_size);
-        self.offsets = self.offsets.grow_by_size(new_size);
-        self.lengths = self.lengths.grow_by_size(new_size);
-        for i in 0..other_updates.size {
-            self.docs
-                .set(self.size, other_updates.docs.get(i as DocId)?);
-            self.offsets.set(
-                self.size,
-                self.values.len() as i64 + other_updates.offsets.get(i as DocId)?,
-            );
-            self.lengths
-                .set(self.size, other_updates.lengths.get(i as DocId)?);
-            self.size += 1;
-        }
-
-        self.values.extend_from_slice(&other_updates.values[..]);
-        Ok(())
-    }
-
-    fn doc_values_type(&self) -> &DocValuesType {
-        &self.doc_values_type
-    }
-
-    fn as_binary(&self) -> &BinaryDocValuesFieldUpdates {
-        self
-    }
-
-    fn as_base(&self) -> &DocValuesFieldUpdates {
-        self
+impl<'a> ReusableIterator for UpdatedBytesIter<'a> {
+    fn reset(&mut self) {
+        self.upto = 0;
     }
 }
 
@@ -1757,3 +1309,58 @@ impl<'a> ReusableIterator for BinaryBytesIterator<'a> {
         self.value.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field_updates_keep_last_update_of_each_doc() {
+        let mut updates = DocValuesFieldUpdates::new(DocValuesType::Numeric);
+        for &(doc, value) in &[(3, 30), (1, 10), (7, 70), (3, 300), (0, 0), (7, 700)] {
+            updates.add(doc, VariantValue::Long(value));
+        }
+        updates.finish();
+        assert_eq!(updates.size(), 4);
+        let values: Vec<(DocId, i64)> = updates
+            .iter()
+            .map(|(doc, value)| (doc, value.get_long().unwrap()))
+            .collect();
+        assert_eq!(values, vec![(0, 0), (1, 10), (3, 300), (7, 700)]);
+        assert_eq!(updates.get(3).and_then(|v| v.get_long()), Some(300));
+        assert!(updates.get(2).is_none());
+
+        let mut other = DocValuesFieldUpdates::new(DocValuesType::Numeric);
+        other.add(1, VariantValue::Long(-1));
+        other.add(5, VariantValue::Long(-5));
+        other.finish();
+        updates.merge(&other);
+        let docs: Vec<DocId> = updates.iter().map(|(doc, _)| doc).collect();
+        assert_eq!(docs, vec![0, 1, 3, 5, 7]);
+        assert_eq!(updates.get(1).and_then(|v| v.get_long()), Some(-1));
+    }
+
+    #[test]
+    fn test_binary_field_updates() {
+        let mut updates = DocValuesFieldUpdates::new(DocValuesType::Binary);
+        updates.add(4, VariantValue::Binary(b"four".to_vec()));
+        updates.add(2, VariantValue::Binary(b"two".to_vec()));
+        updates.add(4, VariantValue::Binary(vec![]));
+        updates.add(9, VariantValue::Binary(b"nine".to_vec()));
+        let unfinished = updates.ram_bytes_used();
+        updates.finish();
+        let values: Vec<(DocId, Vec<u8>)> = updates
+            .iter()
+            .map(|(doc, value)| match value {
+                VariantValue::Binary(v) => (doc, v),
+                v => panic!("unexpected value {:?}", v),
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![(2, b"two".to_vec()), (4, vec![]), (9, b"nine".to_vec())]
+        );
+        // the bytes of the replaced update are released
+        assert!(updates.ram_bytes_used() < unfinished);
+    }
+}
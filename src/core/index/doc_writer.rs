@@ -12,10 +12,11 @@
 // limitations under the License.
 
 use core::codec::Codec;
+use core::index::doc_values_writer::DocValuesUpdate;
 use core::index::doc_writer_delete_queue::DocumentsWriterDeleteQueue;
 use core::index::doc_writer_flush_queue::DocumentsWriterFlushQueue;
-use core::index::flush_control::DocumentsWriterFlushControl;
-use core::index::flush_policy::FlushByRamOrCountsPolicy;
+use core::index::flush_control::{DocumentsWriterFlushControl, IndexingThreadStats};
+use core::index::flush_policy::FlushPolicy;
 use core::index::index_writer::{IndexWriter, IndexWriterInner};
 use core::index::index_writer_config::IndexWriterConfig;
 use core::index::thread_doc_writer::{
@@ -24,7 +25,7 @@ use core::index::thread_doc_writer::{
 use core::index::{Fieldable, SegmentInfo, Term};
 use core::search::Query;
 use core::store::{Directory, LockValidatingDirectoryWrapper};
-use core::util::Volatile;
+use core::util::{Accountable, RamUsage, Volatile};
 use error::{ErrorKind::AlreadyClosed, Result};
 
 use crossbeam::queue::SegQueue;
@@ -116,7 +117,7 @@ pub(crate) struct DocumentsWriter<
     // committed. See also self.any_change() & self.flush_all_threads.
     pending_changes_in_current_full_flush: Volatile<bool>,
     pub per_thread_pool: DocumentsWriterPerThreadPool<D, C, MS, MP>,
-    pub flush_policy: Arc<FlushByRamOrCountsPolicy<C, MS, MP>>,
+    pub flush_policy: Arc<dyn FlushPolicy>,
     flush_control: DocumentsWriterFlushControl<D, C, MS, MP>,
     config: Arc<IndexWriterConfig<C, MS, MP>>,
     writer: Weak<IndexWriterInner<D, C, MS, MP>>,
@@ -138,7 +139,7 @@ where
         directory_orig: Arc<D>,
         directory: Arc<LockValidatingDirectoryWrapper<D>>,
     ) -> Self {
-        let flush_policy = Arc::new(FlushByRamOrCountsPolicy::new(Arc::clone(&config)));
+        let flush_policy = config.flush_policy();
         let flush_control =
             DocumentsWriterFlushControl::new(Arc::clone(&config), Arc::clone(&flush_policy));
         DocumentsWriter {
@@ -150,7 +151,7 @@ where
             delete_queue: Arc::new(DocumentsWriterDeleteQueue::default()),
             ticket_queue: DocumentsWriterFlushQueue::new(),
             pending_changes_in_current_full_flush: Volatile::new(false),
-            per_thread_pool: DocumentsWriterPerThreadPool::new(),
+            per_thread_pool: DocumentsWriterPerThreadPool::new(config.max_thread_states()),
             flush_policy,
             flush_control,
             config,
@@ -205,21 +206,20 @@ where
         let has_events = self.pre_update()?;
 
         let per_thread = self.flush_control.obtain_and_lock()?;
-        let (seq_no, flush_dwpt) = {
-            let l = match per_thread.lock.try_lock() {
-                Ok(g) => g,
-                Err(e) => {
-                    bail!(
-                        "update document try obtain per_thread.lock failed by: {:?}",
-                        e
-                    );
-                }
-            };
-            let per_thread_mut = per_thread.thread_state_mut(&l);
-            self.do_update_documents(per_thread_mut, docs, del_term)?
+        let res = match per_thread.lock.try_lock() {
+            Ok(l) => {
+                let per_thread_mut = per_thread.thread_state_mut(&l);
+                self.do_update_documents(per_thread_mut, docs, del_term)
+            }
+            Err(e) => Err(format!(
+                "update document try obtain per_thread.lock failed by: {:?}",
+                e
+            )
+            .into()),
         };
-
+        // release the state even on failure, the pool may be bounded
         self.per_thread_pool.release(per_thread);
+        let (seq_no, flush_dwpt) = res?;
 
         let has_event = self.post_update(flush_dwpt, has_events)?;
         Ok((seq_no, has_event))
@@ -284,20 +284,20 @@ where
         let mut has_event = self.pre_update()?;
 
         let per_thread = self.flush_control.obtain_and_lock()?;
-        let (seq_no, flush_dwpt) = {
-            let guard = match per_thread.lock.try_lock() {
-                Ok(g) => g,
-                Err(e) => {
-                    bail!(
-                        "update document try obtain per_thread.state failed by: {:?}",
-                        e
-                    );
-                }
-            };
-            let per_thread_mut = per_thread.thread_state_mut(&guard);
-            self.do_update_document(per_thread_mut, doc, del_term)?
+        let res = match per_thread.lock.try_lock() {
+            Ok(guard) => {
+                let per_thread_mut = per_thread.thread_state_mut(&guard);
+                self.do_update_document(per_thread_mut, doc, del_term)
+            }
+            Err(e) => Err(format!(
+                "update document try obtain per_thread.state failed by: {:?}",
+                e
+            )
+            .into()),
         };
+        // release the state even on failure, the pool may be bounded
         self.per_thread_pool.release(per_thread);
+        let (seq_no, flush_dwpt) = res?;
 
         has_event = self.post_update(flush_dwpt, has_event)?;
 
@@ -447,10 +447,26 @@ where
         Ok((seq_no, applyed))
     }
 
+    pub fn update_doc_values(&self, updates: Vec<DocValuesUpdate>) -> Result<(u64, bool)> {
+        debug_assert!(self.inited);
+        let l = self.lock.lock()?;
+        let doc_writer_mut = unsafe { self.doc_writer_mut(&l) };
+        let seq_no = self.delete_queue.add_doc_values_updates(updates)?;
+        doc_writer_mut.flush_control.do_on_delete();
+
+        let applyed = self.apply_all_deletes_local()?;
+        doc_writer_mut.last_seq_no = max(self.last_seq_no, seq_no);
+        Ok((seq_no, applyed))
+    }
+
     pub fn num_docs(&self) -> u32 {
         self.num_docs_in_ram.load(Ordering::Acquire)
     }
 
+    pub fn indexing_stats(&self) -> IndexingThreadStats {
+        self.flush_control.stats()
+    }
+
     fn apply_all_deletes_local(&self) -> Result<bool> {
         if self.flush_control.get_and_reset_apply_all_deletes() {
             if !self.flush_control.is_full_flush() {
@@ -526,6 +542,8 @@ where
 
         self.flush_control.abort_pending_flushes();
         self.flush_control.wait_for_flush()?;
+        // let new thread states be created again once all the old ones are aborted
+        self.per_thread_pool.clear_abort();
         Ok(aborted_doc_count)
     }
 
@@ -588,6 +606,12 @@ where
             has_events = true;
             if !self.apply_all_deletes_local()? {
                 debug!("DW: force apply deletes");
+                self.config.info("DW", || {
+                    format!(
+                        "force apply deletes, delete_bytes_used={}",
+                        self.flush_control.delete_bytes_used()
+                    )
+                });
                 self.put_event(WriterEvent::ApplyDeletes);
             }
         }
@@ -674,6 +698,13 @@ where
     /// is called after this method, to release the flush lock in DWFlushControl
     pub fn flush_all_threads(&self) -> Result<(bool, u64)> {
         debug!("DW: start full flush");
+        self.config.info("DW", || {
+            format!(
+                "start full flush: ram_docs={} bytes_used={}",
+                self.num_docs(),
+                self.ram_bytes_used()
+            )
+        });
 
         let (seq_no, flushing_queue) = {
             let _l = self.lock.lock()?;
@@ -715,6 +746,8 @@ where
             thread::current().name(),
             success
         );
+        self.config
+            .info("DW", || format!("finish full flush, success={}", success));
         if success {
             self.flush_control.finish_full_flush();
         } else {
@@ -729,6 +762,22 @@ where
     }
 }
 
+impl<D, C, MS, MP> Accountable for DocumentsWriter<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    fn ram_bytes_used(&self) -> usize {
+        self.flush_control.ram_bytes_used()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.flush_control.child_resources()
+    }
+}
+
 impl<D, C, MS, MP> Drop for DocumentsWriter<D, C, MS, MP>
 where
     D: Directory + Send + Sync + 'static,
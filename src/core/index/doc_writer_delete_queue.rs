@@ -13,9 +13,10 @@
 
 use core::codec::Codec;
 use core::index::bufferd_updates::{BufferedUpdates, FrozenBufferedUpdates};
+use core::index::doc_values_writer::DocValuesUpdate;
 use core::index::Term;
 use core::search::{Query, NO_MORE_DOCS};
-use core::util::DocId;
+use core::util::{Accountable, DocId};
 
 use error::Result;
 
@@ -133,6 +134,13 @@ impl<C: Codec> DocumentsWriterDeleteQueue<C> {
         Ok(seq_no)
     }
 
+    pub fn add_doc_values_updates(&self, updates: Vec<DocValuesUpdate>) -> Result<u64> {
+        let node = Arc::new(DeleteListNode::new(DeleteNode::DocValuesUpdates(updates)));
+        let seq_no = self.add_node(node)?;
+        self.try_apply_global_slice()?;
+        Ok(seq_no)
+    }
+
     /// invariant for document update
     pub fn add_term_to_slice(&self, term: Term, slice: &mut DeleteSlice<C>) -> Result<u64> {
         let del_node = Arc::new(DeleteListNode::new(DeleteNode::Term(term)));
@@ -170,14 +178,6 @@ impl<C: Codec> DocumentsWriterDeleteQueue<C> {
             || !tail_guard.next.load(Ordering::Acquire).is_null()
     }
 
-    pub fn ram_bytes_used(&self) -> usize {
-        self.global_data
-            .lock()
-            .unwrap()
-            .global_buffered_updates
-            .bytes_used()
-    }
-
     pub fn next_sequence_number(&self) -> u64 {
         let no = self.next_seq_no.fetch_add(1, Ordering::AcqRel);
         debug_assert!(no < self.max_seq_no.get());
@@ -280,10 +280,22 @@ enum DeleteNode<C: Codec> {
     Term(Term),
     TermArray(Vec<Term>),
     QueryArray(Vec<Arc<dyn Query<C>>>),
+    DocValuesUpdates(Vec<DocValuesUpdate>),
     None,
     // used for sentinel head
 }
 
+/// The deletes and updates buffered in the global slice, not yet frozen.
+impl<C: Codec> Accountable for DocumentsWriterDeleteQueue<C> {
+    fn ram_bytes_used(&self) -> usize {
+        self.global_data
+            .lock()
+            .unwrap()
+            .global_buffered_updates
+            .bytes_used()
+    }
+}
+
 impl<C: Codec> DeleteNode<C> {
     fn apply(&self, buffered_deletes: &mut BufferedUpdates<C>, doc_id_upto: DocId) {
         match self {
@@ -300,6 +312,11 @@ impl<C: Codec> DeleteNode<C> {
                     buffered_deletes.add_query(Arc::clone(q), doc_id_upto);
                 }
             }
+            DeleteNode::DocValuesUpdates(updates) => {
+                for u in updates {
+                    buffered_deletes.add_doc_values_update(u.clone(), doc_id_upto);
+                }
+            }
             DeleteNode::None => {
                 unreachable!();
             }
@@ -27,10 +27,12 @@ use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
 
 // use core::attribute::{OffsetAttribute, PayloadAttribute, PositionIncrementAttribute};
-use core::codec::{PER_FIELD_POSTING_FORMAT_KEY, PER_FIELD_POSTING_SUFFIX_KEY};
+use core::codec::{
+    PER_FIELD_POSTING_FORMAT_KEY, PER_FIELD_POSTING_SUFFIX_KEY, PER_FIELD_VALUE_SUFFIX_KEY,
+};
 use core::index::point_values;
 use core::index::term::*;
-use core::index::{DocValuesType, IndexOptions};
+use core::index::{DocValuesType, IndexOptions, VectorSimilarityFunction, MAX_VECTOR_DIMENSIONS};
 
 fn variant_eq<T>(a: &T, b: &T) -> bool {
     discriminant(a) == discriminant(b)
@@ -49,6 +51,8 @@ pub struct FieldInfo {
     pub dv_gen: i64,
     pub point_dimension_count: u32,
     pub point_num_bytes: u32,
+    pub vector_dimension: u32,
+    pub vector_similarity_function: VectorSimilarityFunction,
 }
 
 impl Serialize for FieldInfo {
@@ -56,7 +60,7 @@ impl Serialize for FieldInfo {
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_struct("FieldInfo", 11)?;
+        let mut s = serializer.serialize_struct("FieldInfo", 13)?;
         s.serialize_field("name", &self.name)?;
         s.serialize_field("number", &self.number)?;
         s.serialize_field("doc_values_type", &self.doc_values_type)?;
@@ -68,6 +72,11 @@ impl Serialize for FieldInfo {
         s.serialize_field("dv_gen", &self.dv_gen)?;
         s.serialize_field("point_dimension_count", &self.point_dimension_count)?;
         s.serialize_field("point_num_bytes", &self.point_num_bytes)?;
+        s.serialize_field("vector_dimension", &self.vector_dimension)?;
+        s.serialize_field(
+            "vector_similarity_function",
+            &self.vector_similarity_function,
+        )?;
         s.end()
     }
 }
@@ -99,6 +108,8 @@ impl FieldInfo {
             dv_gen,
             point_dimension_count,
             point_num_bytes,
+            vector_dimension: 0,
+            vector_similarity_function: VectorSimilarityFunction::default(),
         };
 
         info.check_consistency()?;
@@ -155,6 +166,13 @@ impl FieldInfo {
             )));
         }
 
+        if self.vector_dimension > MAX_VECTOR_DIMENSIONS {
+            bail!(IllegalState(format!(
+                "Illegal State: vector dimension of field '{}' must be <= {}, got {}",
+                &self.name, MAX_VECTOR_DIMENSIONS, self.vector_dimension
+            )));
+        }
+
         if self.dv_gen != -1
             && match self.doc_values_type {
                 DocValuesType::Null => true,
@@ -229,6 +247,30 @@ impl FieldInfo {
         Ok(())
     }
 
+    pub fn set_vector_dimension_and_similarity_function(
+        &mut self,
+        dimension: u32,
+        similarity_function: VectorSimilarityFunction,
+    ) -> Result<()> {
+        if self.vector_dimension == 0 && dimension > 0 {
+            self.vector_dimension = dimension;
+            self.vector_similarity_function = similarity_function;
+        } else if dimension != 0
+            && (self.vector_dimension != dimension
+                || self.vector_similarity_function != similarity_function)
+        {
+            bail!(IllegalArgument(format!(
+                "cannot change field '{}' vector dimension or similarity function",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn has_vector_values(&self) -> bool {
+        self.vector_dimension > 0
+    }
+
     pub fn has_norms(&self) -> bool {
         match self.index_options {
             IndexOptions::Null => false,
@@ -376,6 +418,7 @@ pub struct FieldInfos {
     pub has_norms: bool,
     pub has_doc_values: bool,
     pub has_point_values: bool,
+    pub has_vector_values: bool,
 
     pub by_number: BTreeMap<u32, FieldInfoRef>,
     pub by_name: HashMap<String, FieldInfoRef>,
@@ -386,7 +429,7 @@ impl Serialize for FieldInfos {
     where
         S: Serializer,
     {
-        let mut s = serializer.serialize_struct("FieldInfos", 9)?;
+        let mut s = serializer.serialize_struct("FieldInfos", 10)?;
         s.serialize_field("has_freq", &self.has_freq)?;
         s.serialize_field("has_prox", &self.has_prox)?;
         s.serialize_field("has_payloads", &self.has_payloads)?;
@@ -395,6 +438,7 @@ impl Serialize for FieldInfos {
         s.serialize_field("has_norms", &self.has_norms)?;
         s.serialize_field("has_doc_values", &self.has_doc_values)?;
         s.serialize_field("has_point_values", &self.has_point_values)?;
+        s.serialize_field("has_vector_values", &self.has_vector_values)?;
 
         let fields: HashMap<&String, &FieldInfo> = self
             .by_name
@@ -426,6 +470,7 @@ impl FieldInfos {
         let mut has_norms = false;
         let mut has_doc_values = false;
         let mut has_point_values = false;
+        let mut has_vector_values = false;
 
         let mut by_number: BTreeMap<u32, FieldInfoRef> = BTreeMap::new();
         let mut by_name: HashMap<String, FieldInfoRef> = HashMap::new();
@@ -433,8 +478,10 @@ impl FieldInfos {
 
         let mut infos = infos;
         for info in &mut infos {
-            if info.index_options != IndexOptions::Null {
-                // force put attr...
+            if info.index_options != IndexOptions::Null
+                && info.attribute(PER_FIELD_POSTING_FORMAT_KEY).is_none()
+            {
+                // fields without an explicit postings format use the default one
                 info.put_attribute(
                     PER_FIELD_POSTING_FORMAT_KEY.to_string(),
                     "Lucene50".to_string(),
@@ -459,6 +506,7 @@ impl FieldInfos {
                 has_doc_values |= !info.doc_values_type.null();
                 has_payloads |= info.has_store_payloads;
                 has_point_values |= info.point_dimension_count != 0;
+                has_vector_values |= info.has_vector_values();
             }
 
             if let Some(previous) = by_number.insert(number, info.clone()) {
@@ -486,6 +534,7 @@ impl FieldInfos {
             has_norms,
             has_doc_values,
             has_point_values,
+            has_vector_values,
             by_number,
             by_name,
         })
@@ -579,7 +628,37 @@ impl<T: AsRef<FieldNumbers>> FieldInfosBuilder<T> {
             fi.doc_values_type,
             fi.point_dimension_count,
             fi.point_num_bytes,
-        )
+        )?;
+        if fi.has_vector_values() {
+            let new_fi = self.by_name.get_mut(&fi.name).unwrap();
+            self.global_field_numbers
+                .as_ref()
+                .set_vector_dimension_and_similarity_function(
+                    new_fi.number,
+                    &fi.name,
+                    fi.vector_dimension,
+                    fi.vector_similarity_function,
+                )?;
+            new_fi.set_vector_dimension_and_similarity_function(
+                fi.vector_dimension,
+                fi.vector_similarity_function,
+            )?;
+        }
+        // keep the per field formats the field was written with, except for
+        // the suffix of the doc values format which is assigned again when
+        // the values are written
+        let attributes: Vec<(String, String)> = fi
+            .attributes
+            .read()?
+            .iter()
+            .filter(|(k, _)| k.as_str() != PER_FIELD_VALUE_SUFFIX_KEY)
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        let new_fi = &self.by_name[&fi.name];
+        for (k, v) in attributes {
+            new_fi.put_attribute(k, v);
+        }
+        Ok(())
     }
 
     fn add_or_update_internal(
@@ -690,10 +769,48 @@ impl FieldNumbers {
         )
     }
 
+    /// Registers the field of an existing segment, returns its global number.
+    pub fn add_field_info(&self, fi: &FieldInfo) -> Result<u32> {
+        let mut inner = self.inner.lock().unwrap();
+        let number = inner.add_or_get(
+            &fi.name,
+            fi.number,
+            fi.doc_values_type,
+            fi.point_dimension_count,
+            fi.point_num_bytes,
+        )?;
+        if fi.has_vector_values() {
+            inner.set_vector_dimension_and_similarity_function(
+                number,
+                &fi.name,
+                fi.vector_dimension,
+                fi.vector_similarity_function,
+            )?;
+        }
+        Ok(number)
+    }
+
     pub fn clear(&self) {
         self.inner.lock().unwrap().clear();
     }
 
+    /// Returns the global number of the field, if it is known.
+    pub fn field_number(&self, field_name: &str) -> Option<u32> {
+        self.inner
+            .lock()
+            .unwrap()
+            .name_to_number
+            .get(field_name)
+            .cloned()
+    }
+
+    /// Returns true if the field is known with the given doc values type.
+    pub fn contains(&self, field_name: &str, dv_type: DocValuesType) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.name_to_number.contains_key(field_name)
+            && inner.doc_values_type.get(field_name) == Some(&dv_type)
+    }
+
     pub fn set_doc_values_type(
         &self,
         number: u32,
@@ -719,6 +836,24 @@ impl FieldNumbers {
             .set_dimensions(number, name, dimension_count, num_bytes)
     }
 
+    pub fn set_vector_dimension_and_similarity_function(
+        &self,
+        number: u32,
+        name: &str,
+        dimension: u32,
+        similarity_function: VectorSimilarityFunction,
+    ) -> Result<()> {
+        self.inner
+            .lock()
+            .unwrap()
+            .set_vector_dimension_and_similarity_function(
+                number,
+                name,
+                dimension,
+                similarity_function,
+            )
+    }
+
     fn verify_consistent(&self, number: u32, name: &str, dv_type: DocValuesType) -> Result<()> {
         self.inner.lock()?.verify_consistent(number, name, dv_type)
     }
@@ -732,6 +867,7 @@ struct FieldNumbersInner {
     // sessions:
     doc_values_type: HashMap<String, DocValuesType>,
     dimensions: HashMap<String, FieldDimensions>,
+    vector_props: HashMap<String, (u32, VectorSimilarityFunction)>,
     // TODO: we should similarly catch an attempt to turn
     // norms back on after they were already ommitted; today
     // we silently discard the norm but this is badly trappy
@@ -745,6 +881,7 @@ impl FieldNumbersInner {
             name_to_number: HashMap::new(),
             doc_values_type: HashMap::new(),
             dimensions: HashMap::new(),
+            vector_props: HashMap::new(),
             lowest_unassigned_field_number: 0,
         }
     }
@@ -912,6 +1049,7 @@ impl FieldNumbersInner {
         self.name_to_number.clear();
         self.doc_values_type.clear();
         self.dimensions.clear();
+        self.vector_props.clear();
     }
 
     pub fn set_doc_values_type(
@@ -949,6 +1087,48 @@ impl FieldNumbersInner {
         );
         Ok(())
     }
+
+    pub fn set_vector_dimension_and_similarity_function(
+        &mut self,
+        number: u32,
+        name: &str,
+        dimension: u32,
+        similarity_function: VectorSimilarityFunction,
+    ) -> Result<()> {
+        if dimension == 0 || dimension > MAX_VECTOR_DIMENSIONS {
+            bail!(IllegalArgument(format!(
+                "vector dimension must be > 0 and <= {}, got {}",
+                MAX_VECTOR_DIMENSIONS, dimension
+            )));
+        }
+        if self.number_to_name.contains_key(&number) && self.number_to_name[&number] != name {
+            bail!(IllegalArgument(format!(
+                "field number {} is already mapped to field name '{}' not '{}'",
+                number, self.number_to_name[&number], name
+            )));
+        }
+        match self.vector_props.entry(name.to_string()) {
+            Entry::Occupied(entry) => {
+                let (dim, func) = *entry.get();
+                if dim != dimension {
+                    bail!(IllegalArgument(format!(
+                        "cannot change vector dimension from {} to {} for field '{}'",
+                        dim, dimension, name
+                    )));
+                }
+                if func != similarity_function {
+                    bail!(IllegalArgument(format!(
+                        "cannot change vector similarity function from {:?} to {:?} for field '{}'",
+                        func, similarity_function, name
+                    )));
+                }
+            }
+            Entry::Vacant(entry) => {
+                entry.insert((dimension, similarity_function));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl AsRef<FieldNumbers> for FieldNumbers {
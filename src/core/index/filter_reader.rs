@@ -0,0 +1,664 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecTVFields, DocValuesProducerRef, VectorReaderRef};
+use core::doc::{Document, DocumentStoredFieldVisitor};
+use core::index::merge_policy::MergePolicy;
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{
+    BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, IndexReader, LeafReader, LeafReaderContext,
+    NumericDocValues, NumericDocValuesRef, SearchLeafReader, SegmentReader, SortedDocValuesRef,
+    SortedNumericDocValuesRef, SortedSetDocValuesRef, StandardDirectoryReader, Status,
+    StoredFieldVisitor, VectorValues,
+};
+use core::search::sort::Sort;
+use core::store::Directory;
+use core::util::external::deferred::Deferred;
+use core::util::{BitsRef, DocId};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Decides what a `FilterLeafReader` exposes of the reader it wraps.
+///
+/// The default methods expose everything, implement only the ones to
+/// filter on.
+pub trait LeafReaderFilter: Send + Sync {
+    /// Whether `field` is visible through the filtered reader. A hidden
+    /// field has no `FieldInfo`, terms, doc values, norms or stored values,
+    /// as if no doc had it.
+    fn accept_field(&self, _field: &str) -> bool {
+        true
+    }
+
+    /// Returns the live docs of the filtered reader, or None to keep the
+    /// ones of `reader`. The returned bits replace the ones of `reader`, so
+    /// they should be combined with `reader.live_docs()` to only hide more
+    /// docs. Called once, when the reader is wrapped.
+    fn live_docs<R: LeafReader + ?Sized>(&self, _reader: &R) -> Result<Option<BitsRef>> {
+        Ok(None)
+    }
+}
+
+/// A `LeafReader` decorating another one with a `LeafReaderFilter`, to hide
+/// fields or docs of a segment, and otherwise delegating to the wrapped
+/// reader.
+///
+/// The filtered reader keeps the codec readers of the wrapped one, so the
+/// fields are only hidden from the per field accessors: `fields()`,
+/// `term_vector` and `point_values` still see the hidden fields, but the
+/// queries go through `field_info` and `terms` and so don't match them.
+/// The `core_cache_key` is the one of the wrapped reader, so a query cache
+/// must not be shared with unfiltered readers when fields are hidden.
+pub struct FilterLeafReader<T: LeafReader, F: LeafReaderFilter> {
+    reader: Arc<T>,
+    filter: Arc<F>,
+    field_infos: Arc<FieldInfos>,
+    live_docs: BitsRef,
+    num_docs: i32,
+}
+
+impl<T: LeafReader, F: LeafReaderFilter> FilterLeafReader<T, F> {
+    pub fn new(reader: Arc<T>, filter: Arc<F>) -> Result<Self> {
+        let infos = reader.field_infos();
+        let field_infos = if infos.by_name.keys().all(|name| filter.accept_field(name)) {
+            reader.clone_field_infos()
+        } else {
+            let accepted = infos
+                .by_number
+                .values()
+                .filter(|info| filter.accept_field(&info.name))
+                .map(|info| info.as_ref().clone())
+                .collect();
+            Arc::new(FieldInfos::new(accepted)?)
+        };
+
+        let (live_docs, num_docs) = match filter.live_docs(reader.as_ref())? {
+            Some(live_docs) => {
+                let max_doc = reader.max_doc();
+                if live_docs.len() != max_doc as usize {
+                    bail!(IllegalArgument(format!(
+                        "the filtered live docs have {} bits, expected max_doc {}",
+                        live_docs.len(),
+                        max_doc
+                    )));
+                }
+                let mut num_docs = 0;
+                for doc in 0..max_doc as usize {
+                    if live_docs.get(doc)? {
+                        num_docs += 1;
+                    }
+                }
+                (live_docs, num_docs)
+            }
+            None => (reader.live_docs(), reader.num_docs()),
+        };
+
+        Ok(FilterLeafReader {
+            reader,
+            filter,
+            field_infos,
+            live_docs,
+            num_docs,
+        })
+    }
+
+    /// The wrapped reader.
+    pub fn delegate(&self) -> &Arc<T> {
+        &self.reader
+    }
+
+    pub fn filter(&self) -> &Arc<F> {
+        &self.filter
+    }
+
+    fn check_field(&self, field: &str) -> Result<()> {
+        if !self.filter.accept_field(field) {
+            bail!(IllegalArgument(format!(
+                "non-exist or filtered out field: {}",
+                field
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<T: LeafReader + 'static, F: LeafReaderFilter + 'static> LeafReader for FilterLeafReader<T, F> {
+    type Codec = T::Codec;
+    type FieldsProducer = T::FieldsProducer;
+    type TVFields = T::TVFields;
+    type TVReader = T::TVReader;
+    type StoredReader = T::StoredReader;
+    type NormsReader = T::NormsReader;
+    type PointsReader = T::PointsReader;
+
+    fn codec(&self) -> &Self::Codec {
+        self.reader.codec()
+    }
+
+    fn fields(&self) -> Result<Self::FieldsProducer> {
+        self.reader.fields()
+    }
+
+    fn name(&self) -> &str {
+        self.reader.name()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<<Self::FieldsProducer as Fields>::Terms>> {
+        if self.filter.accept_field(field) {
+            self.reader.terms(field)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn term_vector(&self, doc_id: DocId) -> Result<Option<Self::TVFields>> {
+        self.reader.term_vector(doc_id)
+    }
+
+    fn field_term_vector(
+        &self,
+        doc_id: DocId,
+        field: &str,
+    ) -> Result<Option<<Self::TVFields as Fields>::Terms>> {
+        if self.filter.accept_field(field) {
+            self.reader.field_term_vector(doc_id, field)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn document(&self, doc_id: DocId, visitor: &mut dyn StoredFieldVisitor) -> Result<()> {
+        let mut visitor = FilterStoredFieldVisitor {
+            filter: self.filter.as_ref(),
+            visitor,
+        };
+        self.reader.document(doc_id, &mut visitor)
+    }
+
+    fn live_docs(&self) -> BitsRef {
+        Arc::clone(&self.live_docs)
+    }
+
+    fn field_info(&self, field: &str) -> Option<&FieldInfo> {
+        self.field_infos.field_info_by_name(field)
+    }
+
+    fn field_infos(&self) -> &FieldInfos {
+        &self.field_infos
+    }
+
+    fn clone_field_infos(&self) -> Arc<FieldInfos> {
+        Arc::clone(&self.field_infos)
+    }
+
+    fn max_doc(&self) -> DocId {
+        self.reader.max_doc()
+    }
+
+    fn num_docs(&self) -> i32 {
+        self.num_docs
+    }
+
+    fn get_numeric_doc_values(&self, field: &str) -> Result<NumericDocValuesRef> {
+        self.check_field(field)?;
+        self.reader.get_numeric_doc_values(field)
+    }
+
+    fn get_binary_doc_values(&self, field: &str) -> Result<BinaryDocValuesRef> {
+        self.check_field(field)?;
+        self.reader.get_binary_doc_values(field)
+    }
+
+    fn get_sorted_doc_values(&self, field: &str) -> Result<SortedDocValuesRef> {
+        self.check_field(field)?;
+        self.reader.get_sorted_doc_values(field)
+    }
+
+    fn get_sorted_numeric_doc_values(&self, field: &str) -> Result<SortedNumericDocValuesRef> {
+        self.check_field(field)?;
+        self.reader.get_sorted_numeric_doc_values(field)
+    }
+
+    fn get_sorted_set_doc_values(&self, field: &str) -> Result<SortedSetDocValuesRef> {
+        self.check_field(field)?;
+        self.reader.get_sorted_set_doc_values(field)
+    }
+
+    fn norm_values(&self, field: &str) -> Result<Option<Box<dyn NumericDocValues>>> {
+        if self.filter.accept_field(field) {
+            self.reader.norm_values(field)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn get_docs_with_field(&self, field: &str) -> Result<BitsRef> {
+        self.check_field(field)?;
+        self.reader.get_docs_with_field(field)
+    }
+
+    fn point_values(&self) -> Option<Self::PointsReader> {
+        self.reader.point_values()
+    }
+
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        if self.filter.accept_field(field) {
+            self.reader.vector_values(field)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn core_cache_key(&self) -> &str {
+        self.reader.core_cache_key()
+    }
+
+    fn index_sort(&self) -> Option<&Sort> {
+        self.reader.index_sort()
+    }
+
+    fn add_core_drop_listener(&self, listener: Deferred) {
+        self.reader.add_core_drop_listener(listener)
+    }
+
+    fn is_codec_reader(&self) -> bool {
+        // the codec readers would bypass the filter
+        false
+    }
+
+    fn store_fields_reader(&self) -> Result<Self::StoredReader> {
+        unreachable!()
+    }
+
+    fn term_vectors_reader(&self) -> Result<Option<Self::TVReader>> {
+        unreachable!()
+    }
+
+    fn norms_reader(&self) -> Result<Option<Self::NormsReader>> {
+        unreachable!()
+    }
+
+    fn doc_values_reader(&self) -> Result<Option<DocValuesProducerRef>> {
+        unreachable!()
+    }
+
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        unreachable!()
+    }
+
+    fn postings_reader(&self) -> Result<Self::FieldsProducer> {
+        unreachable!()
+    }
+}
+
+/// Skips the stored values of the fields hidden by the filter.
+struct FilterStoredFieldVisitor<'a, F: LeafReaderFilter> {
+    filter: &'a F,
+    visitor: &'a mut dyn StoredFieldVisitor,
+}
+
+impl<'a, F: LeafReaderFilter> StoredFieldVisitor for FilterStoredFieldVisitor<'a, F> {
+    fn binary_field(&mut self, field_info: &FieldInfo, value: Vec<u8>) -> Result<()> {
+        self.visitor.binary_field(field_info, value)
+    }
+
+    fn string_field(&mut self, field_info: &FieldInfo, value: Vec<u8>) -> Result<()> {
+        self.visitor.string_field(field_info, value)
+    }
+
+    fn int_field(&mut self, field_info: &FieldInfo, value: i32) -> Result<()> {
+        self.visitor.int_field(field_info, value)
+    }
+
+    fn long_field(&mut self, field_info: &FieldInfo, value: i64) -> Result<()> {
+        self.visitor.long_field(field_info, value)
+    }
+
+    fn float_field(&mut self, field_info: &FieldInfo, value: f32) -> Result<()> {
+        self.visitor.float_field(field_info, value)
+    }
+
+    fn double_field(&mut self, field_info: &FieldInfo, value: f64) -> Result<()> {
+        self.visitor.double_field(field_info, value)
+    }
+
+    fn needs_field(&self, field_info: &FieldInfo) -> Status {
+        if self.filter.accept_field(&field_info.name) {
+            self.visitor.needs_field(field_info)
+        } else {
+            Status::No
+        }
+    }
+}
+
+/// A `StandardDirectoryReader` whose segment readers are all wrapped in a
+/// `FilterLeafReader` with the same `LeafReaderFilter`, e.g. to hide some
+/// fields of the index to the searches.
+///
+/// Refreshing the reader reopens the wrapped one and wraps the new segment
+/// readers with the same filter.
+pub struct FilterDirectoryReader<D, C, MS, MP, F>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+    F: LeafReaderFilter + 'static,
+{
+    reader: StandardDirectoryReader<D, C, MS, MP>,
+    filter: Arc<F>,
+    leaves: Vec<FilterLeafReader<SegmentReader<D, C>, F>>,
+    starts: Vec<i32>,
+    num_docs: i32,
+}
+
+impl<D, C, MS, MP, F> FilterDirectoryReader<D, C, MS, MP, F>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+    F: LeafReaderFilter + 'static,
+{
+    pub fn new(reader: StandardDirectoryReader<D, C, MS, MP>, filter: Arc<F>) -> Result<Self> {
+        let mut leaves = Vec::with_capacity(reader.segment_readers().len());
+        let mut starts = Vec::with_capacity(leaves.capacity() + 1);
+        let mut max_doc = 0;
+        let mut num_docs = 0;
+        for segment_reader in reader.segment_readers() {
+            let leaf = FilterLeafReader::new(Arc::clone(segment_reader), Arc::clone(&filter))?;
+            starts.push(max_doc);
+            max_doc += leaf.max_doc();
+            num_docs += leaf.num_docs();
+            leaves.push(leaf);
+        }
+        starts.push(max_doc);
+        Ok(FilterDirectoryReader {
+            reader,
+            filter,
+            leaves,
+            starts,
+            num_docs,
+        })
+    }
+
+    /// The wrapped reader, which sees the whole index.
+    pub fn delegate(&self) -> &StandardDirectoryReader<D, C, MS, MP> {
+        &self.reader
+    }
+
+    pub fn filter(&self) -> &Arc<F> {
+        &self.filter
+    }
+
+    /// Reopens the wrapped reader, see `StandardDirectoryReader::open_if_changed`,
+    /// and wraps it with the same filter. Returns None if the index didn't change.
+    pub fn open_if_changed(&self) -> Result<Option<Self>> {
+        match self.reader.open_if_changed(None)? {
+            Some(reader) => Ok(Some(Self::new(reader, Arc::clone(&self.filter))?)),
+            None => Ok(None),
+        }
+    }
+
+    fn leaf_index(&self, doc_id: DocId) -> Result<usize> {
+        if doc_id < 0 || doc_id >= self.max_doc() {
+            bail!(IllegalArgument(format!(
+                "doc_id {} invalid: [max_doc={}]",
+                doc_id,
+                self.max_doc()
+            )));
+        }
+        Ok(match self.starts.binary_search(&doc_id) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        })
+    }
+}
+
+impl<D, C, MS, MP, F> IndexReader for FilterDirectoryReader<D, C, MS, MP, F>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+    F: LeafReaderFilter + 'static,
+{
+    type Codec = C;
+
+    fn leaves(&self) -> Vec<LeafReaderContext<'_, C>> {
+        self.leaves
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                LeafReaderContext::new(self, r as &SearchLeafReader<C>, i, self.starts[i])
+            })
+            .collect()
+    }
+
+    fn term_vector(&self, doc_id: DocId) -> Result<Option<CodecTVFields<C>>> {
+        let i = self.leaf_index(doc_id)?;
+        self.leaves[i].term_vector(doc_id - self.starts[i])
+    }
+
+    fn document(&self, doc_id: DocId, fields_load: &[String]) -> Result<Document> {
+        let i = self.leaf_index(doc_id)?;
+        let mut visitor = DocumentStoredFieldVisitor::new(fields_load);
+        self.leaves[i].document(doc_id - self.starts[i], &mut visitor)?;
+        Ok(visitor.document())
+    }
+
+    fn max_doc(&self) -> i32 {
+        self.starts[self.leaves.len()]
+    }
+
+    fn num_docs(&self) -> i32 {
+        self.num_docs
+    }
+
+    fn refresh(&self) -> Result<Option<Box<dyn IndexReader<Codec = C>>>> {
+        match self.open_if_changed()? {
+            Some(reader) => Ok(Some(Box::new(reader))),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<D, C, MS, MP, F> fmt::Debug for FilterDirectoryReader<D, C, MS, MP, F>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+    F: LeafReaderFilter + 'static,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FilterDirectoryReader({:?})", self.reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::{Fieldable, IndexOptions, IndexWriter, Term};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::bit_set::{BitSet, FixedBitSet};
+    use core::util::{MatchAllBits, VariantValue};
+    use std::fs;
+    use std::path::PathBuf;
+
+    type TestReader = StandardDirectoryReader<
+        FSDirectory<NativeFSLockFactory>,
+        CodecEnum,
+        SerialMergeScheduler,
+        TieredMergePolicy,
+    >;
+
+    /// Hides the "secret" fields and the docs with an odd value.
+    struct HideSecrets;
+
+    impl LeafReaderFilter for HideSecrets {
+        fn accept_field(&self, field: &str) -> bool {
+            !field.starts_with("secret")
+        }
+
+        fn live_docs<R: LeafReader + ?Sized>(&self, reader: &R) -> Result<Option<BitsRef>> {
+            let live_docs = reader.live_docs();
+            let values = reader.get_numeric_doc_values("value")?;
+            let mut bits = FixedBitSet::new(reader.max_doc() as usize);
+            for doc in 0..reader.max_doc() {
+                if live_docs.get(doc as usize)? && values.get(doc)? % 2 == 0 {
+                    bits.set(doc as usize);
+                }
+            }
+            Ok(Some(Arc::new(bits)))
+        }
+    }
+
+    struct WrongLiveDocs;
+
+    impl LeafReaderFilter for WrongLiveDocs {
+        fn live_docs<R: LeafReader + ?Sized>(&self, reader: &R) -> Result<Option<BitsRef>> {
+            Ok(Some(Arc::new(MatchAllBits::new(
+                reader.max_doc() as usize + 1,
+            ))))
+        }
+    }
+
+    fn string_field(name: &str, value: String) -> Box<dyn Fieldable> {
+        let mut field_type = FieldType::default();
+        field_type.tokenized = false;
+        field_type.stored = true;
+        field_type.index_options = IndexOptions::Docs;
+        field_type.omit_norms = true;
+        Box::new(Field::new(
+            name.into(),
+            field_type,
+            Some(VariantValue::VString(value)),
+            None,
+        ))
+    }
+
+    fn document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        vec![
+            string_field("id", id.to_string()),
+            string_field("secret", format!("s{}", id)),
+            Box::new(NumericDocValuesField::new("value", id)),
+            Box::new(NumericDocValuesField::new("secret_value", id)),
+        ]
+    }
+
+    /// Writes docs 0 to 7 in two segments and deletes doc 6.
+    fn test_index(name: &str) -> (PathBuf, TestReader) {
+        let path = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..8 {
+            writer.add_document(document(i)).unwrap();
+            if i == 3 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.commit().unwrap();
+        writer
+            .delete_documents_by_terms(vec![Term::new("id".into(), b"6".to_vec())])
+            .unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+        (path, StandardDirectoryReader::open(dir).unwrap())
+    }
+
+    #[test]
+    fn test_hidden_fields() {
+        let (path, reader) = test_index("rucene_filter_reader_fields");
+        let reader = FilterDirectoryReader::new(reader, Arc::new(HideSecrets)).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 2);
+        for leaf in &leaves {
+            let leaf = leaf.reader;
+            assert!(leaf.field_info("id").is_some());
+            assert!(leaf.field_info("secret").is_none());
+            assert!(leaf
+                .field_infos()
+                .field_info_by_name("secret_value")
+                .is_none());
+            assert_eq!(leaf.field_infos().len(), 2);
+
+            assert!(leaf.terms("id").unwrap().is_some());
+            assert!(leaf.terms("secret").unwrap().is_none());
+            assert!(leaf.get_numeric_doc_values("value").is_ok());
+            assert!(leaf.get_numeric_doc_values("secret_value").is_err());
+            assert!(leaf.get_docs_with_field("secret_value").is_err());
+            assert!(leaf.norm_values("secret").unwrap().is_none());
+        }
+
+        // the stored values of the hidden fields are skipped
+        let fields = ["id".to_string(), "secret".to_string()];
+        let document = reader.document(2, &fields).unwrap();
+        assert_eq!(document.fields.len(), 1);
+        assert_eq!(document.fields[0].field.name(), "id");
+        assert_eq!(document.fields[0].field.string_value(), Some("2"));
+        // while the wrapped reader still sees them
+        let document = reader.delegate().document(2, &fields).unwrap();
+        assert_eq!(document.fields.len(), 2);
+
+        drop(reader);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_hidden_docs() {
+        let (path, reader) = test_index("rucene_filter_reader_docs");
+        assert_eq!(reader.num_docs(), 7);
+        let reader = FilterDirectoryReader::new(reader, Arc::new(HideSecrets)).unwrap();
+        assert_eq!(reader.max_doc(), 8);
+        // the odd docs are hidden on top of the deleted doc 6
+        assert_eq!(reader.num_docs(), 3);
+
+        let mut live = vec![];
+        for leaf in reader.leaves() {
+            let live_docs = leaf.reader.live_docs();
+            assert_eq!(live_docs.len(), leaf.reader.max_doc() as usize);
+            let mut num_docs = 0;
+            for doc in 0..leaf.reader.max_doc() {
+                if live_docs.get(doc as usize).unwrap() {
+                    live.push(leaf.doc_base + doc);
+                    num_docs += 1;
+                }
+            }
+            assert_eq!(leaf.reader.num_docs(), num_docs);
+        }
+        assert_eq!(live, vec![0, 2, 4]);
+
+        assert!(reader.document(8, &["id".to_string()]).is_err());
+
+        drop(reader);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_live_docs() {
+        let (path, reader) = test_index("rucene_filter_reader_wrong_live_docs");
+        assert!(FilterDirectoryReader::new(reader, Arc::new(WrongLiveDocs)).is_err());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -15,19 +15,21 @@ use core::codec::Codec;
 use core::index::bufferd_updates::BufferedUpdatesStream;
 use core::index::doc_writer::DocumentsWriter;
 use core::index::doc_writer_delete_queue::DocumentsWriterDeleteQueue;
-use core::index::flush_policy::{FlushByRamOrCountsPolicy, FlushPolicy};
+use core::index::flush_policy::{FlushAction, FlushPolicy, FlushStats};
 use core::index::index_writer_config::{IndexWriterConfig, DISABLE_AUTO_FLUSH};
 use core::index::merge_policy::MergePolicy;
 use core::index::merge_scheduler::MergeScheduler;
 use core::index::thread_doc_writer::DocumentsWriterPerThreadPool;
 use core::index::thread_doc_writer::{DocumentsWriterPerThread, ThreadState};
-use core::util::Volatile;
+use core::util::{Accountable, RamUsage, Volatile};
 use error::{ErrorKind::IllegalState, Result};
 
 use core::store::Directory;
+
+use crossbeam::queue::SegQueue;
 use std::cell::Cell;
 use std::cmp::max;
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashMap;
 use std::mem;
 use std::ptr;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -61,7 +63,10 @@ pub(crate) struct DocumentsWriterFlushControl<
     // only with assert
     flush_deletes: AtomicBool,
     full_flush: AtomicBool,
-    pub flush_queue: VecDeque<DocumentsWriterPerThread<D, C, MS, MP>>,
+    // DWPTs checked out for flushing, published and taken without the lock
+    // so the indexing threads checking for queued flushes after each
+    // document don't contend on it
+    flush_queue: SegQueue<DocumentsWriterPerThread<D, C, MS, MP>>,
     // only for safety reasons if a DWPT is close to the RAM limit
     blocked_flushes: Vec<BlockedFlush<D, C, MS, MP>>,
     // key is segment_name of the DocumentsWriterPerThread
@@ -79,7 +84,7 @@ pub(crate) struct DocumentsWriterFlushControl<
     flush_by_ram_was_disabled: Cell<bool>,
     stall_control: DocumentsWriterStallControl,
     per_thread_pool: *mut DocumentsWriterPerThreadPool<D, C, MS, MP>,
-    flush_policy: Arc<FlushByRamOrCountsPolicy<C, MS, MP>>,
+    flush_policy: Arc<dyn FlushPolicy>,
     closed: bool,
     documents_writer: *const DocumentsWriter<D, C, MS, MP>,
     config: Arc<IndexWriterConfig<C, MS, MP>>,
@@ -95,7 +100,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
 {
     pub fn new(
         config: Arc<IndexWriterConfig<C, MS, MP>>,
-        flush_policy: Arc<FlushByRamOrCountsPolicy<C, MS, MP>>,
+        flush_policy: Arc<dyn FlushPolicy>,
     ) -> Self {
         let hard_max_bytes_per_dwpt = config.per_thread_hard_limit();
         DocumentsWriterFlushControl {
@@ -108,7 +113,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
             num_docs_since_stalled: 0,
             flush_deletes: AtomicBool::new(false),
             full_flush: AtomicBool::new(false),
-            flush_queue: VecDeque::new(),
+            flush_queue: SegQueue::new(),
             blocked_flushes: vec![],
             flushing_writers: HashMap::new(),
             max_configured_ram_buffer: Cell::new(0.0),
@@ -190,18 +195,26 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
     ) -> Result<Option<DocumentsWriterPerThread<D, C, MS, MP>>> {
         self.commit_per_thread_bytes(per_thread);
         if !per_thread.flush_pending() {
-            unsafe {
-                let writer = self as *mut DocumentsWriterFlushControl<D, C, MS, MP>;
-                if is_update {
-                    self.flush_policy.on_update(&mut *writer, lg, per_thread);
-                } else {
-                    self.flush_policy.on_insert(&mut *writer, lg, per_thread);
-                }
+            let stats = self.flush_stats(Some(per_thread));
+            match self.flush_policy.on_insert(&stats) {
+                FlushAction::None => {}
+                FlushAction::FlushThread => self.set_flush_pending(per_thread, lg),
+                FlushAction::FlushLargestThread => self.mark_largest_writer_pending(per_thread, lg),
+            }
+            if is_update && self.flush_policy.on_delete(&stats) {
+                self.set_apply_all_deletes();
             }
 
             if !per_thread.flush_pending() && per_thread.bytes_used > self.hard_max_bytes_per_dwpt {
                 // Safety check to prevent a single DWPT exceeding its RAM limit. This
                 // is super important since we can not address more than 2048 MB per DWPT
+                self.config.info("DWFC", || {
+                    format!(
+                        "force flush {}: bytes_used={} over the per thread hard limit",
+                        &per_thread.dwpt().segment_info.name,
+                        per_thread.bytes_used
+                    )
+                });
                 self.set_flush_pending(per_thread, lg);
             }
         }
@@ -233,10 +246,80 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
     }
 
     pub fn do_on_delete(&mut self) {
-        // pass None this is a global delete no update
-        unsafe {
-            let writer = self as *mut DocumentsWriterFlushControl<D, C, MS, MP>;
-            self.flush_policy.on_delete(&mut *writer, None);
+        // this is a global delete no update
+        let stats = self.flush_stats(None);
+        if self.flush_policy.on_delete(&stats) {
+            self.set_apply_all_deletes();
+        }
+    }
+
+    /// The memory buffered now, for the `FlushPolicy`.
+    fn flush_stats(&self, per_thread: Option<&ThreadState<D, C, MS, MP>>) -> FlushStats {
+        let (thread_docs, thread_bytes) = match per_thread {
+            Some(state) => (state.dwpt().num_docs_in_ram, state.bytes_used),
+            None => (0, 0),
+        };
+        FlushStats {
+            thread_docs,
+            thread_bytes,
+            active_bytes: self.active_bytes,
+            delete_bytes: self.delete_bytes_used() as u64,
+            delete_terms: self.num_global_term_deletes(),
+        }
+    }
+
+    /// Marks the most RAM consuming non-pending `ThreadState` with at least
+    /// one indexed document as flush pending, or `per_thread_state` if it's
+    /// the largest.
+    fn mark_largest_writer_pending(
+        &mut self,
+        per_thread_state: &ThreadState<D, C, MS, MP>,
+        lg: &MutexGuard<FlushControlLock>,
+    ) {
+        debug!("FP - set largest ram consuming thread pending on lower watermark");
+        if let Some(locked_state) = self.find_largest_non_pending_writer(per_thread_state) {
+            self.set_flush_pending(&*locked_state, lg);
+        } else {
+            self.set_flush_pending(per_thread_state, lg);
+        }
+    }
+
+    /// Returns the current most RAM consuming non-pending `ThreadState` with
+    /// at least one indexed document, None if `per_thread_state` is the largest.
+    fn find_largest_non_pending_writer(
+        &self,
+        per_thread_state: &ThreadState<D, C, MS, MP>,
+    ) -> Option<Arc<ThreadState<D, C, MS, MP>>> {
+        debug_assert!(per_thread_state.dwpt().num_docs_in_ram > 0);
+        let mut max_ram_so_far = per_thread_state.bytes_used;
+        // the dwpt which needs to be flushed eventually
+        debug_assert!(!per_thread_state.flush_pending());
+        let mut count = 0;
+        let mut max_thread_state_idx = usize::max_value();
+        let pool = self.per_thread_pool();
+        for idx in 0..pool.active_thread_state_count() {
+            let state = pool.get_thread_state(idx);
+            if !state.flush_pending() {
+                let next_ram = state.bytes_used();
+                if next_ram > 0 && state.dwpt().num_docs_in_ram > 0 {
+                    debug!(
+                        "FP - thread state has {} bytes; doc_in_ram: {}",
+                        next_ram,
+                        state.dwpt().num_docs_in_ram
+                    );
+                }
+                count += 1;
+                if next_ram > max_ram_so_far {
+                    max_ram_so_far = next_ram;
+                    max_thread_state_idx = idx;
+                }
+            }
+        }
+        debug!("FP - {} in-use non-flushing threads states.", count);
+        if max_thread_state_idx != usize::max_value() {
+            Some(pool.locked_state(max_thread_state_idx))
+        } else {
+            None
         }
     }
 
@@ -318,8 +401,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         let l = self.lock.lock().unwrap();
         let control_mut = unsafe { self.flush_control_mut(&l) };
 
-        let flush_queue = mem::replace(&mut control_mut.flush_queue, VecDeque::with_capacity(0));
-        for mut dwpt in flush_queue {
+        while let Ok(mut dwpt) = self.flush_queue.pop() {
             self.documents_writer()
                 .subtract_flushed_num_docs(dwpt.num_docs_in_ram);
             dwpt.abort();
@@ -338,7 +420,6 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
             self.do_after_flush(blocked_flush.dwpt, &l);
         }
 
-        control_mut.flush_queue.clear();
         self.update_stall_state();
     }
 
@@ -426,6 +507,17 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         let stall = (self.active_bytes + self.flush_bytes) > limit
             && self.active_bytes < limit
             && !self.closed;
+        if stall != self.stall_control.stalled.read() {
+            self.config.info("DWFC", || {
+                format!(
+                    "{} indexing threads: active_bytes={} flush_bytes={} limit={}",
+                    if stall { "stall" } else { "release" },
+                    self.active_bytes,
+                    self.flush_bytes,
+                    limit
+                )
+            });
+        }
         self.stall_control.update_stalled(stall);
         stall
     }
@@ -439,7 +531,6 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
     }
 
     pub fn num_queued_flushes(&self) -> usize {
-        let _l = self.lock.lock().unwrap();
         self.flush_queue.len()
     }
 
@@ -455,7 +546,45 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         self.stall_control.stalled.read()
     }
 
+    pub fn stats(&self) -> IndexingThreadStats {
+        let l = self.lock.lock().unwrap();
+        let pool = self.per_thread_pool();
+        let mut stats = IndexingThreadStats {
+            thread_states: pool.active_thread_state_count(),
+            active_thread_states: 0,
+            flush_pending: 0,
+            flushing: self.num_flushing_dwpt(&l),
+            queued_flushes: self.flush_queue.len(),
+            blocked_flushes: self.num_blocked_flushes(&l),
+            waiting_threads: pool.num_waiting_threads(),
+            stalled: self.any_stalled_threads(),
+            active_bytes: self.active_bytes,
+            flush_bytes: self.flush_bytes,
+        };
+        for i in 0..stats.thread_states {
+            let state = pool.get_thread_state(i);
+            if state.flush_pending() {
+                stats.flush_pending += 1;
+            } else if state
+                .dwpt
+                .as_ref()
+                .map_or(false, |dwpt| dwpt.num_docs_in_ram > 0)
+            {
+                stats.active_thread_states += 1;
+            }
+        }
+        stats
+    }
+
     pub fn next_pending_flush(&self) -> Option<DocumentsWriterPerThread<D, C, MS, MP>> {
+        // called after each document: queued flushes are taken without the
+        // lock, which is only needed to check out a flush pending DWPT.
+        if let Ok(dwpt) = self.flush_queue.pop() {
+            return Some(dwpt);
+        }
+        if self.num_pending.read() == 0 || self.is_full_flush() {
+            return None;
+        }
         let guard = self.lock.lock().unwrap();
         self.do_next_pending_flush(&guard)
     }
@@ -469,7 +598,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
         {
             let flush_control_mut = unsafe { self.flush_control_mut(lg) };
 
-            if let Some(dwpt) = flush_control_mut.flush_queue.pop_front() {
+            if let Ok(dwpt) = flush_control_mut.flush_queue.pop() {
                 self.update_stall_state();
                 return Some(dwpt);
             }
@@ -577,7 +706,9 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
             control_mut.prune_blocked_queue(flushing_queue.generation);
             debug_assert!(self.assert_blocked_flushes());
             let full_flush_buffer = mem::replace(&mut control_mut.full_flush_buffer, vec![]);
-            control_mut.flush_queue.extend(full_flush_buffer);
+            for dwpt in full_flush_buffer {
+                self.flush_queue.push(dwpt);
+            }
             self.update_stall_state();
         }
         debug_assert!(self.assert_active_delete_queue());
@@ -680,7 +811,7 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
                 blocked_flush.bytes,
             );
             // don't decr pending here - it's already done when DWPT is blocked
-            self.flush_queue.push_back(blocked_flush.dwpt);
+            self.flush_queue.push(blocked_flush.dwpt);
         }
     }
 
@@ -776,6 +907,62 @@ impl<D: Directory + Send + Sync + 'static, C: Codec, MS: MergeScheduler, MP: Mer
     }
 }
 
+/// The indexed docs buffered by the active and the flushing DWPTs, and the
+/// deletes not yet applied.
+impl<D, C, MS, MP> Accountable for DocumentsWriterFlushControl<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    fn ram_bytes_used(&self) -> usize {
+        self.child_resources()
+            .iter()
+            .map(|usage| usage.ram_bytes_used)
+            .sum()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        let buffered_bytes = {
+            let _l = self.lock.lock().unwrap();
+            self.active_bytes + self.flush_bytes
+        };
+        vec![
+            RamUsage::new("indexing buffer", buffered_bytes as usize),
+            RamUsage::new("deletes", self.delete_bytes_used()),
+        ]
+    }
+}
+
+/// A snapshot of the indexing threads of an `IndexWriter`, see
+/// `IndexWriter::indexing_stats`. Each indexing thread buffers its docs in a
+/// `DocumentsWriterPerThread` (DWPT) of its `ThreadState`, which is flushed
+/// to its own segment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IndexingThreadStats {
+    /// number of thread states, see `IndexWriterConfig::max_thread_states`
+    pub thread_states: usize,
+    /// thread states buffering docs and not pending for flush
+    pub active_thread_states: usize,
+    /// thread states marked pending for flush, not yet checked out
+    pub flush_pending: usize,
+    /// DWPTs checked out for flush, including the queued and blocked ones
+    pub flushing: usize,
+    /// DWPTs waiting for a thread to flush them
+    pub queued_flushes: usize,
+    /// DWPTs blocked until the running full flush is done
+    pub blocked_flushes: usize,
+    /// threads waiting for a free thread state
+    pub waiting_threads: usize,
+    /// true if the indexing threads are stalled because flushing can't keep up
+    pub stalled: bool,
+    /// bytes buffered by the thread states not pending for flush
+    pub active_bytes: u64,
+    /// bytes buffered by the DWPTs pending for flush or flushing
+    pub flush_bytes: u64,
+}
+
 struct BlockedFlush<
     D: Directory + Send + Sync + 'static,
     C: Codec,
@@ -11,148 +11,62 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::Codec;
-use core::index::flush_control::{DocumentsWriterFlushControl, FlushControlLock};
-use core::index::index_writer_config::IndexWriterConfig;
-use core::index::merge_policy::MergePolicy;
-use core::index::merge_scheduler::MergeScheduler;
-use core::index::thread_doc_writer::ThreadState;
-use core::store::Directory;
+use std::fmt;
 
-use std::sync::{Arc, MutexGuard};
+/// What the writer buffers in memory when its `FlushPolicy` is consulted.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlushStats {
+    /// number of docs buffered by the thread state which just indexed a doc,
+    /// 0 for a delete
+    pub thread_docs: u32,
+    /// bytes buffered by the thread state which just indexed a doc
+    pub thread_bytes: u64,
+    /// bytes buffered by all the thread states not pending for flush
+    pub active_bytes: u64,
+    /// bytes buffered for the deletes and doc values updates not yet applied
+    pub delete_bytes: u64,
+    /// number of buffered delete terms
+    pub delete_terms: usize,
+}
+
+/// What a `FlushPolicy` decides after a doc was indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushAction {
+    /// keep buffering
+    None,
+    /// flush the thread state which just indexed the doc
+    FlushThread,
+    /// flush the thread state buffering the most bytes
+    FlushLargestThread,
+}
 
 /// `FlushPolicy` controls when segments are flushed from a RAM resident
-/// internal data-structure to the `IndexWriter`s `Directory`.
+/// internal data-structure to the `IndexWriter`s `Directory`, see
+/// `IndexWriterConfig::set_flush_policy`.
 ///
 /// Segments are traditionally flushed by:
-/// - RAM consumption - configured via
-/// - `IndexWriterConfig#setRAMBufferSizeMB(double)`
+/// - RAM consumption - configured via `IndexWriterConfig::set_ram_buffer_size`
 /// - Number of RAM resident documents - configured via
-/// - `IndexWriterConfig#setMaxBufferedDocs(int)`
+/// `IndexWriterConfig::set_max_buffered_docs`
 ///
 /// The policy also applies pending delete operations (by term and/or query),
-/// given the threshold set in
-/// `IndexWriterConfig#setMaxBufferedDeleteTerms(int)`.
+/// given the threshold set in `IndexWriterConfig::set_max_buffered_delete_terms`.
 ///
-/// `IndexWriter` consults the provided `FlushPolicy` to control the
-/// flushing process. The policy is informed for each added or updated document
-/// as well as for each delete term. Based on the `FlushPolicy`, the
-/// information provided via `ThreadState` and
-/// `DocumentsWriterFlushControl`, the `FlushPolicy` decides if a
-/// `DocumentsWriterPerThread` needs flushing and mark it as flush-pending
-/// via `DocumentsWriterFlushControl#setFlushPending`, or if deletes need
-/// to be applied.
+/// `IndexWriter` consults the `FlushPolicy` for each added or updated
+/// document as well as for each delete, with the memory currently buffered.
+/// Independently of the policy, a `DocumentsWriterPerThread` exceeding
+/// `IndexWriterConfig::per_thread_hard_limit_mb` is always flushed.
 ///
-/// @see ThreadState
-/// @see DocumentsWriterFlushControl
-/// @see DocumentsWriterPerThread
-/// @see IndexWriterConfig#setFlushPolicy(FlushPolicy)
-pub(crate) trait FlushPolicy {
-    /// Called for each delete term. If this is a delete triggered due to an update
-    /// the given `ThreadState` is non-null.
-    ///
-    /// Note: This method is called synchronized on the given
-    /// `DocumentsWriterFlushControl` and it is guaranteed that the calling
-    /// thread holds the lock on the given `ThreadState`
-    fn on_delete<D, C, MS, MP>(
-        &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
-        state: Option<&ThreadState<D, C, MS, MP>>,
-    ) where
-        D: Directory + Send + Sync + 'static,
-        C: Codec,
-        MS: MergeScheduler,
-        MP: MergePolicy;
-
-    /// Called for each document update on the given `ThreadState`'s
-    /// `DocumentsWriterPerThread`.
-    ///
-    /// Note: This method is called  synchronized on the given
-    /// `DocumentsWriterFlushControl` and it is guaranteed that the calling
-    /// thread holds the lock on the given `ThreadState`
-    fn on_update<D, C, MS, MP>(
-        &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
-        lg: &MutexGuard<FlushControlLock>,
-        state: &ThreadState<D, C, MS, MP>,
-    ) where
-        D: Directory + Send + Sync + 'static,
-        C: Codec,
-        MS: MergeScheduler,
-        MP: MergePolicy,
-    {
-        self.on_insert(control, lg, state);
-        self.on_delete(control, Some(state));
-    }
-
-    /// Called for each document addition on the given `ThreadState`s
-    /// `DocumentsWriterPerThread`.
-    ///
-    /// Note: This method is synchronized by the given
-    /// `DocumentsWriterFlushControl` and it is guaranteed that the calling
-    /// thread holds the lock on the given `ThreadState`
-    fn on_insert<D, C, MS, MP>(
-        &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
-        lg: &MutexGuard<FlushControlLock>,
-        state: &ThreadState<D, C, MS, MP>,
-    ) where
-        D: Directory + Send + Sync + 'static,
-        C: Codec,
-        MS: MergeScheduler,
-        MP: MergePolicy;
+/// The policy is called under the lock of the flush control, so it must be
+/// cheap.
+pub trait FlushPolicy: Send + Sync + fmt::Debug {
+    /// Called for each document added or updated, decides which thread state
+    /// must be flushed, if any.
+    fn on_insert(&self, stats: &FlushStats) -> FlushAction;
 
-    /// Returns the current most RAM consuming non-pending `ThreadState` with
-    /// at least one indexed document.
-    ///
-    /// @Return: Arc<ThreadState>, the largest pending writer
-    ///          None: if the current is the largest
-    fn find_largest_non_pending_writer<D, C, MS, MP>(
-        &self,
-        control: &DocumentsWriterFlushControl<D, C, MS, MP>,
-        per_thread_state: &ThreadState<D, C, MS, MP>,
-    ) -> Option<Arc<ThreadState<D, C, MS, MP>>>
-    where
-        D: Directory + Send + Sync + 'static,
-        C: Codec,
-        MS: MergeScheduler,
-        MP: MergePolicy,
-    {
-        debug_assert!(per_thread_state.dwpt().num_docs_in_ram > 0);
-        let mut max_ram_so_far = per_thread_state.bytes_used;
-        // the dwpt which needs to be flushed eventually
-        debug_assert!(!per_thread_state.flush_pending());
-        let mut count = 0;
-        let mut max_thread_state_idx = usize::max_value();
-        let pool = control.per_thread_pool();
-        {
-            for idx in 0..control.per_thread_pool().active_thread_state_count() {
-                let state = pool.get_thread_state(idx);
-                if !state.flush_pending() {
-                    let next_ram = state.bytes_used();
-                    if next_ram > 0 && state.dwpt().num_docs_in_ram > 0 {
-                        debug!(
-                            "FP - thread state has {} bytes; doc_in_ram: {}",
-                            next_ram,
-                            state.dwpt().num_docs_in_ram
-                        );
-                    }
-                    count += 1;
-                    if next_ram > max_ram_so_far {
-                        max_ram_so_far = next_ram;
-                        max_thread_state_idx = idx;
-                    }
-                }
-            }
-        }
-        debug!("FP - {} in-use non-flushing threads states.", count);
-        debug!("FP - set largest ram consuming thread pending on lower watermark");
-        if max_thread_state_idx != usize::max_value() {
-            Some(control.per_thread_pool().locked_state(max_thread_state_idx))
-        } else {
-            None
-        }
-    }
+    /// Called for each delete, including the delete of an update. Returns
+    /// true if all the buffered deletes must be applied.
+    fn on_delete(&self, stats: &FlushStats) -> bool;
 }
 
 /// Default `FlushPolicy` implementation that flushes new segments based on
@@ -160,127 +74,112 @@ pub(crate) trait FlushPolicy {
 /// `IndexWriterConfig`. It also applies pending deletes based on the
 /// number of buffered delete terms.
 ///
-/// <ul>
-/// <li>
-/// `#onDelete(DocumentsWriterFlushControl, DocumentsWriterPerThreadPool.ThreadState)`
-/// - applies pending delete operations based on the global number of buffered
-/// delete terms iff `IndexWriterConfig#getMaxBufferedDeleteTerms()` is
-/// enabled</li>
-/// <li>
-/// `#onInsert(DocumentsWriterFlushControl, DocumentsWriterPerThreadPool.ThreadState)`
-/// - flushes either on the number of documents per
-/// `DocumentsWriterPerThread` (
-/// `DocumentsWriterPerThread#getNumDocsInRAM()`) or on the global active
-/// memory consumption in the current indexing session iff
-/// `IndexWriterConfig#getMaxBufferedDocs()` or
-/// `IndexWriterConfig#getRAMBufferSizeMB()` is enabled respectively</li>
-/// <li>
-/// `#onUpdate(DocumentsWriterFlushControl, DocumentsWriterPerThreadPool.ThreadState)`
-/// - calls
-/// `#onInsert(DocumentsWriterFlushControl, DocumentsWriterPerThreadPool.ThreadState)`
-/// and
-/// `#onDelete(DocumentsWriterFlushControl, DocumentsWriterPerThreadPool.ThreadState)`
-/// in order</li>
-/// </ul>
-/// All `IndexWriterConfig` settings are used to mark
-/// `DocumentsWriterPerThread` as flush pending during indexing with
-/// respect to their live updates.
+/// - `on_delete` applies pending delete operations based on the global number
+/// of buffered delete terms iff `max_buffered_delete_terms` is enabled, or if
+/// the deletes use more than the RAM buffer.
+/// - `on_insert` flushes either on the number of documents per
+/// `DocumentsWriterPerThread` or on the global active memory consumption in the
+/// current indexing session iff `max_buffered_docs` or `ram_buffer_size_mb`
+/// is enabled respectively.
 ///
-/// If `IndexWriterConfig#setRAMBufferSizeMB(double)` is enabled, the
-/// largest ram consuming `DocumentsWriterPerThread` will be marked as
-/// pending iff the global active RAM consumption is {@code >=} the configured max RAM
-/// buffer.
-pub(crate) struct FlushByRamOrCountsPolicy<C: Codec, MS: MergeScheduler, MP: MergePolicy> {
-    index_write_config: Arc<IndexWriterConfig<C, MS, MP>>,
+/// If the RAM buffer is enabled, the largest ram consuming
+/// `DocumentsWriterPerThread` will be marked as pending iff the global active
+/// RAM consumption is `>=` the configured max RAM buffer.
+#[derive(Debug, Clone, Default)]
+pub struct FlushByRamOrCountsPolicy {
+    ram_buffer_size_mb: Option<f64>,
+    max_buffered_docs: Option<u32>,
+    max_buffered_delete_terms: Option<u32>,
 }
 
-impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> FlushByRamOrCountsPolicy<C, MS, MP> {
-    pub fn new(index_write_config: Arc<IndexWriterConfig<C, MS, MP>>) -> Self {
-        FlushByRamOrCountsPolicy { index_write_config }
+impl FlushByRamOrCountsPolicy {
+    /// Creates a policy with the given triggers, `None` disables a trigger.
+    pub fn new(
+        ram_buffer_size_mb: Option<f64>,
+        max_buffered_docs: Option<u32>,
+        max_buffered_delete_terms: Option<u32>,
+    ) -> Self {
+        FlushByRamOrCountsPolicy {
+            ram_buffer_size_mb,
+            max_buffered_docs,
+            max_buffered_delete_terms,
+        }
     }
 
-    fn mark_largest_writer_pending<
-        D: Directory + Send + Sync + 'static,
-        C1: Codec,
-        MS1: MergeScheduler,
-        MP1: MergePolicy,
-    >(
-        &self,
-        control: &mut DocumentsWriterFlushControl<D, C1, MS1, MP1>,
-        lg: &MutexGuard<FlushControlLock>,
-        per_thread_state: &ThreadState<D, C1, MS1, MP1>,
-    ) {
-        if let Some(locked_state) = self.find_largest_non_pending_writer(control, per_thread_state)
-        {
-            control.set_flush_pending(&*locked_state, lg);
-        } else {
-            control.set_flush_pending(per_thread_state, lg);
-        }
+    fn ram_buffer_size(&self) -> Option<u64> {
+        self.ram_buffer_size_mb
+            .map(|mb| (mb * 1024.0 * 1024.0) as u64)
     }
 }
 
-impl<C1: Codec, MS1: MergeScheduler, MP1: MergePolicy> FlushPolicy
-    for FlushByRamOrCountsPolicy<C1, MS1, MP1>
-{
-    fn on_delete<D, C, MS, MP>(
-        &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
-        _state: Option<&ThreadState<D, C, MS, MP>>,
-    ) where
-        D: Directory + Send + Sync + 'static,
-        C: Codec,
-        MS: MergeScheduler,
-        MP: MergePolicy,
-    {
-        if self.index_write_config.flush_on_delete_terms() {
-            // flush this state by num del terms
-            if control.num_global_term_deletes()
-                >= self.index_write_config.max_buffered_delete_terms() as usize
-            {
-                control.set_apply_all_deletes();
+impl FlushPolicy for FlushByRamOrCountsPolicy {
+    fn on_insert(&self, stats: &FlushStats) -> FlushAction {
+        if let Some(max_docs) = self.max_buffered_docs {
+            if stats.thread_docs >= max_docs {
+                // Flush this state by num docs
+                return FlushAction::FlushThread;
             }
         }
-
-        if self.index_write_config.flush_on_ram()
-            && control.delete_bytes_used() > self.index_write_config.ram_buffer_size()
-        {
-            control.set_apply_all_deletes();
-            debug!(
-                "FP - force apply deletes bytes_used: {} vs ram_bufffer_mb={}",
-                control.delete_bytes_used(),
-                self.index_write_config.ram_buffer_size_mb()
-            );
+        if let Some(limit) = self.ram_buffer_size() {
+            if stats.active_bytes + stats.delete_bytes >= limit {
+                debug!(
+                    "FP - trigger flush: active_bytes={}, delete_bytes={} vs limit={}",
+                    stats.active_bytes, stats.delete_bytes, limit
+                );
+                return FlushAction::FlushLargestThread;
+            }
         }
+        FlushAction::None
     }
 
-    fn on_insert<D, C, MS, MP>(
-        &self,
-        control: &mut DocumentsWriterFlushControl<D, C, MS, MP>,
-        lg: &MutexGuard<FlushControlLock>,
-        state: &ThreadState<D, C, MS, MP>,
-    ) where
-        D: Directory + Send + Sync + 'static,
-        C: Codec,
-        MS: MergeScheduler,
-        MP: MergePolicy,
-    {
-        if self.index_write_config.flush_on_doc_count()
-            && state.dwpt().num_docs_in_ram >= self.index_write_config.max_buffered_docs()
-        {
-            // Flush this state by num docs
-            control.set_flush_pending(state, lg);
-        } else if self.index_write_config.flush_on_ram() {
-            let limit = self.index_write_config.ram_buffer_size();
-            let total_ram = control.active_bytes as usize + control.delete_bytes_used();
-            if total_ram >= limit {
+    fn on_delete(&self, stats: &FlushStats) -> bool {
+        if let Some(max_terms) = self.max_buffered_delete_terms {
+            // flush this state by num del terms
+            if stats.delete_terms >= max_terms as usize {
+                return true;
+            }
+        }
+        if let Some(limit) = self.ram_buffer_size() {
+            if stats.delete_bytes > limit {
                 debug!(
-                    "FP - trigger flush: active_bytes={}, delete_bytes={} vs limit={}",
-                    control.active_bytes,
-                    control.delete_bytes_used(),
-                    limit
+                    "FP - force apply deletes bytes_used: {} vs ram_bufffer_mb={:?}",
+                    stats.delete_bytes, self.ram_buffer_size_mb
                 );
-                self.mark_largest_writer_pending(control, lg, state);
+                return true;
             }
         }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flush_by_ram_or_counts() {
+        let policy = FlushByRamOrCountsPolicy::new(Some(1.0), Some(10), Some(5));
+        let mut stats = FlushStats::default();
+        assert_eq!(policy.on_insert(&stats), FlushAction::None);
+        assert!(!policy.on_delete(&stats));
+
+        stats.thread_docs = 10;
+        assert_eq!(policy.on_insert(&stats), FlushAction::FlushThread);
+
+        stats.thread_docs = 1;
+        stats.active_bytes = 1024 * 1024 - 10;
+        assert_eq!(policy.on_insert(&stats), FlushAction::None);
+        stats.delete_bytes = 10;
+        assert_eq!(policy.on_insert(&stats), FlushAction::FlushLargestThread);
+
+        stats.delete_terms = 5;
+        assert!(policy.on_delete(&stats));
+        stats.delete_terms = 0;
+        stats.delete_bytes = 1024 * 1024 + 1;
+        assert!(policy.on_delete(&stats));
+
+        let disabled = FlushByRamOrCountsPolicy::new(None, None, None);
+        assert_eq!(disabled.on_insert(&stats), FlushAction::None);
+        assert!(!disabled.on_delete(&stats));
     }
 }
@@ -12,8 +12,8 @@
 // limitations under the License.
 
 use core::codec::Codec;
-use core::index::delete_policy::{IndexDeletionPolicy, KeepOnlyLastCommitDeletionPolicy};
-use core::index::index_commit::IndexCommit;
+use core::index::delete_policy::IndexDeletionPolicy;
+use core::index::index_commit::{IndexCommit, IndexCommitPoint};
 use core::index::index_writer::INDEX_WRITE_LOCK_NAME;
 use core::index::{
     generation_from_segments_file_name, parse_generation, parse_segment_name, SegmentInfos,
@@ -67,8 +67,10 @@ use error::{ErrorKind, Result};
 /// instantiating this class.  It opens segments_N file(s)
 /// directly with no retry logic.
 pub struct IndexFileDeleter<D: Directory, C: Codec> {
-    /// Reference count for all files in the index. Counts
-    /// how many existing commits reference a file.
+    /// Reference count for all files in the index. Counts how many
+    /// existing commits, near-real-time readers, running merges and the
+    /// writer's in-memory segments reference a file, a file is only
+    /// deleted once nothing references it anymore.
     ref_counts: Arc<RwLock<HashMap<String, RefCount>>>,
     /// Holds all commits (segments_N) currently in the index.
     /// this will have just 1 commit if you are using the default
@@ -82,7 +84,7 @@ pub struct IndexFileDeleter<D: Directory, C: Codec> {
     commits_to_delete: Vec<CommitPoint<D>>,
     directory_orig: Arc<D>,
     directory: Arc<LockValidatingDirectoryWrapper<D>>,
-    policy: KeepOnlyLastCommitDeletionPolicy,
+    policy: Arc<dyn IndexDeletionPolicy>,
     pub starting_commit_deleted: bool,
     last_segment_infos: Option<SegmentInfos<D, C>>,
     inited: bool,
@@ -92,7 +94,7 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
     pub fn new(
         directory_orig: Arc<D>,
         directory: Arc<LockValidatingDirectoryWrapper<D>>,
-        // policy: Box<IndexDeletionPolicy>,
+        policy: Arc<dyn IndexDeletionPolicy>,
     ) -> Self {
         IndexFileDeleter {
             ref_counts: Arc::new(RwLock::new(HashMap::new())),
@@ -101,7 +103,7 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
             commits_to_delete: vec![],
             directory_orig,
             directory,
-            policy: KeepOnlyLastCommitDeletionPolicy {},
+            policy,
             starting_commit_deleted: false,
             last_segment_infos: None,
             inited: false,
@@ -223,7 +225,8 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
         // Finally, give policy a chance to remove things on
         // startup:
         {
-            let mut commits: Vec<&mut IndexCommit<D>> = Vec::with_capacity(self.commits.len());
+            let mut commits: Vec<&mut dyn IndexCommitPoint> =
+                Vec::with_capacity(self.commits.len());
             for i in &mut self.commits {
                 commits.push(i);
             }
@@ -352,7 +355,8 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
 
             // Tell policy so it can remove commits:
             {
-                let mut commits: Vec<&mut IndexCommit<D>> = Vec::with_capacity(self.commits.len());
+                let mut commits: Vec<&mut dyn IndexCommitPoint> =
+                    Vec::with_capacity(self.commits.len());
                 for i in &mut self.commits {
                     i.commits_to_delete = &mut self.commits_to_delete;
                     commits.push(i);
@@ -372,6 +376,24 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
         }
     }
 
+    /// Asks the policy again which commits to delete, e.g. after a snapshot
+    /// of the policy is released, and deletes their files.
+    pub fn revisit_policy(&mut self) -> Result<()> {
+        if !self.commits.is_empty() {
+            {
+                let mut commits: Vec<&mut dyn IndexCommitPoint> =
+                    Vec::with_capacity(self.commits.len());
+                for i in &mut self.commits {
+                    i.commits_to_delete = &mut self.commits_to_delete;
+                    commits.push(i);
+                }
+                self.policy.on_commit(commits)?;
+            }
+            self.delete_commits()?;
+        }
+        Ok(())
+    }
+
     pub fn exists(&self, filename: &str) -> bool {
         if !self.ref_counts.read().unwrap().contains_key(filename) {
             false
@@ -381,6 +403,15 @@ impl<D: Directory, C: Codec> IndexFileDeleter<D, C> {
         }
     }
 
+    /// Returns the number of references currently held on the file.
+    pub fn ref_count(&self, filename: &str) -> u32 {
+        self.ref_counts
+            .read()
+            .unwrap()
+            .get(filename)
+            .map_or(0, |rc| rc.count)
+    }
+
     fn ensure_ref_count(&self, file_name: &str) {
         let mut ref_counts = self.ref_counts.write().unwrap();
         if !ref_counts.contains_key(file_name) {
@@ -658,7 +689,7 @@ impl<D: Directory> CommitPoint<D> {
     }
 }
 
-impl<D: Directory> IndexCommit<D> for CommitPoint<D> {
+impl<D: Directory> IndexCommitPoint for CommitPoint<D> {
     fn segments_file_name(&self) -> &str {
         &self.segments_file_name
     }
@@ -667,10 +698,6 @@ impl<D: Directory> IndexCommit<D> for CommitPoint<D> {
         Ok(&self.files)
     }
 
-    fn directory(&self) -> &D {
-        self.directory_orig.as_ref()
-    }
-
     fn delete(&mut self) -> Result<()> {
         if !self.deleted {
             self.deleted = true;
@@ -699,6 +726,12 @@ impl<D: Directory> IndexCommit<D> for CommitPoint<D> {
     }
 }
 
+impl<D: Directory> IndexCommit<D> for CommitPoint<D> {
+    fn directory(&self) -> &D {
+        self.directory_orig.as_ref()
+    }
+}
+
 impl<D: Directory> Clone for CommitPoint<D> {
     fn clone(&self) -> Self {
         CommitPoint {
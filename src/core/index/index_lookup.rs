@@ -85,7 +85,7 @@ pub struct LeafIndexFieldTerm<T: PostingIterator> {
     freq: i32,
 }
 
-impl<T: PostingIterator> LeafIndexFieldTerm<T> {
+impl<T: PostingIterator + 'static> LeafIndexFieldTerm<T> {
     pub fn new<TI: TermIterator<Postings = T>, Tm: Terms<Iterator = TI>, F: Fields<Terms = Tm>>(
         term: &str,
         field_name: &str,
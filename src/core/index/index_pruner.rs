@@ -0,0 +1,801 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CompoundFormat, FieldsConsumer, NormsProducer, PostingsFormat};
+use core::index::{Fields, LeafReader, SearchLeafReader, SegmentCommitInfo, SegmentInfos};
+use core::index::{SeekStatus, TermIterator, Terms};
+use core::index::{SegmentReader, SegmentWriteState};
+use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
+use core::search::searcher::DefaultSimilarityProducer;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{DocIterator, Similarity, SimilarityProducer, NO_MORE_DOCS};
+use core::store::{Directory, IOContext, TrackingDirectoryWrapper};
+use core::util::{BitsRef, DocId};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::f32;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// Decides which postings survive when an index is pruned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PruningPolicy {
+    /// Keep the K highest scoring documents of every term, per segment.
+    TopKPerTerm(usize),
+    /// Keep, for every document, its K highest scoring terms of each pruned field.
+    TopKPerDoc(usize),
+}
+
+impl PruningPolicy {
+    fn k(self) -> usize {
+        match self {
+            PruningPolicy::TopKPerTerm(k) | PruningPolicy::TopKPerDoc(k) => k,
+        }
+    }
+}
+
+/// Posting counts of the pruned fields, summed over all segments.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PruneStats {
+    pub total_postings: u64,
+    pub kept_postings: u64,
+}
+
+/// Offline static index pruning.
+///
+/// `IndexPruner` rewrites the latest commit of a source index into an empty target
+/// directory, keeping only the postings with the highest impact according to the
+/// configured similarity. Everything else in the segments (stored fields, doc values,
+/// norms, points, live docs) is copied verbatim, so doc ids are unchanged and the
+/// pruned index can serve the same documents from a much smaller term dictionary.
+///
+/// Postings of deleted documents are dropped from the pruned fields. Fields that are
+/// not pruned keep their postings untouched.
+pub struct IndexPruner<P> {
+    policy: PruningPolicy,
+    similarity: P,
+    fields: Option<HashSet<String>>,
+}
+
+impl IndexPruner<DefaultSimilarityProducer> {
+    /// Creates a pruner that scores postings with the default `BM25Similarity`.
+    pub fn new(policy: PruningPolicy) -> Self {
+        Self::with_similarity(policy, DefaultSimilarityProducer {})
+    }
+}
+
+impl<P> IndexPruner<P> {
+    pub fn with_similarity(policy: PruningPolicy, similarity: P) -> Self {
+        IndexPruner {
+            policy,
+            similarity,
+            fields: None,
+        }
+    }
+
+    /// Restricts pruning to the given fields, by default every indexed field is pruned.
+    pub fn set_fields(&mut self, fields: Vec<String>) {
+        self.fields = Some(fields.into_iter().collect());
+    }
+
+    pub fn policy(&self) -> PruningPolicy {
+        self.policy
+    }
+
+    /// Writes a pruned copy of the latest commit in `source` to `target` and
+    /// commits it there.
+    pub fn prune<D, DW, C>(&self, source: Arc<D>, target: Arc<DW>) -> Result<PruneStats>
+    where
+        D: Directory + 'static,
+        DW: Directory,
+        C: Codec,
+        P: SimilarityProducer<C>,
+    {
+        if self.policy.k() == 0 {
+            bail!(IllegalArgument(
+                "pruning must keep at least one posting".into()
+            ));
+        }
+        if !target.list_all()?.is_empty() {
+            bail!(IllegalArgument(format!(
+                "target directory {} is not empty",
+                target
+            )));
+        }
+
+        let mut infos: SegmentInfos<D, C> = SegmentInfos::read_latest_commit(&source)?;
+        let mut stats = PruneStats::default();
+        for sci in &infos.segments {
+            self.prune_segment(&source, &target, sci, &mut stats)?;
+        }
+
+        infos.prepare_commit(target.as_ref())?;
+        infos.finish_commit(target.as_ref())?;
+        Ok(stats)
+    }
+
+    fn prune_segment<D, DW, C>(
+        &self,
+        source: &Arc<D>,
+        target: &Arc<DW>,
+        sci: &Arc<SegmentCommitInfo<D, C>>,
+        stats: &mut PruneStats,
+    ) -> Result<()>
+    where
+        D: Directory + 'static,
+        DW: Directory,
+        C: Codec,
+        P: SimilarityProducer<C>,
+    {
+        let reader = Arc::new(SegmentReader::open(sci, &IOContext::READ)?);
+        let info = &sci.info;
+        let codec = info.codec();
+
+        // write the pruned postings, the file names are the same as the source
+        // segment's since we reuse its segment info and field infos.
+        let tracking = Arc::new(TrackingDirectoryWrapper::new(Arc::clone(target)));
+        {
+            let fields = PrunedFields::new(
+                reader.fields()?,
+                Arc::clone(&reader),
+                self.policy,
+                &self.similarity,
+                self.fields.as_ref(),
+            )?;
+            let state = SegmentWriteState::new(
+                Arc::clone(&tracking),
+                info.clone(),
+                reader.field_infos().clone(),
+                None,
+                IOContext::Default,
+                String::new(),
+            );
+            {
+                // pruning keeps the doc ids, so the norms of the source segment apply
+                let norms = reader.norms_reader()?;
+                let mut consumer = codec.postings_format().fields_consumer(&state)?;
+                consumer.write(&fields, norms.as_ref().map(|n| n as &dyn NormsProducer))?;
+            }
+            let (total, kept) = fields.counts();
+            stats.total_postings += total;
+            stats.kept_postings += kept;
+        }
+        let postings_files = tracking.get_create_files();
+
+        let mut written = postings_files.clone();
+        if info.is_compound_file() {
+            let compound = Arc::new(codec.compound_format().get_compound_reader(
+                Arc::clone(source),
+                info,
+                &IOContext::READ,
+            )?);
+            let mut inner_files = postings_files.clone();
+            for file in compound.list_all()? {
+                if !postings_files.contains(&file) {
+                    target.copy_from(Arc::clone(&compound), &file, &file, &IOContext::Default)?;
+                }
+                inner_files.insert(file);
+            }
+
+            let mut packed = info.clone();
+            packed.set_files(&inner_files)?;
+            let cfs_tracking = TrackingDirectoryWrapper::new(target.as_ref());
+            codec
+                .compound_format()
+                .write(&cfs_tracking, &packed, &IOContext::Default)?;
+            for file in &inner_files {
+                target.delete_file(file)?;
+            }
+            written = cfs_tracking.get_create_files();
+        }
+
+        let files = sci.files();
+        for file in &files {
+            if !written.contains(file) {
+                target.copy_from(Arc::clone(source), file, file, &IOContext::Default)?;
+            }
+        }
+        target.sync(&files)
+    }
+}
+
+/// Per field scoring state shared by the terms iterators of a pruned field.
+struct FieldPruner<D: Directory + 'static, C: Codec> {
+    field: String,
+    reader: Arc<SegmentReader<D, C>>,
+    live_docs: BitsRef,
+    similarity: Box<dyn Similarity<C>>,
+    collection_stats: CollectionStatistics,
+    policy: PruningPolicy,
+    has_freqs: bool,
+    // per doc score threshold, only used by `PruningPolicy::TopKPerDoc`
+    thresholds: Vec<f32>,
+    total: Cell<u64>,
+    kept: Cell<u64>,
+}
+
+impl<D: Directory + 'static, C: Codec> FieldPruner<D, C> {
+    fn new<T: Terms>(
+        field: &str,
+        terms: &T,
+        reader: Arc<SegmentReader<D, C>>,
+        policy: PruningPolicy,
+        similarity: Box<dyn Similarity<C>>,
+    ) -> Result<Self> {
+        let collection_stats = CollectionStatistics::new(
+            field.to_string(),
+            i64::from(reader.max_doc()),
+            i64::from(terms.doc_count()?),
+            terms.sum_total_term_freq()?,
+            terms.sum_doc_freq()?,
+        );
+        let mut pruner = FieldPruner {
+            field: field.to_string(),
+            live_docs: reader.live_docs(),
+            reader,
+            similarity,
+            collection_stats,
+            policy,
+            has_freqs: terms.has_freqs()?,
+            thresholds: Vec::with_capacity(0),
+            total: Cell::new(0),
+            kept: Cell::new(0),
+        };
+        if let PruningPolicy::TopKPerDoc(k) = policy {
+            pruner.thresholds = pruner.doc_thresholds(terms, k)?;
+        }
+        Ok(pruner)
+    }
+
+    /// Scores every live posting of the term the iterator is positioned on.
+    fn score_term(&self, iter: &mut impl TermIterator) -> Result<Vec<(DocId, f32)>> {
+        let term_stats = TermStatistics::new(
+            iter.term()?.to_vec(),
+            i64::from(iter.doc_freq()?),
+            iter.total_term_freq()?,
+        );
+        let sim_weight =
+            self.similarity
+                .compute_weight(&self.collection_stats, &[term_stats], None, 1.0);
+        let leaf_reader: &SearchLeafReader<C> = self.reader.as_ref();
+        let mut sim_scorer = sim_weight.sim_scorer(leaf_reader)?;
+
+        let mut postings = iter.postings_with_flags(PostingIteratorFlags::FREQS)?;
+        let mut scores = Vec::with_capacity(iter.doc_freq()? as usize);
+        loop {
+            let doc = postings.next()?;
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            if !self.live_docs.get(doc as usize)? {
+                continue;
+            }
+            let freq = if self.has_freqs { postings.freq()? } else { 1 };
+            scores.push((doc, sim_scorer.score(doc, freq as f32)?));
+        }
+        Ok(scores)
+    }
+
+    /// Computes the score of the K-th best term of every doc, a doc with less
+    /// than K terms keeps all of them.
+    fn doc_thresholds<T: Terms>(&self, terms: &T, k: usize) -> Result<Vec<f32>> {
+        let mut top_scores: Vec<Vec<f32>> = vec![Vec::new(); self.reader.max_doc() as usize];
+        let mut iter = terms.iterator()?;
+        while iter.next()?.is_some() {
+            for (doc, score) in self.score_term(&mut iter)? {
+                // each list is kept sorted in descending order
+                let top = &mut top_scores[doc as usize];
+                let pos = top
+                    .iter()
+                    .position(|s| *s < score)
+                    .unwrap_or_else(|| top.len());
+                if pos < k {
+                    top.insert(pos, score);
+                    top.truncate(k);
+                }
+            }
+        }
+        Ok(top_scores
+            .into_iter()
+            .map(|top| {
+                if top.len() < k {
+                    f32::NEG_INFINITY
+                } else {
+                    top[k - 1]
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the sorted doc ids of the current term that survive pruning.
+    fn kept_docs(&self, iter: &mut impl TermIterator) -> Result<Vec<DocId>> {
+        let total = i64::from(iter.doc_freq()?);
+        let mut scores = self.score_term(iter)?;
+        let mut docs: Vec<DocId> = match self.policy {
+            PruningPolicy::TopKPerTerm(k) => {
+                if scores.len() > k {
+                    scores.sort_by(|a, b| match b.1.partial_cmp(&a.1) {
+                        Some(Ordering::Equal) | None => a.0.cmp(&b.0),
+                        Some(o) => o,
+                    });
+                    scores.truncate(k);
+                }
+                scores.into_iter().map(|(doc, _)| doc).collect()
+            }
+            PruningPolicy::TopKPerDoc(_) => scores
+                .into_iter()
+                .filter(|(doc, score)| *score >= self.thresholds[*doc as usize])
+                .map(|(doc, _)| doc)
+                .collect(),
+        };
+        docs.sort();
+
+        self.total.set(self.total.get() + total as u64);
+        self.kept.set(self.kept.get() + docs.len() as u64);
+        Ok(docs)
+    }
+}
+
+/// A `Fields` view of a segment's postings with the low impact postings removed.
+struct PrunedFields<T: Fields, D: Directory + 'static, C: Codec> {
+    fields: T,
+    pruners: HashMap<String, Rc<FieldPruner<D, C>>>,
+}
+
+impl<T: Fields, D: Directory + 'static, C: Codec> PrunedFields<T, D, C> {
+    fn new<P: SimilarityProducer<C>>(
+        fields: T,
+        reader: Arc<SegmentReader<D, C>>,
+        policy: PruningPolicy,
+        similarity: &P,
+        pruned_fields: Option<&HashSet<String>>,
+    ) -> Result<Self> {
+        let mut pruners = HashMap::new();
+        for field in fields.fields() {
+            if pruned_fields.map_or(false, |f| !f.contains(&field)) {
+                continue;
+            }
+            if let Some(terms) = fields.terms(&field)? {
+                let pruner = FieldPruner::new(
+                    &field,
+                    &terms,
+                    Arc::clone(&reader),
+                    policy,
+                    similarity.create(&field),
+                )?;
+                pruners.insert(field, Rc::new(pruner));
+            }
+        }
+        Ok(PrunedFields { fields, pruners })
+    }
+
+    fn counts(&self) -> (u64, u64) {
+        self.pruners.values().fold((0, 0), |(total, kept), p| {
+            (total + p.total.get(), kept + p.kept.get())
+        })
+    }
+}
+
+impl<T: Fields, D: Directory + 'static, C: Codec> Fields for PrunedFields<T, D, C> {
+    type Terms = PrunedTerms<T::Terms, D, C>;
+
+    fn fields(&self) -> Vec<String> {
+        self.fields.fields()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<Self::Terms>> {
+        Ok(self.fields.terms(field)?.map(|terms| PrunedTerms {
+            terms,
+            pruner: self.pruners.get(field).cloned(),
+        }))
+    }
+
+    fn size(&self) -> usize {
+        self.fields.size()
+    }
+}
+
+struct PrunedTerms<T: Terms, D: Directory + 'static, C: Codec> {
+    terms: T,
+    pruner: Option<Rc<FieldPruner<D, C>>>,
+}
+
+impl<T: Terms, D: Directory + 'static, C: Codec> Terms for PrunedTerms<T, D, C> {
+    type Iterator = PrunedTermIterator<T::Iterator, D, C>;
+
+    fn iterator(&self) -> Result<Self::Iterator> {
+        Ok(PrunedTermIterator {
+            iter: self.terms.iterator()?,
+            pruner: self.pruner.clone(),
+        })
+    }
+
+    // the statistics below are those of the unpruned field, the writer
+    // recomputes them from the postings it actually writes.
+    fn size(&self) -> Result<i64> {
+        self.terms.size()
+    }
+
+    fn sum_total_term_freq(&self) -> Result<i64> {
+        self.terms.sum_total_term_freq()
+    }
+
+    fn sum_doc_freq(&self) -> Result<i64> {
+        self.terms.sum_doc_freq()
+    }
+
+    fn doc_count(&self) -> Result<i32> {
+        self.terms.doc_count()
+    }
+
+    fn has_freqs(&self) -> Result<bool> {
+        self.terms.has_freqs()
+    }
+
+    fn has_offsets(&self) -> Result<bool> {
+        self.terms.has_offsets()
+    }
+
+    fn has_positions(&self) -> Result<bool> {
+        self.terms.has_positions()
+    }
+
+    fn has_payloads(&self) -> Result<bool> {
+        self.terms.has_payloads()
+    }
+}
+
+struct PrunedTermIterator<T: TermIterator, D: Directory + 'static, C: Codec> {
+    iter: T,
+    pruner: Option<Rc<FieldPruner<D, C>>>,
+}
+
+impl<T: TermIterator, D: Directory + 'static, C: Codec> TermIterator
+    for PrunedTermIterator<T, D, C>
+{
+    type Postings = PrunedPostingIterator<T::Postings>;
+    type TermState = T::TermState;
+
+    fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        self.iter.next()
+    }
+
+    fn seek_ceil(&mut self, text: &[u8]) -> Result<SeekStatus> {
+        self.iter.seek_ceil(text)
+    }
+
+    fn seek_exact_ord(&mut self, ord: i64) -> Result<()> {
+        self.iter.seek_exact_ord(ord)
+    }
+
+    fn term(&self) -> Result<&[u8]> {
+        self.iter.term()
+    }
+
+    fn ord(&self) -> Result<i64> {
+        self.iter.ord()
+    }
+
+    fn doc_freq(&mut self) -> Result<i32> {
+        self.iter.doc_freq()
+    }
+
+    fn total_term_freq(&mut self) -> Result<i64> {
+        self.iter.total_term_freq()
+    }
+
+    fn postings_with_flags(&mut self, flags: u16) -> Result<Self::Postings> {
+        let kept = match self.pruner {
+            Some(ref pruner) => Some(pruner.kept_docs(&mut self.iter)?),
+            None => None,
+        };
+        Ok(PrunedPostingIterator {
+            postings: self.iter.postings_with_flags(flags)?,
+            kept,
+            upto: 0,
+        })
+    }
+}
+
+/// Filters the wrapped postings down to the kept docs, positions, offsets and
+/// payloads of a kept doc are those of the wrapped postings.
+struct PrunedPostingIterator<T: PostingIterator> {
+    postings: T,
+    // None if the field is not pruned
+    kept: Option<Vec<DocId>>,
+    upto: usize,
+}
+
+impl<T: PostingIterator> DocIterator for PrunedPostingIterator<T> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        match self.kept {
+            Some(ref kept) => {
+                if self.upto < kept.len() {
+                    let target = kept[self.upto];
+                    self.upto += 1;
+                    self.postings.advance(target)
+                } else {
+                    self.postings.advance(NO_MORE_DOCS)
+                }
+            }
+            None => self.postings.next(),
+        }
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.slow_advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        match self.kept {
+            Some(ref kept) => kept.len(),
+            None => self.postings.cost(),
+        }
+    }
+}
+
+impl<T: PostingIterator> PostingIterator for PrunedPostingIterator<T> {
+    fn freq(&self) -> Result<i32> {
+        self.postings.freq()
+    }
+
+    fn next_position(&mut self) -> Result<i32> {
+        self.postings.next_position()
+    }
+
+    fn start_offset(&self) -> Result<i32> {
+        self.postings.start_offset()
+    }
+
+    fn end_offset(&self) -> Result<i32> {
+        self.postings.end_offset()
+    }
+
+    fn payload(&self) -> Result<Vec<u8>> {
+        self.postings.payload()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::{Analyzer, SimpleAnalyzer};
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter};
+    use core::index::{LeafReaderContext, StandardDirectoryReader, Term};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::path::PathBuf;
+
+    type TestReader = StandardDirectoryReader<
+        FSDirectory<NativeFSLockFactory>,
+        CodecEnum,
+        SerialMergeScheduler,
+        TieredMergePolicy,
+    >;
+
+    fn test_directory(name: &str) -> (PathBuf, Arc<FSDirectory<NativeFSLockFactory>>) {
+        let path = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let dir = FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap();
+        (path, Arc::new(dir))
+    }
+
+    fn document(id: i64, body: &str) -> Vec<Box<dyn Fieldable>> {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.stored = true;
+        id_type.index_options = IndexOptions::Docs;
+        id_type.omit_norms = true;
+        let mut body_type = FieldType::default();
+        body_type.stored = true;
+        body_type.index_options = IndexOptions::DocsAndFreqs;
+        let tokens = SimpleAnalyzer::default()
+            .token_stream("body", body)
+            .unwrap();
+        vec![
+            Box::new(Field::new(
+                "id".into(),
+                id_type,
+                Some(VariantValue::VString(id.to_string())),
+                None,
+            )),
+            Box::new(Field::new(
+                "body".into(),
+                body_type,
+                Some(VariantValue::VString(body.to_string())),
+                Some(tokens),
+            )),
+        ]
+    }
+
+    /// Builds a source index of two segments, the first one holds a deleted doc
+    /// which is the only doc of term "c".
+    fn source_index(
+        name: &str,
+        compound: bool,
+    ) -> (PathBuf, Arc<FSDirectory<NativeFSLockFactory>>) {
+        let (path, dir) = test_directory(name);
+        let mut config = IndexWriterConfig::default();
+        config.use_compound_file = compound;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        // the live docs have the same length, so the scores follow the freqs
+        writer.add_document(document(0, "a a a a b")).unwrap();
+        writer.add_document(document(1, "a a a b b")).unwrap();
+        writer.add_document(document(2, "a b b b b")).unwrap();
+        writer.add_document(document(3, "a a b b b c")).unwrap();
+        writer.commit().unwrap();
+        writer.add_document(document(4, "b b b b b")).unwrap();
+        writer
+            .delete_documents_by_terms(vec![Term::new("id".into(), b"3".to_vec())])
+            .unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+        (path, dir)
+    }
+
+    fn postings(leaf: &LeafReaderContext<CodecEnum>, field: &str, term: &str) -> Vec<(DocId, i32)> {
+        let term = Term::new(field.into(), term.as_bytes().to_vec());
+        let flags = i32::from(PostingIteratorFlags::FREQS);
+        let mut result = vec![];
+        if let Some(mut postings) = leaf.reader.postings(&term, flags).unwrap() {
+            loop {
+                let doc = postings.next().unwrap();
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                result.push((doc, postings.freq().unwrap()));
+            }
+        }
+        result
+    }
+
+    fn check_pruned(
+        name: &str,
+        policy: PruningPolicy,
+        compound: bool,
+        expected: &[Vec<(&str, Vec<(DocId, i32)>)>],
+        expected_stats: PruneStats,
+    ) {
+        let name = format!("rucene_index_pruner_{}_{}", name, compound);
+        let (source_path, source) = source_index(&format!("{}_source", name), compound);
+        let (target_path, target) = test_directory(&format!("{}_target", name));
+
+        let mut pruner = IndexPruner::new(policy);
+        pruner.set_fields(vec!["body".into()]);
+        let stats = pruner
+            .prune::<_, _, CodecEnum>(source, Arc::clone(&target))
+            .unwrap();
+        assert_eq!(stats, expected_stats);
+
+        let has_cfs = target
+            .list_all()
+            .unwrap()
+            .iter()
+            .any(|f| f.ends_with(".cfs"));
+        assert_eq!(has_cfs, compound);
+
+        let reader: TestReader = StandardDirectoryReader::open(target).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), expected.len());
+        for (leaf, expected_terms) in leaves.iter().zip(expected) {
+            for (term, docs) in expected_terms {
+                assert_eq!(&postings(leaf, "body", term), docs, "term {}", term);
+            }
+        }
+
+        // the live docs are copied, and the deleted doc is dropped from the
+        // pruned field only
+        assert_eq!(reader.max_doc(), 5);
+        assert_eq!(reader.num_docs(), 4);
+        let live_docs = leaves[0].reader.live_docs();
+        assert!(!live_docs.get(3).unwrap());
+        assert!(live_docs.get(2).unwrap());
+        assert!(postings(&leaves[0], "body", "c").is_empty());
+        assert_eq!(postings(&leaves[0], "id", "3"), vec![(3, 1)]);
+
+        let bodies = ["a a a a b", "a a a b b", "a b b b b", "", "b b b b b"];
+        for doc in &[0, 1, 2, 4] {
+            let document = reader
+                .document(*doc, &["id".to_string(), "body".to_string()])
+                .unwrap();
+            let value = |name: &str| {
+                document
+                    .fields
+                    .iter()
+                    .find(|f| f.field.name() == name)
+                    .and_then(|f| f.field.string_value())
+                    .map(|v| v.to_string())
+            };
+            assert_eq!(value("id"), Some(doc.to_string()));
+            assert_eq!(value("body"), Some(bodies[*doc as usize].to_string()));
+        }
+
+        drop(reader);
+        fs::remove_dir_all(&source_path).unwrap();
+        fs::remove_dir_all(&target_path).unwrap();
+    }
+
+    #[test]
+    fn test_prune_top_k_per_term() {
+        for &compound in &[true, false] {
+            check_pruned(
+                "per_term",
+                PruningPolicy::TopKPerTerm(1),
+                compound,
+                &[
+                    vec![("a", vec![(0, 4)]), ("b", vec![(2, 4)])],
+                    vec![("a", vec![]), ("b", vec![(0, 5)])],
+                ],
+                PruneStats {
+                    total_postings: 10,
+                    kept_postings: 3,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_prune_top_k_per_doc() {
+        for &compound in &[true, false] {
+            check_pruned(
+                "per_doc",
+                PruningPolicy::TopKPerDoc(1),
+                compound,
+                &[
+                    vec![("a", vec![(0, 4), (1, 3)]), ("b", vec![(2, 4)])],
+                    vec![("a", vec![]), ("b", vec![(0, 5)])],
+                ],
+                PruneStats {
+                    total_postings: 10,
+                    kept_postings: 4,
+                },
+            );
+        }
+    }
+
+    #[test]
+    fn test_prune_invalid_target() {
+        let (source_path, source) = source_index("rucene_index_pruner_invalid_source", true);
+        let (target_path, target) = test_directory("rucene_index_pruner_invalid_target");
+
+        let pruner = IndexPruner::new(PruningPolicy::TopKPerTerm(0));
+        assert!(pruner
+            .prune::<_, _, CodecEnum>(Arc::clone(&source), Arc::clone(&target))
+            .is_err());
+        assert!(target.list_all().unwrap().is_empty());
+
+        let pruner = IndexPruner::new(PruningPolicy::TopKPerTerm(2));
+        pruner
+            .prune::<_, _, CodecEnum>(Arc::clone(&source), Arc::clone(&target))
+            .unwrap();
+        // the target now holds a commit
+        assert!(pruner.prune::<_, _, CodecEnum>(source, target).is_err());
+
+        fs::remove_dir_all(&source_path).unwrap();
+        fs::remove_dir_all(&target_path).unwrap();
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::index_writer::IndexWriter;
+use core::index::merge_policy::MergePolicy;
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{
+    BinaryDocValuesRef, DocValuesReader, DocValuesType, LeafReader, NumericValuesReader,
+    SegmentReader, SortedDocValuesRef, SortedNumericValuesReader, SortedSetDocValuesRef,
+    NO_MORE_ORDS,
+};
+use core::store::Directory;
+use core::util::bit_set::{BitSet, FixedBitSet};
+use core::util::{BitsRef, DocId};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use fasthash::murmur3;
+
+use std::sync::Arc;
+
+/// Returns the shard out of `num_shards` a document with the `routing` value
+/// belongs to, the murmur3 hash of the value modulo the number of shards.
+///
+/// The documents indexed after a split must be routed with this function to
+/// land in the same shard as the split ones.
+pub fn routing_shard(routing: &[u8], num_shards: usize) -> usize {
+    debug_assert!(num_shards > 0);
+    murmur3::hash32(routing) as usize % num_shards
+}
+
+/// The number of live documents written to each shard by a split.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SplitStats {
+    pub docs_per_shard: Vec<u64>,
+}
+
+/// Splits an index into shards by the hash of the value of a routing field,
+/// see `routing_shard`, without reindexing the documents from the source
+/// data.
+///
+/// The routing value of a document is read from the doc values of the
+/// routing field: the bytes of a sorted or binary value, the big endian
+/// bytes of a numeric value, and the first value of a multi valued field.
+///
+/// A document without a routing value goes to the same shard as the next
+/// document of its segment having one, so the blocks added by
+/// `IndexWriter::add_documents` stay together as long as only their last,
+/// parent, document has a routing value. A live document without a routing
+/// value and no such document after it is an error.
+pub struct IndexSplitter {
+    routing_field: String,
+}
+
+impl IndexSplitter {
+    pub fn new(routing_field: &str) -> IndexSplitter {
+        IndexSplitter {
+            routing_field: routing_field.to_string(),
+        }
+    }
+
+    pub fn routing_field(&self) -> &str {
+        &self.routing_field
+    }
+
+    /// Adds the live documents of the segment `readers`, e.g. the ones of
+    /// `StandardDirectoryReader::segment_readers`, to the writer of their
+    /// shard, one writer per shard, and commits the writers.
+    ///
+    /// The documents are merged into the shards with
+    /// `IndexWriter::add_indexes_from_readers`, so they keep their order
+    /// unless the writers are configured with another index sort than the
+    /// source index, and the source index is left untouched.
+    pub fn split<D, C, MS, MP>(
+        &self,
+        readers: &[Arc<SegmentReader<D, C>>],
+        writers: &[IndexWriter<D, C, MS, MP>],
+    ) -> Result<SplitStats>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let num_shards = writers.len();
+        if num_shards == 0 {
+            bail!(IllegalArgument("split needs at least one writer".into()));
+        }
+
+        let mut shard_readers: Vec<Vec<Arc<SegmentReader<D, C>>>> = vec![vec![]; num_shards];
+        let mut stats = SplitStats {
+            docs_per_shard: vec![0; num_shards],
+        };
+        for reader in readers {
+            let shard_docs = self.route_segment(reader.as_ref(), num_shards)?;
+            for (shard, (live_docs, num_docs)) in shard_docs.into_iter().enumerate() {
+                if num_docs == 0 {
+                    continue;
+                }
+                stats.docs_per_shard[shard] += num_docs as u64;
+                let live_docs: BitsRef = Arc::new(live_docs);
+                shard_readers[shard].push(Arc::new(SegmentReader::build_from(
+                    Arc::clone(&reader.si),
+                    reader,
+                    live_docs,
+                    num_docs,
+                    false,
+                )?));
+            }
+        }
+
+        for (writer, readers) in writers.iter().zip(shard_readers) {
+            writer.add_indexes_from_readers(&readers)?;
+            writer.commit()?;
+        }
+        Ok(stats)
+    }
+
+    /// Computes the live docs of each shard in the segment, with their count.
+    fn route_segment<D, C>(
+        &self,
+        reader: &SegmentReader<D, C>,
+        num_shards: usize,
+    ) -> Result<Vec<(FixedBitSet, i32)>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+    {
+        let max_doc = reader.max_doc();
+        let mut shards: Vec<(FixedBitSet, i32)> = (0..num_shards)
+            .map(|_| (FixedBitSet::new(max_doc as usize), 0))
+            .collect();
+        let live_docs = reader.live_docs();
+        let mut routing = RoutingValues::new(reader, &self.routing_field)?;
+
+        // the docs without routing value waiting for the next routed doc
+        let mut pending: Vec<DocId> = vec![];
+        for doc in 0..max_doc {
+            let value = match routing {
+                Some(ref mut values) => values.read(doc)?,
+                None => None,
+            };
+            let value = match value {
+                Some(value) => value,
+                None => {
+                    pending.push(doc);
+                    continue;
+                }
+            };
+            let (ref mut bits, ref mut num_docs) = shards[routing_shard(&value, num_shards)];
+            pending.push(doc);
+            for doc in pending.drain(..) {
+                if live_docs.get(doc as usize)? {
+                    bits.set(doc as usize);
+                    *num_docs += 1;
+                }
+            }
+        }
+        for doc in pending {
+            if live_docs.get(doc as usize)? {
+                bail!(IllegalArgument(format!(
+                    "doc {} of segment {} has no value for the routing field {}",
+                    doc, reader.si.info.name, &self.routing_field
+                )));
+            }
+        }
+        Ok(shards)
+    }
+}
+
+/// Reads the routing value of the docs of a segment as bytes.
+enum RoutingValues {
+    Numeric(NumericValuesReader),
+    SortedNumeric(SortedNumericValuesReader),
+    Binary(BinaryDocValuesRef, BitsRef),
+    Sorted(SortedDocValuesRef),
+    SortedSet(SortedSetDocValuesRef),
+}
+
+impl RoutingValues {
+    fn new<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<Option<RoutingValues>> {
+        let doc_values_type = match reader.field_info(field) {
+            Some(info) => info.doc_values_type,
+            None => return Ok(None),
+        };
+        Ok(Some(match doc_values_type {
+            DocValuesType::Null => bail!(IllegalArgument(format!(
+                "the routing field {} has no doc values",
+                field
+            ))),
+            DocValuesType::Numeric => {
+                RoutingValues::Numeric(NumericValuesReader::new(reader, field)?)
+            }
+            DocValuesType::SortedNumeric => {
+                RoutingValues::SortedNumeric(SortedNumericValuesReader::new(reader, field)?)
+            }
+            DocValuesType::Binary => RoutingValues::Binary(
+                reader.get_binary_doc_values(field)?,
+                reader.get_docs_with_field(field)?,
+            ),
+            DocValuesType::Sorted => RoutingValues::Sorted(reader.get_sorted_doc_values(field)?),
+            DocValuesType::SortedSet => {
+                RoutingValues::SortedSet(reader.get_sorted_set_doc_values(field)?)
+            }
+        }))
+    }
+
+    fn read(&mut self, doc: DocId) -> Result<Option<Vec<u8>>> {
+        Ok(match *self {
+            RoutingValues::Numeric(ref mut values) => {
+                values.read(doc)?.map(|v| v.to_be_bytes().to_vec())
+            }
+            RoutingValues::SortedNumeric(ref mut values) => {
+                values.read(doc)?.map(|vs| vs[0].to_be_bytes().to_vec())
+            }
+            RoutingValues::Binary(ref values, ref docs_with_field) => {
+                if docs_with_field.get(doc as usize)? {
+                    Some(values.get(doc)?)
+                } else {
+                    None
+                }
+            }
+            RoutingValues::Sorted(ref values) => {
+                let ord = values.get_ord(doc)?;
+                if ord >= 0 {
+                    Some(values.lookup_ord(ord)?)
+                } else {
+                    None
+                }
+            }
+            RoutingValues::SortedSet(ref values) => {
+                let mut ctx = values.set_document(doc)?;
+                let ord = values.next_ord(&mut ctx)?;
+                if ord != NO_MORE_ORDS {
+                    Some(values.lookup_ord(ord)?)
+                } else {
+                    None
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routing_shard() {
+        for num_shards in 1..8 {
+            let shard = routing_shard(b"user-42", num_shards);
+            assert!(shard < num_shards);
+            assert_eq!(shard, routing_shard(b"user-42", num_shards));
+        }
+        assert_eq!(routing_shard(b"user-42", 1), 0);
+    }
+}
@@ -11,10 +11,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::{Codec, CompoundFormat, FieldInfosFormat, LiveDocsFormat, SegmentInfoFormat};
+use core::codec::{
+    Codec, CompoundFormat, DocValuesConsumer, DocValuesFormat, FieldInfosFormat, LiveDocsFormat,
+    SegmentInfoFormat,
+};
 use core::index::bufferd_updates::BufferedUpdatesStream;
 use core::index::bufferd_updates::FrozenBufferedUpdates;
 use core::index::directory_reader::index_exist;
+use core::index::doc_values_writer::{
+    DocValuesFieldUpdates, DocValuesUpdate, UpdatedBytesIter, UpdatedValuesIter,
+};
 use core::index::doc_writer::{DocumentsWriter, Event};
 use core::index::index_file_deleter::IndexFileDeleter;
 use core::index::index_writer_config::{IndexWriterConfig, OpenMode};
@@ -25,33 +31,39 @@ use core::index::merge_state::{DocMap, MergeState};
 use core::index::segment_merger::SegmentMerger;
 use core::index::thread_doc_writer::FlushedSegment;
 use core::index::{
-    file_name_from_generation, get_last_commit_segments_filename, FieldInfos, FieldNumbers,
-    FieldNumbersRef, Fieldable, LeafReader, SegmentCommitInfo, SegmentInfo, SegmentInfos,
-    SegmentReader, StandardDirectoryReader, Term, INDEX_FILE_PENDING_SEGMENTS,
+    add_segment_bytes, file_name_from_generation, get_last_commit_segments_filename,
+    strip_segment_name, CommitReport, CompactionReport, DocValuesReader, DocValuesType, FieldInfo,
+    FieldInfos, FieldNumbers, FieldNumbersRef, Fieldable, IndexOptions, LeafReader,
+    NumericValuesReader, SegmentCommitInfo, SegmentInfo, SegmentInfos, SegmentReader,
+    SegmentWriteState, StandardDirectoryReader, Term, INDEX_FILE_PENDING_SEGMENTS,
 };
+use core::index::{FlushReport, IndexingThreadStats, MergeStartReport};
 use core::search::match_all::MATCH_ALL;
 use core::search::Query;
 use core::store::{
-    Directory, FlushInfo, IOContext, IndexInput, Lock, LockValidatingDirectoryWrapper,
+    Directory, FlushInfo, IOContext, IndexInput, Lock, LockValidatingDirectoryWrapper, MergeInfo,
     RateLimitIndexOutput, RateLimiter, TrackingDirectoryWrapper,
 };
 use core::util::bits::{Bits, BitsRef};
 use core::util::io::delete_file_ignoring_error;
 use core::util::string_util::random_id;
-use core::util::{to_base36, DerefWrapper, DocId, VERSION_LATEST};
+use core::util::{
+    to_base36, Accountable, DerefWrapper, DocId, Numeric, RamUsage, VariantValue, VERSION_LATEST,
+};
 
 use core::index::ErrorKind::MergeAborted;
 use error::ErrorKind::{AlreadyClosed, IllegalArgument, IllegalState, Index, RuntimeError};
 use error::{Error, Result};
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::fmt;
 use std::mem;
 use std::ops::Deref;
 use std::ptr;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU32, AtomicU64, Ordering};
-use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
-use std::time::{Duration, SystemTime};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock, Weak};
+use std::time::{Duration, Instant, SystemTime};
 
 use core::index::merge_rate_limiter::MergeRateLimiter;
 use thread_local::ThreadLocal;
@@ -209,6 +221,18 @@ where
         count
     }
 
+    /// Returns the number of docs buffered in RAM, not yet flushed to a
+    /// segment, see `IndexWriterConfig::set_flush_policy`.
+    pub fn num_ram_docs(&self) -> u32 {
+        self.writer.doc_writer.num_docs()
+    }
+
+    /// Returns the state of the indexing threads and of their flushes, e.g.
+    /// to tune `IndexWriterConfig::max_thread_states`.
+    pub fn indexing_stats(&self) -> IndexingThreadStats {
+        self.writer.doc_writer.indexing_stats()
+    }
+
     #[inline]
     /// Returns the Directory used by this index.
     pub fn directory(&self) -> &Arc<D> {
@@ -220,15 +244,34 @@ where
         IndexWriterInner::close(self)
     }
 
-    /// Close the <code>IndexWriter</code> without committing
-    /// any changes that have occurred since the last commit
-    /// (or since it was opened, if commit hasn't been called).
-    /// This removes any temporary files that had been created,
-    /// after which the state of the index will be the same as
-    /// it was when commit() was last called or when this
-    /// writer was first opened.  This also clears a previous
-    /// call to `#prepareCommit`.
-    /// @throws IOException if there is a low-level IO error
+    /// Tries right away to delete the files of the directory whose deletion
+    /// failed earlier, for instance because they were still open on Windows.
+    /// Returns the files which still couldn't be deleted.
+    pub fn delete_pending_files(&self) -> Result<HashSet<String>> {
+        self.writer.directory.delete_pending_files()?;
+        self.writer.directory.pending_deletions()
+    }
+
+    /// Expert: asks the deletion policy again which commits to delete and
+    /// removes their files, without waiting for the next commit. Useful
+    /// after releasing a snapshot of a `SnapshotDeletionPolicy` to free the
+    /// disk space right away.
+    pub fn delete_unused_files(&self) -> Result<()> {
+        self.writer.delete_unused_files()
+    }
+
+    /// Close the `IndexWriter` without committing any changes that have
+    /// occurred since the last commit (or since it was opened, if commit
+    /// hasn't been called).
+    ///
+    /// The running merges are aborted, the buffered docs and deletes are
+    /// dropped, and the segments flushed since the last commit are deleted
+    /// along with a pending `prepare_commit`, after which the state of the
+    /// index will be the same as it was when `commit()` was last called or
+    /// when this writer was first opened. The write lock is released, so a
+    /// new writer can be opened on the directory to retry a failed batch.
+    ///
+    /// Rolling back an already closed writer is a no-op.
     pub fn rollback(&self) -> Result<()> {
         self.writer.rollback()
     }
@@ -274,19 +317,16 @@ where
     }
 
     /// Updates a document by first deleting the document(s)
-    /// containing <code>term</code> and then adding the new
-    /// document.  The delete and then add are atomic as seen
-    /// by a reader on the same index (flush may happen only after
-    /// the add).
+    /// containing `term` and then adding the new document. The delete
+    /// and then add are atomic as seen by a reader on the same index
+    /// (flush may happen only after the add), so a reader never sees
+    /// both the old and the new version, nor neither of them.
     ///
-    /// @return The <a href="#sequence_number">sequence number</a>
-    /// for this operation
+    /// The delete only applies to the docs added before this call, the
+    /// new document is kept even if it contains `term`, which is usually
+    /// the case for an id field. With no `term` this is `add_document`.
     ///
-    /// @param term the term to identify the document(s) to be
-    /// deleted
-    /// @param doc the document to be added
-    /// @throws CorruptIndexException if the index is corrupt
-    /// @throws IOException if there is a low-level IO error
+    /// Returns the sequence number for this operation.
     pub fn update_document<F: Fieldable>(&self, doc: Vec<F>, term: Option<Term>) -> Result<u64> {
         IndexWriterInner::update_document(self, doc, term)
     }
@@ -295,40 +335,19 @@ where
     /// assigned document IDs, such that an external reader
     /// will see all or none of the documents.
     ///
-    /// <b>WARNING</b>: the index does not currently record
-    /// which documents were added as a block.  Today this is
-    /// fine, because merging will preserve a block. The order of
-    /// documents within a segment will be preserved, even when child
-    /// documents within a block are deleted. Most search features
-    /// (like result grouping and block joining) require you to
-    /// mark documents; when these documents are deleted these
-    /// search features will not work as expected. Obviously adding
-    /// documents to an existing block will require you the reindex
-    /// the entire block.
-    ///
-    /// However it's possible that in the future Lucene may
-    /// merge more aggressively re-order documents (for example,
-    /// perhaps to obtain better index compression), in which case
-    /// you may need to fully re-index your documents at that time.
-    ///
-    /// See `#addDocument(Iterable)` for details on
-    /// index and IndexWriter state after an Exception, and
-    /// flushing/merging temporary free space requirements.
-    ///
-    /// <b>NOTE</b>: tools that do offline splitting of an index
-    /// (for example, IndexSplitter in contrib) or
-    /// re-sorting of documents (for example, IndexSorter in
-    /// contrib) are not aware of these atomically added documents
-    /// and will likely break them up.  Use such tools at your
-    /// own risk!
-    ///
-    /// @return The <a href="#sequence_number">sequence number</a>
-    /// for this operation
+    /// The documents of a block are written contiguously, in the given
+    /// order, to the same segment, and the merges keep them contiguous as
+    /// they concatenate the docs of the merged segments, which is what the
+    /// block join queries rely on, e.g. with the parent doc last.
     ///
-    /// @throws CorruptIndexException if the index is corrupt
-    /// @throws IOException if there is a low-level IO error
+    /// <b>WARNING</b>: the index does not record which documents were
+    /// added as a block. An index sort reorders the docs of the flushed
+    /// and merged segments and so breaks the blocks up, as do the tools
+    /// splitting or re-sorting an index offline. Deleting a doc of a block
+    /// keeps the other ones in place, but adding documents to an existing
+    /// block requires to reindex the entire block, see `update_documents`.
     ///
-    /// @lucene.experimental
+    /// Returns the sequence number for this operation.
     pub fn add_documents<F: Fieldable>(&self, docs: Vec<Vec<F>>) -> Result<u64> {
         IndexWriterInner::update_documents(self, docs, None)
     }
@@ -338,13 +357,9 @@ where
     /// assigned document IDs, such that an external reader
     /// will see all or none of the documents.
     ///
-    /// See `#addDocuments()`.
-    ///
-    /// @return The <a href="#sequence_number">sequence number</a>
-    /// for this operation
+    /// See `add_documents`.
     ///
-    /// @throws CorruptIndexException if the index is corrupt
-    /// @throws IOException if there is a low-level IO error
+    /// Returns the sequence number for this operation.
     pub fn update_documents<F: Fieldable>(
         &self,
         docs: Vec<Vec<F>>,
@@ -368,16 +383,44 @@ where
         IndexWriterInner::delete_documents_by_terms(self, terms)
     }
 
-    /// Deletes the document(s) matching any of the provided queries.
-    /// All given deletes are applied and flushed atomically at the same time.
+    /// Updates a document's `NumericDocValues` for `field` to the
+    /// given `value`. You can only update fields that already exist in
+    /// the index, not add new fields through this method.
     ///
     /// @return The <a href="#sequence_number">sequence number</a>
     /// for this operation
     ///
-    /// @param queries array of queries to identify the documents
-    /// to be deleted
-    /// @throws CorruptIndexException if the index is corrupt
-    /// @throws IOException if there is a low-level IO error
+    /// @param term the term to identify the document(s) to be updated
+    /// @param field field name of the `NumericDocValues` field
+    /// @param value new value for the field
+    pub fn update_numeric_doc_value(&self, term: Term, field: &str, value: i64) -> Result<u64> {
+        IndexWriterInner::update_numeric_doc_value(self, term, field, value)
+    }
+
+    /// Updates a document's `BinaryDocValues` for `field` to the
+    /// given `value`. You can only update fields that already exist in
+    /// the index, not add new fields through this method.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    ///
+    /// @param term the term to identify the document(s) to be updated
+    /// @param field field name of the `BinaryDocValues` field
+    /// @param value new value for the field
+    pub fn update_binary_doc_value(&self, term: Term, field: &str, value: Vec<u8>) -> Result<u64> {
+        IndexWriterInner::update_binary_doc_value(self, term, field, value)
+    }
+
+    /// Deletes the document(s) matching any of the provided queries.
+    /// All given deletes are applied and flushed atomically at the same time.
+    ///
+    /// The queries are buffered along with the deleted terms, and only run
+    /// against the segments when the deletes are applied, i.e. on flush,
+    /// on a NRT reader open or on commit. A query only deletes the docs
+    /// added before it, the docs added after the call are kept even if they
+    /// match. A `MatchAllDocsQuery` drops all the docs as `delete_all` does.
+    ///
+    /// Returns the sequence number for this operation.
     pub fn delete_documents_by_queries(&self, queries: Vec<Arc<dyn Query<C>>>) -> Result<u64> {
         IndexWriterInner::delete_documents_by_queries(self, queries)
     }
@@ -473,6 +516,59 @@ where
         IndexWriterInner::force_merge(self, max_num_segments, do_wait)
     }
 
+    /// Adds all segments from the latest commit of each of the given
+    /// directories to this index, so indexes built separately, e.g. offline
+    /// shards, can be combined without re-indexing their documents.
+    ///
+    /// The segment files are copied as is and renamed for new segments of
+    /// this index, the source indexes are not modified. Their write lock is
+    /// held while the files are copied, so they must not be open by another
+    /// writer, which also prevents adding this writer's own directory.
+    ///
+    /// The segments must have been written by the codec of this writer, the
+    /// same field names must have the same doc values type and points config
+    /// as in this index, and if this index is sorted the segments must be
+    /// sorted the same way, otherwise an `IllegalArgument` error is returned
+    /// before any file is copied. Use `add_indexes_from_readers` to add the
+    /// segments of an unsorted index to a sorted one.
+    ///
+    /// The pending documents of this writer are flushed first, and the new
+    /// segments are only visible once committed, like a flushed segment. The
+    /// merge policy may then merge them with the existing segments.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    pub fn add_indexes<D1: Directory>(&self, dirs: &[Arc<D1>]) -> Result<u64> {
+        IndexWriterInner::add_indexes(self, dirs)
+    }
+
+    /// Merges the live documents of the provided segment readers into a new
+    /// segment of this index. Unlike `add_indexes`, which copies the files of
+    /// the segments as is, the documents are re-encoded through the codec of
+    /// this writer, so this can be used to migrate an index to the current
+    /// codec or to drop its deleted documents. The readers may be opened on
+    /// any index, e.g. with `StandardDirectoryReader::segment_readers`, and
+    /// are left open.
+    ///
+    /// The same field names must have the same doc values type and points
+    /// config as in this index. If this index is sorted, the documents of the
+    /// readers are sorted by the index sort while merged, through a
+    /// `SortingLeafReader`, so a legacy unsorted index can be converted to a
+    /// sorted one, to benefit from the early termination of the searches
+    /// sorted by the index sort, by adding its segment readers to a new
+    /// writer configured with `IndexWriterConfig::set_index_sort`.
+    ///
+    /// The pending documents of this writer are flushed first, and the new
+    /// segment is only visible once committed. Whether it is written as a
+    /// compound file is decided by the merge policy, like for a merged
+    /// segment.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    pub fn add_indexes_from_readers(&self, readers: &[Arc<SegmentReader<D, C>>]) -> Result<u64> {
+        IndexWriterInner::add_indexes_from_readers(self, readers)
+    }
+
     /// Returns true if there may be changes that have not been
     /// committed.  There are cases where this may return true
     /// when there are no actual "real" changes to the index,
@@ -486,6 +582,25 @@ where
         self.writer.has_uncommitted_changes()
     }
 
+    /// Expert: prepare for commit. This does the first phase of 2-phase
+    /// commit: all the changes are flushed and the new segments file is
+    /// written as a pending segments file, and everything is synced, but the
+    /// commit isn't visible to readers yet. Call `commit` to publish it, or
+    /// `rollback` to drop it along with all the changes since the last
+    /// commit, e.g. once the other resources of a transaction are prepared.
+    ///
+    /// Only one prepared commit may be pending, `close` fails while a
+    /// prepared commit is pending when `IndexWriterConfig#commit_on_close`.
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// of the last operation in the commit. All sequence numbers <= this
+    /// value will be reflected in the commit, and all others will not.
+    pub fn prepare_commit(&self) -> Result<i64> {
+        IndexWriterInner::prepare_commit(self)
+    }
+
+    /// Commits all pending changes, finishing a commit prepared by
+    /// `prepare_commit` if any, otherwise preparing and finishing a new one.
     pub fn commit(&self) -> Result<i64> {
         IndexWriterInner::commit(self)
     }
@@ -525,6 +640,11 @@ where
         Self { writer }
     }
 
+    /// Whether both handles refer to the same writer.
+    pub(crate) fn same_writer(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.writer, &other.writer)
+    }
+
     #[inline]
     pub(crate) fn reader_pool(&self) -> &ReaderPool<D, C, MS, MP> {
         &self.writer.reader_pool
@@ -618,6 +738,24 @@ where
     }
 }
 
+/// The memory buffered by the writer for the docs and the deletes not yet
+/// flushed, bounded by `IndexWriterConfig::ram_buffer_size_mb`.
+impl<D, C, MS, MP> Accountable for IndexWriter<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    fn ram_bytes_used(&self) -> usize {
+        self.writer.doc_writer.ram_bytes_used()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.writer.doc_writer.child_resources()
+    }
+}
+
 // TODO: maybe we should impl this for `IndexWriterInner`,
 // but currently, some of the methods called depends on the IndexWriter strust
 // instead of IndexWriterInner
@@ -728,7 +866,7 @@ pub(crate) struct IndexWriterInner<
 
     // Used only by commit and prepareCommit, below; lock order is commit_lock -> IW
     commit_lock: Mutex<()>,
-    rate_limiters: Arc<ThreadLocal<Arc<MergeRateLimiter>>>,
+    rate_limiters: Arc<MergeRateLimiters<MergeRateLimiter>>,
     // when unrecoverable disaster strikes, we populate this
     // with the reason that we had to close IndexWriter
     tragedy: Option<Error>,
@@ -778,6 +916,16 @@ where
     fn new(d: Arc<D>, conf: Arc<IndexWriterConfig<C, MS, MP>>) -> Result<Self> {
         let write_lock = Arc::from(d.obtain_lock(INDEX_WRITE_LOCK_NAME)?);
 
+        // files left pending delete, e.g. by a previous writer on Windows,
+        // could collide with the files this writer creates
+        d.delete_pending_files()?;
+        if !d.pending_deletions()?.is_empty() {
+            bail!(IllegalArgument(format!(
+                "directory {} still has pending deleted files, cannot initialize IndexWriter",
+                d
+            )));
+        }
+
         let directory = Arc::new(LockValidatingDirectoryWrapper::new(
             Arc::clone(&d),
             Arc::clone(&write_lock),
@@ -858,13 +1006,7 @@ where
         for info in &segment_infos.segments {
             let fis = read_field_infos(info.as_ref())?;
             for fi in fis.by_number.values() {
-                global_field_numbers.add_or_get(
-                    &fi.name,
-                    fi.number,
-                    fi.doc_values_type,
-                    fi.point_dimension_count,
-                    fi.point_num_bytes,
-                )?;
+                global_field_numbers.add_field_info(fi)?;
             }
         }
 
@@ -879,7 +1021,7 @@ where
         let mut deleter = IndexFileDeleter::new(
             Arc::clone(&d),
             Arc::clone(&directory),
-            // conf.index_deletion_policy(),
+            conf.index_deletion_policy(),
         );
         deleter.init(&files, &mut segment_infos, initial_index_exists, false)?;
 
@@ -1024,6 +1166,9 @@ where
             }
             Err(e) => {
                 error!("IW - hit error during NRT reader: {:?}", e);
+                if let Err(err) = Self::process_events(index_writer, false, true) {
+                    error!("process events failed after flush_and_open by: '{:?}'", err);
+                }
                 Err(e)
             }
         }
@@ -1203,12 +1348,48 @@ where
         new_segment: FlushedSegment<D, C>,
         global_packet: Option<FrozenBufferedUpdates<C>>,
     ) -> Result<()> {
+        let flushed = if self.config.events.is_some() {
+            Some((
+                Arc::clone(&new_segment.segment_info),
+                new_segment.del_count,
+                new_segment.flush_time,
+            ))
+        } else {
+            None
+        };
         let res = self.do_publish_flushed_segment(new_segment, global_packet);
         self.flush_count.fetch_add(1, Ordering::AcqRel);
         self.do_after_flush();
+        if res.is_ok() {
+            if let Some((info, del_count, duration)) = flushed {
+                self.flush_finished(&info, del_count, duration);
+            }
+        }
         res
     }
 
+    /// Reports a published flushed segment to the configured `IndexWriterEvents`.
+    fn flush_finished(&self, info: &SegmentCommitInfo<D, C>, del_count: u32, duration: Duration) {
+        let events = match self.config.events {
+            Some(ref events) => events,
+            None => return,
+        };
+        let mut bytes = BTreeMap::new();
+        if let Err(e) = add_segment_bytes(info, &mut bytes) {
+            warn!(
+                "compute size of segment {} failed by '{:?}'",
+                &info.info.name, e
+            );
+        }
+        events.flush_finished(&FlushReport {
+            segment: info.info.name.clone(),
+            num_docs: info.info.max_doc,
+            del_count,
+            bytes,
+            duration,
+        });
+    }
+
     fn do_publish_flushed_segment(
         &self,
         mut new_segment: FlushedSegment<D, C>,
@@ -1243,6 +1424,15 @@ where
             "publish sets new_segment del_gen={}, seg={}",
             next_gen, &new_segment.segment_info
         );
+        self.config.info("IW", || {
+            format!(
+                "publish flushed segment {} max_doc={} del_count={} del_gen={}",
+                &new_segment.segment_info.info.name,
+                new_segment.segment_info.info.max_doc,
+                new_segment.del_count,
+                next_gen
+            )
+        });
 
         new_segment
             .segment_info
@@ -1342,7 +1532,6 @@ where
             let l = lock.lock()?;
             let _ = self.abort_merges(l)?;
         }
-        self.rate_limiters = Arc::new(ThreadLocal::new());
         debug!("IW - rollback: done finish merges");
 
         // Must pre-close in case it increments changeCount so that we can then
@@ -1461,6 +1650,304 @@ where
         Ok(seq_no)
     }
 
+    fn add_indexes<D1: Directory>(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        dirs: &[Arc<D1>],
+    ) -> Result<u64> {
+        let writer = &index_writer.writer;
+        writer.ensure_open(true)?;
+
+        // hold the write lock of the source indexes so they can't change while
+        // their files are copied, this fails for our own directory
+        let mut locks = Vec::with_capacity(dirs.len());
+        for dir in dirs {
+            locks.push(dir.obtain_lock(INDEX_WRITE_LOCK_NAME)?);
+        }
+
+        Self::flush(index_writer, false, true)?;
+
+        // read and validate all the commits before copying any file
+        let mut commits: Vec<SegmentInfos<D1, C>> = Vec::with_capacity(dirs.len());
+        let mut total_max_doc = 0i64;
+        for dir in dirs {
+            let sis = SegmentInfos::read_latest_commit(dir)?;
+            for info in &sis.segments {
+                if let Some(index_sort) = writer.config.index_sort() {
+                    if info.info.index_sort() != Some(index_sort) {
+                        bail!(IllegalArgument(format!(
+                            "cannot change index sort from {:?} to {:?}, use \
+                             add_indexes_from_readers to sort the segments",
+                            info.info.index_sort(),
+                            index_sort
+                        )));
+                    }
+                }
+                let fis = read_field_infos(info.as_ref())?;
+                for fi in fis.by_number.values() {
+                    writer.global_field_numbers.add_field_info(fi)?;
+                }
+                total_max_doc += i64::from(info.info.max_doc());
+            }
+            commits.push(sis);
+        }
+        if writer.pending_num_docs.load(Ordering::Acquire) + total_max_doc > INDEX_MAX_DOCS as i64 {
+            bail!(IllegalArgument(format!(
+                "number of documents in the index cannot exceed {}",
+                INDEX_MAX_DOCS
+            )));
+        }
+
+        let mut infos = Vec::new();
+        let mut created_files = HashSet::new();
+        for (dir, sis) in dirs.iter().zip(&commits) {
+            for info in &sis.segments {
+                let new_name = writer.new_segment_name();
+                match writer.copy_segment_as_is(dir, info, &new_name, &mut created_files) {
+                    Ok(info) => infos.push(Arc::new(info)),
+                    Err(e) => {
+                        writer.delete_new_files(&created_files)?;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        let seq_no = {
+            let l = writer.lock.lock()?;
+            if let Err(e) = writer.ensure_open(true) {
+                writer.delete_new_files(&created_files)?;
+                return Err(e);
+            }
+            let seq_no = writer.doc_writer.delete_queue.next_sequence_number();
+            if writer.stop_merges {
+                // rollback is running, the copied segments are dropped
+                writer.delete_new_files(&created_files)?;
+                return Ok(seq_no);
+            }
+            if let Err(e) = writer.reserve_docs(total_max_doc) {
+                writer.delete_new_files(&created_files)?;
+                return Err(e);
+            }
+
+            let writer_mut = unsafe { writer.writer_mut(&l) };
+            // deletes buffered before this call don't apply to the new segments
+            let next_gen = writer.buffered_updates_stream.get_next_gen() as i64;
+            for info in infos {
+                info.set_buffered_deletes_gen(next_gen);
+                writer_mut.segment_infos.add(info);
+            }
+            writer_mut.check_point(&l)?;
+            writer_mut.doc_writer.last_seq_no = seq_no;
+            seq_no
+        };
+
+        Self::maybe_merge(index_writer, MergerTrigger::Explicit, None)?;
+        Ok(seq_no)
+    }
+
+    fn add_indexes_from_readers(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        readers: &[Arc<SegmentReader<D, C>>],
+    ) -> Result<u64> {
+        let writer = &index_writer.writer;
+        writer.ensure_open(true)?;
+
+        Self::flush(index_writer, false, true)?;
+
+        let mut num_docs = 0i64;
+        for reader in readers {
+            for fi in reader.field_infos.by_number.values() {
+                writer.global_field_numbers.add_field_info(fi)?;
+            }
+            num_docs += i64::from(reader.num_docs());
+        }
+        if writer.pending_num_docs.load(Ordering::Acquire) + num_docs > INDEX_MAX_DOCS as i64 {
+            bail!(IllegalArgument(format!(
+                "number of documents in the index cannot exceed {}",
+                INDEX_MAX_DOCS
+            )));
+        }
+        if num_docs == 0 {
+            return Ok(writer.doc_writer.delete_queue.next_sequence_number());
+        }
+
+        let segment_name = writer.new_segment_name();
+        let context = IOContext::Merge(MergeInfo::new(num_docs as u32, 0, true, None));
+        let mut diagnostics = HashMap::new();
+        diagnostics.insert("source".into(), "add_indexes_from_readers".into());
+        let mut si = SegmentInfo::new(
+            VERSION_LATEST.clone(),
+            &segment_name,
+            -1,
+            Arc::clone(&writer.directory_orig),
+            false,
+            Some(Arc::clone(&writer.config.codec)),
+            diagnostics,
+            random_id(),
+            HashMap::new(),
+            writer.config.index_sort().map(Clone::clone),
+        )?;
+
+        let tracking_dir = Arc::new(TrackingDirectoryWrapper::new(writer.directory.as_ref()));
+        let res = SegmentMerger::new(
+            readers.to_vec(),
+            &si,
+            Arc::clone(&tracking_dir),
+            FieldNumbersRef::new(Arc::clone(&writer.global_field_numbers)),
+            context.clone(),
+        )
+        .and_then(|mut merger| merger.merge());
+        if let Err(e) = res {
+            writer.delete_new_files(&tracking_dir.create_files())?;
+            return Err(e);
+        }
+        si.set_files(&tracking_dir.create_files())?;
+        let mut info = SegmentCommitInfo::new(si, 0, -1, -1, -1, HashMap::new(), HashSet::new());
+
+        let use_compound_file = {
+            let _l = writer.lock.lock()?;
+            writer.config.merge_policy().use_compound_file(
+                &writer.segment_infos,
+                &info,
+                index_writer,
+            )
+        };
+        if use_compound_file {
+            let files_to_remove = info.files();
+            let tracking_cfs_dir = TrackingDirectoryWrapper::new(writer.directory.as_ref());
+            if let Err(e) = writer.create_compound_file(&tracking_cfs_dir, &mut info.info, &context)
+            {
+                writer.delete_new_files(&files_to_remove)?;
+                return Err(e);
+            }
+            writer.delete_new_files(&files_to_remove)?;
+            info.info.set_use_compound_file();
+        }
+
+        // write the segment info after the compound file so it isn't part of it
+        if let Err(e) = writer.config.codec().segment_info_format().write(
+            &writer.directory,
+            &mut info.info,
+            &context,
+        ) {
+            writer.delete_new_files(&info.files())?;
+            return Err(e);
+        }
+
+        let seq_no = {
+            let l = writer.lock.lock()?;
+            if let Err(e) = writer.ensure_open(true) {
+                writer.delete_new_files(&info.files())?;
+                return Err(e);
+            }
+            let seq_no = writer.doc_writer.delete_queue.next_sequence_number();
+            if writer.stop_merges {
+                // rollback is running, the new segment is dropped
+                writer.delete_new_files(&info.files())?;
+                return Ok(seq_no);
+            }
+            if let Err(e) = writer.reserve_docs(num_docs) {
+                writer.delete_new_files(&info.files())?;
+                return Err(e);
+            }
+
+            let writer_mut = unsafe { writer.writer_mut(&l) };
+            let info = Arc::new(info);
+            info.set_buffered_deletes_gen(writer.buffered_updates_stream.get_next_gen() as i64);
+            writer_mut.segment_infos.add(info);
+            writer_mut.check_point(&l)?;
+            writer_mut.doc_writer.last_seq_no = seq_no;
+            seq_no
+        };
+
+        Self::maybe_merge(index_writer, MergerTrigger::Explicit, None)?;
+        Ok(seq_no)
+    }
+
+    /// Copies the files of a segment of another index to this index, renamed
+    /// for the segment `segment_name`, and returns the new segment. Only the
+    /// segment info file is rewritten since it holds the names of the files,
+    /// the other files only refer to the segment by its id, which is kept.
+    fn copy_segment_as_is<D1: Directory>(
+        &self,
+        dir: &Arc<D1>,
+        info: &SegmentCommitInfo<D1, C>,
+        segment_name: &str,
+        created_files: &mut HashSet<String>,
+    ) -> Result<SegmentCommitInfo<D, C>> {
+        let rename = |file: &String| format!("{}{}", segment_name, strip_segment_name(file));
+
+        let mut new_info = SegmentInfo::new(
+            info.info.version.clone(),
+            segment_name,
+            info.info.max_doc(),
+            Arc::clone(&self.directory_orig),
+            info.info.is_compound_file(),
+            Some(Arc::clone(info.info.codec())),
+            info.info.diagnostics.clone(),
+            info.info.id,
+            info.info.attributes.clone(),
+            info.info.index_sort.clone(),
+        )?;
+        new_info.set_files(info.info.files())?;
+
+        let dv_updates_files = info
+            .dv_updates_files()
+            .iter()
+            .map(|(field, files)| (*field, files.iter().map(rename).collect()))
+            .collect();
+        let field_infos_files = info.field_infos_files().iter().map(rename).collect();
+
+        let context = IOContext::Flush(FlushInfo::new(
+            info.info.max_doc() as u32,
+            info.size_in_bytes().max(0) as u64,
+        ));
+        let tracking_dir = Arc::new(TrackingDirectoryWrapper::new(self.directory.as_ref()));
+        let res =
+            info.info
+                .codec()
+                .segment_info_format()
+                .write(&tracking_dir, &mut new_info, &context);
+        let si_files = tracking_dir.create_files();
+        created_files.extend(si_files.iter().cloned());
+        res?;
+
+        for file in info.files() {
+            let new_file = rename(&file);
+            // the segment info file was written above
+            if si_files.contains(&new_file) {
+                continue;
+            }
+            created_files.insert(new_file.clone());
+            self.directory
+                .copy_from(Arc::clone(dir), &file, &new_file, &context)?;
+        }
+
+        Ok(SegmentCommitInfo::new(
+            new_info,
+            info.del_count(),
+            info.del_gen(),
+            info.field_infos_gen(),
+            info.doc_values_gen(),
+            dv_updates_files,
+            field_infos_files,
+        ))
+    }
+
+    fn reserve_docs(&self, num_docs: i64) -> Result<()> {
+        if self.pending_num_docs.fetch_add(num_docs, Ordering::AcqRel) + num_docs
+            > INDEX_MAX_DOCS as i64
+        {
+            // Reserve failed: put the docs back and throw exc:
+            self.pending_num_docs.fetch_sub(num_docs, Ordering::AcqRel);
+            bail!(IllegalArgument(format!(
+                "number of documents in the index cannot exceed {}",
+                INDEX_MAX_DOCS
+            )));
+        }
+        Ok(())
+    }
+
     /// Called whenever the SegmentInfos has been updated and the index files
     /// referenced exist (correctly) in the index directory.
     fn check_point(&mut self, lock: &MutexGuard<()>) -> Result<()> {
@@ -1559,6 +2046,10 @@ where
             }
             merge.register_done = false;
         }
+        if !merge.referenced_files.is_empty() {
+            let files = mem::replace(&mut merge.referenced_files, HashSet::new());
+            self.deleter.dec_ref_without_error(&files);
+        }
         debug_assert!(self.running_merges.contains_key(&merge.id));
         self.running_merges.remove(&merge.id);
     }
@@ -1593,6 +2084,13 @@ where
 
     /// Used internally to throw an `AlreadyClosedException` if this
     /// IndexWriter has been closed or is in the process of closing.
+    fn delete_unused_files(&self) -> Result<()> {
+        self.ensure_open(false)?;
+        let l = self.lock.lock()?;
+        let writer = unsafe { self.writer_mut(&l) };
+        writer.deleter.revisit_policy()
+    }
+
     fn ensure_open(&self, fail_if_closing: bool) -> Result<()> {
         if self.closed.load(Ordering::Acquire)
             || (fail_if_closing && self.closing.load(Ordering::Acquire))
@@ -1775,8 +2273,33 @@ where
             || self.buffered_updates_stream.any()
     }
 
+    fn prepare_commit(index_writer: &IndexWriter<D, C, MS, MP>) -> Result<i64> {
+        index_writer.writer.ensure_open(true)?;
+        debug!("IW - prepare commit: start");
+
+        let mut do_maybe_merge = false;
+        let seq_no = {
+            let l = index_writer.writer.commit_lock.lock()?;
+            let writer = unsafe { index_writer.writer.writer_mut(&l) };
+            let seq_no = writer.prepare_commit_internal(&mut do_maybe_merge, index_writer, &l)?;
+            writer.pending_seq_no.store(seq_no, Ordering::Release);
+            seq_no
+        };
+
+        // we must do this outside of the commit lock else we can deadlock
+        if do_maybe_merge {
+            Self::maybe_merge(index_writer, MergerTrigger::FullFlush, None)?;
+        }
+        Ok(seq_no)
+    }
+
     fn commit(index_writer: &IndexWriter<D, C, MS, MP>) -> Result<i64> {
         debug!("IW - commit: start");
+        index_writer
+            .writer
+            .config
+            .info("IW", || "commit: start".into());
+        let start = Instant::now();
 
         let mut do_maybe_merge = false;
         let seq_no: i64;
@@ -1796,7 +2319,18 @@ where
                 index_writer.writer.pending_seq_no.load(Ordering::Acquire)
             };
 
+            let committing = index_writer.writer.pending_commit.is_some();
             writer.finish_commit()?;
+            if committing {
+                if let Some(ref events) = index_writer.writer.config.events {
+                    events.commit_finished(&CommitReport {
+                        generation: writer.segment_infos.last_generation,
+                        num_segments: writer.segment_infos.segments.len(),
+                        seq_no,
+                        duration: start.elapsed(),
+                    });
+                }
+            }
         }
 
         if do_maybe_merge {
@@ -1895,7 +2429,18 @@ where
         flush_success: &mut bool,
         any_segment_flushed: &mut bool,
     ) -> Result<SegmentInfos<D, C>> {
-        let (any_flushed, no) = self.doc_writer.flush_all_threads()?;
+        let res = self.doc_writer.flush_all_threads();
+        // events are processed even if the flush failed, so that the files it wrote get deleted
+        if let Err(e) = Self::process_events(index_writer, false, true) {
+            if res.is_ok() {
+                return Err(e);
+            }
+            error!(
+                "process events failed after flush_all_threads by: '{:?}'",
+                e
+            );
+        }
+        let (any_flushed, no) = res?;
         *seq_no = no;
         *any_segment_flushed = any_flushed;
         if !any_flushed {
@@ -1903,7 +2448,6 @@ where
             // the flush count if we flushed anything.
             self.flush_count.fetch_add(1, Ordering::AcqRel);
         }
-        Self::process_events(index_writer, false, true)?;
         *flush_success = true;
 
         let lock = Arc::clone(&self.lock);
@@ -1969,6 +2513,8 @@ where
 
             if self.pending_commit_change_count.load(Ordering::Acquire) > self.change_count() {
                 debug!("IW - skip start_commit(): no changes pending");
+                self.config
+                    .info("IW", || "skip start_commit(): no changes pending".into());
                 let res = self.deleter.dec_ref_batch((&self.files_to_commit).iter());
                 self.files_to_commit.clear();
                 return res;
@@ -2114,6 +2660,12 @@ where
             "IW - commit: done writing segments file {}",
             &committed_segments_file
         );
+        self.config.info("IW", || {
+            format!(
+                "commit: done writing segments file {}",
+                &committed_segments_file
+            )
+        });
 
         // NOTE: don't use this.checkpoint() here, because
         // we do not want to increment changeCount:
@@ -2181,6 +2733,13 @@ where
         index_writer.writer.do_before_flush();
 
         debug!("IW - start flush: apply_all_deletes={}", apply_deletes);
+        index_writer.writer.config.info("IW", || {
+            format!(
+                "start flush: apply_all_deletes={} ram_docs={}",
+                apply_deletes,
+                index_writer.writer.doc_writer.num_docs()
+            )
+        });
         // debug!("IW - index before flush");
 
         let mut any_changes = false;
@@ -2241,6 +2800,13 @@ where
             "IW: now apply all deletes for all segments, max_doc={}",
             self.doc_writer.num_docs() + self.segment_infos.total_max_doc() as u32
         );
+        self.config.info("IW", || {
+            format!(
+                "now apply all deletes for all segments, buffered_terms={} buffered_bytes={}",
+                self.buffered_updates_stream.num_terms(),
+                self.buffered_updates_stream.ram_bytes_used()
+            )
+        });
 
         let writer_mut = unsafe { self.writer_mut(l) };
 
@@ -2253,6 +2819,16 @@ where
 
         if !self.keep_fully_deleted_segments && !result.all_deleted.is_empty() {
             debug!("IW: drop 100% deleted segments.");
+            self.config.info("IW", || {
+                format!(
+                    "drop 100% deleted segments: {:?}",
+                    result
+                        .all_deleted
+                        .iter()
+                        .map(|s| &s.info.name)
+                        .collect::<Vec<_>>()
+                )
+            });
 
             for info in result.all_deleted {
                 // If a merge has already registered for this
@@ -2317,13 +2893,13 @@ where
     /// Deletes the document(s) matching any of the provided queries.
     /// All given deletes are applied and flushed atomically at the same time.
     ///
-    /// @return The <a href="#sequence_number">sequence number</a>
-    /// for this operation
+    /// The queries are buffered along with the deleted terms, and only run
+    /// against the segments when the deletes are applied, i.e. on flush,
+    /// on a NRT reader open or on commit. A query only deletes the docs
+    /// added before it, the docs added after the call are kept even if they
+    /// match. A `MatchAllDocsQuery` drops all the docs as `delete_all` does.
     ///
-    /// @param queries array of queries to identify the documents
-    /// to be deleted
-    /// @throws CorruptIndexException if the index is corrupt
-    /// @throws IOException if there is a low-level IO error
+    /// Returns the sequence number for this operation.
     fn delete_documents_by_queries(
         index_writer: &IndexWriter<D, C, MS, MP>,
         queries: Vec<Arc<dyn Query<C>>>,
@@ -2376,9 +2952,80 @@ where
     ///           if the index is corrupt
     /// @throws IOException
     ///           if there is a low-level IO error
-    #[allow(dead_code)]
-    fn update_numeric_doc_value(&mut self, _field: &str, _value: i64, _term: Term) -> Result<u64> {
-        unimplemented!()
+    fn update_numeric_doc_value(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        term: Term,
+        field: &str,
+        value: i64,
+    ) -> Result<u64> {
+        index_writer.writer.ensure_open(true)?;
+        if !index_writer
+            .writer
+            .global_field_numbers
+            .contains(field, DocValuesType::Numeric)
+        {
+            bail!(IllegalArgument(
+                "can only update existing numeric-docvalues fields!".into()
+            ));
+        }
+
+        let update = DocValuesUpdate::new(
+            DocValuesType::Numeric,
+            term,
+            field.to_string(),
+            VariantValue::Long(value),
+        );
+        Self::update_doc_values(index_writer, vec![update])
+    }
+
+    /// Updates a document's `BinaryDocValues` for <code>field</code> to the
+    /// given <code>value</code>. You can only update fields that already exist in
+    /// the index, not add new fields through this method.
+    ///
+    /// @param term
+    ///          the term to identify the document(s) to be updated
+    /// @param field
+    ///          field name of the `BinaryDocValues` field
+    /// @param value
+    ///          new value for the field
+    ///
+    /// @return The <a href="#sequence_number">sequence number</a>
+    /// for this operation
+    fn update_binary_doc_value(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        term: Term,
+        field: &str,
+        value: Vec<u8>,
+    ) -> Result<u64> {
+        index_writer.writer.ensure_open(true)?;
+        if !index_writer
+            .writer
+            .global_field_numbers
+            .contains(field, DocValuesType::Binary)
+        {
+            bail!(IllegalArgument(
+                "can only update existing binary-docvalues fields!".into()
+            ));
+        }
+
+        let update = DocValuesUpdate::new(
+            DocValuesType::Binary,
+            term,
+            field.to_string(),
+            VariantValue::Binary(value),
+        );
+        Self::update_doc_values(index_writer, vec![update])
+    }
+
+    fn update_doc_values(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        updates: Vec<DocValuesUpdate>,
+    ) -> Result<u64> {
+        let (seq_no, changed) = index_writer.writer.doc_writer.update_doc_values(updates)?;
+        if changed {
+            Self::process_events(index_writer, true, false)?;
+        }
+        Ok(seq_no)
     }
 
     pub fn new_segment_name(&self) -> String {
@@ -2492,9 +3139,10 @@ where
             // Now mark all pending & running merges for forced merge:
             for merge in &mut writer_mut.pending_merges {
                 merge.max_num_segments.set(Some(max_num_segments));
-                writer_mut
-                    .segments_to_merge
-                    .insert(Arc::clone(merge.info.as_ref().unwrap()), true);
+                // the info of a merge is only set once it starts
+                if let Some(ref info) = merge.info {
+                    writer_mut.segments_to_merge.insert(Arc::clone(info), true);
+                }
             }
             let new_running_merges = HashMap::with_capacity(writer_mut.running_merges.len());
             let mut running_merges =
@@ -2632,8 +3280,17 @@ where
                 merge
                     .estimated_merge_bytes
                     .update(|bytes| *bytes += (total_size as f64 * (1.0 - del_ratio)) as u64);
-                merge.total_merge_bytes = total_size as u64;
+                merge.total_merge_bytes += total_size as u64;
+            }
+        }
+
+        // Hold a reference on the files being merged, so that they are not
+        // deleted under the merge if the segments are dropped meanwhile
+        if !is_external {
+            for info in &merge.segments {
+                merge.referenced_files.extend(info.files());
             }
+            self.deleter.inc_ref_files(&merge.referenced_files);
         }
 
         // Merge is now registered
@@ -2680,10 +3337,20 @@ where
                 )?;
             }
         }
+        let aborted = match res {
+            Err(Error(Index(MergeAborted(_)), _)) => true,
+            // writes paused by the rate limiter fail with an io error once the merge is aborted
+            Err(_) => merge.rate_limiter.aborted(),
+            Ok(()) => false,
+        };
         match res {
-            Err(Error(Index(MergeAborted(_)), _)) => {
+            Err(_) if aborted => {
                 let segments: Vec<_> = merge.segments.iter().map(|s| &s.info.name).collect();
                 warn!("the merge for segments {:?} is aborted!", segments);
+                index_writer
+                    .writer
+                    .config
+                    .info("IW", || format!("merge of {:?} aborted", segments));
                 // the merge is aborted, ignore this error
                 Ok(())
             }
@@ -2696,20 +3363,23 @@ where
         index_writer: &IndexWriter<D, C, MS, MP>,
         merge: &mut OneMerge<D, C>,
     ) -> Result<()> {
-        index_writer
+        // a thread may run several merges, so writes must be limited by the
+        // rate limiter of the merge it is running now
+        let rate_limiter = index_writer
             .writer
             .rate_limiters
-            .get_or(|| Box::new(Arc::clone(&merge.rate_limiter)));
+            .get_or(|| Box::new(RefCell::new(None)));
+        rate_limiter.replace(Some(Arc::clone(&merge.rate_limiter)));
 
         // let t0 = SystemTime::now();
 
-        index_writer.writer.merge_init(merge)?;
-
-        trace!("IW - now merge");
-
-        Self::merge_middle(index_writer, merge)?;
+        let res = index_writer.writer.merge_init(merge).and_then(|_| {
+            trace!("IW - now merge");
+            Self::merge_middle(index_writer, merge)
+        });
+        rate_limiter.replace(None);
         // self.merge_success();
-        Ok(())
+        res.map(|_| ())
     }
 
     /// Does initial setup for a merge, which is fast but holds
@@ -2836,7 +3506,32 @@ where
     ) -> Result<i32> {
         merge.rate_limiter.check_abort()?;
 
+        index_writer.writer.config.info("IW", || {
+            format!(
+                "merge seg={} {:?} total_max_doc={} total_bytes={}",
+                &merge.info.as_ref().unwrap().info.name,
+                merge
+                    .segments
+                    .iter()
+                    .map(|s| s.info.name.as_str())
+                    .collect::<Vec<_>>(),
+                merge.total_max_doc,
+                merge.total_merge_bytes
+            )
+        });
+        if let Some(ref events) = index_writer.writer.config.events {
+            events.merge_started(&MergeStartReport {
+                segment: merge.info.as_ref().unwrap().info.name.clone(),
+                merged_segments: merge.segments.iter().map(|s| s.info.name.clone()).collect(),
+                total_max_doc: merge.total_max_doc,
+                total_bytes: merge.total_merge_bytes,
+            });
+        }
+
         let context = IOContext::Merge(merge.store_merge_info());
+        let start = Instant::now();
+        let mut bytes_before = BTreeMap::new();
+        let mut deletes_reclaimed = 0;
 
         let dir_wrapper = Arc::new(TrackingDirectoryWrapper::new(DerefWrapper(
             index_writer.writer.merge_directory.clone(),
@@ -2900,6 +3595,15 @@ where
 
             merge.readers.push(reader);
             debug_assert!(del_count <= merge.segments[seg_upto].info.max_doc);
+            deletes_reclaimed += del_count;
+            if index_writer.writer.config.events.is_some() {
+                if let Err(e) = add_segment_bytes(&merge.segments[seg_upto], &mut bytes_before) {
+                    warn!(
+                        "compute size of segment {} failed by '{:?}'",
+                        &merge.segments[seg_upto].info.name, e
+                    );
+                }
+            }
             seg_upto += 1;
         }
 
@@ -2918,7 +3622,14 @@ where
 
         // This is where all the work happens:
         if merger.should_merge() {
-            merger.merge()?;
+            if let Err(e) = merger.merge() {
+                // delete the partially merged files: they were never registered with IFD
+                let _l = index_writer.writer.lock.lock()?;
+                index_writer
+                    .writer
+                    .delete_new_files(&dir_wrapper.create_files())?;
+                return Err(e);
+            }
         }
         merger
             .merge_state
@@ -2929,9 +3640,12 @@ where
             // Merge would produce a 0-doc segment, so we do nothing except commit the merge to
             // remove all the 0-doc segments that we "merged":
             debug_assert_eq!(merger.merge_state.segment_info().max_doc, 0);
-            index_writer
+            if index_writer
                 .writer
-                .commit_merge(merge, &merger.merge_state)?;
+                .commit_merge(merge, &merger.merge_state)?
+            {
+                Self::merge_finished(index_writer, merge, bytes_before, deletes_reclaimed, start);
+            }
             return Ok(0);
         }
         debug_assert!(merger.merge_state.segment_info().max_doc > 0);
@@ -3021,6 +3735,21 @@ where
             }
         }
 
+        if index_writer.writer.pool_readers() {
+            if let Some(ref warmer) = index_writer.writer.config.merged_segment_warmer {
+                let info = merge.info.as_ref().unwrap();
+                if info.info.max_doc > 0 {
+                    let reader_pool = &index_writer.writer.reader_pool;
+                    let rld = reader_pool.get_or_create(info)?;
+                    let res = rld
+                        .get_readonly_clone(&IOContext::READ)
+                        .and_then(|reader| warmer.warm(&reader));
+                    reader_pool.release(&rld, false)?;
+                    res?;
+                }
+            }
+        }
+
         if !index_writer
             .writer
             .commit_merge(merge, &merger.merge_state)?
@@ -3028,10 +3757,55 @@ where
             // commitMerge will return false if this merge was aborted
             return Ok(0);
         }
+        index_writer.writer.config.info("IW", || {
+            format!(
+                "merged seg={} max_doc={} deletes_reclaimed={} took {:?}",
+                &merge.info.as_ref().unwrap().info.name,
+                merge.info.as_ref().unwrap().info.max_doc,
+                deletes_reclaimed,
+                start.elapsed()
+            )
+        });
+        Self::merge_finished(index_writer, merge, bytes_before, deletes_reclaimed, start);
 
         Ok(merge.info.as_ref().unwrap().info.max_doc)
     }
 
+    /// Reports a committed merge to the configured `IndexWriterEvents`.
+    fn merge_finished(
+        index_writer: &IndexWriter<D, C, MS, MP>,
+        merge: &OneMerge<D, C>,
+        bytes_before: BTreeMap<&'static str, u64>,
+        deletes_reclaimed: i32,
+        start: Instant,
+    ) {
+        let events = match index_writer.writer.config.events {
+            Some(ref events) => events,
+            None => return,
+        };
+        let info = merge.info.as_ref().unwrap();
+        let mut bytes_after = BTreeMap::new();
+        if info.info.max_doc > 0 {
+            if let Err(e) = add_segment_bytes(info, &mut bytes_after) {
+                warn!(
+                    "compute size of segment {} failed by '{:?}'",
+                    &info.info.name, e
+                );
+            }
+        }
+        let report = CompactionReport {
+            segment: info.info.name.clone(),
+            merged_segments: merge.segments.iter().map(|s| s.info.name.clone()).collect(),
+            docs_before: merge.segments.iter().map(|s| s.info.max_doc).sum(),
+            docs_after: info.info.max_doc,
+            deletes_reclaimed,
+            bytes_before,
+            bytes_after,
+            duration: start.elapsed(),
+        };
+        events.merge_finished(&report);
+    }
+
     /// Carefully merges deletes and updates for the segments we just merged. This
     /// is tricky because, although merging will clear all deletes (compacts the
     /// documents) and compact all the updates, new deletes and updates may have
@@ -3051,6 +3825,7 @@ where
 
         // lazy init (only when we find a delete to carry over):
         let mut holder = MergedDeletesAndUpdates::default();
+        let mut merged_dv_updates: HashMap<String, DocValuesFieldUpdates> = HashMap::new();
         debug_assert_eq!(merge.segments.len(), merge_state.doc_maps.len());
         for i in 0..merge.segments.len() {
             let info = &merge.segments[i];
@@ -3124,6 +3899,35 @@ where
                     }
                 }
             }
+
+            // carry over the doc values updates which occurred while merging,
+            // mapped to the docs of the merged segment:
+            for (field, updates) in inner.merging_dv_updates() {
+                let merged = merged_dv_updates
+                    .entry(field.clone())
+                    .or_insert_with(|| DocValuesFieldUpdates::new(updates.doc_values_type));
+                for (doc, value) in updates.iter() {
+                    let doc_id =
+                        merge_state.doc_maps[i].get(merge_state.leaf_doc_maps[i].get(doc)?)?;
+                    if doc_id >= 0 {
+                        merged.add(doc_id, value);
+                    }
+                }
+            }
+        }
+        for updates in merged_dv_updates.values_mut() {
+            updates.finish();
+        }
+
+        if merged_dv_updates.values().any(|u| u.any()) {
+            holder.init(&self.reader_pool, merge, false)?;
+            let rld = holder.merged_deletes_and_updates.as_ref().unwrap();
+            rld.create_reader_if_not_exist(&IOContext::READ)?;
+            rld.inner.lock()?.write_field_updates(
+                &rld.info,
+                &rld.info.info.directory,
+                &merged_dv_updates,
+            )?;
         }
 
         merge
@@ -3824,8 +4628,8 @@ where
     MS: MergeScheduler,
     MP: MergePolicy,
 {
-    // used for process DocValues update which is not currently implemented
-    _writer: Weak<IndexWriterInner<D, C, MS, MP>>,
+    // used to resolve the numbers of the fields updated on this segment
+    writer: Weak<IndexWriterInner<D, C, MS, MP>>,
     // Set once (null, and then maybe set, and never set again):
     reader: Option<Arc<SegmentReader<D, C>>>,
     // Holds the current shared (readable and writable)
@@ -3846,7 +4650,7 @@ where
     // That way, when the segment is done merging, IndexWriter can apply the
     // updates on the merged segment too.
     is_merging: bool,
-    // merging_dv_updates: HashMap<String, DocValuesFieldUpdates>,
+    merging_dv_updates: HashMap<String, DocValuesFieldUpdates>,
 }
 
 impl<D, C, MS, MP> ReadersAndUpdatesInner<D, C, MS, MP>
@@ -3858,12 +4662,13 @@ where
 {
     fn new(writer: Weak<IndexWriterInner<D, C, MS, MP>>) -> Self {
         ReadersAndUpdatesInner {
-            _writer: writer,
+            writer,
             reader: None,
             live_docs: None,
             pending_delete_count: 0,
             live_docs_shared: true,
             is_merging: false,
+            merging_dv_updates: HashMap::new(),
         }
     }
 
@@ -3876,12 +4681,13 @@ where
         let pending_delete_count = reader.num_deleted_docs();
         debug_assert!(pending_delete_count >= 0);
         ReadersAndUpdatesInner {
-            _writer: writer,
+            writer,
             reader: Some(Arc::new(reader)),
             live_docs: Some(live_docs),
             pending_delete_count: pending_delete_count as u32,
             live_docs_shared: true,
             is_merging: false,
+            merging_dv_updates: HashMap::new(),
         }
     }
 
@@ -4065,12 +4871,15 @@ where
     }
 
     fn drop_merging_updates(&mut self) {
-        // self.merging_dv_updates.clear();
+        self.merging_dv_updates.clear();
         self.is_merging = false;
     }
 
-    // TODO used for doc values update
-    #[allow(dead_code)]
+    /// Returns the updates registered while this segment was merging.
+    pub fn merging_dv_updates(&self) -> &HashMap<String, DocValuesFieldUpdates> {
+        &self.merging_dv_updates
+    }
+
     fn write_field_infos_gen<F: FieldInfosFormat>(
         &self,
         info: &Arc<SegmentCommitInfo<D, C>>,
@@ -4097,20 +4906,255 @@ where
             field_infos,
             &infos_context,
         )?;
-        info.advance_del_gen();
         Ok(tracking_dir.get_create_files())
     }
 
-    // Writes field updates (new _X_N updates files) to the directory
-    pub fn write_field_updates<DW: Directory>(&self, _dir: &DW) -> Result<()> {
-        unreachable!()
+    // Writes field updates (new _X_N updates files) to the directory, and
+    // replaces the reader by one seeing the updated values.
+    pub fn write_field_updates(
+        &mut self,
+        info: &Arc<SegmentCommitInfo<D, C>>,
+        dir: &Arc<D>,
+        updates: &HashMap<String, DocValuesFieldUpdates>,
+    ) -> Result<()> {
+        debug_assert!(self.reader.is_some());
+        if !updates.values().any(|u| u.any()) {
+            return Ok(());
+        }
+
+        // Do this so we can delete any created files on exceptions;
+        // this saves all codecs from having to do it:
+        let tracking_dir = Arc::new(TrackingDirectoryWrapper::new(Arc::clone(dir)));
+        let res = self.do_write_field_updates(info, dir, &tracking_dir, updates);
+        if let Err(e) = res {
+            // Advance only the next write gens so that a 2nd attempt to
+            // write will write to new files
+            info.set_next_write_doc_values_gen(info.next_write_doc_values_gen() + 1);
+            info.set_next_write_field_infos_gen(info.next_write_field_infos_gen() + 1);
+            for file_name in &tracking_dir.get_create_files() {
+                delete_file_ignoring_error(dir.as_ref(), file_name);
+            }
+            return Err(e);
+        }
+
+        if self.is_merging {
+            for (field, field_updates) in updates {
+                self.merging_dv_updates
+                    .entry(field.clone())
+                    .or_insert_with(|| DocValuesFieldUpdates::new(field_updates.doc_values_type))
+                    .merge(field_updates);
+            }
+        }
+        Ok(())
+    }
+
+    fn do_write_field_updates(
+        &mut self,
+        info: &Arc<SegmentCommitInfo<D, C>>,
+        dir: &Arc<D>,
+        tracking_dir: &Arc<TrackingDirectoryWrapper<D, Arc<D>>>,
+        updates: &HashMap<String, DocValuesFieldUpdates>,
+    ) -> Result<()> {
+        let reader = Arc::clone(self.reader());
+        let max_doc = info.info.max_doc();
+
+        // clone the latest field infos, the attributes are changed by the
+        // doc values format when the updates are written
+        let mut infos: BTreeMap<u32, FieldInfo> = BTreeMap::new();
+        for fi in reader.field_infos.by_number.values() {
+            let mut fi = fi.as_ref().clone();
+            let attributes = fi.attributes.read()?.clone();
+            fi.attributes = Arc::new(RwLock::new(attributes));
+            infos.insert(fi.number, fi);
+        }
+
+        let mut fields: Vec<&String> = updates.keys().collect();
+        fields.sort();
+        let mut dv_updates_files = HashMap::new();
+        for field in fields {
+            let field_updates = &updates[field];
+            if !field_updates.any() {
+                continue;
+            }
+            let existing = infos
+                .values()
+                .find(|fi| &fi.name == field)
+                .map(|fi| fi.number);
+            let number = match existing {
+                Some(number) => number,
+                None => {
+                    // the field never existed in this segment
+                    let number = match self.writer.upgrade() {
+                        Some(writer) => writer.global_field_numbers.field_number(field),
+                        None => None,
+                    };
+                    let number = match number {
+                        Some(number) => number,
+                        None => bail!(IllegalState(format!(
+                            "field {} is unknown to the writer",
+                            field
+                        ))),
+                    };
+                    let fi = FieldInfo::new(
+                        field.clone(),
+                        number,
+                        false,
+                        false,
+                        false,
+                        IndexOptions::Null,
+                        field_updates.doc_values_type,
+                        -1,
+                        HashMap::new(),
+                        0,
+                        0,
+                    )?;
+                    infos.insert(number, fi);
+                    number
+                }
+            };
+
+            let gen = info.next_write_doc_values_gen();
+            let fi = infos.get_mut(&number).unwrap();
+            fi.dv_gen = gen;
+
+            let files_before = tracking_dir.get_create_files();
+            {
+                let context = IOContext::Flush(FlushInfo::new(
+                    max_doc as u32,
+                    field_updates.ram_bytes_used() as u64,
+                ));
+                let state = SegmentWriteState::new(
+                    Arc::clone(tracking_dir),
+                    info.info.clone(),
+                    FieldInfos::new(vec![fi.clone()])?,
+                    None,
+                    context,
+                    to_base36(gen as u64),
+                );
+                let mut consumer = info
+                    .info
+                    .codec()
+                    .doc_values_format()
+                    .fields_consumer(&state)?;
+                // materialize the values of the field with the updates applied
+                match field_updates.doc_values_type {
+                    DocValuesType::Numeric => {
+                        let values = Self::updated_numeric_values(
+                            reader.as_ref(),
+                            field,
+                            field_updates,
+                            existing.is_some(),
+                        )?;
+                        consumer.add_numeric_field(fi, &mut UpdatedValuesIter::new(&values))?;
+                    }
+                    DocValuesType::Binary => {
+                        let values = Self::updated_binary_values(
+                            reader.as_ref(),
+                            field,
+                            field_updates,
+                            existing.is_some(),
+                        )?;
+                        consumer.add_binary_field(fi, &mut UpdatedBytesIter::new(&values))?;
+                    }
+                    t => bail!(IllegalArgument(format!(
+                        "can't update doc values of type {:?}",
+                        t
+                    ))),
+                }
+                // the consumer is closed on drop
+            }
+            let files: HashSet<String> = tracking_dir
+                .get_create_files()
+                .difference(&files_before)
+                .cloned()
+                .collect();
+            dv_updates_files.insert(number as i32, files);
+            info.advance_doc_values_gen();
+        }
+
+        let field_infos = FieldInfos::new(infos.into_iter().map(|(_, fi)| fi).collect())?;
+        let field_infos_files = self.write_field_infos_gen(
+            info,
+            &field_infos,
+            dir,
+            &info.info.codec().field_infos_format(),
+        )?;
+        info.advance_field_infos_gen();
+        info.set_field_infos_files(field_infos_files);
+        info.set_dv_updates_files(dv_updates_files);
+
+        // the new reader shares the core of the previous one and reads the
+        // new field infos
+        if self.live_docs.is_none() {
+            self.live_docs = Some(reader.live_docs());
+        }
+        let live_docs = Arc::clone(self.live_docs.as_ref().unwrap());
+        let num_docs = max_doc - info.del_count() - self.pending_delete_count as i32;
+        let new_reader =
+            SegmentReader::build_from(Arc::clone(info), &reader, live_docs, num_docs, false)?;
+        self.reader = Some(Arc::new(new_reader));
+        self.live_docs_shared = true;
+        Ok(())
+    }
+
+    fn updated_numeric_values(
+        reader: &SegmentReader<D, C>,
+        field: &str,
+        updates: &DocValuesFieldUpdates,
+        exists: bool,
+    ) -> Result<Vec<Numeric>> {
+        let mut values = vec![Numeric::Null; reader.max_doc() as usize];
+        if exists {
+            let mut current = NumericValuesReader::new(reader, field)?;
+            for (doc, value) in values.iter_mut().enumerate() {
+                if let Some(v) = current.read(doc as DocId)? {
+                    *value = Numeric::Long(v);
+                }
+            }
+        }
+        for (doc, value) in updates.iter() {
+            if let VariantValue::Long(v) = value {
+                values[doc as usize] = Numeric::Long(v);
+            }
+        }
+        Ok(values)
+    }
+
+    fn updated_binary_values(
+        reader: &SegmentReader<D, C>,
+        field: &str,
+        updates: &DocValuesFieldUpdates,
+        exists: bool,
+    ) -> Result<Vec<Vec<u8>>> {
+        let mut values = vec![Vec::with_capacity(0); reader.max_doc() as usize];
+        if exists {
+            let current = reader.get_binary_doc_values(field)?;
+            let docs_with_field = reader.get_docs_with_field(field)?;
+            for (doc, value) in values.iter_mut().enumerate() {
+                if docs_with_field.get(doc)? {
+                    *value = current.get(doc as DocId)?;
+                }
+            }
+        }
+        for (doc, value) in updates.iter() {
+            if let VariantValue::Binary(v) = value {
+                values[doc as usize] = v;
+            }
+        }
+        Ok(values)
     }
 }
 
+/// The rate limiter of the merge each thread is running, if any.
+type MergeRateLimiters<RL> = ThreadLocal<RefCell<Option<Arc<RL>>>>;
+
+/// Wraps the directory merges write to, so that the outputs they create are
+/// throttled by the rate limiter of the merge, which the merge scheduler
+/// updates while the merge runs.
 struct RateLimitFilterDirectory<D: Directory, RL: RateLimiter + ?Sized> {
     dir: Arc<D>,
     // reference to IndexWriter.rate_limiter
-    rate_limiter: Arc<ThreadLocal<Arc<RL>>>,
+    rate_limiter: Arc<MergeRateLimiters<RL>>,
 }
 
 impl<D, RL> RateLimitFilterDirectory<D, RL>
@@ -4118,7 +5162,7 @@ where
     D: Directory,
     RL: RateLimiter + ?Sized,
 {
-    pub fn new(dir: Arc<D>, rate_limiter: Arc<ThreadLocal<Arc<RL>>>) -> Self {
+    pub fn new(dir: Arc<D>, rate_limiter: Arc<MergeRateLimiters<RL>>) -> Self {
         RateLimitFilterDirectory { dir, rate_limiter }
     }
 }
@@ -4142,7 +5186,13 @@ where
 
     fn create_output(&self, name: &str, context: &IOContext) -> Result<Self::IndexOutput> {
         debug_assert!(context.is_merge());
-        let rate_limiter = Arc::clone(&self.rate_limiter.get().unwrap());
+        let rate_limiter = match self.rate_limiter.get().and_then(|r| r.borrow().clone()) {
+            Some(rate_limiter) => rate_limiter,
+            None => bail!(IllegalState(format!(
+                "can't create {}: no merge is running on this thread",
+                name
+            ))),
+        };
         let index_output = self.dir.create_output(name, context)?;
 
         Ok(RateLimitIndexOutput::new(rate_limiter, index_output))
@@ -4180,6 +5230,14 @@ where
     fn rename(&self, source: &str, dest: &str) -> Result<()> {
         self.dir.rename(source, dest)
     }
+
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        self.dir.pending_deletions()
+    }
+
+    fn delete_pending_files(&self) -> Result<()> {
+        self.dir.delete_pending_files()
+    }
 }
 
 impl<D, RL> Clone for RateLimitFilterDirectory<D, RL>
@@ -4257,3 +5315,428 @@ where
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::flush_policy::{FlushAction, FlushPolicy, FlushStats};
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::thread_doc_writer::UNSORTED_DIAGNOSTIC;
+    use core::index::{Fieldable, IndexOptions, IndexReader, StandardDirectoryReader};
+    use core::search::sort::Sort;
+    use core::search::sort_field::{SimpleSortField, SortField, SortFieldType};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    fn test_directory(name: &str) -> (PathBuf, Arc<FSDirectory<NativeFSLockFactory>>) {
+        let path = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let dir = FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap();
+        (path, Arc::new(dir))
+    }
+
+    fn document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.stored = true;
+        id_type.index_options = IndexOptions::Docs;
+        id_type.omit_norms = true;
+        vec![
+            Box::new(Field::new(
+                "id".into(),
+                id_type,
+                Some(VariantValue::VString(id.to_string())),
+                None,
+            )),
+            Box::new(NumericDocValuesField::new("value", id)),
+        ]
+    }
+
+    #[test]
+    fn test_add_document_after_delete_all() {
+        let (path, dir) = test_directory("rucene_index_writer_delete_all");
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..5 {
+            writer.add_document(document(i)).unwrap();
+        }
+        writer.commit().unwrap();
+
+        // the thread states are aborted by `delete_all`, new documents must
+        // still be accepted afterwards
+        writer.delete_all().unwrap();
+        writer.add_document(document(5)).unwrap();
+        writer.commit().unwrap();
+        assert_eq!(writer.num_docs(), 1);
+
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    /// Flushes a thread state every `max_docs` docs, and counts the docs.
+    #[derive(Debug)]
+    struct EveryDocsPolicy {
+        max_docs: u32,
+        inserts: AtomicUsize,
+    }
+
+    impl FlushPolicy for EveryDocsPolicy {
+        fn on_insert(&self, stats: &FlushStats) -> FlushAction {
+            self.inserts.fetch_add(1, Ordering::AcqRel);
+            if stats.thread_docs >= self.max_docs {
+                FlushAction::FlushThread
+            } else {
+                FlushAction::None
+            }
+        }
+
+        fn on_delete(&self, _stats: &FlushStats) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_custom_flush_policy() {
+        let (path, dir) = test_directory("rucene_index_writer_flush_policy");
+        let policy = Arc::new(EveryDocsPolicy {
+            max_docs: 3,
+            inserts: AtomicUsize::new(0),
+        });
+        let mut config = IndexWriterConfig::default();
+        config.set_flush_policy(Arc::clone(&policy) as Arc<dyn FlushPolicy>);
+        // the custom policy replaces the RAM and doc count triggers
+        config.set_ram_buffer_size(0.0);
+        config.validate().unwrap();
+        let writer = IndexWriter::new(dir, Arc::new(config)).unwrap();
+        for i in 0..10 {
+            writer.add_document(document(i)).unwrap();
+        }
+        writer.commit().unwrap();
+        assert_eq!(policy.inserts.load(Ordering::Acquire), 10);
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let mut max_docs: Vec<i32> = reader.leaves().iter().map(|l| l.reader.max_doc()).collect();
+        max_docs.sort();
+        assert_eq!(max_docs, vec![1, 3, 3, 3]);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_flushes() {
+        let (path, dir) = test_directory("rucene_index_writer_concurrent_flushes");
+        let mut config = IndexWriterConfig::default();
+        config.set_max_buffered_docs(Some(7)).unwrap();
+        let writer = Arc::new(IndexWriter::new(dir, Arc::new(config)).unwrap());
+
+        // the indexing threads flush their own segments while the full flushes
+        // of the commits below queue the others
+        let handles: Vec<_> = (0..4)
+            .map(|t| {
+                let writer = Arc::clone(&writer);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        writer.add_document(document(t * 100 + i)).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for _ in 0..5 {
+            writer.commit().unwrap();
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        writer.commit().unwrap();
+        assert_eq!(writer.num_docs(), 400);
+
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.num_docs(), 400);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_force_merge_doc_values() {
+        let (path, dir) = test_directory("rucene_index_writer_force_merge");
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..6 {
+            writer.add_document(document(i)).unwrap();
+            if i % 2 == 1 {
+                writer.commit().unwrap();
+            }
+        }
+
+        // the per field format attributes of the merged fields must not
+        // clash with the ones recorded when the merged values are written
+        writer.force_merge(1, true).unwrap();
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let values = leaves[0].reader.get_numeric_doc_values("value").unwrap();
+        let mut merged: Vec<i64> = (0..6).map(|doc| values.get(doc).unwrap()).collect();
+        merged.sort();
+        assert_eq!(merged, vec![0, 1, 2, 3, 4, 5]);
+
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    fn sorted_config() -> Arc<IndexWriterConfig<CodecEnum, SerialMergeScheduler, TieredMergePolicy>>
+    {
+        let mut config = IndexWriterConfig::default();
+        config
+            .set_index_sort(Sort::new(vec![SortField::Simple(SimpleSortField::new(
+                "value".into(),
+                SortFieldType::Long,
+                false,
+            ))]))
+            .unwrap();
+        Arc::new(config)
+    }
+
+    fn doc_values(
+        reader: &StandardDirectoryReader<
+            FSDirectory<NativeFSLockFactory>,
+            CodecEnum,
+            SerialMergeScheduler,
+            TieredMergePolicy,
+        >,
+    ) -> Vec<i64> {
+        let mut values = vec![];
+        for leaf in reader.leaves() {
+            let doc_values = leaf.reader.get_numeric_doc_values("value").unwrap();
+            for doc in 0..leaf.reader.max_doc() {
+                values.push(doc_values.get(doc).unwrap());
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn test_flushed_segments_sorted() {
+        let (path, dir) = test_directory("rucene_index_writer_sorted_flush");
+        let writer = IndexWriter::new(Arc::clone(&dir), sorted_config()).unwrap();
+        for &value in &[5, 3, 8, 1] {
+            writer.add_document(document(value)).unwrap();
+        }
+        writer.commit().unwrap();
+        // the doc values update is pending on the doc ids of the flushed
+        // segment, which is left unsorted until it's merged
+        for &value in &[9, 2, 7] {
+            writer.add_document(document(value)).unwrap();
+        }
+        writer
+            .update_numeric_doc_value(id_term(2), "value", 20)
+            .unwrap();
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let segments = reader.segment_readers();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].index_sort(), writer.config().index_sort());
+        assert!(!segments[0]
+            .si
+            .info
+            .diagnostics
+            .contains_key(UNSORTED_DIAGNOSTIC));
+        assert_eq!(segments[1].index_sort(), None);
+        assert_eq!(
+            segments[1].si.info.diagnostics[UNSORTED_DIAGNOSTIC],
+            "0 delete queries and 1 doc values updates pending on flush"
+        );
+        assert_eq!(doc_values(&reader), vec![1, 3, 5, 8, 9, 20, 7]);
+        drop(reader);
+
+        writer.force_merge(1, true).unwrap();
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 1);
+        assert_eq!(
+            reader.segment_readers()[0].index_sort(),
+            writer.config().index_sort()
+        );
+        assert_eq!(doc_values(&reader), vec![1, 3, 5, 7, 8, 9, 20]);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_add_indexes_from_readers_sorts_index() {
+        let (unsorted_path, unsorted_dir) = test_directory("rucene_index_writer_unsorted");
+        let writer = IndexWriter::new(
+            Arc::clone(&unsorted_dir),
+            Arc::new(IndexWriterConfig::default()),
+        )
+        .unwrap();
+        for (i, &value) in [5, 3, 8, 1, 9, 0, 7, 2, 6, 4].iter().enumerate() {
+            writer.add_document(document(value)).unwrap();
+            if i == 4 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.close().unwrap();
+
+        let unsorted = StandardDirectoryReader::open(unsorted_dir).unwrap();
+        assert_eq!(unsorted.leaves().len(), 2);
+        assert_eq!(doc_values(&unsorted), vec![5, 3, 8, 1, 9, 0, 7, 2, 6, 4]);
+
+        let (sorted_path, sorted_dir) = test_directory("rucene_index_writer_sorted");
+        let writer = IndexWriter::new(Arc::clone(&sorted_dir), sorted_config()).unwrap();
+        writer
+            .add_indexes_from_readers(unsorted.segment_readers())
+            .unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+
+        let sorted = StandardDirectoryReader::open(sorted_dir).unwrap();
+        assert_eq!(sorted.leaves().len(), 1);
+        assert_eq!(doc_values(&sorted), (0..10).collect::<Vec<i64>>());
+        // the stored fields follow the documents
+        for doc in 0..10 {
+            let document = sorted.document(doc, &["id".to_string()]).unwrap();
+            assert_eq!(
+                document.fields[0].field.string_value(),
+                Some(doc.to_string().as_str())
+            );
+        }
+
+        drop(unsorted);
+        drop(sorted);
+        fs::remove_dir_all(&unsorted_path).unwrap();
+        fs::remove_dir_all(&sorted_path).unwrap();
+    }
+
+    fn id_term(id: i64) -> Term {
+        Term::new("id".into(), id.to_string().into_bytes())
+    }
+
+    #[test]
+    fn test_update_numeric_doc_values() {
+        let (path, dir) = test_directory("rucene_index_writer_update_numeric");
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..10 {
+            writer.add_document(document(i)).unwrap();
+        }
+        writer.commit().unwrap();
+
+        // every commit writes a new generation of the updated field
+        writer
+            .update_numeric_doc_value(id_term(3), "value", -30)
+            .unwrap();
+        writer.commit().unwrap();
+        writer
+            .update_numeric_doc_value(id_term(7), "value", 70)
+            .unwrap();
+        // the last update of a document wins
+        writer
+            .update_numeric_doc_value(id_term(7), "value", 700)
+            .unwrap();
+        // a buffered document is updated when it's flushed
+        writer.add_document(document(10)).unwrap();
+        writer
+            .update_numeric_doc_value(id_term(10), "value", i64::max_value())
+            .unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+
+        let reader = StandardDirectoryReader::open(dir).unwrap();
+        assert_eq!(
+            doc_values(&reader),
+            vec![0, 1, 2, -30, 4, 5, 6, 700, 8, 9, i64::max_value()]
+        );
+
+        drop(reader);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_merge_updated_doc_values() {
+        let (path, dir) = test_directory("rucene_index_writer_merge_updates");
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..10 {
+            writer.add_document(document(i)).unwrap();
+            if i == 4 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.commit().unwrap();
+        for &(id, value) in &[(1, 100), (4, 400), (6, 600), (1, 1000)] {
+            writer
+                .update_numeric_doc_value(id_term(id), "value", value)
+                .unwrap();
+            writer.commit().unwrap();
+        }
+
+        // the merged segment keeps the values of the latest generations
+        writer.force_merge(1, true).unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+
+        let reader = StandardDirectoryReader::open(dir).unwrap();
+        assert_eq!(reader.leaves().len(), 1);
+        let mut values = doc_values(&reader);
+        values.sort();
+        assert_eq!(values, vec![0, 2, 3, 5, 7, 8, 9, 400, 600, 1000]);
+
+        drop(reader);
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_update_doc_values_keeps_deletes() {
+        let (path, dir) = test_directory("rucene_index_writer_update_deleted");
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..10 {
+            writer.add_document(document(i)).unwrap();
+        }
+        writer.commit().unwrap();
+        writer.delete_documents_by_terms(vec![id_term(2)]).unwrap();
+        writer.commit().unwrap();
+
+        // the updates write new field infos and doc values generations, the
+        // live docs of the previous commit stay the current ones
+        writer
+            .update_numeric_doc_value(id_term(5), "value", 50)
+            .unwrap();
+        writer.commit().unwrap();
+        let reader = StandardDirectoryReader::open(Arc::clone(&dir)).unwrap();
+        assert_eq!(reader.num_docs(), 9);
+        assert_eq!(doc_values(&reader)[5], 50);
+        drop(reader);
+
+        // deletes after an update are written to the next live docs generation
+        writer.delete_documents_by_terms(vec![id_term(7)]).unwrap();
+        writer
+            .update_numeric_doc_value(id_term(8), "value", 80)
+            .unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+
+        let reader = StandardDirectoryReader::open(dir).unwrap();
+        assert_eq!(reader.num_docs(), 8);
+        let live_docs = reader.leaves()[0].reader.live_docs();
+        let live: Vec<usize> = (0..10).filter(|&d| live_docs.get(d).unwrap()).collect();
+        assert_eq!(live, vec![0, 1, 3, 4, 5, 6, 8, 9]);
+        assert_eq!(doc_values(&reader), vec![0, 1, 2, 3, 4, 50, 6, 7, 80, 9]);
+
+        drop(reader);
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
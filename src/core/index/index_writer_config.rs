@@ -12,28 +12,46 @@
 // limitations under the License.
 
 use core::codec::{Codec, CodecEnum, Lucene62Codec};
-use core::index::delete_policy::KeepOnlyLastCommitDeletionPolicy;
+use core::index::delete_policy::{IndexDeletionPolicy, KeepOnlyLastCommitDeletionPolicy};
 use core::index::merge_policy::{MergePolicy, TieredMergePolicy};
 use core::index::merge_scheduler::MergeScheduler;
 use core::index::merge_scheduler::SerialMergeScheduler;
+use core::index::sorter::Sorter;
+use core::index::{FlushByRamOrCountsPolicy, FlushPolicy, IndexReader, IndexWriterEvents};
+use core::search::bm25_similarity::NormsEncoding;
 use core::search::sort::Sort;
+use core::search::sort_field::{SortField, SortFieldType};
+use core::util::{InfoStream, NoOutputInfoStream};
 
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::fmt;
 use std::sync::Arc;
 
-/// Holds all the configuration that is used to create an {@link IndexWriter}.
-/// Once {@link IndexWriter} has been created with this object, changes to this
-/// object will not affect the {@link IndexWriter} instance. For that, use
-/// {@link LiveIndexWriterConfig} that is returned from {@link IndexWriter#getConfig()}.
+/// Warms a newly merged segment before it's published to the near real-time
+/// readers, so that the first searches on it don't pay for loading its data,
+/// see `IndexWriterConfig::merged_segment_warmer`.
+pub trait IndexReaderWarmer<C: Codec>: Send + Sync {
+    fn warm(&self, reader: &dyn IndexReader<Codec = C>) -> Result<()>;
+}
+
+/// Holds all the configuration that is used to create an `IndexWriter`.
+/// Once `IndexWriter` has been created with this object, changes to this
+/// object will not affect the `IndexWriter` instance.
 ///
-/// All setter methods return {@link IndexWriterConfig} to allow chaining
-/// settings conveniently, for example:
+/// The config is best created with `IndexWriterConfigBuilder`, which checks
+/// the settings together when it's built, for example:
 ///
-/// <pre class="prettyprint">
-/// IndexWriterConfig conf = new IndexWriterConfig(analyzer);
-/// conf.setter1().setter2();
-/// </pre>
+/// ```ignore
+/// let config = IndexWriterConfig::builder()
+///     .merge_scheduler(ConcurrentMergeScheduler::default())
+///     .ram_buffer_size_mb(Some(64.0))
+///     .build()?;
+/// ```
 ///
-/// @see IndexWriter#getConfig()
+/// The `Display` of a config lists its effective settings.
+///
+/// @see IndexWriter#config()
 pub struct IndexWriterConfig<C: Codec, MS: MergeScheduler, MP: MergePolicy> {
     pub ram_buffer_size_mb: Option<f64>,
     pub use_compound_file: bool,
@@ -45,9 +63,35 @@ pub struct IndexWriterConfig<C: Codec, MS: MergeScheduler, MP: MergePolicy> {
     /// True if readers should be pooled.
     pub reader_pooling: bool,
     pub open_mode: OpenMode,
+    /// A DWPT buffering more than this is flushed, whatever the flush policy.
     pub per_thread_hard_limit_mb: u32,
+    /// The max number of DWPTs indexing concurrently, unbounded by default.
+    /// Each indexing thread gets its own DWPT until the max is reached, the
+    /// other threads then wait for a DWPT to be free.
+    pub max_thread_states: Option<u32>,
     pub codec: Arc<C>,
     pub commit_on_close: bool,
+    /// Notified of merges and other internal events of the writer.
+    pub events: Option<Arc<dyn IndexWriterEvents>>,
+    /// Decides when the buffered docs are flushed, a
+    /// `FlushByRamOrCountsPolicy` built from the RAM buffer size and the max
+    /// buffered docs and delete terms by default.
+    pub flush_policy: Option<Arc<dyn FlushPolicy>>,
+    /// Receives the detailed diagnostics of the writer, none by default.
+    pub info_stream: Arc<dyn InfoStream>,
+    /// Decides which commits to delete when a new commit is done,
+    /// `KeepOnlyLastCommitDeletionPolicy` by default. Older commits can be
+    /// retained with `KeepLastCommitsDeletionPolicy` or
+    /// `KeepYoungCommitsDeletionPolicy`.
+    pub index_deletion_policy: Arc<dyn IndexDeletionPolicy>,
+    /// Warms the newly merged segments before they are visible to the near
+    /// real-time readers, only used when the readers are pooled.
+    pub merged_segment_warmer: Option<Arc<dyn IndexReaderWarmer<C>>>,
+    /// How the length of the fields is encoded in their norms,
+    /// `NormsEncoding::Boosted` by default. The encoding is recorded with the
+    /// fields of each new segment, segments merged together are re-encoded
+    /// with the encoding of the last one.
+    pub norms_encoding: NormsEncoding,
     // pub similarity: Box<Similarity>,
 }
 
@@ -61,6 +105,14 @@ impl Default for IndexWriterConfig<CodecEnum, SerialMergeScheduler, TieredMergeP
     }
 }
 
+impl IndexWriterConfig<CodecEnum, SerialMergeScheduler, TieredMergePolicy> {
+    /// Returns a builder starting from the default config.
+    pub fn builder() -> IndexWriterConfigBuilder<CodecEnum, SerialMergeScheduler, TieredMergePolicy>
+    {
+        IndexWriterConfigBuilder::new(Arc::new(CodecEnum::Lucene62(Lucene62Codec::default())))
+    }
+}
+
 impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP> {
     pub fn new(codec: Arc<C>, merge_scheduler: MS, merge_policy: MP) -> Self {
         IndexWriterConfig {
@@ -75,8 +127,15 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
             reader_pooling: true,
             open_mode: OpenMode::CreateOrAppend,
             per_thread_hard_limit_mb: DEFAULT_RAM_PER_THREAD_HARD_LIMIT_MB,
+            max_thread_states: None,
             codec,
             commit_on_close: true,
+            events: None,
+            flush_policy: None,
+            info_stream: Arc::new(NoOutputInfoStream),
+            index_deletion_policy: Arc::new(KeepOnlyLastCommitDeletionPolicy),
+            merged_segment_warmer: None,
+            norms_encoding: NormsEncoding::default(),
             // similarity: Box::new(BM25Similarity::default()),
         }
     }
@@ -92,6 +151,8 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
         (self.ram_buffer_size_mb() * 1024.0 * 1024.0) as usize
     }
 
+    /// Sets the amount of RAM in MB the buffered docs and deletes may use
+    /// before they are flushed, a size `<= 0` disables flushing by RAM.
     pub fn set_ram_buffer_size(&mut self, size: f64) {
         if size <= 0.0 {
             self.ram_buffer_size_mb = None;
@@ -100,14 +161,61 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
         }
     }
 
+    /// Same as `set_ram_buffer_size`, but fails instead of disabling the RAM
+    /// buffer when the max buffered docs are disabled too.
+    pub fn try_set_ram_buffer_size(&mut self, size: f64) -> Result<()> {
+        if size <= 0.0 && self.max_buffered_docs.is_none() {
+            bail!(IllegalArgument(
+                "at least one of ram buffer size and max buffered docs must be enabled".into()
+            ));
+        }
+        self.set_ram_buffer_size(size);
+        Ok(())
+    }
+
     pub fn max_buffered_delete_terms(&self) -> u32 {
         self.max_buffered_delete_terms.unwrap_or(0)
     }
 
+    /// Sets the number of buffered delete terms which triggers applying the
+    /// buffered deletes, `None` to only apply them by RAM.
+    pub fn set_max_buffered_delete_terms(&mut self, max: Option<u32>) -> Result<()> {
+        if let Some(max) = max {
+            if max < 1 {
+                bail!(IllegalArgument(
+                    "max buffered delete terms must at least be 1 when enabled".into()
+                ));
+            }
+        }
+        self.max_buffered_delete_terms = max;
+        Ok(())
+    }
+
     pub fn max_buffered_docs(&self) -> u32 {
         self.max_buffered_docs.unwrap_or(0)
     }
 
+    /// Sets the number of docs a thread buffers before they are flushed to
+    /// a new segment, `None` to only flush by RAM. The RAM buffer and the max
+    /// buffered docs can't both be disabled.
+    pub fn set_max_buffered_docs(&mut self, max: Option<u32>) -> Result<()> {
+        match max {
+            Some(max) if max < 2 => {
+                bail!(IllegalArgument(
+                    "max buffered docs must at least be 2 when enabled".into()
+                ));
+            }
+            None if self.ram_buffer_size_mb.is_none() => {
+                bail!(IllegalArgument(
+                    "at least one of ram buffer size and max buffered docs must be enabled".into()
+                ));
+            }
+            _ => {}
+        }
+        self.max_buffered_docs = max;
+        Ok(())
+    }
+
     pub fn flush_on_delete_terms(&self) -> bool {
         self.max_buffered_delete_terms.is_some()
     }
@@ -124,16 +232,201 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
         &self.merge_policy
     }
 
+    /// Returns this config with `merge_policy` instead of the current policy,
+    /// e.g. `NoMergePolicy` to keep the flushed segments.
+    pub fn with_merge_policy<P: MergePolicy>(self, merge_policy: P) -> IndexWriterConfig<C, MS, P> {
+        IndexWriterConfig {
+            ram_buffer_size_mb: self.ram_buffer_size_mb,
+            use_compound_file: self.use_compound_file,
+            max_buffered_delete_terms: self.max_buffered_delete_terms,
+            max_buffered_docs: self.max_buffered_docs,
+            merge_policy,
+            merge_scheduler: self.merge_scheduler,
+            index_sort: self.index_sort,
+            reader_pooling: self.reader_pooling,
+            open_mode: self.open_mode,
+            per_thread_hard_limit_mb: self.per_thread_hard_limit_mb,
+            max_thread_states: self.max_thread_states,
+            codec: self.codec,
+            commit_on_close: self.commit_on_close,
+            events: self.events,
+            flush_policy: self.flush_policy,
+            info_stream: self.info_stream,
+            index_deletion_policy: self.index_deletion_policy,
+            merged_segment_warmer: self.merged_segment_warmer,
+            norms_encoding: self.norms_encoding,
+        }
+    }
+
+    /// Returns this config with `merge_scheduler` instead of the current
+    /// scheduler, e.g. `SerialMergeScheduler` to run the merges synchronously
+    /// in the thread triggering them.
+    pub fn with_merge_scheduler<S: MergeScheduler>(
+        self,
+        merge_scheduler: S,
+    ) -> IndexWriterConfig<C, S, MP> {
+        IndexWriterConfig {
+            ram_buffer_size_mb: self.ram_buffer_size_mb,
+            use_compound_file: self.use_compound_file,
+            max_buffered_delete_terms: self.max_buffered_delete_terms,
+            max_buffered_docs: self.max_buffered_docs,
+            merge_policy: self.merge_policy,
+            merge_scheduler,
+            index_sort: self.index_sort,
+            reader_pooling: self.reader_pooling,
+            open_mode: self.open_mode,
+            per_thread_hard_limit_mb: self.per_thread_hard_limit_mb,
+            max_thread_states: self.max_thread_states,
+            codec: self.codec,
+            commit_on_close: self.commit_on_close,
+            events: self.events,
+            flush_policy: self.flush_policy,
+            info_stream: self.info_stream,
+            index_deletion_policy: self.index_deletion_policy,
+            merged_segment_warmer: self.merged_segment_warmer,
+            norms_encoding: self.norms_encoding,
+        }
+    }
+
     pub fn index_sort(&self) -> Option<&Sort> {
         self.index_sort.as_ref()
     }
 
+    /// Sets the order of the documents in the segments: flushed segments are
+    /// written sorted and merges preserve the order, so that searches sorted
+    /// the same way can terminate early, see
+    /// `EarlyTerminatingSortingCollector`.
+    ///
+    /// Only numeric doc values sort fields, of type Long, Int, Double or
+    /// Float, are supported.
+    pub fn set_index_sort(&mut self, sort: Sort) -> Result<()> {
+        Self::check_index_sort(&sort)?;
+        self.index_sort = Some(sort);
+        Ok(())
+    }
+
+    fn check_index_sort(sort: &Sort) -> Result<()> {
+        for field in sort.get_sort() {
+            let valid = match *field {
+                SortField::Simple(_) | SortField::SortedNumeric(_) => {
+                    match Sorter::sort_field_type(field) {
+                        SortFieldType::Long
+                        | SortFieldType::Int
+                        | SortFieldType::Double
+                        | SortFieldType::Float => true,
+                        _ => false,
+                    }
+                }
+                _ => false,
+            };
+            if !valid {
+                bail!(IllegalArgument(format!(
+                    "invalid index sort field: must be one of Int, Float, Long, Double but got: \
+                     {:?}",
+                    field
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the settings are valid together, see
+    /// `IndexWriterConfigBuilder::build`.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(size) = self.ram_buffer_size_mb {
+            if !(size > 0.0) {
+                bail!(IllegalArgument(format!(
+                    "ram buffer size must be greater than 0 when enabled, got {}",
+                    size
+                )));
+            }
+        }
+        if let Some(max) = self.max_buffered_docs {
+            if max < 2 {
+                bail!(IllegalArgument(
+                    "max buffered docs must at least be 2 when enabled".into()
+                ));
+            }
+        }
+        if self.max_buffered_delete_terms == Some(0) {
+            bail!(IllegalArgument(
+                "max buffered delete terms must at least be 1 when enabled".into()
+            ));
+        }
+        if self.ram_buffer_size_mb.is_none()
+            && self.max_buffered_docs.is_none()
+            && self.flush_policy.is_none()
+        {
+            bail!(IllegalArgument(
+                "at least one of ram buffer size and max buffered docs must be enabled".into()
+            ));
+        }
+        if self.per_thread_hard_limit_mb == 0 || self.per_thread_hard_limit_mb >= 2048 {
+            bail!(IllegalArgument(format!(
+                "per thread hard limit must be greater than 0 and less than 2048 MB, got {}",
+                self.per_thread_hard_limit_mb
+            )));
+        }
+        if self.max_thread_states == Some(0) {
+            bail!(IllegalArgument(
+                "max thread states must at least be 1 when enabled".into()
+            ));
+        }
+        if let Some(ref sort) = self.index_sort {
+            Self::check_index_sort(sort)?;
+        }
+        if self.merged_segment_warmer.is_some() && !self.reader_pooling {
+            bail!(IllegalArgument(
+                "a merged segment warmer requires reader pooling".into()
+            ));
+        }
+        Ok(())
+    }
+
     pub fn per_thread_hard_limit(&self) -> u64 {
         self.per_thread_hard_limit_mb as u64 * 1024 * 1024
     }
 
-    pub fn index_deletion_policy(&self) -> KeepOnlyLastCommitDeletionPolicy {
-        KeepOnlyLastCommitDeletionPolicy::default()
+    /// Sets the RAM in MB a single DWPT may use before it's flushed, it must
+    /// be in `(0, 2048)` MB.
+    pub fn set_per_thread_hard_limit_mb(&mut self, limit_mb: u32) -> Result<()> {
+        if limit_mb == 0 || limit_mb >= 2048 {
+            bail!(IllegalArgument(format!(
+                "per thread hard limit must be greater than 0 and less than 2048 MB, got {}",
+                limit_mb
+            )));
+        }
+        self.per_thread_hard_limit_mb = limit_mb;
+        Ok(())
+    }
+
+    pub fn max_thread_states(&self) -> Option<u32> {
+        self.max_thread_states
+    }
+
+    /// Sets the max number of DWPTs indexing concurrently, `None` for no
+    /// limit. Fewer DWPTs flush larger segments, at the cost of indexing
+    /// threads waiting for each other.
+    pub fn set_max_thread_states(&mut self, max: Option<u32>) -> Result<()> {
+        if max == Some(0) {
+            bail!(IllegalArgument(
+                "max thread states must at least be 1 when enabled".into()
+            ));
+        }
+        self.max_thread_states = max;
+        Ok(())
+    }
+
+    pub fn index_deletion_policy(&self) -> Arc<dyn IndexDeletionPolicy> {
+        Arc::clone(&self.index_deletion_policy)
+    }
+
+    pub fn set_index_deletion_policy(&mut self, policy: Arc<dyn IndexDeletionPolicy>) {
+        self.index_deletion_policy = policy;
+    }
+
+    pub fn set_merged_segment_warmer(&mut self, warmer: Arc<dyn IndexReaderWarmer<C>>) {
+        self.merged_segment_warmer = Some(warmer);
     }
 
     pub fn merge_scheduler(&self) -> MS {
@@ -144,11 +437,250 @@ impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfig<C, MS, MP>
         self.codec.as_ref()
     }
 
+    /// Returns the policy deciding when the buffered docs are flushed.
+    pub fn flush_policy(&self) -> Arc<dyn FlushPolicy> {
+        match self.flush_policy {
+            Some(ref policy) => Arc::clone(policy),
+            None => Arc::new(FlushByRamOrCountsPolicy::new(
+                self.ram_buffer_size_mb,
+                self.max_buffered_docs,
+                self.max_buffered_delete_terms,
+            )),
+        }
+    }
+
+    /// Sets a custom policy deciding when the buffered docs are flushed,
+    /// instead of flushing by the RAM buffer size and the max buffered docs.
+    pub fn set_flush_policy(&mut self, policy: Arc<dyn FlushPolicy>) {
+        self.flush_policy = Some(policy);
+    }
+
+    pub fn info_stream(&self) -> &dyn InfoStream {
+        self.info_stream.as_ref()
+    }
+
+    /// Sets the sink of the detailed diagnostics of the writer, its flushes
+    /// and its merges, e.g. `PrintStreamInfoStream::stdout()`.
+    pub fn set_info_stream(&mut self, info_stream: Arc<dyn InfoStream>) {
+        self.info_stream = info_stream;
+    }
+
+    /// Emits the message built by `message` to the info stream, only if
+    /// `component` is enabled.
+    pub(crate) fn info<F: FnOnce() -> String>(&self, component: &str, message: F) {
+        if self.info_stream.is_enabled(component) {
+            self.info_stream.message(component, &message());
+        }
+    }
+
+    pub fn events(&self) -> Option<&Arc<dyn IndexWriterEvents>> {
+        self.events.as_ref()
+    }
+
+    pub fn set_events(&mut self, events: Arc<dyn IndexWriterEvents>) {
+        self.events = Some(events);
+    }
+
     // pub fn similarity(&self) -> &Similarity {
     //     self.similarity.as_ref()
     // }
 }
 
+impl<C, MS, MP> fmt::Display for IndexWriterConfig<C, MS, MP>
+where
+    C: Codec,
+    MS: MergeScheduler + fmt::Debug,
+    MP: MergePolicy + fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn or_disabled<T: fmt::Display>(value: Option<T>) -> String {
+            value.map_or_else(|| "disabled".to_string(), |v| v.to_string())
+        }
+        fn set_or_none<T>(value: &Option<T>) -> &'static str {
+            if value.is_some() {
+                "set"
+            } else {
+                "none"
+            }
+        }
+        writeln!(f, "codec = {}", self.codec.name())?;
+        writeln!(f, "merge_policy = {:?}", self.merge_policy)?;
+        writeln!(f, "merge_scheduler = {:?}", self.merge_scheduler)?;
+        writeln!(f, "index_sort = {:?}", self.index_sort)?;
+        writeln!(f, "open_mode = {:?}", self.open_mode)?;
+        writeln!(f, "use_compound_file = {}", self.use_compound_file)?;
+        writeln!(f, "reader_pooling = {}", self.reader_pooling)?;
+        writeln!(f, "commit_on_close = {}", self.commit_on_close)?;
+        writeln!(
+            f,
+            "ram_buffer_size_mb = {}",
+            or_disabled(self.ram_buffer_size_mb)
+        )?;
+        writeln!(
+            f,
+            "max_buffered_docs = {}",
+            or_disabled(self.max_buffered_docs)
+        )?;
+        writeln!(
+            f,
+            "max_buffered_delete_terms = {}",
+            or_disabled(self.max_buffered_delete_terms)
+        )?;
+        writeln!(
+            f,
+            "per_thread_hard_limit_mb = {}",
+            self.per_thread_hard_limit_mb
+        )?;
+        writeln!(
+            f,
+            "max_thread_states = {}",
+            self.max_thread_states
+                .map_or_else(|| "unbounded".to_string(), |v| v.to_string())
+        )?;
+        writeln!(f, "flush_policy = {:?}", self.flush_policy())?;
+        writeln!(
+            f,
+            "merged_segment_warmer = {}",
+            set_or_none(&self.merged_segment_warmer)
+        )?;
+        writeln!(f, "norms_encoding = {:?}", self.norms_encoding)?;
+        write!(f, "events = {}", set_or_none(&self.events))
+    }
+}
+
+/// Builds an `IndexWriterConfig`, checking the settings together in `build`
+/// rather than one by one, see `IndexWriterConfig::validate`. The merge
+/// policy and scheduler may be changed to other types.
+pub struct IndexWriterConfigBuilder<C: Codec, MS: MergeScheduler, MP: MergePolicy> {
+    config: IndexWriterConfig<C, MS, MP>,
+}
+
+impl<C: Codec> IndexWriterConfigBuilder<C, SerialMergeScheduler, TieredMergePolicy> {
+    /// Starts from the default settings with the given codec.
+    pub fn new(codec: Arc<C>) -> Self {
+        IndexWriterConfigBuilder {
+            config: IndexWriterConfig::new(
+                codec,
+                SerialMergeScheduler {},
+                TieredMergePolicy::default(),
+            ),
+        }
+    }
+}
+
+impl<C: Codec, MS: MergeScheduler, MP: MergePolicy> IndexWriterConfigBuilder<C, MS, MP> {
+    pub fn merge_policy<P: MergePolicy>(
+        self,
+        merge_policy: P,
+    ) -> IndexWriterConfigBuilder<C, MS, P> {
+        IndexWriterConfigBuilder {
+            config: self.config.with_merge_policy(merge_policy),
+        }
+    }
+
+    pub fn merge_scheduler<S: MergeScheduler>(
+        self,
+        merge_scheduler: S,
+    ) -> IndexWriterConfigBuilder<C, S, MP> {
+        IndexWriterConfigBuilder {
+            config: self.config.with_merge_scheduler(merge_scheduler),
+        }
+    }
+
+    /// The order of the docs in the segments, see
+    /// `IndexWriterConfig::set_index_sort`.
+    pub fn index_sort(mut self, sort: Sort) -> Self {
+        self.config.index_sort = Some(sort);
+        self
+    }
+
+    /// `None` disables flushing by RAM.
+    pub fn ram_buffer_size_mb(mut self, size: Option<f64>) -> Self {
+        self.config.ram_buffer_size_mb = size;
+        self
+    }
+
+    /// `None` disables flushing by doc count.
+    pub fn max_buffered_docs(mut self, max: Option<u32>) -> Self {
+        self.config.max_buffered_docs = max;
+        self
+    }
+
+    /// `None` only applies the buffered deletes by RAM.
+    pub fn max_buffered_delete_terms(mut self, max: Option<u32>) -> Self {
+        self.config.max_buffered_delete_terms = max;
+        self
+    }
+
+    pub fn per_thread_hard_limit_mb(mut self, limit_mb: u32) -> Self {
+        self.config.per_thread_hard_limit_mb = limit_mb;
+        self
+    }
+
+    /// `None` for no limit.
+    pub fn max_thread_states(mut self, max: Option<u32>) -> Self {
+        self.config.max_thread_states = max;
+        self
+    }
+
+    pub fn flush_policy(mut self, policy: Arc<dyn FlushPolicy>) -> Self {
+        self.config.flush_policy = Some(policy);
+        self
+    }
+
+    pub fn use_compound_file(mut self, use_compound_file: bool) -> Self {
+        self.config.use_compound_file = use_compound_file;
+        self
+    }
+
+    pub fn reader_pooling(mut self, reader_pooling: bool) -> Self {
+        self.config.reader_pooling = reader_pooling;
+        self
+    }
+
+    pub fn open_mode(mut self, open_mode: OpenMode) -> Self {
+        self.config.open_mode = open_mode;
+        self
+    }
+
+    pub fn commit_on_close(mut self, commit_on_close: bool) -> Self {
+        self.config.commit_on_close = commit_on_close;
+        self
+    }
+
+    pub fn index_deletion_policy(mut self, policy: Arc<dyn IndexDeletionPolicy>) -> Self {
+        self.config.index_deletion_policy = policy;
+        self
+    }
+
+    pub fn merged_segment_warmer(mut self, warmer: Arc<dyn IndexReaderWarmer<C>>) -> Self {
+        self.config.merged_segment_warmer = Some(warmer);
+        self
+    }
+
+    pub fn info_stream(mut self, info_stream: Arc<dyn InfoStream>) -> Self {
+        self.config.info_stream = info_stream;
+        self
+    }
+
+    pub fn events(mut self, events: Arc<dyn IndexWriterEvents>) -> Self {
+        self.config.events = Some(events);
+        self
+    }
+
+    pub fn norms_encoding(mut self, norms_encoding: NormsEncoding) -> Self {
+        self.config.norms_encoding = norms_encoding;
+        self
+    }
+
+    /// Returns the config, or an `IllegalArgument` error naming the first
+    /// invalid setting.
+    pub fn build(self) -> Result<IndexWriterConfig<C, MS, MP>> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
 /// Denotes a flush trigger is disabled.
 pub const DISABLE_AUTO_FLUSH: i32 = -1;
 
@@ -178,3 +710,48 @@ pub enum OpenMode {
     Append,
     CreateOrAppend,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::index::merge_policy::NoMergePolicy;
+
+    #[test]
+    fn test_builder_validation() {
+        let config = IndexWriterConfig::builder()
+            .merge_policy(NoMergePolicy)
+            .ram_buffer_size_mb(None)
+            .max_buffered_docs(Some(1000))
+            .build()
+            .unwrap();
+        assert!(!config.flush_on_ram());
+        assert_eq!(config.max_buffered_docs(), 1000);
+        assert!(config.to_string().contains("ram_buffer_size_mb = disabled"));
+
+        let builder = || IndexWriterConfig::builder();
+        assert!(builder().ram_buffer_size_mb(None).build().is_err());
+        assert!(builder().ram_buffer_size_mb(Some(0.0)).build().is_err());
+        assert!(builder().max_buffered_docs(Some(1)).build().is_err());
+        assert!(builder().per_thread_hard_limit_mb(2048).build().is_err());
+        assert!(builder().max_thread_states(Some(0)).build().is_err());
+    }
+
+    #[test]
+    fn test_set_ram_buffer_size() {
+        let mut config = IndexWriterConfig::default();
+        assert!(config.try_set_ram_buffer_size(0.0).is_err());
+        assert!(config.flush_on_ram());
+
+        config.set_ram_buffer_size(32.0);
+        assert_eq!(config.ram_buffer_size_mb(), 32.0);
+        config.set_max_buffered_docs(Some(100)).unwrap();
+        config.try_set_ram_buffer_size(-1.0).unwrap();
+        assert!(!config.flush_on_ram());
+
+        // the infallible setter leaves the check to `validate`
+        config.set_ram_buffer_size(32.0);
+        config.set_max_buffered_docs(None).unwrap();
+        config.set_ram_buffer_size(0.0);
+        assert!(config.validate().is_err());
+    }
+}
@@ -0,0 +1,193 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CompoundFormat};
+use core::index::SegmentCommitInfo;
+use core::store::{Directory, IOContext};
+
+use error::Result;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Receives notifications of what an `IndexWriter` does internally, see
+/// `IndexWriterConfig::set_events`, e.g. to expose merge telemetry or to warm
+/// caches once a commit is done. All the callbacks default to doing nothing.
+///
+/// The callbacks are invoked from the thread doing the work (e.g. the merge
+/// thread), so they must be cheap and must not call back into the writer.
+pub trait IndexWriterEvents: Send + Sync {
+    /// Called before a merge starts reading the segments it merges.
+    fn merge_started(&self, _report: &MergeStartReport) {}
+
+    /// Called once a merge was committed to the writer.
+    fn merge_finished(&self, _report: &CompactionReport) {}
+
+    /// Called once a flushed segment was published to the writer, it is
+    /// visible to the readers opened from the writer from now on.
+    fn flush_finished(&self, _report: &FlushReport) {}
+
+    /// Called once a commit wrote its segments file, the commit is durable.
+    fn commit_finished(&self, _report: &CommitReport) {}
+}
+
+/// The segments a merge is about to merge.
+#[derive(Debug, Clone)]
+pub struct MergeStartReport {
+    /// name of the segment being merged into
+    pub segment: String,
+    /// names of the segments to merge
+    pub merged_segments: Vec<String>,
+    /// sum of the max doc of the segments to merge
+    pub total_max_doc: u32,
+    /// sum of the size in bytes of the segments to merge
+    pub total_bytes: u64,
+}
+
+/// A segment flushed from the indexing buffer.
+#[derive(Debug, Clone)]
+pub struct FlushReport {
+    /// name of the flushed segment
+    pub segment: String,
+    /// max doc of the flushed segment
+    pub num_docs: i32,
+    /// number of docs deleted while they were buffered
+    pub del_count: u32,
+    /// bytes of the flushed segment per data structure, see `data_structure`
+    pub bytes: BTreeMap<&'static str, u64>,
+    /// time writing the segment took
+    pub duration: Duration,
+}
+
+impl FlushReport {
+    pub fn total_bytes(&self) -> u64 {
+        self.bytes.values().sum()
+    }
+}
+
+/// A successful commit.
+#[derive(Debug, Clone)]
+pub struct CommitReport {
+    /// generation of the written segments file
+    pub generation: i64,
+    /// number of segments in the commit
+    pub num_segments: usize,
+    /// sequence number of the last operation included in the commit
+    pub seq_no: i64,
+    /// time the commit took, including the flush of the buffered docs
+    pub duration: Duration,
+}
+
+/// How much a merge compacted the segments it merged.
+#[derive(Debug, Clone)]
+pub struct CompactionReport {
+    /// name of the merged segment
+    pub segment: String,
+    /// names of the segments that were merged
+    pub merged_segments: Vec<String>,
+    /// sum of the max doc of the merged segments
+    pub docs_before: i32,
+    /// max doc of the merged segment
+    pub docs_after: i32,
+    /// number of deleted documents that were dropped by the merge
+    pub deletes_reclaimed: i32,
+    /// bytes of the merged segments per data structure, see `data_structure`
+    pub bytes_before: BTreeMap<&'static str, u64>,
+    /// bytes of the merged segment per data structure
+    pub bytes_after: BTreeMap<&'static str, u64>,
+    /// time the merge took, from opening the readers to committing
+    pub duration: Duration,
+}
+
+impl CompactionReport {
+    pub fn total_bytes_before(&self) -> u64 {
+        self.bytes_before.values().sum()
+    }
+
+    pub fn total_bytes_after(&self) -> u64 {
+        self.bytes_after.values().sum()
+    }
+
+    /// The fraction of bytes the merge saved, negative if it grew the data.
+    pub fn compaction_ratio(&self) -> f64 {
+        let before = self.total_bytes_before();
+        if before == 0 {
+            0.0
+        } else {
+            1.0 - self.total_bytes_after() as f64 / before as f64
+        }
+    }
+
+    /// Merged bytes read per second, in MB.
+    pub fn throughput_mb_per_sec(&self) -> f64 {
+        let secs = self.duration.as_secs() as f64 + f64::from(self.duration.subsec_nanos()) / 1e9;
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.total_bytes_before() as f64 / 1024.0 / 1024.0 / secs
+        }
+    }
+}
+
+/// Returns the data structure an index file holds, based on its extension.
+pub fn data_structure(file_name: &str) -> &'static str {
+    let extension = file_name.rsplit('.').next().unwrap_or("");
+    match extension {
+        "tim" | "tip" => "terms",
+        "doc" => "postings",
+        "pos" => "positions",
+        "pay" => "payloads",
+        "fdt" | "fdx" => "stored_fields",
+        "tvd" | "tvx" => "term_vectors",
+        "nvd" | "nvm" => "norms",
+        "dvd" | "dvm" => "doc_values",
+        "dii" | "dim" => "points",
+        "liv" => "live_docs",
+        "fnm" => "field_infos",
+        "si" => "segment_info",
+        "cfs" | "cfe" => "compound",
+        _ => "other",
+    }
+}
+
+/// Adds the size of the files of `info` to `bytes`, per data structure. The
+/// files inside a compound file are accounted separately.
+pub fn add_segment_bytes<D: Directory, C: Codec>(
+    info: &SegmentCommitInfo<D, C>,
+    bytes: &mut BTreeMap<&'static str, u64>,
+) -> Result<()> {
+    let directory = &info.info.directory;
+    let mut compound_inner = 0i64;
+    if info.info.is_compound_file() {
+        let compound = info.info.codec().compound_format().get_compound_reader(
+            Arc::clone(directory),
+            &info.info,
+            &IOContext::READ,
+        )?;
+        for file in compound.list_all()? {
+            let length = compound.file_length(&file)?;
+            compound_inner += length;
+            *bytes.entry(data_structure(&file)).or_insert(0) += length as u64;
+        }
+    }
+    for file in info.files() {
+        let mut length = directory.file_length(&file)?;
+        if file.ends_with(".cfs") {
+            // only account the compound overhead, the inner files are above
+            length = (length - compound_inner).max(0);
+        }
+        *bytes.entry(data_structure(&file)).or_insert(0) += length as u64;
+    }
+    Ok(())
+}
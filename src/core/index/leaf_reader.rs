@@ -14,12 +14,12 @@
 use core::codec::{
     Codec, CodecFieldsProducer, CodecNormsProducer, CodecPointsReader, CodecStoredFieldsReader,
     CodecTVFields, CodecTVReader, DocValuesProducerRef, FieldsProducer, NormsProducer,
-    StoredFieldsReader, TermVectorsReader,
+    StoredFieldsReader, TermVectorsReader, VectorReaderRef,
 };
 use core::index::{
     BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, IndexReader, NumericDocValues,
     NumericDocValuesRef, SortedDocValuesRef, SortedNumericDocValuesRef, SortedSetDocValuesRef,
-    StoredFieldVisitor, Term, TermIterator, Terms,
+    StoredFieldVisitor, Term, TermIterator, Terms, VectorValues,
 };
 use core::search::sort::Sort;
 use core::util::external::deferred::Deferred;
@@ -93,6 +93,20 @@ pub trait LeafReader {
 
     fn term_vector(&self, leaf_doc_id: DocId) -> Result<Option<Self::TVFields>>;
 
+    /// Returns the term vector of `field` for the doc, or None if the doc has
+    /// no term vector for it, i.e. the field wasn't indexed with
+    /// `store_term_vectors` or the doc has no value for it.
+    fn field_term_vector(
+        &self,
+        leaf_doc_id: DocId,
+        field: &str,
+    ) -> Result<Option<<Self::TVFields as Fields>::Terms>> {
+        match self.term_vector(leaf_doc_id)? {
+            Some(fields) => fields.terms(field),
+            None => Ok(None),
+        }
+    }
+
     fn document(&self, doc_id: DocId, visitor: &mut dyn StoredFieldVisitor) -> Result<()>;
 
     fn live_docs(&self) -> BitsRef;
@@ -125,6 +139,9 @@ pub trait LeafReader {
     /// spatial searches, or None if there are no point fields.
     fn point_values(&self) -> Option<Self::PointsReader>;
 
+    /// Returns the vectors of the field, or None if the field has no vector.
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>>;
+
     /// Expert: Returns a key for this IndexReader, so CachingWrapperFilter can find
     // it again.
     // This key must not have equals()/hashCode() methods, so &quot;equals&quot; means
@@ -151,6 +168,8 @@ pub trait LeafReader {
 
     fn doc_values_reader(&self) -> Result<Option<DocValuesProducerRef>>;
 
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>>;
+
     fn postings_reader(&self) -> Result<Self::FieldsProducer>;
 }
 
@@ -14,7 +14,7 @@
 use core::codec::{
     Codec, CodecFieldsProducer, CodecNormsProducer, CodecPointsReader, CodecStoredFieldsReader,
     CodecTVFields, CodecTVReader, DocValuesProducer, FieldsProducer, NormsProducer,
-    StoredFieldsReader, TermVectorsReader,
+    StoredFieldsReader, TermVectorsReader, VectorReader, VectorReaderRef,
 };
 use core::index::sorter::{PackedLongDocMap, SorterDocMap};
 use core::index::SegmentReader;
@@ -30,13 +30,14 @@ use core::index::{
     SortedNumericDocValues, SortedNumericDocValuesContext, SortedNumericDocValuesRef,
 };
 use core::index::{SortedSetDocValues, SortedSetDocValuesContext, SortedSetDocValuesRef};
+use core::index::{VectorSimilarityFunction, VectorValues};
 use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
 use core::search::sort::Sort;
 use core::search::{DocIterator, Payload, NO_MORE_DOCS};
 use core::store::{DataInput, Directory, IndexInput, IndexOutput, RAMOutputStream};
 use core::util::external::deferred::Deferred;
 use core::util::fst::bytes_store::{BytesStore, StoreBytesReader};
-use core::util::{Bits, BitsContext, BitsRef, DocId};
+use core::util::{Accountable, Bits, BitsContext, BitsRef, DocId, RamUsage};
 
 use error::{ErrorKind::IllegalArgument, Result};
 
@@ -225,6 +226,10 @@ impl<D: Directory + 'static, C: Codec> LeafReader for MergeReaderWrapper<D, C> {
         self.reader.point_values()
     }
 
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        self.reader.vector_values(field)
+    }
+
     fn core_cache_key(&self) -> &str {
         self.reader.core_cache_key()
     }
@@ -257,6 +262,10 @@ impl<D: Directory + 'static, C: Codec> LeafReader for MergeReaderWrapper<D, C> {
         unreachable!()
     }
 
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        unreachable!()
+    }
+
     fn postings_reader(&self) -> Result<CodecFieldsProducer<C>> {
         unreachable!()
     }
@@ -399,6 +408,16 @@ impl<T: LeafReader + 'static> LeafReader for SortingLeafReader<T> {
         }
     }
 
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        match self.reader.vector_values(field)? {
+            Some(v) => Ok(Some(Box::new(SortingVectorValues::new(
+                v,
+                self.doc_map.as_ref(),
+            )))),
+            None => Ok(None),
+        }
+    }
+
     /// Expert: Returns a key for this IndexReader, so CachingWrapperFilter can find
     // it again.
     // This key must not have equals()/hashCode() methods, so &quot;equals&quot; means
@@ -437,6 +456,10 @@ impl<T: LeafReader + 'static> LeafReader for SortingLeafReader<T> {
         unreachable!()
     }
 
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        unreachable!()
+    }
+
     fn postings_reader(&self) -> Result<Self::FieldsProducer> {
         unreachable!()
     }
@@ -486,6 +509,16 @@ impl<T: FieldsProducer> FieldsProducer for SortingFields<T> {
     }
 }
 
+impl<T: FieldsProducer> Accountable for SortingFields<T> {
+    fn ram_bytes_used(&self) -> usize {
+        self.fields.ram_bytes_used()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.fields.child_resources()
+    }
+}
+
 pub struct SortingTerms<T: Terms> {
     terms: T,
     doc_map: Arc<PackedLongDocMap>,
@@ -1061,6 +1094,11 @@ impl BinaryDocValues for SortingBinaryDocValues {
     fn get(&self, doc_id: i32) -> Result<Vec<u8>> {
         self.doc_values.get(self.doc_map.new_to_old(doc_id))
     }
+
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        self.doc_values
+            .get_into(self.doc_map.new_to_old(doc_id), value)
+    }
 }
 
 pub struct SortingNumericDocValues<T: AsRef<NumericDocValues> + Send + Sync> {
@@ -1148,6 +1186,49 @@ impl Bits for SortingBits {
     }
 }
 
+/// Vectors of a sorted reader, their ords follow the new doc ids.
+struct SortingVectorValues {
+    values: Box<dyn VectorValues>,
+    // (new doc id, ord in `values`), sorted by doc id
+    docs: Vec<(DocId, usize)>,
+}
+
+impl SortingVectorValues {
+    fn new(values: Box<dyn VectorValues>, doc_map: &PackedLongDocMap) -> Self {
+        let mut docs: Vec<(DocId, usize)> = (0..values.size())
+            .map(|ord| (doc_map.old_to_new(values.ord_to_doc(ord)), ord))
+            .collect();
+        docs.sort_unstable();
+        SortingVectorValues { values, docs }
+    }
+}
+
+impl VectorValues for SortingVectorValues {
+    fn dimension(&self) -> usize {
+        self.values.dimension()
+    }
+
+    fn size(&self) -> usize {
+        self.docs.len()
+    }
+
+    fn similarity_function(&self) -> VectorSimilarityFunction {
+        self.values.similarity_function()
+    }
+
+    fn ord_to_doc(&self, ord: usize) -> DocId {
+        self.docs[ord].0
+    }
+
+    fn doc_to_ord(&self, doc: DocId) -> Option<usize> {
+        self.docs.binary_search_by_key(&doc, |d| d.0).ok()
+    }
+
+    fn vector_value(&self, ord: usize, vector: &mut [f32]) -> Result<()> {
+        self.values.vector_value(self.docs[ord].1, vector)
+    }
+}
+
 #[derive(Clone)]
 pub struct SortingPointValues<P: PointValues> {
     point_values: P,
@@ -1172,6 +1253,15 @@ impl<P: PointValues + 'static> PointValues for SortingPointValues<P> {
         self.point_values.intersect(field_name, &mut sort_visitor)
     }
 
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        visitor: &mut impl IntersectVisitor,
+    ) -> Result<i64> {
+        // only the cells are compared, the doc ids don't need to be mapped
+        self.point_values.estimate_point_count(field_name, visitor)
+    }
+
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
         self.point_values.min_packed_value(field_name)
     }
@@ -1258,6 +1348,11 @@ impl BinaryDocValues for SortingSortedDocValues {
     fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
         self.doc_values.get(self.doc_map.new_to_old(doc_id))
     }
+
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        self.doc_values
+            .get_into(self.doc_map.new_to_old(doc_id), value)
+    }
 }
 
 struct SortingSortedSetDocValues {
@@ -1399,6 +1494,10 @@ impl<T: LeafReader + 'static> LeafReader for SlowCodecReaderWrapper<T> {
         self.reader.point_values()
     }
 
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        self.reader.vector_values(field)
+    }
+
     /// Expert: Returns a key for this IndexReader, so CachingWrapperFilter can find
     // it again.
     // This key must not have equals()/hashCode() methods, so &quot;equals&quot; means
@@ -1443,6 +1542,12 @@ impl<T: LeafReader + 'static> LeafReader for SlowCodecReaderWrapper<T> {
         ))))
     }
 
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        Ok(Some(Arc::new(LeafReaderAsVectorReader::new(Arc::clone(
+            &self.reader,
+        )))))
+    }
+
     fn postings_reader(&self) -> Result<Self::FieldsProducer> {
         self.reader.fields()
     }
@@ -1552,6 +1657,13 @@ impl<T: LeafReader> LeafReaderAsDocValuesProducer<T> {
     }
 }
 
+// the doc values are owned by the wrapped reader
+impl<T: LeafReader> Accountable for LeafReaderAsDocValuesProducer<T> {
+    fn ram_bytes_used(&self) -> usize {
+        0
+    }
+}
+
 impl<T: LeafReader + 'static> DocValuesProducer for LeafReaderAsDocValuesProducer<T> {
     fn get_numeric(&self, field_info: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
         self.reader.get_numeric_doc_values(&field_info.name)
@@ -1592,3 +1704,31 @@ impl<T: LeafReader + 'static> DocValuesProducer for LeafReaderAsDocValuesProduce
 // the dv producer has this restrict
 unsafe impl<T: LeafReader> Send for LeafReaderAsDocValuesProducer<T> {}
 unsafe impl<T: LeafReader> Sync for LeafReaderAsDocValuesProducer<T> {}
+
+struct LeafReaderAsVectorReader<T: LeafReader> {
+    reader: Arc<T>,
+}
+
+impl<T: LeafReader> LeafReaderAsVectorReader<T> {
+    fn new(reader: Arc<T>) -> Self {
+        Self { reader }
+    }
+}
+
+impl<T: LeafReader + 'static> VectorReader for LeafReaderAsVectorReader<T> {
+    fn check_integrity(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        self.reader.vector_values(field)
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+// same hack as `LeafReaderAsDocValuesProducer`, only used when merging
+unsafe impl<T: LeafReader> Send for LeafReaderAsVectorReader<T> {}
+unsafe impl<T: LeafReader> Sync for LeafReaderAsVectorReader<T> {}
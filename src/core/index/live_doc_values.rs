@@ -0,0 +1,453 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::index::{
+    BinaryDocValuesRef, DocValuesType, LeafReader, NumericDocValuesContext, NumericDocValuesRef,
+    SortedDocValuesRef, SortedNumericDocValuesContext, SortedNumericDocValuesRef,
+    SortedSetDocValuesRef, NO_MORE_ORDS,
+};
+use core::util::{BitsContext, BitsRef, DocId};
+
+use error::Result;
+
+/// Iterates the live docs of a leaf reader in increasing order, the live docs
+/// are only checked when the reader has deletions.
+pub struct LiveDocsIterator {
+    live_docs: Option<BitsRef>,
+    ctx: BitsContext,
+    doc: DocId,
+    max_doc: DocId,
+}
+
+impl LiveDocsIterator {
+    pub fn new<R: LeafReader + ?Sized>(reader: &R) -> LiveDocsIterator {
+        let live_docs = if reader.num_docs() < reader.max_doc() {
+            Some(reader.live_docs())
+        } else {
+            None
+        };
+        LiveDocsIterator {
+            live_docs,
+            ctx: None,
+            doc: -1,
+            max_doc: reader.max_doc(),
+        }
+    }
+
+    /// Whether `doc` is live, which does not move the iterator.
+    pub fn is_live(&self, doc: DocId) -> Result<bool> {
+        match self.live_docs {
+            Some(ref live_docs) => live_docs.get(doc as usize),
+            None => Ok(true),
+        }
+    }
+}
+
+impl Iterator for LiveDocsIterator {
+    type Item = Result<DocId>;
+
+    fn next(&mut self) -> Option<Result<DocId>> {
+        loop {
+            self.doc += 1;
+            if self.doc >= self.max_doc {
+                self.doc = self.max_doc;
+                return None;
+            }
+            let live_docs = match self.live_docs {
+                Some(ref live_docs) => live_docs,
+                None => return Some(Ok(self.doc)),
+            };
+            match live_docs.get_with_ctx(self.ctx, self.doc as usize) {
+                Ok((true, ctx)) => {
+                    self.ctx = ctx;
+                    return Some(Ok(self.doc));
+                }
+                Ok((false, ctx)) => self.ctx = ctx,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Reads the doc values of a single field for `LiveDocValuesIterator`.
+pub trait DocValuesReader {
+    type Value;
+
+    /// Returns the value of `doc`, None if it has no value.
+    fn read(&mut self, doc: DocId) -> Result<Option<Self::Value>>;
+}
+
+pub struct NumericValuesReader {
+    values: NumericDocValuesRef,
+    docs_with_field: BitsRef,
+    ctx: NumericDocValuesContext,
+    bits_ctx: BitsContext,
+}
+
+impl NumericValuesReader {
+    pub fn new<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<NumericValuesReader> {
+        Ok(NumericValuesReader {
+            values: reader.get_numeric_doc_values(field)?,
+            docs_with_field: reader.get_docs_with_field(field)?,
+            ctx: None,
+            bits_ctx: None,
+        })
+    }
+}
+
+impl DocValuesReader for NumericValuesReader {
+    type Value = i64;
+
+    fn read(&mut self, doc: DocId) -> Result<Option<i64>> {
+        let (exists, bits_ctx) = self
+            .docs_with_field
+            .get_with_ctx(self.bits_ctx, doc as usize)?;
+        self.bits_ctx = bits_ctx;
+        if !exists {
+            return Ok(None);
+        }
+        let (value, ctx) = self.values.get_with_ctx(self.ctx.take(), doc)?;
+        self.ctx = ctx;
+        Ok(Some(value))
+    }
+}
+
+pub struct SortedNumericValuesReader {
+    values: SortedNumericDocValuesRef,
+    ctx: Option<SortedNumericDocValuesContext>,
+}
+
+impl SortedNumericValuesReader {
+    pub fn new<R: LeafReader + ?Sized>(
+        reader: &R,
+        field: &str,
+    ) -> Result<SortedNumericValuesReader> {
+        Ok(SortedNumericValuesReader {
+            values: reader.get_sorted_numeric_doc_values(field)?,
+            ctx: None,
+        })
+    }
+}
+
+impl DocValuesReader for SortedNumericValuesReader {
+    type Value = Vec<i64>;
+
+    fn read(&mut self, doc: DocId) -> Result<Option<Vec<i64>>> {
+        let ctx = self.values.set_document(self.ctx.take(), doc)?;
+        let count = self.values.count(&ctx);
+        let mut values = Vec::with_capacity(count);
+        for i in 0..count {
+            values.push(self.values.value_at(&ctx, i)?);
+        }
+        self.ctx = Some(ctx);
+        Ok(if values.is_empty() {
+            None
+        } else {
+            Some(values)
+        })
+    }
+}
+
+pub struct BinaryValuesReader {
+    values: BinaryDocValuesRef,
+    docs_with_field: BitsRef,
+    bits_ctx: BitsContext,
+}
+
+impl DocValuesReader for BinaryValuesReader {
+    type Value = Vec<u8>;
+
+    fn read(&mut self, doc: DocId) -> Result<Option<Vec<u8>>> {
+        let (exists, bits_ctx) = self
+            .docs_with_field
+            .get_with_ctx(self.bits_ctx, doc as usize)?;
+        self.bits_ctx = bits_ctx;
+        if exists {
+            Ok(Some(self.values.get(doc)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Reads the ordinal of the value of each doc.
+pub struct SortedValuesReader {
+    values: SortedDocValuesRef,
+}
+
+impl DocValuesReader for SortedValuesReader {
+    type Value = i32;
+
+    fn read(&mut self, doc: DocId) -> Result<Option<i32>> {
+        let ord = self.values.get_ord(doc)?;
+        Ok(if ord < 0 { None } else { Some(ord) })
+    }
+}
+
+/// Reads the ordinals of the values of each doc.
+pub struct SortedSetValuesReader {
+    values: SortedSetDocValuesRef,
+}
+
+impl DocValuesReader for SortedSetValuesReader {
+    type Value = Vec<i64>;
+
+    fn read(&mut self, doc: DocId) -> Result<Option<Vec<i64>>> {
+        let mut ctx = self.values.set_document(doc)?;
+        let mut ords = Vec::new();
+        loop {
+            let ord = self.values.next_ord(&mut ctx)?;
+            if ord == NO_MORE_ORDS {
+                break;
+            }
+            ords.push(ord);
+        }
+        Ok(if ords.is_empty() { None } else { Some(ords) })
+    }
+}
+
+/// Iterates the `(doc, value)` pairs of the live docs having a value for a
+/// doc values field, so that consumers such as aggregations or exports do not
+/// have to check the live docs and the docs with field themselves.
+///
+/// A field without doc values in the segment has no pairs, asking for the
+/// wrong doc values type is an error.
+pub struct LiveDocValuesIterator<V: DocValuesReader> {
+    docs: LiveDocsIterator,
+    values: Option<V>,
+}
+
+impl<V: DocValuesReader> LiveDocValuesIterator<V> {
+    pub fn new(docs: LiveDocsIterator, values: Option<V>) -> LiveDocValuesIterator<V> {
+        LiveDocValuesIterator { docs, values }
+    }
+}
+
+fn has_doc_values<R: LeafReader + ?Sized>(reader: &R, field: &str) -> bool {
+    match reader.field_info(field) {
+        Some(info) => info.doc_values_type != DocValuesType::Null,
+        None => false,
+    }
+}
+
+impl LiveDocValuesIterator<NumericValuesReader> {
+    pub fn numeric<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<Self> {
+        let values = if has_doc_values(reader, field) {
+            Some(NumericValuesReader::new(reader, field)?)
+        } else {
+            None
+        };
+        Ok(Self::new(LiveDocsIterator::new(reader), values))
+    }
+}
+
+impl LiveDocValuesIterator<SortedNumericValuesReader> {
+    pub fn sorted_numeric<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<Self> {
+        let values = if has_doc_values(reader, field) {
+            Some(SortedNumericValuesReader::new(reader, field)?)
+        } else {
+            None
+        };
+        Ok(Self::new(LiveDocsIterator::new(reader), values))
+    }
+}
+
+impl LiveDocValuesIterator<BinaryValuesReader> {
+    pub fn binary<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<Self> {
+        let values = if has_doc_values(reader, field) {
+            Some(BinaryValuesReader {
+                values: reader.get_binary_doc_values(field)?,
+                docs_with_field: reader.get_docs_with_field(field)?,
+                bits_ctx: None,
+            })
+        } else {
+            None
+        };
+        Ok(Self::new(LiveDocsIterator::new(reader), values))
+    }
+}
+
+impl LiveDocValuesIterator<SortedValuesReader> {
+    pub fn sorted<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<Self> {
+        let values = if has_doc_values(reader, field) {
+            Some(SortedValuesReader {
+                values: reader.get_sorted_doc_values(field)?,
+            })
+        } else {
+            None
+        };
+        Ok(Self::new(LiveDocsIterator::new(reader), values))
+    }
+}
+
+impl LiveDocValuesIterator<SortedSetValuesReader> {
+    pub fn sorted_set<R: LeafReader + ?Sized>(reader: &R, field: &str) -> Result<Self> {
+        let values = if has_doc_values(reader, field) {
+            Some(SortedSetValuesReader {
+                values: reader.get_sorted_set_doc_values(field)?,
+            })
+        } else {
+            None
+        };
+        Ok(Self::new(LiveDocsIterator::new(reader), values))
+    }
+}
+
+impl<V: DocValuesReader> Iterator for LiveDocValuesIterator<V> {
+    type Item = Result<(DocId, V::Value)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let values = self.values.as_mut()?;
+        for doc in &mut self.docs {
+            let doc = match doc {
+                Ok(doc) => doc,
+                Err(e) => return Some(Err(e)),
+            };
+            match values.read(doc) {
+                Ok(Some(value)) => return Some(Ok((doc, value))),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{
+        BinaryDocValuesField, Field, FieldType, NumericDocValuesField, SortedNumericDocValuesField,
+        SortedSetDocValuesField,
+    };
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter, Term};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.index_options = IndexOptions::Docs;
+        id_type.omit_norms = true;
+        let mut fields: Vec<Box<dyn Fieldable>> = vec![
+            Box::new(Field::new(
+                "id".into(),
+                id_type,
+                Some(VariantValue::VString(id.to_string())),
+                None,
+            )),
+            Box::new(BinaryDocValuesField::new(
+                "bin",
+                format!("b{}", id).as_bytes(),
+            )),
+            Box::new(SortedSetDocValuesField::new(
+                "tags",
+                format!("t{}", id % 2).as_bytes(),
+            )),
+        ];
+        if id % 2 == 0 {
+            fields.push(Box::new(NumericDocValuesField::new("num", id * 10)));
+        }
+        if id % 3 != 0 {
+            fields.push(Box::new(SortedNumericDocValuesField::new("multi", id)));
+            fields.push(Box::new(SortedNumericDocValuesField::new("multi", -id)));
+        }
+        fields
+    }
+
+    fn collect<V: DocValuesReader>(iter: LiveDocValuesIterator<V>) -> Vec<(DocId, V::Value)> {
+        iter.collect::<Result<Vec<_>>>().unwrap()
+    }
+
+    #[test]
+    fn test_skip_deleted_docs() {
+        let path = ::std::env::temp_dir().join("rucene_live_doc_values");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for id in 0..8 {
+            writer.add_document(document(id)).unwrap();
+        }
+        writer.commit().unwrap();
+        writer
+            .delete_documents_by_terms(vec![
+                Term::new("id".into(), b"2".to_vec()),
+                Term::new("id".into(), b"3".to_vec()),
+            ])
+            .unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = leaves[0].reader;
+        assert_eq!(leaf.num_docs(), 6);
+
+        let docs = LiveDocsIterator::new(leaf);
+        assert!(!docs.is_live(2).unwrap());
+        assert!(docs.is_live(4).unwrap());
+        let docs: Vec<DocId> = docs.collect::<Result<_>>().unwrap();
+        assert_eq!(docs, vec![0, 1, 4, 5, 6, 7]);
+
+        let num = LiveDocValuesIterator::numeric(leaf, "num").unwrap();
+        assert_eq!(collect(num), vec![(0, 0), (4, 40), (6, 60)]);
+        let multi = LiveDocValuesIterator::sorted_numeric(leaf, "multi").unwrap();
+        assert_eq!(
+            collect(multi),
+            vec![
+                (1, vec![-1, 1]),
+                (4, vec![-4, 4]),
+                (5, vec![-5, 5]),
+                (7, vec![-7, 7]),
+            ]
+        );
+        let bin = LiveDocValuesIterator::binary(leaf, "bin").unwrap();
+        let bin: Vec<(DocId, String)> = collect(bin)
+            .into_iter()
+            .map(|(doc, value)| (doc, String::from_utf8(value).unwrap()))
+            .collect();
+        assert_eq!(
+            bin,
+            vec![
+                (0, "b0".to_string()),
+                (1, "b1".to_string()),
+                (4, "b4".to_string()),
+                (5, "b5".to_string()),
+                (6, "b6".to_string()),
+                (7, "b7".to_string()),
+            ]
+        );
+        // the ordinals of "t0" and "t1"
+        let tags = LiveDocValuesIterator::sorted_set(leaf, "tags").unwrap();
+        assert_eq!(
+            collect(tags),
+            vec![
+                (0, vec![0]),
+                (1, vec![1]),
+                (4, vec![0]),
+                (5, vec![1]),
+                (6, vec![0]),
+                (7, vec![1]),
+            ]
+        );
+
+        let unknown = LiveDocValuesIterator::numeric(leaf, "unknown").unwrap();
+        assert!(collect(unknown).is_empty());
+        assert!(LiveDocValuesIterator::numeric(leaf, "tags").is_err());
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
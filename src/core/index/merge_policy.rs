@@ -29,6 +29,7 @@ use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::f64;
 use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::SystemTime;
 
@@ -267,6 +268,9 @@ pub struct OneMerge<D: Directory, C: Codec> {
     pub merge_start_time: Arc<Volatile<Option<SystemTime>>>,
     /// Total number of documents in segments to be merged, not accounting for deletions.
     pub total_max_doc: u32,
+    /// Files of the segments to be merged that the writer's `IndexFileDeleter`
+    /// holds a reference on until the merge is finished.
+    pub referenced_files: HashSet<String>,
     // error: Result<()>
 }
 
@@ -295,6 +299,7 @@ impl<D: Directory, C: Codec> OneMerge<D, C> {
             rate_limiter,
             merge_start_time: Arc::new(Volatile::new(None)),
             total_max_doc: count as u32,
+            referenced_files: HashSet::new(),
         })
     }
 
@@ -404,6 +409,7 @@ unsafe impl Sync for OneMergeScheduleInfo {}
 // TODO
 //   - we could try to take into account whether a large merge is already running (under CMS) and
 //     then bias ourselves towards picking smaller merges if so (or, maybe CMS should do so)
+#[derive(Debug)]
 pub struct TieredMergePolicy {
     no_cfs_ratio: f64,
     max_cfs_segment_size: u64,
@@ -977,3 +983,730 @@ impl MergeScore {
         format!("skew {} non_del_ratio: {}", self.skew, self.non_del_ratio)
     }
 }
+
+/// Default merge factor of a `LogMergePolicy`.
+pub const DEFAULT_MERGE_FACTOR: u32 = 10;
+
+/// Default maximum number of docs of a segment to be merged by a
+/// `LogMergePolicy`.
+pub const DEFAULT_MAX_MERGE_DOCS: i64 = i32::max_value() as i64;
+
+/// Default minimum segment size of a `LogByteSizeMergePolicy`.
+pub const DEFAULT_MIN_MERGE_MB: f64 = 1.6;
+
+/// Default maximum segment size of a `LogByteSizeMergePolicy`, a segment
+/// larger than this is never merged, except by `force_merge`.
+pub const DEFAULT_MAX_MERGE_MB: f64 = 2048.0;
+
+/// Default minimum segment size of a `LogDocMergePolicy`.
+pub const DEFAULT_MIN_MERGE_DOCS: i64 = 1000;
+
+// the levels of the segments span by at most this value
+const LEVEL_LOG_SPAN: f64 = 0.75;
+
+/// How a `LogMergePolicy` measures the size of a segment.
+pub trait LogSizeMeasure: 'static {
+    fn size<D, C, MS, MP>(
+        info: &SegmentCommitInfo<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+        calibrate_size_by_deletes: bool,
+    ) -> i64
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy;
+}
+
+/// Measures segments by their size in bytes.
+#[derive(Debug)]
+pub struct ByteSizeMeasure;
+
+impl LogSizeMeasure for ByteSizeMeasure {
+    fn size<D, C, MS, MP>(
+        info: &SegmentCommitInfo<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+        calibrate_size_by_deletes: bool,
+    ) -> i64
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        if calibrate_size_by_deletes {
+            let del_count = writer.num_deleted_docs(info);
+            let max_doc = info.info.max_doc;
+            let byte_size = info.size_in_bytes();
+            if max_doc <= 0 {
+                byte_size
+            } else {
+                (byte_size as f64 * (1.0 - del_count as f64 / f64::from(max_doc))) as i64
+            }
+        } else {
+            info.size_in_bytes()
+        }
+    }
+}
+
+/// Measures segments by their number of docs.
+#[derive(Debug)]
+pub struct DocCountMeasure;
+
+impl LogSizeMeasure for DocCountMeasure {
+    fn size<D, C, MS, MP>(
+        info: &SegmentCommitInfo<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+        calibrate_size_by_deletes: bool,
+    ) -> i64
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        log_size_docs(info, writer, calibrate_size_by_deletes)
+    }
+}
+
+fn log_size_docs<D, C, MS, MP>(
+    info: &SegmentCommitInfo<D, C>,
+    writer: &IndexWriter<D, C, MS, MP>,
+    calibrate_size_by_deletes: bool,
+) -> i64
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    let max_doc = i64::from(info.info.max_doc);
+    if calibrate_size_by_deletes {
+        max_doc - i64::from(writer.num_deleted_docs(info))
+    } else {
+        max_doc
+    }
+}
+
+/// A `LogMergePolicy` measuring segments by their size in bytes.
+pub type LogByteSizeMergePolicy = LogMergePolicy<ByteSizeMeasure>;
+
+/// A `LogMergePolicy` measuring segments by their number of docs, which
+/// suits indices whose docs have about the same size.
+pub type LogDocMergePolicy = LogMergePolicy<DocCountMeasure>;
+
+/// This class implements a `MergePolicy` that tries to merge segments into
+/// levels of exponentially increasing size, where each level has fewer
+/// segments than the value of the merge factor. Whenever extra segments
+/// (beyond the merge factor upper bound) are encountered, all segments
+/// within the level are merged.
+///
+/// Unlike `TieredMergePolicy`, only adjacent segments are merged, which
+/// makes the merges predictable and keeps the docs in the order they were
+/// added. The size of a segment is measured in bytes by
+/// `LogByteSizeMergePolicy` and in docs by `LogDocMergePolicy`, pro-rated by
+/// the percentage of deleted docs by default.
+#[derive(Debug)]
+pub struct LogMergePolicy<M: LogSizeMeasure> {
+    merge_factor: u32,
+    min_merge_size: i64,
+    max_merge_size: i64,
+    max_merge_size_for_forced_merge: i64,
+    max_merge_docs: i64,
+    calibrate_size_by_deletes: bool,
+    no_cfs_ratio: f64,
+    max_cfs_segment_size: u64,
+    measure: PhantomData<M>,
+}
+
+fn mb_to_bytes(mb: f64) -> i64 {
+    let bytes = mb * 1024.0 * 1024.0;
+    if bytes > i64::max_value() as f64 {
+        i64::max_value()
+    } else {
+        bytes as i64
+    }
+}
+
+impl Default for LogByteSizeMergePolicy {
+    fn default() -> Self {
+        LogMergePolicy::new(
+            mb_to_bytes(DEFAULT_MIN_MERGE_MB),
+            mb_to_bytes(DEFAULT_MAX_MERGE_MB),
+        )
+    }
+}
+
+impl LogByteSizeMergePolicy {
+    /// Segments smaller than this are all considered on the same level, so
+    /// that a lot of tiny segments are merged together.
+    pub fn set_min_merge_mb(&mut self, mb: f64) {
+        self.min_merge_size = mb_to_bytes(mb);
+    }
+
+    /// Segments larger than this are never merged, except by `force_merge`.
+    pub fn set_max_merge_mb(&mut self, mb: f64) {
+        self.max_merge_size = mb_to_bytes(mb);
+    }
+
+    /// Segments larger than this are not merged by `force_merge` either.
+    pub fn set_max_merge_mb_for_forced_merge(&mut self, mb: f64) {
+        self.max_merge_size_for_forced_merge = mb_to_bytes(mb);
+    }
+}
+
+impl Default for LogDocMergePolicy {
+    fn default() -> Self {
+        LogMergePolicy::new(DEFAULT_MIN_MERGE_DOCS, i64::max_value())
+    }
+}
+
+impl LogDocMergePolicy {
+    /// Segments with less docs than this are all considered on the same
+    /// level, so that a lot of tiny segments are merged together.
+    pub fn set_min_merge_docs(&mut self, min_merge_docs: i64) {
+        self.min_merge_size = min_merge_docs;
+    }
+}
+
+impl<M: LogSizeMeasure> LogMergePolicy<M> {
+    fn new(min_merge_size: i64, max_merge_size: i64) -> Self {
+        LogMergePolicy {
+            merge_factor: DEFAULT_MERGE_FACTOR,
+            min_merge_size,
+            max_merge_size,
+            max_merge_size_for_forced_merge: i64::max_value(),
+            max_merge_docs: DEFAULT_MAX_MERGE_DOCS,
+            calibrate_size_by_deletes: true,
+            no_cfs_ratio: DEFAULT_NO_CFS_RATIO,
+            max_cfs_segment_size: DEFAULT_MAX_CFS_SEGMENT_SIZE,
+            measure: PhantomData,
+        }
+    }
+
+    pub fn merge_factor(&self) -> u32 {
+        self.merge_factor
+    }
+
+    /// Sets how often segments are merged: with a smaller value less RAM is
+    /// used while indexing and searches are faster, but indexing is slower.
+    /// With a larger value more RAM is used while indexing and searches are
+    /// slower, but indexing is faster.
+    pub fn set_merge_factor(&mut self, merge_factor: u32) -> Result<()> {
+        if merge_factor < 2 {
+            bail!(IllegalArgument(format!(
+                "merge_factor cannot be less than 2, got {}",
+                merge_factor
+            )));
+        }
+        self.merge_factor = merge_factor;
+        Ok(())
+    }
+
+    /// Segments with more docs than this are never merged.
+    pub fn set_max_merge_docs(&mut self, max_merge_docs: i64) {
+        self.max_merge_docs = max_merge_docs;
+    }
+
+    /// Whether the size of a segment is pro-rated by its percentage of
+    /// deleted docs, the default is true.
+    pub fn set_calibrate_size_by_deletes(&mut self, calibrate_size_by_deletes: bool) {
+        self.calibrate_size_by_deletes = calibrate_size_by_deletes;
+    }
+
+    pub fn set_no_cfs_ratio(&mut self, no_cfs_ratio: f64) -> Result<()> {
+        if no_cfs_ratio < 0.0 || no_cfs_ratio > 1.0 {
+            bail!(IllegalArgument(format!(
+                "no_cfs_ratio must be 0.0 to 1.0 inclusive, got {}",
+                no_cfs_ratio
+            )));
+        }
+        self.no_cfs_ratio = no_cfs_ratio;
+        Ok(())
+    }
+
+    fn segment_size<D, C, MS, MP>(
+        &self,
+        info: &SegmentCommitInfo<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> i64
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        M::size(info, writer, self.calibrate_size_by_deletes)
+    }
+
+    fn too_large_for_forced_merge<D, C, MS, MP>(
+        &self,
+        info: &SegmentCommitInfo<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> bool
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        self.segment_size(info, writer) > self.max_merge_size_for_forced_merge
+            || log_size_docs(info, writer, self.calibrate_size_by_deletes) > self.max_merge_docs
+    }
+
+    /// Returns true if the number of segments eligible for merging is less
+    /// than or equal to `max_num_segments`.
+    fn is_merged_to<D, C, MS, MP>(
+        &self,
+        infos: &SegmentInfos<D, C>,
+        max_num_segments: u32,
+        segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> bool
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let mut num_to_merge = 0;
+        let mut merge_info = None;
+        let mut segment_is_original = false;
+        for info in &infos.segments {
+            if num_to_merge > max_num_segments {
+                break;
+            }
+            if let Some(&is_original) = segments_to_merge.get(info) {
+                segment_is_original = is_original;
+                num_to_merge += 1;
+                merge_info = Some(info);
+            }
+        }
+        num_to_merge <= max_num_segments
+            && (num_to_merge != 1
+                || !segment_is_original
+                || self.is_merged(infos, merge_info.unwrap().as_ref(), writer))
+    }
+
+    /// Returns the merges necessary to merge the index, taking the max
+    /// merge size or max merge docs into consideration: the segments too
+    /// large are skipped and the segments between them merged.
+    fn find_forced_merges_size_limit<D, C, MS, MP>(
+        &self,
+        infos: &SegmentInfos<D, C>,
+        mut last: usize,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &infos.segments;
+        let mut spec = MergeSpecification::default();
+        let mut start = last as isize - 1;
+        while start >= 0 {
+            let s = start as usize;
+            if self.too_large_for_forced_merge(segments[s].as_ref(), writer) {
+                // need to skip that segment and add a merge for the segments
+                // on its right, unless there is only one which is merged
+                if last - s - 1 > 1
+                    || (s != last - 1 && !self.is_merged(infos, segments[s + 1].as_ref(), writer))
+                {
+                    spec.add(OneMerge::new(
+                        segments[s + 1..last].to_vec(),
+                        writer.next_merge_id(),
+                    )?);
+                }
+                last = s;
+            } else if last - s == self.merge_factor as usize {
+                spec.add(OneMerge::new(
+                    segments[s..last].to_vec(),
+                    writer.next_merge_id(),
+                )?);
+                last = s;
+            }
+            start -= 1;
+        }
+
+        // add any left-over segments, unless there is just one already merged
+        if last > 0 {
+            let s = (start + 1) as usize;
+            if s + 1 < last || !self.is_merged(infos, segments[s].as_ref(), writer) {
+                spec.add(OneMerge::new(
+                    segments[s..last].to_vec(),
+                    writer.next_merge_id(),
+                )?);
+            }
+        }
+        Ok(if spec.merges.is_empty() {
+            None
+        } else {
+            Some(spec)
+        })
+    }
+
+    /// Returns the merges necessary to merge the index down to
+    /// `max_num_segments`.
+    fn find_forced_merges_max_num_segments<D, C, MS, MP>(
+        &self,
+        infos: &SegmentInfos<D, C>,
+        max_num_segments: u32,
+        mut last: usize,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &infos.segments;
+        let merge_factor = self.merge_factor as usize;
+        let max_num_segments = max_num_segments as usize;
+        let mut spec = MergeSpecification::default();
+
+        // first, enroll all "full" merges (size merge_factor) to potentially
+        // be run concurrently
+        while last + 1 >= merge_factor + max_num_segments {
+            spec.add(OneMerge::new(
+                segments[last - merge_factor..last].to_vec(),
+                writer.next_merge_id(),
+            )?);
+            last -= merge_factor;
+        }
+
+        // only if there are no full merges pending do we add a final partial
+        // (< merge_factor segments) merge
+        if spec.merges.is_empty() {
+            if max_num_segments == 1 {
+                // since we must merge down to 1 segment, the choice is simple
+                if last > 1 || !self.is_merged(infos, segments[0].as_ref(), writer) {
+                    spec.add(OneMerge::new(
+                        segments[..last].to_vec(),
+                        writer.next_merge_id(),
+                    )?);
+                }
+            } else if last > max_num_segments {
+                // take care to pick a partial merge that is least cost, but
+                // does not make the index too lopsided, which always picking
+                // the partial tail would do over time
+                let final_merge_size = last - max_num_segments + 1;
+                let mut best_size = 0;
+                let mut best_start = 0;
+                for i in 0..=last - final_merge_size {
+                    let sum_size: i64 = segments[i..i + final_merge_size]
+                        .iter()
+                        .map(|info| self.segment_size(info.as_ref(), writer))
+                        .sum();
+                    if i == 0
+                        || (sum_size < 2 * self.segment_size(segments[i - 1].as_ref(), writer)
+                            && sum_size < best_size)
+                    {
+                        best_start = i;
+                        best_size = sum_size;
+                    }
+                }
+                spec.add(OneMerge::new(
+                    segments[best_start..best_start + final_merge_size].to_vec(),
+                    writer.next_merge_id(),
+                )?);
+            }
+        }
+        Ok(if spec.merges.is_empty() {
+            None
+        } else {
+            Some(spec)
+        })
+    }
+}
+
+impl<M: LogSizeMeasure> MergePolicy for LogMergePolicy<M> {
+    /// Checks if any merges are now necessary and returns them: the segments
+    /// are grouped in levels by the log, base merge factor, of their size
+    /// and a merge is returned for each run of merge factor adjacent
+    /// segments of the same level.
+    fn find_merges<D, C, MS, MP>(
+        &self,
+        _merge_trigger: MergerTrigger,
+        segment_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &segment_infos.segments;
+        let merging = writer.merging_segments();
+        let norm = f64::from(self.merge_factor).ln();
+
+        // compute the levels, which are the log base merge factor of the
+        // size of each segment, tiny segments being floored
+        let levels: Vec<f64> = segments
+            .iter()
+            .map(|info| (self.segment_size(info.as_ref(), writer).max(1) as f64).ln() / norm)
+            .collect();
+        let level_floor = if self.min_merge_size <= 0 {
+            0.0
+        } else {
+            (self.min_merge_size as f64).ln() / norm
+        };
+
+        // Now, we quantize the log values into levels. The first level is
+        // any segment whose log size is within LEVEL_LOG_SPAN of the max
+        // size, or, who has such as segment "to the right". Then, we find
+        // the max of all other segments and use that to define the next
+        // level segment, etc.
+        let mut spec = MergeSpecification::default();
+        let num_segments = segments.len();
+        let mut start = 0;
+        while start < num_segments {
+            // find the max level of all the segments not already quantized
+            let max_level = levels[start..]
+                .iter()
+                .cloned()
+                .fold(f64::NEG_INFINITY, f64::max);
+
+            let level_bottom = if max_level <= level_floor {
+                // all the remaining segments fall into the min level
+                -1.0
+            } else {
+                // force a boundary at the level floor
+                (max_level - LEVEL_LOG_SPAN).max(level_floor)
+            };
+
+            // search backwards for the rightmost segment of this level
+            let mut upto = num_segments as isize - 1;
+            while upto >= start as isize && levels[upto as usize] < level_bottom {
+                upto -= 1;
+            }
+
+            // finally, record all merges that are viable at this level
+            let mut end = start + self.merge_factor as usize;
+            while end as isize <= upto + 1 {
+                let mut any_too_large = false;
+                let mut any_merging = false;
+                for info in &segments[start..end] {
+                    if merging.contains(&info.info.name) {
+                        any_merging = true;
+                        break;
+                    }
+                    any_too_large |= self.segment_size(info.as_ref(), writer)
+                        >= self.max_merge_size
+                        || log_size_docs(info.as_ref(), writer, self.calibrate_size_by_deletes)
+                            >= self.max_merge_docs;
+                }
+                if !any_merging && !any_too_large {
+                    let merge =
+                        OneMerge::new(segments[start..end].to_vec(), writer.next_merge_id())?;
+                    debug!("LMP: add merge={:?}", &merge.segments);
+                    spec.add(merge);
+                }
+                start = end;
+                end = start + self.merge_factor as usize;
+            }
+            start = (upto + 1) as usize;
+        }
+
+        Ok(if spec.merges.is_empty() {
+            None
+        } else {
+            Some(spec)
+        })
+    }
+
+    /// Returns the merges necessary to merge the index down to
+    /// `max_segment_count` segments. The segments larger than the max merge
+    /// size or max merge docs for forced merges are left alone, so the
+    /// index may end up with more segments than requested.
+    fn find_forced_merges<D, C, MS, MP>(
+        &self,
+        segment_infos: &SegmentInfos<D, C>,
+        max_segment_count: u32,
+        segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        debug_assert!(max_segment_count > 0);
+        if self.is_merged_to(segment_infos, max_segment_count, segments_to_merge, writer) {
+            return Ok(None);
+        }
+
+        // find the newest (rightmost) segment that needs to be merged, other
+        // segments may have been flushed since merging started
+        let segments = &segment_infos.segments;
+        let last = match segments
+            .iter()
+            .rposition(|info| segments_to_merge.contains_key(info))
+        {
+            Some(pos) => pos + 1,
+            None => return Ok(None),
+        };
+
+        // there is only one segment already, and it is merged
+        if max_segment_count == 1
+            && last == 1
+            && self.is_merged(segment_infos, segments[0].as_ref(), writer)
+        {
+            return Ok(None);
+        }
+
+        if segments[..last]
+            .iter()
+            .any(|info| self.too_large_for_forced_merge(info.as_ref(), writer))
+        {
+            self.find_forced_merges_size_limit(segment_infos, last, writer)
+        } else {
+            self.find_forced_merges_max_num_segments(segment_infos, max_segment_count, last, writer)
+        }
+    }
+
+    /// Returns the merges necessary to expunge all the deletes: each run of
+    /// adjacent segments with deletions is merged, by merge factor segments
+    /// at most.
+    fn find_forced_deletes_mergers<D, C, MS, MP>(
+        &self,
+        segments_infos: &SegmentInfos<D, C>,
+        writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let segments = &segments_infos.segments;
+        let mut spec = MergeSpecification::default();
+        let mut first_with_deletions = None;
+        for (i, info) in segments.iter().enumerate() {
+            if writer.num_deleted_docs(info.as_ref()) > 0 {
+                match first_with_deletions {
+                    None => first_with_deletions = Some(i),
+                    Some(first) if i - first == self.merge_factor as usize => {
+                        // we've seen merge factor segments in a row with
+                        // deletions, so force a merge now
+                        spec.add(OneMerge::new(
+                            segments[first..i].to_vec(),
+                            writer.next_merge_id(),
+                        )?);
+                        first_with_deletions = Some(i);
+                    }
+                    _ => {}
+                }
+            } else if let Some(first) = first_with_deletions.take() {
+                // end of a run of segments with deletions, merge it even if
+                // it's shorter than merge factor
+                spec.add(OneMerge::new(
+                    segments[first..i].to_vec(),
+                    writer.next_merge_id(),
+                )?);
+            }
+        }
+        if let Some(first) = first_with_deletions {
+            spec.add(OneMerge::new(
+                segments[first..].to_vec(),
+                writer.next_merge_id(),
+            )?);
+        }
+        Ok(if spec.merges.is_empty() {
+            None
+        } else {
+            Some(spec)
+        })
+    }
+
+    fn max_cfs_segment_size(&self) -> u64 {
+        self.max_cfs_segment_size
+    }
+
+    fn no_cfs_ratio(&self) -> f64 {
+        self.no_cfs_ratio
+    }
+}
+
+/// A `MergePolicy` which never returns merges to execute, so that the
+/// segments are exactly the flushed ones, including for `force_merge` and
+/// `force_merge_deletes`. Use it when merging is controlled by the
+/// application, e.g. in tests, or to bulk load an index and switch to
+/// another policy before merging it once.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoMergePolicy;
+
+impl MergePolicy for NoMergePolicy {
+    fn find_merges<D, C, MS, MP>(
+        &self,
+        _merge_trigger: MergerTrigger,
+        _segment_infos: &SegmentInfos<D, C>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        Ok(None)
+    }
+
+    fn find_forced_merges<D, C, MS, MP>(
+        &self,
+        _segment_infos: &SegmentInfos<D, C>,
+        _max_segment_count: u32,
+        _segments_to_merge: &HashMap<Arc<SegmentCommitInfo<D, C>>, bool>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        Ok(None)
+    }
+
+    fn find_forced_deletes_mergers<D, C, MS, MP>(
+        &self,
+        _segments_infos: &SegmentInfos<D, C>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> Result<Option<MergeSpecification<D, C>>>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        Ok(None)
+    }
+
+    fn max_cfs_segment_size(&self) -> u64 {
+        DEFAULT_MAX_CFS_SEGMENT_SIZE
+    }
+
+    fn no_cfs_ratio(&self) -> f64 {
+        DEFAULT_NO_CFS_RATIO
+    }
+
+    /// Keeps the compound file setting of the segment.
+    fn use_compound_file<D, C, MS, MP>(
+        &self,
+        _infos: &SegmentInfos<D, C>,
+        merged_info: &SegmentCommitInfo<D, C>,
+        _writer: &IndexWriter<D, C, MS, MP>,
+    ) -> bool
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        merged_info.info.is_compound_file()
+    }
+}
@@ -23,6 +23,7 @@ use num_cpus;
 
 use std::cmp::Ordering;
 use std::f64;
+use std::fmt;
 use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
 use std::thread::{self, ThreadId};
 use std::time::{Duration, SystemTime};
@@ -52,7 +53,9 @@ pub trait MergeScheduler: Send + Sync + Clone + 'static {
     fn close(&self) -> Result<()>;
 }
 
-#[derive(Copy, Clone)]
+/// A `MergeScheduler` that simply does each merge sequentially, in the
+/// thread that triggered it, so that merging is deterministic.
+#[derive(Copy, Clone, Debug)]
 pub struct SerialMergeScheduler;
 
 impl MergeScheduler for SerialMergeScheduler {
@@ -141,6 +144,17 @@ pub struct ConcurrentMergeScheduler {
     inner: Arc<ConcurrentMergeSchedulerInner>,
 }
 
+impl fmt::Debug for ConcurrentMergeScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let _l = self.inner.lock.lock().unwrap();
+        f.debug_struct("ConcurrentMergeScheduler")
+            .field("max_thread_count", &self.inner.max_thread_count)
+            .field("max_merge_count", &self.inner.max_merge_count)
+            .field("do_auto_io_throttle", &self.inner.do_auto_io_throttle)
+            .finish()
+    }
+}
+
 impl Default for ConcurrentMergeScheduler {
     fn default() -> Self {
         let max_thread_count = 1.max(4.min(num_cpus::get() / 2));
@@ -235,6 +249,13 @@ impl ConcurrentMergeSchedulerInner {
                 return (false, guard);
             }
 
+            writer.config().info("MS", || {
+                format!(
+                    "too many merges; stalling: merge_thread_count={} max_merge_count={}",
+                    self.merge_thread_count(),
+                    self.max_merge_count
+                )
+            });
             // Defensively wait for only .25 seconds in case we are missing a .notify/All somewhere:
             let (g, _) = self
                 .cond
@@ -425,6 +446,18 @@ impl MergeScheduler for ConcurrentMergeScheduler {
                     _live_sentinel: sentinel,
                 };
                 let merge_info = merge.schedule_info();
+                writer.config().info("MS", || {
+                    format!(
+                        "launch merge thread #{} for {:?}, {} bytes",
+                        scheduler.merge_thread_count,
+                        merge
+                            .segments
+                            .iter()
+                            .map(|s| s.info.name.as_str())
+                            .collect::<Vec<_>>(),
+                        merge.total_merge_bytes
+                    )
+                });
                 let handler = thread::Builder::new()
                     .name(format!(
                         "Rucene Merge Thread #{}",
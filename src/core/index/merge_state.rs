@@ -14,7 +14,7 @@
 use core::codec::{
     Codec, CodecFieldsProducer, CodecNormsProducer, CodecPointsReader, CodecStoredFieldsReader,
     CodecTVFields, CodecTVReader, DocValuesProducer, FieldsProducer, NormsProducer,
-    StoredFieldsReader, TermVectorsReader,
+    StoredFieldsReader, TermVectorsReader, VectorReaderRef,
 };
 use core::index::leaf_reader_wrapper::{
     LeafReaderAsNormsProducer, LeafReaderAsStoreFieldsReader, LeafReaderAsTermVectorsReaderWrapper,
@@ -26,6 +26,7 @@ use core::index::{
     BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, LeafReader, NumericDocValues,
     NumericDocValuesRef, PointValues, SeekStatus, SegmentInfo, SegmentReader, SortedDocValuesRef,
     SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor, TermIterator, Terms,
+    VectorValues,
 };
 use core::search::sort::Sort;
 use core::util::external::deferred::Deferred;
@@ -33,7 +34,7 @@ use core::util::packed::{
     PackedLongValues, PackedLongValuesBuilder, PackedLongValuesBuilderType, DEFAULT_PAGE_SIZE,
 };
 use core::util::packed_misc::COMPACT;
-use core::util::{Bits, BitsRef, DocId};
+use core::util::{Accountable, Bits, BitsRef, DocId};
 
 use error::ErrorKind::IllegalArgument;
 use error::Result;
@@ -76,6 +77,7 @@ pub struct MergeState<D: Directory + 'static, C: Codec> {
     pub live_docs: Vec<BitsRef>,
     pub fields_producers: Vec<MergeFieldsProducer<CodecFieldsProducer<C>>>,
     pub points_readers: Vec<Option<MergePointValuesEnum<Arc<CodecPointsReader<C>>>>>,
+    pub vector_readers: Vec<Option<VectorReaderRef>>,
     pub max_docs: Vec<i32>,
     /// Indicates if the index needs to be sorted
     pub needs_index_sort: bool,
@@ -111,6 +113,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
         let mut term_vectors_readers = Vec::with_capacity(num_readers);
         let mut doc_values_producers = Vec::with_capacity(num_readers);
         let mut points_readers = Vec::with_capacity(num_readers);
+        let mut vector_readers = Vec::with_capacity(num_readers);
         let mut fields_infos = Vec::with_capacity(num_readers);
         let mut live_docs = Vec::with_capacity(num_readers);
 
@@ -131,6 +134,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
             term_vectors_readers.push(reader.term_vectors_reader()?);
             fields_producers.push(reader.postings_reader()?);
             points_readers.push(reader.point_values());
+            vector_readers.push(reader.vector_reader()?);
             num_docs += reader.num_docs();
         }
         // TODO: hack logic
@@ -151,6 +155,7 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
             live_docs,
             fields_producers,
             points_readers,
+            vector_readers,
             max_docs,
             needs_index_sort,
         })
@@ -190,11 +195,9 @@ impl<D: Directory + 'static, C: Codec> MergeState<D, C> {
                     ReaderWrapperEnum::Segment(leaf)
                 }
             } else {
-                // TODO: fix IW to also sort when flushing?  It's somewhat tricky because of stored
-                // fields and term vectors, which write "live" to their index files
-                // on each indexed document:
-
-                // This segment was written by flush, so documents are not yet sorted, so we sort
+                // This segment was written by a flush with delete queries or doc values
+                // updates pending, see `DocumentsWriterPerThread::sort_flushed_segment`, or
+                // before the index sort was set, so documents are not yet sorted, so we sort
                 // them now:
                 let sort_doc_map = sorter.sort_leaf_reader(&leaf.leaf_context())?;
                 if let Some(sort_doc_map) = sort_doc_map {
@@ -427,6 +430,13 @@ impl<D: Directory + 'static, C: Codec> LeafReader for ReaderWrapperEnum<D, C> {
         }
     }
 
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        match self {
+            ReaderWrapperEnum::Segment(s) => s.vector_values(field),
+            ReaderWrapperEnum::SortedSegment(s) => s.vector_values(field),
+        }
+    }
+
     /// Expert: Returns a key for this IndexReader, so CachingWrapperFilter can find
     // it again.
     // This key must not have equals()/hashCode() methods, so &quot;equals&quot; means
@@ -517,6 +527,13 @@ impl<D: Directory + 'static, C: Codec> LeafReader for ReaderWrapperEnum<D, C> {
         }
     }
 
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        match self {
+            ReaderWrapperEnum::Segment(s) => s.vector_reader(),
+            ReaderWrapperEnum::SortedSegment(s) => s.vector_reader(),
+        }
+    }
+
     fn postings_reader(&self) -> Result<Self::FieldsProducer> {
         self.fields()
     }
@@ -658,6 +675,17 @@ impl<P: PointValues + Clone + 'static> PointValues for MergePointValuesEnum<P> {
         }
     }
 
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        visitor: &mut impl IntersectVisitor,
+    ) -> Result<i64> {
+        match self {
+            MergePointValuesEnum::Raw(p) => p.estimate_point_count(field_name, visitor),
+            MergePointValuesEnum::Sorting(p) => p.estimate_point_count(field_name, visitor),
+        }
+    }
+
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
         match self {
             MergePointValuesEnum::Raw(p) => p.min_packed_value(field_name),
@@ -720,6 +748,15 @@ impl<T: FieldsProducer> FieldsProducer for MergeFieldsProducer<T> {
     }
 }
 
+impl<T: FieldsProducer> Accountable for MergeFieldsProducer<T> {
+    fn ram_bytes_used(&self) -> usize {
+        match &self.0 {
+            MergeFieldsProducerEnum::Raw(f) => f.ram_bytes_used(),
+            MergeFieldsProducerEnum::Sort(f) => f.ram_bytes_used(),
+        }
+    }
+}
+
 impl<T: FieldsProducer> Fields for MergeFieldsProducer<T> {
     type Terms = MergeTerms<T::Terms>;
     fn fields(&self) -> Vec<String> {
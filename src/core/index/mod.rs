@@ -120,6 +120,10 @@ mod multi_terms;
 
 pub use self::multi_terms::*;
 
+mod multi_doc_values;
+
+pub use self::multi_doc_values::*;
+
 mod reader_slice;
 
 pub use self::reader_slice::*;
@@ -136,13 +140,58 @@ mod point_values_writer;
 
 pub use self::point_values_writer::*;
 
+mod vector_values;
+
+pub use self::vector_values::*;
+
+mod vector_values_writer;
+
+pub use self::vector_values_writer::*;
+
+mod index_pruner;
+
+pub use self::index_pruner::*;
+
+mod index_splitter;
+
+pub use self::index_splitter::*;
+
+mod index_writer_events;
+
+pub use self::index_writer_events::*;
+
+mod live_doc_values;
+
+pub use self::live_doc_values::*;
+
+mod index_commit;
+
+pub use self::index_commit::*;
+
+mod delete_policy;
+
+pub use self::delete_policy::*;
+
+mod reindex;
+
+pub use self::reindex::*;
+
+mod filter_reader;
+
+pub use self::filter_reader::*;
+
+mod parallel_reader;
+
+pub use self::parallel_reader::*;
+
 pub use self::doc_values_term_iterator::DocValuesTermIterator;
+pub use self::flush_control::IndexingThreadStats;
+pub use self::flush_policy::{FlushAction, FlushByRamOrCountsPolicy, FlushPolicy, FlushStats};
 
 pub mod doc_id_merger;
 
 mod bufferd_updates;
 mod byte_slice_reader;
-mod delete_policy;
 mod doc_consumer;
 mod doc_values_term_iterator;
 mod doc_writer;
@@ -150,7 +199,6 @@ mod doc_writer_delete_queue;
 mod doc_writer_flush_queue;
 mod flush_control;
 mod flush_policy;
-mod index_commit;
 mod index_file_deleter;
 pub mod index_writer_config;
 mod leaf_reader_wrapper;
@@ -174,7 +222,7 @@ use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::result;
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicI64, Ordering as AtomicOrdering};
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use regex::Regex;
 
@@ -705,16 +753,16 @@ pub struct SegmentCommitInfo<D: Directory, C: Codec> {
     /// write
     pub next_write_field_infos_gen: AtomicI64,
     /// Generation number of the DocValues (-1 if there are no updates)
-    pub doc_values_gen: i64,
+    doc_values_gen: AtomicI64,
     /// Normally 1+dvGen, unless an exception was hit on last attempt to
     /// write
     pub next_write_doc_values_gen: AtomicI64,
     /// Track the per-field DocValues update files
-    pub dv_updates_files: HashMap<i32, HashSet<String>>,
+    dv_updates_files: RwLock<HashMap<i32, HashSet<String>>>,
     /// TODO should we add .files() to FieldInfosFormat, like we have on
     /// LiveDocsFormat?
     /// track the fieldInfos update files
-    pub field_infos_files: HashSet<String>,
+    field_infos_files: RwLock<HashSet<String>>,
 
     pub size_in_bytes: AtomicI64,
     // NOTE: only used in-RAM by IW to track buffered deletes;
@@ -750,19 +798,25 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
             next_write_del_gen: AtomicI64::new(if del_gen == -1 { 1i64 } else { del_gen + 1 }),
             field_infos_gen: AtomicI64::new(field_infos_gen),
             next_write_field_infos_gen: AtomicI64::new(field_info_gen),
-            doc_values_gen,
+            doc_values_gen: AtomicI64::new(doc_values_gen),
             next_write_doc_values_gen: AtomicI64::new(if doc_values_gen == -1 {
                 1
             } else {
                 doc_values_gen + 1
             }),
-            dv_updates_files,
-            field_infos_files,
+            dv_updates_files: RwLock::new(dv_updates_files),
+            field_infos_files: RwLock::new(field_infos_files),
             size_in_bytes: AtomicI64::new(-1),
             buffered_deletes_gen: AtomicI64::new(0),
         }
     }
 
+    /// Returns all files referenced by this segment.
+    ///
+    /// Only the latest doc values update generation of each field is
+    /// referenced, the files of older generations are deleted once no commit
+    /// uses them. A merge rewrites the updated values into the merged segment,
+    /// which starts without any update generation.
     pub fn files(&self) -> HashSet<String> {
         let mut files = HashSet::new();
         // Start from the wrapped info's files:
@@ -776,14 +830,14 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
         self.info.codec().live_docs_format().files(self, &mut files);
 
         // must separately add any field updates files
-        for (_, fs) in &self.dv_updates_files {
+        for fs in self.dv_updates_files.read().unwrap().values() {
             for f in fs {
                 files.insert(f.clone());
             }
         }
 
         // must separately add field_infos files
-        for f in &self.field_infos_files {
+        for f in self.field_infos_files.read().unwrap().iter() {
             files.insert(f.clone());
         }
 
@@ -833,6 +887,42 @@ impl<D: Directory, C: Codec> SegmentCommitInfo<D, C> {
             .store(gen, AtomicOrdering::Release);
     }
 
+    pub fn doc_values_gen(&self) -> i64 {
+        self.doc_values_gen.load(AtomicOrdering::Acquire)
+    }
+
+    pub fn advance_doc_values_gen(&self) {
+        self.doc_values_gen
+            .store(self.next_write_doc_values_gen(), AtomicOrdering::Release);
+        self.next_write_doc_values_gen
+            .store(self.doc_values_gen() + 1, AtomicOrdering::Release);
+        self.size_in_bytes.store(-1, AtomicOrdering::Release);
+    }
+
+    /// Returns the per-field DocValues update files, by field number
+    pub fn dv_updates_files(&self) -> HashMap<i32, HashSet<String>> {
+        self.dv_updates_files.read().unwrap().clone()
+    }
+
+    /// Sets the DocValues update files of the given fields, replacing the
+    /// files of their previous updates
+    pub fn set_dv_updates_files(&self, files: HashMap<i32, HashSet<String>>) {
+        let mut dv_updates_files = self.dv_updates_files.write().unwrap();
+        for (field, fs) in files {
+            dv_updates_files.insert(field, fs);
+        }
+        self.size_in_bytes.store(-1, AtomicOrdering::Release);
+    }
+
+    pub fn field_infos_files(&self) -> HashSet<String> {
+        self.field_infos_files.read().unwrap().clone()
+    }
+
+    pub fn set_field_infos_files(&self, files: HashSet<String>) {
+        *self.field_infos_files.write().unwrap() = files;
+        self.size_in_bytes.store(-1, AtomicOrdering::Release);
+    }
+
     pub fn advance_field_infos_gen(&self) {
         self.field_infos_gen
             .store(self.next_field_infos_gen(), AtomicOrdering::Release);
@@ -910,9 +1000,9 @@ impl<D: Directory, C: Codec> Clone for SegmentCommitInfo<D, C> {
             self.del_count(),
             self.del_gen(),
             self.field_infos_gen(),
-            self.doc_values_gen,
-            self.dv_updates_files.clone(),
-            self.field_infos_files.clone(),
+            self.doc_values_gen(),
+            self.dv_updates_files(),
+            self.field_infos_files(),
         );
         // Not clear that we need to carry over nextWriteDelGen
         // (i.e. do we ever clone after a failed write and
@@ -955,13 +1045,13 @@ impl<D: Directory, C: Codec> Serialize for SegmentCommitInfo<D, C> {
                 .next_write_field_infos_gen
                 .load(AtomicOrdering::Acquire),
         )?;
-        s.serialize_field("doc_values_gen", &self.doc_values_gen)?;
+        s.serialize_field("doc_values_gen", &self.doc_values_gen())?;
         s.serialize_field(
             "next_write_doc_values_gen",
             &self.next_write_doc_values_gen(),
         )?;
-        s.serialize_field("dv_updates_files", &self.dv_updates_files)?;
-        s.serialize_field("field_infos_files", &self.field_infos_files)?;
+        s.serialize_field("dv_updates_files", &self.dv_updates_files())?;
+        s.serialize_field("field_infos_files", &self.field_infos_files())?;
         s.serialize_field("size_in_bytes", &self.size_in_bytes())?;
         s.end()
     }
@@ -1070,7 +1160,7 @@ impl<D: Directory, DW: Directory, C: Codec> SegmentWriteState<D, DW, C> {
             if parts.len() == 2 {
                 true
             } else if parts.len() == 1 {
-                segment_suffix.parse::<i64>().is_ok()
+                i64::from_str_radix(segment_suffix, 36).is_ok()
             } else {
                 false // invalid
             }
@@ -1289,6 +1379,10 @@ pub mod tests {
             unimplemented!()
         }
 
+        fn vector_values(&self, _field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+            unimplemented!()
+        }
+
         fn document(&self, _doc_id: DocId, _visitor: &mut StoredFieldVisitor) -> Result<()> {
             unimplemented!()
         }
@@ -1376,6 +1470,10 @@ pub mod tests {
             unreachable!()
         }
 
+        fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+            unreachable!()
+        }
+
         fn postings_reader(&self) -> Result<Self::FieldsProducer> {
             unreachable!()
         }
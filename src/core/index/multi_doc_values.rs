@@ -0,0 +1,478 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{
+    BinaryDocValues, BinaryDocValuesRef, DocValuesTermIterator, DocValuesType,
+    EmptyBinaryDocValues, EmptyNumericDocValues, EmptySortedDocValues, EmptySortedNumericDocValues,
+    EmptySortedSetDocValues, IndexReader, LeafReaderContext, NumericDocValues,
+    NumericDocValuesContext, NumericDocValuesRef, OrdinalMap, SortedDocValues, SortedDocValuesRef,
+    SortedDocValuesTermIterator, SortedNumericDocValuesRef, SortedSetDocValuesRef, NO_MORE_ORDS,
+};
+use core::util::{Bits, BitsContext, BitsRef, DocId, LongValues, MatchNoBits};
+use error::Result;
+
+use std::sync::Arc;
+
+// Merged top-level views of the doc values of a composite reader, e.g. a
+// `StandardDirectoryReader`, for the code which needs one logical view over
+// all the segments, such as global facet ordinals.
+//
+// The views find the segment of a doc by a binary search over the doc bases
+// for each access, so they're slower than the per segment doc values and
+// should not be used in the hot loops of a search, use the `LeafReaderContext`
+// doc values there.
+
+/// Returns the numeric doc values of `field` over all the leaves of `reader`,
+/// or `None` if no leaf has numeric values for it. The docs of a leaf without
+/// the field have the value 0.
+pub fn get_numeric_values<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    field: &str,
+) -> Result<Option<NumericDocValuesRef>> {
+    let leaves = reader.leaves();
+    if !has_doc_values(&leaves, field, DocValuesType::Numeric) {
+        return Ok(None);
+    }
+    if leaves.len() == 1 {
+        return Ok(Some(leaves[0].reader.get_numeric_doc_values(field)?));
+    }
+    let mut values = Vec::with_capacity(leaves.len());
+    for leaf in &leaves {
+        values.push(if leaf_has_doc_values(leaf, field) {
+            leaf.reader.get_numeric_doc_values(field)?
+        } else {
+            Arc::new(EmptyNumericDocValues)
+        });
+    }
+    Ok(Some(Arc::new(MultiNumericDocValues {
+        values,
+        starts: doc_starts(reader, &leaves),
+    })))
+}
+
+/// Returns the docs having a value of any doc values type for `field` over
+/// all the leaves of `reader`, or `None` if no leaf has doc values for it.
+pub fn get_docs_with_field<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    field: &str,
+) -> Result<Option<BitsRef>> {
+    let leaves = reader.leaves();
+    if !leaves.iter().any(|leaf| leaf_has_doc_values(leaf, field)) {
+        return Ok(None);
+    }
+    if leaves.len() == 1 {
+        return Ok(Some(leaves[0].reader.get_docs_with_field(field)?));
+    }
+    let mut bits = Vec::with_capacity(leaves.len());
+    for leaf in &leaves {
+        bits.push(if leaf_has_doc_values(leaf, field) {
+            leaf.reader.get_docs_with_field(field)?
+        } else {
+            Arc::new(MatchNoBits::new(leaf.reader.max_doc() as usize))
+        });
+    }
+    Ok(Some(Arc::new(MultiBits {
+        bits,
+        starts: doc_starts(reader, &leaves),
+    })))
+}
+
+/// Returns the binary doc values of `field` over all the leaves of `reader`,
+/// or `None` if no leaf has binary values for it. The docs of a leaf without
+/// the field have an empty value.
+pub fn get_binary_values<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    field: &str,
+) -> Result<Option<BinaryDocValuesRef>> {
+    let leaves = reader.leaves();
+    if !has_doc_values(&leaves, field, DocValuesType::Binary) {
+        return Ok(None);
+    }
+    if leaves.len() == 1 {
+        return Ok(Some(leaves[0].reader.get_binary_doc_values(field)?));
+    }
+    let mut values = Vec::with_capacity(leaves.len());
+    for leaf in &leaves {
+        values.push(if leaf_has_doc_values(leaf, field) {
+            leaf.reader.get_binary_doc_values(field)?
+        } else {
+            Arc::new(EmptyBinaryDocValues)
+        });
+    }
+    Ok(Some(Arc::new(MultiBinaryDocValues {
+        values,
+        starts: doc_starts(reader, &leaves),
+    })))
+}
+
+/// Returns the sorted doc values of `field` over all the leaves of `reader`,
+/// with the ordinals of the leaves mapped to global ordinals, or `None` if no
+/// leaf has sorted values for it.
+///
+/// Building the `OrdinalMap` walks the terms of all the leaves, so the view
+/// should be cached for a given reader.
+pub fn get_sorted_values<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    field: &str,
+) -> Result<Option<MultiSortedDocValues>> {
+    let leaves = reader.leaves();
+    if !has_doc_values(&leaves, field, DocValuesType::Sorted) {
+        return Ok(None);
+    }
+    let mut values: Vec<SortedDocValuesRef> = Vec::with_capacity(leaves.len());
+    for leaf in &leaves {
+        values.push(if leaf_has_doc_values(leaf, field) {
+            leaf.reader.get_sorted_doc_values(field)?
+        } else {
+            Arc::new(EmptySortedDocValues)
+        });
+    }
+    let mapping = OrdinalMap::from_sorted_values(&values)?;
+    Ok(Some(MultiSortedDocValues::new(
+        values,
+        doc_starts(reader, &leaves),
+        Arc::new(mapping),
+    )))
+}
+
+/// Returns the sorted set doc values of `field` over all the leaves of
+/// `reader`, with the ordinals of the leaves mapped to global ordinals, or
+/// `None` if no leaf has sorted set values for it.
+///
+/// Building the `OrdinalMap` walks the terms of all the leaves, so the view
+/// should be cached for a given reader.
+pub fn get_sorted_set_values<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    field: &str,
+) -> Result<Option<MultiSortedSetDocValues>> {
+    let leaves = reader.leaves();
+    if !has_doc_values(&leaves, field, DocValuesType::SortedSet) {
+        return Ok(None);
+    }
+    let mut values: Vec<SortedSetDocValuesRef> = Vec::with_capacity(leaves.len());
+    for leaf in &leaves {
+        values.push(if leaf_has_doc_values(leaf, field) {
+            leaf.reader.get_sorted_set_doc_values(field)?
+        } else {
+            Arc::new(EmptySortedSetDocValues)
+        });
+    }
+    let mapping = Arc::new(OrdinalMap::from_sorted_set_values(&values)?);
+    let global_ords = (0..values.len())
+        .map(|i| mapping.get_global_ords(i))
+        .collect();
+    Ok(Some(MultiSortedSetDocValues {
+        values,
+        starts: doc_starts(reader, &leaves),
+        mapping,
+        global_ords,
+    }))
+}
+
+/// Returns the sorted numeric doc values of `field` over all the leaves of
+/// `reader`, or `None` if no leaf has sorted numeric values for it.
+pub fn get_sorted_numeric_values<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    field: &str,
+) -> Result<Option<MultiSortedNumericDocValues>> {
+    let leaves = reader.leaves();
+    if !has_doc_values(&leaves, field, DocValuesType::SortedNumeric) {
+        return Ok(None);
+    }
+    let mut values: Vec<SortedNumericDocValuesRef> = Vec::with_capacity(leaves.len());
+    for leaf in &leaves {
+        values.push(if leaf_has_doc_values(leaf, field) {
+            leaf.reader.get_sorted_numeric_doc_values(field)?
+        } else {
+            Arc::new(EmptySortedNumericDocValues)
+        });
+    }
+    Ok(Some(MultiSortedNumericDocValues {
+        values,
+        starts: doc_starts(reader, &leaves),
+    }))
+}
+
+fn leaf_has_doc_values<C: Codec>(leaf: &LeafReaderContext<'_, C>, field: &str) -> bool {
+    leaf.reader
+        .field_info(field)
+        .map_or(false, |info| info.doc_values_type != DocValuesType::Null)
+}
+
+// the leaves with values of another type fail when their values are read
+fn has_doc_values<C: Codec>(
+    leaves: &[LeafReaderContext<'_, C>],
+    field: &str,
+    doc_values_type: DocValuesType,
+) -> bool {
+    leaves.iter().any(|leaf| {
+        leaf.reader
+            .field_info(field)
+            .map_or(false, |info| info.doc_values_type == doc_values_type)
+    })
+}
+
+// the doc bases of the leaves followed by the max doc of the reader
+fn doc_starts<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
+    reader: &IR,
+    leaves: &[LeafReaderContext<'_, C>],
+) -> Vec<DocId> {
+    let mut starts: Vec<DocId> = leaves.iter().map(|leaf| leaf.doc_base()).collect();
+    starts.push(reader.max_doc());
+    starts
+}
+
+// the index of the leaf holding `doc`, the empty leaves are skipped
+fn sub_index(doc: DocId, starts: &[DocId]) -> usize {
+    match starts.binary_search(&doc) {
+        Ok(mut i) => {
+            while i + 2 < starts.len() && starts[i + 1] == doc {
+                i += 1;
+            }
+            i
+        }
+        Err(i) => i - 1,
+    }
+}
+
+/// Numeric doc values over the leaves of a composite reader.
+pub struct MultiNumericDocValues {
+    values: Vec<NumericDocValuesRef>,
+    starts: Vec<DocId>,
+}
+
+impl NumericDocValues for MultiNumericDocValues {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        let i = sub_index(doc_id, &self.starts);
+        let value = self.values[i].get(doc_id - self.starts[i])?;
+        Ok((value, ctx))
+    }
+}
+
+/// The docs with a value over the leaves of a composite reader.
+pub struct MultiBits {
+    bits: Vec<BitsRef>,
+    starts: Vec<DocId>,
+}
+
+impl Bits for MultiBits {
+    fn get_with_ctx(&self, ctx: BitsContext, index: usize) -> Result<(bool, BitsContext)> {
+        let doc = index as DocId;
+        let i = sub_index(doc, &self.starts);
+        let value = self.bits[i].get((doc - self.starts[i]) as usize)?;
+        Ok((value, ctx))
+    }
+
+    fn len(&self) -> usize {
+        self.starts[self.starts.len() - 1] as usize
+    }
+}
+
+/// Binary doc values over the leaves of a composite reader.
+pub struct MultiBinaryDocValues {
+    values: Vec<BinaryDocValuesRef>,
+    starts: Vec<DocId>,
+}
+
+impl BinaryDocValues for MultiBinaryDocValues {
+    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
+        let i = sub_index(doc_id, &self.starts);
+        self.values[i].get(doc_id - self.starts[i])
+    }
+
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        let i = sub_index(doc_id, &self.starts);
+        self.values[i].get_into(doc_id - self.starts[i], value)
+    }
+}
+
+/// Sorted doc values over the leaves of a composite reader, the ordinals are
+/// global ordinals given by an `OrdinalMap`.
+#[derive(Clone)]
+pub struct MultiSortedDocValues {
+    values: Vec<SortedDocValuesRef>,
+    starts: Vec<DocId>,
+    mapping: Arc<OrdinalMap>,
+    global_ords: Vec<Arc<dyn LongValues>>,
+}
+
+impl MultiSortedDocValues {
+    fn new(
+        values: Vec<SortedDocValuesRef>,
+        starts: Vec<DocId>,
+        mapping: Arc<OrdinalMap>,
+    ) -> MultiSortedDocValues {
+        let global_ords = (0..values.len())
+            .map(|i| mapping.get_global_ords(i))
+            .collect();
+        MultiSortedDocValues {
+            values,
+            starts,
+            mapping,
+            global_ords,
+        }
+    }
+
+    /// The mapping of the ordinals of the leaves to the global ordinals.
+    pub fn mapping(&self) -> &Arc<OrdinalMap> {
+        &self.mapping
+    }
+
+    /// The sorted doc values of the leaves.
+    pub fn values(&self) -> &[SortedDocValuesRef] {
+        &self.values
+    }
+}
+
+impl SortedDocValues for MultiSortedDocValues {
+    fn get_ord(&self, doc_id: DocId) -> Result<i32> {
+        let i = sub_index(doc_id, &self.starts);
+        let segment_ord = self.values[i].get_ord(doc_id - self.starts[i])?;
+        if segment_ord == -1 {
+            Ok(-1)
+        } else {
+            Ok(self.global_ords[i].get64(i64::from(segment_ord))? as i32)
+        }
+    }
+
+    fn lookup_ord(&self, ord: i32) -> Result<Vec<u8>> {
+        let segment = self.mapping.first_segment_number(i64::from(ord)) as usize;
+        let segment_ord = self.mapping.first_segment_ord(i64::from(ord));
+        self.values[segment].lookup_ord(segment_ord as i32)
+    }
+
+    fn get_value_count(&self) -> usize {
+        self.mapping.value_count() as usize
+    }
+
+    fn term_iterator(&self) -> Result<DocValuesTermIterator> {
+        Ok(DocValuesTermIterator::sorted_multi(
+            SortedDocValuesTermIterator::new(self.clone()),
+        ))
+    }
+}
+
+impl BinaryDocValues for MultiSortedDocValues {
+    fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
+        let ord = self.get_ord(doc_id)?;
+        if ord == -1 {
+            Ok(Vec::with_capacity(0))
+        } else {
+            self.lookup_ord(ord)
+        }
+    }
+
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        value.clear();
+        let ord = self.get_ord(doc_id)?;
+        if ord != -1 {
+            value.extend_from_slice(&self.lookup_ord(ord)?);
+        }
+        Ok(())
+    }
+}
+
+/// Sorted set doc values over the leaves of a composite reader, the ordinals
+/// are global ordinals given by an `OrdinalMap`.
+///
+/// Unlike `SortedSetDocValues` the ordinals of a doc are returned at once,
+/// the per leaf iteration state doesn't fit in a `SortedSetDocValuesContext`.
+pub struct MultiSortedSetDocValues {
+    values: Vec<SortedSetDocValuesRef>,
+    starts: Vec<DocId>,
+    mapping: Arc<OrdinalMap>,
+    global_ords: Vec<Arc<dyn LongValues>>,
+}
+
+impl MultiSortedSetDocValues {
+    /// The global ordinals of the values of `doc`, in increasing order.
+    pub fn ords(&self, doc: DocId) -> Result<Vec<i64>> {
+        let i = sub_index(doc, &self.starts);
+        let values = &self.values[i];
+        let mut ctx = values.set_document(doc - self.starts[i])?;
+        let mut ords = vec![];
+        loop {
+            let segment_ord = values.next_ord(&mut ctx)?;
+            if segment_ord == NO_MORE_ORDS {
+                break;
+            }
+            ords.push(self.global_ords[i].get64(segment_ord)?);
+        }
+        Ok(ords)
+    }
+
+    /// The value of the global ordinal `ord`.
+    pub fn lookup_ord(&self, ord: i64) -> Result<Vec<u8>> {
+        let segment = self.mapping.first_segment_number(ord) as usize;
+        let segment_ord = self.mapping.first_segment_ord(ord);
+        self.values[segment].lookup_ord(segment_ord)
+    }
+
+    /// The number of unique values over all the leaves.
+    pub fn get_value_count(&self) -> usize {
+        self.mapping.value_count() as usize
+    }
+
+    /// The mapping of the ordinals of the leaves to the global ordinals.
+    pub fn mapping(&self) -> &Arc<OrdinalMap> {
+        &self.mapping
+    }
+
+    /// The sorted set doc values of the leaves.
+    pub fn values(&self) -> &[SortedSetDocValuesRef] {
+        &self.values
+    }
+}
+
+/// Sorted numeric doc values over the leaves of a composite reader.
+pub struct MultiSortedNumericDocValues {
+    values: Vec<SortedNumericDocValuesRef>,
+    starts: Vec<DocId>,
+}
+
+impl MultiSortedNumericDocValues {
+    /// The values of `doc`, in increasing order.
+    pub fn values(&self, doc: DocId) -> Result<Vec<i64>> {
+        let i = sub_index(doc, &self.starts);
+        let values = &self.values[i];
+        let ctx = values.set_document(None, doc - self.starts[i])?;
+        let count = values.count(&ctx);
+        let mut result = Vec::with_capacity(count);
+        for j in 0..count {
+            result.push(values.value_at(&ctx, j)?);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sub_index() {
+        // the second leaf is empty
+        let starts = [0, 10, 10, 25, 40];
+        assert_eq!(sub_index(0, &starts), 0);
+        assert_eq!(sub_index(9, &starts), 0);
+        assert_eq!(sub_index(10, &starts), 2);
+        assert_eq!(sub_index(24, &starts), 2);
+        assert_eq!(sub_index(25, &starts), 3);
+        assert_eq!(sub_index(39, &starts), 3);
+    }
+}
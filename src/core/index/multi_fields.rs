@@ -25,7 +25,7 @@ use core::index::ReaderSlice;
 use core::search::posting_iterator::PostingIterator;
 use core::search::{DocIterator, Payload, NO_MORE_DOCS};
 use core::store::Directory;
-use core::util::DocId;
+use core::util::{Accountable, DocId};
 
 use error::ErrorKind::{CorruptIndex, UnsupportedOperation};
 use error::Result;
@@ -109,6 +109,12 @@ where
     }
 }
 
+impl<T: FieldsProducer> Accountable for MultiFields<T> {
+    fn ram_bytes_used(&self) -> usize {
+        self.subs.iter().map(Accountable::ram_bytes_used).sum()
+    }
+}
+
 fn fields<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
     reader: &IR,
 ) -> Result<FieldsEnum<CodecFieldsProducer<C>>> {
@@ -130,6 +136,10 @@ fn fields<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
         Ok(FieldsEnum::Multi(MultiFields::new(fields, slices)))
     }
 }
+
+/// Returns the terms of `field` merged over all the leaves of `reader`, the
+/// doc ids of the postings are top-level doc ids. See `get_sorted_values`
+/// for the merged views of the doc values.
 pub fn get_terms<C: Codec, IR: IndexReader<Codec = C> + ?Sized>(
     reader: &IR,
     field: &str,
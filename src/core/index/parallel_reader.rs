@@ -0,0 +1,640 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CodecTVFields, DocValuesProducerRef, VectorReaderRef};
+use core::doc::{Document, DocumentStoredFieldVisitor};
+use core::index::merge_policy::MergePolicy;
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{
+    BinaryDocValuesRef, FieldInfo, FieldInfos, Fields, IndexReader, LeafReader, LeafReaderContext,
+    NumericDocValues, NumericDocValuesRef, SearchLeafReader, SegmentReader, SortedDocValuesRef,
+    SortedNumericDocValuesRef, SortedSetDocValuesRef, StandardDirectoryReader, Status,
+    StoredFieldVisitor, VectorValues,
+};
+use core::search::sort::Sort;
+use core::store::Directory;
+use core::util::external::deferred::Deferred;
+use core::util::{BitsRef, DocId};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A `LeafReader` stitching several readers of segments having the same
+/// docs, with the same doc ids, but different fields, into one reader
+/// having all the fields, e.g. to keep the fast changing fields of the
+/// docs in a small side index which is cheap to rebuild.
+///
+/// Each field is read from the first reader having it. The live docs are
+/// the ones of the first reader. The readers keep their codec readers, so
+/// `fields()` is the one of the first reader, while `terms`, the doc
+/// values, the norms and the stored fields are read from the reader of the
+/// field. At most one of the readers may have term vectors and at most one
+/// may have points.
+pub struct ParallelLeafReader<T: LeafReader> {
+    readers: Vec<Arc<T>>,
+    field_infos: Arc<FieldInfos>,
+    // the index in `readers` of the reader of each field
+    field_to_reader: HashMap<String, usize>,
+    tv_reader: Option<usize>,
+    points_reader: Option<usize>,
+    core_cache_key: String,
+}
+
+impl<T: LeafReader> ParallelLeafReader<T> {
+    pub fn new(readers: Vec<Arc<T>>) -> Result<Self> {
+        if readers.is_empty() {
+            bail!(IllegalArgument(
+                "a ParallelLeafReader needs at least one reader".into()
+            ));
+        }
+        let max_doc = readers[0].max_doc();
+        let num_docs = readers[0].num_docs();
+        let mut infos = Vec::new();
+        let mut field_to_reader = HashMap::new();
+        let mut tv_reader = None;
+        let mut points_reader = None;
+        for (i, reader) in readers.iter().enumerate() {
+            if reader.max_doc() != max_doc || reader.num_docs() != num_docs {
+                bail!(IllegalArgument(format!(
+                    "all readers must have the same max_doc and num_docs, expected {}/{} but \
+                     reader {} has {}/{}",
+                    max_doc,
+                    num_docs,
+                    reader.name(),
+                    reader.max_doc(),
+                    reader.num_docs()
+                )));
+            }
+            let mut has_vectors = false;
+            let mut has_points = false;
+            // the field numbers of the readers are unrelated, renumber them
+            for info in reader.field_infos().by_number.values() {
+                if field_to_reader.contains_key(&info.name) {
+                    continue;
+                }
+                has_vectors |= info.has_store_term_vector;
+                has_points |= info.point_dimension_count > 0;
+                field_to_reader.insert(info.name.clone(), i);
+                let mut info = info.as_ref().clone();
+                info.number = infos.len() as u32;
+                infos.push(info);
+            }
+            if has_vectors {
+                if tv_reader.is_some() {
+                    bail!(IllegalArgument(
+                        "only one of the parallel readers may have term vectors".into()
+                    ));
+                }
+                tv_reader = Some(i);
+            }
+            if has_points {
+                if points_reader.is_some() {
+                    bail!(IllegalArgument(
+                        "only one of the parallel readers may have points".into()
+                    ));
+                }
+                points_reader = Some(i);
+            }
+        }
+        let keys: Vec<&str> = readers.iter().map(|r| r.core_cache_key()).collect();
+        let core_cache_key = format!("parallel({})", keys.join(","));
+        Ok(ParallelLeafReader {
+            field_infos: Arc::new(FieldInfos::new(infos)?),
+            readers,
+            field_to_reader,
+            tv_reader,
+            points_reader,
+            core_cache_key,
+        })
+    }
+
+    pub fn readers(&self) -> &[Arc<T>] {
+        &self.readers
+    }
+
+    fn reader_for_field(&self, field: &str) -> Option<&T> {
+        self.field_to_reader
+            .get(field)
+            .map(|&i| self.readers[i].as_ref())
+    }
+
+    fn checked_reader_for_field(&self, field: &str) -> Result<&T> {
+        match self.reader_for_field(field) {
+            Some(reader) => Ok(reader),
+            None => bail!(IllegalArgument(format!("non-exist field: {}", field))),
+        }
+    }
+}
+
+impl<T: LeafReader + 'static> LeafReader for ParallelLeafReader<T> {
+    type Codec = T::Codec;
+    type FieldsProducer = T::FieldsProducer;
+    type TVFields = T::TVFields;
+    type TVReader = T::TVReader;
+    type StoredReader = T::StoredReader;
+    type NormsReader = T::NormsReader;
+    type PointsReader = T::PointsReader;
+
+    fn codec(&self) -> &Self::Codec {
+        self.readers[0].codec()
+    }
+
+    fn fields(&self) -> Result<Self::FieldsProducer> {
+        self.readers[0].fields()
+    }
+
+    fn name(&self) -> &str {
+        self.readers[0].name()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<<Self::FieldsProducer as Fields>::Terms>> {
+        match self.reader_for_field(field) {
+            Some(reader) => reader.terms(field),
+            None => Ok(None),
+        }
+    }
+
+    fn term_vector(&self, doc_id: DocId) -> Result<Option<Self::TVFields>> {
+        match self.tv_reader {
+            Some(i) => self.readers[i].term_vector(doc_id),
+            None => Ok(None),
+        }
+    }
+
+    fn document(&self, doc_id: DocId, visitor: &mut dyn StoredFieldVisitor) -> Result<()> {
+        for (i, reader) in self.readers.iter().enumerate() {
+            let mut visitor = ParallelStoredFieldVisitor {
+                field_to_reader: &self.field_to_reader,
+                reader: i,
+                visitor: &mut *visitor,
+            };
+            reader.document(doc_id, &mut visitor)?;
+        }
+        Ok(())
+    }
+
+    fn live_docs(&self) -> BitsRef {
+        self.readers[0].live_docs()
+    }
+
+    fn field_info(&self, field: &str) -> Option<&FieldInfo> {
+        self.field_infos.field_info_by_name(field)
+    }
+
+    fn field_infos(&self) -> &FieldInfos {
+        &self.field_infos
+    }
+
+    fn clone_field_infos(&self) -> Arc<FieldInfos> {
+        Arc::clone(&self.field_infos)
+    }
+
+    fn max_doc(&self) -> DocId {
+        self.readers[0].max_doc()
+    }
+
+    fn num_docs(&self) -> i32 {
+        self.readers[0].num_docs()
+    }
+
+    fn get_numeric_doc_values(&self, field: &str) -> Result<NumericDocValuesRef> {
+        self.checked_reader_for_field(field)?
+            .get_numeric_doc_values(field)
+    }
+
+    fn get_binary_doc_values(&self, field: &str) -> Result<BinaryDocValuesRef> {
+        self.checked_reader_for_field(field)?
+            .get_binary_doc_values(field)
+    }
+
+    fn get_sorted_doc_values(&self, field: &str) -> Result<SortedDocValuesRef> {
+        self.checked_reader_for_field(field)?
+            .get_sorted_doc_values(field)
+    }
+
+    fn get_sorted_numeric_doc_values(&self, field: &str) -> Result<SortedNumericDocValuesRef> {
+        self.checked_reader_for_field(field)?
+            .get_sorted_numeric_doc_values(field)
+    }
+
+    fn get_sorted_set_doc_values(&self, field: &str) -> Result<SortedSetDocValuesRef> {
+        self.checked_reader_for_field(field)?
+            .get_sorted_set_doc_values(field)
+    }
+
+    fn norm_values(&self, field: &str) -> Result<Option<Box<dyn NumericDocValues>>> {
+        match self.reader_for_field(field) {
+            Some(reader) => reader.norm_values(field),
+            None => Ok(None),
+        }
+    }
+
+    fn get_docs_with_field(&self, field: &str) -> Result<BitsRef> {
+        self.checked_reader_for_field(field)?
+            .get_docs_with_field(field)
+    }
+
+    fn point_values(&self) -> Option<Self::PointsReader> {
+        self.points_reader
+            .and_then(|i| self.readers[i].point_values())
+    }
+
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        match self.reader_for_field(field) {
+            Some(reader) => reader.vector_values(field),
+            None => Ok(None),
+        }
+    }
+
+    fn core_cache_key(&self) -> &str {
+        &self.core_cache_key
+    }
+
+    fn index_sort(&self) -> Option<&Sort> {
+        self.readers[0].index_sort()
+    }
+
+    fn add_core_drop_listener(&self, listener: Deferred) {
+        // the parallel readers are opened and dropped together
+        self.readers[0].add_core_drop_listener(listener)
+    }
+
+    fn is_codec_reader(&self) -> bool {
+        false
+    }
+
+    fn store_fields_reader(&self) -> Result<Self::StoredReader> {
+        unreachable!()
+    }
+
+    fn term_vectors_reader(&self) -> Result<Option<Self::TVReader>> {
+        unreachable!()
+    }
+
+    fn norms_reader(&self) -> Result<Option<Self::NormsReader>> {
+        unreachable!()
+    }
+
+    fn doc_values_reader(&self) -> Result<Option<DocValuesProducerRef>> {
+        unreachable!()
+    }
+
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        unreachable!()
+    }
+
+    fn postings_reader(&self) -> Result<Self::FieldsProducer> {
+        unreachable!()
+    }
+}
+
+/// Only visits the stored fields read from `reader`, so that a field in
+/// several readers is visited once.
+struct ParallelStoredFieldVisitor<'a> {
+    field_to_reader: &'a HashMap<String, usize>,
+    reader: usize,
+    visitor: &'a mut dyn StoredFieldVisitor,
+}
+
+impl<'a> StoredFieldVisitor for ParallelStoredFieldVisitor<'a> {
+    fn binary_field(&mut self, field_info: &FieldInfo, value: Vec<u8>) -> Result<()> {
+        self.visitor.binary_field(field_info, value)
+    }
+
+    fn string_field(&mut self, field_info: &FieldInfo, value: Vec<u8>) -> Result<()> {
+        self.visitor.string_field(field_info, value)
+    }
+
+    fn int_field(&mut self, field_info: &FieldInfo, value: i32) -> Result<()> {
+        self.visitor.int_field(field_info, value)
+    }
+
+    fn long_field(&mut self, field_info: &FieldInfo, value: i64) -> Result<()> {
+        self.visitor.long_field(field_info, value)
+    }
+
+    fn float_field(&mut self, field_info: &FieldInfo, value: f32) -> Result<()> {
+        self.visitor.float_field(field_info, value)
+    }
+
+    fn double_field(&mut self, field_info: &FieldInfo, value: f64) -> Result<()> {
+        self.visitor.double_field(field_info, value)
+    }
+
+    fn needs_field(&self, field_info: &FieldInfo) -> Status {
+        if self.field_to_reader.get(&field_info.name) == Some(&self.reader) {
+            self.visitor.needs_field(field_info)
+        } else {
+            Status::No
+        }
+    }
+}
+
+/// An `IndexReader` stitching several indexes having the same docs, with
+/// the same doc ids, but different fields into one index having all the
+/// fields, see `ParallelLeafReader`.
+///
+/// The indexes must have the same segments, i.e. the same number of
+/// segments with the same number of docs, which is the case when they are
+/// written with the same sequence of `add_document` and `commit` calls, a
+/// `LogDocMergePolicy` or no merges at all.
+pub struct ParallelCompositeReader<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    readers: Vec<StandardDirectoryReader<D, C, MS, MP>>,
+    leaves: Vec<ParallelLeafReader<SegmentReader<D, C>>>,
+    starts: Vec<i32>,
+    num_docs: i32,
+}
+
+impl<D, C, MS, MP> ParallelCompositeReader<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    pub fn new(readers: Vec<StandardDirectoryReader<D, C, MS, MP>>) -> Result<Self> {
+        if readers.is_empty() {
+            bail!(IllegalArgument(
+                "a ParallelCompositeReader needs at least one reader".into()
+            ));
+        }
+        let segment_count = readers[0].segment_readers().len();
+        if let Some(r) = readers
+            .iter()
+            .find(|r| r.segment_readers().len() != segment_count)
+        {
+            bail!(IllegalArgument(format!(
+                "all readers must have the same segments, expected {} segments but got {}",
+                segment_count,
+                r.segment_readers().len()
+            )));
+        }
+
+        let mut leaves = Vec::with_capacity(segment_count);
+        let mut starts = Vec::with_capacity(segment_count + 1);
+        let mut max_doc = 0;
+        let mut num_docs = 0;
+        for i in 0..segment_count {
+            let segment_readers = readers
+                .iter()
+                .map(|r| Arc::clone(&r.segment_readers()[i]))
+                .collect();
+            let leaf = ParallelLeafReader::new(segment_readers)?;
+            starts.push(max_doc);
+            max_doc += leaf.max_doc();
+            num_docs += leaf.num_docs();
+            leaves.push(leaf);
+        }
+        starts.push(max_doc);
+        Ok(ParallelCompositeReader {
+            readers,
+            leaves,
+            starts,
+            num_docs,
+        })
+    }
+
+    pub fn readers(&self) -> &[StandardDirectoryReader<D, C, MS, MP>] {
+        &self.readers
+    }
+
+    fn leaf_index(&self, doc_id: DocId) -> Result<usize> {
+        if doc_id < 0 || doc_id >= self.max_doc() {
+            bail!(IllegalArgument(format!(
+                "doc_id {} invalid: [max_doc={}]",
+                doc_id,
+                self.max_doc()
+            )));
+        }
+        Ok(match self.starts.binary_search(&doc_id) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        })
+    }
+}
+
+impl<D, C, MS, MP> IndexReader for ParallelCompositeReader<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    type Codec = C;
+
+    fn leaves(&self) -> Vec<LeafReaderContext<'_, C>> {
+        self.leaves
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                LeafReaderContext::new(self, r as &SearchLeafReader<C>, i, self.starts[i])
+            })
+            .collect()
+    }
+
+    fn term_vector(&self, doc_id: DocId) -> Result<Option<CodecTVFields<C>>> {
+        let i = self.leaf_index(doc_id)?;
+        self.leaves[i].term_vector(doc_id - self.starts[i])
+    }
+
+    fn document(&self, doc_id: DocId, fields_load: &[String]) -> Result<Document> {
+        let i = self.leaf_index(doc_id)?;
+        let mut visitor = DocumentStoredFieldVisitor::new(fields_load);
+        self.leaves[i].document(doc_id - self.starts[i], &mut visitor)?;
+        Ok(visitor.document())
+    }
+
+    fn max_doc(&self) -> i32 {
+        self.starts[self.leaves.len()]
+    }
+
+    fn num_docs(&self) -> i32 {
+        self.num_docs
+    }
+}
+
+impl<D, C, MS, MP> fmt::Debug for ParallelCompositeReader<D, C, MS, MP>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ParallelCompositeReader({:?})", self.readers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::CodecEnum;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::merge_policy::TieredMergePolicy;
+    use core::index::merge_scheduler::SerialMergeScheduler;
+    use core::index::{Fieldable, IndexOptions, IndexWriter};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::path::PathBuf;
+
+    type TestReader = StandardDirectoryReader<
+        FSDirectory<NativeFSLockFactory>,
+        CodecEnum,
+        SerialMergeScheduler,
+        TieredMergePolicy,
+    >;
+
+    fn string_field(name: &str, value: String) -> Box<dyn Fieldable> {
+        let mut field_type = FieldType::default();
+        field_type.tokenized = false;
+        field_type.stored = true;
+        field_type.index_options = IndexOptions::Docs;
+        field_type.omit_norms = true;
+        Box::new(Field::new(
+            name.into(),
+            field_type,
+            Some(VariantValue::VString(value)),
+            None,
+        ))
+    }
+
+    fn main_document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        vec![
+            string_field("id", id.to_string()),
+            Box::new(NumericDocValuesField::new("value", id)),
+        ]
+    }
+
+    /// The side docs also have an "id" and a "value", which are shadowed by
+    /// the ones of the main docs.
+    fn side_document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        vec![
+            string_field("id", format!("side{}", id)),
+            string_field("rank", format!("r{}", id)),
+            Box::new(NumericDocValuesField::new("value", -1)),
+            Box::new(NumericDocValuesField::new("rank_value", id * 100)),
+        ]
+    }
+
+    /// Writes `num_docs` docs, committing a segment every `segment_size` docs.
+    fn test_index(
+        name: &str,
+        num_docs: i64,
+        segment_size: i64,
+        document: fn(i64) -> Vec<Box<dyn Fieldable>>,
+    ) -> (PathBuf, TestReader) {
+        let path = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..num_docs {
+            writer.add_document(document(i)).unwrap();
+            if (i + 1) % segment_size == 0 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.commit().unwrap();
+        writer.close().unwrap();
+        (path, StandardDirectoryReader::open(dir).unwrap())
+    }
+
+    #[test]
+    fn test_stitched_fields() {
+        let (main_path, main) = test_index("rucene_parallel_reader_main", 6, 3, main_document);
+        let (side_path, side) = test_index("rucene_parallel_reader_side", 6, 3, side_document);
+        let reader = ParallelCompositeReader::new(vec![main, side]).unwrap();
+        assert_eq!(reader.max_doc(), 6);
+        assert_eq!(reader.num_docs(), 6);
+
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 2);
+        for leaf in &leaves {
+            let leaf_reader = leaf.reader;
+            let infos = leaf_reader.field_infos();
+            assert_eq!(infos.len(), 4);
+            // the field numbers of the side reader are renumbered
+            let numbers: HashSet<u32> = infos.by_number.keys().cloned().collect();
+            assert_eq!(numbers.len(), 4);
+            for name in &["id", "value", "rank", "rank_value"] {
+                assert!(leaf_reader.field_info(name).is_some());
+            }
+            assert!(leaf_reader.field_info("missing").is_none());
+
+            assert!(leaf_reader.terms("id").unwrap().is_some());
+            assert!(leaf_reader.terms("rank").unwrap().is_some());
+            assert!(leaf_reader.terms("missing").unwrap().is_none());
+            assert!(leaf_reader.get_numeric_doc_values("missing").is_err());
+
+            let values = leaf_reader.get_numeric_doc_values("value").unwrap();
+            let rank_values = leaf_reader.get_numeric_doc_values("rank_value").unwrap();
+            for doc in 0..leaf_reader.max_doc() {
+                let id = i64::from(leaf.doc_base + doc);
+                assert_eq!(values.get(doc).unwrap(), id);
+                assert_eq!(rank_values.get(doc).unwrap(), id * 100);
+            }
+        }
+
+        let fields = ["id".to_string(), "rank".to_string()];
+        for doc in 0..6 {
+            let document = reader.document(doc, &fields).unwrap();
+            assert_eq!(document.fields.len(), 2);
+            let value = |name: &str| {
+                document
+                    .fields
+                    .iter()
+                    .find(|f| f.field.name() == name)
+                    .and_then(|f| f.field.string_value())
+                    .map(|v| v.to_string())
+            };
+            assert_eq!(value("id"), Some(doc.to_string()));
+            assert_eq!(value("rank"), Some(format!("r{}", doc)));
+        }
+        assert!(reader.document(6, &fields).is_err());
+
+        drop(reader);
+        fs::remove_dir_all(&main_path).unwrap();
+        fs::remove_dir_all(&side_path).unwrap();
+    }
+
+    #[test]
+    fn test_max_doc_mismatch() {
+        let (main_path, main) =
+            test_index("rucene_parallel_reader_main_mismatch", 6, 3, main_document);
+        let (side_path, side) =
+            test_index("rucene_parallel_reader_side_mismatch", 6, 4, side_document);
+        // the same number of segments, but with different max docs
+        assert_eq!(main.segment_readers().len(), side.segment_readers().len());
+        let leaf_readers = vec![
+            Arc::clone(&main.segment_readers()[0]),
+            Arc::clone(&side.segment_readers()[0]),
+        ];
+        assert!(ParallelLeafReader::new(leaf_readers).is_err());
+        assert!(ParallelCompositeReader::new(vec![main, side]).is_err());
+
+        fs::remove_dir_all(&main_path).unwrap();
+        fs::remove_dir_all(&side_path).unwrap();
+    }
+}
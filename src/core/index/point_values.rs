@@ -78,6 +78,17 @@ pub trait PointValues {
     /// to test whether each document is deleted, if necessary.
     fn intersect(&self, field_name: &str, visitor: &mut impl IntersectVisitor) -> Result<()>;
 
+    /// Estimates the number of points `intersect` would visit with the given
+    /// visitor, e.g. to pick a cheaper way to run a query. The default is the
+    /// number of points of the field.
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        _visitor: &mut impl IntersectVisitor,
+    ) -> Result<i64> {
+        self.size(field_name)
+    }
+
     /// Returns minimum value for each dimension, packed, or null if `size` is 0
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>>;
 
@@ -105,6 +116,14 @@ impl<T: PointValues + 'static> PointValues for Arc<T> {
         (**self).intersect(field_name, visitor)
     }
 
+    fn estimate_point_count(
+        &self,
+        field_name: &str,
+        visitor: &mut impl IntersectVisitor,
+    ) -> Result<i64> {
+        (**self).estimate_point_count(field_name, visitor)
+    }
+
     fn min_packed_value(&self, field_name: &str) -> Result<Vec<u8>> {
         (**self).min_packed_value(field_name)
     }
@@ -14,7 +14,7 @@
 use core::index::Term;
 use core::store::{DataInput, DataOutput, IndexInput, RAMOutputStream};
 use core::util::fst::bytes_store::{BytesStore, StoreBytesReader};
-use core::util::{BytesRef, BytesRefBuilder};
+use core::util::{Accountable, BytesRef, BytesRefBuilder};
 
 use error::Result;
 
@@ -44,6 +44,12 @@ impl Default for PrefixCodedTerms {
     }
 }
 
+impl Accountable for PrefixCodedTerms {
+    fn ram_bytes_used(&self) -> usize {
+        self.buffer.len() + 2 * 8
+    }
+}
+
 impl PrefixCodedTerms {
     fn new(buffer: BytesStore, size: usize) -> Self {
         PrefixCodedTerms {
@@ -53,10 +59,6 @@ impl PrefixCodedTerms {
         }
     }
 
-    pub fn ram_bytes_used(&self) -> usize {
-        self.buffer.len() + 2 * 8
-    }
-
     /// Records del gen for this packet.
     pub fn set_del_gen(&self, del_gen: u64) {
         self.del_gen.store(del_gen, AtomicOrdering::Release);
@@ -237,8 +239,14 @@ impl TermsIteratorByTerm {
 
 impl Ord for TermsIteratorByTerm {
     fn cmp(&self, other: &Self) -> Ordering {
-        // reversed ordering for BinaryHeap
-        other.iter.bytes().cmp(&self.iter.bytes())
+        // reversed ordering for BinaryHeap, a term in several packets comes
+        // first from the newest one: the deletes are applied once per term
+        // and segment, and only the packets newer than the segment apply
+        other
+            .iter
+            .bytes()
+            .cmp(&self.iter.bytes())
+            .then_with(|| self.iter.del_gen.cmp(&other.iter.del_gen))
     }
 }
 
@@ -252,7 +260,7 @@ impl Eq for TermsIteratorByTerm {}
 
 impl PartialEq for TermsIteratorByTerm {
     fn eq(&self, other: &Self) -> bool {
-        self.iter.bytes() == other.iter.bytes()
+        self.cmp(other) == Ordering::Equal
     }
 }
 
@@ -403,3 +411,40 @@ impl FieldTermIter for FieldTermIterator {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(terms: &[&str], del_gen: u64) -> Arc<PrefixCodedTerms> {
+        let mut builder = PrefixCodedTermsBuilder::default();
+        for term in terms {
+            builder.add("id".into(), term.as_bytes()).unwrap();
+        }
+        let terms = builder.finish();
+        terms.set_del_gen(del_gen);
+        Arc::new(terms)
+    }
+
+    #[test]
+    fn test_merged_iterator_newest_packet_first() {
+        let packets = vec![
+            packet(&["a", "b"], 2),
+            packet(&["b", "c"], 6),
+            packet(&["b"], 4),
+        ];
+        let mut iter = MergedPrefixCodedTermsIterator::new(&packets).unwrap();
+        let mut terms = vec![];
+        while let Some(term) = iter.next().unwrap() {
+            terms.push((term.bytes().to_vec(), iter.del_gen()));
+        }
+        let expected: Vec<(Vec<u8>, i64)> = vec![
+            (b"a".to_vec(), 2),
+            (b"b".to_vec(), 6),
+            (b"b".to_vec(), 4),
+            (b"b".to_vec(), 2),
+            (b"c".to_vec(), 6),
+        ];
+        assert_eq!(terms, expected);
+    }
+}
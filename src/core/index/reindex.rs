@@ -0,0 +1,484 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, CompressionMode, BINARY_AUTO_COMPRESSION, BINARY_COMPRESSION_KEY};
+use core::doc::{
+    BinaryDocValuesField, DocumentStoredFieldVisitor, Field, NumericDocValuesField,
+    SortedNumericDocValuesField, SortedSetDocValuesField, StoredField,
+    SORTED_DOC_VALUES_FIELD_TYPE,
+};
+use core::index::index_writer::IndexWriter;
+use core::index::merge_policy::MergePolicy;
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{
+    BinaryDocValuesRef, DocValuesReader, DocValuesType, Fieldable, IndexReader, LeafReader,
+    LiveDocsIterator, NumericValuesReader, SortedDocValuesRef, SortedNumericValuesReader,
+    SortedSetDocValuesRef, NO_MORE_ORDS,
+};
+use core::store::Directory;
+use core::util::{BitsRef, DocId, VariantValue};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::mem;
+
+/// The default number of documents added to the destination writer at once.
+pub const DEFAULT_REINDEX_BATCH_SIZE: usize = 1000;
+
+/// A document read from the source index of a reindex.
+///
+/// Only the stored fields and the doc values can be read back from an index,
+/// the indexed fields have to be rebuilt by the transformation, e.g. by
+/// analyzing the value of a stored field again.
+pub struct ReindexDocument {
+    /// The id of the document in the source reader.
+    pub doc: DocId,
+    pub stored_fields: Vec<StoredField>,
+    /// One field per doc values value, of the type of the source field.
+    pub doc_values: Vec<Box<dyn Fieldable>>,
+}
+
+impl ReindexDocument {
+    /// Returns the fields to add to copy this document as is: the stored
+    /// fields are only stored, not indexed, and the doc values are kept.
+    pub fn into_fields(self) -> Vec<Box<dyn Fieldable>> {
+        let mut fields: Vec<Box<dyn Fieldable>> =
+            Vec::with_capacity(self.stored_fields.len() + self.doc_values.len());
+        for stored in self.stored_fields {
+            let name = stored.field.name().to_string();
+            if let Some(value) = stored.field.fields_data() {
+                fields.push(Box::new(StoredField::new(&name, None, value.clone()).field));
+            }
+        }
+        fields.extend(self.doc_values);
+        fields
+    }
+}
+
+/// A document which couldn't be transformed.
+#[derive(Debug, Clone)]
+pub struct ReindexFailure {
+    pub doc: DocId,
+    pub error: String,
+}
+
+/// The progress of a reindex, reported after each batch.
+#[derive(Debug, Default, Clone)]
+pub struct ReindexStats {
+    /// The number of live documents read from the source.
+    pub read: u64,
+    /// The number of documents added to the destination.
+    pub written: u64,
+    /// The number of documents the transformation dropped.
+    pub skipped: u64,
+    /// The documents the transformation failed on, only collected when
+    /// `Reindexer::set_continue_on_error` is set.
+    pub failures: Vec<ReindexFailure>,
+}
+
+/// Copies the live documents of a reader to an `IndexWriter`, through a
+/// transformation of each document, e.g. to migrate an index to a new schema
+/// without reading the documents back from an external source.
+///
+/// The transformation returns the fields of the new document, or None to
+/// drop the document. The documents are added by batches of `batch_size`
+/// with `IndexWriter::add_documents`, and the progress is reported after
+/// each batch. Nothing is committed, which is left to the caller.
+///
+/// An error of the transformation aborts the reindex, unless
+/// `set_continue_on_error` is set, then the failed documents are skipped and
+/// reported in `ReindexStats::failures`. Errors reading the source or adding
+/// the documents always abort the reindex.
+pub struct Reindexer<T> {
+    transform: T,
+    batch_size: usize,
+    continue_on_error: bool,
+    fields: Vec<String>,
+}
+
+impl<T> Reindexer<T>
+where
+    T: FnMut(ReindexDocument) -> Result<Option<Vec<Box<dyn Fieldable>>>>,
+{
+    pub fn new(transform: T) -> Reindexer<T> {
+        Reindexer {
+            transform,
+            batch_size: DEFAULT_REINDEX_BATCH_SIZE,
+            continue_on_error: false,
+            fields: vec![],
+        }
+    }
+
+    pub fn set_batch_size(&mut self, batch_size: usize) {
+        self.batch_size = batch_size;
+    }
+
+    pub fn set_continue_on_error(&mut self, continue_on_error: bool) {
+        self.continue_on_error = continue_on_error;
+    }
+
+    /// Restricts the stored fields and doc values read from the source to
+    /// the given fields, by default all of them are read.
+    pub fn set_fields(&mut self, fields: Vec<String>) {
+        self.fields = fields;
+    }
+
+    /// Reindexes all the live documents of `source` into `writer`, calling
+    /// `progress` after each batch.
+    pub fn reindex<R, D, C, MS, MP, P>(
+        &mut self,
+        source: &R,
+        writer: &IndexWriter<D, C, MS, MP>,
+        mut progress: P,
+    ) -> Result<ReindexStats>
+    where
+        R: IndexReader + ?Sized,
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+        P: FnMut(&ReindexStats),
+    {
+        if self.batch_size == 0 {
+            bail!(IllegalArgument("batch_size must be positive".into()));
+        }
+        let mut stats = ReindexStats::default();
+        let mut batch = Vec::with_capacity(self.batch_size);
+        for leaf in source.leaves() {
+            let reader = leaf.reader;
+            let mut doc_values = self.doc_values_fields(reader)?;
+            for doc in LiveDocsIterator::new(reader) {
+                let doc = doc?;
+                let mut visitor = DocumentStoredFieldVisitor::new(&self.fields);
+                reader.document(doc, &mut visitor)?;
+                let mut values = vec![];
+                for field in &mut doc_values {
+                    field.read(doc, &mut values)?;
+                }
+                stats.read += 1;
+
+                let document = ReindexDocument {
+                    doc: leaf.doc_base + doc,
+                    stored_fields: visitor.fields,
+                    doc_values: values,
+                };
+                match (self.transform)(document) {
+                    Ok(Some(fields)) => batch.push(fields),
+                    Ok(None) => stats.skipped += 1,
+                    Err(e) => {
+                        if !self.continue_on_error {
+                            return Err(e);
+                        }
+                        stats.failures.push(ReindexFailure {
+                            doc: leaf.doc_base + doc,
+                            error: e.to_string(),
+                        });
+                    }
+                }
+                if batch.len() >= self.batch_size {
+                    Self::add_batch(writer, &mut batch, &mut stats)?;
+                    progress(&stats);
+                }
+            }
+        }
+        if !batch.is_empty() {
+            Self::add_batch(writer, &mut batch, &mut stats)?;
+        }
+        progress(&stats);
+        Ok(stats)
+    }
+
+    fn add_batch<D, C, MS, MP>(
+        writer: &IndexWriter<D, C, MS, MP>,
+        batch: &mut Vec<Vec<Box<dyn Fieldable>>>,
+        stats: &mut ReindexStats,
+    ) -> Result<()>
+    where
+        D: Directory + Send + Sync + 'static,
+        C: Codec,
+        MS: MergeScheduler,
+        MP: MergePolicy,
+    {
+        let docs = mem::replace(batch, Vec::with_capacity(batch.capacity()));
+        let count = docs.len() as u64;
+        writer.add_documents(docs)?;
+        stats.written += count;
+        Ok(())
+    }
+
+    fn doc_values_fields<L: LeafReader + ?Sized>(&self, reader: &L) -> Result<Vec<FieldValues>> {
+        let mut fields = vec![];
+        for info in reader.field_infos().by_number.values() {
+            if !self.fields.is_empty() && !self.fields.contains(&info.name) {
+                continue;
+            }
+            let name = info.name.clone();
+            let values = match info.doc_values_type {
+                DocValuesType::Null => continue,
+                DocValuesType::Numeric => {
+                    DocValues::Numeric(NumericValuesReader::new(reader, &name)?)
+                }
+                DocValuesType::SortedNumeric => {
+                    DocValues::SortedNumeric(SortedNumericValuesReader::new(reader, &name)?)
+                }
+                DocValuesType::Binary => DocValues::Binary(
+                    reader.get_binary_doc_values(&name)?,
+                    reader.get_docs_with_field(&name)?,
+                    info.attribute(BINARY_COMPRESSION_KEY),
+                ),
+                DocValuesType::Sorted => DocValues::Sorted(reader.get_sorted_doc_values(&name)?),
+                DocValuesType::SortedSet => {
+                    DocValues::SortedSet(reader.get_sorted_set_doc_values(&name)?)
+                }
+            };
+            fields.push(FieldValues { name, values });
+        }
+        Ok(fields)
+    }
+}
+
+enum DocValues {
+    Numeric(NumericValuesReader),
+    SortedNumeric(SortedNumericValuesReader),
+    // the compression attribute of the field, if any
+    Binary(BinaryDocValuesRef, BitsRef, Option<String>),
+    Sorted(SortedDocValuesRef),
+    SortedSet(SortedSetDocValuesRef),
+}
+
+/// The doc values of a field of the current leaf, read back as the fields
+/// which would index them again.
+struct FieldValues {
+    name: String,
+    values: DocValues,
+}
+
+impl FieldValues {
+    fn read(&mut self, doc: DocId, fields: &mut Vec<Box<dyn Fieldable>>) -> Result<()> {
+        let name = &self.name;
+        match self.values {
+            DocValues::Numeric(ref mut values) => {
+                if let Some(v) = values.read(doc)? {
+                    fields.push(Box::new(NumericDocValuesField::new(name, v)));
+                }
+            }
+            DocValues::SortedNumeric(ref mut values) => {
+                if let Some(vs) = values.read(doc)? {
+                    for v in vs {
+                        fields.push(Box::new(SortedNumericDocValuesField::new(name, v)));
+                    }
+                }
+            }
+            DocValues::Binary(ref values, ref docs_with_field, ref compression) => {
+                if docs_with_field.get(doc as usize)? {
+                    let value = values.get(doc)?;
+                    let field = match compression {
+                        Some(mode) if mode == BINARY_AUTO_COMPRESSION => {
+                            BinaryDocValuesField::with_auto_compression(name, &value)
+                        }
+                        Some(mode) => match CompressionMode::for_name(mode) {
+                            Some(mode) => {
+                                BinaryDocValuesField::with_compression(name, &value, mode)
+                            }
+                            None => BinaryDocValuesField::new(name, &value),
+                        },
+                        None => BinaryDocValuesField::new(name, &value),
+                    };
+                    fields.push(Box::new(field));
+                }
+            }
+            DocValues::Sorted(ref values) => {
+                let ord = values.get_ord(doc)?;
+                if ord >= 0 {
+                    fields.push(Box::new(Field::new(
+                        name.clone(),
+                        SORTED_DOC_VALUES_FIELD_TYPE,
+                        Some(VariantValue::Binary(values.lookup_ord(ord)?)),
+                        None,
+                    )));
+                }
+            }
+            DocValues::SortedSet(ref values) => {
+                let mut ctx = values.set_document(doc)?;
+                loop {
+                    let ord = values.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    let value = values.lookup_ord(ord)?;
+                    fields.push(Box::new(SortedSetDocValuesField::new(name, &value)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::FieldType;
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{IndexOptions, Term};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    fn test_directory(name: &str) -> (PathBuf, Arc<FSDirectory<NativeFSLockFactory>>) {
+        let path = ::std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&path);
+        let dir = FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap();
+        (path, Arc::new(dir))
+    }
+
+    fn document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.stored = true;
+        id_type.index_options = IndexOptions::Docs;
+        id_type.omit_norms = true;
+        vec![
+            Box::new(Field::new(
+                "id".into(),
+                id_type,
+                Some(VariantValue::VString(id.to_string())),
+                None,
+            )),
+            Box::new(
+                StoredField::new(
+                    "title",
+                    None,
+                    VariantValue::VString(format!("title {}", id)),
+                )
+                .field,
+            ),
+            Box::new(NumericDocValuesField::new("num", id * 10)),
+            Box::new(SortedSetDocValuesField::new(
+                "tags",
+                format!("t{}", id % 2).as_bytes(),
+            )),
+            Box::new(SortedSetDocValuesField::new("tags", b"all")),
+        ]
+    }
+
+    fn stored_id(document: &ReindexDocument) -> String {
+        document
+            .stored_fields
+            .iter()
+            .find(|f| f.field.name() == "id")
+            .and_then(|f| f.field.string_value())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_reindex() {
+        let (source_path, source_dir) = test_directory("rucene_reindex_source");
+        let source = IndexWriter::new(source_dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for id in 0..6 {
+            source.add_document(document(id)).unwrap();
+        }
+        source
+            .delete_documents_by_terms(vec![Term::new("id".into(), b"2".to_vec())])
+            .unwrap();
+        source.commit().unwrap();
+        let source_reader = source.get_reader(true, false).unwrap();
+
+        let (dest_path, dest_dir) = test_directory("rucene_reindex_dest");
+        let dest = IndexWriter::new(dest_dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // the doc of id 4 is dropped
+        let mut reindexer = Reindexer::new(|document: ReindexDocument| {
+            if stored_id(&document) == "4" {
+                return Ok(None);
+            }
+            Ok(Some(document.into_fields()))
+        });
+        reindexer.set_batch_size(2);
+        let mut progress = vec![];
+        let stats = reindexer
+            .reindex(&source_reader, &dest, |stats| progress.push(stats.written))
+            .unwrap();
+        assert_eq!(stats.read, 5);
+        assert_eq!(stats.written, 4);
+        assert_eq!(stats.skipped, 1);
+        assert!(stats.failures.is_empty());
+        assert_eq!(progress, vec![2, 4, 4]);
+        dest.commit().unwrap();
+
+        let dest_reader = dest.get_reader(true, false).unwrap();
+        assert_eq!(dest_reader.num_docs(), 4);
+        let leaves = dest_reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let leaf = leaves[0].reader;
+        let num = leaf.get_numeric_doc_values("num").unwrap();
+        let tags = leaf.get_sorted_set_doc_values("tags").unwrap();
+        let load = vec!["id".to_string(), "title".to_string()];
+        let mut ids = vec![];
+        for doc in 0..leaf.max_doc() {
+            let stored = dest_reader.document(doc, &load).unwrap();
+            assert_eq!(stored.fields.len(), 2);
+            let id: i64 = stored.fields[0]
+                .field
+                .string_value()
+                .unwrap()
+                .parse()
+                .unwrap();
+            assert_eq!(
+                stored.fields[1].field.string_value(),
+                Some(format!("title {}", id).as_str())
+            );
+            assert_eq!(num.get(doc).unwrap(), id * 10);
+            let mut ctx = tags.set_document(doc).unwrap();
+            let mut values = vec![];
+            loop {
+                let ord = tags.next_ord(&mut ctx).unwrap();
+                if ord == NO_MORE_ORDS {
+                    break;
+                }
+                values.push(tags.lookup_ord(ord).unwrap());
+            }
+            assert_eq!(
+                values,
+                vec![b"all".to_vec(), format!("t{}", id % 2).into_bytes()]
+            );
+            ids.push(id);
+        }
+        ids.sort();
+        assert_eq!(ids, vec![0, 1, 3, 5]);
+
+        // a failing doc aborts the reindex, unless the errors are collected
+        let transform = |document: ReindexDocument| -> Result<Option<Vec<Box<dyn Fieldable>>>> {
+            if stored_id(&document) == "3" {
+                bail!(IllegalArgument("bad doc".into()));
+            }
+            Ok(None)
+        };
+        assert!(Reindexer::new(transform)
+            .reindex(&source_reader, &dest, |_| {})
+            .is_err());
+        let mut reindexer = Reindexer::new(transform);
+        reindexer.set_continue_on_error(true);
+        let stats = reindexer.reindex(&source_reader, &dest, |_| {}).unwrap();
+        assert_eq!(stats.written, 0);
+        assert_eq!(stats.failures.len(), 1);
+        assert_eq!(stats.failures[0].doc, 3);
+
+        drop(source_reader);
+        drop(dest_reader);
+        source.close().unwrap();
+        dest.close().unwrap();
+        fs::remove_dir_all(&source_path).unwrap();
+        fs::remove_dir_all(&dest_path).unwrap();
+    }
+}
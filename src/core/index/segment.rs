@@ -22,7 +22,7 @@ use core::codec::{
     codec_util, Codec, CodecNormsProducer, CodecPointsReader, CodecStoredFieldsReader,
     CodecTVReader, CompoundFormat, FieldInfosFormat, Lucene50CompoundReader, NormsFormat,
     PointsFormat, PostingsFormat, SegmentInfoFormat, StoredFieldsFormat, TermVectorsFormat,
-    CODEC_MAGIC,
+    VectorFormat, VectorReaderRef, CODEC_MAGIC,
 };
 use core::index::index_commit::IndexCommit;
 use core::index::merge_policy::OneMerge;
@@ -37,7 +37,7 @@ use core::util::external::deferred::Deferred;
 use core::util::ptr_eq;
 use core::util::string_util::{id2str, random_id, ID_LENGTH};
 use core::util::{to_base36, Version, VERSION_LATEST};
-use error::ErrorKind::{IOError, IllegalState, NumError};
+use error::ErrorKind::{CorruptIndex, IOError, IllegalState, NumError};
 use error::Result;
 
 /// The file format version for the segments_N codec header, since 5.0+
@@ -48,6 +48,12 @@ const SEGMENT_VERSION_51: i32 = 5;
 /// Adds the {@link Version} that committed this segments_N file, as well as the {@link Version} of
 /// the oldest segment, since 5.3+
 const SEGMENT_VERSION_53: i32 = 6;
+/// Adds the major version of the Lucene version that created the index, since 7.0+
+const SEGMENT_VERSION_70: i32 = 7;
+/// The counter is written as a vlong, since 7.2+
+const SEGMENT_VERSION_72: i32 = 8;
+/// Adds the soft deletes count of each segment, since 7.4+
+const SEGMENT_VERSION_74: i32 = 9;
 
 const SEGMENT_VERSION_CURRENT: i32 = SEGMENT_VERSION_53;
 
@@ -281,10 +287,14 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
             }
             output.write_int(del_count)?;
             output.write_long(commit.field_infos_gen())?;
-            output.write_long(commit.doc_values_gen)?;
-            output.write_set_of_strings(&commit.field_infos_files)?;
-            // TODO add doc_values_updates_files
-            output.write_int(0)?;
+            output.write_long(commit.doc_values_gen())?;
+            output.write_set_of_strings(&commit.field_infos_files())?;
+            let dv_updates_files = commit.dv_updates_files();
+            output.write_int(dv_updates_files.len() as i32)?;
+            for (field, files) in &dv_updates_files {
+                output.write_int(*field)?;
+                output.write_set_of_strings(files)?;
+            }
         }
         output.write_map_of_strings(&HashMap::with_capacity(0))?;
         codec_util::write_footer(output)
@@ -451,11 +461,12 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
             return Err("invalid magic number".into());
         }
 
+        // commits of Lucene 7 are read too, but always written in the current format
         let format = codec_util::check_header_no_magic(
             input,
             "segments",
             SEGMENT_VERSION_50,
-            SEGMENT_VERSION_CURRENT,
+            SEGMENT_VERSION_74,
         )?;
 
         let mut id = [0; ID_LENGTH];
@@ -470,8 +481,28 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
         } else {
             None
         };
+        if format >= SEGMENT_VERSION_70 {
+            let index_created_version_major = input.read_vint()?;
+            if index_created_version_major < 6 {
+                bail!(CorruptIndex(format!(
+                    "invalid index created version major: {}",
+                    index_created_version_major
+                )));
+            }
+        }
         let version = input.read_long()?;
-        let counter = input.read_int()?;
+        let counter = if format >= SEGMENT_VERSION_72 {
+            let counter = input.read_vlong()?;
+            if counter > i64::from(i32::max_value()) {
+                bail!(CorruptIndex(format!(
+                    "invalid segment counter: {}",
+                    counter
+                )));
+            }
+            counter as i32
+        } else {
+            input.read_int()?
+        };
         let num_segs = input.read_int()?;
         if num_segs < 0 {
             return Err(format!("invalid segment count: {}", num_segs).into());
@@ -517,6 +548,19 @@ impl<D: Directory, C: Codec> SegmentInfos<D, C> {
             }
             let field_infos_gen = input.read_long()?;
             let dv_gen = input.read_long()?;
+            if format >= SEGMENT_VERSION_74 {
+                // soft deletes are only hidden by readers wrapping the index, like
+                // Lucene's plain readers we just ignore them
+                let soft_del_count = input.read_int()?;
+                if soft_del_count < 0 || soft_del_count + del_count > info.max_doc() {
+                    bail!(CorruptIndex(format!(
+                        "invalid soft deletion count: {} vs maxDoc={}, del_count={}",
+                        soft_del_count,
+                        info.max_doc(),
+                        del_count
+                    )));
+                }
+            }
             let field_infos_files = input.read_set_of_strings()?;
             let num_dv_fields = input.read_int()?;
             let dv_update_files = if num_dv_fields == 0 {
@@ -767,6 +811,7 @@ pub struct SegmentCoreReaders<D: Directory, C: Codec> {
     /// in the case of DV updates, SR may hold a newer version.
     pub core_field_infos: Arc<FieldInfos>,
     pub points_reader: Option<Arc<CodecPointsReader<C>>>,
+    pub vector_reader: Option<VectorReaderRef>,
     pub core_dropped_listeners: Mutex<Vec<Deferred>>,
     pub core_cache_key: String,
 }
@@ -843,6 +888,13 @@ impl<D: Directory, C: Codec> SegmentCoreReaders<D, C> {
         } else {
             None
         };
+        let vector_reader: Option<VectorReaderRef> = if core_field_infos.has_vector_values {
+            Some(Arc::new(
+                codec.vector_format().fields_reader(&segment_read_state)?,
+            ))
+        } else {
+            None
+        };
         // TODO process norms_producers/store_fields_reader/term vectors
 
         Ok(SegmentCoreReaders {
@@ -855,6 +907,7 @@ impl<D: Directory, C: Codec> SegmentCoreReaders<D, C> {
             cfs_reader,
             core_field_infos,
             points_reader,
+            vector_reader,
             core_dropped_listeners: Mutex::new(vec![]),
             core_cache_key: format!("{}@{}", si.name, id2str(&random_id())),
         })
@@ -12,13 +12,17 @@
 // limitations under the License.
 
 use core::codec::{Codec, DocValuesFormat, DocValuesProducer};
-use core::index::FieldInfos;
+use core::index::{
+    BinaryDocValues, DocValuesType, FieldInfo, FieldInfos, NumericDocValues, SortedDocValues,
+    SortedNumericDocValues, SortedSetDocValues,
+};
 use core::index::{SegmentCommitInfo, SegmentReadState};
 use core::store::{Directory, IOContext};
-use core::util::to_base36;
-use error::ErrorKind::IllegalState;
+use core::util::{to_base36, Accountable, BitsRef};
+use error::ErrorKind::{IllegalArgument, IllegalState};
 use error::Result;
 
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 
 /// Manage the `DocValuesProducer` held by `SegmentReader`.
@@ -43,6 +47,62 @@ impl SegmentDocValues {
         }
     }
 
+    /// Returns the producer of the doc values of `field_infos`, the latest
+    /// field infos of the segment. The fields which were never updated are
+    /// read from the segment files, with the field infos of the core, while
+    /// the updated fields are read from the files of their generation.
+    pub fn get_segment_doc_values_producer<D: Directory, DW: Directory, C: Codec>(
+        si: &SegmentCommitInfo<D, C>,
+        dir: Arc<DW>,
+        core_field_infos: &Arc<FieldInfos>,
+        field_infos: &Arc<FieldInfos>,
+    ) -> Result<Box<dyn DocValuesProducer>> {
+        let mut gen_infos: BTreeMap<i64, Vec<FieldInfo>> = BTreeMap::new();
+        for fi in field_infos.by_number.values() {
+            if fi.doc_values_type != DocValuesType::Null && fi.dv_gen != -1 {
+                gen_infos
+                    .entry(fi.dv_gen)
+                    .or_insert_with(Vec::new)
+                    .push(fi.as_ref().clone());
+            }
+        }
+        if gen_infos.is_empty() {
+            // simple case, no DocValues updates
+            return Self::get_doc_values_producer(-1, si, dir, Arc::clone(field_infos));
+        }
+
+        let mut producers: Vec<Arc<dyn DocValuesProducer>> = Vec::with_capacity(gen_infos.len());
+        let mut fields = HashMap::new();
+        for fi in field_infos.by_number.values() {
+            if fi.doc_values_type != DocValuesType::Null && fi.dv_gen == -1 {
+                if producers.is_empty() {
+                    let base = Self::get_doc_values_producer(
+                        -1,
+                        si,
+                        Arc::clone(&dir),
+                        Arc::clone(core_field_infos),
+                    )?;
+                    producers.push(Arc::from(base));
+                }
+                fields.insert(fi.name.clone(), 0);
+            }
+        }
+        for (gen, infos) in gen_infos {
+            let names: Vec<String> = infos.iter().map(|fi| fi.name.clone()).collect();
+            let producer = Self::get_doc_values_producer(
+                gen,
+                si,
+                Arc::clone(&dir),
+                Arc::new(FieldInfos::new(infos)?),
+            )?;
+            for name in names {
+                fields.insert(name, producers.len());
+            }
+            producers.push(Arc::from(producer));
+        }
+        Ok(Box::new(SegmentDocValuesProducer { producers, fields }))
+    }
+
     fn do_get_doc_values_producer<D: Directory, DW: Directory, C: Codec>(
         si: &SegmentCommitInfo<D, C>,
         dv_dir: Arc<DW>,
@@ -67,3 +127,79 @@ impl SegmentDocValues {
         }
     }
 }
+
+/// A `DocValuesProducer` over a segment with updated doc values, which
+/// dispatches each field to the producer of its generation.
+pub struct SegmentDocValuesProducer {
+    producers: Vec<Arc<dyn DocValuesProducer>>,
+    fields: HashMap<String, usize>,
+}
+
+impl SegmentDocValuesProducer {
+    fn producer(&self, field: &FieldInfo) -> Result<&Arc<dyn DocValuesProducer>> {
+        match self.fields.get(&field.name) {
+            Some(idx) => Ok(&self.producers[*idx]),
+            None => bail!(IllegalArgument(format!(
+                "field {} has no doc values",
+                field.name
+            ))),
+        }
+    }
+}
+
+impl Accountable for SegmentDocValuesProducer {
+    fn ram_bytes_used(&self) -> usize {
+        self.producers.iter().map(|p| p.ram_bytes_used()).sum()
+    }
+}
+
+impl DocValuesProducer for SegmentDocValuesProducer {
+    fn get_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn NumericDocValues>> {
+        self.producer(field)?.get_numeric(field)
+    }
+
+    fn get_binary(&self, field: &FieldInfo) -> Result<Arc<dyn BinaryDocValues>> {
+        self.producer(field)?.get_binary(field)
+    }
+
+    fn get_sorted(&self, field: &FieldInfo) -> Result<Arc<dyn SortedDocValues>> {
+        self.producer(field)?.get_sorted(field)
+    }
+
+    fn get_sorted_numeric(&self, field: &FieldInfo) -> Result<Arc<dyn SortedNumericDocValues>> {
+        self.producer(field)?.get_sorted_numeric(field)
+    }
+
+    fn get_sorted_set(&self, field: &FieldInfo) -> Result<Arc<dyn SortedSetDocValues>> {
+        self.producer(field)?.get_sorted_set(field)
+    }
+
+    fn get_docs_with_field(&self, field: &FieldInfo) -> Result<BitsRef> {
+        self.producer(field)?.get_docs_with_field(field)
+    }
+
+    fn check_integrity(&self) -> Result<()> {
+        for producer in &self.producers {
+            producer.check_integrity()?;
+        }
+        Ok(())
+    }
+
+    fn get_merge_instance(&self) -> Result<Box<dyn DocValuesProducer>> {
+        let mut producers = Vec::with_capacity(self.producers.len());
+        for producer in &self.producers {
+            producers.push(Arc::from(producer.get_merge_instance()?));
+        }
+        Ok(Box::new(SegmentDocValuesProducer {
+            producers,
+            fields: self.fields.clone(),
+        }))
+    }
+
+    fn evict(&self, field: &FieldInfo) -> Result<()> {
+        match self.fields.get(&field.name) {
+            Some(idx) => self.producers[*idx].evict(field),
+            None => Ok(()),
+        }
+    }
+}
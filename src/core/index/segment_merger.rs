@@ -13,12 +13,12 @@
 
 use core::codec::{
     Codec, DocValuesConsumer, DocValuesFormat, FieldInfosFormat, FieldsConsumer, NormsConsumer,
-    NormsFormat, PointsFormat, PointsWriter, PostingsFormat, StoredFieldsFormat,
-    StoredFieldsWriter, TermVectorsFormat, TermVectorsWriter,
+    NormsFormat, NormsProducer, PointsFormat, PointsWriter, PostingsFormat, StoredFieldsFormat,
+    StoredFieldsWriter, TermVectorsFormat, TermVectorsWriter, VectorFormat, VectorWriter,
 };
 use core::index::merge_state::MergeState;
 use core::index::{FieldInfosBuilder, FieldNumbersRef};
-use core::index::{SegmentInfo, SegmentReader, SegmentWriteState};
+use core::index::{SegmentInfo, SegmentReadState, SegmentReader, SegmentWriteState};
 use core::store::{Directory, IOContext};
 use error::ErrorKind::{IllegalArgument, IllegalState};
 use error::Result;
@@ -96,6 +96,17 @@ where
             self.context.clone(),
             "".into(),
         );
+        // the norms are merged first so that the postings can record their impacts
+        if self
+            .merge_state
+            .merge_field_infos
+            .as_ref()
+            .unwrap()
+            .has_norms
+        {
+            self.merge_norms(&segment_write_state)?;
+        }
+
         self.merge_terms(&segment_write_state)?;
 
         if self
@@ -116,16 +127,16 @@ where
         {
             self.merge_points(&segment_write_state)?;
         }
-
         if self
             .merge_state
             .merge_field_infos
             .as_ref()
             .unwrap()
-            .has_norms
+            .has_vector_values
         {
-            self.merge_norms(&segment_write_state)?;
+            self.merge_vector_values(&segment_write_state)?;
         }
+
         if self
             .merge_state
             .merge_field_infos
@@ -171,6 +182,17 @@ where
         writer.merge(&mut self.merge_state)
     }
 
+    fn merge_vector_values(
+        &mut self,
+        segment_write_state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<()> {
+        let mut writer = self
+            .codec
+            .vector_format()
+            .fields_writer(segment_write_state)?;
+        writer.merge(&self.merge_state)
+    }
+
     fn merge_norms(&mut self, segment_write_state: &SegmentWriteState<D, DW, C>) -> Result<()> {
         let mut consumer = self
             .codec
@@ -221,10 +243,27 @@ where
     }
 
     fn merge_terms(&mut self, segment_write_state: &SegmentWriteState<D, DW, C>) -> Result<()> {
+        let field_infos = Arc::clone(self.merge_state.merge_field_infos.as_ref().unwrap());
+        let norms = if field_infos.has_norms {
+            let read_state = SegmentReadState::new(
+                Arc::clone(&self.directory),
+                &segment_write_state.segment_info,
+                field_infos,
+                &self.context,
+                String::new(),
+            );
+            Some(self.codec.norms_format().norms_producer(&read_state)?)
+        } else {
+            None
+        };
+
         let mut consumer = self
             .codec
             .postings_format()
             .fields_consumer(segment_write_state)?;
-        consumer.merge(&mut self.merge_state)
+        consumer.merge(
+            &mut self.merge_state,
+            norms.as_ref().map(|n| n as &dyn NormsProducer),
+        )
     }
 }
@@ -14,27 +14,31 @@
 use std::{
     cell::RefCell,
     collections::{hash_map::Entry, HashMap},
-    sync::Arc,
+    sync::atomic::{AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
 };
-use thread_local::{CachedThreadLocal, ThreadLocal};
+use thread_local::CachedThreadLocal;
 
 use core::store::Directory;
 use core::{
     codec::{
         Codec, CodecFieldsProducer, CodecNormsProducer, CodecPointsReader, CodecStoredFieldsReader,
         CodecTVFields, CodecTVReader, DocValuesProducer, FieldInfosFormat, LiveDocsFormat,
-        NormsProducer, StoredFieldsReader, TermVectorsReader,
+        NormsProducer, StoredFieldsReader, TermVectorsReader, VectorReaderRef,
     },
     doc::{Document, DocumentStoredFieldVisitor},
     index::{
         leaf_reader::LeafReaderContext, BinaryDocValuesRef, CfsDirectory, DocValuesType, FieldInfo,
         FieldInfos, IndexReader, LeafReader, NumericDocValues, NumericDocValuesRef,
         SegmentCommitInfo, SegmentCoreReaders, SegmentDocValues, SortedDocValuesRef,
-        SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor,
+        SortedNumericDocValuesRef, SortedSetDocValuesRef, StoredFieldVisitor, VectorValues,
     },
     search::sort::Sort,
     store::IOContext,
-    util::{external::deferred::Deferred, numeric::to_base36, BitsRef, DocId, MatchAllBits},
+    util::{
+        external::deferred::Deferred, numeric::to_base36, Accountable, BitsRef, DocId,
+        MatchAllBits, RamUsage,
+    },
 };
 use error::{ErrorKind::IllegalArgument, Result};
 
@@ -46,7 +50,15 @@ pub enum DocValuesRefEnum {
     SortedSet(SortedSetDocValuesRef),
 }
 
-pub type ThreadLocalDocValueProducer = ThreadLocal<Arc<dyn DocValuesProducer>>;
+/// The doc values read by a thread from a segment, kept until their field
+/// is evicted.
+#[derive(Default)]
+struct LocalDocValues {
+    // the number of evicted fields already dropped from this cache
+    num_evicted: usize,
+    doc_values: HashMap<String, DocValuesRefEnum>,
+    docs_with_field: HashMap<String, BitsRef>,
+}
 
 pub struct SegmentReader<D: Directory, C: Codec> {
     pub si: Arc<SegmentCommitInfo<D, C>>,
@@ -56,9 +68,13 @@ pub struct SegmentReader<D: Directory, C: Codec> {
     pub is_nrt: bool,
     pub field_infos: Arc<FieldInfos>,
     // context: LeafReaderContext
-    doc_values_producer: ThreadLocalDocValueProducer,
-    docs_with_field_local: CachedThreadLocal<RefCell<HashMap<String, BitsRef>>>,
-    doc_values_local: CachedThreadLocal<RefCell<HashMap<String, DocValuesRefEnum>>>,
+    // opened on the first access to doc values, and shared by the threads
+    doc_values_producer: Mutex<Option<Arc<dyn DocValuesProducer>>>,
+    doc_values_local: CachedThreadLocal<RefCell<LocalDocValues>>,
+    // the fields evicted so far, dropped from the caches of the threads on
+    // their next access to doc values
+    evicted_fields: Mutex<Vec<String>>,
+    num_evicted: AtomicUsize,
 }
 
 unsafe impl<D: Directory + Send + Sync + 'static, C: Codec> Sync for SegmentReader<D, C> {}
@@ -75,14 +91,7 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
         core: Arc<SegmentCoreReaders<D, C>>,
         is_nrt: bool,
         field_infos: Arc<FieldInfos>,
-        doc_values_producer: ThreadLocalDocValueProducer,
     ) -> SegmentReader<D, C> {
-        let docs_with_field_local = CachedThreadLocal::new();
-        docs_with_field_local.get_or(|| Box::new(RefCell::new(HashMap::new())));
-
-        let doc_values_local = CachedThreadLocal::new();
-        doc_values_local.get_or(|| Box::new(RefCell::new(HashMap::new())));
-
         SegmentReader {
             si,
             live_docs,
@@ -90,9 +99,10 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
             core,
             is_nrt,
             field_infos,
-            doc_values_producer,
-            docs_with_field_local,
-            doc_values_local,
+            doc_values_producer: Mutex::new(None),
+            doc_values_local: CachedThreadLocal::new(),
+            evicted_fields: Mutex::new(vec![]),
+            num_evicted: AtomicUsize::new(0),
         }
     }
 
@@ -103,17 +113,7 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
         core: Arc<SegmentCoreReaders<D, C>>,
     ) -> Result<Self> {
         let field_infos = Self::init_field_infos(si.as_ref(), core.as_ref())?;
-        let doc_values =
-            Self::init_doc_values_producer(core.as_ref(), si.as_ref(), Arc::clone(&field_infos))?;
-        Ok(Self::new(
-            si,
-            live_docs,
-            num_docs,
-            core,
-            true,
-            field_infos,
-            doc_values,
-        ))
+        Ok(Self::new(si, live_docs, num_docs, core, true, field_infos))
     }
 
     pub fn build_from_reader(
@@ -155,11 +155,6 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
         }
 
         let field_infos = Self::init_field_infos(si.as_ref(), sr.core.as_ref())?;
-        let doc_values_producer = Self::init_doc_values_producer(
-            sr.core.as_ref(),
-            si.as_ref(),
-            Arc::clone(&field_infos),
-        )?;
         Ok(SegmentReader::new(
             si,
             live_docs,
@@ -167,7 +162,6 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
             Arc::clone(&sr.core),
             is_nrt,
             field_infos,
-            doc_values_producer,
         ))
     }
 
@@ -201,7 +195,7 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
             Arc::clone(&core.core_field_infos)
         } else {
             let fis_format = codec.field_infos_format();
-            let segment_suffix = format!("{}", si.field_infos_gen());
+            let segment_suffix = to_base36(si.field_infos_gen() as u64);
             let field_infos = fis_format.read(
                 si.info.directory.as_ref(),
                 &si.info,
@@ -222,9 +216,6 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
             Arc::new(MatchAllBits::new(si.info.max_doc() as usize))
         };
 
-        let doc_values_producer =
-            SegmentReader::init_doc_values_producer(&core, &si, Arc::clone(&field_infos))?;
-
         Ok(SegmentReader::new(
             Arc::clone(si),
             live_docs,
@@ -232,7 +223,6 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
             core,
             false,
             field_infos,
-            doc_values_producer,
         ))
     }
 
@@ -249,63 +239,89 @@ impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
 }
 
 impl<D: Directory + 'static, C: Codec> SegmentReader<D, C> {
-    fn init_doc_values_producer(
+    /// The doc values producer of the segment, opened on the first call, so
+    /// a reader only reading postings never loads the doc values metadata.
+    fn doc_values_producer(&self) -> Result<Arc<dyn DocValuesProducer>> {
+        let mut producer = self.doc_values_producer.lock()?;
+        if let Some(ref producer) = *producer {
+            return Ok(Arc::clone(producer));
+        }
+        if !self.field_infos.has_doc_values {
+            bail!(IllegalArgument(format!(
+                "segment {} has no doc values",
+                &self.si.info.name
+            )));
+        }
+        let opened: Arc<dyn DocValuesProducer> = Arc::from(Self::new_doc_values_producer(
+            &self.core,
+            &self.si,
+            &self.field_infos,
+        )?);
+        *producer = Some(Arc::clone(&opened));
+        Ok(opened)
+    }
+
+    fn loaded_doc_values_producer(&self) -> Option<Arc<dyn DocValuesProducer>> {
+        self.doc_values_producer
+            .lock()
+            .ok()
+            .and_then(|producer| producer.clone())
+    }
+
+    /// The doc values cache of the calling thread, without the fields
+    /// evicted since its last access.
+    fn local_doc_values(&self) -> Result<&RefCell<LocalDocValues>> {
+        let local = self
+            .doc_values_local
+            .get_or(|| Box::new(RefCell::new(LocalDocValues::default())));
+        if local.borrow().num_evicted != self.num_evicted.load(Ordering::Acquire) {
+            let evicted = self.evicted_fields.lock()?;
+            let mut local = local.borrow_mut();
+            for field in &evicted[local.num_evicted..] {
+                local.doc_values.remove(field);
+                local.docs_with_field.remove(field);
+            }
+            local.num_evicted = evicted.len();
+        }
+        Ok(local)
+    }
+
+    /// Evicts the doc values of `field` loaded by this reader, e.g. a field
+    /// only used by a rare aggregation, so their memory can be reclaimed.
+    ///
+    /// The values are loaded again on the next access. The instances still
+    /// held by a caller are not affected.
+    pub fn evict_doc_values(&self, field: &str) -> Result<()> {
+        {
+            let mut evicted = self.evicted_fields.lock()?;
+            evicted.push(field.to_string());
+            self.num_evicted.store(evicted.len(), Ordering::Release);
+        }
+        if let Some(field_info) = self.field_infos.field_info_by_name(field) {
+            if let Some(producer) = self.loaded_doc_values_producer() {
+                producer.evict(field_info)?;
+            }
+        }
+        Ok(())
+    }
+
+    // init most recent DocValues for the current commit, the updated fields
+    // are read from the files of their generation.
+    fn new_doc_values_producer(
         core: &SegmentCoreReaders<D, C>,
         si: &SegmentCommitInfo<D, C>,
-        field_infos: Arc<FieldInfos>,
-    ) -> Result<ThreadLocalDocValueProducer> {
-        // initDocValuesProducer: init most recent DocValues for the current commit
+        field_infos: &Arc<FieldInfos>,
+    ) -> Result<Box<dyn DocValuesProducer>> {
         let dir = match core.cfs_reader {
             Some(ref d) => Arc::clone(d),
             None => Arc::new(CfsDirectory::Raw(Arc::clone(&si.info.directory))),
         };
-
-        let doc_values_producer = if !field_infos.has_doc_values {
-            ThreadLocal::new()
-        } else if si.has_field_updates() {
-            unimplemented!()
-        } else {
-            // simple case, no DocValues updates
-            let dv_producer =
-                SegmentDocValues::get_doc_values_producer(-1_i64, &si, dir, field_infos)?;
-
-            let doc_values_producer = ThreadLocal::new();
-            doc_values_producer.get_or(|| Box::new(Arc::from(dv_producer)));
-            doc_values_producer
-        };
-        Ok(doc_values_producer)
-    }
-
-    fn init_local_doc_values_producer(&self) -> Result<()> {
-        if self.field_infos.has_doc_values {
-            if self.si.has_field_updates() {
-                unimplemented!()
-            } else {
-                if self.doc_values_producer.get().is_some() {
-                    return Ok(());
-                }
-
-                let dv_producer = if let Some(ref cfs_dir) = self.core.cfs_reader {
-                    SegmentDocValues::get_doc_values_producer(
-                        -1_i64,
-                        &self.si,
-                        Arc::clone(cfs_dir),
-                        Arc::clone(&self.field_infos),
-                    )?
-                } else {
-                    SegmentDocValues::get_doc_values_producer(
-                        -1_i64,
-                        &self.si,
-                        Arc::clone(&self.si.info.directory),
-                        Arc::clone(&self.field_infos),
-                    )?
-                };
-
-                self.doc_values_producer
-                    .get_or(|| Box::new(Arc::from(dv_producer)));
-            }
-        }
-        Ok(())
+        SegmentDocValues::get_segment_doc_values_producer(
+            si,
+            dir,
+            &core.core_field_infos,
+            field_infos,
+        )
     }
 
     fn init_field_infos<C1: Codec>(
@@ -422,12 +438,10 @@ where
     }
 
     fn get_numeric_doc_values(&self, field: &str) -> Result<NumericDocValuesRef> {
-        self.init_local_doc_values_producer()?;
-
         match self
-            .doc_values_local
-            .get_or(|| Box::new(RefCell::new(HashMap::new())))
+            .local_doc_values()?
             .borrow_mut()
+            .doc_values
             .entry(String::from(field))
         {
             Entry::Occupied(o) => match *o.get() {
@@ -438,8 +452,8 @@ where
                 ))),
             },
             Entry::Vacant(v) => match self.get_dv_field(field, DocValuesType::Numeric) {
-                Some(fi) if self.doc_values_producer.get().is_some() => {
-                    let dv_producer = self.doc_values_producer.get().unwrap();
+                Some(fi) => {
+                    let dv_producer = self.doc_values_producer()?;
                     let cell = dv_producer.get_numeric(fi)?;
                     v.insert(DocValuesRefEnum::Numeric(Arc::clone(&cell)));
                     Ok(cell)
@@ -454,11 +468,10 @@ where
     }
 
     fn get_binary_doc_values(&self, field: &str) -> Result<BinaryDocValuesRef> {
-        self.init_local_doc_values_producer()?;
         match self
-            .doc_values_local
-            .get_or(|| Box::new(RefCell::new(HashMap::new())))
+            .local_doc_values()?
             .borrow_mut()
+            .doc_values
             .entry(String::from(field))
         {
             Entry::Occupied(o) => match *o.get() {
@@ -469,8 +482,8 @@ where
                 ))),
             },
             Entry::Vacant(v) => match self.get_dv_field(field, DocValuesType::Binary) {
-                Some(fi) if self.doc_values_producer.get().is_some() => {
-                    let dv_producer = self.doc_values_producer.get().unwrap();
+                Some(fi) => {
+                    let dv_producer = self.doc_values_producer()?;
                     let dv = dv_producer.get_binary(fi)?;
                     let cell = Arc::from(dv);
                     v.insert(DocValuesRefEnum::Binary(Arc::clone(&cell)));
@@ -486,12 +499,10 @@ where
     }
 
     fn get_sorted_doc_values(&self, field: &str) -> Result<SortedDocValuesRef> {
-        self.init_local_doc_values_producer()?;
-
         match self
-            .doc_values_local
-            .get_or(|| Box::new(RefCell::new(HashMap::new())))
+            .local_doc_values()?
             .borrow_mut()
+            .doc_values
             .entry(String::from(field))
         {
             Entry::Occupied(o) => match *o.get() {
@@ -502,8 +513,8 @@ where
                 ))),
             },
             Entry::Vacant(v) => match self.get_dv_field(field, DocValuesType::Sorted) {
-                Some(fi) if self.doc_values_producer.get().is_some() => {
-                    let dv_producer = self.doc_values_producer.get().unwrap();
+                Some(fi) => {
+                    let dv_producer = self.doc_values_producer()?;
                     let dv = dv_producer.get_sorted(fi)?;
                     v.insert(DocValuesRefEnum::Sorted(Arc::clone(&dv)));
                     Ok(dv)
@@ -517,12 +528,10 @@ where
     }
 
     fn get_sorted_numeric_doc_values(&self, field: &str) -> Result<SortedNumericDocValuesRef> {
-        self.init_local_doc_values_producer()?;
-
         match self
-            .doc_values_local
-            .get_or(|| Box::new(RefCell::new(HashMap::new())))
+            .local_doc_values()?
             .borrow_mut()
+            .doc_values
             .entry(String::from(field))
         {
             Entry::Occupied(o) => match *o.get() {
@@ -533,8 +542,8 @@ where
                 ))),
             },
             Entry::Vacant(v) => match self.get_dv_field(field, DocValuesType::SortedNumeric) {
-                Some(fi) if self.doc_values_producer.get().is_some() => {
-                    let dv_producer = self.doc_values_producer.get().unwrap();
+                Some(fi) => {
+                    let dv_producer = self.doc_values_producer()?;
                     let dv = dv_producer.get_sorted_numeric(fi)?;
                     let cell = Arc::from(dv);
                     v.insert(DocValuesRefEnum::SortedNumeric(Arc::clone(&cell)));
@@ -549,12 +558,10 @@ where
     }
 
     fn get_sorted_set_doc_values(&self, field: &str) -> Result<SortedSetDocValuesRef> {
-        self.init_local_doc_values_producer()?;
-
         match self
-            .doc_values_local
-            .get_or(|| Box::new(RefCell::new(HashMap::new())))
+            .local_doc_values()?
             .borrow_mut()
+            .doc_values
             .entry(String::from(field))
         {
             Entry::Occupied(o) => match *o.get() {
@@ -565,8 +572,8 @@ where
                 ))),
             },
             Entry::Vacant(v) => match self.get_dv_field(field, DocValuesType::SortedSet) {
-                Some(fi) if self.doc_values_producer.get().is_some() => {
-                    let dv_producer = self.doc_values_producer.get().unwrap();
+                Some(fi) => {
+                    let dv_producer = self.doc_values_producer()?;
                     let dv = dv_producer.get_sorted_set(fi)?;
                     let cell = Arc::from(dv);
                     v.insert(DocValuesRefEnum::SortedSet(Arc::clone(&cell)));
@@ -594,28 +601,17 @@ where
     }
 
     fn get_docs_with_field(&self, field: &str) -> Result<BitsRef> {
-        if let Some(prev) = self
-            .docs_with_field_local
-            .get_or(|| Box::new(RefCell::new(HashMap::new())))
-            .borrow_mut()
-            .get(field)
-        {
+        let local = self.local_doc_values()?;
+        if let Some(prev) = local.borrow().docs_with_field.get(field) {
             return Ok(Arc::clone(prev));
         }
 
-        self.init_local_doc_values_producer()?;
-
         match self.field_infos.field_info_by_name(field) {
-            Some(fi)
-                if fi.doc_values_type != DocValuesType::Null
-                    && self.doc_values_producer.get().is_some() =>
-            {
-                let dv_producer = self.doc_values_producer.get().unwrap();
-                let dv = dv_producer.get_docs_with_field(fi)?;
-                self.docs_with_field_local
-                    .get()
-                    .unwrap()
+            Some(fi) if fi.doc_values_type != DocValuesType::Null => {
+                let dv = self.doc_values_producer()?.get_docs_with_field(fi)?;
+                local
                     .borrow_mut()
+                    .docs_with_field
                     .insert(field.to_string(), Arc::clone(&dv));
                 Ok(dv)
             }
@@ -632,6 +628,13 @@ where
         self.core.points_reader.clone()
     }
 
+    fn vector_values(&self, field: &str) -> Result<Option<Box<dyn VectorValues>>> {
+        match self.core.vector_reader {
+            Some(ref reader) => reader.vector_values(field),
+            None => Ok(None),
+        }
+    }
+
     fn core_cache_key(&self) -> &str {
         // use segment name as unique segment cache key
         &self.core.core_cache_key
@@ -662,7 +665,15 @@ where
     }
 
     fn doc_values_reader(&self) -> Result<Option<Arc<dyn DocValuesProducer>>> {
-        Ok(self.doc_values_producer.get().map(Arc::clone))
+        if self.field_infos.has_doc_values {
+            Ok(Some(self.doc_values_producer()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn vector_reader(&self) -> Result<Option<VectorReaderRef>> {
+        Ok(self.core.vector_reader.clone())
     }
 
     fn postings_reader(&self) -> Result<Self::FieldsProducer> {
@@ -670,6 +681,25 @@ where
     }
 }
 
+/// The postings are shared by the readers of the segment, the doc values
+/// producer is only counted once some doc values were read.
+impl<D: Directory + 'static, C: Codec> Accountable for SegmentReader<D, C> {
+    fn ram_bytes_used(&self) -> usize {
+        let doc_values = self
+            .loaded_doc_values_producer()
+            .map_or(0, |producer| producer.ram_bytes_used());
+        self.core.fields.ram_bytes_used() + doc_values
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        let mut resources = vec![RamUsage::of("postings", &self.core.fields)];
+        if let Some(producer) = self.loaded_doc_values_producer() {
+            resources.push(RamUsage::of("doc values", &*producer));
+        }
+        resources
+    }
+}
+
 impl<D: Directory + 'static, C: Codec> AsRef<IndexReader<Codec = C>> for SegmentReader<D, C> {
     fn as_ref(&self) -> &(IndexReader<Codec = C> + 'static) {
         self
@@ -50,6 +50,10 @@ pub trait SortedDocValues: BinaryDocValues {
         Ok(-(low + 1)) // key not found
     }
 
+    /// Returns an iterator over the distinct values of the field in order,
+    /// the `ord` of each term being its ordinal. It can seek to a term with
+    /// `seek_exact` or `seek_ceil`, e.g. to list the values having a prefix,
+    /// or to an ordinal with `seek_exact_ord`.
     fn term_iterator(&self) -> Result<DocValuesTermIterator>;
 }
 
@@ -79,6 +83,11 @@ impl BinaryDocValues for EmptySortedDocValues {
     fn get(&self, _doc_id: DocId) -> Result<Vec<u8>> {
         Ok(Vec::with_capacity(0))
     }
+
+    fn get_into(&self, _doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        value.clear();
+        Ok(())
+    }
 }
 
 impl<T: SortedDocValues + ?Sized> SortedDocValues for Arc<T> {
@@ -107,6 +116,10 @@ impl<T: SortedDocValues + ?Sized> BinaryDocValues for Arc<T> {
     fn get(&self, doc_id: DocId) -> Result<Vec<u8>> {
         (**self).get(doc_id)
     }
+
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        (**self).get_into(doc_id, value)
+    }
 }
 
 #[derive(Clone)]
@@ -177,6 +190,15 @@ impl BinaryDocValues for TailoredSortedDocValues {
             self.lookup_ord(ord)
         }
     }
+
+    fn get_into(&self, doc_id: DocId, value: &mut Vec<u8>) -> Result<()> {
+        value.clear();
+        let ord = self.get_ord(doc_id)?;
+        if ord != -1 {
+            value.extend_from_slice(&self.lookup_ord(ord)?);
+        }
+        Ok(())
+    }
 }
 
 pub struct TailoredSortedDocValuesInner {
@@ -21,7 +21,6 @@ use core::util::DocId;
 use core::util::LongValues;
 use error::Result;
 
-use core::util::packed::MixinMonotonicLongValues;
 use std::sync::Arc;
 
 pub const NO_MORE_ORDS: i64 = -1;
@@ -53,6 +52,8 @@ pub trait SortedSetDocValues: Send + Sync {
         Ok(-(low + 1)) // key not found
     }
 
+    /// Returns an iterator over the distinct values of the field in order,
+    /// see `SortedDocValues::term_iterator`.
     fn term_iterator(&self) -> Result<DocValuesTermIterator>;
 }
 
@@ -97,7 +98,7 @@ impl AddressedRandomAccessOrds {
     pub fn new(
         binary: Box<dyn LongBinaryDocValues>,
         ordinals: Box<dyn LongValues>,
-        ord_index: MixinMonotonicLongValues,
+        ord_index: Box<dyn LongValues>,
         value_count: usize,
     ) -> Self {
         let inner = AddressedRandomAccessOrdsInner::new(binary, ordinals, ord_index, value_count);
@@ -109,7 +110,7 @@ impl AddressedRandomAccessOrds {
     pub fn with_compression(
         binary: CompressedBinaryDocValues,
         ordinals: Box<dyn LongValues>,
-        ord_index: MixinMonotonicLongValues,
+        ord_index: Box<dyn LongValues>,
         value_count: usize,
     ) -> Self {
         let inner = AddressedRandomAccessOrdsInner::with_compression(
@@ -172,7 +173,7 @@ impl RandomAccessOrds for AddressedRandomAccessOrds {
 pub struct AddressedRandomAccessOrdsInner {
     binary: BoxedBinaryDocValuesEnum,
     ordinals: Box<dyn LongValues>,
-    ord_index: MixinMonotonicLongValues,
+    ord_index: Box<dyn LongValues>,
     value_count: usize,
 }
 
@@ -180,7 +181,7 @@ impl AddressedRandomAccessOrdsInner {
     fn new(
         binary: Box<dyn LongBinaryDocValues>,
         ordinals: Box<dyn LongValues>,
-        ord_index: MixinMonotonicLongValues,
+        ord_index: Box<dyn LongValues>,
         value_count: usize,
     ) -> Self {
         AddressedRandomAccessOrdsInner {
@@ -194,7 +195,7 @@ impl AddressedRandomAccessOrdsInner {
     fn with_compression(
         binary: CompressedBinaryDocValues,
         ordinals: Box<dyn LongValues>,
-        ord_index: MixinMonotonicLongValues,
+        ord_index: Box<dyn LongValues>,
         value_count: usize,
     ) -> Self {
         AddressedRandomAccessOrdsInner {
@@ -46,6 +46,7 @@ impl Sorter {
         match sort {
             SortField::Simple(s) => s.field_type(),
             SortField::SortedNumeric(s) => s.numeric_type(),
+            SortField::DoubleValues(_) | SortField::GeoDistance(_) => SortFieldType::Double,
         }
     }
 
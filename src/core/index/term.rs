@@ -11,7 +11,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::search::posting_iterator::{EmptyPostingIterator, PostingIterator, PostingIteratorFlags};
+use core::search::posting_iterator::{
+    EmptyPostingIterator, ImpactsEnum, PostingIterator, PostingIteratorFlags, SlowImpactsEnum,
+};
 
 use error::ErrorKind::{IllegalArgument, UnsupportedOperation};
 use error::Result;
@@ -305,7 +307,7 @@ pub enum SeekStatus {
 }
 
 pub trait TermIterator: 'static {
-    type Postings: PostingIterator;
+    type Postings: PostingIterator + 'static;
     type TermState: TermState;
     /// Increments the iteration to the next {@link BytesRef} in the iterator.
     /// Returns the resulting {@link BytesRef} or <code>null</code> if the end of
@@ -395,6 +397,18 @@ pub trait TermIterator: 'static {
     }
     fn postings_with_flags(&mut self, flags: u16) -> Result<Self::Postings>;
 
+    /// Return an `ImpactsEnum` for the current term, whose impacts give an
+    /// upper bound of the (freq, norm) pairs of upcoming blocks of docs. Do
+    /// not call this when the enum is unpositioned.
+    ///
+    /// The default implementation can't skip anything and returns impacts that
+    /// match any freq and norm, codecs that record impacts override it.
+    fn impacts(&mut self, flags: u16) -> Result<Box<dyn ImpactsEnum>> {
+        Ok(Box::new(SlowImpactsEnum::new(
+            self.postings_with_flags(flags)?,
+        )))
+    }
+
     /// Expert: Returns the TermsEnums internal state to position the TermsEnum
     /// without re-seeking the term dictionary.
     /// <p>
@@ -595,4 +609,8 @@ where
     fn postings_with_flags(&mut self, flags: u16) -> Result<Self::Postings> {
         self.base_mut().terms.postings_with_flags(flags)
     }
+
+    fn impacts(&mut self, flags: u16) -> Result<Box<dyn ImpactsEnum>> {
+        self.base_mut().terms.impacts(flags)
+    }
 }
@@ -11,7 +11,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::codec::{Codec, FieldsConsumer, PostingsFormat};
+use core::codec::{Codec, FieldsConsumer, NormsFormat, NormsProducer, PostingsFormat};
 use core::index::byte_slice_reader::ByteSliceReader;
 use core::index::merge_policy::MergePolicy;
 use core::index::merge_scheduler::MergeScheduler;
@@ -19,13 +19,13 @@ use core::index::term::{SeekStatus, TermIterator, Terms, UnreachableTermState};
 use core::index::term_vector::TermVectorsConsumer;
 use core::index::terms_hash_per_field::{FreqProxTermsWriterPerField, TermsHashPerField};
 use core::index::thread_doc_writer::DocumentsWriterPerThread;
-use core::index::SegmentWriteState;
 use core::index::{
     FieldInfo, FieldInfosBuilder, FieldInvertState, FieldNumbersRef, Fields, IndexOptions,
 };
+use core::index::{SegmentReadState, SegmentWriteState};
 use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
 use core::search::{DocIterator, Payload, NO_MORE_DOCS};
-use core::store::{DataInput, Directory};
+use core::store::{DataInput, Directory, IOContext};
 use core::util::bit_set::{BitSet, FixedBitSet};
 use core::util::byte_block_pool::{ByteBlockAllocator, ByteBlockPool};
 use core::util::int_block_pool::IntBlockPool;
@@ -35,6 +35,7 @@ use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::ptr;
+use std::sync::Arc;
 
 use error::{ErrorKind, Result};
 
@@ -303,13 +304,33 @@ where
 
             apply_deletes(state, &fields)?;
 
+            // the norms are already flushed, read them back to record the impacts
+            let norms = if state.field_infos.has_norms {
+                let read_state = SegmentReadState::new(
+                    Arc::clone(&state.directory),
+                    &state.segment_info,
+                    Arc::new(state.field_infos.clone()),
+                    &IOContext::READ,
+                    state.segment_suffix.clone(),
+                );
+                Some(
+                    state
+                        .segment_info
+                        .codec()
+                        .norms_format()
+                        .norms_producer(&read_state)?,
+                )
+            } else {
+                None
+            };
+
             let mut consumer = state
                 .segment_info
                 .codec()
                 .postings_format()
                 .fields_consumer(state)?;
 
-            consumer.write(&fields)
+            consumer.write(&fields, norms.as_ref().map(|n| n as &dyn NormsProducer))
         } else {
             Ok(())
         }
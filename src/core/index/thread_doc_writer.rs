@@ -21,14 +21,18 @@ use core::{
         index_writer_config::IndexWriterConfig,
         merge_policy::MergePolicy,
         merge_scheduler::MergeScheduler,
+        segment_merger::SegmentMerger,
+        sorter::Sorter,
         FieldInfos, FieldInfosBuilder, FieldNumbers, FieldNumbersRef, Fieldable, SegmentCommitInfo,
-        SegmentInfo, SegmentWriteState, Term,
+        SegmentInfo, SegmentReader, SegmentWriteState, Term,
     },
+    search::{bm25_similarity::NormsEncoding, sort::Sort},
     store::{
-        Directory, FlushInfo, IOContext, LockValidatingDirectoryWrapper, TrackingDirectoryWrapper,
+        Directory, FlushInfo, IOContext, LockValidatingDirectoryWrapper, MergeInfo,
+        TrackingDirectoryWrapper,
     },
     util::{
-        bit_set::BitSet,
+        bit_set::{BitSet, FixedBitSet},
         byte_block_pool::DirectTrackingAllocator,
         int_block_pool::{IntAllocator, INT_BLOCK_SIZE},
         string_util::random_id,
@@ -37,17 +41,23 @@ use core::{
 };
 
 use std::collections::{HashMap, HashSet};
+use std::mem;
 use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
 use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use core::util::Volatile;
 use error::ErrorKind::IllegalArgument;
 use error::Result;
 
+/// The diagnostic of the flushed segments left unsorted although the writer
+/// has an index sort, holding the reason why.
+pub const UNSORTED_DIAGNOSTIC: &str = "unsorted";
+
 pub struct DocState {
     // analyzer: Analyzer,  // TODO, current Analyzer is not implemented
     // pub similarity: Option<Box<Similarity>>,
+    pub norms_encoding: NormsEncoding,
     pub doc_id: DocId,
     // pub doc: Vec<Box<dyn Fieldable>>,
 }
@@ -55,6 +65,7 @@ pub struct DocState {
 impl DocState {
     pub fn new() -> Self {
         DocState {
+            norms_encoding: NormsEncoding::default(),
             doc_id: 0,
             // similarity: None,
         }
@@ -131,7 +142,8 @@ where
             None,
         )?;
         let delete_slice = delete_queue.new_slice();
-        let doc_state = DocState::new();
+        let mut doc_state = DocState::new();
+        doc_state.norms_encoding = index_writer_config.norms_encoding;
         // doc_state.similarity = Some(index_writer_config.similarity());
         Ok(DocumentsWriterPerThread {
             directory,
@@ -394,7 +406,7 @@ where
             ctx,
             "".into(),
         );
-        let _start_mb_used = self.bytes_used() as f64 / 1024.0 / 1024.0;
+        let start_mb_used = self.bytes_used() as f64 / 1024.0 / 1024.0;
 
         // Apply delete-by-docID now (delete-byDocID only
         // happens when an exception is hit processing that
@@ -424,8 +436,24 @@ where
             "DWPT: flush postings as segment '{}' num_docs={}",
             &flush_state.segment_info.name, self.num_docs_in_ram
         );
+        self.index_writer_config.info("DWPT", || {
+            format!(
+                "flush postings as segment {} num_docs={} ram_used={:.3} MB",
+                &flush_state.segment_info.name, self.num_docs_in_ram, start_mb_used
+            )
+        });
         let res = self.do_flush(flush_state);
         if res.is_err() {
+            // the writer deletes the files of the segment once the flush is marked as failed,
+            // but the failure may have happened before they were set, so every other file
+            // written for this flush has to be deleted too
+            let segment_files = self.segment_info.files();
+            self.files_to_delete = self
+                .directory
+                .create_files()
+                .into_iter()
+                .filter(|f| !segment_files.contains(f))
+                .collect();
             self.abort();
         }
         res
@@ -446,8 +474,12 @@ where
         self.pending_updates.deleted_terms.clear();
         self.segment_info
             .set_files(&self.directory.create_files())?;
+        let sorted_info = match self.index_writer_config.index_sort().cloned() {
+            Some(sort) => self.sort_flushed_segment(sort, &mut flush_state)?,
+            None => None,
+        };
         let segment_info_per_commit = SegmentCommitInfo::new(
-            self.segment_info.clone(),
+            sorted_info.unwrap_or_else(|| self.segment_info.clone()),
             0,
             -1,
             -1,
@@ -457,7 +489,9 @@ where
         );
 
         let mut fs = {
-            let segment_deletes = if self.pending_updates.deleted_queries.is_empty() {
+            let segment_deletes = if self.pending_updates.deleted_queries.is_empty()
+                && self.pending_updates.dv_updates.is_empty()
+            {
                 self.pending_updates.clear();
                 None
             } else {
@@ -473,13 +507,128 @@ where
         };
         self.seal_flushed_segment(&mut fs)?;
 
-        debug!(
-            "DWPT: flush time {:?}",
-            SystemTime::now().duration_since(t0).unwrap()
-        );
+        fs.flush_time = SystemTime::now().duration_since(t0).unwrap();
+        debug!("DWPT: flush time {:?}", fs.flush_time);
+        self.index_writer_config.info("DWPT", || {
+            format!(
+                "flushed segment {} del_count={} took {:?}",
+                &fs.segment_info.info.name, fs.del_count, fs.flush_time
+            )
+        });
         Ok(Some(fs))
     }
 
+    /// Rewrites the segment just flushed in the order of the index sort, by
+    /// merging it alone into a new sorted segment which replaces it, see
+    /// `MergeState`. The docs deleted on flush are dropped on the way.
+    /// Returns the info of the sorted segment if it was rewritten, this DWPT
+    /// keeps its own segment name which the flush control tracks it by.
+    ///
+    /// The segment is left unsorted, to be sorted by its first merge, if
+    /// delete queries or doc values updates are pending for it, as they are
+    /// applied on the doc ids of the flushed segment once it's published. Its
+    /// index sort is then unset and the reason is recorded in its
+    /// `UNSORTED_DIAGNOSTIC` diagnostic.
+    fn sort_flushed_segment<DW: Directory + 'static>(
+        &mut self,
+        sort: Sort,
+        flush_state: &mut SegmentWriteState<D, DW, C>,
+    ) -> Result<Option<SegmentInfo<D, C>>> {
+        if !self.pending_updates.deleted_queries.is_empty()
+            || !self.pending_updates.dv_updates.is_empty()
+        {
+            let reason = format!(
+                "{} delete queries and {} doc values updates pending on flush",
+                self.pending_updates.deleted_queries.len(),
+                self.pending_updates.dv_updates.len()
+            );
+            self.index_writer_config.info("DWPT", || {
+                format!(
+                    "segment {} left unsorted: {}",
+                    self.segment_info.name, reason
+                )
+            });
+            self.segment_info
+                .diagnostics
+                .insert(UNSORTED_DIAGNOSTIC.to_string(), reason);
+            return Ok(None);
+        }
+        let max_doc = self.segment_info.max_doc();
+        let num_docs = max_doc - flush_state.del_count_on_flush as i32;
+        if num_docs == 0 {
+            return Ok(None);
+        }
+
+        let unsorted = Arc::new(SegmentCommitInfo::new(
+            self.segment_info.clone(),
+            0,
+            -1,
+            -1,
+            -1,
+            HashMap::new(),
+            HashSet::new(),
+        ));
+        let mut reader = SegmentReader::open(&unsorted, &IOContext::READ)?;
+        if Sorter::new(sort.clone())
+            .sort_leaf_reader(&reader.leaf_context())?
+            .is_none()
+            && flush_state.del_count_on_flush == 0
+        {
+            // already in order, nothing to rewrite
+            self.segment_info.index_sort = Some(sort);
+            return Ok(None);
+        }
+        if flush_state.del_count_on_flush > 0 {
+            let live_docs = mem::replace(&mut flush_state.live_docs, FixedBitSet::default());
+            reader =
+                SegmentReader::build_from(unsorted, &reader, Arc::new(live_docs), num_docs, false)?;
+        }
+
+        let writer = self.index_writer();
+        let mut sorted_info = SegmentInfo::new(
+            VERSION_LATEST.clone(),
+            &writer.new_segment_name(),
+            -1,
+            Arc::clone(&self.directory_orig),
+            false,
+            Some(Arc::clone(self.segment_info.codec())),
+            HashMap::new(),
+            random_id(),
+            HashMap::new(),
+            Some(sort),
+        )?;
+        let unsorted_files = self.directory.create_files();
+        let ctx = IOContext::Merge(MergeInfo::new(
+            max_doc as u32,
+            self.bytes_used() as u64,
+            false,
+            None,
+        ));
+        let mut merger = SegmentMerger::new(
+            vec![Arc::new(reader)],
+            &sorted_info,
+            Arc::clone(&self.directory),
+            FieldNumbersRef::new(Arc::clone(writer.global_field_numbers())),
+            ctx,
+        )?;
+        merger.merge()?;
+        let field_infos = merger.merge_state.merge_field_infos.take().unwrap();
+        drop(merger);
+
+        let sorted_files: HashSet<String> = self
+            .directory
+            .create_files()
+            .difference(&unsorted_files)
+            .cloned()
+            .collect();
+        self.files_to_delete.extend(unsorted_files);
+        sorted_info.set_files(&sorted_files)?;
+        flush_state.field_infos = field_infos.as_ref().clone();
+        flush_state.live_docs = FixedBitSet::default();
+        flush_state.del_count_on_flush = 0;
+        Ok(Some(sorted_info))
+    }
+
     fn seal_flushed_segment(&mut self, flushed_segment: &mut FlushedSegment<D, C>) -> Result<()> {
         // set_diagnostics(&mut flushed_segment.segment_info.info, index_writer::SOURCE_FLUSH);
 
@@ -571,6 +720,8 @@ pub struct FlushedSegment<D: Directory, C: Codec> {
     pub segment_updates: Option<FrozenBufferedUpdates<C>>,
     pub live_docs: BitsRef,
     pub del_count: u32,
+    /// time writing the segment took
+    pub flush_time: Duration,
 }
 
 impl<D: Directory, C: Codec> FlushedSegment<D, C> {
@@ -593,6 +744,7 @@ impl<D: Directory, C: Codec> FlushedSegment<D, C> {
             segment_updates,
             live_docs,
             del_count,
+            flush_time: Duration::default(),
         }
     }
 }
@@ -609,6 +761,9 @@ impl<D: Directory, C: Codec> FlushedSegment<D, C> {
 /// Once a `DocumentsWriterPerThread` is selected for flush the thread pool
 /// is reusing the flushing `DocumentsWriterPerThread`s ThreadState with a
 /// new `DocumentsWriterPerThread` instance.
+///
+/// The pool creates at most `max_thread_states` states, once they are all in
+/// use the indexing threads wait for one to be released.
 pub(crate) struct DocumentsWriterPerThreadPool<
     D: Directory + Send + Sync + 'static,
     C: Codec,
@@ -618,6 +773,7 @@ pub(crate) struct DocumentsWriterPerThreadPool<
     inner: Mutex<DWPTPoolInner<D, C, MS, MP>>,
     aborted: Volatile<bool>,
     cond: Condvar,
+    max_thread_states: usize,
 }
 
 struct DWPTPoolInner<
@@ -629,6 +785,8 @@ struct DWPTPoolInner<
     thread_states: Vec<Arc<ThreadState<D, C, MS, MP>>>,
     // valid thread_state index in `self.thread_states`
     free_list: Vec<usize>,
+    // number of threads waiting for a free thread state
+    num_waiting: usize,
 }
 
 impl<D, C, MS, MP> DocumentsWriterPerThreadPool<D, C, MS, MP>
@@ -638,15 +796,17 @@ where
     MS: MergeScheduler,
     MP: MergePolicy,
 {
-    pub fn new() -> Self {
+    pub fn new(max_thread_states: Option<u32>) -> Self {
         let inner = DWPTPoolInner {
             thread_states: vec![],
             free_list: vec![],
+            num_waiting: 0,
         };
         DocumentsWriterPerThreadPool {
             inner: Mutex::new(inner),
             aborted: Volatile::new(false),
             cond: Condvar::new(),
+            max_thread_states: max_thread_states.map_or(usize::max_value(), |max| max as usize),
         }
     }
 
@@ -655,6 +815,11 @@ where
         self.inner.lock().unwrap().thread_states.len()
     }
 
+    /// Returns the number of threads waiting for a free `ThreadState`.
+    pub fn num_waiting_threads(&self) -> usize {
+        self.inner.lock().unwrap().num_waiting
+    }
+
     pub fn get_thread_state(&self, i: usize) -> Arc<ThreadState<D, C, MS, MP>> {
         let guard = self.inner.lock().unwrap();
         debug_assert!(i < guard.thread_states.len());
@@ -666,8 +831,7 @@ where
         self.aborted.write(true);
     }
 
-    #[allow(dead_code)]
-    fn clear_abort(&self) {
+    pub(crate) fn clear_abort(&self) {
         let _guard = self.inner.lock().unwrap();
         self.aborted.write(false);
         self.cond.notify_all();
@@ -705,6 +869,12 @@ where
     /// to do an indexing operation (add/update_document).
     pub fn get_and_lock(&self) -> Result<Arc<ThreadState<D, C, MS, MP>>> {
         let mut guard = self.inner.lock().unwrap();
+        while guard.free_list.is_empty() && guard.thread_states.len() >= self.max_thread_states {
+            // all the thread states are in use, wait for one to be released
+            guard.num_waiting += 1;
+            guard = self.cond.wait(guard)?;
+            guard.num_waiting -= 1;
+        }
         if let Some(mut idx) = guard.free_list.pop() {
             if guard.thread_states[idx].dwpt.is_none() {
                 // This thread-state is not initialized, e.g. it
@@ -738,9 +908,12 @@ where
         debug_assert!(!guard.free_list.contains(&state.index));
         guard.free_list.push(state.index);
         // In case any thread is waiting, wake one of them up since we just
-        // released a thread state; notify() should be sufficient but we do
-        // notifyAll defensively:
-        self.cond.notify_all();
+        // released a thread state, this is called after each document so
+        // skip the notification when nobody waits. The condition is shared
+        // with the threads waiting for an abort to clear, so wake them all:
+        if guard.num_waiting > 0 {
+            self.cond.notify_all();
+        }
     }
 
     pub fn locked_state(&self, idx: usize) -> Arc<ThreadState<D, C, MS, MP>> {
@@ -0,0 +1,166 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::util::DocId;
+
+use error::{ErrorKind::CorruptIndex, Result};
+
+/// Maximum number of dimensions of an indexed vector.
+pub const MAX_VECTOR_DIMENSIONS: u32 = 1024;
+
+/// The function used to compare the vectors of a field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub enum VectorSimilarityFunction {
+    /// Euclidean distance
+    Euclidean,
+    /// Dot product, the vectors must be of unit length
+    DotProduct,
+    /// Cosine of the angle between the vectors
+    Cosine,
+}
+
+impl Default for VectorSimilarityFunction {
+    fn default() -> Self {
+        VectorSimilarityFunction::Euclidean
+    }
+}
+
+impl VectorSimilarityFunction {
+    /// Scores the similarity of two vectors of the same dimension, the more
+    /// similar the vectors are, the higher the score is, between 0 and 1.
+    pub fn compare(self, v1: &[f32], v2: &[f32]) -> f32 {
+        debug_assert_eq!(v1.len(), v2.len());
+        match self {
+            VectorSimilarityFunction::Euclidean => 1.0 / (1.0 + squared_distance(v1, v2)),
+            VectorSimilarityFunction::DotProduct => (1.0 + dot_product(v1, v2)) / 2.0,
+            VectorSimilarityFunction::Cosine => (1.0 + cosine(v1, v2)) / 2.0,
+        }
+    }
+
+    /// The code the function is written with in the index.
+    pub fn ord(self) -> u8 {
+        match self {
+            VectorSimilarityFunction::Euclidean => 0,
+            VectorSimilarityFunction::DotProduct => 1,
+            VectorSimilarityFunction::Cosine => 2,
+        }
+    }
+
+    pub fn from_ord(ord: u8) -> Result<VectorSimilarityFunction> {
+        match ord {
+            0 => Ok(VectorSimilarityFunction::Euclidean),
+            1 => Ok(VectorSimilarityFunction::DotProduct),
+            2 => Ok(VectorSimilarityFunction::Cosine),
+            _ => bail!(CorruptIndex(format!(
+                "invalid vector similarity function: {}",
+                ord
+            ))),
+        }
+    }
+}
+
+pub fn dot_product(v1: &[f32], v2: &[f32]) -> f32 {
+    v1.iter().zip(v2).map(|(a, b)| a * b).sum()
+}
+
+pub fn cosine(v1: &[f32], v2: &[f32]) -> f32 {
+    let mut dot = 0.0f32;
+    let mut norm1 = 0.0f32;
+    let mut norm2 = 0.0f32;
+    for (a, b) in v1.iter().zip(v2) {
+        dot += a * b;
+        norm1 += a * a;
+        norm2 += b * b;
+    }
+    if norm1 == 0.0 || norm2 == 0.0 {
+        0.0
+    } else {
+        dot / (norm1 * norm2).sqrt()
+    }
+}
+
+pub fn squared_distance(v1: &[f32], v2: &[f32]) -> f32 {
+    v1.iter().zip(v2).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+/// Access to the vectors of a field.
+///
+/// The documents having a vector are numbered by ords, from 0 to `size()`,
+/// in the order of their doc ids, the vectors can be read in any order
+/// either by ord or by doc id.
+pub trait VectorValues: Send + Sync {
+    /// The dimension of the vectors.
+    fn dimension(&self) -> usize;
+
+    /// The number of documents having a vector.
+    fn size(&self) -> usize;
+
+    fn similarity_function(&self) -> VectorSimilarityFunction;
+
+    /// The doc id of the document of the given ord.
+    fn ord_to_doc(&self, ord: usize) -> DocId;
+
+    /// The ord of the vector of the given document, `None` if the document
+    /// has no vector.
+    fn doc_to_ord(&self, doc: DocId) -> Option<usize>;
+
+    /// Reads the vector of the given ord into `vector`, which must have
+    /// `dimension()` values.
+    fn vector_value(&self, ord: usize, vector: &mut [f32]) -> Result<()>;
+
+    /// Reads the vector of the given document into `vector`, returns false
+    /// if the document has no vector.
+    fn vector(&self, doc: DocId, vector: &mut [f32]) -> Result<bool> {
+        match self.doc_to_ord(doc) {
+            Some(ord) => {
+                self.vector_value(ord, vector)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity_functions() {
+        let v1 = [1.0f32, 0.0];
+        let v2 = [0.0f32, 1.0];
+        assert_eq!(VectorSimilarityFunction::Euclidean.compare(&v1, &v1), 1.0);
+        assert_eq!(
+            VectorSimilarityFunction::Euclidean.compare(&v1, &v2),
+            1.0 / 3.0
+        );
+        assert_eq!(VectorSimilarityFunction::DotProduct.compare(&v1, &v2), 0.5);
+        assert_eq!(
+            VectorSimilarityFunction::Cosine.compare(&v1, &[2.0, 0.0]),
+            1.0
+        );
+        assert_eq!(
+            VectorSimilarityFunction::Cosine.compare(&v1, &[-2.0, 0.0]),
+            0.0
+        );
+
+        for f in &[
+            VectorSimilarityFunction::Euclidean,
+            VectorSimilarityFunction::DotProduct,
+            VectorSimilarityFunction::Cosine,
+        ] {
+            assert_eq!(VectorSimilarityFunction::from_ord(f.ord()).unwrap(), *f);
+        }
+        assert!(VectorSimilarityFunction::from_ord(3).is_err());
+    }
+}
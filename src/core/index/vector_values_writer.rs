@@ -0,0 +1,104 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, VectorWriter};
+use core::index::{FieldInfo, SegmentWriteState, VectorSimilarityFunction, VectorValues};
+use core::store::Directory;
+use core::util::DocId;
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+/// Buffers the vectors of a field in memory until the segment is flushed.
+pub struct VectorValuesWriter {
+    field_info: FieldInfo,
+    docs: Vec<DocId>,
+    values: Vec<f32>,
+}
+
+impl VectorValuesWriter {
+    pub fn new(field_info: &FieldInfo) -> VectorValuesWriter {
+        VectorValuesWriter {
+            field_info: field_info.clone(),
+            docs: vec![],
+            values: vec![],
+        }
+    }
+
+    pub fn add_value(&mut self, doc_id: DocId, vector: &[f32]) -> Result<()> {
+        if let Some(&last) = self.docs.last() {
+            if last == doc_id {
+                bail!(IllegalArgument(format!(
+                    "VectorValuesField \"{}\" appears more than once in this document (only one \
+                     value is allowed per field)",
+                    self.field_info.name
+                )));
+            }
+            debug_assert!(doc_id > last);
+        }
+        if vector.len() != self.field_info.vector_dimension as usize {
+            bail!(IllegalArgument(format!(
+                "field=\"{}\": this field's vectors have dimension={} but got {}",
+                self.field_info.name,
+                self.field_info.vector_dimension,
+                vector.len()
+            )));
+        }
+        self.docs.push(doc_id);
+        self.values.extend_from_slice(vector);
+        Ok(())
+    }
+
+    pub fn ram_bytes_used(&self) -> usize {
+        self.docs.capacity() * 4 + self.values.capacity() * 4
+    }
+
+    pub fn flush<D: Directory, DW: Directory, C: Codec, W: VectorWriter>(
+        &self,
+        _state: &SegmentWriteState<D, DW, C>,
+        writer: &mut W,
+    ) -> Result<()> {
+        writer.write_field(&self.field_info, &BufferedVectorValues { writer: self })
+    }
+}
+
+struct BufferedVectorValues<'a> {
+    writer: &'a VectorValuesWriter,
+}
+
+impl<'a> VectorValues for BufferedVectorValues<'a> {
+    fn dimension(&self) -> usize {
+        self.writer.field_info.vector_dimension as usize
+    }
+
+    fn size(&self) -> usize {
+        self.writer.docs.len()
+    }
+
+    fn similarity_function(&self) -> VectorSimilarityFunction {
+        self.writer.field_info.vector_similarity_function
+    }
+
+    fn ord_to_doc(&self, ord: usize) -> DocId {
+        self.writer.docs[ord]
+    }
+
+    fn doc_to_ord(&self, doc: DocId) -> Option<usize> {
+        self.writer.docs.binary_search(&doc).ok()
+    }
+
+    fn vector_value(&self, ord: usize, vector: &mut [f32]) -> Result<()> {
+        let dim = self.dimension();
+        vector.copy_from_slice(&self.writer.values[ord * dim..(ord + 1) * dim]);
+        Ok(())
+    }
+}
@@ -19,4 +19,5 @@ pub mod highlight;
 pub mod index;
 pub mod search;
 pub mod store;
+pub mod suggest;
 pub mod util;
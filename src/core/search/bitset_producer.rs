@@ -0,0 +1,171 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::index_writer_config::IndexReaderWarmer;
+use core::index::{IndexReader, LeafReaderContext};
+use core::search::query_cache::{NoCacheQueryCache, QueryCache};
+use core::search::searcher::{DefaultIndexSearcher, SearchPlanBuilder};
+use core::search::{DocIterator, Query, NO_MORE_DOCS};
+use core::util::bit_set::{BitSet, FixedBitSet};
+use core::util::external::deferred::Deferred;
+use error::Result;
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Produces the bit set of the docs of a segment matching a filter, e.g. the
+/// parent docs of the blocks of a block join.
+pub trait BitSetProducer<C: Codec>: Send + Sync {
+    /// The docs of the segment matching the filter, deleted docs included,
+    /// or None if no doc matches.
+    fn get_bit_set(&self, leaf: &LeafReaderContext<'_, C>) -> Result<Option<Arc<FixedBitSet>>>;
+}
+
+type BitSetCache = Arc<RwLock<HashMap<String, Option<Arc<FixedBitSet>>>>>;
+
+/// A `BitSetProducer` running a query and caching the resulting bit set per
+/// segment core, so that it's computed once per segment whatever the number
+/// of readers sharing the segment. A cached bit set is dropped with the core
+/// of its segment.
+pub struct QueryBitSetProducer<C: Codec> {
+    query: Arc<dyn Query<C>>,
+    cache: BitSetCache,
+}
+
+// the query is only read to create its weights
+unsafe impl<C: Codec> Send for QueryBitSetProducer<C> {}
+
+unsafe impl<C: Codec> Sync for QueryBitSetProducer<C> {}
+
+impl<C: Codec> QueryBitSetProducer<C> {
+    pub fn new(query: Arc<dyn Query<C>>) -> QueryBitSetProducer<C> {
+        QueryBitSetProducer {
+            query,
+            cache: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub fn query(&self) -> &dyn Query<C> {
+        self.query.as_ref()
+    }
+
+    /// The number of segments the bit set is currently cached for.
+    pub fn cached_segments(&self) -> usize {
+        self.cache.read().unwrap().len()
+    }
+
+    fn compute(&self, leaf: &LeafReaderContext<'_, C>) -> Result<Option<Arc<FixedBitSet>>> {
+        let mut searcher = DefaultIndexSearcher::new(leaf.parent);
+        let query_cache: Arc<dyn QueryCache<C>> = Arc::new(NoCacheQueryCache::new());
+        searcher.set_query_cache(query_cache);
+        let weight = searcher.create_normalized_weight(self.query.as_ref(), false)?;
+        match weight.create_scorer(leaf)? {
+            Some(mut scorer) => {
+                let mut bits = FixedBitSet::new(leaf.reader.max_doc() as usize);
+                loop {
+                    let doc = scorer.next()?;
+                    if doc == NO_MORE_DOCS {
+                        break;
+                    }
+                    bits.set(doc as usize);
+                }
+                Ok(Some(Arc::new(bits)))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl<C: Codec> BitSetProducer<C> for QueryBitSetProducer<C> {
+    fn get_bit_set(&self, leaf: &LeafReaderContext<'_, C>) -> Result<Option<Arc<FixedBitSet>>> {
+        let key = leaf.reader.core_cache_key();
+        if let Some(bits) = self.cache.read()?.get(key) {
+            return Ok(bits.clone());
+        }
+        let bits = self.compute(leaf)?;
+        let inserted = {
+            let mut cache = self.cache.write()?;
+            if cache.contains_key(key) {
+                false
+            } else {
+                cache.insert(key.to_string(), bits.clone());
+                true
+            }
+        };
+        if inserted {
+            let key = key.to_string();
+            let cache = Arc::clone(&self.cache);
+            leaf.reader.add_core_drop_listener(Deferred::new(move || {
+                cache.write().unwrap().remove(&key);
+            }));
+        }
+        Ok(bits)
+    }
+}
+
+/// Precomputes the bit sets of the registered filters on new segments, so
+/// that the first query using a filter after a refresh or a merge doesn't pay
+/// for computing it.
+///
+/// Set it as the `merged_segment_warmer` of the `IndexWriterConfig` to warm
+/// the merged segments before they are visible to the near real-time
+/// readers, and register it on the `SearcherManager` to warm the segments of
+/// each refreshed searcher, e.g. the flushed ones.
+pub struct FilterWarmer<C: Codec> {
+    producers: RwLock<Vec<Arc<dyn BitSetProducer<C>>>>,
+}
+
+impl<C: Codec> FilterWarmer<C> {
+    pub fn new() -> FilterWarmer<C> {
+        FilterWarmer {
+            producers: RwLock::new(vec![]),
+        }
+    }
+
+    /// Registers a filter to warm on the segments of the next readers.
+    pub fn register(&self, producer: Arc<dyn BitSetProducer<C>>) {
+        self.producers.write().unwrap().push(producer);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.producers.read().unwrap().is_empty()
+    }
+
+    /// Computes the bit sets of all the registered filters on each segment of
+    /// `reader`, the bit sets already cached are left as is.
+    pub fn warm<R: IndexReader<Codec = C> + ?Sized>(&self, reader: &R) -> Result<()> {
+        let producers = self.producers.read()?;
+        if producers.is_empty() {
+            return Ok(());
+        }
+        for leaf in reader.leaves() {
+            for producer in producers.iter() {
+                producer.get_bit_set(&leaf)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<C: Codec> Default for FilterWarmer<C> {
+    fn default() -> Self {
+        FilterWarmer::new()
+    }
+}
+
+impl<C: Codec> IndexReaderWarmer<C> for FilterWarmer<C> {
+    fn warm(&self, reader: &dyn IndexReader<Codec = C>) -> Result<()> {
+        FilterWarmer::warm(self, reader)
+    }
+}
@@ -11,13 +11,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use error::ErrorKind::CorruptIndex;
 use error::Result;
 use std::fmt;
 use std::sync::Arc;
 
 use core::codec::Codec;
 use core::index::field_info::FieldInvertState;
-use core::index::{NumericDocValues, SearchLeafReader};
+use core::index::{
+    FieldInfo, NumericDocValues, NumericDocValuesContext, NumericDocValuesRef, SearchLeafReader,
+};
 use core::search::explanation::Explanation;
 use core::search::statistics::{CollectionStatistics, TermStatistics};
 use core::search::{SimScorer, SimWeight, Similarity};
@@ -38,6 +41,117 @@ lazy_static! {
         norm_table[0] = 1f32 / norm_table[255];
         norm_table
     };
+    static ref LENGTH_TABLE: [f32; 256] = {
+        let mut length_table: [f32; 256] = [0f32; 256];
+        for (i, length) in length_table.iter_mut().enumerate() {
+            *length = SmallFloat::byte4_to_int(i as u8) as f32;
+        }
+        length_table
+    };
+}
+
+/// How the length of a field is encoded in its norms. Norms are written at
+/// index time with `IndexWriterConfig::norms_encoding`, which is recorded in
+/// the `NormsEncoding::ATTRIBUTE_KEY` attribute of the field in each segment,
+/// so that `BM25Similarity` decodes them the same way.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormsEncoding {
+    /// `boost / sqrt(length)` encoded by `SmallFloat::float_to_byte315`, the
+    /// encoding of Lucene 6, which is very coarse on long fields.
+    Boosted,
+    /// The length encoded by `SmallFloat::int_to_byte4`, the encoding of
+    /// Lucene 7 and later: exact for short fields and within 1/8 of the length
+    /// otherwise. The index time boost of the field is ignored.
+    Length,
+}
+
+impl Default for NormsEncoding {
+    fn default() -> Self {
+        NormsEncoding::Boosted
+    }
+}
+
+impl NormsEncoding {
+    /// The field info attribute holding the encoding of the norms of a field.
+    pub const ATTRIBUTE_KEY: &'static str = "NormsEncoding";
+
+    /// Returns the encoding of the norms of `field_info`, `Boosted` if the
+    /// segment was written before the encoding was recorded.
+    pub fn of(field_info: Option<&FieldInfo>) -> Result<NormsEncoding> {
+        let value = match field_info.and_then(|fi| fi.attribute(Self::ATTRIBUTE_KEY)) {
+            Some(value) => value,
+            None => return Ok(NormsEncoding::default()),
+        };
+        match value.as_str() {
+            "Boosted" => Ok(NormsEncoding::Boosted),
+            "Length" => Ok(NormsEncoding::Length),
+            _ => bail!(CorruptIndex(format!(
+                "unknown norms encoding {:?} of field {}",
+                value,
+                field_info.unwrap().name
+            ))),
+        }
+    }
+
+    /// Records the encoding in the attributes of `field_info`.
+    pub fn put_attribute(self, field_info: &FieldInfo) {
+        field_info.put_attribute(Self::ATTRIBUTE_KEY.to_string(), format!("{:?}", self));
+    }
+
+    /// Returns the norm of a field from its inverted state.
+    pub fn encode(self, state: &FieldInvertState) -> i64 {
+        let num_terms = state.length - state.num_overlap;
+        match self {
+            NormsEncoding::Boosted => BM25Similarity::encode_norm_value(state.boost, num_terms),
+            NormsEncoding::Length => SmallFloat::int_to_byte4(num_terms),
+        }
+        .into()
+    }
+
+    /// Returns the field length, divided by the square of its boost for
+    /// `Boosted`, decoded from a norm returned by `encode`.
+    pub fn decode(self, norm: u8) -> f32 {
+        match self {
+            NormsEncoding::Boosted => NORM_TABLE[norm as usize],
+            NormsEncoding::Length => LENGTH_TABLE[norm as usize],
+        }
+    }
+
+    /// Returns `norms` encoded by `self` re-encoded with `to`, as close as
+    /// the encodings allow, e.g. when merging segments indexed with different
+    /// encodings.
+    pub fn reencode(self, norms: NumericDocValuesRef, to: NormsEncoding) -> NumericDocValuesRef {
+        if self == to {
+            return norms;
+        }
+        let mut table = [0u8; 256];
+        for (norm, reencoded) in table.iter_mut().enumerate() {
+            let length = f64::from(self.decode(norm as u8))
+                .round()
+                .min(f64::from(i32::max_value())) as i32;
+            *reencoded = match to {
+                NormsEncoding::Boosted => BM25Similarity::encode_norm_value(1.0, length),
+                NormsEncoding::Length => SmallFloat::int_to_byte4(length),
+            };
+        }
+        Arc::new(ReencodedNorms { norms, table })
+    }
+}
+
+struct ReencodedNorms {
+    norms: NumericDocValuesRef,
+    table: [u8; 256],
+}
+
+impl NumericDocValues for ReencodedNorms {
+    fn get_with_ctx(
+        &self,
+        ctx: NumericDocValuesContext,
+        doc_id: DocId,
+    ) -> Result<(i64, NumericDocValuesContext)> {
+        let (norm, ctx) = self.norms.get_with_ctx(ctx, doc_id)?;
+        Ok((self.table[(norm & 0xFF) as usize].into(), ctx))
+    }
 }
 
 pub const DEFAULT_BM25_K1: f32 = 1.2;
@@ -80,19 +194,13 @@ impl BM25Similarity {
     }
 
     pub fn compute_norm(state: &FieldInvertState) -> i64 {
-        let num_terms = state.length - state.num_overlap;
-        BM25Similarity::encode_norm_value(state.boost, num_terms) as i64
+        NormsEncoding::Boosted.encode(state)
     }
 
     pub fn encode_norm_value(boost: f32, field_length: i32) -> u8 {
         SmallFloat::float_to_byte315(boost / (field_length as f32).sqrt())
     }
 
-    #[inline]
-    fn decode_norm_value(b: usize) -> f32 {
-        NORM_TABLE[b]
-    }
-
     fn idf(term_stats: &[TermStatistics], collection_stats: &CollectionStatistics) -> f32 {
         let mut idf = 0.0f32;
         let doc_count = if collection_stats.doc_count == -1 {
@@ -155,18 +263,21 @@ impl<C: Codec> Similarity<C> for BM25Similarity {
         let avgdl = BM25Similarity::avg_field_length(&collection_stats);
         let idf = BM25Similarity::idf(&term_stats, &collection_stats);
         let field = collection_stats.field.clone();
-        let mut cache: [f32; 256] = [0f32; 256];
-        for (i, c) in cache.iter_mut().enumerate() {
-            *c = self.k1
-                * ((1.0 - self.b) + self.b * (BM25Similarity::decode_norm_value(i) / avgdl));
-        }
+        let cache = |norms_encoding: NormsEncoding| {
+            let mut cache: [f32; 256] = [0f32; 256];
+            for (i, c) in cache.iter_mut().enumerate() {
+                *c = self.k1 * ((1.0 - self.b) + self.b * (norms_encoding.decode(i as u8) / avgdl));
+            }
+            cache
+        };
 
         Box::new(BM25SimWeight::new(
             self.k1,
             self.b,
             idf,
             field,
-            cache,
+            cache(NormsEncoding::Boosted),
+            cache(NormsEncoding::Length),
             self.idf_explain(collection_stats, term_stats),
             BM25Similarity::avg_field_length(collection_stats),
             boost,
@@ -188,11 +299,15 @@ pub struct BM25SimScorer {
 }
 
 impl BM25SimScorer {
-    fn new(weight: &BM25SimWeight, norms: Option<Box<dyn NumericDocValues>>) -> BM25SimScorer {
+    fn new(
+        weight: &BM25SimWeight,
+        norms_encoding: NormsEncoding,
+        norms: Option<Box<dyn NumericDocValues>>,
+    ) -> BM25SimScorer {
         BM25SimScorer {
             k1: weight.k1,
             weight: weight.weight,
-            cache: Arc::clone(&weight.cache),
+            cache: Arc::clone(weight.cache(norms_encoding)),
             norms,
         }
     }
@@ -226,6 +341,7 @@ pub struct BM25SimWeight {
     idf: f32,
     field: String,
     cache: Arc<[f32; 256]>,
+    length_cache: Arc<[f32; 256]>,
     boost: f32,
     weight: f32,
     idf_explanation: Explanation,
@@ -239,6 +355,7 @@ impl BM25SimWeight {
         idf: f32,
         field: String,
         cache: [f32; 256],
+        length_cache: [f32; 256],
         idf_explanation: Explanation,
         avg_dl: f32,
         boost: f32,
@@ -249,6 +366,7 @@ impl BM25SimWeight {
             idf,
             field,
             cache: Arc::new(cache),
+            length_cache: Arc::new(length_cache),
             boost: 1.0,
             weight: 0.0,
             idf_explanation,
@@ -258,10 +376,19 @@ impl BM25SimWeight {
         weight
     }
 
+    /// The `k1 * (1 - b + b * length / avgdl)` of each norm of `norms_encoding`.
+    fn cache(&self, norms_encoding: NormsEncoding) -> &Arc<[f32; 256]> {
+        match norms_encoding {
+            NormsEncoding::Boosted => &self.cache,
+            NormsEncoding::Length => &self.length_cache,
+        }
+    }
+
     fn explain_tf_norm(
         &self,
         doc: DocId,
         freq: Explanation,
+        norms_encoding: NormsEncoding,
         norms: Option<Box<dyn NumericDocValues>>,
     ) -> Result<Explanation> {
         let mut subs: Vec<Explanation> = vec![];
@@ -277,7 +404,7 @@ impl BM25SimWeight {
 
         match norms {
             Some(n) => {
-                let doc_len = NORM_TABLE[n.get(doc)? as usize];
+                let doc_len = norms_encoding.decode(n.get(doc)? as u8);
                 subs.push(Explanation::new(
                     true,
                     self.b,
@@ -330,6 +457,7 @@ impl BM25SimWeight {
         &self,
         doc: DocId,
         freq: Explanation,
+        norms_encoding: NormsEncoding,
         norms: Option<Box<dyn NumericDocValues>>,
     ) -> Result<Explanation> {
         let mut subs: Vec<Explanation> = vec![];
@@ -344,7 +472,7 @@ impl BM25SimWeight {
         subs.push(self.idf_explanation.clone());
 
         let freq_string = freq.to_string(0);
-        let tf_explanation = self.explain_tf_norm(doc, freq, norms)?;
+        let tf_explanation = self.explain_tf_norm(doc, freq, norms_encoding, norms)?;
         let tf_value = tf_explanation.value();
         subs.push(tf_explanation);
 
@@ -372,8 +500,9 @@ impl<C: Codec> SimWeight<C> for BM25SimWeight {
     }
 
     fn sim_scorer(&self, reader: &SearchLeafReader<C>) -> Result<Box<dyn SimScorer>> {
+        let norms_encoding = NormsEncoding::of(reader.field_info(&self.field))?;
         let norm = reader.norm_values(&self.field)?;
-        Ok(Box::new(BM25SimScorer::new(self, norm)))
+        Ok(Box::new(BM25SimScorer::new(self, norms_encoding, norm)))
     }
 
     fn explain(
@@ -382,28 +511,119 @@ impl<C: Codec> SimWeight<C> for BM25SimWeight {
         doc: DocId,
         freq: Explanation,
     ) -> Result<Explanation> {
+        let norms_encoding = NormsEncoding::of(reader.field_info(&self.field))?;
         let norms = reader.norm_values(&self.field)?;
-        self.explain_score(doc, freq, norms)
+        self.explain_score(doc, freq, norms_encoding, norms)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use core::analysis::{Analyzer, SimpleAnalyzer};
+    use core::doc::{Field, FieldType};
+    use core::index::index_writer_config::IndexWriterConfig;
     use core::index::tests::MockLeafReader;
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
 
     // copy from Lucene TestBM25Similarity
     #[test]
     fn test_sane_norm_values() {
         for i in 0..256 {
-            let len = BM25Similarity::decode_norm_value(i);
+            let len = NormsEncoding::Boosted.decode(i as u8);
             assert!(len >= 0f32);
             assert!(!len.is_nan());
             assert!(!len.is_infinite());
             if i > 0 {
-                assert!(len < BM25Similarity::decode_norm_value(i - 1));
+                assert!(len < NormsEncoding::Boosted.decode((i - 1) as u8));
+            }
+        }
+    }
+
+    #[test]
+    fn test_length_norm_values() {
+        let mut state = FieldInvertState::with_name("body".into());
+        for &length in &[1, 10, 39, 100, 1000] {
+            state.length = length;
+            let norm = NormsEncoding::Length.encode(&state) as u8;
+            let decoded = NormsEncoding::Length.decode(norm);
+            assert!(decoded <= length as f32);
+            assert!(decoded >= length as f32 * 7.0 / 8.0);
+            if length < 40 {
+                assert_eq!(decoded, length as f32);
             }
         }
+        for i in 1..256 {
+            let len = NormsEncoding::Length.decode(i as u8);
+            assert!(len > NormsEncoding::Length.decode((i - 1) as u8));
+        }
+    }
+
+    fn body(text: &str) -> Vec<Box<dyn Fieldable>> {
+        let mut field_type = FieldType::default();
+        field_type.index_options = IndexOptions::DocsAndFreqs;
+        let tokens = SimpleAnalyzer::default()
+            .token_stream("body", text)
+            .unwrap();
+        vec![Box::new(Field::new(
+            "body".into(),
+            field_type,
+            Some(VariantValue::VString(text.into())),
+            Some(tokens),
+        ))]
+    }
+
+    #[test]
+    fn test_norms_encoding_of_segments() {
+        let path = ::std::env::temp_dir().join("rucene_bm25_norms_encoding");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        writer.add_document(body("a b c d")).unwrap();
+        writer.commit().unwrap();
+        writer.close().unwrap();
+
+        let mut config = IndexWriterConfig::default();
+        config.norms_encoding = NormsEncoding::Length;
+        let writer = IndexWriter::new(Arc::clone(&dir), Arc::new(config)).unwrap();
+        writer.add_document(body("a b c d e f g")).unwrap();
+        writer.commit().unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 2);
+        let mut lengths = vec![];
+        for leaf in &leaves {
+            let encoding = NormsEncoding::of(leaf.reader.field_info("body")).unwrap();
+            let norms = leaf.reader.norm_values("body").unwrap().unwrap();
+            lengths.push((encoding, encoding.decode(norms.get(0).unwrap() as u8)));
+        }
+        assert_eq!(
+            lengths,
+            vec![(NormsEncoding::Boosted, 4.0), (NormsEncoding::Length, 7.0)]
+        );
+
+        // the norms of the first segment are re-encoded by the merge
+        writer.force_merge(1, true).unwrap();
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let leaves = reader.leaves();
+        assert_eq!(leaves.len(), 1);
+        let info = leaves[0].reader.field_info("body");
+        assert_eq!(NormsEncoding::of(info).unwrap(), NormsEncoding::Length);
+        let norms = leaves[0].reader.norm_values("body").unwrap().unwrap();
+        for (doc, &length) in [4.0, 7.0].iter().enumerate() {
+            let norm = norms.get(doc as DocId).unwrap() as u8;
+            assert_eq!(NormsEncoding::Length.decode(norm), length);
+        }
+        writer.close().unwrap();
+
+        fs::remove_dir_all(&path).unwrap();
     }
 
     #[test]
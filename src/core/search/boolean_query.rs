@@ -12,14 +12,16 @@
 // limitations under the License.
 
 use std::any::Any;
+use std::collections::HashSet;
 use std::fmt;
 
 use core::codec::Codec;
 use core::index::LeafReaderContext;
+use core::search::boolean_scorer::BooleanScorer;
 use core::search::conjunction::ConjunctionScorer;
 use core::search::disjunction::DisjunctionSumScorer;
 use core::search::explanation::Explanation;
-use core::search::match_all::ConstantScoreQuery;
+use core::search::match_all::{ConstantScoreQuery, ConstantScoreWeight};
 use core::search::req_opt::ReqOptScorer;
 use core::search::searcher::SearchPlanBuilder;
 use core::search::term_query::TermQuery;
@@ -69,6 +71,77 @@ impl<C: Codec> BooleanQuery<C> {
         }))
     }
 
+    /// Rewrites the clauses of this query into a simpler but equivalent set of
+    /// clauses, the query tree is left untouched:
+    ///
+    /// - nested conjunctions (boolean queries without should clauses) under a must or filter clause
+    ///   and nested disjunctions (should clauses only) under a should clause are flattened into
+    ///   this query,
+    /// - duplicate filters and filters that are also must clauses are removed,
+    /// - if scores are not needed, must clauses become filters and should clauses are dropped when
+    ///   they are optional.
+    pub fn rewrite(&self, needs_scores: bool) -> BooleanClauses<'_, C> {
+        let mut musts: Vec<&dyn Query<C>> = Vec::with_capacity(self.must_queries.len());
+        let mut shoulds: Vec<&dyn Query<C>> = Vec::with_capacity(self.should_queries.len());
+        let mut filters: Vec<&dyn Query<C>> = Vec::with_capacity(self.filter_queries.len());
+
+        for q in &self.must_queries {
+            match Self::nested(q.as_ref()) {
+                Some(nested) if nested.should_queries.is_empty() => {
+                    let clauses = nested.rewrite(needs_scores);
+                    musts.extend(clauses.musts);
+                    filters.extend(clauses.filters);
+                }
+                _ => musts.push(q.as_ref()),
+            }
+        }
+        for q in &self.should_queries {
+            match Self::nested(q.as_ref()) {
+                Some(nested) if nested.is_disjunction() => {
+                    shoulds.extend(nested.rewrite(needs_scores).shoulds);
+                }
+                _ => shoulds.push(q.as_ref()),
+            }
+        }
+        for q in &self.filter_queries {
+            match Self::nested(q.as_ref()) {
+                Some(nested) if nested.should_queries.is_empty() => {
+                    let clauses = nested.rewrite(false);
+                    filters.extend(clauses.musts);
+                    filters.extend(clauses.filters);
+                }
+                _ => filters.push(q.as_ref()),
+            }
+        }
+
+        if !needs_scores {
+            // should clauses only matter for matching when they are the only
+            // clauses, see `BooleanWeight::create_scorer`
+            if !musts.is_empty() || !filters.is_empty() {
+                shoulds.clear();
+            }
+            musts.append(&mut filters);
+            ::std::mem::swap(&mut musts, &mut filters);
+        }
+
+        let mut seen: HashSet<String> = musts.iter().map(|q| q.to_string()).collect();
+        filters.retain(|q| seen.insert(q.to_string()));
+
+        BooleanClauses {
+            musts,
+            shoulds,
+            filters,
+        }
+    }
+
+    fn nested(query: &dyn Query<C>) -> Option<&BooleanQuery<C>> {
+        query.as_any().downcast_ref::<BooleanQuery<C>>()
+    }
+
+    fn is_disjunction(&self) -> bool {
+        self.must_queries.is_empty() && self.filter_queries.is_empty()
+    }
+
     fn queries_to_str(&self, queries: &[Box<dyn Query<C>>]) -> String {
         let query_strs: Vec<String> = queries.iter().map(|q| format!("{}", q)).collect();
         query_strs.join(", ")
@@ -81,17 +154,31 @@ impl<C: Codec> Query<C> for BooleanQuery<C> {
         searcher: &dyn SearchPlanBuilder<C>,
         needs_scores: bool,
     ) -> Result<Box<dyn Weight<C>>> {
-        let mut must_weights =
-            Vec::with_capacity(self.must_queries.len() + self.filter_queries.len());
-        for q in &self.must_queries {
-            must_weights.push(searcher.create_weight(q.as_ref(), needs_scores)?);
+        let clauses = self.rewrite(needs_scores);
+        if clauses.musts.is_empty() && clauses.shoulds.is_empty() && needs_scores {
+            // filters only: match as a single non-scoring (thus cacheable)
+            // conjunction, scoring 0 as a single filter would
+            let weight = searcher.create_weight(self, false)?;
+            return Ok(Box::new(ConstantScoreWeight::new(weight, 0f32)));
         }
-        for q in &self.filter_queries {
-            must_weights.push(searcher.create_weight(q.as_ref(), false)?);
+        if clauses.len() == 1 {
+            return if let Some(q) = clauses.musts.first().or(clauses.shoulds.first()) {
+                searcher.create_weight(*q, needs_scores)
+            } else {
+                searcher.create_weight(clauses.filters[0], false)
+            };
         }
-        let mut should_weights = Vec::with_capacity(self.should_queries.len());
-        for q in &self.should_queries {
-            should_weights.push(searcher.create_weight(q.as_ref(), needs_scores)?);
+
+        let mut must_weights = Vec::with_capacity(clauses.musts.len() + clauses.filters.len());
+        for q in clauses.musts {
+            must_weights.push(searcher.create_weight(q, needs_scores)?);
+        }
+        for q in clauses.filters {
+            must_weights.push(searcher.create_weight(q, false)?);
+        }
+        let mut should_weights = Vec::with_capacity(clauses.shoulds.len());
+        for q in clauses.shoulds {
+            should_weights.push(searcher.create_weight(q, needs_scores)?);
         }
 
         Ok(Box::new(BooleanWeight::new(
@@ -141,10 +228,26 @@ impl<C: Codec> fmt::Display for BooleanQuery<C> {
     }
 }
 
+/// The clauses of a `BooleanQuery` after `BooleanQuery::rewrite`.
+pub struct BooleanClauses<'a, C: Codec> {
+    pub musts: Vec<&'a dyn Query<C>>,
+    pub shoulds: Vec<&'a dyn Query<C>>,
+    pub filters: Vec<&'a dyn Query<C>>,
+}
+
+impl<'a, C: Codec> BooleanClauses<'a, C> {
+    pub fn len(&self) -> usize {
+        self.musts.len() + self.shoulds.len() + self.filters.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 pub struct BooleanWeight<C: Codec> {
     must_weights: Vec<Box<dyn Weight<C>>>,
     should_weights: Vec<Box<dyn Weight<C>>>,
-    #[allow(dead_code)]
     minimum_should_match: i32,
     needs_scores: bool,
 }
@@ -164,6 +267,19 @@ impl<C: Codec> BooleanWeight<C> {
         }
     }
 
+    fn should_scorers(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Vec<Box<dyn Scorer>>> {
+        let mut scorers = vec![];
+        for weight in &self.should_weights {
+            if let Some(scorer) = weight.create_scorer(leaf_reader)? {
+                scorers.push(scorer);
+            }
+        }
+        Ok(scorers)
+    }
+
     fn weights_to_str(&self, weights: &[Box<dyn Weight<C>>]) -> String {
         let weight_strs: Vec<String> = weights.iter().map(|q| format!("{}", q)).collect();
         weight_strs.join(", ")
@@ -193,12 +309,7 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
             None
         };
         let should_scorer: Option<Box<dyn Scorer>> = {
-            let mut scorers = vec![];
-            for weight in &self.should_weights {
-                if let Some(scorer) = weight.create_scorer(leaf_reader)? {
-                    scorers.push(scorer);
-                }
-            }
+            let mut scorers = self.should_scorers(leaf_reader)?;
             match scorers.len() {
                 0 => None,
                 1 => Some(scorers.remove(0)),
@@ -221,6 +332,33 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
         }
     }
 
+    /// Pure disjunctions are scored by windows of docs with a `BooleanScorer`
+    /// when iterated in order, which is faster than the `DisjunctionSumScorer`
+    /// for many clauses. If the docs can be scored out of order, the docs of
+    /// each window are returned in the order their buckets were filled.
+    fn create_bulk_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+        score_docs_in_order: bool,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if !self.must_weights.is_empty() || self.should_weights.len() < 2 {
+            return self.create_scorer(leaf_reader);
+        }
+        let mut scorers = self.should_scorers(leaf_reader)?;
+        Ok(match scorers.len() {
+            0 => None,
+            1 => Some(scorers.remove(0)),
+            _ if score_docs_in_order => Some(Box::new(BooleanScorer::new(
+                scorers,
+                self.minimum_should_match,
+            ))),
+            _ => Some(Box::new(BooleanScorer::out_of_order(
+                scorers,
+                self.minimum_should_match,
+            ))),
+        })
+    }
+
     fn query_type(&self) -> &'static str {
         BOOLEAN
     }
@@ -262,10 +400,23 @@ impl<C: Codec> Weight<C> for BooleanWeight<C> {
             max_coord += 1;
 
             if e.is_match() {
-                sum += e.value();
                 coord += 1;
                 match_count += 1;
-                subs.push(e);
+                if self.needs_scores && !w.needs_scores() {
+                    // a filter clause, it doesn't contribute to the score
+                    subs.push(Explanation::new(
+                        true,
+                        0.0f32,
+                        "match on required clause, product of:".to_string(),
+                        vec![
+                            Explanation::new(true, 0.0f32, "# clause".to_string(), vec![]),
+                            e,
+                        ],
+                    ));
+                } else {
+                    sum += e.value();
+                    subs.push(e);
+                }
             } else {
                 fail = true;
                 subs.push(Explanation::new(
@@ -352,3 +503,66 @@ impl<C: Codec> fmt::Display for BooleanWeight<C> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::index::Term;
+
+    fn term(text: &str) -> Box<dyn Query<TestCodec>> {
+        Box::new(TermQuery::new(
+            Term::new("title".into(), text.as_bytes().to_vec()),
+            1.0,
+            None,
+        ))
+    }
+
+    fn to_strs(queries: &[&dyn Query<TestCodec>]) -> Vec<String> {
+        queries.iter().map(|q| q.to_string()).collect()
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let conjunction = BooleanQuery::build(vec![term("b"), term("c")], vec![], vec![term("d")]);
+        let disjunction = BooleanQuery::build(vec![], vec![term("e"), term("f")], vec![]);
+        let query = BooleanQuery::build(
+            vec![term("a"), conjunction.unwrap()],
+            vec![disjunction.unwrap()],
+            vec![term("a"), term("d"), term("g")],
+        )
+        .unwrap();
+        let query = query
+            .as_any()
+            .downcast_ref::<BooleanQuery<TestCodec>>()
+            .unwrap();
+
+        let clauses = query.rewrite(true);
+        assert_eq!(
+            to_strs(&clauses.musts),
+            to_strs(&[&*term("a"), &*term("b"), &*term("c")])
+        );
+        assert_eq!(
+            to_strs(&clauses.shoulds),
+            to_strs(&[&*term("e"), &*term("f")])
+        );
+        assert_eq!(
+            to_strs(&clauses.filters),
+            to_strs(&[&*term("d"), &*term("g")])
+        );
+
+        let clauses = query.rewrite(false);
+        assert!(clauses.musts.is_empty());
+        assert!(clauses.shoulds.is_empty());
+        assert_eq!(
+            to_strs(&clauses.filters),
+            to_strs(&[
+                &*term("a"),
+                &*term("b"),
+                &*term("c"),
+                &*term("d"),
+                &*term("g")
+            ])
+        );
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::{DocIterator, Scorer, NO_MORE_DOCS};
+use core::util::DocId;
+use error::{ErrorKind::UnsupportedOperation, Result};
+
+use std::mem;
+
+const SHIFT: usize = 11;
+/// The number of docs of a window.
+pub const WINDOW_SIZE: usize = 1 << SHIFT;
+const MASK: usize = WINDOW_SIZE - 1;
+const SET_SIZE: usize = WINDOW_SIZE / 64;
+
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+    score: f64,
+    freq: i32,
+}
+
+/// A disjunction scorer which scores docs by windows of `WINDOW_SIZE` docs:
+/// each clause is iterated over the whole window at once, accumulating the
+/// partial scores of the docs in buckets, then the matching docs of the
+/// window are returned in order.
+///
+/// This avoids the priority queue of `DisjunctionSumScorer` which is
+/// updated for every matching doc of every clause, so it is much faster on
+/// disjunctions of many clauses as long as the docs are iterated in order
+/// with `next`. `advance` is supported but refills a whole window when the
+/// target is beyond the current one, so this scorer should only be used to
+/// score the top level query, see `Weight::create_bulk_scorer`.
+///
+/// When the collector accepts docs out of order, the docs of a window are
+/// returned in the order their buckets were first filled instead, which
+/// avoids scanning the bitset of the window, `advance` isn't supported then.
+pub struct BooleanScorer<T: Scorer> {
+    scorers: Vec<T>,
+    buckets: Vec<Bucket>,
+    matching: [u64; SET_SIZE],
+    min_should_match: i32,
+    in_order: bool,
+    // the filled buckets of the window, only tracked out of order
+    touched: Vec<u16>,
+    next_touched: usize,
+    // the first doc of the current window
+    window_base: DocId,
+    doc: DocId,
+    score: f32,
+    cost: usize,
+}
+
+impl<T: Scorer> BooleanScorer<T> {
+    pub fn new(scorers: Vec<T>, min_should_match: i32) -> BooleanScorer<T> {
+        Self::with_order(scorers, min_should_match, true)
+    }
+
+    /// Creates a scorer returning the docs of each window out of order.
+    pub fn out_of_order(scorers: Vec<T>, min_should_match: i32) -> BooleanScorer<T> {
+        Self::with_order(scorers, min_should_match, false)
+    }
+
+    fn with_order(scorers: Vec<T>, min_should_match: i32, in_order: bool) -> BooleanScorer<T> {
+        debug_assert!(scorers.len() > 1);
+        let cost = scorers.iter().map(|s| s.cost()).sum();
+        BooleanScorer {
+            scorers,
+            buckets: vec![Bucket::default(); WINDOW_SIZE],
+            matching: [0u64; SET_SIZE],
+            min_should_match: min_should_match.max(1),
+            in_order,
+            touched: if in_order {
+                vec![]
+            } else {
+                Vec::with_capacity(WINDOW_SIZE)
+            },
+            next_touched: 0,
+            // no window is filled yet, the first one starts at or after doc 0
+            window_base: -(WINDOW_SIZE as DocId),
+            doc: -1,
+            score: 0f32,
+            cost,
+        }
+    }
+
+    fn window_max(&self) -> DocId {
+        self.window_base + WINDOW_SIZE as DocId
+    }
+
+    /// Scores all the clauses over the window containing the first doc
+    /// on or after `min` matched by any clause.
+    fn fill_window(&mut self, min: DocId) -> Result<bool> {
+        let mut lead = NO_MORE_DOCS;
+        for scorer in &mut self.scorers {
+            let mut doc = scorer.doc_id();
+            if doc < min {
+                doc = scorer.advance(min)?;
+            }
+            lead = lead.min(doc);
+        }
+        if lead == NO_MORE_DOCS {
+            return Ok(false);
+        }
+
+        self.window_base = lead & !(MASK as DocId);
+        self.touched.clear();
+        self.next_touched = 0;
+        let window_max = self.window_max();
+        for scorer in &mut self.scorers {
+            let mut doc = scorer.doc_id();
+            while doc < window_max {
+                let i = doc as usize & MASK;
+                let word = &mut self.matching[i >> 6];
+                let bit = 1u64 << (i & 63);
+                if !self.in_order && *word & bit == 0 {
+                    self.touched.push(i as u16);
+                }
+                *word |= bit;
+                let bucket = &mut self.buckets[i];
+                bucket.score += f64::from(scorer.score()?);
+                bucket.freq += 1;
+                doc = scorer.next()?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Moves to the first doc of the current window whose offset is on or
+    /// after `from` with enough matching clauses, the buckets of the skipped
+    /// docs are cleared. Out of order, `from` is always after the previous doc
+    /// so the next filled bucket is returned instead.
+    fn next_in_window(&mut self, from: usize) -> Option<DocId> {
+        if !self.in_order {
+            return self.next_touched_in_window();
+        }
+        let mut word_index = from >> 6;
+        if word_index >= SET_SIZE {
+            return None;
+        }
+        // discard the docs before `from`
+        let skipped = self.matching[word_index] & ((1u64 << (from & 63)) - 1);
+        self.clear_bits(word_index, skipped);
+        let mut word = self.matching[word_index];
+        loop {
+            while word == 0 {
+                word_index += 1;
+                if word_index == SET_SIZE {
+                    return None;
+                }
+                word = self.matching[word_index];
+            }
+            let bit = word.trailing_zeros() as usize;
+            word &= word - 1;
+            self.matching[word_index] &= !(1u64 << bit);
+            let i = (word_index << 6) | bit;
+            let bucket = self.buckets[i];
+            self.buckets[i] = Bucket::default();
+            if bucket.freq >= self.min_should_match {
+                self.score = bucket.score as f32;
+                return Some(self.window_base + i as DocId);
+            }
+        }
+    }
+
+    fn next_touched_in_window(&mut self) -> Option<DocId> {
+        while self.next_touched < self.touched.len() {
+            let i = self.touched[self.next_touched] as usize;
+            self.next_touched += 1;
+            self.matching[i >> 6] &= !(1u64 << (i & 63));
+            let bucket = mem::replace(&mut self.buckets[i], Bucket::default());
+            if bucket.freq >= self.min_should_match {
+                self.score = bucket.score as f32;
+                return Some(self.window_base + i as DocId);
+            }
+        }
+        None
+    }
+
+    fn clear_bits(&mut self, word_index: usize, mut bits: u64) {
+        self.matching[word_index] &= !bits;
+        while bits != 0 {
+            let bit = bits.trailing_zeros() as usize;
+            bits &= bits - 1;
+            self.buckets[(word_index << 6) | bit] = Bucket::default();
+        }
+    }
+
+    fn clear_window(&mut self) {
+        for word_index in 0..SET_SIZE {
+            let bits = self.matching[word_index];
+            if bits != 0 {
+                self.clear_bits(word_index, bits);
+            }
+        }
+    }
+
+    /// Moves to the first matching doc on or after `target`.
+    fn move_to(&mut self, target: DocId) -> Result<DocId> {
+        let mut target = target;
+        // out of order the next doc may be before the previous one, so the
+        // window is only left once all its filled buckets are returned
+        if target < self.window_max() || !self.in_order {
+            let from = (target - self.window_base).max(0) as usize;
+            if let Some(doc) = self.next_in_window(from) {
+                self.doc = doc;
+                return Ok(doc);
+            }
+            target = self.window_max();
+        } else {
+            self.clear_window();
+        }
+        loop {
+            if !self.fill_window(target)? {
+                self.doc = NO_MORE_DOCS;
+                return Ok(NO_MORE_DOCS);
+            }
+            if let Some(doc) = self.next_in_window(0) {
+                self.doc = doc;
+                return Ok(doc);
+            }
+            // no doc of the window matches enough clauses
+            target = self.window_max();
+        }
+    }
+}
+
+impl<T: Scorer> Scorer for BooleanScorer<T> {
+    fn score(&mut self) -> Result<f32> {
+        Ok(self.score)
+    }
+}
+
+impl<T: Scorer> DocIterator for BooleanScorer<T> {
+    fn doc_id(&self) -> DocId {
+        self.doc
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        if self.doc == NO_MORE_DOCS {
+            return Ok(NO_MORE_DOCS);
+        }
+        let target = self.doc + 1;
+        self.move_to(target)
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        if !self.in_order {
+            bail!(UnsupportedOperation(
+                "advance isn't supported when scoring docs out of order".into()
+            ));
+        }
+        self.move_to(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.cost
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::tests::*;
+
+    fn collect<T: Scorer>(scorer: &mut BooleanScorer<T>) -> Vec<(DocId, f32)> {
+        let mut res = vec![];
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            res.push((doc, scorer.score().unwrap()));
+        }
+        res
+    }
+
+    #[test]
+    fn test_boolean_scorer() {
+        let far = WINDOW_SIZE as DocId * 3 + 5;
+        let scorers = vec![
+            create_mock_scorer(vec![1, 2, 3, far]),
+            create_mock_scorer(vec![2, 3, 5]),
+            create_mock_scorer(vec![3, 4, far]),
+        ];
+        let mut scorer = BooleanScorer::new(scorers, 1);
+        assert_eq!(scorer.doc_id(), -1);
+        assert_eq!(
+            collect(&mut scorer),
+            vec![
+                (1, 1.0),
+                (2, 4.0),
+                (3, 9.0),
+                (4, 4.0),
+                (5, 5.0),
+                (far, 2.0 * far as f32),
+            ]
+        );
+
+        let scorers = vec![
+            create_mock_scorer(vec![1, 2, 3, far]),
+            create_mock_scorer(vec![2, 3, 5]),
+            create_mock_scorer(vec![3, 4, far]),
+        ];
+        let mut scorer = BooleanScorer::new(scorers, 2);
+        assert_eq!(
+            collect(&mut scorer),
+            vec![(2, 4.0), (3, 9.0), (far, 2.0 * far as f32)]
+        );
+    }
+
+    #[test]
+    fn test_advance() {
+        let far = WINDOW_SIZE as DocId * 2 + 7;
+        let scorers = vec![
+            create_mock_scorer(vec![1, 10, 100, far]),
+            create_mock_scorer(vec![5, 10, far + 1]),
+        ];
+        let mut scorer = BooleanScorer::new(scorers, 1);
+        assert_eq!(scorer.advance(6).unwrap(), 10);
+        assert_eq!(scorer.score().unwrap(), 20.0);
+        assert_eq!(scorer.advance(11).unwrap(), 100);
+        assert_eq!(scorer.advance(WINDOW_SIZE as DocId).unwrap(), far);
+        assert_eq!(scorer.next().unwrap(), far + 1);
+        assert_eq!(scorer.next().unwrap(), NO_MORE_DOCS);
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let far = WINDOW_SIZE as DocId + 1;
+        let scorers = vec![
+            create_mock_scorer(vec![3, 7, far]),
+            create_mock_scorer(vec![1, 3, 5, far]),
+        ];
+        let mut scorer = BooleanScorer::out_of_order(scorers, 1);
+        // the buckets of the first clause are filled first in each window
+        assert_eq!(
+            collect(&mut scorer),
+            vec![
+                (3, 6.0),
+                (7, 7.0),
+                (1, 1.0),
+                (5, 5.0),
+                (far, 2.0 * far as f32),
+            ]
+        );
+        assert!(scorer.advance(0).is_err());
+    }
+}
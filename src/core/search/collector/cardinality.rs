@@ -0,0 +1,336 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{
+    BinaryDocValuesRef, DocValuesReader, DocValuesType, LeafReaderContext, NumericValuesReader,
+    SortedDocValuesRef, SortedNumericValuesReader, SortedSetDocValuesRef, NO_MORE_ORDS,
+};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::{Scorer, NO_MORE_DOCS};
+use core::util::bit_set::{BitSet, FixedBitSet, ImmutableBitSet};
+use core::util::hyper_log_log::HyperLogLogPlusPlus;
+use core::util::{Bits, DocId};
+use error::Result;
+
+use std::mem;
+
+/// The values of the field of a leaf, added to the sketch as they are
+/// collected, except for ordinals which are only resolved to their terms
+/// once the leaf is finished.
+enum CardinalityValues {
+    Numeric(NumericValuesReader),
+    SortedNumeric(SortedNumericValuesReader),
+    /// the values with the buffer they are read into
+    Binary(BinaryDocValuesRef, Vec<u8>),
+    Sorted(SortedDocValuesRef, FixedBitSet),
+    SortedSet(SortedSetDocValuesRef, FixedBitSet),
+    /// the field has no doc values in this leaf
+    Missing,
+}
+
+impl CardinalityValues {
+    fn new<C: Codec>(reader: &LeafReaderContext<'_, C>, field: &str) -> Result<Self> {
+        let leaf = reader.reader;
+        let values = match leaf.field_info(field).map(|info| info.doc_values_type) {
+            Some(DocValuesType::Numeric) => {
+                CardinalityValues::Numeric(NumericValuesReader::new(leaf, field)?)
+            }
+            Some(DocValuesType::SortedNumeric) => {
+                CardinalityValues::SortedNumeric(SortedNumericValuesReader::new(leaf, field)?)
+            }
+            Some(DocValuesType::Binary) => {
+                CardinalityValues::Binary(leaf.get_binary_doc_values(field)?, vec![])
+            }
+            Some(DocValuesType::Sorted) => {
+                let values = leaf.get_sorted_doc_values(field)?;
+                let ords = FixedBitSet::new(values.get_value_count().max(1));
+                CardinalityValues::Sorted(values, ords)
+            }
+            Some(DocValuesType::SortedSet) => {
+                let values = leaf.get_sorted_set_doc_values(field)?;
+                let ords = FixedBitSet::new(values.get_value_count().max(1));
+                CardinalityValues::SortedSet(values, ords)
+            }
+            None | Some(DocValuesType::Null) => CardinalityValues::Missing,
+        };
+        Ok(values)
+    }
+}
+
+/// The per leaf state of a cardinality estimation.
+struct CardinalityAggregator {
+    field: String,
+    values: CardinalityValues,
+    sketch: HyperLogLogPlusPlus,
+}
+
+impl CardinalityAggregator {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.finish_leaf()?;
+        self.values = CardinalityValues::new(reader, &self.field)?;
+        Ok(())
+    }
+
+    fn collect(&mut self, doc: DocId) -> Result<()> {
+        match self.values {
+            CardinalityValues::Numeric(ref mut values) => {
+                if let Some(v) = values.read(doc)? {
+                    self.sketch.add_i64(v);
+                }
+            }
+            CardinalityValues::SortedNumeric(ref mut values) => {
+                if let Some(vs) = values.read(doc)? {
+                    for v in vs {
+                        self.sketch.add_i64(v);
+                    }
+                }
+            }
+            CardinalityValues::Binary(ref values, ref mut value) => {
+                values.get_into(doc, value)?;
+                if !value.is_empty() {
+                    self.sketch.add_bytes(value);
+                }
+            }
+            CardinalityValues::Sorted(ref values, ref mut ords) => {
+                let ord = values.get_ord(doc)?;
+                if ord >= 0 {
+                    ords.set(ord as usize);
+                }
+            }
+            CardinalityValues::SortedSet(ref values, ref mut ords) => {
+                let mut ctx = values.set_document(doc)?;
+                loop {
+                    let ord = values.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    ords.set(ord as usize);
+                }
+            }
+            CardinalityValues::Missing => {}
+        }
+        Ok(())
+    }
+
+    /// Adds the terms of the ordinals seen in the current leaf to the sketch,
+    /// so every term is looked up and hashed once per leaf.
+    fn finish_leaf(&mut self) -> Result<()> {
+        let sketch = &mut self.sketch;
+        match mem::replace(&mut self.values, CardinalityValues::Missing) {
+            CardinalityValues::Sorted(values, ords) => for_each_ord(&ords, |ord| {
+                sketch.add_bytes(&values.lookup_ord(ord as i32)?);
+                Ok(())
+            }),
+            CardinalityValues::SortedSet(values, ords) => for_each_ord(&ords, |ord| {
+                sketch.add_bytes(&values.lookup_ord(ord as i64)?);
+                Ok(())
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn for_each_ord<F: FnMut(usize) -> Result<()>>(ords: &FixedBitSet, mut f: F) -> Result<()> {
+    let len = ords.len();
+    let mut ord = 0;
+    while ord < len {
+        let next = ords.next_set_bit(ord);
+        if next == NO_MORE_DOCS {
+            break;
+        }
+        f(next as usize)?;
+        ord = next as usize + 1;
+    }
+    Ok(())
+}
+
+/// A collector estimating the number of distinct values of a doc values
+/// field over the matching docs, with a HyperLogLog++ sketch.
+///
+/// Numeric, binary, sorted and sorted set doc values are supported. The
+/// sketches of the leaves are merged, so the collector can be used under the
+/// parallel search path too.
+pub struct CardinalityCollector {
+    aggregator: CardinalityAggregator,
+}
+
+impl CardinalityCollector {
+    /// Creates a collector over `field` whose sketch uses `2^precision` bytes,
+    /// see `HyperLogLogPlusPlus`.
+    pub fn new(field: &str, precision: u8) -> Result<CardinalityCollector> {
+        Ok(CardinalityCollector {
+            aggregator: CardinalityAggregator {
+                field: field.to_string(),
+                values: CardinalityValues::Missing,
+                sketch: HyperLogLogPlusPlus::new(precision)?,
+            },
+        })
+    }
+
+    /// The estimated number of distinct values.
+    pub fn cardinality(&mut self) -> Result<u64> {
+        self.aggregator.finish_leaf()?;
+        Ok(self.aggregator.sketch.cardinality())
+    }
+
+    /// The sketch of all the collected values, which can be merged with the
+    /// sketches of other searches, e.g. on other shards.
+    pub fn sketch(&mut self) -> Result<&HyperLogLogPlusPlus> {
+        self.aggregator.finish_leaf()?;
+        Ok(&self.aggregator.sketch)
+    }
+}
+
+impl SearchCollector for CardinalityCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.aggregator.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for CardinalityCollector {
+    type Collector = CardinalityLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<CardinalityLeafCollector> {
+        let mut aggregator = CardinalityAggregator {
+            field: self.aggregator.field.clone(),
+            values: CardinalityValues::Missing,
+            sketch: HyperLogLogPlusPlus::new(self.aggregator.sketch.precision())?,
+        };
+        aggregator.set_next_reader(reader)?;
+        Ok(CardinalityLeafCollector { aggregator })
+    }
+
+    fn reduce(&mut self, collectors: Vec<CardinalityLeafCollector>) -> Result<()> {
+        for mut c in collectors {
+            c.aggregator.finish_leaf()?;
+            self.aggregator.sketch.merge(&c.aggregator.sketch)?;
+        }
+        Ok(())
+    }
+}
+
+impl Collector for CardinalityCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+pub struct CardinalityLeafCollector {
+    aggregator: CardinalityAggregator,
+}
+
+impl Collector for CardinalityLeafCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+impl ParallelLeafCollector for CardinalityLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        self.aggregator.finish_leaf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{NumericDocValuesField, SortedSetDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexReader, IndexWriter};
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+    use std::sync::Arc;
+
+    const PRECISION: u8 = 10;
+
+    #[test]
+    fn test_cardinality_across_segments() {
+        let path = ::std::env::temp_dir().join("rucene_cardinality_collector");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // the values of the segments overlap, 5000 distinct numbers and 50
+        // distinct tags in total
+        for segment in 0..3 {
+            for value in segment * 1500..segment * 1500 + 2000 {
+                let fields: Vec<Box<dyn Fieldable>> = vec![
+                    Box::new(NumericDocValuesField::new("n", value)),
+                    Box::new(SortedSetDocValuesField::new(
+                        "tag",
+                        format!("t{}", value % 50).as_bytes(),
+                    )),
+                ];
+                writer.add_document(fields).unwrap();
+            }
+            writer.commit().unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 3);
+        let searcher = DefaultIndexSearcher::new(&reader);
+
+        let mut collector = CardinalityCollector::new("n", PRECISION).unwrap();
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        let mut one_pass = HyperLogLogPlusPlus::new(PRECISION).unwrap();
+        for value in 0..5000 {
+            one_pass.add_i64(value);
+        }
+        // 5000 values don't fit the sparse representation of 1024 registers
+        assert!(!collector.sketch().unwrap().is_sparse());
+        let cardinality = collector.cardinality().unwrap();
+        assert_eq!(cardinality, one_pass.cardinality());
+        // about 3 times the standard error of `1.04 / sqrt(1024)`
+        assert!((cardinality as f64 - 5000.0).abs() / 5000.0 < 0.1);
+
+        // few distinct terms stay sparse, and are counted exactly
+        let mut collector = CardinalityCollector::new("tag", PRECISION).unwrap();
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        assert!(collector.sketch().unwrap().is_sparse());
+        assert_eq!(collector.cardinality().unwrap(), 50);
+
+        let mut collector = CardinalityCollector::new("unknown", PRECISION).unwrap();
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        assert_eq!(collector.cardinality().unwrap(), 0);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
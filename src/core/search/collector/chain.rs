@@ -13,7 +13,9 @@
 
 use core::codec::Codec;
 use core::index::LeafReaderContext;
-use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
 use core::search::Scorer;
 use core::util::DocId;
 use error::Result;
@@ -36,8 +38,6 @@ where
     A: SearchCollector,
     B: SearchCollector,
 {
-    type LC = ChainedCollector<A::LC, B::LC>;
-
     fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
         self.first.set_next_reader(reader)?;
         self.second.set_next_reader(reader)
@@ -46,21 +46,35 @@ where
     fn support_parallel(&self) -> bool {
         self.first.support_parallel() && self.second.support_parallel()
     }
+}
 
-    fn leaf_collector<C: Codec>(
+impl<A, B> CollectorManager for ChainedCollector<A, B>
+where
+    A: CollectorManager,
+    B: CollectorManager,
+{
+    type Collector = ChainedCollector<A::Collector, B::Collector>;
+
+    fn new_collector<C: Codec>(
         &mut self,
         reader: &LeafReaderContext<'_, C>,
-    ) -> Result<ChainedCollector<A::LC, B::LC>> {
+    ) -> Result<Self::Collector> {
         Ok(ChainedCollector {
-            first: self.first.leaf_collector(reader)?,
-            second: self.second.leaf_collector(reader)?,
+            first: self.first.new_collector(reader)?,
+            second: self.second.new_collector(reader)?,
         })
     }
 
-    fn finish_parallel(&mut self) -> Result<()> {
+    fn reduce(&mut self, collectors: Vec<Self::Collector>) -> Result<()> {
+        let mut firsts = Vec::with_capacity(collectors.len());
+        let mut seconds = Vec::with_capacity(collectors.len());
+        for c in collectors {
+            firsts.push(c.first);
+            seconds.push(c.second);
+        }
         // reverse order for finish
-        self.second.finish_parallel()?;
-        self.first.finish_parallel()
+        self.second.reduce(seconds)?;
+        self.first.reduce(firsts)
     }
 }
 
@@ -73,6 +87,10 @@ where
         self.first.needs_scores() || self.second.needs_scores()
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        self.first.accepts_docs_out_of_order() && self.second.accepts_docs_out_of_order()
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
         self.first.collect(doc, scorer)?;
         self.second.collect(doc, scorer)
@@ -14,7 +14,9 @@
 use core::codec::Codec;
 use core::index::LeafReaderContext;
 use core::search::collector;
-use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
 use core::search::Scorer;
 use core::util::DocId;
 use error::{ErrorKind, Result};
@@ -46,7 +48,6 @@ impl EarlyTerminatingSortingCollector {
 }
 
 impl SearchCollector for EarlyTerminatingSortingCollector {
-    type LC = EarlyTerminatingLeafCollector;
     fn set_next_reader<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<()> {
         self.num_docs_collected_per_reader = 0;
         Ok(())
@@ -55,16 +56,22 @@ impl SearchCollector for EarlyTerminatingSortingCollector {
     fn support_parallel(&self) -> bool {
         true
     }
+}
+
+impl CollectorManager for EarlyTerminatingSortingCollector {
+    type Collector = EarlyTerminatingLeafCollector;
 
-    fn leaf_collector<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
-        assert!(self.support_parallel());
+    fn new_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<EarlyTerminatingLeafCollector> {
         Ok(EarlyTerminatingLeafCollector::new(
             self.num_docs_to_collect_per_reader,
             Arc::clone(&self.early_terminated),
         ))
     }
 
-    fn finish_parallel(&mut self) -> Result<()> {
+    fn reduce(&mut self, _collectors: Vec<EarlyTerminatingLeafCollector>) -> Result<()> {
         Ok(())
     }
 }
@@ -0,0 +1,293 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, SortedNumericDocValuesRef};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::Scorer;
+use core::util::geo;
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::f64;
+use std::sync::Arc;
+
+/// A bucket of a geo distance aggregation, holding the docs with a point at
+/// a distance in `[from, to)` meters of the origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeoDistanceBucket {
+    pub key: String,
+    pub from: f64,
+    pub to: f64,
+    pub doc_count: u64,
+}
+
+#[derive(Clone, Debug)]
+struct GeoDistanceRange {
+    key: String,
+    from: f64,
+    to: f64,
+}
+
+impl GeoDistanceRange {
+    fn contains(&self, distance: f64) -> bool {
+        distance >= self.from && distance < self.to
+    }
+}
+
+#[derive(Clone, Debug)]
+struct GeoDistanceConfig {
+    field: String,
+    latitude: f64,
+    longitude: f64,
+    ranges: Vec<GeoDistanceRange>,
+}
+
+/// The per leaf state of a geo distance aggregation.
+struct GeoDistanceAggregator {
+    config: Arc<GeoDistanceConfig>,
+    values: Option<SortedNumericDocValuesRef>,
+    counts: Vec<u64>,
+    distances: Vec<f64>,
+}
+
+impl GeoDistanceAggregator {
+    fn new(config: Arc<GeoDistanceConfig>) -> GeoDistanceAggregator {
+        let counts = vec![0; config.ranges.len()];
+        GeoDistanceAggregator {
+            config,
+            values: None,
+            counts,
+            distances: vec![],
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        let field = &self.config.field;
+        self.values = match reader.reader.field_info(field) {
+            Some(info) if info.doc_values_type == DocValuesType::SortedNumeric => {
+                Some(reader.reader.get_sorted_numeric_doc_values(field)?)
+            }
+            Some(info) if info.doc_values_type != DocValuesType::Null => {
+                bail!(IllegalArgument(format!(
+                    "field {} has no LatLon doc values, got {:?}",
+                    field, info.doc_values_type
+                )));
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+
+    fn collect(&mut self, doc: DocId) -> Result<()> {
+        let values = match self.values {
+            Some(ref values) => values,
+            None => return Ok(()),
+        };
+        let ctx = values.set_document(None, doc)?;
+        self.distances.clear();
+        for i in 0..values.count(&ctx) {
+            let (lat, lon) = geo::decode_point(values.value_at(&ctx, i)?);
+            self.distances.push(geo::haversin_meters(
+                self.config.latitude,
+                self.config.longitude,
+                lat,
+                lon,
+            ));
+        }
+        // a doc with several points is counted once per range
+        for (i, range) in self.config.ranges.iter().enumerate() {
+            if self.distances.iter().any(|&d| range.contains(d)) {
+                self.counts[i] += 1;
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, counts: &[u64]) {
+        for (count, other) in self.counts.iter_mut().zip(counts) {
+            *count += *other;
+        }
+    }
+}
+
+/// A collector bucketing the matching docs by their distance to an origin,
+/// over the points of a `LatLonDocValuesField`, into ranges of distances in
+/// meters, e.g. 0 to 1km, 1km to 5km and more than 5km.
+///
+/// The ranges are `[from, to)` and may overlap, a doc is counted in each
+/// range one of its points falls in. Docs without a point are ignored.
+pub struct GeoDistanceCollector {
+    aggregator: GeoDistanceAggregator,
+}
+
+impl GeoDistanceCollector {
+    /// Creates an aggregation over the points of `field` by their distance
+    /// to `(latitude, longitude)`, with no range yet, see `add_range`.
+    pub fn new(field: &str, latitude: f64, longitude: f64) -> Result<Self> {
+        geo::check_latitude(latitude)?;
+        geo::check_longitude(longitude)?;
+        let config = GeoDistanceConfig {
+            field: field.to_string(),
+            latitude,
+            longitude,
+            ranges: vec![],
+        };
+        Ok(GeoDistanceCollector {
+            aggregator: GeoDistanceAggregator::new(Arc::new(config)),
+        })
+    }
+
+    /// Adds the range of distances `[from, to)` in meters, `to` may be
+    /// `f64::INFINITY` for an unbounded range. The bucket is keyed by
+    /// `key`, or `"from-to"` by default with `*` for an unbounded end.
+    ///
+    /// The ranges must be set before the search.
+    pub fn add_range(&mut self, key: Option<&str>, from: f64, to: f64) -> Result<()> {
+        if !(from >= 0.0 && from.is_finite() && to > from) {
+            bail!(IllegalArgument(format!(
+                "invalid distance range [{}, {})",
+                from, to
+            )));
+        }
+        let key = match key {
+            Some(key) => key.to_string(),
+            None if to.is_finite() => format!("{}-{}", from, to),
+            None => format!("{}-*", from),
+        };
+        Arc::make_mut(&mut self.aggregator.config)
+            .ranges
+            .push(GeoDistanceRange { key, from, to });
+        self.aggregator.counts.push(0);
+        Ok(())
+    }
+
+    /// The buckets, in the order their ranges were added.
+    pub fn buckets(&self) -> Vec<GeoDistanceBucket> {
+        self.aggregator
+            .config
+            .ranges
+            .iter()
+            .zip(&self.aggregator.counts)
+            .map(|(range, &doc_count)| GeoDistanceBucket {
+                key: range.key.clone(),
+                from: range.from,
+                to: range.to,
+                doc_count,
+            })
+            .collect()
+    }
+}
+
+impl SearchCollector for GeoDistanceCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.aggregator.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for GeoDistanceCollector {
+    type Collector = GeoDistanceLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<GeoDistanceLeafCollector> {
+        let mut aggregator = GeoDistanceAggregator::new(Arc::clone(&self.aggregator.config));
+        aggregator.set_next_reader(reader)?;
+        Ok(GeoDistanceLeafCollector { aggregator })
+    }
+
+    fn reduce(&mut self, collectors: Vec<GeoDistanceLeafCollector>) -> Result<()> {
+        for c in collectors {
+            self.aggregator.merge(&c.aggregator.counts);
+        }
+        Ok(())
+    }
+}
+
+impl Collector for GeoDistanceCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+pub struct GeoDistanceLeafCollector {
+    aggregator: GeoDistanceAggregator,
+}
+
+impl Collector for GeoDistanceLeafCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+impl ParallelLeafCollector for GeoDistanceLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranges() {
+        assert!(GeoDistanceCollector::new("p", 91.0, 0.0).is_err());
+        let mut collector = GeoDistanceCollector::new("p", 48.8566, 2.3522).unwrap();
+        assert!(collector.add_range(None, 1000.0, 1000.0).is_err());
+        assert!(collector.add_range(None, -1.0, 1000.0).is_err());
+        collector.add_range(None, 0.0, 1000.0).unwrap();
+        collector.add_range(Some("near"), 1000.0, 5000.0).unwrap();
+        collector.add_range(None, 5000.0, f64::INFINITY).unwrap();
+
+        let mut leaf = GeoDistanceAggregator::new(Arc::clone(&collector.aggregator.config));
+        leaf.counts[0] = 2;
+        leaf.counts[2] = 1;
+        collector.aggregator.merge(&leaf.counts);
+        collector.aggregator.merge(&leaf.counts);
+
+        let buckets = collector.buckets();
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets[0].key, "0-1000");
+        assert_eq!(buckets[0].doc_count, 4);
+        assert_eq!(buckets[1].key, "near");
+        assert_eq!(buckets[1].doc_count, 0);
+        assert_eq!(buckets[2].key, "5000-*");
+        assert_eq!(buckets[2].doc_count, 2);
+        assert!(collector.aggregator.config.ranges[1].contains(1000.0));
+        assert!(!collector.aggregator.config.ranges[1].contains(5000.0));
+    }
+}
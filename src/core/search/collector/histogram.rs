@@ -0,0 +1,497 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{
+    DocValuesReader, DocValuesType, LeafReaderContext, NumericValuesReader,
+    SortedNumericValuesReader,
+};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::double_values::numeric_to_double;
+use core::search::sort_field::SortFieldType;
+use core::search::Scorer;
+use core::util::numeric::{sortable_double_bits, sortable_float_bits};
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::collections::BTreeMap;
+use std::f64;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The values of a numeric or sorted numeric doc values field of a leaf,
+/// converted to doubles according to the type of the field.
+pub enum NumericFieldValues {
+    Numeric(NumericValuesReader, SortFieldType),
+    SortedNumeric(SortedNumericValuesReader, SortFieldType),
+    /// the field has no doc values in this leaf
+    Missing,
+}
+
+impl NumericFieldValues {
+    pub fn new<C: Codec>(
+        reader: &LeafReaderContext<'_, C>,
+        field: &str,
+        field_type: SortFieldType,
+    ) -> Result<NumericFieldValues> {
+        let leaf = reader.reader;
+        let values = match leaf.field_info(field).map(|info| info.doc_values_type) {
+            Some(DocValuesType::Numeric) => {
+                NumericFieldValues::Numeric(NumericValuesReader::new(leaf, field)?, field_type)
+            }
+            Some(DocValuesType::SortedNumeric) => NumericFieldValues::SortedNumeric(
+                SortedNumericValuesReader::new(leaf, field)?,
+                field_type,
+            ),
+            None | Some(DocValuesType::Null) => NumericFieldValues::Missing,
+            Some(t) => bail!(IllegalArgument(format!(
+                "field {} has non-numeric doc values {:?}",
+                field, t
+            ))),
+        };
+        Ok(values)
+    }
+
+    /// Replaces the content of `values` with the values of `doc`, in
+    /// increasing order.
+    pub fn read(&mut self, doc: DocId, values: &mut Vec<f64>) -> Result<()> {
+        values.clear();
+        match self {
+            NumericFieldValues::Numeric(reader, field_type) => {
+                if let Some(v) = reader.read(doc)? {
+                    values.push(numeric_to_double(v, *field_type));
+                }
+            }
+            NumericFieldValues::SortedNumeric(reader, field_type) => {
+                if let Some(raw) = reader.read(doc)? {
+                    // sorted numeric doc values encode floats and doubles as
+                    // sortable bits
+                    values.extend(raw.into_iter().map(|v| match field_type {
+                        SortFieldType::Float => {
+                            numeric_to_double(i64::from(sortable_float_bits(v as i32)), *field_type)
+                        }
+                        SortFieldType::Double => {
+                            numeric_to_double(sortable_double_bits(v), *field_type)
+                        }
+                        _ => numeric_to_double(v, *field_type),
+                    }));
+                }
+            }
+            NumericFieldValues::Missing => {}
+        }
+        Ok(())
+    }
+}
+
+/// Statistics of the values of a metric field over the docs of a bucket.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MetricStats {
+    pub count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for MetricStats {
+    fn default() -> Self {
+        MetricStats {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl MetricStats {
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn merge(&mut self, other: &MetricStats) {
+        self.count += other.count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// The average of the values, None if there is no value.
+    pub fn avg(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.sum / self.count as f64)
+        }
+    }
+}
+
+/// A bucket of a histogram, holding the docs whose value is in
+/// `[key, key + interval)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramBucket {
+    pub key: f64,
+    pub doc_count: u64,
+    /// the statistics of the metric field, if one was set
+    pub metric: Option<MetricStats>,
+}
+
+#[derive(Default)]
+struct BucketState {
+    doc_count: u64,
+    metric: MetricStats,
+}
+
+#[derive(Clone, Debug)]
+struct HistogramConfig {
+    field: String,
+    field_type: SortFieldType,
+    interval: f64,
+    offset: f64,
+    min_doc_count: u64,
+    metric: Option<(String, SortFieldType)>,
+}
+
+impl HistogramConfig {
+    fn bucket_index(&self, value: f64) -> i64 {
+        ((value - self.offset) / self.interval).floor() as i64
+    }
+
+    fn bucket_key(&self, index: i64) -> f64 {
+        index as f64 * self.interval + self.offset
+    }
+}
+
+/// The per leaf state of a histogram.
+struct HistogramAggregator {
+    config: Arc<HistogramConfig>,
+    values: NumericFieldValues,
+    metric_values: Option<NumericFieldValues>,
+    buckets: BTreeMap<i64, BucketState>,
+    doc_values: Vec<f64>,
+    doc_metric_values: Vec<f64>,
+}
+
+impl HistogramAggregator {
+    fn new(config: Arc<HistogramConfig>) -> HistogramAggregator {
+        HistogramAggregator {
+            config,
+            values: NumericFieldValues::Missing,
+            metric_values: None,
+            buckets: BTreeMap::new(),
+            doc_values: vec![],
+            doc_metric_values: vec![],
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.values = NumericFieldValues::new(reader, &self.config.field, self.config.field_type)?;
+        self.metric_values = match self.config.metric {
+            Some((ref field, field_type)) => {
+                Some(NumericFieldValues::new(reader, field, field_type)?)
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    fn collect(&mut self, doc: DocId) -> Result<()> {
+        self.values.read(doc, &mut self.doc_values)?;
+        if self.doc_values.is_empty() {
+            return Ok(());
+        }
+        if let Some(ref mut metric_values) = self.metric_values {
+            metric_values.read(doc, &mut self.doc_metric_values)?;
+        }
+
+        // values are sorted, so a doc falling several times in the same
+        // bucket is only counted once
+        let mut last_index = None;
+        for &value in &self.doc_values {
+            if value.is_nan() {
+                continue;
+            }
+            let index = self.config.bucket_index(value);
+            if last_index == Some(index) {
+                continue;
+            }
+            last_index = Some(index);
+            let bucket = self
+                .buckets
+                .entry(index)
+                .or_insert_with(BucketState::default);
+            bucket.doc_count += 1;
+            for &v in &self.doc_metric_values {
+                bucket.metric.add(v);
+            }
+        }
+        Ok(())
+    }
+
+    fn merge(&mut self, buckets: BTreeMap<i64, BucketState>) {
+        for (index, other) in buckets {
+            let bucket = self
+                .buckets
+                .entry(index)
+                .or_insert_with(BucketState::default);
+            bucket.doc_count += other.doc_count;
+            bucket.metric.merge(&other.metric);
+        }
+    }
+}
+
+/// A collector bucketing the matching docs by the value of a numeric doc
+/// values field, with buckets of a fixed `interval`.
+///
+/// The bucket of a value `v` is keyed by `floor((v - offset) / interval) *
+/// interval + offset`. Docs without a value are ignored, and a multi-valued
+/// doc is counted once in each bucket its values fall in. Optionally the
+/// statistics (count, sum, min, max and average) of another numeric field are
+/// computed per bucket during the same pass.
+pub struct HistogramCollector {
+    aggregator: HistogramAggregator,
+}
+
+impl HistogramCollector {
+    /// Creates a histogram over `field` whose doc values are of `field_type`.
+    pub fn new(field: &str, field_type: SortFieldType, interval: f64) -> Result<Self> {
+        match field_type {
+            SortFieldType::Int
+            | SortFieldType::Long
+            | SortFieldType::Float
+            | SortFieldType::Double => {}
+            _ => bail!(IllegalArgument(format!(
+                "field {} must have a numeric type, got {:?}",
+                field, field_type
+            ))),
+        }
+        if !(interval > 0.0 && interval.is_finite()) {
+            bail!(IllegalArgument(format!(
+                "interval must be a positive number, got {}",
+                interval
+            )));
+        }
+        let config = HistogramConfig {
+            field: field.to_string(),
+            field_type,
+            interval,
+            offset: 0.0,
+            min_doc_count: 1,
+            metric: None,
+        };
+        Ok(HistogramCollector {
+            aggregator: HistogramAggregator::new(Arc::new(config)),
+        })
+    }
+
+    /// Creates a date histogram over `field`, whose doc values are timestamps
+    /// in milliseconds, with buckets of a fixed `interval`.
+    pub fn date_histogram(field: &str, interval: Duration) -> Result<Self> {
+        let millis = interval.as_secs() as f64 * 1000.0 + f64::from(interval.subsec_millis());
+        Self::new(field, SortFieldType::Long, millis)
+    }
+
+    fn config_mut(&mut self) -> &mut HistogramConfig {
+        Arc::make_mut(&mut self.aggregator.config)
+    }
+
+    /// Shifts the bucket boundaries by `offset`, which is useful to align
+    /// date buckets on a time zone.
+    pub fn set_offset(&mut self, offset: f64) {
+        self.config_mut().offset = offset;
+    }
+
+    /// Buckets with less than `min_doc_count` docs are not returned, the
+    /// default is 1. With 0, empty buckets between the first and the last
+    /// non-empty buckets are returned too.
+    pub fn set_min_doc_count(&mut self, min_doc_count: u64) {
+        self.config_mut().min_doc_count = min_doc_count;
+    }
+
+    /// Computes the statistics of the numeric doc values of `field` per bucket.
+    pub fn set_metric(&mut self, field: &str, field_type: SortFieldType) {
+        self.config_mut().metric = Some((field.to_string(), field_type));
+    }
+
+    pub fn interval(&self) -> f64 {
+        self.aggregator.config.interval
+    }
+
+    /// The buckets, by increasing key.
+    pub fn buckets(&self) -> Vec<HistogramBucket> {
+        let config = &self.aggregator.config;
+        let has_metric = config.metric.is_some();
+        let to_bucket = |index: i64, state: Option<&BucketState>| HistogramBucket {
+            key: config.bucket_key(index),
+            doc_count: state.map_or(0, |s| s.doc_count),
+            metric: if has_metric {
+                Some(state.map_or_else(MetricStats::default, |s| s.metric))
+            } else {
+                None
+            },
+        };
+
+        let buckets = &self.aggregator.buckets;
+        if config.min_doc_count == 0 {
+            match (buckets.keys().next(), buckets.keys().next_back()) {
+                (Some(&first), Some(&last)) => (first..=last)
+                    .map(|index| to_bucket(index, buckets.get(&index)))
+                    .collect(),
+                _ => vec![],
+            }
+        } else {
+            buckets
+                .iter()
+                .filter(|(_, state)| state.doc_count >= config.min_doc_count)
+                .map(|(&index, state)| to_bucket(index, Some(state)))
+                .collect()
+        }
+    }
+}
+
+impl SearchCollector for HistogramCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.aggregator.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for HistogramCollector {
+    type Collector = HistogramLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<HistogramLeafCollector> {
+        let mut aggregator = HistogramAggregator::new(Arc::clone(&self.aggregator.config));
+        aggregator.set_next_reader(reader)?;
+        Ok(HistogramLeafCollector { aggregator })
+    }
+
+    fn reduce(&mut self, collectors: Vec<HistogramLeafCollector>) -> Result<()> {
+        for c in collectors {
+            self.aggregator.merge(c.aggregator.buckets);
+        }
+        Ok(())
+    }
+}
+
+impl Collector for HistogramCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+pub struct HistogramLeafCollector {
+    aggregator: HistogramAggregator,
+}
+
+impl Collector for HistogramLeafCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+impl ParallelLeafCollector for HistogramLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(interval: f64, offset: f64) -> HistogramConfig {
+        HistogramConfig {
+            field: "f".into(),
+            field_type: SortFieldType::Long,
+            interval,
+            offset,
+            min_doc_count: 1,
+            metric: None,
+        }
+    }
+
+    #[test]
+    fn test_bucket_keys() {
+        let c = config(10.0, 0.0);
+        assert_eq!(c.bucket_key(c.bucket_index(0.0)), 0.0);
+        assert_eq!(c.bucket_key(c.bucket_index(9.9)), 0.0);
+        assert_eq!(c.bucket_key(c.bucket_index(10.0)), 10.0);
+        assert_eq!(c.bucket_key(c.bucket_index(-0.5)), -10.0);
+
+        let c = config(10.0, 3.0);
+        assert_eq!(c.bucket_key(c.bucket_index(2.0)), -7.0);
+        assert_eq!(c.bucket_key(c.bucket_index(3.0)), 3.0);
+        assert_eq!(c.bucket_key(c.bucket_index(12.9)), 3.0);
+    }
+
+    #[test]
+    fn test_buckets() {
+        let mut collector = HistogramCollector::new("f", SortFieldType::Long, 5.0).unwrap();
+        assert!(HistogramCollector::new("f", SortFieldType::Long, 0.0).is_err());
+        collector.set_metric("m", SortFieldType::Long);
+
+        let mut leaf = BTreeMap::new();
+        leaf.insert(
+            0,
+            BucketState {
+                doc_count: 2,
+                metric: MetricStats::default(),
+            },
+        );
+        leaf.insert(3, BucketState::default());
+        leaf.get_mut(&0).unwrap().metric.add(4.0);
+        leaf.get_mut(&0).unwrap().metric.add(2.0);
+        leaf.get_mut(&3).unwrap().doc_count = 1;
+        collector.aggregator.merge(leaf);
+
+        let buckets = collector.buckets();
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].key, 0.0);
+        assert_eq!(buckets[0].doc_count, 2);
+        assert_eq!(buckets[0].metric.unwrap().avg(), Some(3.0));
+        assert_eq!(buckets[1].key, 15.0);
+        assert_eq!(buckets[1].metric.unwrap().avg(), None);
+
+        collector.set_min_doc_count(0);
+        let buckets = collector.buckets();
+        assert_eq!(buckets.len(), 4);
+        assert_eq!(buckets[1].key, 5.0);
+        assert_eq!(buckets[1].doc_count, 0);
+    }
+}
@@ -21,6 +21,9 @@ use core::util::DocId;
 pub mod top_docs;
 pub use self::top_docs::TopDocsCollector;
 
+mod top_field;
+pub use self::top_field::{TopFieldCollector, TopFieldLeafCollector};
+
 mod early_terminating;
 pub use self::early_terminating::EarlyTerminatingSortingCollector;
 
@@ -30,6 +33,34 @@ pub use self::timeout::TimeoutCollector;
 mod chain;
 pub use self::chain::ChainedCollector;
 
+mod histogram;
+pub use self::histogram::{
+    HistogramBucket, HistogramCollector, HistogramLeafCollector, MetricStats, NumericFieldValues,
+};
+
+mod cardinality;
+pub use self::cardinality::{CardinalityCollector, CardinalityLeafCollector};
+
+mod stats;
+pub use self::stats::{StatsCollector, StatsLeafCollector};
+
+mod terms;
+pub use self::terms::{
+    TermBucket, TermsCollector, TermsLeafCollector, DEFAULT_MAX_BUCKETS_IN_MEMORY,
+};
+
+mod sampling;
+pub use self::sampling::{
+    ReservoirSamplingCollector, ReservoirSamplingLeafCollector, SamplingCollector,
+    SamplingLeafCollector,
+};
+
+mod score_breakdown;
+pub use self::score_breakdown::ScoreBreakdownCollector;
+
+mod geo_distance;
+pub use self::geo_distance::{GeoDistanceBucket, GeoDistanceCollector, GeoDistanceLeafCollector};
+
 error_chain! {
     types {
         Error, ErrorKind, ResultExt;
@@ -66,23 +97,37 @@ error_chain! {
 /// Not all collectors will need to rebase the docID.  For
 /// example, a collector that simply counts the total number
 /// of hits would skip it.
-pub trait SearchCollector: Collector {
-    type LC: ParallelLeafCollector;
+///
+/// For parallel search, every `SearchCollector` is also a `CollectorManager`
+/// which creates one leaf collector per segment and reduces them afterwards.
+pub trait SearchCollector: Collector + CollectorManager {
     /// This method is called before collecting on a new leaf.
     fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()>;
 
     /// iff this collector support parallel collect
     fn support_parallel(&self) -> bool;
 
-    /// segment collector for parallel search
-    fn leaf_collector<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<Self::LC>;
+    /// Whether this collector records the partial score of each top level
+    /// clause of the query, the searcher then calls `set_clause_scorers`
+    /// after each `set_next_reader` and collects the docs in order.
+    fn needs_score_breakdown(&self) -> bool {
+        false
+    }
 
-    fn finish_parallel(&mut self) -> Result<()>;
+    /// Called before collecting on a new leaf with the scorers of the
+    /// scoring clauses of the query for the leaf, None for the clauses
+    /// matching no doc of the leaf. `clauses` are the descriptions of the
+    /// clauses, the same for all the leaves.
+    fn set_clause_scorers(
+        &mut self,
+        _clauses: &[String],
+        _scorers: Vec<Option<Box<dyn Scorer>>>,
+    ) -> Result<()> {
+        Ok(())
+    }
 }
 
 impl<'a, T: SearchCollector + 'a> SearchCollector for &'a mut T {
-    type LC = T::LC;
-
     fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
         (**self).set_next_reader(reader)
     }
@@ -91,12 +136,53 @@ impl<'a, T: SearchCollector + 'a> SearchCollector for &'a mut T {
         (**self).support_parallel()
     }
 
-    fn leaf_collector<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<Self::LC> {
-        (**self).leaf_collector(reader)
+    fn needs_score_breakdown(&self) -> bool {
+        (**self).needs_score_breakdown()
+    }
+
+    fn set_clause_scorers(
+        &mut self,
+        clauses: &[String],
+        scorers: Vec<Option<Box<dyn Scorer>>>,
+    ) -> Result<()> {
+        (**self).set_clause_scorers(clauses, scorers)
+    }
+}
+
+/// A manager of collectors for parallel search, following a map-reduce contract:
+///
+/// * `new_collector` is called once per leaf (slice) to create an independent
+/// `ParallelLeafCollector`, which will be moved into a search thread.
+/// * `reduce` is called once all the leaves have been collected, with all the
+/// leaf collectors that were created, ordered by their leaf ord.
+///
+/// Leaf collectors keep their partial results in their own state, so no shared
+/// channel or lock is needed while collecting.
+pub trait CollectorManager {
+    type Collector: ParallelLeafCollector;
+
+    /// Returns a new leaf collector for the given leaf.
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Self::Collector>;
+
+    /// Reduce the results of the leaf collectors into this manager.
+    fn reduce(&mut self, collectors: Vec<Self::Collector>) -> Result<()>;
+}
+
+impl<'a, T: CollectorManager + 'a> CollectorManager for &'a mut T {
+    type Collector = T::Collector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Self::Collector> {
+        (**self).new_collector(reader)
     }
 
-    fn finish_parallel(&mut self) -> Result<()> {
-        (**self).finish_parallel()
+    fn reduce(&mut self, collectors: Vec<Self::Collector>) -> Result<()> {
+        (**self).reduce(collectors)
     }
 }
 
@@ -105,6 +191,21 @@ pub trait Collector {
     /// return `true` if scores are needed.
     fn needs_scores(&self) -> bool;
 
+    /// Return `true` if this collector does not require the matching docIDs to
+    /// be delivered in int sort order (smallest to largest) to `collect`.
+    ///
+    /// Most collectors only aggregate the collected docs and return `true`,
+    /// which allows the top level scorer to collect the docs of a leaf in any
+    /// order, e.g. bucket by bucket for disjunctions, see
+    /// `Weight::create_bulk_scorer`. Collectors which early terminate or keep
+    /// the first docs only must return `false`, which is the default.
+    ///
+    /// For parallel search, this is only asked to the `SearchCollector`, whose
+    /// leaf collectors must accept the same order.
+    fn accepts_docs_out_of_order(&self) -> bool {
+        false
+    }
+
     /// Called once for every document matching a query, with the unbased document
     /// number.
     /// Note: The collection of the current segment can be terminated by throwing
@@ -123,11 +224,16 @@ impl<'a, T: Collector + 'a> Collector for &'a mut T {
         (**self).needs_scores()
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        (**self).accepts_docs_out_of_order()
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, doc: i32, scorer: &mut S) -> Result<()> {
         (**self).collect(doc, scorer)
     }
 }
 
 pub trait ParallelLeafCollector: Collector + Send + 'static {
+    /// Called in the search thread once the leaf has been fully collected.
     fn finish_leaf(&mut self) -> Result<()>;
 }
@@ -0,0 +1,512 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::top_docs::ScoreDoc;
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, Result};
+
+use rand::rngs::SmallRng;
+use rand::{FromEntropy, Rng, SeedableRng};
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+fn new_rng(seed: Option<u64>) -> SmallRng {
+    match seed {
+        Some(seed) => SmallRng::seed_from_u64(seed),
+        None => SmallRng::from_entropy(),
+    }
+}
+
+/// Returns the number of docs to skip before the next sampled one, so that
+/// every doc is sampled independently with probability `rate`. The gaps
+/// between Bernoulli trials follow a geometric distribution, which avoids
+/// drawing a random number per matching doc.
+fn next_skip(rng: &mut SmallRng, rate: f64) -> u64 {
+    if rate >= 1.0 {
+        return 0;
+    }
+    // in (0, 1], so that the logarithm is finite
+    let u = 1.0 - rng.gen::<f64>();
+    let skip = (u.ln() / (1.0 - rate).ln()).floor();
+    if skip >= u64::max_value() as f64 {
+        u64::max_value()
+    } else {
+        skip as u64
+    }
+}
+
+/// A collector which forwards a uniformly random subset of the matching docs
+/// to the wrapped collector, each doc being kept independently with
+/// probability `rate`.
+///
+/// This lets aggregations over huge result sets trade accuracy for latency:
+/// counts computed by the wrapped collector can be extrapolated with
+/// `scale_factor`. Scores are only computed for the sampled docs.
+pub struct SamplingCollector<T> {
+    inner: T,
+    rate: f64,
+    rng: SmallRng,
+    skip: u64,
+    total_hits: usize,
+    sampled_hits: usize,
+}
+
+impl<T: SearchCollector> SamplingCollector<T> {
+    /// Creates a collector sampling docs with probability `rate`, which must be
+    /// in (0, 1].
+    pub fn new(inner: T, rate: f64) -> Result<SamplingCollector<T>> {
+        Self::create(inner, rate, None)
+    }
+
+    /// Same as `new` but the sample is reproducible for the given `seed`, as
+    /// long as the docs are collected in the same order.
+    pub fn with_seed(inner: T, rate: f64, seed: u64) -> Result<SamplingCollector<T>> {
+        Self::create(inner, rate, Some(seed))
+    }
+
+    fn create(inner: T, rate: f64, seed: Option<u64>) -> Result<SamplingCollector<T>> {
+        if !(rate > 0.0 && rate <= 1.0) {
+            bail!(IllegalArgument(format!(
+                "sampling rate must be in (0, 1], got {}",
+                rate
+            )));
+        }
+        let mut rng = new_rng(seed);
+        let skip = next_skip(&mut rng, rate);
+        Ok(SamplingCollector {
+            inner,
+            rate,
+            rng,
+            skip,
+            total_hits: 0,
+            sampled_hits: 0,
+        })
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// The number of docs which matched, sampled or not.
+    pub fn total_hits(&self) -> usize {
+        self.total_hits
+    }
+
+    /// The number of docs forwarded to the wrapped collector.
+    pub fn sampled_hits(&self) -> usize {
+        self.sampled_hits
+    }
+
+    /// The factor to multiply the counts of the wrapped collector with to
+    /// estimate the counts over all the matching docs.
+    pub fn scale_factor(&self) -> f64 {
+        if self.sampled_hits == 0 {
+            1.0
+        } else {
+            self.total_hits as f64 / self.sampled_hits as f64
+        }
+    }
+
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T: SearchCollector> SearchCollector for SamplingCollector<T> {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.inner.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        self.inner.support_parallel()
+    }
+}
+
+impl<T: SearchCollector> CollectorManager for SamplingCollector<T> {
+    type Collector = SamplingLeafCollector<T::Collector>;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<SamplingLeafCollector<T::Collector>> {
+        let inner = self.inner.new_collector(reader)?;
+        let mut rng = SmallRng::seed_from_u64(self.rng.gen());
+        let skip = next_skip(&mut rng, self.rate);
+        Ok(SamplingLeafCollector {
+            inner,
+            rate: self.rate,
+            rng,
+            skip,
+            total_hits: 0,
+            sampled_hits: 0,
+        })
+    }
+
+    fn reduce(&mut self, collectors: Vec<SamplingLeafCollector<T::Collector>>) -> Result<()> {
+        let mut inners = Vec::with_capacity(collectors.len());
+        for c in collectors {
+            self.total_hits += c.total_hits;
+            self.sampled_hits += c.sampled_hits;
+            inners.push(c.inner);
+        }
+        self.inner.reduce(inners)
+    }
+}
+
+impl<T: SearchCollector> Collector for SamplingCollector<T> {
+    fn needs_scores(&self) -> bool {
+        self.inner.needs_scores()
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        self.inner.accepts_docs_out_of_order()
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        if self.skip > 0 {
+            self.skip -= 1;
+            return Ok(());
+        }
+        self.sampled_hits += 1;
+        self.skip = next_skip(&mut self.rng, self.rate);
+        self.inner.collect(doc, scorer)
+    }
+}
+
+pub struct SamplingLeafCollector<T> {
+    inner: T,
+    rate: f64,
+    rng: SmallRng,
+    skip: u64,
+    total_hits: usize,
+    sampled_hits: usize,
+}
+
+impl<T: ParallelLeafCollector> Collector for SamplingLeafCollector<T> {
+    fn needs_scores(&self) -> bool {
+        self.inner.needs_scores()
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        self.inner.accepts_docs_out_of_order()
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        if self.skip > 0 {
+            self.skip -= 1;
+            return Ok(());
+        }
+        self.sampled_hits += 1;
+        self.skip = next_skip(&mut self.rng, self.rate);
+        self.inner.collect(doc, scorer)
+    }
+}
+
+impl<T: ParallelLeafCollector> ParallelLeafCollector for SamplingLeafCollector<T> {
+    fn finish_leaf(&mut self) -> Result<()> {
+        self.inner.finish_leaf()
+    }
+}
+
+/// A doc of the sample of `ReservoirSamplingCollector`, along with the random
+/// key it was assigned.
+struct SampledDoc {
+    key: u64,
+    doc: DocId,
+    score: f32,
+}
+
+impl PartialEq for SampledDoc {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key && self.doc == other.doc
+    }
+}
+
+impl Eq for SampledDoc {}
+
+impl PartialOrd for SampledDoc {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SampledDoc {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key
+            .cmp(&other.key)
+            .then_with(|| self.doc.cmp(&other.doc))
+    }
+}
+
+/// Keeps the `size` docs with the smallest keys in `heap`.
+fn offer(heap: &mut BinaryHeap<SampledDoc>, size: usize, doc: SampledDoc) {
+    if heap.len() < size {
+        heap.push(doc);
+    } else if let Some(mut top) = heap.peek_mut() {
+        if doc < *top {
+            *top = doc;
+        }
+    }
+}
+
+/// A collector keeping a uniform random sample of at most `size` matching
+/// docs, whatever the number of matches.
+///
+/// Every matching doc is assigned a random key and the docs with the `size`
+/// smallest keys are kept. Unlike classic reservoir sampling, such samples
+/// can be merged, so collection can run in parallel over the leaves.
+pub struct ReservoirSamplingCollector {
+    size: usize,
+    track_scores: bool,
+    rng: SmallRng,
+    heap: BinaryHeap<SampledDoc>,
+    doc_base: DocId,
+    total_hits: usize,
+}
+
+impl ReservoirSamplingCollector {
+    pub fn new(size: usize) -> ReservoirSamplingCollector {
+        Self::create(size, None)
+    }
+
+    /// Same as `new` but the sample is reproducible for the given `seed`.
+    pub fn with_seed(size: usize, seed: u64) -> ReservoirSamplingCollector {
+        Self::create(size, Some(seed))
+    }
+
+    fn create(size: usize, seed: Option<u64>) -> ReservoirSamplingCollector {
+        ReservoirSamplingCollector {
+            size,
+            track_scores: false,
+            rng: new_rng(seed),
+            heap: BinaryHeap::with_capacity(size),
+            doc_base: 0,
+            total_hits: 0,
+        }
+    }
+
+    /// Whether the scores of the sampled docs are computed, they are 0
+    /// otherwise.
+    pub fn set_track_scores(&mut self, track_scores: bool) {
+        self.track_scores = track_scores;
+    }
+
+    /// The number of docs which matched.
+    pub fn total_hits(&self) -> usize {
+        self.total_hits
+    }
+
+    /// The factor to multiply counts over the sample with to estimate the
+    /// counts over all the matching docs.
+    pub fn scale_factor(&self) -> f64 {
+        if self.heap.is_empty() {
+            1.0
+        } else {
+            self.total_hits as f64 / self.heap.len() as f64
+        }
+    }
+
+    /// The sampled docs, by increasing doc id.
+    pub fn sample(&self) -> Vec<ScoreDoc> {
+        let mut docs: Vec<ScoreDoc> = self
+            .heap
+            .iter()
+            .map(|d| ScoreDoc::new(d.doc, d.score))
+            .collect();
+        docs.sort_by_key(|d| d.doc);
+        docs
+    }
+}
+
+impl SearchCollector for ReservoirSamplingCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for ReservoirSamplingCollector {
+    type Collector = ReservoirSamplingLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<ReservoirSamplingLeafCollector> {
+        Ok(ReservoirSamplingLeafCollector {
+            size: self.size,
+            track_scores: self.track_scores,
+            rng: SmallRng::seed_from_u64(self.rng.gen()),
+            heap: BinaryHeap::new(),
+            doc_base: reader.doc_base,
+            total_hits: 0,
+        })
+    }
+
+    fn reduce(&mut self, collectors: Vec<ReservoirSamplingLeafCollector>) -> Result<()> {
+        for c in collectors {
+            self.total_hits += c.total_hits;
+            for doc in c.heap {
+                offer(&mut self.heap, self.size, doc);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Collector for ReservoirSamplingCollector {
+    fn needs_scores(&self) -> bool {
+        self.track_scores
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        let key = self.rng.gen::<u64>();
+        if self.heap.len() < self.size || self.heap.peek().map_or(false, |top| key < top.key) {
+            let score = if self.track_scores {
+                scorer.score()?
+            } else {
+                0.0
+            };
+            let doc = SampledDoc {
+                key,
+                doc: doc + self.doc_base,
+                score,
+            };
+            offer(&mut self.heap, self.size, doc);
+        }
+        Ok(())
+    }
+}
+
+pub struct ReservoirSamplingLeafCollector {
+    size: usize,
+    track_scores: bool,
+    rng: SmallRng,
+    heap: BinaryHeap<SampledDoc>,
+    doc_base: DocId,
+    total_hits: usize,
+}
+
+impl Collector for ReservoirSamplingLeafCollector {
+    fn needs_scores(&self) -> bool {
+        self.track_scores
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        let key = self.rng.gen::<u64>();
+        if self.heap.len() < self.size || self.heap.peek().map_or(false, |top| key < top.key) {
+            let score = if self.track_scores {
+                scorer.score()?
+            } else {
+                0.0
+            };
+            let doc = SampledDoc {
+                key,
+                doc: doc + self.doc_base,
+                score,
+            };
+            offer(&mut self.heap, self.size, doc);
+        }
+        Ok(())
+    }
+}
+
+impl ParallelLeafCollector for ReservoirSamplingLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::index::tests::*;
+    use core::index::IndexReader;
+    use core::search::collector::TopDocsCollector;
+    use core::search::tests::*;
+    use core::search::{DocIterator, NO_MORE_DOCS};
+
+    #[test]
+    fn test_reservoir_sampling() {
+        let docs: Vec<DocId> = (0..100).collect();
+        let mut scorer = create_mock_scorer(docs);
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        let mut collector = ReservoirSamplingCollector::with_seed(10, 42);
+        collector.set_next_reader(&leaves[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, &mut scorer).unwrap();
+        }
+
+        let sample = collector.sample();
+        assert_eq!(sample.len(), 10);
+        assert_eq!(collector.total_hits(), 100);
+        assert!((collector.scale_factor() - 10.0).abs() < 1e-9);
+        assert!(sample.windows(2).all(|w| w[0].doc < w[1].doc));
+    }
+
+    #[test]
+    fn test_sampling_collector() {
+        let docs: Vec<DocId> = (0..1000).collect();
+        let mut scorer = create_mock_scorer(docs);
+        let leaf_reader = MockLeafReader::new(0);
+        let index_reader = MockIndexReader::new(vec![leaf_reader]);
+        let leaves = index_reader.leaves();
+
+        assert!(SamplingCollector::new(TopDocsCollector::new(10), 0.0).is_err());
+
+        let mut collector =
+            SamplingCollector::with_seed(TopDocsCollector::new(1000), 0.1, 7).unwrap();
+        collector.set_next_reader(&leaves[0]).unwrap();
+        loop {
+            let doc = scorer.next().unwrap();
+            if doc == NO_MORE_DOCS {
+                break;
+            }
+            collector.collect(doc, &mut scorer).unwrap();
+        }
+
+        assert_eq!(collector.total_hits(), 1000);
+        let sampled = collector.sampled_hits();
+        assert!(sampled > 50 && sampled < 150);
+        assert_eq!(collector.inner_mut().top_docs().total_hits(), sampled);
+    }
+}
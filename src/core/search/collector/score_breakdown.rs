@@ -0,0 +1,169 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::collector::top_docs::TopDocsLeafCollector;
+use core::search::collector::{Collector, CollectorManager, SearchCollector};
+use core::search::top_docs::{ScoreDoc, ScoreDocHit, TopDocs, TopScoreDocs};
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalState, Result};
+
+/// A `TopDocsCollector` for relevance tuning, which also records for each
+/// top hit the partial score of every scoring clause of the query in
+/// `ScoreDoc::breakdown`, the clauses being listed in `TopScoreDocs::clauses`.
+///
+/// The clauses are the must and should clauses of a top level
+/// `BooleanQuery`, or the query itself otherwise. Their scorers are advanced
+/// along with the collection and only queried for the docs entering the top
+/// hits, which is much cheaper than calling `IndexSearcher::explain` for each
+/// hit afterwards. The docs are collected in order and by a single thread.
+pub struct ScoreBreakdownCollector {
+    pq: BinaryHeap<ScoreDoc>,
+    estimated_hits: usize,
+    total_hits: usize,
+    cur_doc_base: DocId,
+    clauses: Vec<String>,
+    clause_scorers: Vec<Option<Box<dyn Scorer>>>,
+}
+
+impl ScoreBreakdownCollector {
+    pub fn new(estimated_hits: usize) -> ScoreBreakdownCollector {
+        ScoreBreakdownCollector {
+            pq: BinaryHeap::with_capacity(estimated_hits),
+            estimated_hits,
+            total_hits: 0,
+            cur_doc_base: 0,
+            clauses: vec![],
+            clause_scorers: vec![],
+        }
+    }
+
+    /// Returns the top docs that were collected by this collector, with the
+    /// breakdown of their scores.
+    pub fn top_docs(&mut self) -> TopDocs {
+        let size = self.total_hits.min(self.pq.len());
+        let mut score_docs = Vec::with_capacity(size);
+        for _ in 0..size {
+            score_docs.push(ScoreDocHit::Score(self.pq.pop().unwrap()));
+        }
+        score_docs.reverse();
+
+        let mut top_docs = TopScoreDocs::new(self.total_hits, score_docs);
+        top_docs.clauses = self.clauses.clone();
+        TopDocs::Score(top_docs)
+    }
+
+    fn competitive(&self, doc: DocId, score: f32) -> bool {
+        if self.pq.len() < self.estimated_hits {
+            return true;
+        }
+        match self.pq.peek() {
+            Some(least) => match score.partial_cmp(&least.score) {
+                Some(Ordering::Greater) => true,
+                Some(Ordering::Equal) => doc < least.doc,
+                _ => false,
+            },
+            None => false,
+        }
+    }
+
+    fn breakdown(&mut self, doc: DocId) -> Result<Vec<f32>> {
+        let mut breakdown = Vec::with_capacity(self.clause_scorers.len());
+        for scorer in &mut self.clause_scorers {
+            let mut partial = 0.0;
+            if let Some(ref mut scorer) = *scorer {
+                if scorer.doc_id() < doc {
+                    scorer.advance(doc)?;
+                }
+                if scorer.doc_id() == doc {
+                    partial = scorer.score()?;
+                }
+            }
+            breakdown.push(partial);
+        }
+        Ok(breakdown)
+    }
+}
+
+impl SearchCollector for ScoreBreakdownCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.cur_doc_base = reader.doc_base;
+        self.clause_scorers.clear();
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        false
+    }
+
+    fn needs_score_breakdown(&self) -> bool {
+        true
+    }
+
+    fn set_clause_scorers(
+        &mut self,
+        clauses: &[String],
+        scorers: Vec<Option<Box<dyn Scorer>>>,
+    ) -> Result<()> {
+        if self.clauses.is_empty() {
+            self.clauses = clauses.to_vec();
+        }
+        self.clause_scorers = scorers;
+        Ok(())
+    }
+}
+
+impl CollectorManager for ScoreBreakdownCollector {
+    type Collector = TopDocsLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        _reader: &LeafReaderContext<'_, C>,
+    ) -> Result<TopDocsLeafCollector> {
+        bail!(IllegalState(
+            "score breakdown can't be collected by a parallel search".into()
+        ))
+    }
+
+    fn reduce(&mut self, _collectors: Vec<TopDocsLeafCollector>) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Collector for ScoreBreakdownCollector {
+    fn needs_scores(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        let score = scorer.score()?;
+        self.total_hits += 1;
+
+        let id = doc + self.cur_doc_base;
+        if !self.competitive(id, score) {
+            return Ok(());
+        }
+        let mut score_doc = ScoreDoc::new(id, score);
+        score_doc.breakdown = self.breakdown(doc)?;
+        if self.pq.len() >= self.estimated_hits {
+            self.pq.pop();
+        }
+        self.pq.push(score_doc);
+        Ok(())
+    }
+}
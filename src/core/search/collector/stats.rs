@@ -0,0 +1,199 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::collector::{
+    Collector, CollectorManager, MetricStats, NumericFieldValues, ParallelLeafCollector,
+    SearchCollector,
+};
+use core::search::sort_field::{SortFieldType, SortedNumericSelectorType};
+use core::search::Scorer;
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+struct StatsConfig {
+    field: String,
+    field_type: SortFieldType,
+    selector: Option<SortedNumericSelectorType>,
+}
+
+/// The per leaf state of a stats aggregation.
+struct StatsAggregator {
+    config: Arc<StatsConfig>,
+    values: NumericFieldValues,
+    doc_values: Vec<f64>,
+    doc_count: u64,
+    stats: MetricStats,
+}
+
+impl StatsAggregator {
+    fn new(config: Arc<StatsConfig>) -> StatsAggregator {
+        StatsAggregator {
+            config,
+            values: NumericFieldValues::Missing,
+            doc_values: vec![],
+            doc_count: 0,
+            stats: MetricStats::default(),
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.values = NumericFieldValues::new(reader, &self.config.field, self.config.field_type)?;
+        Ok(())
+    }
+
+    fn collect(&mut self, doc: DocId) -> Result<()> {
+        self.values.read(doc, &mut self.doc_values)?;
+        if self.doc_values.is_empty() {
+            return Ok(());
+        }
+        self.doc_count += 1;
+        // the values of a doc are sorted
+        match self.config.selector {
+            Some(SortedNumericSelectorType::Min) => self.stats.add(self.doc_values[0]),
+            Some(SortedNumericSelectorType::Max) => {
+                self.stats.add(self.doc_values[self.doc_values.len() - 1])
+            }
+            None => {
+                for &v in &self.doc_values {
+                    self.stats.add(v);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A collector computing the statistics (count, sum, min, max and average)
+/// of the values of a numeric or sorted numeric doc values field over the
+/// matching docs.
+///
+/// Every value of a multi-valued doc is aggregated by default, or only its
+/// smallest or largest one with `set_selector`, like the sorting of a
+/// `SortedNumericSortField`. Docs without a value are ignored.
+pub struct StatsCollector {
+    aggregator: StatsAggregator,
+}
+
+impl StatsCollector {
+    /// Creates a collector over `field` whose doc values are of `field_type`.
+    pub fn new(field: &str, field_type: SortFieldType) -> Result<StatsCollector> {
+        match field_type {
+            SortFieldType::Int
+            | SortFieldType::Long
+            | SortFieldType::Float
+            | SortFieldType::Double => {}
+            _ => bail!(IllegalArgument(format!(
+                "field {} must have a numeric type, got {:?}",
+                field, field_type
+            ))),
+        }
+        let config = StatsConfig {
+            field: field.to_string(),
+            field_type,
+            selector: None,
+        };
+        Ok(StatsCollector {
+            aggregator: StatsAggregator::new(Arc::new(config)),
+        })
+    }
+
+    /// Aggregates a single value per doc, selected among the values of a
+    /// multi-valued doc by `selector`, instead of all of them.
+    pub fn set_selector(&mut self, selector: SortedNumericSelectorType) {
+        Arc::make_mut(&mut self.aggregator.config).selector = Some(selector);
+    }
+
+    /// The statistics of the collected values.
+    pub fn stats(&self) -> MetricStats {
+        self.aggregator.stats
+    }
+
+    /// The number of collected docs having at least one value.
+    pub fn doc_count(&self) -> u64 {
+        self.aggregator.doc_count
+    }
+}
+
+impl SearchCollector for StatsCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.aggregator.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for StatsCollector {
+    type Collector = StatsLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<StatsLeafCollector> {
+        let mut aggregator = StatsAggregator::new(Arc::clone(&self.aggregator.config));
+        aggregator.set_next_reader(reader)?;
+        Ok(StatsLeafCollector { aggregator })
+    }
+
+    fn reduce(&mut self, collectors: Vec<StatsLeafCollector>) -> Result<()> {
+        for c in collectors {
+            self.aggregator.doc_count += c.aggregator.doc_count;
+            self.aggregator.stats.merge(&c.aggregator.stats);
+        }
+        Ok(())
+    }
+}
+
+impl Collector for StatsCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+pub struct StatsLeafCollector {
+    aggregator: StatsAggregator,
+}
+
+impl Collector for StatsLeafCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+impl ParallelLeafCollector for StatsLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
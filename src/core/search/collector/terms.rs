@@ -0,0 +1,604 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{
+    DocValuesType, LeafReaderContext, SortedDocValuesRef, SortedSetDocValuesRef, NO_MORE_ORDS,
+};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::Scorer;
+use core::store::Directory;
+use core::util::offline_sorter::{BufferSize, ByteSequencesReader, OfflineSorter, MAX_TEMP_FILES};
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem;
+use std::str;
+use std::sync::Arc;
+
+/// The default number of distinct terms a `TermsCollector` keeps in memory
+/// before spilling their counts, once a spill directory is set.
+pub const DEFAULT_MAX_BUCKETS_IN_MEMORY: usize = 100_000;
+
+/// The ordinals of a sorted or sorted set doc values field of a leaf.
+enum OrdinalValues {
+    Sorted(SortedDocValuesRef),
+    SortedSet(SortedSetDocValuesRef),
+    /// the field has no doc values in this leaf
+    Missing,
+}
+
+impl OrdinalValues {
+    fn new<C: Codec>(reader: &LeafReaderContext<'_, C>, field: &str) -> Result<OrdinalValues> {
+        let leaf = reader.reader;
+        let values = match leaf.field_info(field).map(|info| info.doc_values_type) {
+            Some(DocValuesType::Sorted) => {
+                OrdinalValues::Sorted(leaf.get_sorted_doc_values(field)?)
+            }
+            Some(DocValuesType::SortedSet) => {
+                OrdinalValues::SortedSet(leaf.get_sorted_set_doc_values(field)?)
+            }
+            None | Some(DocValuesType::Null) => OrdinalValues::Missing,
+            Some(t) => bail!(IllegalArgument(format!(
+                "field {} must have sorted or sorted set doc values, got {:?}",
+                field, t
+            ))),
+        };
+        Ok(values)
+    }
+
+    fn value_count(&self) -> usize {
+        match self {
+            OrdinalValues::Sorted(values) => values.get_value_count(),
+            OrdinalValues::SortedSet(values) => values.get_value_count(),
+            OrdinalValues::Missing => 0,
+        }
+    }
+
+    fn lookup_ord(&self, ord: usize) -> Result<Vec<u8>> {
+        match self {
+            OrdinalValues::Sorted(values) => values.lookup_ord(ord as i32),
+            OrdinalValues::SortedSet(values) => values.lookup_ord(ord as i64),
+            OrdinalValues::Missing => unreachable!(),
+        }
+    }
+}
+
+/// A bucket of a terms aggregation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TermBucket {
+    pub term: Vec<u8>,
+    pub doc_count: u64,
+}
+
+impl TermBucket {
+    /// The term as a string, None if it isn't valid UTF-8.
+    pub fn term_str(&self) -> Option<&str> {
+        str::from_utf8(&self.term).ok()
+    }
+}
+
+/// Counts the docs per term, by segment ordinal while collecting a leaf, and
+/// by term once the leaf is finished.
+struct TermsAggregator {
+    field: String,
+    values: OrdinalValues,
+    // doc counts of the current leaf, indexed by segment ordinal
+    ord_counts: Vec<u64>,
+    // doc counts of the finished leaves
+    term_counts: HashMap<Vec<u8>, u64>,
+    missing: u64,
+}
+
+impl TermsAggregator {
+    fn new(field: String) -> TermsAggregator {
+        TermsAggregator {
+            field,
+            values: OrdinalValues::Missing,
+            ord_counts: vec![],
+            term_counts: HashMap::new(),
+            missing: 0,
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.finish_leaf()?;
+        self.values = OrdinalValues::new(reader, &self.field)?;
+        self.ord_counts = vec![0; self.values.value_count()];
+        Ok(())
+    }
+
+    fn collect(&mut self, doc: DocId) -> Result<()> {
+        let mut has_value = false;
+        match self.values {
+            OrdinalValues::Sorted(ref values) => {
+                let ord = values.get_ord(doc)?;
+                if ord >= 0 {
+                    self.ord_counts[ord as usize] += 1;
+                    has_value = true;
+                }
+            }
+            OrdinalValues::SortedSet(ref values) => {
+                // ords of a doc are distinct, so every term is counted once
+                let mut ctx = values.set_document(doc)?;
+                loop {
+                    let ord = values.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    self.ord_counts[ord as usize] += 1;
+                    has_value = true;
+                }
+            }
+            OrdinalValues::Missing => {}
+        }
+        if !has_value {
+            self.missing += 1;
+        }
+        Ok(())
+    }
+
+    /// Resolves the ordinals counted in the current leaf to their terms, only
+    /// the ordinals of collected docs are looked up.
+    fn finish_leaf(&mut self) -> Result<()> {
+        let ord_counts = mem::replace(&mut self.ord_counts, vec![]);
+        for (ord, &count) in ord_counts.iter().enumerate() {
+            if count > 0 {
+                let term = self.values.lookup_ord(ord)?;
+                *self.term_counts.entry(term).or_insert(0) += count;
+            }
+        }
+        self.values = OrdinalValues::Missing;
+        Ok(())
+    }
+
+    fn merge(&mut self, other: TermsAggregator) {
+        for (term, count) in other.term_counts {
+            *self.term_counts.entry(term).or_insert(0) += count;
+        }
+        self.missing += other.missing;
+    }
+}
+
+/// A collector computing the terms of a keyword field, indexed as sorted or
+/// sorted set doc values, with the most matching docs.
+///
+/// Docs are counted per segment ordinal while collecting, which is cheap, and
+/// the ordinals are only resolved to terms once per segment. The per segment
+/// counts are then merged by term, so the counts are exact across segments.
+/// Buckets are ordered by decreasing doc count, then by term.
+///
+/// With a spill directory, see `set_spill_directory`, the counts by term are
+/// written to temp files whenever more than `max_buckets_in_memory` terms are
+/// held, and merged by an `OfflineSorter` when the buckets are computed, so
+/// that high cardinality fields don't need unbounded memory.
+pub struct TermsCollector {
+    aggregator: TermsAggregator,
+    size: usize,
+    min_doc_count: u64,
+    max_buckets_in_memory: usize,
+    spill: Option<Box<dyn BucketSpill>>,
+}
+
+impl TermsCollector {
+    /// Creates a collector returning the top `size` terms of `field`.
+    pub fn new(field: &str, size: usize) -> TermsCollector {
+        TermsCollector {
+            aggregator: TermsAggregator::new(field.to_string()),
+            size,
+            min_doc_count: 1,
+            max_buckets_in_memory: DEFAULT_MAX_BUCKETS_IN_MEMORY,
+            spill: None,
+        }
+    }
+
+    /// Terms with less than `min_doc_count` docs are not returned, the default
+    /// is 1.
+    pub fn set_min_doc_count(&mut self, min_doc_count: u64) {
+        self.min_doc_count = min_doc_count.max(1);
+    }
+
+    /// Spills the counts to temp files of `dir` when more than
+    /// `max_buckets_in_memory` terms are held, the temp files are deleted
+    /// once the buckets are computed or the collector is dropped.
+    pub fn set_spill_directory<D: Directory + Send + Sync + 'static>(
+        &mut self,
+        dir: Arc<D>,
+        max_buckets_in_memory: usize,
+    ) -> Result<()> {
+        if max_buckets_in_memory == 0 {
+            bail!(IllegalArgument(
+                "max_buckets_in_memory must be positive".into()
+            ));
+        }
+        let sorter = OfflineSorter::new(
+            dir,
+            "terms_agg",
+            Box::new(compare_spilled_terms),
+            BufferSize::megabytes(16)?,
+            MAX_TEMP_FILES,
+        )?;
+        self.max_buckets_in_memory = max_buckets_in_memory;
+        self.spill = Some(Box::new(DirectorySpill::new(sorter)));
+        Ok(())
+    }
+
+    /// The number of collected docs without any value for the field.
+    pub fn missing(&self) -> u64 {
+        self.aggregator.missing
+    }
+
+    /// The top terms.
+    pub fn buckets(&mut self) -> Result<Vec<TermBucket>> {
+        Ok(self.reduce_buckets()?.0)
+    }
+
+    /// The number of docs counted in the terms which are not returned by
+    /// `buckets`, because they aren't among the top terms.
+    pub fn sum_other_doc_count(&mut self) -> Result<u64> {
+        let (buckets, total) = self.reduce_buckets()?;
+        let top: u64 = buckets.iter().map(|b| b.doc_count).sum();
+        Ok(total - top)
+    }
+
+    fn maybe_spill(&mut self) -> Result<()> {
+        if let Some(ref mut spill) = self.spill {
+            if self.aggregator.term_counts.len() > self.max_buckets_in_memory {
+                spill.spill(&mut self.aggregator.term_counts)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the top buckets and the total doc count of all the terms.
+    fn reduce_buckets(&mut self) -> Result<(Vec<TermBucket>, u64)> {
+        self.aggregator.finish_leaf()?;
+        let size = self.size;
+        let min_doc_count = self.min_doc_count;
+        let mut buckets = vec![];
+        let mut total = 0;
+        {
+            let mut add = |term: &[u8], count: u64| {
+                total += count;
+                if count < min_doc_count {
+                    return;
+                }
+                buckets.push(TermBucket {
+                    term: term.to_vec(),
+                    doc_count: count,
+                });
+                // keep the candidates bounded
+                if buckets.len() >= size.max(64) * 2 {
+                    sort_buckets(&mut buckets);
+                    buckets.truncate(size);
+                }
+            };
+            match self.spill {
+                Some(ref mut spill) if spill.has_spilled() => {
+                    spill.reduce(&mut self.aggregator.term_counts, &mut add)?
+                }
+                _ => {
+                    for (term, &count) in &self.aggregator.term_counts {
+                        add(term, count);
+                    }
+                }
+            }
+        }
+        sort_buckets(&mut buckets);
+        buckets.truncate(size);
+        Ok((buckets, total))
+    }
+}
+
+fn sort_buckets(buckets: &mut Vec<TermBucket>) {
+    buckets.sort_by(|a, b| match b.doc_count.cmp(&a.doc_count) {
+        Ordering::Equal => a.term.cmp(&b.term),
+        o => o,
+    });
+}
+
+/// Spilled counts are encoded as the term followed by the count as 8 big
+/// endian bytes, they are sorted by term.
+fn compare_spilled_terms(a: &[u8], b: &[u8]) -> Ordering {
+    a[..a.len() - 8].cmp(&b[..b.len() - 8])
+}
+
+fn decode_spilled_count(record: &[u8]) -> u64 {
+    let mut count = 0u64;
+    for &b in &record[record.len() - 8..] {
+        count = (count << 8) | u64::from(b);
+    }
+    count
+}
+
+/// Where the counts by term are spilled.
+trait BucketSpill {
+    fn has_spilled(&self) -> bool;
+
+    /// Spills and clears `counts`.
+    fn spill(&mut self, counts: &mut HashMap<Vec<u8>, u64>) -> Result<()>;
+
+    /// Spills the remaining `counts`, then calls `f` with the total count of
+    /// each term in term order.
+    fn reduce(
+        &mut self,
+        counts: &mut HashMap<Vec<u8>, u64>,
+        f: &mut dyn FnMut(&[u8], u64),
+    ) -> Result<()>;
+}
+
+struct DirectorySpill<D: Directory> {
+    sorter: OfflineSorter<D>,
+    // the files of spilled counts, unsorted
+    spilled: Vec<String>,
+}
+
+impl<D: Directory> DirectorySpill<D> {
+    fn new(sorter: OfflineSorter<D>) -> DirectorySpill<D> {
+        DirectorySpill {
+            sorter,
+            spilled: vec![],
+        }
+    }
+
+    fn write(&self, counts: &HashMap<Vec<u8>, u64>) -> Result<String> {
+        let mut writer = self.sorter.create_input()?;
+        let mut record = vec![];
+        for (term, count) in counts {
+            record.clear();
+            record.extend_from_slice(term);
+            for i in (0..8).rev() {
+                record.push((count >> (i * 8)) as u8);
+            }
+            writer.write(&record)?;
+        }
+        writer.finish()
+    }
+
+    fn delete(&self, name: &str) {
+        if let Err(e) = self.sorter.directory().delete_file(name) {
+            warn!("failed to delete temp file {}: {:?}", name, e);
+        }
+    }
+}
+
+impl<D: Directory> BucketSpill for DirectorySpill<D> {
+    fn has_spilled(&self) -> bool {
+        !self.spilled.is_empty()
+    }
+
+    fn spill(&mut self, counts: &mut HashMap<Vec<u8>, u64>) -> Result<()> {
+        let name = self.write(counts)?;
+        self.spilled.push(name);
+        counts.clear();
+        Ok(())
+    }
+
+    fn reduce(
+        &mut self,
+        counts: &mut HashMap<Vec<u8>, u64>,
+        f: &mut dyn FnMut(&[u8], u64),
+    ) -> Result<()> {
+        if !counts.is_empty() {
+            self.spill(counts)?;
+        }
+        let sorted = self.sorter.sort_files(&self.spilled)?;
+        let res = (|| {
+            let mut reader = ByteSequencesReader::open(self.sorter.directory().as_ref(), &sorted)?;
+            let mut current: Option<(Vec<u8>, u64)> = None;
+            while let Some(record) = reader.next()? {
+                let count = decode_spilled_count(&record);
+                let term = &record[..record.len() - 8];
+                if let Some((ref last, ref mut total)) = current {
+                    if last.as_slice() == term {
+                        *total += count;
+                        continue;
+                    }
+                }
+                if let Some((last, total)) = current.take() {
+                    f(&last, total);
+                }
+                current = Some((term.to_vec(), count));
+            }
+            if let Some((last, total)) = current {
+                f(&last, total);
+            }
+            Ok(())
+        })();
+        self.delete(&sorted);
+        res
+    }
+}
+
+impl<D: Directory> Drop for DirectorySpill<D> {
+    fn drop(&mut self) {
+        for name in &self.spilled {
+            self.delete(name);
+        }
+    }
+}
+
+impl SearchCollector for TermsCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.aggregator.set_next_reader(reader)?;
+        self.maybe_spill()
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for TermsCollector {
+    type Collector = TermsLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<TermsLeafCollector> {
+        let mut aggregator = TermsAggregator::new(self.aggregator.field.clone());
+        aggregator.set_next_reader(reader)?;
+        Ok(TermsLeafCollector { aggregator })
+    }
+
+    fn reduce(&mut self, collectors: Vec<TermsLeafCollector>) -> Result<()> {
+        for mut c in collectors {
+            c.aggregator.finish_leaf()?;
+            self.aggregator.merge(c.aggregator);
+            self.maybe_spill()?;
+        }
+        Ok(())
+    }
+}
+
+impl Collector for TermsCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+pub struct TermsLeafCollector {
+    aggregator: TermsAggregator,
+}
+
+impl Collector for TermsLeafCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        self.aggregator.collect(doc)
+    }
+}
+
+impl ParallelLeafCollector for TermsLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        self.aggregator.finish_leaf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{NumericDocValuesField, SortedSetDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexWriter};
+    use core::search::match_all::MatchAllDocsQuery;
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+
+    fn document(id: i64, tags: &[&str]) -> Vec<Box<dyn Fieldable>> {
+        let mut fields: Vec<Box<dyn Fieldable>> =
+            vec![Box::new(NumericDocValuesField::new("id", id))];
+        for tag in tags {
+            fields.push(Box::new(SortedSetDocValuesField::new(
+                "tags",
+                tag.as_bytes(),
+            )));
+        }
+        fields
+    }
+
+    fn bucket(term: &str, doc_count: u64) -> TermBucket {
+        TermBucket {
+            term: term.as_bytes().to_vec(),
+            doc_count,
+        }
+    }
+
+    #[test]
+    fn test_terms_across_segments() {
+        let path = ::std::env::temp_dir().join("rucene_terms_collector");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // the ordinals of the segments differ: a, b, c then a, b, d
+        let segments: [&[&[&str]]; 2] = [
+            &[&["a", "b"], &["b"], &["c"], &[]],
+            &[&["b", "d"], &["d"], &["a"], &[]],
+        ];
+        let mut id = 0;
+        for docs in &segments {
+            for tags in docs.iter() {
+                writer.add_document(document(id, tags)).unwrap();
+                id += 1;
+            }
+            writer.commit().unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+        let searcher = DefaultIndexSearcher::new(&reader);
+
+        let mut collector = TermsCollector::new("tags", 3);
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        // a and d are tied, they are ordered by term
+        assert_eq!(
+            collector.buckets().unwrap(),
+            vec![bucket("b", 3), bucket("a", 2), bucket("d", 2)]
+        );
+        assert_eq!(collector.sum_other_doc_count().unwrap(), 1);
+        assert_eq!(collector.missing(), 2);
+
+        let mut collector = TermsCollector::new("tags", 10);
+        collector.set_min_doc_count(3);
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        assert_eq!(collector.buckets().unwrap(), vec![bucket("b", 3)]);
+
+        // the counts of a term spilled by several segments are summed
+        let spill_path = ::std::env::temp_dir().join("rucene_terms_collector_spill");
+        let _ = fs::remove_dir_all(&spill_path);
+        let spill_dir =
+            Arc::new(FSDirectory::new(&spill_path, NativeFSLockFactory::default()).unwrap());
+        let mut collector = TermsCollector::new("tags", 10);
+        collector.set_spill_directory(spill_dir, 1).unwrap();
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        assert_eq!(
+            collector.buckets().unwrap(),
+            vec![
+                bucket("b", 3),
+                bucket("a", 2),
+                bucket("d", 2),
+                bucket("c", 1)
+            ]
+        );
+        drop(collector);
+
+        // a field without doc values only has missing docs
+        let mut collector = TermsCollector::new("unknown", 10);
+        searcher.search(&MatchAllDocsQuery, &mut collector).unwrap();
+        assert!(collector.buckets().unwrap().is_empty());
+        assert_eq!(collector.missing(), 8);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        fs::remove_dir_all(&spill_path).unwrap();
+    }
+}
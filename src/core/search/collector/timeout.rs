@@ -13,34 +13,40 @@
 
 use core::codec::Codec;
 use core::index::LeafReaderContext;
-use core::search::collector;
-use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::query_timeout::QueryTimeout;
 use core::search::Scorer;
 use core::util::DocId;
-use error::{ErrorKind, Result};
-use std::sync::atomic::{AtomicBool, Ordering};
+use error::Result;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
 
 pub struct TimeoutCollector {
-    timeout_duration: Duration,
-    start_time: SystemTime,
+    query_timeout: QueryTimeout,
     pub timeout: Arc<AtomicBool>,
 }
 
 impl TimeoutCollector {
     pub fn new(timeout_duration: Duration, start_time: SystemTime) -> TimeoutCollector {
+        Self::with_query_timeout(QueryTimeout::new(timeout_duration, start_time))
+    }
+
+    /// Stops the collection once `query_timeout` is exceeded, sharing the
+    /// budget with the other stages of the query it is set on, e.g. by
+    /// `DefaultIndexSearcher::set_query_timeout`.
+    pub fn with_query_timeout(query_timeout: QueryTimeout) -> TimeoutCollector {
+        let timeout = query_timeout.timed_out_flag();
         TimeoutCollector {
-            timeout_duration,
-            start_time,
-            timeout: Arc::new(AtomicBool::new(false)),
+            query_timeout,
+            timeout,
         }
     }
 }
 
 impl SearchCollector for TimeoutCollector {
-    type LC = TimeoutLeafCollector;
-
     fn set_next_reader<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<()> {
         Ok(())
     }
@@ -48,19 +54,21 @@ impl SearchCollector for TimeoutCollector {
     fn support_parallel(&self) -> bool {
         true
     }
+}
 
-    fn leaf_collector<C: Codec>(
+impl CollectorManager for TimeoutCollector {
+    type Collector = TimeoutLeafCollector;
+
+    fn new_collector<C: Codec>(
         &mut self,
         _reader: &LeafReaderContext<'_, C>,
     ) -> Result<TimeoutLeafCollector> {
-        Ok(TimeoutLeafCollector::new(
-            self.timeout_duration,
-            self.start_time,
-            Arc::clone(&self.timeout),
+        Ok(TimeoutLeafCollector::with_query_timeout(
+            self.query_timeout.clone(),
         ))
     }
 
-    fn finish_parallel(&mut self) -> Result<()> {
+    fn reduce(&mut self, _collectors: Vec<TimeoutLeafCollector>) -> Result<()> {
         Ok(())
     }
 }
@@ -70,35 +78,22 @@ impl Collector for TimeoutCollector {
         false
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: DocId, _scorer: &mut S) -> Result<()> {
-        let now = SystemTime::now();
-        if self.start_time < now && now.duration_since(self.start_time)? >= self.timeout_duration {
-            self.timeout.store(true, Ordering::Release);
-            bail!(ErrorKind::Collector(
-                collector::ErrorKind::CollectionTerminated,
-            ))
-        }
-        Ok(())
+        self.query_timeout.check()
     }
 }
 
 pub struct TimeoutLeafCollector {
-    timeout_duration: Duration,
-    start_time: SystemTime,
-    timeout: Arc<AtomicBool>,
+    query_timeout: QueryTimeout,
 }
 
 impl TimeoutLeafCollector {
-    pub fn new(
-        timeout_duration: Duration,
-        start_time: SystemTime,
-        timeout: Arc<AtomicBool>,
-    ) -> TimeoutLeafCollector {
-        TimeoutLeafCollector {
-            timeout_duration,
-            start_time,
-            timeout,
-        }
+    pub fn with_query_timeout(query_timeout: QueryTimeout) -> TimeoutLeafCollector {
+        TimeoutLeafCollector { query_timeout }
     }
 }
 
@@ -107,15 +102,12 @@ impl Collector for TimeoutLeafCollector {
         false
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: i32, _scorer: &mut S) -> Result<()> {
-        let now = SystemTime::now();
-        if self.start_time < now && now.duration_since(self.start_time)? >= self.timeout_duration {
-            self.timeout.store(true, Ordering::Release);
-            bail!(ErrorKind::Collector(
-                collector::ErrorKind::CollectionTerminated,
-            ))
-        }
-        Ok(())
+        self.query_timeout.check()
     }
 }
 
@@ -11,19 +11,20 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cmp::Ordering;
 use std::collections::BinaryHeap;
 use std::f32;
 use std::usize;
 
 use core::codec::Codec;
 use core::index::LeafReaderContext;
-use core::search::collector::{Collector, ParallelLeafCollector, SearchCollector};
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
 use core::search::top_docs::{ScoreDoc, ScoreDocHit, TopDocs, TopScoreDocs};
 use core::search::Scorer;
 use core::util::DocId;
-use error::{ErrorKind::IllegalState, Result};
-
-use crossbeam::channel::{unbounded, Receiver, Sender};
+use error::Result;
 
 type ScoreDocPriorityQueue = BinaryHeap<ScoreDoc>;
 
@@ -40,9 +41,6 @@ pub struct TopDocsCollector {
     total_hits: usize,
 
     cur_doc_base: DocId,
-
-    // TODO used for parallel collect, maybe should be move the new struct for parallel search
-    channel: Option<(Sender<ScoreDoc>, Receiver<ScoreDoc>)>,
 }
 
 impl TopDocsCollector {
@@ -53,7 +51,6 @@ impl TopDocsCollector {
             estimated_hits,
             total_hits: 0,
             cur_doc_base: 0,
-            channel: None,
         }
     }
 
@@ -71,17 +68,30 @@ impl TopDocsCollector {
     }
 
     fn add_doc(&mut self, doc_id: DocId, score: f32) {
-        debug_assert!(self.pq.len() <= self.estimated_hits);
-
         self.total_hits += 1;
+        Self::insert_with_overflow(&mut self.pq, self.estimated_hits, doc_id, score);
+    }
 
-        let at_capacity = self.pq.len() == self.estimated_hits;
-
-        if !at_capacity {
-            let score_doc = ScoreDoc::new(doc_id, score);
-            self.pq.push(score_doc);
-        } else if let Some(mut doc) = self.pq.peek_mut() {
-            if doc.score < score {
+    fn insert_with_overflow(
+        pq: &mut ScoreDocPriorityQueue,
+        size: usize,
+        doc_id: DocId,
+        score: f32,
+    ) {
+        debug_assert!(pq.len() <= size);
+
+        if pq.len() < size {
+            pq.push(ScoreDoc::new(doc_id, score));
+        } else if let Some(mut doc) = pq.peek_mut() {
+            // on ties the smallest doc id wins, docs collected in order never
+            // replace a hit with the same score, but docs collected out of
+            // order or reduced from the leaves may
+            let competitive = match score.partial_cmp(&doc.score) {
+                Some(Ordering::Greater) => true,
+                Some(Ordering::Equal) => doc_id < doc.doc,
+                _ => false,
+            };
+            if competitive {
                 doc.reset(doc_id, score);
             }
         }
@@ -89,8 +99,6 @@ impl TopDocsCollector {
 }
 
 impl SearchCollector for TopDocsCollector {
-    type LC = TopDocsLeafCollector;
-
     fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
         self.cur_doc_base = reader.doc_base;
 
@@ -100,28 +108,26 @@ impl SearchCollector for TopDocsCollector {
     fn support_parallel(&self) -> bool {
         true
     }
+}
+
+impl CollectorManager for TopDocsCollector {
+    type Collector = TopDocsLeafCollector;
 
-    fn leaf_collector<C: Codec>(
+    fn new_collector<C: Codec>(
         &mut self,
         reader: &LeafReaderContext<'_, C>,
     ) -> Result<TopDocsLeafCollector> {
-        if self.channel.is_none() {
-            self.channel = Some(unbounded());
-        }
         Ok(TopDocsLeafCollector::new(
             reader.doc_base,
-            self.channel.as_ref().unwrap().0.clone(),
+            self.estimated_hits,
         ))
     }
 
-    fn finish_parallel(&mut self) -> Result<()> {
-        let channel = self.channel.take();
-        // iff all the `weight.create_scorer(leaf_reader)` return None, the channel won't
-        // inited and thus stay None
-        if let Some((sender, receiver)) = channel {
-            drop(sender);
-            while let Ok(doc) = receiver.recv() {
-                self.add_doc(doc.doc, doc.score)
+    fn reduce(&mut self, collectors: Vec<TopDocsLeafCollector>) -> Result<()> {
+        for collector in collectors {
+            self.total_hits += collector.total_hits;
+            for doc in collector.pq.into_vec() {
+                Self::insert_with_overflow(&mut self.pq, self.estimated_hits, doc.doc, doc.score);
             }
         }
 
@@ -134,6 +140,10 @@ impl Collector for TopDocsCollector {
         true
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
         let score = scorer.score()?;
         debug_assert!((score - f32::NEG_INFINITY).abs() >= f32::EPSILON);
@@ -146,14 +156,23 @@ impl Collector for TopDocsCollector {
     }
 }
 
+/// Leaf collector of `TopDocsCollector` for parallel search, which keeps the
+/// top docs of a single segment until they are reduced.
 pub struct TopDocsLeafCollector {
     doc_base: DocId,
-    channel: Sender<ScoreDoc>,
+    estimated_hits: usize,
+    total_hits: usize,
+    pq: ScoreDocPriorityQueue,
 }
 
 impl TopDocsLeafCollector {
-    pub fn new(doc_base: DocId, channel: Sender<ScoreDoc>) -> TopDocsLeafCollector {
-        TopDocsLeafCollector { doc_base, channel }
+    pub fn new(doc_base: DocId, estimated_hits: usize) -> TopDocsLeafCollector {
+        TopDocsLeafCollector {
+            doc_base,
+            estimated_hits,
+            total_hits: 0,
+            pq: ScoreDocPriorityQueue::with_capacity(estimated_hits),
+        }
     }
 }
 
@@ -169,15 +188,20 @@ impl Collector for TopDocsLeafCollector {
         true
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, doc: i32, scorer: &mut S) -> Result<()> {
-        let score_doc = ScoreDoc::new(doc + self.doc_base, scorer.score()?);
-        self.channel.send(score_doc).map_err(|e| {
-            IllegalState(format!(
-                "channel unexpected closed before search complete with err: {:?}",
-                e
-            ))
-            .into()
-        })
+        let score = scorer.score()?;
+        self.total_hits += 1;
+        TopDocsCollector::insert_with_overflow(
+            &mut self.pq,
+            self.estimated_hits,
+            doc + self.doc_base,
+            score,
+        );
+        Ok(())
     }
 }
 
@@ -220,4 +244,56 @@ mod tests {
         assert_eq!(score_docs[1].doc_id(), 3);
         assert_eq!(score_docs[2].doc_id(), 3);
     }
+
+    #[test]
+    fn test_reduce() {
+        let leaf_reader1 = MockLeafReader::new(0);
+        let leaf_reader2 = MockLeafReader::new(10);
+        let index_reader = MockIndexReader::new(vec![leaf_reader1, leaf_reader2]);
+        let leaf_reader_contexts = index_reader.leaves();
+        let mut collector = TopDocsCollector::new(3);
+
+        let mut leaf_collectors = vec![];
+        for (ctx, docs) in leaf_reader_contexts
+            .iter()
+            .zip(vec![vec![1, 4, 6], vec![2, 3, 5, 7]])
+        {
+            let mut leaf_collector = collector.new_collector(ctx).unwrap();
+            let mut scorer = create_mock_scorer(docs);
+            loop {
+                let doc = scorer.next().unwrap();
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                leaf_collector.collect(doc, &mut scorer).unwrap();
+            }
+            leaf_collector.finish_leaf().unwrap();
+            leaf_collectors.push(leaf_collector);
+        }
+        collector.reduce(leaf_collectors).unwrap();
+
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 7);
+
+        let score_docs = top_docs.score_docs();
+        assert_eq!(score_docs.len(), 3);
+        assert_eq!(score_docs[0].doc_id(), 7);
+        assert_eq!(score_docs[1].doc_id(), 6);
+        assert_eq!(score_docs[2].doc_id(), 5);
+    }
+
+    #[test]
+    fn test_ties_out_of_order() {
+        let mut collector = TopDocsCollector::new(2);
+        assert!(collector.accepts_docs_out_of_order());
+        for &doc in &[5, 3, 9, 1, 4] {
+            collector.add_doc(doc, 1.0);
+        }
+        collector.add_doc(7, 0.5);
+
+        let top_docs = collector.top_docs();
+        assert_eq!(top_docs.total_hits(), 6);
+        let docs: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        assert_eq!(docs, vec![1, 3]);
+    }
 }
@@ -0,0 +1,607 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::doc::{DoublePoint, FloatPoint, IntPoint, LongPoint};
+use core::index::{IntersectVisitor, LeafReaderContext, PointValues, Relation};
+use core::search::collector;
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::field_comparator::{ComparatorValue, FieldComparator, FieldComparatorEnum};
+use core::search::sort::Sort;
+use core::search::sort_field::{SortField, SortFieldType};
+use core::search::top_docs::{compare_sort_values, FieldDoc, ScoreDocHit, TopDocs, TopFieldDocs};
+use core::search::Scorer;
+use core::util::bit_set::{BitSet, FixedBitSet};
+use core::util::{Bits, DocId, VariantValue};
+use error::{ErrorKind, Result};
+
+use std::cmp::Ordering;
+use std::f32;
+
+/// The competitive docs of a leaf are computed again from the points once the
+/// bottom of the queue changed and at least this many docs were collected, as
+/// each computation intersects the BKD tree of the leaf.
+const MIN_DOCS_BETWEEN_UPDATES: usize = 256;
+
+/// The points of a leaf, type erased so the collector doesn't depend on the
+/// codec.
+trait LeafPoints {
+    fn intersect_competitive(&self, field: &str, visitor: &mut CompetitiveVisitor) -> Result<()>;
+}
+
+impl<P: PointValues> LeafPoints for P {
+    fn intersect_competitive(&self, field: &str, visitor: &mut CompetitiveVisitor) -> Result<()> {
+        self.intersect(field, visitor)
+    }
+}
+
+/// Collects the docs whose point is on or before the bound in sort order.
+struct CompetitiveVisitor {
+    bound: Vec<u8>,
+    reverse: bool,
+    docs: FixedBitSet,
+    max_doc: DocId,
+}
+
+impl CompetitiveVisitor {
+    fn is_competitive(&self, packed_value: &[u8]) -> bool {
+        if self.reverse {
+            packed_value >= self.bound.as_slice()
+        } else {
+            packed_value <= self.bound.as_slice()
+        }
+    }
+
+    fn add(&mut self, doc_id: DocId) {
+        self.docs.set(doc_id as usize);
+        self.max_doc = self.max_doc.max(doc_id);
+    }
+}
+
+impl IntersectVisitor for CompetitiveVisitor {
+    fn visit(&mut self, doc_id: DocId) -> Result<()> {
+        self.add(doc_id);
+        Ok(())
+    }
+
+    fn visit_by_packed_value(&mut self, doc_id: DocId, packed_value: &[u8]) -> Result<()> {
+        if self.is_competitive(packed_value) {
+            self.add(doc_id);
+        }
+        Ok(())
+    }
+
+    fn compare(&self, min_packed_value: &[u8], max_packed_value: &[u8]) -> Relation {
+        let bound = self.bound.as_slice();
+        if self.reverse {
+            if max_packed_value < bound {
+                Relation::CellOutsideQuery
+            } else if min_packed_value >= bound {
+                Relation::CellInsideQuery
+            } else {
+                Relation::CellCrossesQuery
+            }
+        } else if min_packed_value > bound {
+            Relation::CellOutsideQuery
+        } else if max_packed_value <= bound {
+            Relation::CellInsideQuery
+        } else {
+            Relation::CellCrossesQuery
+        }
+    }
+}
+
+/// Skips the docs whose value of the first sort field can't be competitive
+/// anymore, by intersecting the points of the field with the range of values
+/// sorting on or before the bottom of the queue.
+struct PointsSkipper {
+    field: String,
+    field_type: SortFieldType,
+    reverse: bool,
+    // the value sorted for the docs without a value
+    missing_value: VariantValue,
+    points: Option<Box<dyn LeafPoints>>,
+    max_doc: usize,
+    // None if all the docs of the leaf may be competitive
+    competitive: Option<FixedBitSet>,
+    // the greatest competitive doc of the leaf
+    last_competitive: DocId,
+    // the bottom value the competitive docs were computed for
+    bottom: Option<VariantValue>,
+    docs_since_update: usize,
+}
+
+// the points reader of a leaf is only used by the thread collecting the leaf
+unsafe impl Send for PointsSkipper {}
+
+impl PointsSkipper {
+    /// Returns a skipper if the first sort field is a numeric doc values field.
+    fn new(sort_field: &SortField) -> Option<PointsSkipper> {
+        let missing_value = match sort_field {
+            SortField::Simple(s) => match s.field_type() {
+                SortFieldType::Int => VariantValue::Int(0),
+                SortFieldType::Long => VariantValue::Long(0),
+                SortFieldType::Float => VariantValue::Float(0.0),
+                SortFieldType::Double => VariantValue::Double(0.0),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(PointsSkipper {
+            field: sort_field.field().to_string(),
+            field_type: sort_field.field_type(),
+            reverse: sort_field.is_reverse(),
+            missing_value: sort_field.missing_value().cloned().unwrap_or(missing_value),
+            points: None,
+            max_doc: 0,
+            competitive: None,
+            last_competitive: 0,
+            bottom: None,
+            docs_since_update: 0,
+        })
+    }
+
+    fn num_bytes(&self) -> usize {
+        match self.field_type {
+            SortFieldType::Int | SortFieldType::Float => 4,
+            _ => 8,
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) {
+        let leaf = reader.reader;
+        self.max_doc = leaf.max_doc() as usize;
+        self.competitive = None;
+        self.bottom = None;
+        self.docs_since_update = 0;
+        // the points must hold the same single value per doc as the doc values
+        self.points = match leaf.field_info(&self.field) {
+            Some(info)
+                if info.point_dimension_count == 1
+                    && info.point_num_bytes as usize == self.num_bytes() =>
+            {
+                leaf.point_values()
+                    .map(|points| Box::new(points) as Box<dyn LeafPoints>)
+            }
+            _ => None,
+        };
+    }
+
+    fn encode(&self, value: &VariantValue, dest: &mut [u8]) {
+        match *value {
+            VariantValue::Int(v) => IntPoint::encode_dimension(v, dest),
+            VariantValue::Long(v) => LongPoint::encode_dimension(v, dest),
+            VariantValue::Float(v) => FloatPoint::encode_dimension(v, dest),
+            VariantValue::Double(v) => DoublePoint::encode_dimension(v, dest),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Called with the new bottom value of the full queue.
+    fn update(&mut self, bottom: &VariantValue) -> Result<()> {
+        if self.points.is_none() || self.bottom.as_ref() == Some(bottom) {
+            return Ok(());
+        }
+        if self.bottom.is_some() && self.docs_since_update < MIN_DOCS_BETWEEN_UPDATES {
+            return Ok(());
+        }
+        self.bottom = Some(bottom.clone());
+        self.docs_since_update = 0;
+
+        // the docs without a value can't be skipped while the missing value
+        // is competitive
+        let ord = self.missing_value.cmp(bottom);
+        if (self.reverse && ord != Ordering::Less) || (!self.reverse && ord != Ordering::Greater) {
+            self.competitive = None;
+            return Ok(());
+        }
+
+        let mut bound = vec![0u8; self.num_bytes()];
+        self.encode(bottom, &mut bound);
+        let mut visitor = CompetitiveVisitor {
+            bound,
+            reverse: self.reverse,
+            docs: FixedBitSet::new(self.max_doc.max(1)),
+            max_doc: -1,
+        };
+        self.points
+            .as_ref()
+            .unwrap()
+            .intersect_competitive(&self.field, &mut visitor)?;
+        self.competitive = Some(visitor.docs);
+        self.last_competitive = visitor.max_doc;
+        Ok(())
+    }
+
+    /// Returns true if the doc can't be competitive.
+    fn skip(&mut self, doc: DocId) -> Result<bool> {
+        self.docs_since_update += 1;
+        match self.competitive {
+            Some(ref docs) => Ok(!docs.get(doc as usize)?),
+            None => Ok(false),
+        }
+    }
+
+    /// Returns true if no doc after `doc` can be competitive.
+    fn is_exhausted(&self, doc: DocId) -> bool {
+        self.competitive.is_some() && doc >= self.last_competitive
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    slot: usize,
+    doc: DocId,
+}
+
+/// The queue of the top hits of a `TopFieldCollector`, whose values are kept
+/// by the comparators in the slot of each entry.
+struct FieldValueHitQueue {
+    comparators: Vec<FieldComparatorEnum>,
+    reverse: Vec<bool>,
+    // a binary heap whose top is the least competitive hit
+    heap: Vec<Entry>,
+    scores: Vec<f32>,
+    num_hits: usize,
+    needs_scores: bool,
+    track_total_hits: bool,
+    total_hits: usize,
+    doc_base: DocId,
+    skipper: Option<PointsSkipper>,
+}
+
+impl FieldValueHitQueue {
+    fn new(sort: &Sort, num_hits: usize, track_total_hits: bool) -> FieldValueHitQueue {
+        let fields = sort.get_sort();
+        FieldValueHitQueue {
+            comparators: fields
+                .iter()
+                .map(|f| f.get_comparator(num_hits, f.missing_value()))
+                .collect(),
+            reverse: fields.iter().map(SortField::is_reverse).collect(),
+            heap: Vec::with_capacity(num_hits),
+            scores: vec![f32::NAN; num_hits],
+            num_hits,
+            needs_scores: sort.needs_scores(),
+            track_total_hits,
+            total_hits: 0,
+            doc_base: 0,
+            skipper: fields.first().and_then(PointsSkipper::new),
+        }
+    }
+
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        for comparator in &mut self.comparators {
+            comparator.get_information_from_reader(reader)?;
+        }
+        if let Some(ref mut skipper) = self.skipper {
+            skipper.set_next_reader(reader);
+            if self.heap.len() == self.num_hits && self.num_hits > 0 {
+                let bottom = self.comparators[0].value(self.heap[0].slot);
+                skipper.update(&bottom)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sort order of two entries, ties are broken by doc id.
+    fn compare(&self, a: Entry, b: Entry) -> Ordering {
+        for (comparator, &reverse) in self.comparators.iter().zip(&self.reverse) {
+            let ord = comparator.compare(a.slot, b.slot);
+            let ord = if reverse { ord.reverse() } else { ord };
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        a.doc.cmp(&b.doc)
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.compare(self.heap[i], self.heap[parent]) != Ordering::Greater {
+                break;
+            }
+            self.heap.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let left = 2 * i + 1;
+            if left >= self.heap.len() {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < self.heap.len()
+                && self.compare(self.heap[right], self.heap[left]) == Ordering::Greater
+            {
+                right
+            } else {
+                left
+            };
+            if self.compare(self.heap[child], self.heap[i]) != Ordering::Greater {
+                break;
+            }
+            self.heap.swap(i, child);
+            i = child;
+        }
+    }
+
+    /// Returns true if the doc sorts before the bottom of the full queue.
+    fn is_competitive(&self, value: ComparatorValue) -> Result<bool> {
+        for (comparator, &reverse) in self.comparators.iter().zip(&self.reverse) {
+            let ord = comparator.compare_bottom(value)?;
+            match if reverse { ord.reverse() } else { ord } {
+                Ordering::Greater => return Ok(true),
+                Ordering::Less => return Ok(false),
+                Ordering::Equal => {}
+            }
+        }
+        // docs are collected in order, so the bottom has a smaller doc id
+        Ok(false)
+    }
+
+    fn update_bottom(&mut self) -> Result<()> {
+        let slot = self.heap[0].slot;
+        for comparator in &mut self.comparators {
+            comparator.set_bottom(slot);
+        }
+        if let Some(ref mut skipper) = self.skipper {
+            skipper.update(&self.comparators[0].value(slot))?;
+        }
+        Ok(())
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.total_hits += 1;
+        if self.num_hits == 0 {
+            return Ok(());
+        }
+        let skip = match self.skipper {
+            Some(ref mut skipper) => skipper.skip(doc)?,
+            None => false,
+        };
+        if !skip {
+            self.add(doc, scorer)?;
+        }
+        if !self.track_total_hits {
+            if let Some(ref skipper) = self.skipper {
+                if skipper.is_exhausted(doc) {
+                    bail!(ErrorKind::Collector(
+                        collector::ErrorKind::LeafCollectionTerminated,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        let score = if self.needs_scores {
+            scorer.score()?
+        } else {
+            f32::NAN
+        };
+        let value = if self.needs_scores {
+            ComparatorValue::DocScore(doc, score)
+        } else {
+            ComparatorValue::Doc(doc)
+        };
+        if self.heap.len() < self.num_hits {
+            let slot = self.heap.len();
+            for comparator in &mut self.comparators {
+                comparator.copy(slot, value)?;
+            }
+            self.scores[slot] = score;
+            self.heap.push(Entry {
+                slot,
+                doc: doc + self.doc_base,
+            });
+            let last = self.heap.len() - 1;
+            self.sift_up(last);
+            if self.heap.len() == self.num_hits {
+                self.update_bottom()?;
+            }
+        } else if self.is_competitive(value)? {
+            let slot = self.heap[0].slot;
+            for comparator in &mut self.comparators {
+                comparator.copy(slot, value)?;
+            }
+            self.scores[slot] = score;
+            self.heap[0].doc = doc + self.doc_base;
+            self.sift_down(0);
+            self.update_bottom()?;
+        }
+        Ok(())
+    }
+
+    fn hits(&self) -> Vec<FieldDoc> {
+        self.heap
+            .iter()
+            .map(|e| {
+                let fields = self.comparators.iter().map(|c| c.value(e.slot)).collect();
+                FieldDoc::new(e.doc, self.scores[e.slot], fields)
+            })
+            .collect()
+    }
+}
+
+/// A collector of the top hits sorted by a `Sort`, the values of the sort
+/// fields are returned in the `FieldDoc`s of the hits.
+///
+/// If the first sort field is a numeric field whose values are also indexed
+/// as points, once the queue is full the docs whose value sorts after the
+/// bottom of the queue are skipped without reading their doc values: the
+/// competitive docs of the leaf are found by intersecting the BKD tree with
+/// the range of values sorting on or before the bottom, which shrinks as
+/// better hits are collected. The points must hold the same single value per
+/// doc as the doc values, fields indexed with other point types are ignored.
+///
+/// If the total hits don't need to be tracked, the collection of a leaf is
+/// terminated once no doc after the current one is competitive anymore, so
+/// `total_hits` is then only a lower bound.
+pub struct TopFieldCollector {
+    sort: Sort,
+    queue: FieldValueHitQueue,
+    // the hits reduced from the leaf collectors of a parallel search
+    reduced: Vec<FieldDoc>,
+}
+
+impl TopFieldCollector {
+    pub fn new(sort: Sort, num_hits: usize, track_total_hits: bool) -> TopFieldCollector {
+        let queue = FieldValueHitQueue::new(&sort, num_hits, track_total_hits);
+        TopFieldCollector {
+            sort,
+            queue,
+            reduced: vec![],
+        }
+    }
+
+    /// Disables the skipping of non competitive docs with points, e.g. if the
+    /// points of the sort field don't hold the same values as its doc values.
+    pub fn disable_points_skipping(&mut self) {
+        self.queue.skipper = None;
+    }
+
+    /// Returns the top docs that were collected by this collector.
+    pub fn top_docs(&mut self) -> TopDocs {
+        let sort = self.sort.get_sort();
+        let mut hits: Vec<ScoreDocHit> = self
+            .queue
+            .hits()
+            .into_iter()
+            .chain(self.reduced.drain(..))
+            .map(ScoreDocHit::Field)
+            .collect();
+        hits.sort_by(|a, b| {
+            compare_sort_values(sort, a, b).then_with(|| a.doc_id().cmp(&b.doc_id()))
+        });
+        hits.truncate(self.queue.num_hits);
+        let max_score = hits.iter().fold(f32::NAN, |max, hit| max.max(hit.score()));
+        TopDocs::Field(TopFieldDocs {
+            total_hits: self.queue.total_hits,
+            score_docs: hits,
+            max_score,
+            fields: sort.to_vec(),
+        })
+    }
+}
+
+impl SearchCollector for TopFieldCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.queue.set_next_reader(reader)
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for TopFieldCollector {
+    type Collector = TopFieldLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<TopFieldLeafCollector> {
+        let mut queue =
+            FieldValueHitQueue::new(&self.sort, self.queue.num_hits, self.queue.track_total_hits);
+        if self.queue.skipper.is_none() {
+            queue.skipper = None;
+        }
+        queue.set_next_reader(reader)?;
+        Ok(TopFieldLeafCollector { queue })
+    }
+
+    fn reduce(&mut self, collectors: Vec<TopFieldLeafCollector>) -> Result<()> {
+        for collector in collectors {
+            self.queue.total_hits += collector.queue.total_hits;
+            self.reduced.extend(collector.queue.hits());
+        }
+        Ok(())
+    }
+}
+
+impl Collector for TopFieldCollector {
+    fn needs_scores(&self) -> bool {
+        self.queue.needs_scores
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.queue.collect(doc, scorer)
+    }
+}
+
+/// Leaf collector of `TopFieldCollector` for parallel search, which keeps the
+/// top hits of a single segment until they are reduced.
+pub struct TopFieldLeafCollector {
+    queue: FieldValueHitQueue,
+}
+
+impl Collector for TopFieldLeafCollector {
+    fn needs_scores(&self) -> bool {
+        self.queue.needs_scores
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, scorer: &mut S) -> Result<()> {
+        self.queue.collect(doc, scorer)
+    }
+}
+
+impl ParallelLeafCollector for TopFieldLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn visitor(bound: i64, reverse: bool) -> CompetitiveVisitor {
+        CompetitiveVisitor {
+            bound: LongPoint::pack(&[bound]),
+            reverse,
+            docs: FixedBitSet::new(4),
+            max_doc: -1,
+        }
+    }
+
+    #[test]
+    fn test_competitive_visitor() {
+        let (min, max) = (LongPoint::pack(&[-10]), LongPoint::pack(&[10]));
+
+        let mut v = visitor(0, false);
+        assert!(v.compare(&min, &max) == Relation::CellCrossesQuery);
+        assert!(v.compare(&min, &min) == Relation::CellInsideQuery);
+        assert!(v.compare(&max, &max) == Relation::CellOutsideQuery);
+        v.visit_by_packed_value(1, &min).unwrap();
+        v.visit_by_packed_value(2, &max).unwrap();
+        v.visit_by_packed_value(3, &LongPoint::pack(&[0])).unwrap();
+        assert!(v.docs.get(1).unwrap() && !v.docs.get(2).unwrap());
+        assert_eq!(v.max_doc, 3);
+
+        let mut v = visitor(0, true);
+        assert!(v.compare(&min, &max) == Relation::CellCrossesQuery);
+        assert!(v.compare(&max, &max) == Relation::CellInsideQuery);
+        assert!(v.compare(&min, &min) == Relation::CellOutsideQuery);
+        v.visit_by_packed_value(1, &min).unwrap();
+        v.visit_by_packed_value(2, &max).unwrap();
+        assert!(!v.docs.get(1).unwrap() && v.docs.get(2).unwrap());
+    }
+}
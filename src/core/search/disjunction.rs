@@ -370,17 +370,12 @@ impl<C: Codec> Weight<C> for DisjunctionMaxWeight<C> {
         if matched {
             let score = max + (sum - max) * self.tie_breaker_multiplier;
             let desc = if self.tie_breaker_multiplier == 0.0f32 {
-                "max of:"
+                "max of:".to_string()
             } else {
-                "max plus "
+                format!("max plus {} times others of:", self.tie_breaker_multiplier)
             };
 
-            Ok(Explanation::new(
-                true,
-                score,
-                format!("{} {} times others of:", desc, self.tie_breaker_multiplier),
-                subs,
-            ))
+            Ok(Explanation::new(true, score, desc, subs))
         } else {
             Ok(Explanation::new(
                 false,
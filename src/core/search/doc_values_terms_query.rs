@@ -0,0 +1,323 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReader, LeafReaderContext, NO_MORE_ORDS};
+use core::search::explanation::Explanation;
+use core::search::match_all::ConstantScoreScorer;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{DocIdSet, DocIterator, Query, Scorer, Weight};
+use core::util::bit_set::{BitSet, FixedBitSet};
+use core::util::{Bits, DocId, DocIdSetBuilder};
+use error::Result;
+
+use std::fmt;
+
+const DOC_VALUES_TERMS: &str = "doc_values_terms";
+
+/// A query matching the docs having any of the `terms` as a value of the
+/// sorted or sorted set doc values of a field, e.g. the docs tagged with one
+/// of some keywords added with `SortedSetDocValuesField`.
+///
+/// Unlike a `BooleanQuery` of `TermQuery`s the field doesn't need to be
+/// indexed, the terms are looked up in the doc values terms dictionary of
+/// each segment and the values of every doc are checked, so it's best used as
+/// a filter on a field already having doc values for faceting or sorting.
+pub struct DocValuesTermsQuery {
+    field: String,
+    terms: Vec<Vec<u8>>,
+}
+
+impl DocValuesTermsQuery {
+    pub fn new(field: String, mut terms: Vec<Vec<u8>>) -> DocValuesTermsQuery {
+        terms.sort();
+        terms.dedup();
+        DocValuesTermsQuery { field, terms }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// The distinct terms, sorted.
+    pub fn terms(&self) -> &[Vec<u8>] {
+        &self.terms
+    }
+}
+
+impl<C: Codec> Query<C> for DocValuesTermsQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(DocValuesTermsWeight {
+            field: self.field.clone(),
+            terms: self.terms.clone(),
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn query_type(&self) -> &'static str {
+        DOC_VALUES_TERMS
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for DocValuesTermsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let terms: Vec<String> = self
+            .terms
+            .iter()
+            .map(|t| String::from_utf8_lossy(t).into_owned())
+            .collect();
+        write!(
+            f,
+            "DocValuesTermsQuery(field: {}, terms: {:?})",
+            &self.field, terms
+        )
+    }
+}
+
+struct DocValuesTermsWeight {
+    field: String,
+    terms: Vec<Vec<u8>>,
+    weight: f32,
+    norm: f32,
+}
+
+impl DocValuesTermsWeight {
+    fn build_matching_doc_set<R: LeafReader + ?Sized>(
+        &self,
+        reader: &R,
+    ) -> Result<Option<DocIdSetBuilder>> {
+        let doc_values_type = match reader.field_info(&self.field) {
+            Some(info) => info.doc_values_type,
+            None => return Ok(None),
+        };
+        let max_doc = reader.max_doc();
+
+        match doc_values_type {
+            DocValuesType::Sorted => {
+                let values = reader.get_sorted_doc_values(&self.field)?;
+                let ords = match self.matching_ords(values.get_value_count(), |term| {
+                    Ok(i64::from(values.lookup_term(term)?))
+                })? {
+                    Some(ords) => ords,
+                    None => return Ok(None),
+                };
+                let mut result = DocIdSetBuilder::with_max_doc(max_doc);
+                for doc in 0..max_doc {
+                    let ord = values.get_ord(doc)?;
+                    if ord >= 0 && ords.get(ord as usize)? {
+                        result.grow(1);
+                        result.add_doc(doc);
+                    }
+                }
+                Ok(Some(result))
+            }
+            DocValuesType::SortedSet => {
+                let values = reader.get_sorted_set_doc_values(&self.field)?;
+                let ords = match self
+                    .matching_ords(values.get_value_count(), |term| values.lookup_term(term))?
+                {
+                    Some(ords) => ords,
+                    None => return Ok(None),
+                };
+                let mut result = DocIdSetBuilder::with_max_doc(max_doc);
+                for doc in 0..max_doc {
+                    let mut ctx = values.set_document(doc)?;
+                    loop {
+                        let ord = values.next_ord(&mut ctx)?;
+                        if ord == NO_MORE_ORDS {
+                            break;
+                        }
+                        if ords.get(ord as usize)? {
+                            result.grow(1);
+                            result.add_doc(doc);
+                            break;
+                        }
+                    }
+                }
+                Ok(Some(result))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// The ordinals of the terms in the segment, None if it has none of them.
+    fn matching_ords<F>(&self, value_count: usize, lookup_term: F) -> Result<Option<FixedBitSet>>
+    where
+        F: Fn(&[u8]) -> Result<i64>,
+    {
+        let mut ords = FixedBitSet::new(value_count);
+        let mut any = false;
+        for term in &self.terms {
+            let ord = lookup_term(term)?;
+            if ord >= 0 {
+                ords.set(ord as usize);
+                any = true;
+            }
+        }
+        Ok(if any { Some(ords) } else { None })
+    }
+}
+
+impl<C: Codec> Weight<C> for DocValuesTermsWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(mut builder) = self.build_matching_doc_set(leaf_reader_ctx.reader)? {
+            if let Some(iterator) = builder.build().iterator()? {
+                let cost = iterator.cost();
+                return Ok(Some(Box::new(ConstantScoreScorer::new(
+                    self.weight,
+                    iterator,
+                    cost,
+                ))));
+            }
+        }
+        Ok(None)
+    }
+
+    fn query_type(&self) -> &'static str {
+        DOC_VALUES_TERMS
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matched = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matched {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.weight, "boost".to_string(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".to_string(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("no matching doc value for field {}", &self.field),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for DocValuesTermsWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DocValuesTermsWeight(field: {}, terms: {}, weight: {}, norm: {})",
+            &self.field,
+            self.terms.len(),
+            self.weight,
+            self.norm
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{NumericDocValuesField, SortedSetDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexReader, IndexWriter};
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_terms_across_segments() {
+        let path = ::std::env::temp_dir().join("rucene_doc_values_terms_query");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // "c" is the ordinal 2 of the first segment and 1 of the second one,
+        // "b" is only in the first segment and "e" only in the second one
+        let segments: [&[&[&str]]; 2] = [
+            &[&["a", "c"], &["b"], &["c"], &[]],
+            &[&["aa"], &["c", "e"], &["e"]],
+        ];
+        let mut id = 0;
+        for docs in &segments {
+            for tags in docs.iter() {
+                let mut fields: Vec<Box<dyn Fieldable>> =
+                    vec![Box::new(NumericDocValuesField::new("id", id))];
+                for tag in tags.iter() {
+                    fields.push(Box::new(SortedSetDocValuesField::new(
+                        "tags",
+                        tag.as_bytes(),
+                    )));
+                }
+                writer.add_document(fields).unwrap();
+                id += 1;
+            }
+            writer.commit().unwrap();
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+        let searcher = DefaultIndexSearcher::new(&reader);
+        let count = |field: &str, terms: &[&str]| {
+            let terms = terms.iter().map(|t| t.as_bytes().to_vec()).collect();
+            searcher
+                .count(&DocValuesTermsQuery::new(field.to_string(), terms))
+                .unwrap()
+        };
+
+        assert_eq!(count("tags", &["c"]), 3);
+        assert_eq!(count("tags", &["b", "e"]), 3);
+        // a doc matching several terms is counted once
+        assert_eq!(count("tags", &["a", "c", "c"]), 3);
+        assert_eq!(count("tags", &["aa", "b"]), 2);
+        assert_eq!(count("tags", &["z"]), 0);
+        assert_eq!(count("tags", &[]), 0);
+        // not sorted or sorted set doc values
+        assert_eq!(count("id", &["c"]), 0);
+        assert_eq!(count("unknown", &["c"]), 0);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
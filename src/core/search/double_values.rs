@@ -0,0 +1,169 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, NumericDocValuesRef};
+use core::search::expression::Expression;
+use core::search::sort_field::{
+    DoubleValuesSortField, SortField, SortFieldType, SortedNumericSelector,
+    SortedNumericSelectorType,
+};
+use core::util::DocId;
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::fmt;
+use std::sync::Arc;
+
+/// A source of per-document double values, computed from doc values fields,
+/// the score of the document or an expression combining them.
+///
+/// A source is independent of any segment, `get_values` binds it to a leaf
+/// reader. It can be used to sort hits, see `sort_field`, or to score them,
+/// see `FunctionScoreQuery`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DoubleValuesSource {
+    /// the score of the document
+    Score,
+    Constant(f64),
+    /// the value of a numeric or sorted numeric doc values field, documents
+    /// without a value get 0
+    Field {
+        field: String,
+        field_type: SortFieldType,
+    },
+    /// an expression and the sources bound to its variables, see
+    /// `Expression::values_source`
+    Expression(Arc<Expression>, Vec<DoubleValuesSource>),
+}
+
+impl Eq for DoubleValuesSource {}
+
+impl DoubleValuesSource {
+    /// A source reading the numeric doc values of `field`, which must be one of
+    /// the numeric sort types.
+    pub fn from_field(field: &str, field_type: SortFieldType) -> Result<DoubleValuesSource> {
+        match field_type {
+            SortFieldType::Int
+            | SortFieldType::Long
+            | SortFieldType::Float
+            | SortFieldType::Double => Ok(DoubleValuesSource::Field {
+                field: field.to_string(),
+                field_type,
+            }),
+            _ => bail!(IllegalArgument(format!(
+                "field {} must have a numeric type, got {:?}",
+                field, field_type
+            ))),
+        }
+    }
+
+    /// Whether the values depend on the score of the document.
+    pub fn needs_scores(&self) -> bool {
+        match self {
+            DoubleValuesSource::Score => true,
+            DoubleValuesSource::Constant(_) | DoubleValuesSource::Field { .. } => false,
+            DoubleValuesSource::Expression(_, sources) => sources.iter().any(|s| s.needs_scores()),
+        }
+    }
+
+    pub fn get_values<C: Codec>(&self, reader: &LeafReaderContext<'_, C>) -> Result<DoubleValues> {
+        let values = match self {
+            DoubleValuesSource::Score => DoubleValues::Score,
+            DoubleValuesSource::Constant(v) => DoubleValues::Constant(*v),
+            DoubleValuesSource::Field { field, field_type } => {
+                let leaf = reader.reader;
+                match leaf.field_info(field).map(|info| info.doc_values_type) {
+                    Some(DocValuesType::Numeric) => {
+                        DoubleValues::Field(leaf.get_numeric_doc_values(field)?, *field_type)
+                    }
+                    Some(DocValuesType::SortedNumeric) => DoubleValues::Field(
+                        SortedNumericSelector::wrap(
+                            leaf.get_sorted_numeric_doc_values(field)?,
+                            SortedNumericSelectorType::Min,
+                            *field_type,
+                        )?,
+                        *field_type,
+                    ),
+                    // the field is not indexed in this segment
+                    None | Some(DocValuesType::Null) => DoubleValues::Constant(0.0),
+                    Some(t) => bail!(IllegalArgument(format!(
+                        "field {} has non-numeric doc values {:?}",
+                        field, t
+                    ))),
+                }
+            }
+            DoubleValuesSource::Expression(expression, sources) => {
+                let mut variables = Vec::with_capacity(sources.len());
+                for source in sources {
+                    variables.push(source.get_values(reader)?);
+                }
+                DoubleValues::Expression(Arc::clone(expression), variables)
+            }
+        };
+        Ok(values)
+    }
+
+    /// A `SortField` sorting hits by these values, ascending unless `reverse`.
+    pub fn sort_field(&self, reverse: bool) -> SortField {
+        SortField::DoubleValues(DoubleValuesSortField::new(self.clone(), reverse))
+    }
+}
+
+impl fmt::Display for DoubleValuesSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DoubleValuesSource::Score => write!(f, "score"),
+            DoubleValuesSource::Constant(v) => write!(f, "{}", v),
+            DoubleValuesSource::Field { field, .. } => write!(f, "{}", field),
+            DoubleValuesSource::Expression(expression, _) => write!(f, "{}", expression),
+        }
+    }
+}
+
+/// The values of a `DoubleValuesSource` bound to a leaf reader.
+pub enum DoubleValues {
+    Score,
+    Constant(f64),
+    Field(NumericDocValuesRef, SortFieldType),
+    Expression(Arc<Expression>, Vec<DoubleValues>),
+}
+
+impl DoubleValues {
+    /// Returns the value of `doc`, `score` is the score of `doc` and is only
+    /// used if the source needs scores.
+    pub fn double_value(&self, doc: DocId, score: f32) -> Result<f64> {
+        match self {
+            DoubleValues::Score => Ok(f64::from(score)),
+            DoubleValues::Constant(v) => Ok(*v),
+            DoubleValues::Field(values, field_type) => {
+                Ok(numeric_to_double(values.get(doc)?, *field_type))
+            }
+            DoubleValues::Expression(expression, variables) => {
+                expression.evaluate(&mut |i| variables[i].double_value(doc, score))
+            }
+        }
+    }
+}
+
+/// Converts a value read from the numeric doc values of a field of
+/// `field_type` to a double.
+pub fn numeric_to_double(raw: i64, field_type: SortFieldType) -> f64 {
+    match field_type {
+        SortFieldType::Int => f64::from(raw as i32),
+        SortFieldType::Float => f64::from(f32::from_bits(raw as u32)),
+        SortFieldType::Double => f64::from_bits(raw as u64),
+        _ => raw as f64,
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{
+    DocValuesType, IndexOptions, IntersectVisitor, LeafReader, LeafReaderContext, PointValues,
+    Relation, TermIterator, Terms,
+};
+use core::search::explanation::Explanation;
+use core::search::match_all::ConstantScoreScorer;
+use core::search::posting_iterator::PostingIteratorFlags;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{DocIdSet, DocIterator, Query, Scorer, Weight};
+use core::util::{DocId, DocIdSetBuilder};
+use error::Result;
+
+use std::fmt;
+
+const EXISTS: &str = "exists";
+
+/// A query matching the docs having a value for a field, whatever the value.
+///
+/// The docs are found per segment from the doc values of the field if it has
+/// some, else from its norms, its postings or its points, in this order. A
+/// field which is only stored matches no doc. Use it in a `BooleanQuery` as a
+/// `must_not` clause to match the docs missing the field.
+pub struct ExistsQuery {
+    field: String,
+}
+
+impl ExistsQuery {
+    pub fn new(field: String) -> ExistsQuery {
+        ExistsQuery { field }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+}
+
+impl<C: Codec> Query<C> for ExistsQuery {
+    fn create_weight(
+        &self,
+        _searcher: &dyn SearchPlanBuilder<C>,
+        _needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        Ok(Box::new(ExistsWeight {
+            field: self.field.clone(),
+            weight: 0f32,
+            norm: 1f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        vec![]
+    }
+
+    fn query_type(&self) -> &'static str {
+        EXISTS
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+impl fmt::Display for ExistsQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExistsQuery(field: {})", &self.field)
+    }
+}
+
+struct ExistsWeight {
+    field: String,
+    weight: f32,
+    norm: f32,
+}
+
+impl ExistsWeight {
+    fn build_matching_doc_set<R: LeafReader + ?Sized>(
+        &self,
+        reader: &R,
+    ) -> Result<Option<DocIdSetBuilder>> {
+        let field_info = match reader.field_info(&self.field) {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        let max_doc = reader.max_doc();
+
+        if field_info.doc_values_type != DocValuesType::Null {
+            let docs_with_field = reader.get_docs_with_field(&self.field)?;
+            let mut result = DocIdSetBuilder::with_max_doc(max_doc);
+            for doc in 0..max_doc {
+                if docs_with_field.get(doc as usize)? {
+                    result.grow(1);
+                    result.add_doc(doc);
+                }
+            }
+            return Ok(Some(result));
+        }
+
+        if field_info.has_norms() {
+            if let Some(norms) = reader.norm_values(&self.field)? {
+                // a doc without the field has no norm
+                let mut result = DocIdSetBuilder::with_max_doc(max_doc);
+                for doc in 0..max_doc {
+                    if norms.get(doc)? != 0 {
+                        result.grow(1);
+                        result.add_doc(doc);
+                    }
+                }
+                return Ok(Some(result));
+            }
+        }
+
+        if field_info.index_options != IndexOptions::Null {
+            if let Some(terms) = reader.terms(&self.field)? {
+                let mut result = DocIdSetBuilder::from_terms(max_doc, &terms)?;
+                let mut iter = terms.iterator()?;
+                while iter.next()?.is_some() {
+                    let mut postings = iter.postings_with_flags(PostingIteratorFlags::NONE)?;
+                    result.add(&mut postings)?;
+                }
+                return Ok(Some(result));
+            }
+        }
+
+        if field_info.point_dimension_count > 0 {
+            if let Some(values) = reader.point_values() {
+                let mut result = DocIdSetBuilder::from_values(max_doc, &values, &self.field)?;
+                {
+                    let mut visitor = AllPointsVisitor {
+                        doc_id_set_builder: &mut result,
+                    };
+                    values.intersect(&self.field, &mut visitor)?;
+                }
+                return Ok(Some(result));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl<C: Codec> Weight<C> for ExistsWeight {
+    fn create_scorer(
+        &self,
+        leaf_reader_ctx: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        if let Some(mut builder) = self.build_matching_doc_set(leaf_reader_ctx.reader)? {
+            if let Some(iterator) = builder.build().iterator()? {
+                let cost = iterator.cost();
+                return Ok(Some(Box::new(ConstantScoreScorer::new(
+                    self.weight,
+                    iterator,
+                    cost,
+                ))));
+            }
+        }
+        Ok(None)
+    }
+
+    fn query_type(&self) -> &'static str {
+        EXISTS
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        self.weight = norm * boost;
+        self.norm = norm;
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight * self.weight
+    }
+
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let matched = match self.create_scorer(reader)? {
+            Some(mut scorer) => scorer.advance(doc)? == doc,
+            None => false,
+        };
+        if matched {
+            Ok(Explanation::new(
+                true,
+                self.weight,
+                format!("{}, product of:", self),
+                vec![
+                    Explanation::new(true, self.weight, "boost".to_string(), vec![]),
+                    Explanation::new(true, self.norm, "queryNorm".to_string(), vec![]),
+                ],
+            ))
+        } else {
+            Ok(Explanation::new(
+                false,
+                0f32,
+                format!("no value for field {}", &self.field),
+                vec![],
+            ))
+        }
+    }
+}
+
+impl fmt::Display for ExistsWeight {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ExistsWeight(field: {}, weight: {}, norm: {})",
+            &self.field, self.weight, self.norm
+        )
+    }
+}
+
+/// Visits all the points of a field.
+struct AllPointsVisitor<'a> {
+    doc_id_set_builder: &'a mut DocIdSetBuilder,
+}
+
+impl<'a> IntersectVisitor for AllPointsVisitor<'a> {
+    fn visit(&mut self, doc_id: DocId) -> Result<()> {
+        self.doc_id_set_builder.add_doc(doc_id);
+        Ok(())
+    }
+
+    fn visit_by_packed_value(&mut self, doc_id: DocId, _packed_value: &[u8]) -> Result<()> {
+        self.doc_id_set_builder.add_doc(doc_id);
+        Ok(())
+    }
+
+    fn compare(&self, _min_packed_value: &[u8], _max_packed_value: &[u8]) -> Relation {
+        Relation::CellInsideQuery
+    }
+
+    fn grow(&mut self, count: usize) {
+        self.doc_id_set_builder.grow(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::{Analyzer, SimpleAnalyzer};
+    use core::doc::{Field, FieldType, IntPoint, NumericDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexReader, IndexWriter};
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::sync::Arc;
+
+    /// Doc `id` has a text field with norms if even, a keyword field without
+    /// norms every 5 docs of the first 6 docs, numeric doc values every 3 docs
+    /// and a point every 4 docs.
+    fn document(id: i32) -> Vec<Box<dyn Fieldable>> {
+        let mut stored_type = FieldType::default();
+        stored_type.stored = true;
+        let mut fields: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+            "stored".into(),
+            stored_type,
+            Some(VariantValue::VString(id.to_string())),
+            None,
+        ))];
+        if id % 2 == 0 {
+            let mut text_type = FieldType::default();
+            text_type.index_options = IndexOptions::DocsAndFreqs;
+            let text = format!("text {}", id);
+            let tokens = SimpleAnalyzer::default()
+                .token_stream("text", &text)
+                .unwrap();
+            fields.push(Box::new(Field::new(
+                "text".into(),
+                text_type,
+                Some(VariantValue::VString(text)),
+                Some(tokens),
+            )));
+        }
+        if id % 5 == 0 && id < 6 {
+            let mut keyword_type = FieldType::default();
+            keyword_type.tokenized = false;
+            keyword_type.index_options = IndexOptions::Docs;
+            keyword_type.omit_norms = true;
+            fields.push(Box::new(Field::new(
+                "keyword".into(),
+                keyword_type,
+                Some(VariantValue::VString("k".into())),
+                None,
+            )));
+        }
+        if id % 3 == 0 {
+            fields.push(Box::new(NumericDocValuesField::new("num", i64::from(id))));
+        }
+        if id % 4 == 0 {
+            let mut point_type = FieldType::default();
+            point_type.tokenized = false;
+            point_type.set_dimensions(1, 4).unwrap();
+            fields.push(Box::new(Field::new(
+                "point".into(),
+                point_type,
+                Some(VariantValue::Binary(IntPoint::pack(&[id]))),
+                None,
+            )));
+        }
+        fields
+    }
+
+    #[test]
+    fn test_exists() {
+        let path = ::std::env::temp_dir().join("rucene_exists_query");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        // the second segment has no keyword field
+        for id in 0..12 {
+            writer.add_document(document(id)).unwrap();
+            if id == 5 {
+                writer.commit().unwrap();
+            }
+        }
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        assert_eq!(reader.leaves().len(), 2);
+        let searcher = DefaultIndexSearcher::new(&reader);
+        let count = |field: &str| {
+            searcher
+                .count(&ExistsQuery::new(field.to_string()))
+                .unwrap()
+        };
+
+        // norms
+        assert_eq!(count("text"), 6);
+        // postings
+        assert_eq!(count("keyword"), 2);
+        // doc values
+        assert_eq!(count("num"), 4);
+        // points
+        assert_eq!(count("point"), 3);
+        // neither indexed nor with doc values
+        assert_eq!(count("stored"), 0);
+        assert_eq!(count("unknown"), 0);
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,586 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::double_values::DoubleValuesSource;
+use core::search::long_values::LongValuesSource;
+use core::search::sort_field::SortFieldType;
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// An arithmetic expression over named variables, such as
+/// `0.5 * score + log(popularity)`.
+///
+/// The syntax is a subset of javascript:
+///
+/// * numbers and variables, variable names may contain dots
+/// * arithmetic operators `+ - * / %`, unary `-` and `+`
+/// * comparisons `< <= > >= == !=` and logical `&& || !`, true is 1 and false is 0, any non zero
+///   value is true
+/// * the conditional operator `cond ? a : b`
+/// * the functions `abs ceil floor sqrt exp ln log log10 log1p logn pow min max`, `log` is the
+///   natural logarithm and `logn(base, x)` the logarithm of `x` in `base`
+///
+/// A parsed expression is turned into a `DoubleValuesSource` by binding its
+/// variables, see `values_source`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    source: String,
+    root: Node,
+    variables: Vec<String>,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Expression> {
+        let mut parser = Parser {
+            source,
+            tokens: tokenize(source)?,
+            pos: 0,
+            variables: Vec::new(),
+        };
+        let root = parser.conditional()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("unexpected token"));
+        }
+        Ok(Expression {
+            source: source.to_string(),
+            root,
+            variables: parser.variables,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The distinct variables referenced by the expression.
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    /// Binds every variable of the expression to its source in `bindings`.
+    pub fn values_source(&self, bindings: &Bindings) -> Result<DoubleValuesSource> {
+        let mut sources = Vec::with_capacity(self.variables.len());
+        for name in &self.variables {
+            match bindings.get(name) {
+                Some(source) => sources.push(source.clone()),
+                None => bail!(IllegalArgument(format!(
+                    "variable {} of expression {} is not bound",
+                    name, self.source
+                ))),
+            }
+        }
+        Ok(DoubleValuesSource::Expression(
+            Arc::new(self.clone()),
+            sources,
+        ))
+    }
+
+    /// Evaluates the expression, `variables` returns the value of the i-th
+    /// variable of `variables()`.
+    pub fn evaluate(&self, variables: &mut dyn FnMut(usize) -> Result<f64>) -> Result<f64> {
+        self.root.evaluate(variables)
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// The sources the variables of expressions are bound to.
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    sources: HashMap<String, DoubleValuesSource>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut sources = HashMap::new();
+        sources.insert("score".to_string(), DoubleValuesSource::Score);
+        Bindings { sources }
+    }
+}
+
+impl Bindings {
+    /// Creates bindings where `score` is the score of the document.
+    pub fn new() -> Bindings {
+        Bindings::default()
+    }
+
+    pub fn add(&mut self, name: &str, source: DoubleValuesSource) {
+        self.sources.insert(name.to_string(), source);
+    }
+
+    /// Binds `name` to the numeric doc values of the field of the same name.
+    pub fn add_field(&mut self, name: &str, field_type: SortFieldType) -> Result<()> {
+        let source = DoubleValuesSource::from_field(name, field_type)?;
+        self.add(name, source);
+        Ok(())
+    }
+
+    /// Binds `name` to the values of a `LongValuesSource`.
+    pub fn add_long(&mut self, name: &str, source: &LongValuesSource) {
+        self.add(name, source.to_double_values_source());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DoubleValuesSource> {
+        self.sources.get(name)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Abs,
+    Ceil,
+    Floor,
+    Sqrt,
+    Exp,
+    Ln,
+    Log10,
+    Log1p,
+    Logn,
+    Pow,
+    Min,
+    Max,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        let function = match name {
+            "abs" => Function::Abs,
+            "ceil" => Function::Ceil,
+            "floor" => Function::Floor,
+            "sqrt" => Function::Sqrt,
+            "exp" => Function::Exp,
+            "ln" | "log" => Function::Ln,
+            "log10" => Function::Log10,
+            "log1p" => Function::Log1p,
+            "logn" => Function::Logn,
+            "pow" => Function::Pow,
+            "min" => Function::Min,
+            "max" => Function::Max,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    fn arity(self) -> usize {
+        match self {
+            Function::Logn | Function::Pow | Function::Min | Function::Max => 2,
+            _ => 1,
+        }
+    }
+
+    fn apply(self, args: &[f64]) -> f64 {
+        match self {
+            Function::Abs => args[0].abs(),
+            Function::Ceil => args[0].ceil(),
+            Function::Floor => args[0].floor(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            Function::Log1p => args[0].ln_1p(),
+            Function::Logn => args[1].log(args[0]),
+            Function::Pow => args[0].powf(args[1]),
+            Function::Min => args[0].min(args[1]),
+            Function::Max => args[0].max(args[1]),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Node {
+    Number(f64),
+    Variable(usize),
+    Negate(Box<Node>),
+    Not(Box<Node>),
+    Binary(BinaryOp, Box<Node>, Box<Node>),
+    Conditional(Box<Node>, Box<Node>, Box<Node>),
+    Call(Function, Vec<Node>),
+}
+
+fn from_bool(b: bool) -> f64 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Node {
+    fn evaluate(&self, variables: &mut dyn FnMut(usize) -> Result<f64>) -> Result<f64> {
+        let value = match self {
+            Node::Number(v) => *v,
+            Node::Variable(i) => variables(*i)?,
+            Node::Negate(n) => -n.evaluate(variables)?,
+            Node::Not(n) => from_bool(n.evaluate(variables)? == 0.0),
+            Node::Binary(BinaryOp::And, l, r) => {
+                from_bool(l.evaluate(variables)? != 0.0 && r.evaluate(variables)? != 0.0)
+            }
+            Node::Binary(BinaryOp::Or, l, r) => {
+                from_bool(l.evaluate(variables)? != 0.0 || r.evaluate(variables)? != 0.0)
+            }
+            Node::Binary(op, l, r) => {
+                let l = l.evaluate(variables)?;
+                let r = r.evaluate(variables)?;
+                match op {
+                    BinaryOp::Add => l + r,
+                    BinaryOp::Sub => l - r,
+                    BinaryOp::Mul => l * r,
+                    BinaryOp::Div => l / r,
+                    BinaryOp::Rem => l % r,
+                    BinaryOp::Lt => from_bool(l < r),
+                    BinaryOp::Le => from_bool(l <= r),
+                    BinaryOp::Gt => from_bool(l > r),
+                    BinaryOp::Ge => from_bool(l >= r),
+                    BinaryOp::Eq => from_bool(l == r),
+                    BinaryOp::Ne => from_bool(l != r),
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+            Node::Conditional(cond, a, b) => {
+                if cond.evaluate(variables)? != 0.0 {
+                    a.evaluate(variables)?
+                } else {
+                    b.evaluate(variables)?
+                }
+            }
+            Node::Call(function, args) => {
+                let mut values = [0.0; 2];
+                for (i, arg) in args.iter().enumerate() {
+                    values[i] = arg.evaluate(variables)?;
+                }
+                function.apply(&values[..args.len()])
+            }
+        };
+        Ok(value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 19] = [
+    "<=", ">=", "==", "!=", "&&", "||", "+", "-", "*", "/", "%", "<", ">", "!", "(", ")", ",", "?",
+    ":",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let c = bytes[pos];
+        if c.is_ascii_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_digit() || c == b'.' {
+            let start = pos;
+            while pos < bytes.len() && (bytes[pos].is_ascii_digit() || bytes[pos] == b'.') {
+                pos += 1;
+            }
+            if pos < bytes.len() && (bytes[pos] == b'e' || bytes[pos] == b'E') {
+                pos += 1;
+                if pos < bytes.len() && (bytes[pos] == b'+' || bytes[pos] == b'-') {
+                    pos += 1;
+                }
+                while pos < bytes.len() && bytes[pos].is_ascii_digit() {
+                    pos += 1;
+                }
+            }
+            match source[start..pos].parse() {
+                Ok(v) => tokens.push((Token::Number(v), start)),
+                Err(_) => bail!(IllegalArgument(format!(
+                    "invalid number {} at {} in expression {}",
+                    &source[start..pos],
+                    start,
+                    source
+                ))),
+            }
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = pos;
+            while pos < bytes.len()
+                && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_' || bytes[pos] == b'.')
+            {
+                pos += 1;
+            }
+            tokens.push((Token::Ident(source[start..pos].to_string()), start));
+        } else {
+            match OPERATORS.iter().find(|op| source[pos..].starts_with(*op)) {
+                Some(op) => {
+                    tokens.push((Token::Op(op), pos));
+                    pos += op.len();
+                }
+                None => bail!(IllegalArgument(format!(
+                    "unexpected character {:?} at {} in expression {}",
+                    source[pos..].chars().next().unwrap(),
+                    pos,
+                    source
+                ))),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    variables: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, msg: &str) -> ::error::Error {
+        let at = self.tokens.get(self.pos).map_or(self.source.len(), |t| t.1);
+        IllegalArgument(format!("{} at {} in expression {}", msg, at, self.source)).into()
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        match self.tokens.get(self.pos) {
+            Some((Token::Op(o), _)) if *o == op => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<()> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", op)))
+        }
+    }
+
+    fn binary(
+        &mut self,
+        ops: &[(&str, BinaryOp)],
+        next: fn(&mut Self) -> Result<Node>,
+    ) -> Result<Node> {
+        let mut node = next(self)?;
+        'outer: loop {
+            for &(op, binary_op) in ops {
+                if self.eat(op) {
+                    node = Node::Binary(binary_op, Box::new(node), Box::new(next(self)?));
+                    continue 'outer;
+                }
+            }
+            return Ok(node);
+        }
+    }
+
+    fn conditional(&mut self) -> Result<Node> {
+        let cond = self.or()?;
+        if !self.eat("?") {
+            return Ok(cond);
+        }
+        let a = self.conditional()?;
+        self.expect(":")?;
+        let b = self.conditional()?;
+        Ok(Node::Conditional(Box::new(cond), Box::new(a), Box::new(b)))
+    }
+
+    fn or(&mut self) -> Result<Node> {
+        self.binary(&[("||", BinaryOp::Or)], Self::and)
+    }
+
+    fn and(&mut self) -> Result<Node> {
+        self.binary(&[("&&", BinaryOp::And)], Self::equality)
+    }
+
+    fn equality(&mut self) -> Result<Node> {
+        self.binary(
+            &[("==", BinaryOp::Eq), ("!=", BinaryOp::Ne)],
+            Self::relational,
+        )
+    }
+
+    fn relational(&mut self) -> Result<Node> {
+        self.binary(
+            &[
+                ("<=", BinaryOp::Le),
+                (">=", BinaryOp::Ge),
+                ("<", BinaryOp::Lt),
+                (">", BinaryOp::Gt),
+            ],
+            Self::additive,
+        )
+    }
+
+    fn additive(&mut self) -> Result<Node> {
+        self.binary(
+            &[("+", BinaryOp::Add), ("-", BinaryOp::Sub)],
+            Self::multiplicative,
+        )
+    }
+
+    fn multiplicative(&mut self) -> Result<Node> {
+        self.binary(
+            &[
+                ("*", BinaryOp::Mul),
+                ("/", BinaryOp::Div),
+                ("%", BinaryOp::Rem),
+            ],
+            Self::unary,
+        )
+    }
+
+    fn unary(&mut self) -> Result<Node> {
+        if self.eat("-") {
+            Ok(Node::Negate(Box::new(self.unary()?)))
+        } else if self.eat("+") {
+            self.unary()
+        } else if self.eat("!") {
+            Ok(Node::Not(Box::new(self.unary()?)))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node> {
+        let token = match self.tokens.get(self.pos) {
+            Some((token, _)) => token.clone(),
+            None => return Err(self.error("unexpected end")),
+        };
+        match token {
+            Token::Number(v) => {
+                self.pos += 1;
+                Ok(Node::Number(v))
+            }
+            Token::Op("(") => {
+                self.pos += 1;
+                let node = self.conditional()?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Ident(name) => {
+                self.pos += 1;
+                if self.eat("(") {
+                    self.call(&name)
+                } else {
+                    let i = match self.variables.iter().position(|v| *v == name) {
+                        Some(i) => i,
+                        None => {
+                            self.variables.push(name);
+                            self.variables.len() - 1
+                        }
+                    };
+                    Ok(Node::Variable(i))
+                }
+            }
+            Token::Op(_) => Err(self.error("unexpected token")),
+        }
+    }
+
+    // the function name and the opening parenthesis are consumed
+    fn call(&mut self, name: &str) -> Result<Node> {
+        let function = match Function::from_name(name) {
+            Some(f) => f,
+            None => return Err(self.error(&format!("unknown function {}", name))),
+        };
+        let mut args = Vec::with_capacity(function.arity());
+        if !self.eat(")") {
+            loop {
+                args.push(self.conditional()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+        if args.len() != function.arity() {
+            return Err(self.error(&format!(
+                "function {} expects {} arguments, got {}",
+                name,
+                function.arity(),
+                args.len()
+            )));
+        }
+        Ok(Node::Call(function, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(source: &str, values: &[f64]) -> f64 {
+        Expression::parse(source)
+            .unwrap()
+            .evaluate(&mut |i| Ok(values[i]))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_expression() {
+        assert!((eval("1 + 2 * 3 - 4 / 2", &[]) - 5.0).abs() < 1e-9);
+        assert!((eval("-(1 + 2) * 2 % 4", &[]) + 2.0).abs() < 1e-9);
+        assert!((eval("pow(2, 10) + max(1.5e1, min(3, 4))", &[]) - 1039.0).abs() < 1e-9);
+        assert!((eval("logn(2, 8) + log(exp(2))", &[]) - 5.0).abs() < 1e-9);
+        assert!((eval("1 < 2 && !(3 >= 4) ? 7 : 8", &[]) - 7.0).abs() < 1e-9);
+
+        let expression = Expression::parse("0.5*score + log(doc.popularity) - score").unwrap();
+        assert_eq!(expression.variables(), &["score", "doc.popularity"]);
+        let value = expression
+            .evaluate(&mut |i| Ok([4.0, ::std::f64::consts::E][i]))
+            .unwrap();
+        assert!((value + 1.0).abs() < 1e-9);
+
+        assert!(Expression::parse("1 +").is_err());
+        assert!(Expression::parse("(1 + 2").is_err());
+        assert!(Expression::parse("foo(1)").is_err());
+        assert!(Expression::parse("pow(1)").is_err());
+        assert!(Expression::parse("1 $ 2").is_err());
+
+        let mut bindings = Bindings::new();
+        assert!(expression.values_source(&bindings).is_err());
+        bindings
+            .add_field("doc.popularity", SortFieldType::Long)
+            .unwrap();
+        let source = expression.values_source(&bindings).unwrap();
+        assert!(source.needs_scores());
+        assert_eq!(
+            source.to_string(),
+            "0.5*score + log(doc.popularity) - score"
+        );
+    }
+}
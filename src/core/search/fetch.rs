@@ -0,0 +1,529 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{Codec, StoredFieldsReader};
+use core::index::{
+    DocValuesType, FieldInfo, IndexReader, LeafReaderContext, Status, StoredFieldVisitor,
+    NO_MORE_ORDS,
+};
+use core::search::top_docs::TopDocs;
+use core::util::{DocId, VariantValue};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::collections::HashMap;
+
+/// Where the values of a fetched field are read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchSource {
+    /// Read the values from stored fields.
+    Stored,
+    /// Read the values from the doc values of the field.
+    DocValues,
+}
+
+/// The format the fetched values are converted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FetchFormat {
+    /// Keep the value as read: stored values keep their stored type, numeric
+    /// doc values are returned as `Long` and the others as `Binary`.
+    Raw,
+    Int,
+    Long,
+    /// For numeric doc values, the raw value is decoded as the bits of a float.
+    Float,
+    /// For numeric doc values, the raw value is decoded as the bits of a double.
+    Double,
+    /// Binary values must be valid UTF-8.
+    String,
+    Binary,
+}
+
+/// A field to resolve for every hit during the fetch phase.
+#[derive(Clone, Debug)]
+pub struct FetchField {
+    pub name: String,
+    pub source: FetchSource,
+    pub format: FetchFormat,
+}
+
+impl FetchField {
+    pub fn new(name: &str, source: FetchSource, format: FetchFormat) -> FetchField {
+        FetchField {
+            name: name.to_string(),
+            source,
+            format,
+        }
+    }
+
+    pub fn stored(name: &str) -> FetchField {
+        Self::new(name, FetchSource::Stored, FetchFormat::Raw)
+    }
+
+    pub fn doc_values(name: &str, format: FetchFormat) -> FetchField {
+        Self::new(name, FetchSource::DocValues, format)
+    }
+}
+
+/// The fields resolved for one hit, keyed by field name. Fields without any
+/// value for the hit are absent.
+#[derive(Debug)]
+pub struct FetchedDoc {
+    pub doc: DocId,
+    pub fields: HashMap<String, Vec<VariantValue>>,
+}
+
+impl FetchedDoc {
+    fn new(doc: DocId) -> FetchedDoc {
+        FetchedDoc {
+            doc,
+            fields: HashMap::new(),
+        }
+    }
+
+    pub fn values(&self, field: &str) -> &[VariantValue] {
+        self.fields.get(field).map_or(&[], |v| v.as_slice())
+    }
+
+    pub fn first(&self, field: &str) -> Option<&VariantValue> {
+        self.values(field).first()
+    }
+}
+
+/// Fetch phase that resolves a fixed set of fields for the hits of a search.
+///
+/// Instead of loading every hit with a random read, the hits are visited
+/// in doc id order so that each segment is read once, in a forward only manner:
+/// the doc values of a field are opened once per segment and the stored fields
+/// are visited through one merge instance of the segment's stored fields reader,
+/// which decompresses each block once for all the hits it holds. The results
+/// are returned in the order of the hits.
+pub struct FieldsFetcher {
+    fields: Vec<FetchField>,
+}
+
+impl FieldsFetcher {
+    pub fn new(fields: Vec<FetchField>) -> FieldsFetcher {
+        FieldsFetcher { fields }
+    }
+
+    pub fn fields(&self) -> &[FetchField] {
+        &self.fields
+    }
+
+    /// Resolves the fields of every hit in `top_docs`.
+    pub fn fetch<R: IndexReader + ?Sized>(
+        &self,
+        reader: &R,
+        top_docs: &TopDocs,
+    ) -> Result<Vec<FetchedDoc>> {
+        let docs: Vec<DocId> = top_docs.score_docs().iter().map(|d| d.doc_id()).collect();
+        self.fetch_docs(reader, &docs)
+    }
+
+    /// Resolves the fields of the given top level doc ids, returning one
+    /// `FetchedDoc` per doc in the same order.
+    pub fn fetch_docs<R: IndexReader + ?Sized>(
+        &self,
+        reader: &R,
+        docs: &[DocId],
+    ) -> Result<Vec<FetchedDoc>> {
+        let mut results: Vec<FetchedDoc> = docs.iter().map(|&d| FetchedDoc::new(d)).collect();
+        if docs.is_empty() || self.fields.is_empty() {
+            return Ok(results);
+        }
+
+        // visit the hits in doc id order
+        let mut order: Vec<usize> = (0..docs.len()).collect();
+        order.sort_by_key(|&i| docs[i]);
+
+        let leaves = reader.leaves();
+        let mut leaf_idx = 0;
+        let mut upto = 0;
+        while upto < order.len() {
+            let doc = docs[order[upto]];
+            while leaf_idx + 1 < leaves.len() && doc >= leaves[leaf_idx + 1].doc_base {
+                leaf_idx += 1;
+            }
+            let leaf = &leaves[leaf_idx];
+            let end_doc = leaf.doc_base + leaf.reader.max_doc();
+            if doc < leaf.doc_base || doc >= end_doc {
+                bail!(IllegalArgument(format!(
+                    "doc {} is out of bounds of the reader",
+                    doc
+                )));
+            }
+
+            let start = upto;
+            while upto < order.len() && docs[order[upto]] < end_doc {
+                upto += 1;
+            }
+            self.fetch_leaf(leaf, &order[start..upto], &mut results)?;
+        }
+        Ok(results)
+    }
+
+    fn fetch_leaf<C: Codec>(
+        &self,
+        leaf: &LeafReaderContext<'_, C>,
+        hits: &[usize],
+        results: &mut [FetchedDoc],
+    ) -> Result<()> {
+        let stored: Vec<&FetchField> = self
+            .fields
+            .iter()
+            .filter(|f| f.source == FetchSource::Stored)
+            .collect();
+        if !stored.is_empty() {
+            // the readers which aren't codec readers filter the stored fields
+            // themselves, so their documents are visited one by one.
+            let mut fields_reader = if leaf.reader.is_codec_reader() {
+                Some(leaf.reader.store_fields_reader()?.get_merge_instance()?)
+            } else {
+                None
+            };
+            for &i in hits {
+                let doc = results[i].doc - leaf.doc_base;
+                let mut visitor = FetchFieldsVisitor {
+                    fields: &stored,
+                    values: &mut results[i].fields,
+                };
+                if let Some(ref mut fields_reader) = fields_reader {
+                    fields_reader.visit_document_mut(doc, &mut visitor)?;
+                } else {
+                    leaf.reader.document(doc, &mut visitor)?;
+                }
+            }
+        }
+
+        for field in &self.fields {
+            if field.source == FetchSource::DocValues {
+                fetch_doc_values(leaf, field, hits, results)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn fetch_doc_values<C: Codec>(
+    leaf: &LeafReaderContext<'_, C>,
+    field: &FetchField,
+    hits: &[usize],
+    results: &mut [FetchedDoc],
+) -> Result<()> {
+    let reader = leaf.reader;
+    let dv_type = match reader.field_info(&field.name) {
+        Some(info) if !info.doc_values_type.null() => info.doc_values_type,
+        _ => {
+            return Ok(());
+        }
+    };
+    let name = &field.name;
+    match dv_type {
+        DocValuesType::Numeric => {
+            let dv = reader.get_numeric_doc_values(name)?;
+            let docs_with_field = reader.get_docs_with_field(name)?;
+            let mut ctx = None;
+            let mut bits_ctx = None;
+            for &i in hits {
+                let doc = results[i].doc - leaf.doc_base;
+                let (exists, c) = docs_with_field.get_with_ctx(bits_ctx, doc as usize)?;
+                bits_ctx = c;
+                if exists {
+                    let (value, c) = dv.get_with_ctx(ctx, doc)?;
+                    ctx = c;
+                    let value = convert_numeric(value, field.format)?;
+                    add_value(&mut results[i], name, value);
+                }
+            }
+        }
+        DocValuesType::SortedNumeric => {
+            let dv = reader.get_sorted_numeric_doc_values(name)?;
+            let mut ctx = None;
+            for &i in hits {
+                let c = dv.set_document(ctx, results[i].doc - leaf.doc_base)?;
+                for idx in 0..dv.count(&c) {
+                    let value = convert_numeric(dv.value_at(&c, idx)?, field.format)?;
+                    add_value(&mut results[i], name, value);
+                }
+                ctx = Some(c);
+            }
+        }
+        DocValuesType::Binary => {
+            let dv = reader.get_binary_doc_values(name)?;
+            let docs_with_field = reader.get_docs_with_field(name)?;
+            for &i in hits {
+                let doc = results[i].doc - leaf.doc_base;
+                if docs_with_field.get(doc as usize)? {
+                    let value = convert_bytes(dv.get(doc)?, field.format)?;
+                    add_value(&mut results[i], name, value);
+                }
+            }
+        }
+        DocValuesType::Sorted => {
+            let dv = reader.get_sorted_doc_values(name)?;
+            for &i in hits {
+                let ord = dv.get_ord(results[i].doc - leaf.doc_base)?;
+                if ord >= 0 {
+                    let value = convert_bytes(dv.lookup_ord(ord)?, field.format)?;
+                    add_value(&mut results[i], name, value);
+                }
+            }
+        }
+        DocValuesType::SortedSet => {
+            let dv = reader.get_sorted_set_doc_values(name)?;
+            for &i in hits {
+                let mut ctx = dv.set_document(results[i].doc - leaf.doc_base)?;
+                loop {
+                    let ord = dv.next_ord(&mut ctx)?;
+                    if ord == NO_MORE_ORDS {
+                        break;
+                    }
+                    let value = convert_bytes(dv.lookup_ord(ord)?, field.format)?;
+                    add_value(&mut results[i], name, value);
+                }
+            }
+        }
+        DocValuesType::Null => unreachable!(),
+    }
+    Ok(())
+}
+
+fn add_value(doc: &mut FetchedDoc, field: &str, value: VariantValue) {
+    if let Some(values) = doc.fields.get_mut(field) {
+        values.push(value);
+        return;
+    }
+    doc.fields.insert(field.to_string(), vec![value]);
+}
+
+fn convert_numeric(value: i64, format: FetchFormat) -> Result<VariantValue> {
+    Ok(match format {
+        FetchFormat::Raw | FetchFormat::Long => VariantValue::Long(value),
+        FetchFormat::Int => VariantValue::Int(value as i32),
+        FetchFormat::Float => VariantValue::Float(f32::from_bits(value as u32)),
+        FetchFormat::Double => VariantValue::Double(f64::from_bits(value as u64)),
+        FetchFormat::String => VariantValue::VString(value.to_string()),
+        FetchFormat::Binary => VariantValue::Binary(value.to_be_bytes().to_vec()),
+    })
+}
+
+fn convert_bytes(value: Vec<u8>, format: FetchFormat) -> Result<VariantValue> {
+    match format {
+        FetchFormat::Raw | FetchFormat::Binary => Ok(VariantValue::Binary(value)),
+        _ => match String::from_utf8(value) {
+            Ok(s) => convert_value(VariantValue::VString(s), format),
+            Err(e) => bail!(IllegalArgument(format!(
+                "binary value is not valid utf-8: {:?}",
+                e
+            ))),
+        },
+    }
+}
+
+/// Converts a stored value to the given format.
+fn convert_value(value: VariantValue, format: FetchFormat) -> Result<VariantValue> {
+    let converted = match (format, &value) {
+        (FetchFormat::Raw, _) => Some(value.clone()),
+        (FetchFormat::Int, VariantValue::Short(v)) => Some(VariantValue::Int(i32::from(*v))),
+        (FetchFormat::Int, VariantValue::Int(v)) => Some(VariantValue::Int(*v)),
+        (FetchFormat::Int, VariantValue::Long(v)) => Some(VariantValue::Int(*v as i32)),
+        (FetchFormat::Int, VariantValue::VString(s)) => s.parse().ok().map(VariantValue::Int),
+        (FetchFormat::Long, VariantValue::Short(v)) => Some(VariantValue::Long(i64::from(*v))),
+        (FetchFormat::Long, VariantValue::Int(v)) => Some(VariantValue::Long(i64::from(*v))),
+        (FetchFormat::Long, VariantValue::Long(v)) => Some(VariantValue::Long(*v)),
+        (FetchFormat::Long, VariantValue::VString(s)) => s.parse().ok().map(VariantValue::Long),
+        (FetchFormat::Float, VariantValue::Int(v)) => Some(VariantValue::Float(*v as f32)),
+        (FetchFormat::Float, VariantValue::Long(v)) => Some(VariantValue::Float(*v as f32)),
+        (FetchFormat::Float, VariantValue::Float(v)) => Some(VariantValue::Float(*v)),
+        (FetchFormat::Float, VariantValue::Double(v)) => Some(VariantValue::Float(*v as f32)),
+        (FetchFormat::Float, VariantValue::VString(s)) => s.parse().ok().map(VariantValue::Float),
+        (FetchFormat::Double, VariantValue::Int(v)) => Some(VariantValue::Double(f64::from(*v))),
+        (FetchFormat::Double, VariantValue::Long(v)) => Some(VariantValue::Double(*v as f64)),
+        (FetchFormat::Double, VariantValue::Float(v)) => Some(VariantValue::Double(f64::from(*v))),
+        (FetchFormat::Double, VariantValue::Double(v)) => Some(VariantValue::Double(*v)),
+        (FetchFormat::Double, VariantValue::VString(s)) => s.parse().ok().map(VariantValue::Double),
+        (FetchFormat::String, VariantValue::Binary(b)) => {
+            String::from_utf8(b.clone()).ok().map(VariantValue::VString)
+        }
+        (FetchFormat::String, VariantValue::Map(_)) => None,
+        (FetchFormat::String, v) => Some(VariantValue::VString(v.to_string())),
+        (FetchFormat::Binary, VariantValue::Binary(b)) => Some(VariantValue::Binary(b.clone())),
+        (FetchFormat::Binary, VariantValue::VString(s)) => {
+            Some(VariantValue::Binary(s.as_bytes().to_vec()))
+        }
+        _ => None,
+    };
+    match converted {
+        Some(v) => Ok(v),
+        None => bail!(IllegalArgument(format!(
+            "can't convert value '{}' to {:?}",
+            value, format
+        ))),
+    }
+}
+
+/// Collects the values of the requested stored fields of one document.
+struct FetchFieldsVisitor<'a> {
+    fields: &'a [&'a FetchField],
+    values: &'a mut HashMap<String, Vec<VariantValue>>,
+}
+
+impl<'a> FetchFieldsVisitor<'a> {
+    fn add(&mut self, field_info: &FieldInfo, value: VariantValue) -> Result<()> {
+        if let Some(field) = self.fields.iter().find(|f| f.name == field_info.name) {
+            let value = convert_value(value, field.format)?;
+            self.values
+                .entry(field_info.name.clone())
+                .or_insert_with(Vec::new)
+                .push(value);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> StoredFieldVisitor for FetchFieldsVisitor<'a> {
+    fn binary_field(&mut self, field_info: &FieldInfo, value: Vec<u8>) -> Result<()> {
+        self.add(field_info, VariantValue::Binary(value))
+    }
+
+    fn string_field(&mut self, field_info: &FieldInfo, value: Vec<u8>) -> Result<()> {
+        match String::from_utf8(value) {
+            Ok(s) => self.add(field_info, VariantValue::VString(s)),
+            Err(e) => bail!(IllegalArgument(format!(
+                "stored field '{}' is not valid utf-8: {:?}",
+                field_info.name, e
+            ))),
+        }
+    }
+
+    fn int_field(&mut self, field_info: &FieldInfo, value: i32) -> Result<()> {
+        self.add(field_info, VariantValue::Int(value))
+    }
+
+    fn long_field(&mut self, field_info: &FieldInfo, value: i64) -> Result<()> {
+        self.add(field_info, VariantValue::Long(value))
+    }
+
+    fn float_field(&mut self, field_info: &FieldInfo, value: f32) -> Result<()> {
+        self.add(field_info, VariantValue::Float(value))
+    }
+
+    fn double_field(&mut self, field_info: &FieldInfo, value: f64) -> Result<()> {
+        self.add(field_info, VariantValue::Double(value))
+    }
+
+    fn needs_field(&self, field_info: &FieldInfo) -> Status {
+        if self.fields.iter().any(|f| f.name == field_info.name) {
+            Status::Yes
+        } else {
+            Status::No
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexOptions, IndexWriter};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+    use std::sync::Arc;
+
+    fn document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.stored = true;
+        id_type.index_options = IndexOptions::Docs;
+        id_type.omit_norms = true;
+        let mut fields: Vec<Box<dyn Fieldable>> = vec![Box::new(Field::new(
+            "id".into(),
+            id_type,
+            Some(VariantValue::VString(id.to_string())),
+            None,
+        ))];
+        // every third document has no doc values
+        if id % 3 != 0 {
+            fields.push(Box::new(NumericDocValuesField::new("value", id * 10)));
+        }
+        fields
+    }
+
+    #[test]
+    fn test_fetch_docs() {
+        let path = ::std::env::temp_dir().join("rucene_fetch_docs");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        for i in 0..10 {
+            writer.add_document(document(i)).unwrap();
+            // spread the documents over several segments
+            if i % 4 == 3 {
+                writer.commit().unwrap();
+            }
+        }
+        let reader = writer.get_reader(true, false).unwrap();
+        assert!(reader.leaves().len() > 1);
+
+        let fetcher = FieldsFetcher::new(vec![
+            FetchField::stored("id"),
+            FetchField::doc_values("value", FetchFormat::Long),
+            FetchField::doc_values("missing", FetchFormat::Long),
+        ]);
+        // the hits are not in doc id order, and several share a segment
+        let docs = vec![7, 2, 9, 0, 3, 8, 5];
+        let fetched = fetcher.fetch_docs(&reader, &docs).unwrap();
+        assert_eq!(fetched.len(), docs.len());
+        for (fetched_doc, &doc) in fetched.iter().zip(docs.iter()) {
+            let id = i64::from(doc);
+            assert_eq!(fetched_doc.doc, doc);
+            assert_eq!(
+                fetched_doc.values("id"),
+                &[VariantValue::VString(id.to_string())]
+            );
+            if id % 3 == 0 {
+                assert!(fetched_doc.first("value").is_none());
+            } else {
+                assert_eq!(fetched_doc.values("value"), &[VariantValue::Long(id * 10)]);
+            }
+            assert!(fetched_doc.first("missing").is_none());
+        }
+        assert!(fetcher.fetch_docs(&reader, &[10]).is_err());
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_convert_values() {
+        let v = convert_numeric(i64::from(1.5f32.to_bits()), FetchFormat::Float).unwrap();
+        assert_eq!(v, VariantValue::Float(1.5));
+        let v = convert_numeric(2.5f64.to_bits() as i64, FetchFormat::Double).unwrap();
+        assert_eq!(v, VariantValue::Double(2.5));
+        let v = convert_numeric(42, FetchFormat::String).unwrap();
+        assert_eq!(v, VariantValue::VString("42".to_string()));
+
+        let v = convert_bytes(b"123".to_vec(), FetchFormat::Long).unwrap();
+        assert_eq!(v, VariantValue::Long(123));
+        let v = convert_bytes(b"abc".to_vec(), FetchFormat::String).unwrap();
+        assert_eq!(v, VariantValue::VString("abc".to_string()));
+        assert!(convert_bytes(vec![0xff, 0xfe], FetchFormat::String).is_err());
+
+        let v = convert_value(VariantValue::Int(7), FetchFormat::Double).unwrap();
+        assert_eq!(v, VariantValue::Double(7.0));
+        assert!(convert_value(VariantValue::Binary(vec![1]), FetchFormat::Long).is_err());
+    }
+}
@@ -11,58 +11,64 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::index::{LeafReaderContext, NumericDocValuesRef, SearchLeafReader};
+use core::index::{
+    DocValuesType, LeafReaderContext, NumericDocValuesRef, SearchLeafReader,
+    SortedNumericDocValuesRef,
+};
+use core::search::double_values::{DoubleValues, DoubleValuesSource};
 use core::search::sort_field::{SortFieldType, SortedWrapperDocValuesSource};
 use core::util::bits::BitsRef;
+use core::util::geo;
 use core::util::{DocId, VariantValue};
 use error::Result;
 
 use core::codec::Codec;
 use std::cmp::Ordering;
+use std::f64;
 use std::fmt;
 
 #[derive(Copy, Clone, Debug)]
 pub enum ComparatorValue {
     Doc(DocId),
     Score(f32), // this is only used in RelevanceComparator
+    /// a doc along with its score, accepted by every comparator
+    DocScore(DocId, f32),
 }
 
 impl ComparatorValue {
     fn is_doc(&self) -> bool {
         match self {
-            ComparatorValue::Doc(_) => true,
+            ComparatorValue::Doc(_) | ComparatorValue::DocScore(..) => true,
             _ => false,
         }
     }
 
     fn is_score(&self) -> bool {
         match self {
-            ComparatorValue::Score(_) => true,
+            ComparatorValue::Score(_) | ComparatorValue::DocScore(..) => true,
             _ => false,
         }
     }
 
     fn doc(&self) -> DocId {
         debug_assert!(self.is_doc());
-        if let ComparatorValue::Doc(d) = self {
-            *d
-        } else {
-            unreachable!()
+        match self {
+            ComparatorValue::Doc(d) | ComparatorValue::DocScore(d, _) => *d,
+            _ => unreachable!(),
         }
     }
 
     fn score(&self) -> f32 {
         debug_assert!(self.is_score());
-        if let ComparatorValue::Score(s) = self {
-            *s
-        } else {
-            unreachable!()
+        match self {
+            ComparatorValue::Score(s) | ComparatorValue::DocScore(_, s) => *s,
+            _ => unreachable!(),
         }
     }
 
     pub fn as_variant(&self) -> VariantValue {
         match self {
-            ComparatorValue::Doc(d) => VariantValue::Int(*d),
+            ComparatorValue::Doc(d) | ComparatorValue::DocScore(d, _) => VariantValue::Int(*d),
             ComparatorValue::Score(s) => VariantValue::Float(*s),
         }
     }
@@ -75,6 +81,7 @@ impl PartialEq for ComparatorValue {
         match (self, other) {
             (ComparatorValue::Doc(d1), ComparatorValue::Doc(d2)) => *d1 == *d2,
             (ComparatorValue::Score(s1), ComparatorValue::Score(s2)) => s1.eq(s2),
+            (ComparatorValue::DocScore(d1, _), ComparatorValue::DocScore(d2, _)) => *d1 == *d2,
             (_, _) => false,
         }
     }
@@ -87,6 +94,7 @@ impl Ord for ComparatorValue {
             (ComparatorValue::Score(s1), ComparatorValue::Score(s2)) => {
                 (*s1).partial_cmp(s2).unwrap()
             }
+            (ComparatorValue::DocScore(d1, _), ComparatorValue::DocScore(d2, _)) => d1.cmp(d2),
             (_, _) => panic!("Non-comparable"),
         }
     }
@@ -122,6 +130,8 @@ pub enum FieldComparatorEnum {
     Doc(DocComparator),
     NumericDV(NumericDocValuesComparator<DefaultDocValuesSource>),
     SortedNumericDV(NumericDocValuesComparator<SortedWrapperDocValuesSource>),
+    DoubleValues(DoubleValuesComparator),
+    GeoDistance(GeoDistanceComparator),
 }
 
 impl FieldComparator for FieldComparatorEnum {
@@ -131,6 +141,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.compare(slot1, slot2),
             FieldComparatorEnum::NumericDV(c) => c.compare(slot1, slot2),
             FieldComparatorEnum::SortedNumericDV(c) => c.compare(slot1, slot2),
+            FieldComparatorEnum::DoubleValues(c) => c.compare(slot1, slot2),
+            FieldComparatorEnum::GeoDistance(c) => c.compare(slot1, slot2),
         }
     }
 
@@ -140,6 +152,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.value(slot),
             FieldComparatorEnum::NumericDV(c) => c.value(slot),
             FieldComparatorEnum::SortedNumericDV(c) => c.value(slot),
+            FieldComparatorEnum::DoubleValues(c) => c.value(slot),
+            FieldComparatorEnum::GeoDistance(c) => c.value(slot),
         }
     }
 
@@ -149,6 +163,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.set_bottom(slot),
             FieldComparatorEnum::NumericDV(c) => c.set_bottom(slot),
             FieldComparatorEnum::SortedNumericDV(c) => c.set_bottom(slot),
+            FieldComparatorEnum::DoubleValues(c) => c.set_bottom(slot),
+            FieldComparatorEnum::GeoDistance(c) => c.set_bottom(slot),
         }
     }
 
@@ -158,6 +174,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.compare_bottom(value),
             FieldComparatorEnum::NumericDV(c) => c.compare_bottom(value),
             FieldComparatorEnum::SortedNumericDV(c) => c.compare_bottom(value),
+            FieldComparatorEnum::DoubleValues(c) => c.compare_bottom(value),
+            FieldComparatorEnum::GeoDistance(c) => c.compare_bottom(value),
         }
     }
 
@@ -167,6 +185,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.copy(slot, value),
             FieldComparatorEnum::NumericDV(c) => c.copy(slot, value),
             FieldComparatorEnum::SortedNumericDV(c) => c.copy(slot, value),
+            FieldComparatorEnum::DoubleValues(c) => c.copy(slot, value),
+            FieldComparatorEnum::GeoDistance(c) => c.copy(slot, value),
         }
     }
 
@@ -179,6 +199,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.get_information_from_reader(reader),
             FieldComparatorEnum::NumericDV(c) => c.get_information_from_reader(reader),
             FieldComparatorEnum::SortedNumericDV(c) => c.get_information_from_reader(reader),
+            FieldComparatorEnum::DoubleValues(c) => c.get_information_from_reader(reader),
+            FieldComparatorEnum::GeoDistance(c) => c.get_information_from_reader(reader),
         }
     }
 
@@ -188,6 +210,8 @@ impl FieldComparator for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => c.get_type(),
             FieldComparatorEnum::NumericDV(c) => c.get_type(),
             FieldComparatorEnum::SortedNumericDV(c) => c.get_type(),
+            FieldComparatorEnum::DoubleValues(c) => c.get_type(),
+            FieldComparatorEnum::GeoDistance(c) => c.get_type(),
         }
     }
 }
@@ -199,6 +223,8 @@ impl fmt::Display for FieldComparatorEnum {
             FieldComparatorEnum::Doc(c) => write!(f, "FieldComparatorEnum({})", c),
             FieldComparatorEnum::NumericDV(c) => write!(f, "FieldComparatorEnum({})", c),
             FieldComparatorEnum::SortedNumericDV(c) => write!(f, "FieldComparatorEnum({})", c),
+            FieldComparatorEnum::DoubleValues(c) => write!(f, "FieldComparatorEnum({})", c),
+            FieldComparatorEnum::GeoDistance(c) => write!(f, "FieldComparatorEnum({})", c),
         }
     }
 }
@@ -302,7 +328,7 @@ impl FieldComparator for DocComparator {
 
     fn compare_bottom(&self, value: ComparatorValue) -> Result<Ordering> {
         debug_assert!(value.is_doc());
-        Ok(self.bottom.cmp(&value.doc()))
+        Ok(self.bottom.cmp(&(value.doc() + self.doc_base)))
     }
 
     fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
@@ -402,7 +428,7 @@ impl<T: DocValuesSource> FieldComparator for NumericDocValuesComparator<T> {
         let doc_id = value.doc();
         let value = self.get_doc_value(doc_id)?;
         if let Some(ref bits) = self.docs_with_fields {
-            if value.is_zero() && bits.get(doc_id as usize)? {
+            if value.is_zero() && !bits.get(doc_id as usize)? {
                 return Ok(self.bottom.cmp(self.missing_value.as_ref().unwrap()));
             }
         }
@@ -414,7 +440,7 @@ impl<T: DocValuesSource> FieldComparator for NumericDocValuesComparator<T> {
         let doc_id = value.doc();
         let mut value = self.get_doc_value(doc_id)?;
         if let Some(ref bits) = self.docs_with_fields {
-            if value.is_zero() && bits.get(doc_id as usize)? {
+            if value.is_zero() && !bits.get(doc_id as usize)? {
                 value = self.missing_value.as_ref().unwrap().clone();
             }
         }
@@ -455,6 +481,186 @@ impl<T: DocValuesSource> fmt::Display for NumericDocValuesComparator<T> {
     }
 }
 
+/// Compares the values of a `DoubleValuesSource`, copied values must carry the
+/// score of the doc if the source needs scores.
+pub struct DoubleValuesComparator {
+    source: DoubleValuesSource,
+    current_values: Option<DoubleValues>,
+    values: Vec<f64>,
+    bottom: f64,
+}
+
+impl DoubleValuesComparator {
+    pub fn new(num_hits: usize, source: DoubleValuesSource) -> Self {
+        DoubleValuesComparator {
+            source,
+            current_values: None,
+            values: vec![0f64; num_hits],
+            bottom: 0f64,
+        }
+    }
+
+    fn get_value(&self, value: ComparatorValue) -> Result<f64> {
+        debug_assert!(value.is_doc());
+        let score = if value.is_score() {
+            value.score()
+        } else {
+            0f32
+        };
+        self.current_values
+            .as_ref()
+            .unwrap()
+            .double_value(value.doc(), score)
+    }
+}
+
+impl FieldComparator for DoubleValuesComparator {
+    fn compare(&self, slot1: usize, slot2: usize) -> Ordering {
+        self.values[slot1]
+            .partial_cmp(&self.values[slot2])
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn value(&self, slot: usize) -> VariantValue {
+        VariantValue::Double(self.values[slot])
+    }
+
+    fn set_bottom(&mut self, slot: usize) {
+        self.bottom = self.values[slot];
+    }
+
+    fn compare_bottom(&self, value: ComparatorValue) -> Result<Ordering> {
+        let value = self.get_value(value)?;
+        Ok(self.bottom.partial_cmp(&value).unwrap_or(Ordering::Equal))
+    }
+
+    fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
+        self.values[slot] = self.get_value(value)?;
+        Ok(())
+    }
+
+    fn get_information_from_reader<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<()> {
+        self.current_values = Some(self.source.get_values(reader)?);
+        Ok(())
+    }
+
+    fn get_type(&self) -> SortFieldType {
+        SortFieldType::Double
+    }
+}
+
+impl fmt::Display for DoubleValuesComparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "DoubleValuesComparator(source: {}, bottom: {})",
+            self.source, self.bottom
+        )
+    }
+}
+
+/// Compares the distance in meters between the closest point of each doc and
+/// a location, docs without a point are infinitely far.
+pub struct GeoDistanceComparator {
+    field: String,
+    latitude: f64,
+    longitude: f64,
+    current_values: Option<SortedNumericDocValuesRef>,
+    values: Vec<f64>,
+    bottom: f64,
+}
+
+impl GeoDistanceComparator {
+    pub fn new(num_hits: usize, field: String, latitude: f64, longitude: f64) -> Self {
+        GeoDistanceComparator {
+            field,
+            latitude,
+            longitude,
+            current_values: None,
+            values: vec![0f64; num_hits],
+            bottom: 0f64,
+        }
+    }
+
+    fn distance(&self, doc: DocId) -> Result<f64> {
+        let mut distance = f64::INFINITY;
+        if let Some(ref values) = self.current_values {
+            let ctx = values.set_document(None, doc)?;
+            for i in 0..values.count(&ctx) {
+                let (lat, lon) = geo::decode_point(values.value_at(&ctx, i)?);
+                distance = distance.min(geo::haversin_meters(
+                    self.latitude,
+                    self.longitude,
+                    lat,
+                    lon,
+                ));
+            }
+        }
+        Ok(distance)
+    }
+}
+
+impl FieldComparator for GeoDistanceComparator {
+    fn compare(&self, slot1: usize, slot2: usize) -> Ordering {
+        self.values[slot1]
+            .partial_cmp(&self.values[slot2])
+            .unwrap_or(Ordering::Equal)
+    }
+
+    fn value(&self, slot: usize) -> VariantValue {
+        VariantValue::Double(self.values[slot])
+    }
+
+    fn set_bottom(&mut self, slot: usize) {
+        self.bottom = self.values[slot];
+    }
+
+    fn compare_bottom(&self, value: ComparatorValue) -> Result<Ordering> {
+        debug_assert!(value.is_doc());
+        let distance = self.distance(value.doc())?;
+        Ok(self
+            .bottom
+            .partial_cmp(&distance)
+            .unwrap_or(Ordering::Equal))
+    }
+
+    fn copy(&mut self, slot: usize, value: ComparatorValue) -> Result<()> {
+        debug_assert!(value.is_doc());
+        self.values[slot] = self.distance(value.doc())?;
+        Ok(())
+    }
+
+    fn get_information_from_reader<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<()> {
+        self.current_values = match reader.reader.field_info(&self.field) {
+            Some(info) if info.doc_values_type == DocValuesType::SortedNumeric => {
+                Some(reader.reader.get_sorted_numeric_doc_values(&self.field)?)
+            }
+            _ => None,
+        };
+        Ok(())
+    }
+
+    fn get_type(&self) -> SortFieldType {
+        SortFieldType::Double
+    }
+}
+
+impl fmt::Display for GeoDistanceComparator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GeoDistanceComparator(field: {}, latitude: {}, longitude: {}, bottom: {})",
+            self.field, self.latitude, self.longitude, self.bottom
+        )
+    }
+}
+
 pub trait DocValuesSource {
     fn numeric_doc_values<C: Codec>(
         &self,
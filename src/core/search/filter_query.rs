@@ -133,7 +133,21 @@ impl<C: Codec> Weight<C> for FilterWeight<C> {
     }
 
     fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
-        self.weight.explain(reader, doc)
+        let explanation = self.weight.explain(reader, doc)?;
+        if !explanation.is_match() {
+            return Ok(explanation);
+        }
+        for filter in &self.filters {
+            if !filter.leaf_function(reader)?.matches(doc)? {
+                return Ok(Explanation::new(
+                    false,
+                    0.0f32,
+                    format!("no match on filter ({})", filter),
+                    vec![explanation],
+                ));
+            }
+        }
+        Ok(explanation)
     }
 }
 
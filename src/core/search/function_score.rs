@@ -0,0 +1,230 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::any::Any;
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::LeafReaderContext;
+use core::search::double_values::{DoubleValues, DoubleValuesSource};
+use core::search::explanation::Explanation;
+use core::search::searcher::SearchPlanBuilder;
+use core::search::term_query::TermQuery;
+use core::search::{DocIterator, Query, Scorer, Weight};
+use core::util::DocId;
+
+use error::Result;
+
+const FUNCTION_SCORE_QUERY: &str = "function_score";
+
+/// A query matching the documents of the wrapped query and scoring them with
+/// a `DoubleValuesSource`, which may use the score of the wrapped query, e.g.
+/// `0.5 * score + log(popularity)`.
+///
+/// A NaN value scores 0.
+pub struct FunctionScoreQuery<C: Codec> {
+    query: Box<dyn Query<C>>,
+    source: DoubleValuesSource,
+}
+
+impl<C: Codec> FunctionScoreQuery<C> {
+    pub fn new(query: Box<dyn Query<C>>, source: DoubleValuesSource) -> FunctionScoreQuery<C> {
+        FunctionScoreQuery { query, source }
+    }
+
+    pub fn query(&self) -> &dyn Query<C> {
+        self.query.as_ref()
+    }
+
+    pub fn source(&self) -> &DoubleValuesSource {
+        &self.source
+    }
+}
+
+impl<C: Codec> Query<C> for FunctionScoreQuery<C> {
+    fn create_weight(
+        &self,
+        searcher: &dyn SearchPlanBuilder<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        let needs_inner_scores = needs_scores && self.source.needs_scores();
+        let weight = searcher.create_weight(self.query.as_ref(), needs_inner_scores)?;
+        Ok(Box::new(FunctionScoreWeight {
+            weight,
+            source: self.source.clone(),
+            needs_scores,
+            boost: 1.0f32,
+        }))
+    }
+
+    fn extract_terms(&self) -> Vec<TermQuery> {
+        self.query.extract_terms()
+    }
+
+    fn query_type(&self) -> &'static str {
+        FUNCTION_SCORE_QUERY
+    }
+
+    fn as_any(&self) -> &Any {
+        self
+    }
+}
+
+impl<C: Codec> fmt::Display for FunctionScoreQuery<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FunctionScoreQuery(query: {}, source: {})",
+            &self.query, &self.source
+        )
+    }
+}
+
+struct FunctionScoreWeight<C: Codec> {
+    weight: Box<dyn Weight<C>>,
+    source: DoubleValuesSource,
+    needs_scores: bool,
+    boost: f32,
+}
+
+impl<C: Codec> Weight<C> for FunctionScoreWeight<C> {
+    fn create_scorer(
+        &self,
+        leaf_reader: &LeafReaderContext<'_, C>,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        match (self.weight.create_scorer(leaf_reader)?, self.needs_scores) {
+            (Some(scorer), true) => Ok(Some(Box::new(FunctionScorer {
+                scorer,
+                values: self.source.get_values(leaf_reader)?,
+                needs_inner_scores: self.weight.needs_scores(),
+                boost: self.boost,
+            }))),
+            (scorer, _) => Ok(scorer),
+        }
+    }
+
+    fn query_type(&self) -> &'static str {
+        FUNCTION_SCORE_QUERY
+    }
+
+    fn actual_query_type(&self) -> &'static str {
+        self.weight.query_type()
+    }
+
+    fn normalize(&mut self, norm: f32, boost: f32) {
+        // the boost applies to the computed score rather than the inner one
+        self.boost = boost;
+        self.weight.normalize(norm, 1.0f32);
+    }
+
+    fn value_for_normalization(&self) -> f32 {
+        self.weight.value_for_normalization()
+    }
+
+    fn needs_scores(&self) -> bool {
+        self.needs_scores
+    }
+
+    fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
+        let inner = self.weight.explain(reader, doc)?;
+        if !inner.is_match() {
+            return Ok(inner);
+        }
+        let value = self
+            .source
+            .get_values(reader)?
+            .double_value(doc, inner.value())? as f32;
+        let value = if value.is_nan() { 0.0 } else { value };
+        let mut details = vec![inner];
+        if (self.boost - 1.0f32).abs() > ::std::f32::EPSILON {
+            details.push(Explanation::new(true, self.boost, "boost".into(), vec![]));
+        }
+        Ok(Explanation::new(
+            true,
+            value * self.boost,
+            format!("{}, computed from:", self.source),
+            details,
+        ))
+    }
+}
+
+impl<C: Codec> fmt::Display for FunctionScoreWeight<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "FunctionScoreWeight(weight: {}, source: {}, boost: {})",
+            &self.weight, &self.source, self.boost
+        )
+    }
+}
+
+struct FunctionScorer {
+    scorer: Box<dyn Scorer>,
+    values: DoubleValues,
+    needs_inner_scores: bool,
+    boost: f32,
+}
+
+impl Scorer for FunctionScorer {
+    fn score(&mut self) -> Result<f32> {
+        let score = if self.needs_inner_scores {
+            self.scorer.score()?
+        } else {
+            0f32
+        };
+        let value = self.values.double_value(self.scorer.doc_id(), score)? as f32;
+        if value.is_nan() {
+            Ok(0f32)
+        } else {
+            Ok(value * self.boost)
+        }
+    }
+
+    fn support_two_phase(&self) -> bool {
+        self.scorer.support_two_phase()
+    }
+}
+
+impl DocIterator for FunctionScorer {
+    fn doc_id(&self) -> DocId {
+        self.scorer.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.scorer.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.scorer.cost()
+    }
+
+    fn matches(&mut self) -> Result<bool> {
+        self.scorer.matches()
+    }
+
+    fn match_cost(&self) -> f32 {
+        self.scorer.match_cost()
+    }
+
+    fn approximate_next(&mut self) -> Result<DocId> {
+        self.scorer.approximate_next()
+    }
+
+    fn approximate_advance(&mut self, target: DocId) -> Result<DocId> {
+        self.scorer.approximate_advance(target)
+    }
+}
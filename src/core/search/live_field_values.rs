@@ -0,0 +1,168 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::util::{ReferenceManager, RefreshListener};
+
+use error::Result;
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::mem;
+use std::sync::Mutex;
+
+/// Loads the value of a document by id from a searcher, used by
+/// `LiveFieldValues` for the documents not indexed since the last refresh.
+pub trait LiveValueLookup<S: ?Sized, V> {
+    /// The value of the live document `id` in `searcher`, or None if there
+    /// is no such document.
+    fn lookup(&self, searcher: &S, id: &str) -> Result<Option<V>>;
+}
+
+impl<S: ?Sized, V, F> LiveValueLookup<S, V> for F
+where
+    F: Fn(&S, &str) -> Result<Option<V>>,
+{
+    fn lookup(&self, searcher: &S, id: &str) -> Result<Option<V>> {
+        (*self)(searcher, id)
+    }
+}
+
+struct LiveMaps<V> {
+    // the values added since the last refresh started, None for a deleted id
+    current: HashMap<String, Option<V>>,
+    // the values added before the running refresh started, which may not be
+    // visible to the searchers yet
+    old: HashMap<String, Option<V>>,
+}
+
+/// Tracks the values, e.g. a version, of the documents indexed or deleted
+/// since the last refresh of a `SearcherManager`, so that getting a value by
+/// id sees the latest write without reopening the searcher after each one.
+///
+/// Call `add` or `delete` right after updating or deleting the document with
+/// the `IndexWriter`, and register this as the refresh listener of the
+/// manager, e.g. as an `Arc<LiveFieldValues>`, so the values are dropped once
+/// a refreshed searcher sees them. `get` returns the tracked value if there
+/// is one, else looks the id up in the current searcher of the manager.
+pub struct LiveFieldValues<S: ?Sized, V, L: LiveValueLookup<S, V>> {
+    maps: Mutex<LiveMaps<V>>,
+    lookup: L,
+    _searcher: PhantomData<fn(&S)>,
+}
+
+impl<S: ?Sized, V: Clone, L: LiveValueLookup<S, V>> LiveFieldValues<S, V, L> {
+    pub fn new(lookup: L) -> Self {
+        LiveFieldValues {
+            maps: Mutex::new(LiveMaps {
+                current: HashMap::new(),
+                old: HashMap::new(),
+            }),
+            lookup,
+            _searcher: PhantomData,
+        }
+    }
+
+    /// Records the value of the document `id` just indexed.
+    pub fn add(&self, id: &str, value: V) -> Result<()> {
+        self.maps
+            .lock()?
+            .current
+            .insert(id.to_string(), Some(value));
+        Ok(())
+    }
+
+    /// Records the document `id` just deleted, `get` then returns None until
+    /// it's added again.
+    pub fn delete(&self, id: &str) -> Result<()> {
+        self.maps.lock()?.current.insert(id.to_string(), None);
+        Ok(())
+    }
+
+    /// The latest value of the document `id`, or None if it's missing or was
+    /// deleted, falls back to the current searcher of `manager` for the ids
+    /// not written since the last refresh.
+    pub fn get<RM, RL>(&self, id: &str, manager: &RM) -> Result<Option<V>>
+    where
+        RM: ReferenceManager<S, RL>,
+        RL: RefreshListener,
+    {
+        if let Some(value) = self.get_tracked(id)? {
+            return Ok(value);
+        }
+        // the writes since the last refresh are all tracked, so the current
+        // searcher is up to date for this id, even if a refresh runs
+        // concurrently
+        let searcher = manager.acquire()?;
+        let res = self.lookup.lookup(&searcher, id);
+        manager.release(&searcher)?;
+        res
+    }
+
+    /// The tracked value of `id`, Some(None) if it was deleted, None if it
+    /// wasn't written since the last refresh.
+    pub fn get_tracked(&self, id: &str) -> Result<Option<Option<V>>> {
+        let maps = self.maps.lock()?;
+        if let Some(value) = maps.current.get(id) {
+            return Ok(Some(value.clone()));
+        }
+        Ok(maps.old.get(id).cloned())
+    }
+
+    /// The number of ids currently tracked.
+    pub fn size(&self) -> Result<usize> {
+        let maps = self.maps.lock()?;
+        Ok(maps.current.len() + maps.old.len())
+    }
+}
+
+impl<S: ?Sized, V, L: LiveValueLookup<S, V>> RefreshListener for LiveFieldValues<S, V, L> {
+    fn before_refresh(&self) -> Result<()> {
+        // the writes from now on may not be seen by the new searcher, so
+        // they go to a fresh map while the old one is kept until then
+        let mut maps = self.maps.lock()?;
+        maps.old = mem::replace(&mut maps.current, HashMap::new());
+        Ok(())
+    }
+
+    fn after_refresh(&self, _refreshed: bool) -> Result<()> {
+        // the current searcher now sees all the old writes, even if it
+        // wasn't refreshed because nothing changed
+        self.maps.lock()?.old = HashMap::new();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_moves_values() {
+        let values = LiveFieldValues::new(|_: &(), _: &str| Ok(Some(0i64)));
+        values.add("a", 1).unwrap();
+        values.delete("b").unwrap();
+        assert_eq!(values.get_tracked("a").unwrap(), Some(Some(1)));
+        assert_eq!(values.get_tracked("b").unwrap(), Some(None));
+
+        values.before_refresh().unwrap();
+        values.add("a", 2).unwrap();
+        assert_eq!(values.get_tracked("a").unwrap(), Some(Some(2)));
+        assert_eq!(values.get_tracked("b").unwrap(), Some(None));
+        assert_eq!(values.size().unwrap(), 3);
+
+        values.after_refresh(true).unwrap();
+        assert_eq!(values.get_tracked("a").unwrap(), Some(Some(2)));
+        assert_eq!(values.get_tracked("b").unwrap(), None);
+        assert_eq!(values.size().unwrap(), 1);
+    }
+}
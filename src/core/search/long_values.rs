@@ -0,0 +1,121 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::{DocValuesType, LeafReaderContext, NumericDocValuesRef};
+use core::search::double_values::DoubleValuesSource;
+use core::search::sort_field::{SortFieldType, SortedNumericSelector, SortedNumericSelectorType};
+use core::util::{Bits, BitsRef, DocId};
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::fmt;
+
+/// A source of per-document long values read from the numeric doc values of
+/// a field, the integer counterpart of `DoubleValuesSource`.
+///
+/// A source is independent of any segment, `get_values` binds it to a leaf
+/// reader. Use `to_double_values_source` to score hits with it, e.g. with a
+/// `FunctionScoreQuery` or as the variable of an expression.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LongValuesSource {
+    Constant(i64),
+    /// the raw value of a numeric or sorted numeric doc values field, the
+    /// smallest one for a multi-valued doc, documents without a value get 0
+    Field(String),
+}
+
+impl LongValuesSource {
+    /// A source reading the numeric or sorted numeric doc values of `field`.
+    pub fn from_field(field: &str) -> LongValuesSource {
+        LongValuesSource::Field(field.to_string())
+    }
+
+    pub fn constant(value: i64) -> LongValuesSource {
+        LongValuesSource::Constant(value)
+    }
+
+    pub fn get_values<C: Codec>(&self, reader: &LeafReaderContext<'_, C>) -> Result<LongValues> {
+        let values = match self {
+            LongValuesSource::Constant(v) => LongValues::Constant(*v),
+            LongValuesSource::Field(field) => {
+                let leaf = reader.reader;
+                let values = match leaf.field_info(field).map(|info| info.doc_values_type) {
+                    Some(DocValuesType::Numeric) => leaf.get_numeric_doc_values(field)?,
+                    Some(DocValuesType::SortedNumeric) => SortedNumericSelector::wrap(
+                        leaf.get_sorted_numeric_doc_values(field)?,
+                        SortedNumericSelectorType::Min,
+                        SortFieldType::Long,
+                    )?,
+                    // the field is not indexed in this segment
+                    None | Some(DocValuesType::Null) => return Ok(LongValues::Missing),
+                    Some(t) => bail!(IllegalArgument(format!(
+                        "field {} has non-numeric doc values {:?}",
+                        field, t
+                    ))),
+                };
+                LongValues::Field(values, leaf.get_docs_with_field(field)?)
+            }
+        };
+        Ok(values)
+    }
+
+    /// The same values as doubles, e.g. to score hits with them.
+    pub fn to_double_values_source(&self) -> DoubleValuesSource {
+        match self {
+            LongValuesSource::Constant(v) => DoubleValuesSource::Constant(*v as f64),
+            LongValuesSource::Field(field) => DoubleValuesSource::Field {
+                field: field.clone(),
+                field_type: SortFieldType::Long,
+            },
+        }
+    }
+}
+
+impl fmt::Display for LongValuesSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LongValuesSource::Constant(v) => write!(f, "{}", v),
+            LongValuesSource::Field(field) => write!(f, "{}", field),
+        }
+    }
+}
+
+/// The values of a `LongValuesSource` bound to a leaf reader.
+pub enum LongValues {
+    Constant(i64),
+    Field(NumericDocValuesRef, BitsRef),
+    /// the field has no doc values in this leaf
+    Missing,
+}
+
+impl LongValues {
+    /// Returns the value of `doc`, 0 if it has none.
+    pub fn long_value(&self, doc: DocId) -> Result<i64> {
+        match self {
+            LongValues::Constant(v) => Ok(*v),
+            LongValues::Field(values, _) => values.get(doc),
+            LongValues::Missing => Ok(0),
+        }
+    }
+
+    /// Whether `doc` has a value, to tell a missing value from a 0.
+    pub fn has_value(&self, doc: DocId) -> Result<bool> {
+        match self {
+            LongValues::Constant(_) => Ok(true),
+            LongValues::Field(_, docs_with_field) => docs_with_field.get(doc as usize),
+            LongValues::Missing => Ok(false),
+        }
+    }
+}
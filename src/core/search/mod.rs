@@ -42,9 +42,15 @@ pub mod spans;
 
 pub mod bulk_scorer;
 pub mod disi;
+pub mod double_values;
+pub mod expression;
+pub mod fetch;
 pub mod field_comparator;
+pub mod long_values;
 pub mod req_opt;
 pub mod rescorer;
+pub mod result_diff;
+pub mod score_fusion;
 pub mod search_group;
 pub mod sort;
 pub mod sort_field;
@@ -53,7 +59,11 @@ pub mod util;
 
 // Queries
 pub mod boolean_query;
+pub mod boolean_scorer;
 pub mod boost;
+pub mod doc_values_terms_query;
+pub mod exists_query;
+pub mod function_score;
 pub mod phrase_query;
 pub mod query_string;
 pub mod term_query;
@@ -63,8 +73,11 @@ pub mod term_scorer;
 
 // Similarities
 pub mod bm25_similarity;
+pub mod boolean_similarity;
+pub mod tf_cap_similarity;
 
 // IndexSearcher
+pub mod query_timeout;
 pub mod searcher;
 
 // Statistics
@@ -77,6 +90,18 @@ pub mod statistics;
 mod search_manager;
 pub use self::search_manager::*;
 
+mod searcher_lifetime;
+pub use self::searcher_lifetime::*;
+
+mod live_field_values;
+pub use self::live_field_values::*;
+
+mod warm_up;
+pub use self::warm_up::*;
+
+mod bitset_producer;
+pub use self::bitset_producer::*;
+
 error_chain! {
     types {
         Error, ErrorKind, ResultExt;
@@ -343,6 +368,23 @@ pub trait Query<C: Codec>: Display {
 pub trait Weight<C: Codec>: Display {
     fn create_scorer(&self, reader: &LeafReaderContext<'_, C>) -> Result<Option<Box<dyn Scorer>>>;
 
+    /// Creates a scorer only iterated with `DocIterator::next`, from the first
+    /// matching doc to the last, as the top level query is by the searcher.
+    /// Weights can return a scorer specialized for this access pattern, the
+    /// default is `create_scorer`.
+    ///
+    /// If `score_docs_in_order` is false the collector accepts docs out of
+    /// order, see `Collector::accepts_docs_out_of_order`, so the scorer may
+    /// return the docs of the leaf in any order, and doesn't need to support
+    /// `DocIterator::advance`.
+    fn create_bulk_scorer(
+        &self,
+        reader: &LeafReaderContext<'_, C>,
+        _score_docs_in_order: bool,
+    ) -> Result<Option<Box<dyn Scorer>>> {
+        self.create_scorer(reader)
+    }
+
     fn hash_code(&self) -> u32 {
         let key = format!("{}", self);
         let mut hasher = DefaultHasher::new();
@@ -268,7 +268,7 @@ impl<C: Codec> Weight<C> for PhraseWeight<C> {
         &self,
         reader_context: &LeafReaderContext<'_, C>,
     ) -> Result<Option<Box<dyn Scorer>>> {
-        debug_assert!(!self.terms.len() >= 2);
+        debug_assert!(self.terms.len() >= 2);
 
         let mut postings_freqs = Vec::with_capacity(self.terms.len());
         let mut term_iter = if let Some(field_terms) = reader_context.reader.terms(&self.field)? {
@@ -344,7 +344,7 @@ impl<C: Codec> Weight<C> for PhraseWeight<C> {
     }
 
     fn explain(&self, reader: &LeafReaderContext<'_, C>, doc: DocId) -> Result<Explanation> {
-        debug_assert!(!self.terms.len() >= 2);
+        debug_assert!(self.terms.len() >= 2);
 
         let mut matched = true;
         let mut postings_freqs = Vec::with_capacity(self.terms.len());
@@ -557,10 +557,10 @@ impl<T: PostingIterator + 'static> ExactPhraseScorer<T> {
             Vec::with_capacity(postings.len());
 
         for (i, posing) in postings.into_iter().enumerate() {
-            let mut iterator = posing.postings;
+            let iterator = Box::new(posing.postings);
             iterators.push(PostingsIterAsScorer { iterator });
             postings_and_positions.push(PostingsAndPosition::new(
-                &mut iterators[i].iterator,
+                &mut *iterators[i].iterator,
                 posing.pos,
             ));
         }
@@ -828,8 +828,10 @@ impl PartialEq for PPElement {
 impl Eq for PPElement {}
 
 // TODO a fake scorer struct used for `ConjunctionScorer`
+// the iterator is boxed so that the raw pointers `PostingsAndPosition` and
+// `PhrasePositions` keep to it stay valid when the conjunction moves it
 struct PostingsIterAsScorer<T: PostingIterator> {
-    pub iterator: T,
+    pub iterator: Box<T>,
 }
 
 impl<T: PostingIterator> Scorer for PostingsIterAsScorer<T> {
@@ -897,10 +899,10 @@ impl<T: PostingIterator + 'static> SloppyPhraseScorer<T> {
         let mut doc_iterators = Vec::with_capacity(num_postings);
         let mut phrase_positions = Vec::with_capacity(num_postings);
         for (idx, posting) in postings.into_iter().enumerate() {
-            let mut iterator = posting.postings;
+            let iterator = Box::new(posting.postings);
             doc_iterators.push(PostingsIterAsScorer { iterator });
             phrase_positions.push(PhrasePositions::new(
-                &mut doc_iterators[idx].iterator,
+                &mut *doc_iterators[idx].iterator,
                 posting.pos,
                 idx as i32,
                 posting.terms.clone(),
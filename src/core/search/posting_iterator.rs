@@ -129,3 +129,137 @@ impl PostingIterator for EmptyPostingIterator {
         Ok(Payload::new())
     }
 }
+
+/// Per-document scoring factors of a term: its frequency and the norm of the
+/// document. An impact competes with another one if it has a higher freq or a
+/// lower norm, norms are compared as unsigned values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Impact {
+    pub freq: i32,
+    pub norm: i64,
+}
+
+impl Impact {
+    pub fn new(freq: i32, norm: i64) -> Impact {
+        Impact { freq, norm }
+    }
+}
+
+/// The competitive impacts of the docs of a postings list, grouped by skip level.
+///
+/// Level `0` covers the docs up to `doc_id_up_to(0)`, higher levels cover
+/// increasingly larger ranges of docs. The impacts of a level are sorted by
+/// increasing freq and increasing unsigned norm.
+#[derive(Debug, Clone)]
+pub struct Impacts {
+    levels: Vec<(DocId, Vec<Impact>)>,
+}
+
+impl Impacts {
+    pub fn new(levels: Vec<(DocId, Vec<Impact>)>) -> Impacts {
+        debug_assert!(!levels.is_empty());
+        Impacts { levels }
+    }
+
+    /// Impacts that can't skip anything: any freq up to `NO_MORE_DOCS`.
+    pub fn dummy() -> Impacts {
+        Impacts::new(vec![(NO_MORE_DOCS, vec![Impact::new(i32::max_value(), 1)])])
+    }
+
+    /// Return the number of levels on which we have impacts, always at least 1.
+    pub fn num_levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Return the maximum inclusive doc id until which the impacts of the
+    /// given level are valid.
+    pub fn doc_id_up_to(&self, level: usize) -> DocId {
+        self.levels[level].0
+    }
+
+    /// Return the competitive impacts of the given level.
+    pub fn impacts(&self, level: usize) -> &[Impact] {
+        &self.levels[level].1
+    }
+}
+
+/// Source of `Impacts`, used by scorers to compute score upper bounds of
+/// blocks of docs, e.g. for block-max WAND.
+pub trait ImpactsSource {
+    /// Shallow-advance to `target`. This is cheaper than calling
+    /// `DocIterator::advance` and allows further calls to `impacts` to ignore
+    /// doc ids that are less than `target`.
+    fn advance_shallow(&mut self, target: DocId) -> Result<()>;
+
+    /// Get information about upcoming blocks of postings, the first level
+    /// always includes the current doc or the target of the last
+    /// `advance_shallow` call.
+    fn impacts(&mut self) -> Result<Impacts>;
+}
+
+/// A `PostingIterator` that also exposes the impacts of its postings.
+pub trait ImpactsEnum: PostingIterator + ImpactsSource {}
+
+impl<T: PostingIterator + ImpactsSource> ImpactsEnum for T {}
+
+/// `ImpactsEnum` for postings that don't record impacts, it returns a
+/// single level of impacts that matches any freq and norm.
+pub struct SlowImpactsEnum<T: PostingIterator> {
+    postings: T,
+}
+
+impl<T: PostingIterator> SlowImpactsEnum<T> {
+    pub fn new(postings: T) -> Self {
+        SlowImpactsEnum { postings }
+    }
+}
+
+impl<T: PostingIterator> ImpactsSource for SlowImpactsEnum<T> {
+    fn advance_shallow(&mut self, _target: DocId) -> Result<()> {
+        Ok(())
+    }
+
+    fn impacts(&mut self) -> Result<Impacts> {
+        Ok(Impacts::dummy())
+    }
+}
+
+impl<T: PostingIterator> PostingIterator for SlowImpactsEnum<T> {
+    fn freq(&self) -> Result<i32> {
+        self.postings.freq()
+    }
+
+    fn next_position(&mut self) -> Result<i32> {
+        self.postings.next_position()
+    }
+
+    fn start_offset(&self) -> Result<i32> {
+        self.postings.start_offset()
+    }
+
+    fn end_offset(&self) -> Result<i32> {
+        self.postings.end_offset()
+    }
+
+    fn payload(&self) -> Result<Payload> {
+        self.postings.payload()
+    }
+}
+
+impl<T: PostingIterator> DocIterator for SlowImpactsEnum<T> {
+    fn doc_id(&self) -> DocId {
+        self.postings.doc_id()
+    }
+
+    fn next(&mut self) -> Result<DocId> {
+        self.postings.next()
+    }
+
+    fn advance(&mut self, target: DocId) -> Result<DocId> {
+        self.postings.advance(target)
+    }
+
+    fn cost(&self) -> usize {
+        self.postings.cost()
+    }
+}
@@ -15,6 +15,7 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
+use std::mem;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, RwLock};
 
@@ -30,7 +31,7 @@ use core::util::bit_set::{BitSet, FixedBitSet, ImmutableBitSet};
 use core::util::bit_util::UnsignedShift;
 use core::util::doc_id_set::{BitDocIdSet, BitSetIterator, DocIdSetDocIterEnum, DocIdSetEnum};
 use core::util::external::deferred::Deferred;
-use core::util::{Bits, DocId};
+use core::util::{vec_ram_bytes, Accountable, Bits, DocId, RamUsage};
 
 use core::codec::Codec;
 use error::Result;
@@ -146,6 +147,15 @@ impl LeafCache {
     }
 }
 
+impl Accountable for LeafCache {
+    fn ram_bytes_used(&self) -> usize {
+        self.leaf_cache
+            .values()
+            .map(Accountable::ram_bytes_used)
+            .sum()
+    }
+}
+
 struct CacheData {
     // maps queries that are contained in the cache to a singleton so that this
     // cache does not store several copies of the same query
@@ -279,6 +289,28 @@ impl LRUQueryCache {
     }
 }
 
+/// The cached doc id sets, the per leaf usage is given by the child
+/// resources.
+impl Accountable for LRUQueryCache {
+    fn ram_bytes_used(&self) -> usize {
+        let cache_data = self.cache_data.read().unwrap();
+        cache_data
+            .cache
+            .values()
+            .map(LeafCache::ram_bytes_used)
+            .sum()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        let cache_data = self.cache_data.read().unwrap();
+        cache_data
+            .cache
+            .iter()
+            .map(|(key, leaf_cache)| RamUsage::of(format!("segment '{}'", key), leaf_cache))
+            .collect()
+    }
+}
+
 impl<C: Codec> QueryCache<C> for LRUQueryCache {
     fn do_cache(
         &self,
@@ -762,6 +794,17 @@ impl RoaringDocIdSetBuilder {
     }
 }
 
+impl Accountable for RoaringDocIdSet {
+    fn ram_bytes_used(&self) -> usize {
+        self.doc_id_sets.len() * mem::size_of::<Option<DocIdSetEnum>>()
+            + self
+                .doc_id_sets
+                .iter()
+                .map(|set| set.as_ref().map_or(0, |s| s.ram_bytes_used()))
+                .sum::<usize>()
+    }
+}
+
 impl DocIdSet for RoaringDocIdSet {
     type Iter = RoaringDocIterator;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
@@ -884,6 +927,12 @@ impl ShortArrayDocIdSet {
     }
 }
 
+impl Accountable for ShortArrayDocIdSet {
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.docs)
+    }
+}
+
 impl DocIdSet for ShortArrayDocIdSet {
     type Iter = ShortArrayDocIterator;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
@@ -964,6 +1013,12 @@ impl<T: DocIdSet> NotDocIdSet<T> {
     }
 }
 
+impl<T: DocIdSet + Accountable> Accountable for NotDocIdSet<T> {
+    fn ram_bytes_used(&self) -> usize {
+        self.set.ram_bytes_used()
+    }
+}
+
 impl<T: DocIdSet> DocIdSet for NotDocIdSet<T> {
     type Iter = NotDocIterator<T::Iter>;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
@@ -1038,6 +1093,15 @@ enum CacheDocIdSetEnum {
     Roaring(RoaringDocIdSet),
 }
 
+impl Accountable for CacheDocIdSetEnum {
+    fn ram_bytes_used(&self) -> usize {
+        match self {
+            CacheDocIdSetEnum::Bit(s) => s.ram_bytes_used(),
+            CacheDocIdSetEnum::Roaring(s) => s.ram_bytes_used(),
+        }
+    }
+}
+
 impl DocIdSet for CacheDocIdSetEnum {
     type Iter = CachedDocIdSetIterEnum;
 
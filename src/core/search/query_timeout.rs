@@ -0,0 +1,210 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::index::{SeekStatus, TermIterator};
+use core::search::collector;
+use error::{ErrorKind, Result};
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// The time budget of a query, shared by every stage of its execution:
+/// weight creation, term enumeration and doc collection.
+///
+/// Clones share the same timed out flag, so once any stage runs out of time
+/// the others see it too.
+#[derive(Clone, Debug)]
+pub struct QueryTimeout {
+    timeout_duration: Duration,
+    start_time: SystemTime,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl QueryTimeout {
+    pub fn new(timeout_duration: Duration, start_time: SystemTime) -> QueryTimeout {
+        QueryTimeout {
+            timeout_duration,
+            start_time,
+            timed_out: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// A budget of `timeout_duration` starting now.
+    pub fn from_now(timeout_duration: Duration) -> QueryTimeout {
+        Self::new(timeout_duration, SystemTime::now())
+    }
+
+    pub fn timeout_duration(&self) -> Duration {
+        self.timeout_duration
+    }
+
+    pub fn start_time(&self) -> SystemTime {
+        self.start_time
+    }
+
+    /// The flag set once the budget is exceeded.
+    pub fn timed_out_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.timed_out)
+    }
+
+    /// Whether the budget has been found exceeded by any stage of the query.
+    pub fn is_timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::Acquire)
+    }
+
+    /// Returns true and marks the query as timed out if the budget is
+    /// exceeded.
+    pub fn should_exit(&self) -> bool {
+        if self.is_timed_out() {
+            return true;
+        }
+        let now = SystemTime::now();
+        let exceeded = match now.duration_since(self.start_time) {
+            Ok(elapsed) => elapsed >= self.timeout_duration,
+            // the clock went backwards
+            Err(_) => false,
+        };
+        if exceeded {
+            self.timed_out.store(true, Ordering::Release);
+        }
+        exceeded
+    }
+
+    /// Fails with `CollectionTerminated` if the budget is exceeded, the error
+    /// the `TimeoutCollector` stops the collection with.
+    pub fn check(&self) -> Result<()> {
+        if self.should_exit() {
+            bail!(ErrorKind::Collector(
+                collector::ErrorKind::CollectionTerminated,
+            ))
+        }
+        Ok(())
+    }
+}
+
+/// The number of terms `TimeLimitingTermIterator` iterates between two
+/// checks of the clock.
+const TERMS_BETWEEN_CHECKS: u32 = 64;
+
+/// A `TermIterator` failing with `CollectionTerminated` once the budget of
+/// the query is exceeded, so expanding a query over a large terms dictionary
+/// can't run past the budget before any doc is collected.
+///
+/// Seeks are checked each time, `next` every `TERMS_BETWEEN_CHECKS` terms.
+pub struct TimeLimitingTermIterator<T: TermIterator> {
+    terms: T,
+    timeout: QueryTimeout,
+    unchecked: u32,
+}
+
+impl<T: TermIterator> TimeLimitingTermIterator<T> {
+    pub fn new(terms: T, timeout: QueryTimeout) -> Self {
+        TimeLimitingTermIterator {
+            terms,
+            timeout,
+            unchecked: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.terms
+    }
+}
+
+impl<T: TermIterator> TermIterator for TimeLimitingTermIterator<T> {
+    type Postings = T::Postings;
+    type TermState = T::TermState;
+
+    fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        self.unchecked += 1;
+        if self.unchecked >= TERMS_BETWEEN_CHECKS {
+            self.unchecked = 0;
+            self.timeout.check()?;
+        }
+        self.terms.next()
+    }
+
+    fn seek_exact(&mut self, text: &[u8]) -> Result<bool> {
+        self.timeout.check()?;
+        self.terms.seek_exact(text)
+    }
+
+    fn seek_ceil(&mut self, text: &[u8]) -> Result<SeekStatus> {
+        self.timeout.check()?;
+        self.terms.seek_ceil(text)
+    }
+
+    fn seek_exact_ord(&mut self, ord: i64) -> Result<()> {
+        self.timeout.check()?;
+        self.terms.seek_exact_ord(ord)
+    }
+
+    fn seek_exact_state(&mut self, text: &[u8], state: &Self::TermState) -> Result<()> {
+        self.terms.seek_exact_state(text, state)
+    }
+
+    fn term(&self) -> Result<&[u8]> {
+        self.terms.term()
+    }
+
+    fn ord(&self) -> Result<i64> {
+        self.terms.ord()
+    }
+
+    fn doc_freq(&mut self) -> Result<i32> {
+        self.terms.doc_freq()
+    }
+
+    fn total_term_freq(&mut self) -> Result<i64> {
+        self.terms.total_term_freq()
+    }
+
+    fn postings_with_flags(&mut self, flags: u16) -> Result<Self::Postings> {
+        self.terms.postings_with_flags(flags)
+    }
+
+    fn term_state(&mut self) -> Result<Self::TermState> {
+        self.terms.term_state()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::index::EmptyTermIterator;
+
+    #[test]
+    fn test_query_timeout() {
+        let timeout = QueryTimeout::from_now(Duration::from_secs(3600));
+        assert!(!timeout.should_exit());
+        assert!(timeout.check().is_ok());
+
+        let start = SystemTime::now() - Duration::from_secs(10);
+        let timeout = QueryTimeout::new(Duration::from_secs(1), start);
+        let shared = timeout.clone();
+        assert!(timeout.check().is_err());
+        assert!(shared.is_timed_out());
+
+        let mut terms = TimeLimitingTermIterator::new(EmptyTermIterator::default(), shared);
+        assert!(terms.seek_ceil(b"abc").is_err());
+        for _ in 0..TERMS_BETWEEN_CHECKS - 1 {
+            assert!(terms.next().unwrap().is_none());
+        }
+        assert!(terms.next().is_err());
+    }
+}
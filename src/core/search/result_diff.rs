@@ -0,0 +1,286 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::Codec;
+use core::index::IndexReader;
+use core::search::collector::top_docs::TopDocsCollector;
+use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+use core::search::top_docs::{ScoreDocHit, TopDocs};
+use core::search::{Query, SimilarityProducer};
+use core::util::DocId;
+
+use error::Result;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+
+/// A document ranked by both result lists of a `ResultDiff`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankChange {
+    pub doc: DocId,
+    pub baseline_rank: usize,
+    pub candidate_rank: usize,
+    pub baseline_score: f32,
+    pub candidate_score: f32,
+}
+
+impl RankChange {
+    /// How many ranks the document moved, negative if it moved up.
+    pub fn rank_delta(&self) -> i64 {
+        self.candidate_rank as i64 - self.baseline_rank as i64
+    }
+
+    pub fn score_delta(&self) -> f32 {
+        self.candidate_score - self.baseline_score
+    }
+}
+
+/// A document ranked by only one of the result lists of a `ResultDiff`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedDoc {
+    pub doc: DocId,
+    pub rank: usize,
+    pub score: f32,
+}
+
+/// The differences between a baseline and a candidate ranking of the same
+/// index, for relevance regression tests.
+///
+/// Ranks are 0-based positions in the result lists. Documents are matched by
+/// doc id, so both rankings must come from the same reader.
+#[derive(Debug, Clone, Default)]
+pub struct ResultDiff {
+    /// documents ranked by both lists, in baseline order
+    pub common: Vec<RankChange>,
+    /// documents only ranked by the candidate, in candidate order
+    pub new_docs: Vec<RankedDoc>,
+    /// documents only ranked by the baseline, in baseline order
+    pub dropped_docs: Vec<RankedDoc>,
+    pub baseline_len: usize,
+    pub candidate_len: usize,
+}
+
+impl ResultDiff {
+    pub fn new(baseline: &[ScoreDocHit], candidate: &[ScoreDocHit]) -> ResultDiff {
+        let mut candidate_ranks: HashMap<DocId, usize> = HashMap::with_capacity(candidate.len());
+        for (rank, hit) in candidate.iter().enumerate() {
+            candidate_ranks.entry(hit.doc_id()).or_insert(rank);
+        }
+
+        let mut diff = ResultDiff {
+            baseline_len: baseline.len(),
+            candidate_len: candidate.len(),
+            ..Default::default()
+        };
+        let mut seen: HashMap<DocId, usize> = HashMap::with_capacity(baseline.len());
+        for (rank, hit) in baseline.iter().enumerate() {
+            if seen.insert(hit.doc_id(), rank).is_some() {
+                continue;
+            }
+            match candidate_ranks.get(&hit.doc_id()) {
+                Some(&candidate_rank) => diff.common.push(RankChange {
+                    doc: hit.doc_id(),
+                    baseline_rank: rank,
+                    candidate_rank,
+                    baseline_score: hit.score(),
+                    candidate_score: candidate[candidate_rank].score(),
+                }),
+                None => diff.dropped_docs.push(RankedDoc {
+                    doc: hit.doc_id(),
+                    rank,
+                    score: hit.score(),
+                }),
+            }
+        }
+        for (rank, hit) in candidate.iter().enumerate() {
+            if !seen.contains_key(&hit.doc_id()) && candidate_ranks[&hit.doc_id()] == rank {
+                diff.new_docs.push(RankedDoc {
+                    doc: hit.doc_id(),
+                    rank,
+                    score: hit.score(),
+                });
+            }
+        }
+        diff
+    }
+
+    pub fn from_top_docs(baseline: &TopDocs, candidate: &TopDocs) -> ResultDiff {
+        Self::new(baseline.score_docs(), candidate.score_docs())
+    }
+
+    /// Whether both lists rank the same documents in the same order, scores
+    /// may still differ.
+    pub fn same_ranking(&self) -> bool {
+        self.new_docs.is_empty()
+            && self.dropped_docs.is_empty()
+            && self.common.iter().all(|c| c.rank_delta() == 0)
+    }
+
+    /// The documents ranked by both lists at different ranks.
+    pub fn moved(&self) -> impl Iterator<Item = &RankChange> {
+        self.common.iter().filter(|c| c.rank_delta() != 0)
+    }
+
+    /// The largest absolute score change of the documents ranked by both lists.
+    pub fn max_score_delta(&self) -> f32 {
+        self.common
+            .iter()
+            .map(|c| c.score_delta().abs())
+            .fold(0.0, f32::max)
+    }
+
+    /// The fraction of documents of the longest list ranked by both lists.
+    pub fn overlap(&self) -> f32 {
+        let len = self.baseline_len.max(self.candidate_len);
+        if len == 0 {
+            1.0
+        } else {
+            self.common.len() as f32 / len as f32
+        }
+    }
+}
+
+impl fmt::Display for ResultDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "baseline: {} hits, candidate: {} hits, common: {}",
+            self.baseline_len,
+            self.candidate_len,
+            self.common.len()
+        )?;
+        for c in self.moved() {
+            writeln!(
+                f,
+                "moved doc {}: rank {} -> {} (score {} -> {})",
+                c.doc, c.baseline_rank, c.candidate_rank, c.baseline_score, c.candidate_score
+            )?;
+        }
+        for d in &self.new_docs {
+            writeln!(f, "new doc {}: rank {} (score {})", d.doc, d.rank, d.score)?;
+        }
+        for d in &self.dropped_docs {
+            writeln!(
+                f,
+                "dropped doc {}: rank {} (score {})",
+                d.doc, d.rank, d.score
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn top_docs<C: Codec, IS: IndexSearcher<C>>(
+    searcher: &IS,
+    query: &dyn Query<C>,
+    top_n: usize,
+) -> Result<TopDocs> {
+    let mut collector = TopDocsCollector::new(top_n);
+    searcher.search(query, &mut collector)?;
+    Ok(collector.top_docs())
+}
+
+/// Diffs the top `top_n` hits of two queries run by the same searcher.
+pub fn diff_queries<C: Codec, IS: IndexSearcher<C>>(
+    searcher: &IS,
+    baseline: &dyn Query<C>,
+    candidate: &dyn Query<C>,
+    top_n: usize,
+) -> Result<ResultDiff> {
+    Ok(ResultDiff::from_top_docs(
+        &top_docs(searcher, baseline, top_n)?,
+        &top_docs(searcher, candidate, top_n)?,
+    ))
+}
+
+/// Diffs the top `top_n` hits of a query run by two searchers, which must
+/// search the same reader for the doc ids to be comparable.
+pub fn diff_searchers<C: Codec, A: IndexSearcher<C>, B: IndexSearcher<C>>(
+    baseline: &A,
+    candidate: &B,
+    query: &dyn Query<C>,
+    top_n: usize,
+) -> Result<ResultDiff> {
+    Ok(ResultDiff::from_top_docs(
+        &top_docs(baseline, query, top_n)?,
+        &top_docs(candidate, query, top_n)?,
+    ))
+}
+
+/// Diffs the top `top_n` hits of a query scored with two similarities, see
+/// `DefaultIndexSearcher::search_with_similarity`.
+pub fn diff_similarities<C, R, IR, SP>(
+    searcher: &DefaultIndexSearcher<C, R, IR, SP>,
+    query: &dyn Query<C>,
+    baseline: &dyn SimilarityProducer<C>,
+    candidate: &dyn SimilarityProducer<C>,
+    top_n: usize,
+) -> Result<ResultDiff>
+where
+    C: Codec,
+    R: IndexReader<Codec = C> + ?Sized,
+    IR: Deref<Target = R>,
+    SP: SimilarityProducer<C>,
+{
+    let mut baseline_collector = TopDocsCollector::new(top_n);
+    searcher.search_with_similarity(query, &mut baseline_collector, baseline)?;
+    let mut candidate_collector = TopDocsCollector::new(top_n);
+    searcher.search_with_similarity(query, &mut candidate_collector, candidate)?;
+    Ok(ResultDiff::from_top_docs(
+        &baseline_collector.top_docs(),
+        &candidate_collector.top_docs(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::top_docs::ScoreDoc;
+
+    fn hits(docs: &[(DocId, f32)]) -> Vec<ScoreDocHit> {
+        docs.iter()
+            .map(|&(doc, score)| ScoreDocHit::Score(ScoreDoc::new(doc, score)))
+            .collect()
+    }
+
+    #[test]
+    fn test_result_diff() {
+        let baseline = hits(&[(1, 3.0), (2, 2.0), (3, 1.0)]);
+        let candidate = hits(&[(2, 2.5), (1, 2.0), (4, 1.5)]);
+        let diff = ResultDiff::new(&baseline, &candidate);
+
+        assert!(!diff.same_ranking());
+        assert_eq!(diff.common.len(), 2);
+        assert_eq!(diff.common[0].doc, 1);
+        assert_eq!(diff.common[0].rank_delta(), 1);
+        assert!((diff.common[0].score_delta() + 1.0).abs() < ::std::f32::EPSILON);
+        assert_eq!(diff.common[1].rank_delta(), -1);
+        assert_eq!(
+            diff.new_docs,
+            vec![RankedDoc {
+                doc: 4,
+                rank: 2,
+                score: 1.5
+            }]
+        );
+        assert_eq!(diff.dropped_docs.len(), 1);
+        assert_eq!(diff.dropped_docs[0].doc, 3);
+        assert_eq!(diff.moved().count(), 2);
+        assert!((diff.overlap() - 2.0 / 3.0).abs() < ::std::f32::EPSILON);
+
+        let diff = ResultDiff::new(&baseline, &hits(&[(1, 1.0), (2, 0.5), (3, 0.1)]));
+        assert!(diff.same_ranking());
+        assert!((diff.max_score_delta() - 2.0).abs() < ::std::f32::EPSILON);
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::search::top_docs::{ScoreDoc, ScoreDocHit, TopDocs, TopScoreDocs};
+use core::util::DocId;
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::f32;
+
+/// The default rank constant of the reciprocal rank fusion, damping the
+/// weight of the top ranks.
+pub const DEFAULT_RRF_RANK_CONSTANT: f32 = 60.0;
+
+/// How the scores of a result list are rescaled before being combined with
+/// the scores of other lists, which are usually on different scales, e.g.
+/// BM25 scores and vector similarities.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScoreNormalization {
+    /// The scores are kept as is.
+    None,
+    /// The scores are rescaled to `[0, 1]` by `(score - min) / (max - min)`,
+    /// they are all 1 if they are equal.
+    MinMax,
+    /// The scores are standardized by `(score - mean) / std_dev`, they are
+    /// all 0 if they are equal.
+    ZScore,
+}
+
+impl ScoreNormalization {
+    /// Rescales the scores in place.
+    pub fn normalize(&self, scores: &mut [f32]) {
+        if scores.is_empty() {
+            return;
+        }
+        match *self {
+            ScoreNormalization::None => {}
+            ScoreNormalization::MinMax => {
+                let min = scores.iter().cloned().fold(f32::INFINITY, f32::min);
+                let max = scores.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+                let range = max - min;
+                for score in scores.iter_mut() {
+                    *score = if range > 0.0 {
+                        (*score - min) / range
+                    } else {
+                        1.0
+                    };
+                }
+            }
+            ScoreNormalization::ZScore => {
+                let len = scores.len() as f64;
+                let mean = scores.iter().map(|&s| f64::from(s)).sum::<f64>() / len;
+                let variance = scores
+                    .iter()
+                    .map(|&s| (f64::from(s) - mean).powi(2))
+                    .sum::<f64>()
+                    / len;
+                let std_dev = variance.sqrt();
+                for score in scores.iter_mut() {
+                    *score = if std_dev > 0.0 {
+                        ((f64::from(*score) - mean) / std_dev) as f32
+                    } else {
+                        0.0
+                    };
+                }
+            }
+        }
+    }
+}
+
+/// How `ResultFusion` combines the result lists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FusionMethod {
+    /// The weighted sum of the normalized scores of a doc in each list, a
+    /// list not ranking the doc adds nothing.
+    WeightedSum(ScoreNormalization),
+    /// Reciprocal rank fusion, the weighted sum of `1 / (rank_constant +
+    /// rank)` over the lists ranking the doc, with 1-based ranks. Only the
+    /// ranks are used, so the lists need no score.
+    ReciprocalRank { rank_constant: f32 },
+}
+
+/// Combines several result lists of the same index, e.g. the lexical and
+/// the vector results of a hybrid search, into a single ranking.
+///
+/// Hits are identified by their shard index and doc id. The fused list is
+/// sorted by fused score, ties are broken by shard index then doc id, so the
+/// fusion is deterministic whatever the order of the hits with equal scores
+/// in the input lists. The `total_hits` of the fused list is the number of
+/// distinct hits in the input lists.
+#[derive(Debug, Clone)]
+pub struct ResultFusion {
+    method: FusionMethod,
+    weights: Vec<f32>,
+}
+
+impl ResultFusion {
+    pub fn new(method: FusionMethod) -> ResultFusion {
+        ResultFusion {
+            method,
+            weights: vec![],
+        }
+    }
+
+    /// The weighted sum of the min-max normalized scores.
+    pub fn min_max() -> ResultFusion {
+        Self::new(FusionMethod::WeightedSum(ScoreNormalization::MinMax))
+    }
+
+    /// The weighted sum of the z-score normalized scores.
+    pub fn z_score() -> ResultFusion {
+        Self::new(FusionMethod::WeightedSum(ScoreNormalization::ZScore))
+    }
+
+    /// Reciprocal rank fusion with the default rank constant.
+    pub fn reciprocal_rank() -> ResultFusion {
+        Self::new(FusionMethod::ReciprocalRank {
+            rank_constant: DEFAULT_RRF_RANK_CONSTANT,
+        })
+    }
+
+    /// Sets the weight of each result list, in the order the lists are
+    /// passed to `fuse`, all the lists weigh 1 by default.
+    pub fn with_weights(mut self, weights: Vec<f32>) -> ResultFusion {
+        self.weights = weights;
+        self
+    }
+
+    pub fn method(&self) -> FusionMethod {
+        self.method
+    }
+
+    /// Fuses the result lists into the top `size` hits.
+    pub fn fuse(&self, results: &[TopDocs], size: usize) -> Result<TopScoreDocs> {
+        if !self.weights.is_empty() && self.weights.len() != results.len() {
+            bail!(IllegalArgument(format!(
+                "got {} weights for {} result lists",
+                self.weights.len(),
+                results.len()
+            )));
+        }
+
+        let mut fused: HashMap<(usize, DocId), f32> = HashMap::new();
+        for (i, result) in results.iter().enumerate() {
+            let weight = self.weights.get(i).cloned().unwrap_or(1.0);
+            let hits = result.score_docs();
+            let scores = self.list_scores(hits)?;
+            for (hit, score) in hits.iter().zip(scores) {
+                *fused
+                    .entry((hit.shard_index(), hit.doc_id()))
+                    .or_insert(0.0) += weight * score;
+            }
+        }
+
+        let total_hits = fused.len();
+        let mut hits: Vec<((usize, DocId), f32)> = fused.into_iter().collect();
+        hits.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        hits.truncate(size);
+        let score_docs = hits
+            .into_iter()
+            .map(|((shard_index, doc), score)| {
+                let mut score_doc = ScoreDoc::new(doc, score);
+                score_doc.shard_index = shard_index;
+                ScoreDocHit::Score(score_doc)
+            })
+            .collect();
+        Ok(TopScoreDocs::new(total_hits, score_docs))
+    }
+
+    // the contribution of each hit of a list before weighting
+    fn list_scores(&self, hits: &[ScoreDocHit]) -> Result<Vec<f32>> {
+        match self.method {
+            FusionMethod::WeightedSum(normalization) => {
+                let mut scores: Vec<f32> = hits.iter().map(|h| h.score()).collect();
+                if scores.iter().any(|s| !s.is_finite()) {
+                    bail!(IllegalArgument(
+                        "can't fuse the scores of a result list whose scores were not tracked"
+                            .into()
+                    ));
+                }
+                normalization.normalize(&mut scores);
+                Ok(scores)
+            }
+            FusionMethod::ReciprocalRank { rank_constant } => Ok((0..hits.len())
+                .map(|rank| 1.0 / (rank_constant + rank as f32 + 1.0))
+                .collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn top_docs(hits: &[(DocId, f32)]) -> TopDocs {
+        let score_docs = hits
+            .iter()
+            .map(|&(doc, score)| ScoreDocHit::Score(ScoreDoc::new(doc, score)))
+            .collect();
+        TopDocs::Score(TopScoreDocs::new(hits.len(), score_docs))
+    }
+
+    fn docs(fused: &TopScoreDocs) -> Vec<DocId> {
+        fused.score_docs().iter().map(|h| h.doc_id()).collect()
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut scores = vec![2.0, 4.0, 6.0];
+        ScoreNormalization::MinMax.normalize(&mut scores);
+        assert_eq!(scores, vec![0.0, 0.5, 1.0]);
+
+        let mut scores = vec![3.0, 3.0];
+        ScoreNormalization::MinMax.normalize(&mut scores);
+        assert_eq!(scores, vec![1.0, 1.0]);
+
+        let mut scores = vec![2.0, 4.0, 6.0];
+        ScoreNormalization::ZScore.normalize(&mut scores);
+        assert!((scores[0] + 1.224_744_9).abs() < 1e-5);
+        assert!(scores[1].abs() < 1e-6);
+        assert!((scores[2] - 1.224_744_9).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_fuse() {
+        let lexical = top_docs(&[(1, 12.0), (2, 8.0), (3, 2.0)]);
+        let vector = top_docs(&[(3, 0.9), (4, 0.8), (1, 0.5)]);
+        let results = [lexical, vector];
+
+        let fused = ResultFusion::min_max().fuse(&results, 10).unwrap();
+        assert_eq!(fused.total_hits, 4);
+        // 1: 1.0 + 0.0, 3: 0.0 + 1.0, 2: 0.6, 4: 0.75
+        assert_eq!(docs(&fused), vec![1, 3, 4, 2]);
+
+        let fused = ResultFusion::min_max()
+            .with_weights(vec![1.0, 2.0])
+            .fuse(&results, 2)
+            .unwrap();
+        assert_eq!(docs(&fused), vec![3, 4]);
+
+        let fused = ResultFusion::reciprocal_rank().fuse(&results, 10).unwrap();
+        assert_eq!(docs(&fused), vec![1, 3, 2, 4]);
+
+        assert!(ResultFusion::reciprocal_rank()
+            .with_weights(vec![1.0])
+            .fuse(&results, 10)
+            .is_err());
+        let untracked = top_docs(&[(1, ::std::f32::NAN)]);
+        assert!(ResultFusion::z_score().fuse(&[untracked], 10).is_err());
+    }
+}
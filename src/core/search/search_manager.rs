@@ -17,7 +17,9 @@ use core::{
         merge_policy::MergePolicy, merge_scheduler::MergeScheduler, IndexReader, IndexWriter,
         StandardDirectoryReader,
     },
-    search::searcher::IndexSearcher,
+    search::query_cache::{LRUQueryCache, QueryCache},
+    search::searcher::{DefaultIndexSearcher, DefaultSimilarityProducer, IndexSearcher},
+    search::{FilterWarmer, WarmUpList},
     store::Directory,
     util::{ReferenceManager, ReferenceManagerBase, RefreshListener},
 };
@@ -25,7 +27,7 @@ use core::{
 use error::Result;
 
 use std::ops::Deref;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 /// Utility class to safely share {@link IndexSearcher} instances across multiple
 /// threads, while periodically reopening. This class ensures each searcher is
@@ -51,10 +53,17 @@ use std::sync::Arc;
 /// since it penalizes the unlucky queries that need to refresh. It's better to use
 /// a separate background thread, that periodically calls {@link #maybeRefresh}. Finally,
 /// be sure to call {@link #close} once you are done
+///
+/// The warm-up list persisted in the index directory, see `WarmUpList`, is
+/// loaded when the manager is created, and its queries are run against each
+/// new searcher before it's published. The filters registered on the
+/// `filter_warmer` are also computed on the segments of each new searcher.
 pub struct SearcherManager<C: Codec, T, SF: SearcherFactory<C>> {
     searcher_factory: SF,
     pub manager_base: ReferenceManagerBase<SF::Searcher>,
     refresh_listener: Option<T>,
+    warm_up_list: RwLock<Arc<WarmUpList>>,
+    filter_warmer: Arc<FilterWarmer<C>>,
 }
 
 impl<C: Codec, T, SF: SearcherFactory<C>> SearcherManager<C, T, SF> {
@@ -84,14 +93,37 @@ impl<C: Codec, T, SF: SearcherFactory<C>> SearcherManager<C, T, SF> {
         MS: MergeScheduler,
         MP: MergePolicy,
     {
+        let warm_up_list = WarmUpList::read(reader.directory().as_ref())?;
         let current = searcher_factory.new_searcher(Arc::new(reader))?;
+        warm_up_list.warm_up(&current);
         let manager_base = ReferenceManagerBase::new(Arc::new(current));
         Ok(SearcherManager {
             searcher_factory,
             manager_base,
             refresh_listener,
+            warm_up_list: RwLock::new(Arc::new(warm_up_list)),
+            filter_warmer: Arc::new(FilterWarmer::new()),
         })
     }
+
+    pub fn warm_up_list(&self) -> Result<Arc<WarmUpList>> {
+        Ok(Arc::clone(&*self.warm_up_list.read()?))
+    }
+
+    /// Replaces the queries run against the searchers of the next refreshes,
+    /// the list has to be persisted with `WarmUpList::write` to be loaded
+    /// again after a restart.
+    pub fn set_warm_up_list(&self, warm_up_list: WarmUpList) -> Result<()> {
+        *self.warm_up_list.write()? = Arc::new(warm_up_list);
+        Ok(())
+    }
+
+    /// The filters warmed on the searchers of the next refreshes, may also be
+    /// set as the merged segment warmer of the writer to warm the merged
+    /// segments before they are published.
+    pub fn filter_warmer(&self) -> &Arc<FilterWarmer<C>> {
+        &self.filter_warmer
+    }
 }
 
 impl<C, T, SF, RL> ReferenceManager<SF::Searcher, RL> for SearcherManager<C, T, SF>
@@ -134,9 +166,14 @@ where
         //            unreachable!()
         //        }
         if let Some(reader) = reference_to_refresh.reader().refresh()? {
-            self.searcher_factory
-                .new_searcher(Arc::from(reader))
-                .map(|s| Some(Arc::new(s)))
+            let searcher = self.searcher_factory.new_searcher(Arc::from(reader))?;
+            self.warm_up_list()?.warm_up(&searcher);
+            // like the warm-up queries, a failed filter is computed again
+            // by the first query using it
+            if let Err(e) = self.filter_warmer.warm(searcher.reader()) {
+                warn!("filter warming failed by '{:?}'", e);
+            }
+            Ok(Some(Arc::new(searcher)))
         } else {
             Ok(None)
         }
@@ -157,3 +194,66 @@ pub trait SearcherFactory<C: Codec> {
     type Searcher: IndexSearcher<C>;
     fn new_searcher(&self, reader: Arc<IndexReader<Codec = C>>) -> Result<Self::Searcher>;
 }
+
+/// The searchers created by `DefaultSearcherFactory`.
+pub type DefaultSearcher<C> = DefaultIndexSearcher<
+    C,
+    dyn IndexReader<Codec = C>,
+    Arc<dyn IndexReader<Codec = C>>,
+    DefaultSimilarityProducer,
+>;
+
+type SearcherWarmer<C> = Box<dyn Fn(&DefaultSearcher<C>) -> Result<()> + Send + Sync>;
+
+/// A `SearcherFactory` creating `DefaultIndexSearcher`s which share a single
+/// query cache, so that the cached filters of the unchanged segments survive
+/// the refreshes of a `SearcherManager`.
+///
+/// An optional warmer runs against each new searcher before it's returned,
+/// e.g. to load the data of the new segments, a failing warmer fails the
+/// refresh and the current searcher is kept.
+pub struct DefaultSearcherFactory<C: Codec> {
+    query_cache: Arc<dyn QueryCache<C>>,
+    warmer: Option<SearcherWarmer<C>>,
+}
+
+impl<C: Codec> DefaultSearcherFactory<C> {
+    pub fn new() -> DefaultSearcherFactory<C> {
+        DefaultSearcherFactory {
+            query_cache: Arc::new(LRUQueryCache::new(1000)),
+            warmer: None,
+        }
+    }
+
+    pub fn with_query_cache(mut self, query_cache: Arc<dyn QueryCache<C>>) -> Self {
+        self.query_cache = query_cache;
+        self
+    }
+
+    pub fn with_warmer<F>(mut self, warmer: F) -> Self
+    where
+        F: Fn(&DefaultSearcher<C>) -> Result<()> + Send + Sync + 'static,
+    {
+        self.warmer = Some(Box::new(warmer));
+        self
+    }
+}
+
+impl<C: Codec> Default for DefaultSearcherFactory<C> {
+    fn default() -> Self {
+        DefaultSearcherFactory::new()
+    }
+}
+
+impl<C: Codec> SearcherFactory<C> for DefaultSearcherFactory<C> {
+    type Searcher = DefaultSearcher<C>;
+
+    fn new_searcher(&self, reader: Arc<IndexReader<Codec = C>>) -> Result<Self::Searcher> {
+        let mut searcher = DefaultIndexSearcher::new(reader);
+        searcher.set_query_cache(Arc::clone(&self.query_cache));
+        if let Some(ref warmer) = self.warmer {
+            warmer(&searcher)?;
+        }
+        Ok(searcher)
+    }
+}
@@ -16,19 +16,23 @@ use std::fmt;
 use std::ops::Deref;
 use std::sync::{Arc, RwLock};
 
-use crossbeam::channel::{unbounded, Receiver, Sender};
+use crossbeam::channel::unbounded;
 
 use core::codec::{Codec, CodecTermState};
 use core::index::LeafReaderContext;
 use core::index::{get_terms, IndexReader, SearchLeafReader};
 use core::index::{Term, TermContext, Terms};
 use core::search::bm25_similarity::BM25Similarity;
+use core::search::boolean_query::BooleanQuery;
 use core::search::bulk_scorer::BulkScorer;
 use core::search::cache_policy::{QueryCachingPolicy, UsageTrackingQueryCachingPolicy};
-use core::search::collector::{self, Collector, ParallelLeafCollector, SearchCollector};
+use core::search::collector::{
+    self, Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
 use core::search::explanation::Explanation;
 use core::search::match_all::{ConstantScoreQuery, MatchAllDocsQuery};
 use core::search::query_cache::{LRUQueryCache, QueryCache};
+use core::search::query_timeout::QueryTimeout;
 use core::search::statistics::{CollectionStatistics, TermStatistics};
 use core::search::term_query::TermQuery;
 use core::search::{Query, Scorer, Weight, NO_MORE_DOCS};
@@ -163,6 +167,13 @@ pub trait SearchPlanBuilder<C: Codec> {
     ) -> TermStatistics;
 
     fn collections_statistics(&self, field: &str) -> Result<CollectionStatistics>;
+
+    /// The time budget of the query being planned, queries enumerating
+    /// terms to build their weight should check it as they go, e.g. through
+    /// a `TimeLimitingTermIterator`.
+    fn query_timeout(&self) -> Option<&QueryTimeout> {
+        None
+    }
 }
 
 pub struct DefaultIndexSearcher<
@@ -178,6 +189,7 @@ pub struct DefaultIndexSearcher<
     collection_statistics: RwLock<HashMap<String, CollectionStatistics>>,
     term_contexts: RwLock<HashMap<String, Arc<TermContext<CodecTermState<C>>>>>,
     thread_pool: Option<Arc<ThreadPool<DefaultContext>>>,
+    query_timeout: Option<QueryTimeout>,
 }
 
 impl<C: Codec, R: IndexReader<Codec = C> + ?Sized, IR: Deref<Target = R>>
@@ -204,6 +216,7 @@ where
             collection_statistics: RwLock::new(HashMap::new()),
             term_contexts: RwLock::new(HashMap::new()),
             thread_pool: None,
+            query_timeout: None,
         }
     }
 
@@ -229,53 +242,91 @@ where
         self.cache_policy = cache_policy;
     }
 
-    fn do_search<S: Scorer + ?Sized, T: Collector + ?Sized, B: Bits + ?Sized>(
-        scorer: &mut S,
-        collector: &mut T,
-        live_docs: &B,
-    ) -> Result<()> {
-        let mut bulk_scorer = BulkScorer::new(scorer);
-        match bulk_scorer.score(collector, Some(live_docs), 0, NO_MORE_DOCS) {
-            Err(Error(ErrorKind::Collector(collector::ErrorKind::CollectionTerminated), _)) => {
-                // Collection was terminated prematurely
-                Ok(())
-            }
-            Err(Error(ErrorKind::Collector(collector::ErrorKind::LeafCollectionTerminated), _))
-            | Ok(_) => {
-                // Leaf collection was terminated prematurely,
-                // continue with the following leaf
-                Ok(())
-            }
-            Err(e) => {
-                // something goes wrong, stop search and return error!
-                return Err(e);
-            }
+    /// Bounds the time spent planning and running queries with this searcher.
+    ///
+    /// Weight creation, term lookups and the scorer creation of every leaf
+    /// fail with `CollectionTerminated` once the budget is exceeded, so
+    /// queries expanding to many terms are stopped before any doc is
+    /// collected. Pass the same budget to a `TimeoutCollector` to bound the
+    /// collection too.
+    pub fn set_query_timeout(&mut self, query_timeout: Option<QueryTimeout>) {
+        self.query_timeout = query_timeout;
+    }
+
+    fn check_timeout(&self) -> Result<()> {
+        match self.query_timeout {
+            Some(ref timeout) => timeout.check(),
+            None => Ok(()),
         }
     }
-}
 
-impl<C, R, IR, SP> IndexSearcher<C> for DefaultIndexSearcher<C, R, IR, SP>
-where
-    C: Codec,
-    R: IndexReader<Codec = C> + ?Sized,
-    IR: Deref<Target = R>,
-    SP: SimilarityProducer<C>,
-{
-    type Reader = R;
-    #[inline]
-    fn reader(&self) -> &R {
-        &*self.reader
+    /// Searches `query` scoring it with `sim_producer` instead of the
+    /// similarity this searcher was built with, for this call only.
+    ///
+    /// The statistics, term states and query cache of the searcher are all
+    /// shared with regular searches: none of them depend on the similarity,
+    /// and only non-scoring weights are ever cached.
+    pub fn search_with_similarity<S>(
+        &self,
+        query: &dyn Query<C>,
+        collector: &mut S,
+        sim_producer: &dyn SimilarityProducer<C>,
+    ) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        let weight = SimilarityOverride::new(self, sim_producer)
+            .create_weight(query, collector.needs_scores())?;
+        self.search_with_weight(weight.as_ref(), collector)
     }
 
-    /// Lower-level search API.
-    fn search<S>(&self, query: &dyn Query<C>, collector: &mut S) -> Result<()>
+    /// Parallel version of `search_with_similarity`.
+    pub fn search_parallel_with_similarity<S>(
+        &self,
+        query: &dyn Query<C>,
+        collector: &mut S,
+        sim_producer: &dyn SimilarityProducer<C>,
+    ) -> Result<()>
     where
         S: SearchCollector + ?Sized,
     {
-        let weight = self.create_weight(query, collector.needs_scores())?;
+        let weight = SimilarityOverride::new(self, sim_producer)
+            .create_weight(query, collector.needs_scores())?;
+        self.search_parallel_with_weight(weight.as_ref(), collector)
+    }
 
+    /// Explains the score `query` gets for `doc` when scored with
+    /// `sim_producer`, see `search_with_similarity`.
+    pub fn explain_with_similarity(
+        &self,
+        query: &dyn Query<C>,
+        doc: DocId,
+        sim_producer: &dyn SimilarityProducer<C>,
+    ) -> Result<Explanation> {
+        let reader = self.reader.leaf_reader_for_doc(doc);
+        let live_docs = reader.reader.live_docs();
+        if !live_docs.get((doc - reader.doc_base()) as usize)? {
+            Ok(Explanation::new(
+                false,
+                0.0f32,
+                format!("Document {} if deleted", doc),
+                vec![],
+            ))
+        } else {
+            SimilarityOverride::new(self, sim_producer)
+                .create_normalized_weight(query, true)?
+                .explain(&reader, doc - reader.doc_base())
+        }
+    }
+
+    fn search_with_weight<S>(&self, weight: &dyn Weight<C>, collector: &mut S) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        let score_docs_in_order = !collector.accepts_docs_out_of_order();
         for reader in self.reader.leaves() {
-            if let Some(mut scorer) = weight.create_scorer(&reader)? {
+            self.check_timeout()?;
+            if let Some(mut scorer) = weight.create_bulk_scorer(&reader, score_docs_in_order)? {
                 // some in running segment maybe wrong, just skip it!
                 // TODO maybe we should matching more specific error type
                 if let Err(e) = collector.set_next_reader(&reader) {
@@ -295,19 +346,71 @@ where
         Ok(())
     }
 
-    fn search_parallel<S>(&self, query: &dyn Query<C>, collector: &mut S) -> Result<()>
+    /// Searches with the scorers of the scoring clauses of `query` passed to
+    /// the collector for each leaf, see `ScoreBreakdownCollector`. The
+    /// clauses are the must and should clauses of a `BooleanQuery`, or the
+    /// query itself otherwise.
+    fn search_with_score_breakdown<S>(
+        &self,
+        query: &dyn Query<C>,
+        weight: &dyn Weight<C>,
+        collector: &mut S,
+    ) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        let clauses: Vec<&dyn Query<C>> = match query.as_any().downcast_ref::<BooleanQuery<C>>() {
+            Some(boolean) => {
+                let clauses = boolean.rewrite(true);
+                clauses.musts.into_iter().chain(clauses.shoulds).collect()
+            }
+            None => vec![query],
+        };
+        let names: Vec<String> = clauses.iter().map(|q| q.to_string()).collect();
+        let clause_weights = clauses
+            .iter()
+            .map(|q| self.create_weight(*q, true))
+            .collect::<Result<Vec<_>>>()?;
+
+        for reader in self.reader.leaves() {
+            self.check_timeout()?;
+            if let Some(mut scorer) = weight.create_bulk_scorer(&reader, true)? {
+                collector.set_next_reader(&reader)?;
+                let clause_scorers = clause_weights
+                    .iter()
+                    .map(|w| w.create_scorer(&reader))
+                    .collect::<Result<Vec<_>>>()?;
+                collector.set_clause_scorers(&names, clause_scorers)?;
+                let live_docs = reader.reader.live_docs();
+
+                Self::do_search(&mut *scorer, collector, live_docs.as_ref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn search_parallel_with_weight<S>(
+        &self,
+        weight: &dyn Weight<C>,
+        collector: &mut S,
+    ) -> Result<()>
     where
         S: SearchCollector + ?Sized,
     {
         if collector.support_parallel() && self.reader.leaves().len() > 1 {
             if let Some(ref thread_pool) = self.thread_pool {
-                let weight = self.create_weight(query, collector.needs_scores())?;
+                let (sender, receiver) = unbounded();
 
-                for (_ord, reader) in self.reader.leaves().iter().enumerate() {
-                    if let Some(scorer) = weight.create_scorer(reader)? {
-                        match collector.leaf_collector(reader) {
+                let score_docs_in_order = !collector.accepts_docs_out_of_order();
+                for reader in self.reader.leaves() {
+                    self.check_timeout()?;
+                    if let Some(scorer) = weight.create_bulk_scorer(&reader, score_docs_in_order)? {
+                        match collector.new_collector(&reader) {
                             Ok(leaf_collector) => {
                                 let live_docs = reader.reader.live_docs();
+                                let ord = reader.ord;
+                                let sender = sender.clone();
                                 thread_pool.execute(move |_ctx| {
                                     let mut collector = leaf_collector;
                                     let mut scorer = scorer;
@@ -329,6 +432,12 @@ where
                                             e
                                         );
                                     }
+                                    if sender.send((ord, collector)).is_err() {
+                                        error!(
+                                            "channel unexpected closed before search complete, \
+                                             may return partial result"
+                                        );
+                                    }
                                 })
                             }
                             Err(e) => {
@@ -341,10 +450,76 @@ where
                         }
                     }
                 }
-                return collector.finish_parallel();
+                // all the leaf collectors are sent back once their search job is done,
+                // so drain the channel until every job drops its sender
+                drop(sender);
+                let mut leaf_collectors: Vec<(usize, S::Collector)> = receiver.iter().collect();
+                leaf_collectors.sort_by_key(|&(ord, _)| ord);
+                return collector.reduce(leaf_collectors.into_iter().map(|(_, c)| c).collect());
             }
         }
-        self.search(query, collector)
+        self.search_with_weight(weight, collector)
+    }
+
+    fn do_search<S: Scorer + ?Sized, T: Collector + ?Sized, B: Bits + ?Sized>(
+        scorer: &mut S,
+        collector: &mut T,
+        live_docs: &B,
+    ) -> Result<()> {
+        let mut bulk_scorer = BulkScorer::new(scorer);
+        match bulk_scorer.score(collector, Some(live_docs), 0, NO_MORE_DOCS) {
+            Err(Error(ErrorKind::Collector(collector::ErrorKind::CollectionTerminated), _)) => {
+                // Collection was terminated prematurely
+                Ok(())
+            }
+            Err(Error(ErrorKind::Collector(collector::ErrorKind::LeafCollectionTerminated), _))
+            | Ok(_) => {
+                // Leaf collection was terminated prematurely,
+                // continue with the following leaf
+                Ok(())
+            }
+            Err(e) => {
+                // something goes wrong, stop search and return error!
+                return Err(e);
+            }
+        }
+    }
+}
+
+impl<C, R, IR, SP> IndexSearcher<C> for DefaultIndexSearcher<C, R, IR, SP>
+where
+    C: Codec,
+    R: IndexReader<Codec = C> + ?Sized,
+    IR: Deref<Target = R>,
+    SP: SimilarityProducer<C>,
+{
+    type Reader = R;
+    #[inline]
+    fn reader(&self) -> &R {
+        &*self.reader
+    }
+
+    /// Lower-level search API.
+    fn search<S>(&self, query: &dyn Query<C>, collector: &mut S) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        let weight = self.create_weight(query, collector.needs_scores())?;
+        if collector.needs_score_breakdown() {
+            return self.search_with_score_breakdown(query, weight.as_ref(), collector);
+        }
+        self.search_with_weight(weight.as_ref(), collector)
+    }
+
+    fn search_parallel<S>(&self, query: &dyn Query<C>, collector: &mut S) -> Result<()>
+    where
+        S: SearchCollector + ?Sized,
+    {
+        let weight = self.create_weight(query, collector.needs_scores())?;
+        if collector.needs_score_breakdown() {
+            return self.search_with_score_breakdown(query, weight.as_ref(), collector);
+        }
+        self.search_parallel_with_weight(weight.as_ref(), collector)
     }
 
     fn count(&self, query: &dyn Query<C>) -> Result<i32> {
@@ -414,7 +589,9 @@ where
         query: &dyn Query<C>,
         needs_scores: bool,
     ) -> Result<Box<dyn Weight<C>>> {
+        self.check_timeout()?;
         let mut weight = query.create_weight(self, needs_scores)?;
+        self.check_timeout()?;
         if !needs_scores {
             weight = self
                 .query_cache
@@ -461,6 +638,7 @@ where
         if builded {
             term_context = Arc::clone(self.term_contexts.read().unwrap().get(&term_key).unwrap());
         } else {
+            self.check_timeout()?;
             let mut context = TermContext::new(&*self.reader);
             context.build(&*self.reader, &term)?;
             term_context = Arc::new(context);
@@ -513,19 +691,121 @@ where
         statistics.insert(field.into(), stat);
         Ok(statistics[field].clone())
     }
+
+    fn query_timeout(&self) -> Option<&QueryTimeout> {
+        self.query_timeout.as_ref()
+    }
+}
+
+/// Plans queries for a `DefaultIndexSearcher` with another similarity than its
+/// own, everything but the similarity is delegated to the searcher.
+struct SimilarityOverride<'a, C, R, IR, SP>
+where
+    C: Codec,
+    R: IndexReader<Codec = C> + ?Sized,
+    IR: Deref<Target = R>,
+    SP: SimilarityProducer<C>,
+{
+    searcher: &'a DefaultIndexSearcher<C, R, IR, SP>,
+    sim_producer: &'a dyn SimilarityProducer<C>,
+}
+
+impl<'a, C, R, IR, SP> SimilarityOverride<'a, C, R, IR, SP>
+where
+    C: Codec,
+    R: IndexReader<Codec = C> + ?Sized,
+    IR: Deref<Target = R>,
+    SP: SimilarityProducer<C>,
+{
+    fn new(
+        searcher: &'a DefaultIndexSearcher<C, R, IR, SP>,
+        sim_producer: &'a dyn SimilarityProducer<C>,
+    ) -> Self {
+        SimilarityOverride {
+            searcher,
+            sim_producer,
+        }
+    }
+}
+
+impl<'a, C, R, IR, SP> SearchPlanBuilder<C> for SimilarityOverride<'a, C, R, IR, SP>
+where
+    C: Codec,
+    R: IndexReader<Codec = C> + ?Sized,
+    IR: Deref<Target = R>,
+    SP: SimilarityProducer<C>,
+{
+    fn num_docs(&self) -> i32 {
+        self.searcher.num_docs()
+    }
+
+    fn max_doc(&self) -> i32 {
+        self.searcher.max_doc()
+    }
+
+    fn create_weight(
+        &self,
+        query: &dyn Query<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        self.searcher.check_timeout()?;
+        // sub queries must be planned by `self` too to pick up the similarity
+        let mut weight = query.create_weight(self, needs_scores)?;
+        if !needs_scores {
+            // non-scoring weights never use the similarity, so they can share
+            // the searcher's cache entries
+            weight = self
+                .searcher
+                .query_cache
+                .do_cache(weight, Arc::clone(&self.searcher.cache_policy));
+        }
+        Ok(weight)
+    }
+
+    fn create_normalized_weight(
+        &self,
+        query: &dyn Query<C>,
+        needs_scores: bool,
+    ) -> Result<Box<dyn Weight<C>>> {
+        self.create_weight(query, needs_scores)
+    }
+
+    fn similarity(&self, field: &str, needs_scores: bool) -> Box<dyn Similarity<C>> {
+        if needs_scores {
+            self.sim_producer.create(field)
+        } else {
+            Box::new(NonScoringSimilarity {})
+        }
+    }
+
+    fn term_state(&self, term: &Term) -> Result<Arc<TermContext<CodecTermState<C>>>> {
+        self.searcher.term_state(term)
+    }
+
+    fn term_statistics(
+        &self,
+        term: Term,
+        context: &TermContext<CodecTermState<C>>,
+    ) -> TermStatistics {
+        self.searcher.term_statistics(term, context)
+    }
+
+    fn collections_statistics(&self, field: &str) -> Result<CollectionStatistics> {
+        self.searcher.collections_statistics(field)
+    }
+
+    fn query_timeout(&self) -> Option<&QueryTimeout> {
+        self.searcher.query_timeout()
+    }
 }
 
 struct TotalHitCountCollector {
     total_hits: i32,
-    channel: Option<(Sender<i32>, Receiver<i32>)>,
 }
 
 impl TotalHitCountCollector {
     pub fn new() -> Self {
-        TotalHitCountCollector {
-            total_hits: 0,
-            channel: None,
-        }
+        TotalHitCountCollector { total_hits: 0 }
     }
 
     pub fn total_hits(&self) -> i32 {
@@ -534,7 +814,6 @@ impl TotalHitCountCollector {
 }
 
 impl SearchCollector for TotalHitCountCollector {
-    type LC = TotalHitsCountLeafCollector;
     fn set_next_reader<C: Codec>(&mut self, _reader: &LeafReaderContext<'_, C>) -> Result<()> {
         Ok(())
     }
@@ -542,31 +821,22 @@ impl SearchCollector for TotalHitCountCollector {
     fn support_parallel(&self) -> bool {
         true
     }
+}
+
+impl CollectorManager for TotalHitCountCollector {
+    type Collector = TotalHitsCountLeafCollector;
 
-    fn leaf_collector<C: Codec>(
+    fn new_collector<C: Codec>(
         &mut self,
         _reader: &LeafReaderContext<'_, C>,
     ) -> Result<TotalHitsCountLeafCollector> {
-        if self.channel.is_none() {
-            self.channel = Some(unbounded());
-        }
-        Ok(TotalHitsCountLeafCollector {
-            count: 0,
-            sender: self.channel.as_ref().unwrap().0.clone(),
-        })
-    }
-
-    fn finish_parallel(&mut self) -> Result<()> {
-        let channel = self.channel.take();
-        // iff all the `weight.create_scorer(leaf_reader)` return None, the channel won't
-        // inited and thus stay None
-        if let Some((sender, receiver)) = channel {
-            drop(sender);
-            while let Ok(v) = receiver.recv() {
-                self.total_hits += v;
-            }
-        }
+        Ok(TotalHitsCountLeafCollector { count: 0 })
+    }
 
+    fn reduce(&mut self, collectors: Vec<TotalHitsCountLeafCollector>) -> Result<()> {
+        for collector in collectors {
+            self.total_hits += collector.count;
+        }
         Ok(())
     }
 }
@@ -576,6 +846,10 @@ impl Collector for TotalHitCountCollector {
         false
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: i32, _scorer: &mut S) -> Result<()> {
         self.total_hits += 1;
         Ok(())
@@ -584,7 +858,6 @@ impl Collector for TotalHitCountCollector {
 
 struct TotalHitsCountLeafCollector {
     count: i32,
-    sender: Sender<i32>,
 }
 
 impl Collector for TotalHitsCountLeafCollector {
@@ -592,6 +865,10 @@ impl Collector for TotalHitsCountLeafCollector {
         false
     }
 
+    fn accepts_docs_out_of_order(&self) -> bool {
+        true
+    }
+
     fn collect<S: Scorer + ?Sized>(&mut self, _doc: i32, _scorer: &mut S) -> Result<()> {
         self.count += 1;
         Ok(())
@@ -600,13 +877,7 @@ impl Collector for TotalHitsCountLeafCollector {
 
 impl ParallelLeafCollector for TotalHitsCountLeafCollector {
     fn finish_leaf(&mut self) -> Result<()> {
-        self.sender.send(self.count).map_err(|e| {
-            ErrorKind::IllegalState(format!(
-                "channel unexpected closed before search complete with err: {:?}",
-                e
-            ))
-            .into()
-        })
+        Ok(())
     }
 }
 
@@ -615,6 +886,7 @@ mod tests {
     use super::*;
     use core::codec::tests::TestCodec;
     use core::index::tests::*;
+    use core::search::boolean_similarity::BooleanSimilarity;
     use core::search::collector::top_docs::*;
     use core::search::collector::*;
     use core::search::term_query::TermQuery;
@@ -705,4 +977,78 @@ mod tests {
         assert!((score_docs[1].score() - 5f32) < ::std::f32::EPSILON);
         assert!((score_docs[2].score() - 5f32) < ::std::f32::EPSILON);
     }
+
+    struct SimilarityRecordingQuery {
+        docs: Vec<DocId>,
+        similarities: ::std::sync::Mutex<Vec<String>>,
+    }
+
+    impl<C: Codec> Query<C> for SimilarityRecordingQuery {
+        fn create_weight(
+            &self,
+            searcher: &dyn SearchPlanBuilder<C>,
+            needs_scores: bool,
+        ) -> Result<Box<dyn Weight<C>>> {
+            let similarity = searcher.similarity("title", needs_scores);
+            self.similarities
+                .lock()
+                .unwrap()
+                .push(similarity.to_string());
+            Ok(Box::new(create_mock_weight(self.docs.clone())))
+        }
+
+        fn extract_terms(&self) -> Vec<TermQuery> {
+            unimplemented!()
+        }
+
+        fn query_type(&self) -> &'static str {
+            MOCK_QUERY
+        }
+
+        fn as_any(&self) -> &::std::any::Any {
+            unreachable!()
+        }
+    }
+
+    impl fmt::Display for SimilarityRecordingQuery {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "SimilarityRecordingQuery")
+        }
+    }
+
+    struct BooleanSimilarityProducer;
+
+    impl<C: Codec> SimilarityProducer<C> for BooleanSimilarityProducer {
+        fn create(&self, _field: &str) -> Box<dyn Similarity<C>> {
+            Box::new(BooleanSimilarity::default())
+        }
+    }
+
+    #[test]
+    fn test_search_with_similarity() {
+        let index_reader: Arc<dyn IndexReader<Codec = TestCodec>> =
+            Arc::new(MockIndexReader::new(vec![
+                MockLeafReader::new(0),
+                MockLeafReader::new(10),
+            ]));
+        let searcher = DefaultIndexSearcher::new(index_reader);
+        let query = SimilarityRecordingQuery {
+            docs: vec![1, 2, 3],
+            similarities: ::std::sync::Mutex::new(vec![]),
+        };
+
+        let mut collector = TopDocsCollector::new(10);
+        searcher
+            .search_with_similarity(&query, &mut collector, &BooleanSimilarityProducer {})
+            .unwrap();
+        assert_eq!(collector.top_docs().total_hits(), 6);
+
+        let mut collector = TopDocsCollector::new(10);
+        searcher.search(&query, &mut collector).unwrap();
+
+        let similarities = query.similarities.lock().unwrap();
+        assert_eq!(similarities.len(), 2);
+        assert_eq!(similarities[0], "BooleanSimilarity");
+        assert_ne!(similarities[1], "BooleanSimilarity");
+    }
 }
@@ -0,0 +1,223 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::{ErrorKind::AlreadyClosed, Result};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Decides which searchers `SearcherLifetimeManager::prune` drops.
+pub trait SearcherPruner<S> {
+    /// Whether to drop `searcher`, `age` is the time since a newer searcher
+    /// was recorded, i.e. since this one stopped being the current one.
+    fn do_prune(&self, age: Duration, searcher: &S) -> bool;
+}
+
+/// Prunes the searchers replaced by a newer one more than `max_age` ago.
+#[derive(Debug, Clone, Copy)]
+pub struct PruneByAge {
+    max_age: Duration,
+}
+
+impl PruneByAge {
+    pub fn new(max_age: Duration) -> PruneByAge {
+        PruneByAge { max_age }
+    }
+}
+
+impl<S> SearcherPruner<S> for PruneByAge {
+    fn do_prune(&self, age: Duration, _searcher: &S) -> bool {
+        age > self.max_age
+    }
+}
+
+struct SearcherTracker<S> {
+    searcher: Arc<S>,
+    recorded: Instant,
+}
+
+struct LifetimeState<S> {
+    trackers: HashMap<u64, SearcherTracker<S>>,
+    next_token: u64,
+}
+
+/// Keeps the searchers a user's first request ran against, so that the
+/// follow-up requests, e.g. the next pages of the results, run against the
+/// exact same point-in-time view of the index, even if the index changed in
+/// the meantime and the `SearcherManager` was refreshed.
+///
+/// `record` the searcher of the first request and hand the returned token
+/// to the user, then `acquire` the searcher by token on the follow-up
+/// requests. A pruned searcher can't be acquired anymore, the request should
+/// then fall back to the current searcher. Call `prune` periodically, e.g.
+/// with `PruneByAge`, to drop the old searchers and the resources they hold.
+pub struct SearcherLifetimeManager<S> {
+    state: Mutex<Option<LifetimeState<S>>>,
+}
+
+impl<S> Default for SearcherLifetimeManager<S> {
+    fn default() -> Self {
+        SearcherLifetimeManager::new()
+    }
+}
+
+impl<S> SearcherLifetimeManager<S> {
+    pub fn new() -> SearcherLifetimeManager<S> {
+        SearcherLifetimeManager {
+            state: Mutex::new(Some(LifetimeState {
+                trackers: HashMap::new(),
+                next_token: 0,
+            })),
+        }
+    }
+
+    /// Records `searcher` and returns its token, recording an already
+    /// recorded searcher returns the same token.
+    pub fn record(&self, searcher: Arc<S>) -> Result<u64> {
+        self.record_at(searcher, Instant::now())
+    }
+
+    fn record_at(&self, searcher: Arc<S>, now: Instant) -> Result<u64> {
+        let mut guard = self.state.lock()?;
+        let state = Self::ensure_open(&mut guard)?;
+        if let Some((&token, _)) = state
+            .trackers
+            .iter()
+            .find(|(_, t)| Arc::ptr_eq(&t.searcher, &searcher))
+        {
+            return Ok(token);
+        }
+        let token = state.next_token;
+        state.next_token += 1;
+        state.trackers.insert(
+            token,
+            SearcherTracker {
+                searcher,
+                recorded: now,
+            },
+        );
+        Ok(token)
+    }
+
+    /// The searcher recorded with `token`, or None if it was pruned.
+    pub fn acquire(&self, token: u64) -> Result<Option<Arc<S>>> {
+        let mut guard = self.state.lock()?;
+        let state = Self::ensure_open(&mut guard)?;
+        Ok(state.trackers.get(&token).map(|t| Arc::clone(&t.searcher)))
+    }
+
+    /// The number of searchers currently recorded.
+    pub fn len(&self) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |s| s.trackers.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the searchers `pruner` decides to, returns how many were
+    /// dropped. The last recorded searcher is never pruned.
+    pub fn prune<P: SearcherPruner<S>>(&self, pruner: &P) -> Result<usize> {
+        self.prune_at(pruner, Instant::now())
+    }
+
+    fn prune_at<P: SearcherPruner<S>>(&self, pruner: &P, now: Instant) -> Result<usize> {
+        let mut guard = self.state.lock()?;
+        let state = Self::ensure_open(&mut guard)?;
+        // newest first, the tokens break the ties of the record times
+        let mut trackers: Vec<(u64, Instant)> = state
+            .trackers
+            .iter()
+            .map(|(&token, t)| (token, t.recorded))
+            .collect();
+        trackers.sort_by(|a, b| b.1.cmp(&a.1).then(b.0.cmp(&a.0)));
+
+        let mut pruned = 0;
+        let mut replaced_at: Option<Instant> = None;
+        for (token, recorded) in trackers {
+            if let Some(replaced_at) = replaced_at {
+                let age = now.duration_since(replaced_at);
+                if pruner.do_prune(age, &state.trackers[&token].searcher) {
+                    state.trackers.remove(&token);
+                    pruned += 1;
+                }
+            }
+            replaced_at = Some(recorded);
+        }
+        Ok(pruned)
+    }
+
+    /// Drops all the searchers, the manager can't be used anymore.
+    pub fn close(&self) -> Result<()> {
+        self.state.lock()?.take();
+        Ok(())
+    }
+
+    fn ensure_open(state: &mut Option<LifetimeState<S>>) -> Result<&mut LifetimeState<S>> {
+        match state.as_mut() {
+            Some(state) => Ok(state),
+            None => bail!(AlreadyClosed(
+                "this SearcherLifetimeManager is closed".into()
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_acquire_prune() {
+        let manager: SearcherLifetimeManager<String> = SearcherLifetimeManager::new();
+        let start = Instant::now();
+        let first = Arc::new("first".to_string());
+        let token = manager.record_at(Arc::clone(&first), start).unwrap();
+        assert_eq!(manager.record_at(Arc::clone(&first), start).unwrap(), token);
+
+        let second = Arc::new("second".to_string());
+        let second_at = start + Duration::from_secs(10);
+        let second_token = manager.record_at(second, second_at).unwrap();
+        assert_ne!(second_token, token);
+        assert_eq!(manager.acquire(token).unwrap().unwrap(), first);
+        assert_eq!(manager.len(), 2);
+
+        // the first searcher was replaced 5s ago
+        let pruner = PruneByAge::new(Duration::from_secs(30));
+        let pruned = manager
+            .prune_at(&pruner, second_at + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(pruned, 0);
+        let pruned = manager
+            .prune_at(&pruner, second_at + Duration::from_secs(31))
+            .unwrap();
+        assert_eq!(pruned, 1);
+        assert!(manager.acquire(token).unwrap().is_none());
+
+        // the current searcher is never pruned
+        let pruned = manager
+            .prune_at(&pruner, second_at + Duration::from_secs(3600))
+            .unwrap();
+        assert_eq!(pruned, 0);
+        assert!(manager.acquire(second_token).unwrap().is_some());
+
+        manager.close().unwrap();
+        assert!(manager.acquire(second_token).is_err());
+        assert!(manager.is_empty());
+    }
+}
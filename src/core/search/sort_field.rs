@@ -16,7 +16,9 @@ use core::index::{
     NumericDocValues, NumericDocValuesContext, NumericDocValuesRef, SearchLeafReader,
     SortedNumericDocValuesRef,
 };
+use core::search::double_values::DoubleValuesSource;
 use core::search::field_comparator::*;
+use core::util::geo;
 use core::util::numeric::{sortable_double_bits, sortable_float_bits};
 use core::util::BitsRef;
 use core::util::VariantValue;
@@ -64,6 +66,8 @@ pub enum SortFieldMissingValue {
 pub enum SortField {
     Simple(SimpleSortField),
     SortedNumeric(SortedNumericSortField),
+    DoubleValues(DoubleValuesSortField),
+    GeoDistance(GeoDistanceSortField),
     // SortedSet(SortedSetSortField),
 }
 
@@ -76,6 +80,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => &s.field,
             SortField::SortedNumeric(s) => &s.raw_field.field,
+            SortField::DoubleValues(s) => &s.field,
+            SortField::GeoDistance(s) => &s.field,
         }
     }
 
@@ -83,6 +89,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.field_type,
             SortField::SortedNumeric(s) => s.raw_field.field_type,
+            SortField::DoubleValues(_) => SortFieldType::Custom,
+            SortField::GeoDistance(_) => SortFieldType::Custom,
         }
     }
 
@@ -90,6 +98,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.is_reverse,
             SortField::SortedNumeric(s) => s.raw_field.is_reverse,
+            SortField::DoubleValues(s) => s.is_reverse,
+            SortField::GeoDistance(_) => false,
         }
     }
 
@@ -97,6 +107,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.missing_value.as_ref(),
             SortField::SortedNumeric(s) => s.raw_field.missing_value.as_ref(),
+            SortField::DoubleValues(_) => None,
+            SortField::GeoDistance(_) => None,
         }
     }
 
@@ -104,6 +116,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.needs_scores(),
             SortField::SortedNumeric(s) => s.raw_field.needs_scores(),
+            SortField::DoubleValues(s) => s.source.needs_scores(),
+            SortField::GeoDistance(_) => false,
         }
     }
 
@@ -115,6 +129,9 @@ impl SortField {
             SortField::SortedNumeric(s) => {
                 s.raw_field.missing_value = value;
             }
+            // the values of a source are never missing and documents without
+            // a point always sort last
+            SortField::DoubleValues(_) | SortField::GeoDistance(_) => {}
         }
     }
 
@@ -126,6 +143,8 @@ impl SortField {
         match self {
             SortField::Simple(s) => s.get_comparator(num_hits, missing_value),
             SortField::SortedNumeric(s) => s.get_comparator(num_hits, missing_value),
+            SortField::DoubleValues(s) => s.get_comparator(num_hits),
+            SortField::GeoDistance(s) => s.get_comparator(num_hits),
         }
     }
 }
@@ -203,6 +222,81 @@ impl SimpleSortField {
     }
 }
 
+/// SortField for the values of a `DoubleValuesSource`, see
+/// `DoubleValuesSource::sort_field`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DoubleValuesSortField {
+    source: DoubleValuesSource,
+    field: String,
+    is_reverse: bool,
+}
+
+impl DoubleValuesSortField {
+    pub fn new(source: DoubleValuesSource, is_reverse: bool) -> Self {
+        let field = source.to_string();
+        DoubleValuesSortField {
+            source,
+            field,
+            is_reverse,
+        }
+    }
+
+    pub fn source(&self) -> &DoubleValuesSource {
+        &self.source
+    }
+
+    pub fn get_comparator(&self, num_hits: usize) -> FieldComparatorEnum {
+        FieldComparatorEnum::DoubleValues(DoubleValuesComparator::new(
+            num_hits,
+            self.source.clone(),
+        ))
+    }
+}
+
+/// SortField for the distance in meters between the points of a
+/// `LatLonDocValuesField` and a location, see
+/// `LatLonDocValuesField::new_distance_sort`.
+///
+/// The sort value of a document is the distance of its closest point, it is
+/// infinite if the document has no point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeoDistanceSortField {
+    field: String,
+    latitude: f64,
+    longitude: f64,
+}
+
+impl Eq for GeoDistanceSortField {}
+
+impl GeoDistanceSortField {
+    pub fn new(field: &str, latitude: f64, longitude: f64) -> Result<Self> {
+        geo::check_latitude(latitude)?;
+        geo::check_longitude(longitude)?;
+        Ok(GeoDistanceSortField {
+            field: field.to_string(),
+            latitude,
+            longitude,
+        })
+    }
+
+    pub fn latitude(&self) -> f64 {
+        self.latitude
+    }
+
+    pub fn longitude(&self) -> f64 {
+        self.longitude
+    }
+
+    pub fn get_comparator(&self, num_hits: usize) -> FieldComparatorEnum {
+        FieldComparatorEnum::GeoDistance(GeoDistanceComparator::new(
+            num_hits,
+            self.field.clone(),
+            self.latitude,
+            self.longitude,
+        ))
+    }
+}
+
 /// SortField for `SortedNumericDocValues`
 /// A SortedNumericDocValues contains multiple values for a field, so sorting with
 /// this technique "selects" a value as the representative sort value for the document.
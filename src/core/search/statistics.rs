@@ -11,6 +11,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::index::{IndexReader, Term, TermIterator, Terms};
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::cmp::Ordering;
+
 #[derive(Clone)]
 pub struct CollectionStatistics {
     pub field: String,
@@ -61,6 +66,187 @@ impl TermStatistics {
     }
 }
 
+/// The default ratio of the docs of an index whose segments are read by a
+/// `TermStatsEstimator`.
+pub const DEFAULT_SAMPLE_RATIO: f64 = 0.2;
+
+/// Under this number of docs all the segments are read, the statistics are
+/// then exact.
+pub const DEFAULT_MIN_SAMPLED_DOCS: i64 = 100_000;
+
+/// The estimated statistics of a term over a whole index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermStatsEstimate {
+    pub doc_freq: i64,
+    /// -1 if the field omits term freqs.
+    pub total_term_freq: i64,
+    /// whether all the segments were read, the statistics are then exact
+    /// (deleted docs included, as for `TermContext`).
+    pub exact: bool,
+}
+
+/// Estimates the `doc_freq` and `total_term_freq` of a batch of terms, for
+/// query planners ordering or pruning clauses before paying for a
+/// `TermContext` per candidate term.
+///
+/// Only the terms dictionaries of a sample of the segments are read, the
+/// postings are never touched: the largest segments are picked until they
+/// hold `sample_ratio` of the docs of the index, and the statistics summed
+/// over them are scaled by the ratio of the docs having the field in the
+/// whole index to those in the sample. The terms of a field are looked up in
+/// order with a single terms iterator per segment.
+pub struct TermStatsEstimator {
+    sample_ratio: f64,
+    min_sampled_docs: i64,
+}
+
+impl Default for TermStatsEstimator {
+    fn default() -> Self {
+        TermStatsEstimator {
+            sample_ratio: DEFAULT_SAMPLE_RATIO,
+            min_sampled_docs: DEFAULT_MIN_SAMPLED_DOCS,
+        }
+    }
+}
+
+impl TermStatsEstimator {
+    pub fn new(sample_ratio: f64) -> Result<TermStatsEstimator> {
+        if !(sample_ratio > 0.0 && sample_ratio <= 1.0) {
+            bail!(IllegalArgument(format!(
+                "sample_ratio must be in (0, 1], got {}",
+                sample_ratio
+            )));
+        }
+        Ok(TermStatsEstimator {
+            sample_ratio,
+            min_sampled_docs: DEFAULT_MIN_SAMPLED_DOCS,
+        })
+    }
+
+    /// Sets the number of docs read at least, whatever the sample ratio.
+    pub fn set_min_sampled_docs(&mut self, min_sampled_docs: i64) {
+        self.min_sampled_docs = min_sampled_docs;
+    }
+
+    /// Returns the estimated statistics of each term, in the order of
+    /// `terms`.
+    pub fn estimate<IR: IndexReader + ?Sized>(
+        &self,
+        reader: &IR,
+        terms: &[Term],
+    ) -> Result<Vec<TermStatsEstimate>> {
+        let leaves = reader.leaves();
+        let max_docs: Vec<i32> = leaves.iter().map(|l| l.reader.max_doc()).collect();
+        let target = (f64::from(reader.max_doc()) * self.sample_ratio).ceil() as i64;
+        let sampled = sample_leaves(&max_docs, target.max(self.min_sampled_docs));
+        let exact = sampled.len() == leaves.len();
+
+        let mut order: Vec<usize> = (0..terms.len()).collect();
+        order.sort_by(|&a, &b| terms[a].cmp(&terms[b]));
+
+        let mut estimates = vec![
+            TermStatsEstimate {
+                doc_freq: 0,
+                total_term_freq: 0,
+                exact,
+            };
+            terms.len()
+        ];
+        let mut start = 0;
+        while start < order.len() {
+            let field = &terms[order[start]].field;
+            let end = order[start..]
+                .iter()
+                .position(|&i| &terms[i].field != field)
+                .map_or(order.len(), |p| start + p);
+            let field_terms = &order[start..end];
+
+            let mut total_doc_count = 0i64;
+            let mut sampled_doc_count = 0i64;
+            let mut sampled_max_doc = 0i64;
+            for (i, leaf) in leaves.iter().enumerate() {
+                let is_sampled = exact || sampled.contains(&i);
+                if is_sampled {
+                    sampled_max_doc += i64::from(max_docs[i]);
+                }
+                let leaf_terms = match leaf.reader.terms(field)? {
+                    Some(t) => t,
+                    None => continue,
+                };
+                let doc_count = i64::from(leaf_terms.doc_count()?);
+                total_doc_count += doc_count;
+                if !is_sampled {
+                    continue;
+                }
+                sampled_doc_count += doc_count;
+                let mut iter = leaf_terms.iterator()?;
+                for &t in field_terms {
+                    if iter.seek_exact(&terms[t].bytes)? {
+                        let estimate = &mut estimates[t];
+                        estimate.doc_freq += i64::from(iter.doc_freq()?);
+                        let ttf = iter.total_term_freq()?;
+                        if estimate.total_term_freq >= 0 && ttf >= 0 {
+                            estimate.total_term_freq += ttf;
+                        } else {
+                            estimate.total_term_freq = -1;
+                        }
+                    }
+                }
+            }
+
+            if !exact {
+                // scale by the docs having the field, or by all the docs if
+                // the codec doesn't record the doc count
+                let scale = if sampled_doc_count > 0 {
+                    total_doc_count as f64 / sampled_doc_count as f64
+                } else if sampled_max_doc > 0 {
+                    f64::from(reader.max_doc()) / sampled_max_doc as f64
+                } else {
+                    1.0
+                };
+                let max_doc_freq = if total_doc_count > 0 {
+                    total_doc_count
+                } else {
+                    i64::from(reader.max_doc())
+                };
+                for &t in field_terms {
+                    let estimate = &mut estimates[t];
+                    estimate.doc_freq =
+                        ((estimate.doc_freq as f64 * scale).round() as i64).min(max_doc_freq);
+                    if estimate.total_term_freq > 0 {
+                        estimate.total_term_freq = ((estimate.total_term_freq as f64 * scale)
+                            .round() as i64)
+                            .max(estimate.doc_freq);
+                    }
+                }
+            }
+            start = end;
+        }
+        Ok(estimates)
+    }
+}
+
+/// Returns the indexes of the largest leaves holding at least `target` docs,
+/// or all of them.
+fn sample_leaves(max_docs: &[i32], target: i64) -> Vec<usize> {
+    let mut by_size: Vec<usize> = (0..max_docs.len()).collect();
+    by_size.sort_by(|&a, &b| match max_docs[b].cmp(&max_docs[a]) {
+        Ordering::Equal => a.cmp(&b),
+        o => o,
+    });
+    let mut sampled_docs = 0i64;
+    let mut count = 0;
+    for &i in &by_size {
+        if sampled_docs >= target {
+            break;
+        }
+        sampled_docs += i64::from(max_docs[i]);
+        count += 1;
+    }
+    by_size.truncate(count);
+    by_size
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,4 +272,14 @@ mod tests {
         assert_eq!(term_statistics.doc_freq, 1);
         assert_eq!(term_statistics.total_term_freq, 1);
     }
+
+    #[test]
+    fn test_sample_leaves() {
+        assert_eq!(sample_leaves(&[10, 50, 30, 50], 60), vec![1, 3]);
+        assert_eq!(sample_leaves(&[10, 50, 30, 50], 100), vec![1, 3]);
+        assert_eq!(sample_leaves(&[10, 50, 30, 50], 101), vec![1, 3, 2]);
+        assert_eq!(sample_leaves(&[10, 50, 30, 50], 1000).len(), 4);
+        assert!(TermStatsEstimator::new(0.0).is_err());
+        assert!(TermStatsEstimator::new(1.5).is_err());
+    }
 }
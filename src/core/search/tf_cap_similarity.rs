@@ -0,0 +1,213 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::Result;
+use std::collections::HashMap;
+use std::fmt;
+
+use core::codec::Codec;
+use core::index::SearchLeafReader;
+use core::search::explanation::Explanation;
+use core::search::statistics::{CollectionStatistics, TermStatistics};
+use core::search::{SimScorer, SimWeight, Similarity, SimilarityProducer};
+use core::util::{DocId, KeyedContext};
+
+/// Wraps a `Similarity` so that the term frequency it scores with never
+/// exceeds `max_tf`.
+///
+/// This bounds the score a document can get by repeating a term, which limits
+/// the effect of keyword stuffing without reindexing. The saturation of BM25
+/// can be tuned as well through the `k1` of `BM25Similarity::new`.
+pub struct TfCapSimilarity<C: Codec> {
+    similarity: Box<dyn Similarity<C>>,
+    max_tf: f32,
+}
+
+impl<C: Codec> TfCapSimilarity<C> {
+    pub fn new(similarity: Box<dyn Similarity<C>>, max_tf: f32) -> TfCapSimilarity<C> {
+        debug_assert!(max_tf > 0.0);
+        TfCapSimilarity { similarity, max_tf }
+    }
+
+    pub fn max_tf(&self) -> f32 {
+        self.max_tf
+    }
+}
+
+impl<C: Codec> Similarity<C> for TfCapSimilarity<C> {
+    fn compute_weight(
+        &self,
+        collection_stats: &CollectionStatistics,
+        term_stats: &[TermStatistics],
+        context: Option<&KeyedContext>,
+        boost: f32,
+    ) -> Box<dyn SimWeight<C>> {
+        Box::new(TfCapSimWeight {
+            sim_weight: self.similarity.compute_weight(
+                collection_stats,
+                term_stats,
+                context,
+                boost,
+            ),
+            max_tf: self.max_tf,
+        })
+    }
+
+    fn query_norm(&self, value_for_normalization: f32, context: Option<&KeyedContext>) -> f32 {
+        self.similarity.query_norm(value_for_normalization, context)
+    }
+}
+
+impl<C: Codec> fmt::Display for TfCapSimilarity<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TfCap(max_tf: {}, {})", self.max_tf, self.similarity)
+    }
+}
+
+struct TfCapSimWeight<C: Codec> {
+    sim_weight: Box<dyn SimWeight<C>>,
+    max_tf: f32,
+}
+
+impl<C: Codec> SimWeight<C> for TfCapSimWeight<C> {
+    fn get_value_for_normalization(&self) -> f32 {
+        self.sim_weight.get_value_for_normalization()
+    }
+
+    fn normalize(&mut self, query_norm: f32, boost: f32) {
+        self.sim_weight.normalize(query_norm, boost)
+    }
+
+    fn sim_scorer(&self, reader: &SearchLeafReader<C>) -> Result<Box<dyn SimScorer>> {
+        Ok(Box::new(TfCapSimScorer {
+            sim_scorer: self.sim_weight.sim_scorer(reader)?,
+            max_tf: self.max_tf,
+        }))
+    }
+
+    fn explain(
+        &self,
+        reader: &SearchLeafReader<C>,
+        doc: DocId,
+        freq: Explanation,
+    ) -> Result<Explanation> {
+        let freq = if freq.value() > self.max_tf {
+            Explanation::new(
+                true,
+                self.max_tf,
+                "min of freq and max_tf".into(),
+                vec![
+                    freq,
+                    Explanation::new(true, self.max_tf, "max_tf".into(), vec![]),
+                ],
+            )
+        } else {
+            freq
+        };
+        self.sim_weight.explain(reader, doc, freq)
+    }
+}
+
+struct TfCapSimScorer {
+    sim_scorer: Box<dyn SimScorer>,
+    max_tf: f32,
+}
+
+impl SimScorer for TfCapSimScorer {
+    fn score(&mut self, doc: DocId, freq: f32) -> Result<f32> {
+        self.sim_scorer.score(doc, freq.min(self.max_tf))
+    }
+
+    fn compute_slop_factor(&self, distance: i32) -> f32 {
+        self.sim_scorer.compute_slop_factor(distance)
+    }
+}
+
+/// A `SimilarityProducer` capping the term frequencies of the similarities of
+/// another producer, see `TfCapSimilarity`.
+///
+/// The cap can be set per field, with a default for the other fields. Pass it
+/// to `DefaultIndexSearcher::search_with_similarity` to cap a single request.
+pub struct TfCapSimilarityProducer<P> {
+    sim_producer: P,
+    default_max_tf: Option<f32>,
+    field_max_tf: HashMap<String, f32>,
+}
+
+impl<P> TfCapSimilarityProducer<P> {
+    pub fn new(sim_producer: P) -> TfCapSimilarityProducer<P> {
+        TfCapSimilarityProducer {
+            sim_producer,
+            default_max_tf: None,
+            field_max_tf: HashMap::new(),
+        }
+    }
+
+    /// Caps the term frequency of the fields without a cap of their own.
+    pub fn set_default_max_tf(&mut self, max_tf: Option<f32>) {
+        self.default_max_tf = max_tf;
+    }
+
+    /// Caps the term frequency of `field`.
+    pub fn set_max_tf(&mut self, field: &str, max_tf: f32) {
+        self.field_max_tf.insert(field.to_string(), max_tf);
+    }
+
+    pub fn max_tf(&self, field: &str) -> Option<f32> {
+        self.field_max_tf
+            .get(field)
+            .cloned()
+            .or(self.default_max_tf)
+    }
+}
+
+impl<C: Codec, P: SimilarityProducer<C>> SimilarityProducer<C> for TfCapSimilarityProducer<P> {
+    fn create(&self, field: &str) -> Box<dyn Similarity<C>> {
+        let similarity = self.sim_producer.create(field);
+        match self.max_tf(field) {
+            Some(max_tf) => Box::new(TfCapSimilarity::new(similarity, max_tf)),
+            None => similarity,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::codec::tests::TestCodec;
+    use core::index::tests::MockLeafReader;
+    use core::search::bm25_similarity::BM25Similarity;
+
+    #[test]
+    fn test_tf_cap() {
+        let collection_stats = CollectionStatistics::new(String::from("title"), 32, 32, 64, 64);
+        let term_stats = vec![TermStatistics::new(Vec::new(), 4, 8)];
+        let reader = MockLeafReader::new(0);
+
+        let bm25: Box<dyn Similarity<TestCodec>> = Box::new(BM25Similarity::default());
+        let mut scorer = bm25
+            .compute_weight(&collection_stats, &term_stats, None, 1.0)
+            .sim_scorer(&reader)
+            .unwrap();
+        let two = scorer.score(1, 2.0).unwrap();
+        assert!(scorer.score(1, 10.0).unwrap() > two);
+
+        let capped = TfCapSimilarity::new(bm25, 2.0);
+        let mut scorer = capped
+            .compute_weight(&collection_stats, &term_stats, None, 1.0)
+            .sim_scorer(&reader)
+            .unwrap();
+        assert!((scorer.score(1, 10.0).unwrap() - two).abs() < ::std::f32::EPSILON);
+        assert!(scorer.score(1, 1.0).unwrap() < two);
+    }
+}
@@ -11,9 +11,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::search::sort_field::SortField;
+use core::search::sort_field::{SortField, SortFieldType};
 use core::util::DocId;
 use core::util::VariantValue;
+use error::{ErrorKind::IllegalArgument, Result};
 use std::cmp::{Ord, Ordering};
 use std::f32;
 
@@ -22,16 +23,27 @@ use std::f32;
 pub struct ScoreDoc {
     pub doc: DocId,
     pub score: f32,
+    /// Only set by `TopDocs::merge`, the index of the shard the hit comes from.
+    pub shard_index: usize,
+    /// Only set by `ScoreBreakdownCollector`, the partial score of each
+    /// clause of `TopScoreDocs::clauses`, zero for the clauses not matched.
+    pub breakdown: Vec<f32>,
 }
 
 impl ScoreDoc {
     pub fn new(doc: DocId, score: f32) -> ScoreDoc {
-        ScoreDoc { doc, score }
+        ScoreDoc {
+            doc,
+            score,
+            shard_index: 0,
+            breakdown: Vec::new(),
+        }
     }
 
     pub fn reset(&mut self, doc: DocId, score: f32) {
         self.doc = doc;
         self.score = score;
+        self.breakdown.clear();
     }
 
     pub fn order_by_doc(d1: &ScoreDoc, d2: &ScoreDoc) -> Ordering {
@@ -51,11 +63,13 @@ impl Ord for ScoreDoc {
     }
 }
 
+/// Reversed, so the top of a `BinaryHeap` is the least competitive hit: the
+/// lowest score, and on ties the greatest doc id.
 impl PartialOrd for ScoreDoc {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         self.score
             .partial_cmp(&other.score)
-            .map(|ord| ord.reverse())
+            .map(|ord| ord.reverse().then(self.doc.cmp(&other.doc)))
     }
 }
 
@@ -158,6 +172,20 @@ impl ScoreDocHit {
         }
     }
 
+    pub fn shard_index(&self) -> usize {
+        match *self {
+            ScoreDocHit::Score(ref s) => s.shard_index,
+            ScoreDocHit::Field(ref f) => f.shard_index,
+        }
+    }
+
+    pub fn set_shard_index(&mut self, shard_index: usize) {
+        match *self {
+            ScoreDocHit::Score(ref mut s) => s.shard_index = shard_index,
+            ScoreDocHit::Field(ref mut f) => f.shard_index = shard_index,
+        }
+    }
+
     pub fn order_by_doc(d1: &ScoreDocHit, d2: &ScoreDocHit) -> Ordering {
         if d1.doc_id() < d2.doc_id() {
             Ordering::Less
@@ -212,6 +240,10 @@ pub struct TopScoreDocs {
 
     /// Stores the maximum score value encountered, needed for normalizing.
     max_score: f32,
+
+    /// The clauses of the query the `ScoreDoc::breakdown` of the hits refer
+    /// to, empty unless collected by `ScoreBreakdownCollector`.
+    pub clauses: Vec<String>,
 }
 
 impl TopScoreDocs {
@@ -220,12 +252,52 @@ impl TopScoreDocs {
             total_hits,
             score_docs,
             max_score: f32::NAN,
+            clauses: vec![],
         }
     }
 
     pub fn score_docs(&self) -> &[ScoreDocHit] {
         &self.score_docs
     }
+
+    pub fn max_score(&self) -> f32 {
+        self.max_score
+    }
+
+    /// Merges the top hits of several shards sorted by score into the hits
+    /// `start..start + size` of their union.
+    ///
+    /// Ties are broken by shard index, then by rank in the shard, as doc ids
+    /// of different shards aren't comparable. If `set_shard_index` is true,
+    /// the shard index of each hit is set to the index of its shard in
+    /// `shard_hits`, otherwise the shard index already set is used, e.g. when
+    /// merging results which were already merged.
+    pub fn merge(
+        start: usize,
+        size: usize,
+        shard_hits: &[TopScoreDocs],
+        set_shard_index: bool,
+    ) -> TopScoreDocs {
+        let total_hits = shard_hits.iter().map(|s| s.total_hits).sum();
+        let max_score = shard_hits
+            .iter()
+            .fold(f32::NAN, |max, s| max.max(s.max_score));
+        let hits: Vec<&[ScoreDocHit]> = shard_hits.iter().map(|s| s.score_docs()).collect();
+        let score_docs = merge_hits(start, size, &hits, set_shard_index, |a, b| {
+            b.score().partial_cmp(&a.score()).unwrap_or(Ordering::Equal)
+        });
+        let clauses = shard_hits
+            .iter()
+            .find(|s| !s.clauses.is_empty())
+            .map(|s| s.clauses.clone())
+            .unwrap_or_default();
+        TopScoreDocs {
+            total_hits,
+            score_docs,
+            max_score,
+            clauses,
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -236,6 +308,111 @@ pub struct TopFieldDocs {
     pub fields: Vec<SortField>,
 }
 
+impl TopFieldDocs {
+    /// Merges the top hits of several shards sorted by `sort` into the hits
+    /// `start..start + size` of their union, see `TopScoreDocs::merge` for
+    /// how ties and shard indices are handled.
+    ///
+    /// The hits of all shards must be `FieldDoc`s with the values of the
+    /// sort fields.
+    pub fn merge(
+        sort: &[SortField],
+        start: usize,
+        size: usize,
+        shard_hits: &[TopFieldDocs],
+        set_shard_index: bool,
+    ) -> Result<TopFieldDocs> {
+        for (shard_index, shard) in shard_hits.iter().enumerate() {
+            for hit in &shard.score_docs {
+                match hit {
+                    ScoreDocHit::Field(f) if f.fields.len() == sort.len() => {}
+                    _ => bail!(IllegalArgument(format!(
+                        "hits of shard {} don't have the values of the {} sort fields",
+                        shard_index,
+                        sort.len()
+                    ))),
+                }
+            }
+        }
+        let total_hits = shard_hits.iter().map(|s| s.total_hits).sum();
+        let max_score = shard_hits
+            .iter()
+            .fold(f32::NAN, |max, s| max.max(s.max_score));
+        let hits: Vec<&[ScoreDocHit]> = shard_hits.iter().map(|s| &s.score_docs[..]).collect();
+        let score_docs = merge_hits(start, size, &hits, set_shard_index, |a, b| {
+            compare_sort_values(sort, a, b)
+        });
+        Ok(TopFieldDocs {
+            total_hits,
+            score_docs,
+            max_score,
+            fields: sort.to_vec(),
+        })
+    }
+}
+
+pub(crate) fn compare_sort_values(
+    sort: &[SortField],
+    a: &ScoreDocHit,
+    b: &ScoreDocHit,
+) -> Ordering {
+    if let (ScoreDocHit::Field(a), ScoreDocHit::Field(b)) = (a, b) {
+        for (i, sort_field) in sort.iter().enumerate() {
+            let mut ord = a.fields[i].cmp(&b.fields[i]);
+            // higher scores sort first
+            if sort_field.field_type() == SortFieldType::Score {
+                ord = ord.reverse();
+            }
+            if sort_field.is_reverse() {
+                ord = ord.reverse();
+            }
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+    }
+    Ordering::Equal
+}
+
+/// Merges the sorted hits of the shards, ties are broken by shard index then
+/// by rank in the shard.
+fn merge_hits<F>(
+    start: usize,
+    size: usize,
+    shard_hits: &[&[ScoreDocHit]],
+    set_shard_index: bool,
+    compare: F,
+) -> Vec<ScoreDocHit>
+where
+    F: Fn(&ScoreDocHit, &ScoreDocHit) -> Ordering,
+{
+    // the top `start + size` hits of a shard are enough
+    let limit = start.saturating_add(size);
+    let mut candidates: Vec<(usize, ScoreDocHit)> = vec![];
+    for (shard_index, hits) in shard_hits.iter().enumerate() {
+        for (rank, hit) in hits.iter().take(limit).enumerate() {
+            let mut hit = hit.clone();
+            if set_shard_index {
+                hit.set_shard_index(shard_index);
+            }
+            candidates.push((rank, hit));
+        }
+    }
+    candidates.sort_by(|(rank1, hit1), (rank2, hit2)| match compare(hit1, hit2) {
+        Ordering::Equal => match hit1.shard_index().cmp(&hit2.shard_index()) {
+            Ordering::Equal => rank1.cmp(rank2),
+            o => o,
+        },
+        o => o,
+    });
+    candidates
+        .into_iter()
+        .skip(start)
+        .take(size)
+        .map(|(_, hit)| hit)
+        .collect()
+}
+
 pub struct CollapseTopFieldDocs {
     /// The total number of hits for the query.
     pub total_hits: usize,
@@ -323,4 +500,124 @@ impl TopDocs {
             TopDocs::Collapse(ref mut c) => &mut c.score_docs,
         }
     }
+
+    /// Merges the top docs of several shards, which must all be sorted the
+    /// same way, into the hits `start..start + size` of their union.
+    ///
+    /// See `TopScoreDocs::merge` and `TopFieldDocs::merge`, collapsed top docs
+    /// can't be merged.
+    pub fn merge(
+        start: usize,
+        size: usize,
+        shard_hits: &[TopDocs],
+        set_shard_index: bool,
+    ) -> Result<TopDocs> {
+        match shard_hits.first() {
+            None => Ok(TopDocs::Score(TopScoreDocs::new(0, vec![]))),
+            Some(TopDocs::Score(_)) => {
+                let mut shards = Vec::with_capacity(shard_hits.len());
+                for hits in shard_hits {
+                    match hits {
+                        TopDocs::Score(s) => shards.push(s.clone()),
+                        _ => bail!(IllegalArgument(
+                            "can't merge top docs sorted by score and by fields".into()
+                        )),
+                    }
+                }
+                Ok(TopDocs::Score(TopScoreDocs::merge(
+                    start,
+                    size,
+                    &shards,
+                    set_shard_index,
+                )))
+            }
+            Some(TopDocs::Field(first)) => {
+                let mut shards = Vec::with_capacity(shard_hits.len());
+                for hits in shard_hits {
+                    match hits {
+                        TopDocs::Field(f) if f.fields == first.fields => shards.push(f.clone()),
+                        _ => bail!(IllegalArgument(
+                            "can't merge top docs with different sorts".into()
+                        )),
+                    }
+                }
+                let merged =
+                    TopFieldDocs::merge(&first.fields, start, size, &shards, set_shard_index)?;
+                Ok(TopDocs::Field(merged))
+            }
+            Some(TopDocs::Collapse(_)) => {
+                bail!(IllegalArgument("collapsed top docs can't be merged".into()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::search::sort_field::SimpleSortField;
+
+    fn score_hits(hits: &[(DocId, f32)]) -> TopScoreDocs {
+        let score_docs = hits
+            .iter()
+            .map(|&(doc, score)| ScoreDocHit::Score(ScoreDoc::new(doc, score)))
+            .collect();
+        TopScoreDocs::new(hits.len() * 2, score_docs)
+    }
+
+    fn field_hits(hits: &[(DocId, i64)]) -> TopFieldDocs {
+        let score_docs = hits
+            .iter()
+            .map(|&(doc, v)| {
+                ScoreDocHit::Field(FieldDoc::new(doc, 0.0, vec![VariantValue::Long(v)]))
+            })
+            .collect();
+        TopFieldDocs {
+            total_hits: hits.len(),
+            score_docs,
+            max_score: f32::NAN,
+            fields: vec![],
+        }
+    }
+
+    fn ids(hits: &[ScoreDocHit]) -> Vec<(usize, DocId)> {
+        hits.iter().map(|h| (h.shard_index(), h.doc_id())).collect()
+    }
+
+    #[test]
+    fn test_merge_by_score() {
+        let shards = vec![
+            score_hits(&[(3, 5.0), (1, 2.0), (0, 1.0)]),
+            score_hits(&[(0, 4.0), (7, 2.0)]),
+        ];
+        let merged = TopScoreDocs::merge(0, 10, &shards, true);
+        assert_eq!(merged.total_hits, 10);
+        assert_eq!(
+            ids(merged.score_docs()),
+            vec![(0, 3), (1, 0), (0, 1), (1, 7), (0, 0)]
+        );
+
+        let page = TopScoreDocs::merge(1, 2, &shards, true);
+        assert_eq!(ids(page.score_docs()), vec![(1, 0), (0, 1)]);
+
+        // the shard indices of already merged hits are kept
+        let remerged = TopScoreDocs::merge(0, 3, &[merged], false);
+        assert_eq!(ids(remerged.score_docs()), vec![(0, 3), (1, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn test_merge_by_fields() {
+        let sort = vec![SortField::Simple(SimpleSortField::new(
+            "f".into(),
+            SortFieldType::Long,
+            true,
+        ))];
+        let mut shards = vec![field_hits(&[(2, 9), (5, 4)]), field_hits(&[(1, 9), (8, 7)])];
+        let merged = TopFieldDocs::merge(&sort, 0, 3, &shards, true).unwrap();
+        assert_eq!(merged.total_hits, 4);
+        assert_eq!(ids(&merged.score_docs), vec![(0, 2), (1, 1), (1, 8)]);
+
+        shards[1].score_docs[0] = ScoreDocHit::Score(ScoreDoc::new(1, 1.0));
+        assert!(TopFieldDocs::merge(&sort, 0, 3, &shards, true).is_err());
+    }
 }
@@ -0,0 +1,194 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{codec_util, Codec};
+use core::search::query_string::QueryStringQueryBuilder;
+use core::search::searcher::IndexSearcher;
+use core::store::{
+    BufferedChecksumIndexInput, ChecksumIndexInput, DataInput, DataOutput, Directory, IOContext,
+};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::collections::HashSet;
+
+/// The name of the file the warm-up list is persisted to in the index
+/// directory. It doesn't look like a codec file so the `IndexFileDeleter`
+/// leaves it alone.
+pub const WARM_UP_FILE_NAME: &str = "warmup_queries";
+
+const WARM_UP_PENDING_FILE_NAME: &str = "pending_warmup_queries";
+
+const WARM_UP_CODEC: &str = "WarmUpQueries";
+const WARM_UP_VERSION_START: i32 = 0;
+const WARM_UP_VERSION_CURRENT: i32 = WARM_UP_VERSION_START;
+
+/// A named query run against every new searcher before it's published, the
+/// query is kept as a query string parsed by `QueryStringQueryBuilder`.
+#[derive(Debug, Clone)]
+pub struct WarmUpQuery {
+    pub name: String,
+    pub query: String,
+    pub fields: Vec<(String, f32)>,
+}
+
+impl WarmUpQuery {
+    pub fn new(name: &str, query: &str, fields: Vec<(String, f32)>) -> WarmUpQuery {
+        WarmUpQuery {
+            name: name.to_string(),
+            query: query.to_string(),
+            fields,
+        }
+    }
+
+    fn builder(&self) -> QueryStringQueryBuilder {
+        QueryStringQueryBuilder::new(self.query.clone(), self.fields.clone(), 1, 1.0)
+    }
+}
+
+/// The list of warm-up queries of an index, in the order they are run.
+///
+/// The list is persisted in the index directory with `write`, so that a
+/// `SearcherManager` opened after a restart loads it with `read` and
+/// populates the cold caches deterministically, see
+/// `SearcherManager::set_warm_up_list`.
+#[derive(Debug, Clone, Default)]
+pub struct WarmUpList {
+    queries: Vec<WarmUpQuery>,
+}
+
+impl WarmUpList {
+    pub fn new() -> WarmUpList {
+        WarmUpList::default()
+    }
+
+    pub fn queries(&self) -> &[WarmUpQuery] {
+        &self.queries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    /// Adds a query, replacing the query of the same name if any. The query
+    /// string is parsed once so that an invalid query is rejected here
+    /// rather than on each refresh.
+    pub fn add<C: Codec>(&mut self, query: WarmUpQuery) -> Result<()> {
+        query.builder().build::<C>()?;
+        if let Some(existing) = self.queries.iter_mut().find(|q| q.name == query.name) {
+            *existing = query;
+        } else {
+            self.queries.push(query);
+        }
+        Ok(())
+    }
+
+    /// Removes the query of the given name, returns whether it was present.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.queries.len();
+        self.queries.retain(|q| q.name != name);
+        self.queries.len() != len
+    }
+
+    /// Runs all the queries against `searcher`, returns the number of
+    /// queries which ran successfully. A failed query is only logged, the
+    /// warm-up is best effort and must not prevent publishing a searcher.
+    pub fn warm_up<C: Codec, S: IndexSearcher<C>>(&self, searcher: &S) -> usize {
+        let mut warmed = 0;
+        for query in &self.queries {
+            let res = query
+                .builder()
+                .build::<C>()
+                .and_then(|q| searcher.count(q.as_ref()));
+            match res {
+                Ok(_) => warmed += 1,
+                Err(e) => warn!("warm-up query '{}' failed by '{:?}'", query.name, e),
+            }
+        }
+        warmed
+    }
+
+    /// Reads the list persisted in `directory`, the list is empty if none
+    /// was ever written.
+    pub fn read<D: Directory>(directory: &D) -> Result<WarmUpList> {
+        if !directory
+            .list_all()?
+            .iter()
+            .any(|name| name == WARM_UP_FILE_NAME)
+        {
+            return Ok(WarmUpList::default());
+        }
+        let input = directory.open_input(WARM_UP_FILE_NAME, &IOContext::READ)?;
+        let mut input = BufferedChecksumIndexInput::new(input);
+        codec_util::check_header(
+            &mut input,
+            WARM_UP_CODEC,
+            WARM_UP_VERSION_START,
+            WARM_UP_VERSION_CURRENT,
+        )?;
+        let count = input.read_vint()?;
+        if count < 0 {
+            bail!(IllegalArgument(format!(
+                "invalid warm-up query count: {}",
+                count
+            )));
+        }
+        let mut queries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let name = input.read_string()?;
+            let query = input.read_string()?;
+            let num_fields = input.read_vint()?;
+            let mut fields = Vec::with_capacity(num_fields.max(0) as usize);
+            for _ in 0..num_fields {
+                let field = input.read_string()?;
+                let boost = f32::from_bits(input.read_int()? as u32);
+                fields.push((field, boost));
+            }
+            queries.push(WarmUpQuery {
+                name,
+                query,
+                fields,
+            });
+        }
+        codec_util::validate_footer(&mut input)?;
+        let digest = input.checksum();
+        codec_util::check_checksum(&mut input, digest)?;
+        Ok(WarmUpList { queries })
+    }
+
+    /// Persists the list in `directory`, the previous list is replaced
+    /// atomically through a pending file renamed once synced.
+    pub fn write<D: Directory>(&self, directory: &D) -> Result<()> {
+        {
+            let mut output =
+                directory.create_output(WARM_UP_PENDING_FILE_NAME, &IOContext::Default)?;
+            codec_util::write_header(&mut output, WARM_UP_CODEC, WARM_UP_VERSION_CURRENT)?;
+            output.write_vint(self.queries.len() as i32)?;
+            for query in &self.queries {
+                output.write_string(&query.name)?;
+                output.write_string(&query.query)?;
+                output.write_vint(query.fields.len() as i32)?;
+                for (field, boost) in &query.fields {
+                    output.write_string(field)?;
+                    output.write_int(boost.to_bits() as i32)?;
+                }
+            }
+            codec_util::write_footer(&mut output)?;
+        }
+        let mut sync_files = HashSet::with_capacity(1);
+        sync_files.insert(WARM_UP_PENDING_FILE_NAME.to_string());
+        directory.sync(&sync_files)?;
+        directory.rename(WARM_UP_PENDING_FILE_NAME, WARM_UP_FILE_NAME)?;
+        directory.sync_meta_data()
+    }
+}
@@ -121,6 +121,8 @@ pub trait Directory: fmt::Display {
         Ok(())
     }
 
+    /// Returns the files created through this directory, only directories tracking
+    /// them like `TrackingDirectoryWrapper` implement it.
     fn create_files(&self) -> HashSet<String> {
         unreachable!()
     }
@@ -128,6 +130,69 @@ pub trait Directory: fmt::Display {
     fn resolve(&self, _name: &str) -> PathBuf {
         unimplemented!()
     }
+
+    /// Returns the files which were deleted but couldn't be removed yet, for
+    /// instance because they are still open on Windows. Their deletion is
+    /// retried on subsequent operations on the directory.
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        Ok(HashSet::new())
+    }
+
+    /// Tries right away to delete the files pending deletion, see
+    /// `pending_deletions`.
+    fn delete_pending_files(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// An object safe view of the files of a `Directory`, handed to the formats
+/// implemented as trait objects which can't be generic over the directory.
+pub trait FilesDirectory {
+    fn open_file(&self, name: &str, ctx: &IOContext) -> Result<Box<dyn IndexInput>>;
+}
+
+impl<D: Directory> FilesDirectory for D {
+    fn open_file(&self, name: &str, ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
+        self.open_input(name, ctx)
+    }
+}
+
+/// The files written by a format implemented as a trait object, the outputs
+/// stay owned by the writer and are handed out by the index returned when
+/// they are created.
+pub trait FilesOutputs {
+    fn create_file(&mut self, name: &str) -> Result<usize>;
+
+    fn file(&mut self, index: usize) -> &mut dyn IndexOutput;
+}
+
+/// `FilesOutputs` creating the outputs in a `Directory`.
+pub struct DirectoryOutputs<D: Directory> {
+    directory: Arc<D>,
+    context: IOContext,
+    outputs: Vec<D::IndexOutput>,
+}
+
+impl<D: Directory> DirectoryOutputs<D> {
+    pub fn new(directory: Arc<D>, context: IOContext) -> Self {
+        DirectoryOutputs {
+            directory,
+            context,
+            outputs: vec![],
+        }
+    }
+}
+
+impl<D: Directory> FilesOutputs for DirectoryOutputs<D> {
+    fn create_file(&mut self, name: &str) -> Result<usize> {
+        let output = self.directory.create_output(name, &self.context)?;
+        self.outputs.push(output);
+        Ok(self.outputs.len() - 1)
+    }
+
+    fn file(&mut self, index: usize) -> &mut dyn IndexOutput {
+        &mut self.outputs[index]
+    }
 }
 
 /// This struct makes a best-effort check that a provided
@@ -208,6 +273,15 @@ impl<D: Directory> Directory for LockValidatingDirectoryWrapper<D> {
         self.write_lock.ensure_valid()?;
         self.dir.copy_from(from, src, dest, ctx)
     }
+
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        self.dir.pending_deletions()
+    }
+
+    fn delete_pending_files(&self) -> Result<()> {
+        self.write_lock.ensure_valid()?;
+        self.dir.delete_pending_files()
+    }
 }
 
 impl<D: Directory> fmt::Display for LockValidatingDirectoryWrapper<D> {
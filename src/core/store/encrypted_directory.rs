@@ -0,0 +1,696 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use aes_ctr::stream_cipher::generic_array::GenericArray;
+use aes_ctr::stream_cipher::{NewStreamCipher, SyncStreamCipher, SyncStreamCipherSeek};
+use aes_ctr::Aes256Ctr;
+use byteorder::{BigEndian, ByteOrder};
+use flate2::Crc;
+use rand::rngs::OsRng;
+use rand::RngCore;
+
+use core::index::index_writer::INDEX_WRITE_LOCK_NAME;
+use core::store::{
+    DataInput, DataOutput, Directory, IOContext, IndexInput, IndexOutput, Lock, RandomAccessInput,
+};
+use error::{
+    ErrorKind::{CorruptIndex, IllegalArgument, IllegalState, UnexpectedEOF},
+    Result,
+};
+
+/// Length in bytes of the AES-256 keys.
+pub const ENCRYPTION_KEY_LENGTH: usize = 32;
+
+// magic number of the header of the encrypted files, "RENC"
+const ENCRYPTED_FILE_MAGIC: i32 = 0x5245_4e43;
+// length of the initialization vector of each file
+const IV_LENGTH: usize = 16;
+// the magic number, the version of the key and the initialization vector
+const HEADER_LENGTH: i64 = 4 + 4 + IV_LENGTH as i64;
+// suffix of the copy of a file re-encrypted with the current key, renamed to
+// the file once written
+const ROTATED_FILE_SUFFIX: &str = ".rotated";
+
+struct EncryptionKeysInner {
+    keys: HashMap<u32, [u8; ENCRYPTION_KEY_LENGTH]>,
+    current: u32,
+}
+
+/// The versioned keys of an `EncryptedDirectory`.
+///
+/// Files are written with the current key and record the version of the key
+/// they were written with, so the files written before a rotation are read
+/// with the key they were written with as long as it is kept.
+pub struct EncryptionKeys {
+    inner: RwLock<EncryptionKeysInner>,
+}
+
+impl EncryptionKeys {
+    pub fn new(version: u32, key: [u8; ENCRYPTION_KEY_LENGTH]) -> EncryptionKeys {
+        let mut keys = HashMap::new();
+        keys.insert(version, key);
+        EncryptionKeys {
+            inner: RwLock::new(EncryptionKeysInner {
+                keys,
+                current: version,
+            }),
+        }
+    }
+
+    /// Returns the version of the key new files are written with.
+    pub fn current_version(&self) -> Result<u32> {
+        Ok(self.inner.read()?.current)
+    }
+
+    /// Returns the versions of the known keys.
+    pub fn versions(&self) -> Result<Vec<u32>> {
+        let mut versions: Vec<u32> = self.inner.read()?.keys.keys().cloned().collect();
+        versions.sort();
+        Ok(versions)
+    }
+
+    /// Adds a key to read the files written with it, fails if another key
+    /// has the same version.
+    pub fn add_key(&self, version: u32, key: [u8; ENCRYPTION_KEY_LENGTH]) -> Result<()> {
+        let mut inner = self.inner.write()?;
+        if let Some(existing) = inner.keys.get(&version) {
+            if *existing != key {
+                bail!(IllegalArgument(format!(
+                    "another key has version {}",
+                    version
+                )));
+            }
+        }
+        inner.keys.insert(version, key);
+        Ok(())
+    }
+
+    /// Adds a key and writes the new files with it, the files written before
+    /// are re-encrypted with it by their next merge or by
+    /// `EncryptedDirectory::rotate_keys`.
+    pub fn rotate(&self, version: u32, key: [u8; ENCRYPTION_KEY_LENGTH]) -> Result<()> {
+        self.add_key(version, key)?;
+        self.inner.write()?.current = version;
+        Ok(())
+    }
+
+    /// Removes a retired key, the files still written with it can't be read
+    /// anymore.
+    pub fn remove_key(&self, version: u32) -> Result<()> {
+        let mut inner = self.inner.write()?;
+        if inner.current == version {
+            bail!(IllegalArgument(format!(
+                "key {} is the current key",
+                version
+            )));
+        }
+        inner.keys.remove(&version);
+        Ok(())
+    }
+
+    fn cipher(&self, version: u32, iv: &[u8]) -> Result<Aes256Ctr> {
+        match self.inner.read()?.keys.get(&version) {
+            Some(key) => Ok(Aes256Ctr::new(
+                GenericArray::from_slice(key),
+                GenericArray::from_slice(iv),
+            )),
+            None => bail!(IllegalState(format!("unknown key version {}", version))),
+        }
+    }
+
+    fn current_cipher(&self) -> Result<(u32, [u8; IV_LENGTH], Aes256Ctr)> {
+        let version = self.current_version()?;
+        let mut iv = [0u8; IV_LENGTH];
+        OsRng::new()
+            .map_err(|e| IllegalState(format!("no random source for the IV: {}", e)))?
+            .fill_bytes(&mut iv);
+        let cipher = self.cipher(version, &iv)?;
+        Ok((version, iv, cipher))
+    }
+}
+
+/// A `Directory` encrypting the files of another one with AES-256 in CTR
+/// mode, so the encrypted files can still be read at random positions.
+///
+/// Each file starts with a header holding the version of the key it was
+/// written with and its random initialization vector. After a rotation of
+/// the keys, the merges write their segments with the new key, and
+/// `rotate_keys` re-encrypts the remaining files at once.
+pub struct EncryptedDirectory<D: Directory> {
+    dir: Arc<D>,
+    keys: Arc<EncryptionKeys>,
+}
+
+impl<D: Directory> EncryptedDirectory<D> {
+    pub fn new(dir: Arc<D>, keys: Arc<EncryptionKeys>) -> EncryptedDirectory<D> {
+        EncryptedDirectory { dir, keys }
+    }
+
+    pub fn keys(&self) -> &Arc<EncryptionKeys> {
+        &self.keys
+    }
+
+    /// Returns the version of the key `name` was written with.
+    pub fn key_version(&self, name: &str) -> Result<u32> {
+        let mut input = self.dir.open_input(name, &IOContext::READ_ONCE)?;
+        let (version, _) = Self::read_header(input.as_mut())?;
+        Ok(version)
+    }
+
+    /// Re-encrypts `name` with the current key if it was written with
+    /// another one, returns whether it was rewritten.
+    pub fn rotate_file(&self, name: &str, ctx: &IOContext) -> Result<bool> {
+        if self.key_version(name)? == self.keys.current_version()? {
+            return Ok(false);
+        }
+        let rotated = format!("{}{}", name, ROTATED_FILE_SUFFIX);
+        {
+            let mut input = self.open_input(name, ctx)?;
+            let mut output = self.create_output(&rotated, ctx)?;
+            let length = input.len() as usize;
+            output.copy_bytes(input.as_mut(), length)?;
+        }
+        let mut files = HashSet::new();
+        files.insert(rotated.clone());
+        self.dir.sync(&files)?;
+        self.dir.rename(&rotated, name)?;
+        Ok(true)
+    }
+
+    /// Re-encrypts all the files written with another key than the current
+    /// one, returns the names of the rewritten files. The write lock of the
+    /// index is held meanwhile, so no `IndexWriter` may be open.
+    ///
+    /// Readers opened before keep reading the files they opened, the keys
+    /// the files were written with should only be removed once they are
+    /// closed.
+    pub fn rotate_keys(&self) -> Result<Vec<String>> {
+        let lock = self.dir.obtain_lock(INDEX_WRITE_LOCK_NAME)?;
+        let res = self.rotate_files();
+        lock.close()?;
+        res
+    }
+
+    fn rotate_files(&self) -> Result<Vec<String>> {
+        let pending = self.dir.pending_deletions()?;
+        let mut rotated = vec![];
+        for name in self.dir.list_all()? {
+            if name == INDEX_WRITE_LOCK_NAME || pending.contains(&name) {
+                continue;
+            }
+            if self.rotate_file(&name, &IOContext::Default)? {
+                rotated.push(name);
+            }
+        }
+        if !rotated.is_empty() {
+            self.dir.sync_meta_data()?;
+        }
+        Ok(rotated)
+    }
+
+    fn read_header(input: &mut dyn IndexInput) -> Result<(u32, [u8; IV_LENGTH])> {
+        if (input.len() as i64) < HEADER_LENGTH {
+            bail!(CorruptIndex(format!(
+                "file {} is too short to be encrypted",
+                input.name()
+            )));
+        }
+        let magic = input.read_int()?;
+        if magic != ENCRYPTED_FILE_MAGIC {
+            bail!(CorruptIndex(format!(
+                "file {} is not encrypted: magic {:x}",
+                input.name(),
+                magic
+            )));
+        }
+        let version = input.read_int()? as u32;
+        let mut iv = [0u8; IV_LENGTH];
+        input.read_bytes(&mut iv, 0, IV_LENGTH)?;
+        Ok((version, iv))
+    }
+
+    fn encrypted_output<O: IndexOutput>(&self, mut output: O) -> Result<EncryptedIndexOutput<O>> {
+        let (version, iv, cipher) = self.keys.current_cipher()?;
+        output.write_int(ENCRYPTED_FILE_MAGIC)?;
+        output.write_int(version as i32)?;
+        output.write_bytes(&iv, 0, IV_LENGTH)?;
+        Ok(EncryptedIndexOutput {
+            output,
+            cipher,
+            crc: Crc::new(),
+            buffer: vec![],
+            pos: 0,
+        })
+    }
+}
+
+impl<D: Directory> Directory for EncryptedDirectory<D> {
+    type LK = D::LK;
+    type IndexOutput = EncryptedIndexOutput<D::IndexOutput>;
+    type TempOutput = EncryptedIndexOutput<D::TempOutput>;
+
+    fn list_all(&self) -> Result<Vec<String>> {
+        self.dir.list_all()
+    }
+
+    fn file_length(&self, name: &str) -> Result<i64> {
+        Ok((self.dir.file_length(name)? - HEADER_LENGTH).max(0))
+    }
+
+    fn create_output(&self, name: &str, context: &IOContext) -> Result<Self::IndexOutput> {
+        let output = self.dir.create_output(name, context)?;
+        self.encrypted_output(output)
+    }
+
+    fn open_input(&self, name: &str, ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
+        let mut input = self.dir.open_input(name, ctx)?;
+        let (version, iv) = Self::read_header(input.as_mut())?;
+        let cipher = self.keys.cipher(version, &iv)?;
+        let length = input.len() - HEADER_LENGTH as u64;
+        Ok(Box::new(EncryptedIndexInput {
+            input,
+            cipher,
+            start: 0,
+            length,
+            pos: 0,
+        }))
+    }
+
+    fn obtain_lock(&self, name: &str) -> Result<Self::LK> {
+        self.dir.obtain_lock(name)
+    }
+
+    fn create_temp_output(
+        &self,
+        prefix: &str,
+        suffix: &str,
+        ctx: &IOContext,
+    ) -> Result<Self::TempOutput> {
+        let output = self.dir.create_temp_output(prefix, suffix, ctx)?;
+        self.encrypted_output(output)
+    }
+
+    fn delete_file(&self, name: &str) -> Result<()> {
+        self.dir.delete_file(name)
+    }
+
+    fn sync(&self, name: &HashSet<String>) -> Result<()> {
+        self.dir.sync(name)
+    }
+
+    fn sync_meta_data(&self) -> Result<()> {
+        self.dir.sync_meta_data()
+    }
+
+    fn rename(&self, source: &str, dest: &str) -> Result<()> {
+        self.dir.rename(source, dest)
+    }
+
+    fn resolve(&self, name: &str) -> PathBuf {
+        self.dir.resolve(name)
+    }
+
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        self.dir.pending_deletions()
+    }
+
+    fn delete_pending_files(&self) -> Result<()> {
+        self.dir.delete_pending_files()
+    }
+}
+
+impl<D: Directory> fmt::Display for EncryptedDirectory<D> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EncryptedDirectory({})", self.dir.as_ref())
+    }
+}
+
+/// Encrypts the bytes written to an output of the wrapped directory, the
+/// file pointer and the checksum being the ones of the plain bytes.
+pub struct EncryptedIndexOutput<O: IndexOutput> {
+    output: O,
+    cipher: Aes256Ctr,
+    crc: Crc,
+    // the encrypted bytes being written
+    buffer: Vec<u8>,
+    pos: i64,
+}
+
+impl<O: IndexOutput> IndexOutput for EncryptedIndexOutput<O> {
+    fn name(&self) -> &str {
+        self.output.name()
+    }
+
+    fn file_pointer(&self) -> i64 {
+        self.pos
+    }
+
+    fn checksum(&self) -> Result<i64> {
+        Ok(i64::from(self.crc.sum()))
+    }
+}
+
+impl<O: IndexOutput> DataOutput for EncryptedIndexOutput<O> {}
+
+impl<O: IndexOutput> Write for EncryptedIndexOutput<O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(buf);
+        self.cipher.apply_keystream(&mut self.buffer);
+        self.output.write_all(&self.buffer)?;
+        self.crc.update(buf);
+        self.pos += buf.len() as i64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+/// Decrypts the bytes read from an input of the wrapped directory, skipping
+/// the header of the file.
+pub struct EncryptedIndexInput {
+    // the input of the whole file, positioned at the next byte to read
+    input: Box<dyn IndexInput>,
+    cipher: Aes256Ctr,
+    // the bounds of this slice in the file, without the header
+    start: u64,
+    length: u64,
+    pos: u64,
+}
+
+impl EncryptedIndexInput {
+    fn slice_impl(&self, offset: i64, length: i64) -> Result<EncryptedIndexInput> {
+        if offset < 0 || length < 0 || (offset + length) as u64 > self.length {
+            bail!(IllegalArgument(format!(
+                "Illegal (offset, length) slice: ({}, {}) for file of length: {}",
+                offset, length, self.length
+            )));
+        }
+        let mut input = IndexInput::clone(self.input.as_ref())?;
+        let start = self.start + offset as u64;
+        input.seek(HEADER_LENGTH + start as i64)?;
+        Ok(EncryptedIndexInput {
+            input,
+            cipher: self.cipher.clone(),
+            start,
+            length: length as u64,
+            pos: 0,
+        })
+    }
+}
+
+impl IndexInput for EncryptedIndexInput {
+    fn clone(&self) -> Result<Box<dyn IndexInput>> {
+        let mut input = self.slice_impl(0, self.length as i64)?;
+        input.seek(self.pos as i64)?;
+        Ok(Box::new(input))
+    }
+
+    fn file_pointer(&self) -> i64 {
+        self.pos as i64
+    }
+
+    fn seek(&mut self, pos: i64) -> Result<()> {
+        if pos < 0 || pos as u64 > self.length {
+            bail!(IllegalArgument(format!(
+                "invalid position, expecting 0 < pos < {}, got: {}",
+                self.length, pos
+            )));
+        }
+        self.input.seek(HEADER_LENGTH + (self.start as i64) + pos)?;
+        self.pos = pos as u64;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.length
+    }
+
+    fn name(&self) -> &str {
+        self.input.name()
+    }
+
+    fn random_access_slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>> {
+        let slice = self.slice_impl(offset, length)?;
+        let input = self
+            .input
+            .random_access_slice(HEADER_LENGTH + slice.start as i64, length)?;
+        Ok(Box::new(EncryptedRandomAccessInput {
+            input,
+            cipher: slice.cipher,
+            start: slice.start,
+        }))
+    }
+
+    fn slice(&self, _description: &str, offset: i64, length: i64) -> Result<Box<dyn IndexInput>> {
+        Ok(Box::new(self.slice_impl(offset, length)?))
+    }
+}
+
+impl DataInput for EncryptedIndexInput {
+    fn read_bytes(&mut self, b: &mut [u8], offset: usize, length: usize) -> Result<()> {
+        if self.pos + length as u64 > self.length {
+            bail!(UnexpectedEOF(format!(
+                "read past EOF of '{}': {} + {} > {}",
+                self.name(),
+                self.pos,
+                length,
+                self.length
+            )));
+        }
+        let mut buf = &mut b[offset..offset + length];
+        while !buf.is_empty() {
+            let count = self.read(buf)?;
+            if count == 0 {
+                bail!(UnexpectedEOF(format!("read past EOF of '{}'", self.name())));
+            }
+            buf = &mut { buf }[count..];
+        }
+        Ok(())
+    }
+}
+
+impl Read for EncryptedIndexInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min((self.length - self.pos) as usize);
+        let count = self.input.read(&mut buf[..len])?;
+        // the cipher follows sequential reads, it's only moved after a seek
+        let pos = self.start + self.pos;
+        if self.cipher.current_pos() != pos {
+            self.cipher.seek(pos);
+        }
+        self.cipher.apply_keystream(&mut buf[..count]);
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+struct EncryptedRandomAccessInput {
+    input: Box<dyn RandomAccessInput>,
+    cipher: Aes256Ctr,
+    start: u64,
+}
+
+impl EncryptedRandomAccessInput {
+    fn decrypt(&self, pos: i64, buf: &mut [u8]) {
+        let mut cipher = self.cipher.clone();
+        cipher.seek(self.start + pos as u64);
+        cipher.apply_keystream(buf);
+    }
+}
+
+impl RandomAccessInput for EncryptedRandomAccessInput {
+    fn read_byte(&self, pos: i64) -> Result<u8> {
+        let mut buf = [self.input.read_byte(pos)?];
+        self.decrypt(pos, &mut buf);
+        Ok(buf[0])
+    }
+
+    fn read_short(&self, pos: i64) -> Result<i16> {
+        let mut buf = [0u8; 2];
+        BigEndian::write_i16(&mut buf, self.input.read_short(pos)?);
+        self.decrypt(pos, &mut buf);
+        Ok(BigEndian::read_i16(&buf))
+    }
+
+    fn read_int(&self, pos: i64) -> Result<i32> {
+        let mut buf = [0u8; 4];
+        BigEndian::write_i32(&mut buf, self.input.read_int(pos)?);
+        self.decrypt(pos, &mut buf);
+        Ok(BigEndian::read_i32(&buf))
+    }
+
+    fn read_long(&self, pos: i64) -> Result<i64> {
+        let mut buf = [0u8; 8];
+        BigEndian::write_i64(&mut buf, self.input.read_long(pos)?);
+        self.decrypt(pos, &mut buf);
+        Ok(BigEndian::read_i64(&buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{Field, FieldType, NumericDocValuesField};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexOptions, IndexReader, IndexWriter};
+    use core::index::{StandardDirectoryReader, Term};
+    use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+    use core::search::term_query::TermQuery;
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::path::Path;
+
+    type TestDirectory = EncryptedDirectory<FSDirectory<NativeFSLockFactory>>;
+
+    fn test_directory(path: &Path, keys: &Arc<EncryptionKeys>) -> Arc<TestDirectory> {
+        let dir = FSDirectory::new(path, NativeFSLockFactory::default()).unwrap();
+        Arc::new(EncryptedDirectory::new(Arc::new(dir), Arc::clone(keys)))
+    }
+
+    fn document(id: i64) -> Vec<Box<dyn Fieldable>> {
+        let mut id_type = FieldType::default();
+        id_type.tokenized = false;
+        id_type.stored = true;
+        id_type.index_options = IndexOptions::Docs;
+        id_type.omit_norms = true;
+        vec![
+            Box::new(Field::new(
+                "id".into(),
+                id_type,
+                Some(VariantValue::VString(format!("doc{}", id))),
+                None,
+            )),
+            Box::new(NumericDocValuesField::new("value", id * 10)),
+        ]
+    }
+
+    /// Checks the docs of the index can be searched and their values read.
+    fn check_index(dir: Arc<TestDirectory>, docs: i64) {
+        let reader = StandardDirectoryReader::open(dir).unwrap();
+        assert_eq!(reader.num_docs(), docs as i32);
+        let searcher = DefaultIndexSearcher::new(&reader);
+        for id in 0..docs {
+            let query = TermQuery::new(
+                Term::new("id".into(), format!("doc{}", id).into_bytes()),
+                1.0,
+                None,
+            );
+            assert_eq!(searcher.count(&query).unwrap(), 1, "doc {}", id);
+        }
+        let mut values = vec![];
+        for leaf in reader.leaves() {
+            let doc_values = leaf.reader.get_numeric_doc_values("value").unwrap();
+            for doc in 0..leaf.reader.max_doc() {
+                values.push(doc_values.get(doc).unwrap());
+            }
+        }
+        values.sort();
+        assert_eq!(values, (0..docs).map(|id| id * 10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_encrypted_directory() {
+        let path = ::std::env::temp_dir().join("rucene_encrypted_directory");
+        let _ = fs::remove_dir_all(&path);
+        let keys = Arc::new(EncryptionKeys::new(1, [7u8; ENCRYPTION_KEY_LENGTH]));
+        let dir = test_directory(&path, &keys);
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for id in 0..20 {
+            writer.add_document(document(id)).unwrap();
+        }
+        writer.close().unwrap();
+        check_index(Arc::clone(&dir), 20);
+
+        // the files can't be read without the key
+        let other_keys = Arc::new(EncryptionKeys::new(1, [8u8; ENCRYPTION_KEY_LENGTH]));
+        assert!(StandardDirectoryReader::open(test_directory(&path, &other_keys)).is_err());
+
+        // the plain bytes are read at random positions of slices
+        {
+            let mut output = dir.create_output("test", &IOContext::Default).unwrap();
+            for i in 0..1000 {
+                output.write_long(i).unwrap();
+            }
+        }
+        assert_eq!(dir.file_length("test").unwrap(), 8000);
+        let input = dir.open_input("test", &IOContext::Default).unwrap();
+        let mut slice = input.slice("test", 800, 800).unwrap();
+        slice.seek(8).unwrap();
+        assert_eq!(slice.read_long().unwrap(), 101);
+        let random_access = input.random_access_slice(4000, 800).unwrap();
+        assert_eq!(random_access.read_long(16).unwrap(), 502);
+        assert_eq!(random_access.read_int(20).unwrap(), 502);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+
+    #[test]
+    fn test_rotate_keys() {
+        let path = ::std::env::temp_dir().join("rucene_encrypted_directory_rotation");
+        let _ = fs::remove_dir_all(&path);
+        let keys = Arc::new(EncryptionKeys::new(1, [7u8; ENCRYPTION_KEY_LENGTH]));
+        let dir = test_directory(&path, &keys);
+        let writer =
+            IndexWriter::new(Arc::clone(&dir), Arc::new(IndexWriterConfig::default())).unwrap();
+        for id in 0..10 {
+            writer.add_document(document(id)).unwrap();
+        }
+        writer.commit().unwrap();
+
+        // the new segments are written with the new key, the older ones
+        // are read with their key
+        keys.rotate(2, [9u8; ENCRYPTION_KEY_LENGTH]).unwrap();
+        for id in 10..20 {
+            writer.add_document(document(id)).unwrap();
+        }
+        writer.commit().unwrap();
+        let versions: HashSet<u32> = dir
+            .list_all()
+            .unwrap()
+            .iter()
+            .filter(|name| name.as_str() != INDEX_WRITE_LOCK_NAME)
+            .map(|name| dir.key_version(name).unwrap())
+            .collect();
+        assert_eq!(versions, vec![1, 2].into_iter().collect());
+        check_index(Arc::clone(&dir), 20);
+
+        // the index can't be rotated while a writer is open
+        assert!(dir.rotate_keys().is_err());
+        writer.close().unwrap();
+        assert!(!dir.rotate_keys().unwrap().is_empty());
+        assert!(dir.rotate_keys().unwrap().is_empty());
+
+        keys.remove_key(1).unwrap();
+        assert!(keys.remove_key(2).is_err());
+        assert_eq!(keys.versions().unwrap(), vec![2]);
+        for name in dir.list_all().unwrap() {
+            if name != INDEX_WRITE_LOCK_NAME {
+                assert_eq!(dir.key_version(&name).unwrap(), 2, "{}", name);
+            }
+        }
+        check_index(Arc::clone(&dir), 20);
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -14,6 +14,7 @@
 use std::collections::{BTreeSet, HashSet};
 use std::fmt;
 use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::RwLock;
@@ -58,27 +59,27 @@ impl<LF: LockFactory> FSDirectory<LF> {
         })
     }
 
-    fn delete_pending_files(pending_deletes: &mut BTreeSet<String>, dir: &PathBuf) -> Result<()> {
-        let mut deleted_set = BTreeSet::new();
-        for name in pending_deletes.iter() {
-            let path = dir.join(name);
-            fs::remove_file(path)?;
-            deleted_set.insert(name.clone());
-        }
-        for name in deleted_set {
-            pending_deletes.remove(&name);
-        }
-        Ok(())
+    /// Tries to delete the files pending delete, the ones which still can't be
+    /// deleted stay pending.
+    fn delete_pending_files_locked(pending_deletes: &mut BTreeSet<String>, dir: &PathBuf) {
+        let still_pending: BTreeSet<String> = pending_deletes
+            .iter()
+            .filter(|name| !try_delete(&dir.join(name)))
+            .cloned()
+            .collect();
+        *pending_deletes = still_pending;
     }
 
     fn maybe_delete_pending_files(&self) -> Result<()> {
         let mut delete_set = self.pending_deletes.write()?;
         if !delete_set.is_empty() {
+            // Only retry once in a while, deleting a file which is still open
+            // is likely to fail again right away
             let count = self.ops_since_last_delete.fetch_add(1, Ordering::AcqRel);
             if count > delete_set.len() {
                 self.ops_since_last_delete
                     .fetch_sub(count, Ordering::Release);
-                Self::delete_pending_files(&mut delete_set, &self.directory)?;
+                Self::delete_pending_files_locked(&mut delete_set, &self.directory);
             }
         }
         Ok(())
@@ -108,24 +109,44 @@ impl<LF: LockFactory> FSDirectory<LF> {
     }
 }
 
-fn list_all<T: AsRef<Path>>(path: &T) -> Result<Vec<String>> {
+fn list_all<T: AsRef<Path>>(path: &T, pending_deletes: &BTreeSet<String>) -> Result<Vec<String>> {
     let mut result = Vec::new();
     for entry in fs::read_dir(path)? {
         let entry = entry?;
         if let Ok(filename) = entry.file_name().into_string() {
-            result.push(filename);
+            if !pending_deletes.contains(&filename) {
+                result.push(filename);
+            }
         }
     }
     Ok(result)
 }
 
+/// Deletes the file at `path`, a missing file counts as deleted. Returns false
+/// if the file couldn't be deleted, which happens for instance on Windows
+/// when the file is still open, so that the deletion can be retried later.
+fn try_delete(path: &Path) -> bool {
+    match fs::remove_file(path) {
+        Ok(()) => true,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => true,
+        Err(e) => {
+            warn!(
+                "failed to delete file {}: {:?}, will retry later",
+                path.display(),
+                e
+            );
+            false
+        }
+    }
+}
+
 impl<LF: LockFactory> Directory for FSDirectory<LF> {
     type LK = LF::LK;
     type IndexOutput = FSIndexOutput;
     type TempOutput = FSIndexOutput;
 
     fn list_all(&self) -> Result<Vec<String>> {
-        list_all(&self.directory)
+        list_all(&self.directory, &*self.pending_deletes.read()?)
     }
 
     fn file_length(&self, name: &str) -> Result<i64> {
@@ -200,10 +221,22 @@ impl<LF: LockFactory> Directory for FSDirectory<LF> {
             )))
         };
 
-        let mut deletes = BTreeSet::new();
-        deletes.insert(name.to_string());
-        Self::delete_pending_files(&mut deletes, &self.directory)?;
-        self.pending_deletes.write()?.remove(name);
+        let path = self.resolve(name);
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) => {
+                if e.kind() == io::ErrorKind::NotFound {
+                    return Err(e.into());
+                }
+                // the file is probably still open, retry later
+                warn!(
+                    "failed to delete file {}: {:?}, will retry later",
+                    path.display(),
+                    e
+                );
+                self.pending_deletes.write()?.insert(name.to_string());
+            }
+        }
 
         self.maybe_delete_pending_files()
     }
@@ -236,6 +269,17 @@ impl<LF: LockFactory> Directory for FSDirectory<LF> {
     fn resolve(&self, name: &str) -> PathBuf {
         self.directory.join(name)
     }
+
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        Ok(self.pending_deletes.read()?.iter().cloned().collect())
+    }
+
+    fn delete_pending_files(&self) -> Result<()> {
+        let mut delete_set = self.pending_deletes.write()?;
+        Self::delete_pending_files_locked(&mut delete_set, &self.directory);
+        self.ops_since_last_delete.store(0, Ordering::Release);
+        Ok(())
+    }
 }
 
 // unsafe impl<LF: LockFactory + Send> Send for FSDirectory<LF> {}
@@ -247,3 +291,33 @@ impl<LF: LockFactory> fmt::Display for FSDirectory<LF> {
         write!(f, "FSDirectory({})", self.directory.display())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::NativeFSLockFactory;
+
+    #[test]
+    fn test_pending_deletes() {
+        let path = ::std::env::temp_dir().join("rucene_fs_directory_pending_deletes");
+        let _ = fs::remove_dir_all(&path);
+        let dir = FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap();
+
+        // a directory can't be removed as a file, which simulates a file
+        // that can't be deleted while it is open
+        fs::create_dir(path.join("_0.cfs")).unwrap();
+        dir.delete_file("_0.cfs").unwrap();
+        assert!(dir.pending_deletions().unwrap().contains("_0.cfs"));
+        assert!(dir.list_all().unwrap().is_empty());
+        assert!(dir.file_length("_0.cfs").is_err());
+
+        dir.delete_pending_files().unwrap();
+        assert_eq!(dir.pending_deletions().unwrap().len(), 1);
+
+        fs::remove_dir(path.join("_0.cfs")).unwrap();
+        dir.delete_pending_files().unwrap();
+        assert!(dir.pending_deletions().unwrap().is_empty());
+
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -139,8 +139,9 @@ impl<O: IndexOutput, RL: RateLimiter + ?Sized> DataOutput for RateLimitIndexOutp
 impl<O: IndexOutput, RL: RateLimiter + ?Sized> io::Write for RateLimitIndexOutput<O, RL> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.bytes_since_last_pause += buf.len();
-        if let Err(_e) = self.check_rate() {
-            return Err(io::Error::from(io::ErrorKind::WouldBlock));
+        if let Err(e) = self.check_rate() {
+            // e.g. the merge was aborted while paused
+            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
         }
         self.delegate.write(buf)
     }
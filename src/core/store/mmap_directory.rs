@@ -17,13 +17,11 @@ use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, Weak};
 
-use memmap::Mmap;
-
 use core::store::fs_index_output::FSIndexOutput;
 use core::store::lock::LockFactory;
 use core::store::{Directory, FSDirectory, IOContext};
-use core::store::{IndexInput, MmapIndexInput, ReadOnlySource};
-use error::Result;
+use core::store::{IndexInput, MmapFile, MmapIndexInput};
+use error::{ErrorKind::IllegalArgument, Result};
 
 #[derive(Default, Clone, Debug)]
 pub struct CacheStat {
@@ -38,7 +36,7 @@ pub struct CacheStat {
 
 pub struct MmapCache {
     stat: CacheStat,
-    cache: HashMap<PathBuf, Weak<Mmap>>,
+    cache: HashMap<PathBuf, Weak<MmapFile>>,
     purge_weak_limit: usize,
 }
 
@@ -68,7 +66,12 @@ impl MmapCache {
         }
     }
 
-    fn get_mmap(&mut self, full_path: &PathBuf) -> Result<Option<Arc<Mmap>>> {
+    fn get_mmap(
+        &mut self,
+        full_path: &PathBuf,
+        max_chunk_size: u32,
+        preload: bool,
+    ) -> Result<Arc<MmapFile>> {
         // if we exceed this limit, then we go through the weak
         // and remove those that are obsolete.
         if self.cache.len() > self.purge_weak_limit {
@@ -79,34 +82,40 @@ impl MmapCache {
             HashMapEntry::Occupied(mut occupied) => {
                 if let Some(mmap) = occupied.get().upgrade() {
                     self.stat.hit += 1;
-                    Ok(Some(Arc::clone(&mmap)))
+                    Ok(mmap)
                 } else {
                     // The entry exists but the weak ref has been destroyed.
                     self.stat.miss_weak += 1;
-                    if let Some(mmap) = MmapIndexInput::mmap(&full_path, 0, 0)? {
-                        occupied.insert(Arc::downgrade(&mmap));
-                        Ok(Some(mmap))
-                    } else {
-                        Ok(None)
-                    }
+                    let mmap = Arc::new(MmapFile::map(&full_path, max_chunk_size, preload)?);
+                    occupied.insert(Arc::downgrade(&mmap));
+                    Ok(mmap)
                 }
             }
 
             HashMapEntry::Vacant(vacant) => {
                 self.stat.miss_empty += 1;
-                if let Some(mmap) = MmapIndexInput::mmap(&full_path, 0, 0)? {
-                    vacant.insert(Arc::downgrade(&mmap));
-                    Ok(Some(mmap))
-                } else {
-                    Ok(None)
-                }
+                let mmap = Arc::new(MmapFile::map(&full_path, max_chunk_size, preload)?);
+                vacant.insert(Arc::downgrade(&mmap));
+                Ok(mmap)
             }
         }
     }
+
+    /// Forgets the mapping of a file that is deleted or overwritten, the inputs
+    /// still open keep it mapped until they are dropped.
+    fn evict(&mut self, full_path: &Path) {
+        self.cache.remove(full_path);
+    }
 }
 
+/// A `Directory` that reads files through memory mapping.
+///
+/// Files larger than `max_chunk_size` are mapped in several chunks, the
+/// inputs opened on a file and their clones and slices share its mapping,
+/// which is unmapped once all of them are dropped.
 pub struct MmapDirectory<LF: LockFactory> {
     directory: FSDirectory<LF>,
+    max_chunk_size: u32,
     pub preload: bool,
     mmap_cache: Arc<Mutex<MmapCache>>,
 }
@@ -115,11 +124,17 @@ impl<LF: LockFactory> MmapDirectory<LF> {
     pub fn new<T: AsRef<Path>>(
         directory: &T,
         lock_factory: LF,
-        _max_chunk_size: u32,
+        max_chunk_size: u32,
     ) -> Result<MmapDirectory<LF>> {
+        if max_chunk_size == 0 {
+            bail!(IllegalArgument(
+                "max_chunk_size must be positive".to_owned()
+            ));
+        }
         let directory = FSDirectory::new(directory, lock_factory)?;
         Ok(MmapDirectory {
             directory,
+            max_chunk_size,
             preload: false,
             mmap_cache: Arc::new(Mutex::new(MmapCache::default())),
         })
@@ -140,18 +155,17 @@ impl<LF: LockFactory> Directory for MmapDirectory<LF> {
     }
 
     fn create_output(&self, name: &str, context: &IOContext) -> Result<Self::IndexOutput> {
+        self.mmap_cache.lock()?.evict(&self.directory.resolve(name));
         self.directory.create_output(name, context)
     }
 
     fn open_input(&self, name: &str, _ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
         let full_path = self.directory.resolve(name);
-        let mut mmap_cache = self.mmap_cache.lock()?;
-        let boxed = mmap_cache
-            .get_mmap(&full_path)?
-            .map(ReadOnlySource::from)
-            .map(MmapIndexInput::from)
-            .unwrap();
-        Ok(Box::new(boxed))
+        let mmap =
+            self.mmap_cache
+                .lock()?
+                .get_mmap(&full_path, self.max_chunk_size, self.preload)?;
+        Ok(Box::new(MmapIndexInput::from(mmap)))
     }
 
     fn obtain_lock(&self, name: &str) -> Result<Self::LK> {
@@ -168,6 +182,7 @@ impl<LF: LockFactory> Directory for MmapDirectory<LF> {
     }
 
     fn delete_file(&self, name: &str) -> Result<()> {
+        self.mmap_cache.lock()?.evict(&self.directory.resolve(name));
         self.directory.delete_file(name)
     }
 
@@ -181,12 +196,25 @@ impl<LF: LockFactory> Directory for MmapDirectory<LF> {
     }
 
     fn rename(&self, source: &str, dest: &str) -> Result<()> {
+        {
+            let mut mmap_cache = self.mmap_cache.lock()?;
+            mmap_cache.evict(&self.directory.resolve(source));
+            mmap_cache.evict(&self.directory.resolve(dest));
+        }
         self.directory.rename(source, dest)
     }
 
     fn resolve(&self, name: &str) -> PathBuf {
         self.directory.resolve(name)
     }
+
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        self.directory.pending_deletions()
+    }
+
+    fn delete_pending_files(&self) -> Result<()> {
+        self.directory.delete_pending_files()
+    }
 }
 
 // unsafe impl<LF: LockFactory> Send for MmapDirectory<LF> {}
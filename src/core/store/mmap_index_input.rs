@@ -12,137 +12,118 @@
 // limitations under the License.
 
 use core::store::{DataInput, IndexInput, RandomAccessInput};
-use error::ErrorKind::{IllegalArgument, IllegalState};
+use error::ErrorKind::IllegalArgument;
 use error::Result;
 use memmap::{Mmap, MmapOptions};
-use std::fmt::Debug;
+use std::cmp::min;
 use std::fs::File;
 use std::io::{self, Read};
 use std::path::Path;
-use std::slice;
+use std::ptr;
 use std::sync::Arc;
 
-pub struct ReadOnlySource {
-    map: Arc<Mmap>,
-    offset: u64,
+/// Default maximum size of a single mapping, larger files are split into
+/// chunks of this size.
+#[cfg(target_pointer_width = "64")]
+pub const DEFAULT_MAX_CHUNK_SIZE: u32 = 1 << 30;
+#[cfg(not(target_pointer_width = "64"))]
+pub const DEFAULT_MAX_CHUNK_SIZE: u32 = 1 << 28;
+
+const PAGE_SIZE: usize = 4096;
+
+/// A file mapped in chunks of `1 << chunk_size_power` bytes, only the last
+/// chunk may be shorter.
+///
+/// The chunks are unmapped when the `MmapFile` is dropped, the inputs over it
+/// hold an `Arc` so the file stays mapped as long as any of them is alive.
+pub struct MmapFile {
+    chunks: Vec<Mmap>,
+    chunk_size_power: u32,
     len: u64,
 }
 
-impl ReadOnlySource {
-    pub fn range(&self, offset: u64, len: u64) -> Result<ReadOnlySource> {
-        if self.len < offset + len {
-            bail!(IllegalArgument("Slice too big".to_owned()));
+impl MmapFile {
+    /// Maps the file at `path`, `max_chunk_size` is rounded down to a power
+    /// of 2. When `preload` is true every page is touched so the file is
+    /// loaded into physical memory.
+    pub fn map(path: &Path, max_chunk_size: u32, preload: bool) -> Result<MmapFile> {
+        if max_chunk_size == 0 {
+            bail!(IllegalArgument(
+                "max_chunk_size must be positive".to_owned()
+            ));
         }
-
-        let source = ReadOnlySource {
-            map: Arc::clone(&self.map),
-            offset: self.offset + offset,
+        let chunk_size_power = 31 - max_chunk_size.leading_zeros();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let chunk_size = 1u64 << chunk_size_power;
+        let num_chunks = ((len + chunk_size - 1) >> chunk_size_power) as usize;
+        let mut chunks = Vec::with_capacity(num_chunks);
+        for i in 0..num_chunks {
+            let offset = i as u64 * chunk_size;
+            let chunk_len = min(chunk_size, len - offset) as usize;
+            let chunk = unsafe {
+                MmapOptions::new()
+                    .offset(offset as usize)
+                    .len(chunk_len)
+                    .map(&file)?
+            };
+            if preload {
+                Self::load(&chunk);
+            }
+            chunks.push(chunk);
+        }
+        Ok(MmapFile {
+            chunks,
+            chunk_size_power,
             len,
-        };
-
-        Ok(source)
-    }
-
-    fn len(&self) -> u64 {
-        self.len
-    }
-
-    /// Returns the data underlying the ReadOnlySource object.
-    pub fn as_slice(&self) -> &[u8] {
-        let offset = self.offset as usize;
-        let stop = (self.offset + self.len) as usize;
-        unsafe { &slice::from_raw_parts(self.map.as_ptr(), self.map.len())[offset..stop] }
-    }
-
-    /// Splits into 2 `ReadOnlySource`, at the offset given
-    /// as an argument.
-    pub fn split(self, addr: u64) -> Result<(ReadOnlySource, ReadOnlySource)> {
-        let left = self.slice(0, addr)?;
-        let right = self.slice_from(addr)?;
-        Ok((left, right))
+        })
     }
 
-    /// Creates a ReadOnlySource that is just a
-    /// view over a slice of the data.
-    ///
-    /// Keep in mind that any living slice extends
-    /// the lifetime of the original ReadOnlySource,
-    ///
-    /// For instance, if `ReadOnlySource` wraps 500MB
-    /// worth of data in anonymous memory, and only a
-    /// 1KB slice is remaining, the whole `500MBs`
-    /// are retained in memory.
-    pub fn slice(&self, from_offset: u64, to_offset: u64) -> Result<ReadOnlySource> {
-        if from_offset > to_offset {
-            bail!(IllegalArgument(format!(
-                "from_offset must be <= to_offset, got: from_offset: {} > to_offset: {}",
-                from_offset, to_offset
-            )));
+    fn load(chunk: &Mmap) {
+        for i in (0..chunk.len()).step_by(PAGE_SIZE) {
+            // volatile so that the reads are not optimized away
+            unsafe { ptr::read_volatile(chunk.as_ptr().add(i)) };
         }
-        self.range(from_offset, to_offset - from_offset)
     }
 
-    /// Like `.slice(...)` but enforcing only the `from`
-    /// boundary.
-    ///
-    /// Equivalent to `.slice(from_offset, self.len())`
-    pub fn slice_from(&self, from_offset: u64) -> Result<ReadOnlySource> {
-        let len = self.len();
-        self.slice(from_offset, len)
+    pub fn len(&self) -> u64 {
+        self.len
     }
 
-    /// Like `.slice(...)` but enforcing only the `to`
-    /// boundary.
-    ///
-    /// Equivalent to `.slice(0, to_offset)`
-    pub fn slice_to(&self, to_offset: u64) -> Result<ReadOnlySource> {
-        self.slice(0, to_offset)
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
-}
 
-impl Clone for ReadOnlySource {
-    fn clone(&self) -> Self {
-        ReadOnlySource {
-            map: Arc::clone(&self.map),
-            offset: self.offset,
-            len: self.len,
-        }
+    #[inline]
+    fn byte(&self, pos: u64) -> u8 {
+        let mask = (1u64 << self.chunk_size_power) - 1;
+        self.chunks[(pos >> self.chunk_size_power) as usize][(pos & mask) as usize]
     }
-}
 
-impl From<Arc<Mmap>> for ReadOnlySource {
-    fn from(mmap: Arc<Mmap>) -> ReadOnlySource {
-        let len = mmap.len() as u64;
-        ReadOnlySource {
-            map: mmap,
-            offset: 0,
-            len,
-        }
+    /// The bytes from `pos` to the end of the chunk `pos` is in.
+    #[inline]
+    fn chunk_from(&self, pos: u64) -> &[u8] {
+        let mask = (1u64 << self.chunk_size_power) - 1;
+        &self.chunks[(pos >> self.chunk_size_power) as usize][(pos & mask) as usize..]
     }
 }
 
+/// An `IndexInput` over a `MmapFile`, clones and slices share the mapping.
 pub struct MmapIndexInput {
-    source: ReadOnlySource,
+    file: Arc<MmapFile>,
     start: u64,
     end: u64,
     position: u64,
-    slice: &'static [u8],
-    cursor: &'static [u8],
     description: String,
 }
 
-impl From<ReadOnlySource> for MmapIndexInput {
-    fn from(source: ReadOnlySource) -> Self {
-        let len = source.len();
-        let slice_ptr = source.as_slice().as_ptr();
-        let cursor = unsafe { slice::from_raw_parts(slice_ptr, len as usize) };
-        let slice = <&[u8]>::clone(&cursor);
+impl From<Arc<MmapFile>> for MmapIndexInput {
+    fn from(file: Arc<MmapFile>) -> Self {
+        let end = file.len();
         MmapIndexInput {
-            source,
-            slice,
-            cursor,
+            file,
             start: 0,
-            end: len,
+            end,
             position: 0,
             description: String::from(""),
         }
@@ -152,9 +133,7 @@ impl From<ReadOnlySource> for MmapIndexInput {
 impl Clone for MmapIndexInput {
     fn clone(&self) -> Self {
         MmapIndexInput {
-            slice: <&[u8]>::clone(&self.slice),
-            cursor: <&[u8]>::clone(&self.cursor),
-            source: self.source.clone(),
+            file: Arc::clone(&self.file),
             start: self.start,
             end: self.end,
             position: self.position,
@@ -164,41 +143,9 @@ impl Clone for MmapIndexInput {
 }
 
 impl MmapIndexInput {
-    pub fn new<P: AsRef<Path> + Debug>(name: P) -> Result<MmapIndexInput> {
-        let mmap = MmapIndexInput::mmap(name.as_ref(), 0, 0)?;
-        Ok(mmap
-            .map(ReadOnlySource::from)
-            .map(MmapIndexInput::from)
-            .ok_or_else(|| IllegalState(format!("Memmap empty file: {:?}", name)))?)
-    }
-
-    pub fn mmap(path: &Path, offset: usize, length: usize) -> Result<Option<Arc<Mmap>>> {
-        let file = File::open(path)?;
-        let meta_data = file.metadata()?;
-        let file_len = meta_data.len() as usize;
-        if file_len == 0 {
-            Ok(None)
-        } else if file_len < offset + length {
-            bail!(IllegalArgument(format!(
-                "Mapping end offset `{}` is beyond the size `{}` of file `{:?}`",
-                offset + length,
-                file_len,
-                path
-            )))
-        } else {
-            let adapted_len = if length == 0 {
-                file_len - offset
-            } else {
-                length
-            };
-            let mmap = unsafe {
-                MmapOptions::new()
-                    .offset(offset)
-                    .len(adapted_len)
-                    .map(&file)?
-            };
-            Ok(Some(Arc::new(mmap)))
-        }
+    pub fn new<P: AsRef<Path>>(name: P) -> Result<MmapIndexInput> {
+        let file = MmapFile::map(name.as_ref(), DEFAULT_MAX_CHUNK_SIZE, false)?;
+        Ok(MmapIndexInput::from(Arc::new(file)))
     }
 
     fn slice_impl(&self, description: &str, offset: i64, length: i64) -> Result<Self> {
@@ -210,15 +157,9 @@ impl MmapIndexInput {
             )));
         };
 
-        let slice_ptr = self.source.as_slice().as_ptr();
         let start = self.start + offset as u64;
-        let cursor =
-            unsafe { slice::from_raw_parts(slice_ptr.offset(start as isize), length as usize) };
-        let slice = <&[u8]>::clone(&cursor);
         Ok(MmapIndexInput {
-            slice,
-            cursor,
-            source: self.source.clone(),
+            file: Arc::clone(&self.file),
             start,
             end: start + length as u64,
             position: 0,
@@ -273,9 +214,19 @@ impl DataInput for MmapIndexInput {}
 
 impl Read for MmapIndexInput {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let mut slice = &self.cursor[self.position as usize..];
-        let count = slice.read(buf)?;
-        self.position += count as u64;
+        // fill as much of `buf` as possible, reads may span several chunks
+        let mut count = 0;
+        while count < buf.len() && self.position < self.len() {
+            let pos = self.start + self.position;
+            let chunk = self.file.chunk_from(pos);
+            let n = min(
+                min(chunk.len(), buf.len() - count),
+                (self.len() - self.position) as usize,
+            );
+            buf[count..count + n].copy_from_slice(&chunk[..n]);
+            count += n;
+            self.position += n as u64;
+        }
         Ok(count)
     }
 }
@@ -290,7 +241,7 @@ impl RandomAccessInput for MmapIndexInput {
             );
             bail!(IllegalArgument(msg));
         };
-        Ok(self.slice[pos as usize])
+        Ok(self.file.byte(self.start + pos as u64))
     }
 
     fn read_short(&self, pos: i64) -> Result<i16> {
@@ -368,4 +319,47 @@ mod tests {
 
         ::std::fs::remove_file(name).unwrap();
     }
+
+    #[test]
+    fn test_mmap_chunks() {
+        let name = "test_mmap_chunks.txt";
+        let path: PathBuf = Path::new(name).into();
+
+        let mut fsout = FSIndexOutput::new(&path).unwrap();
+        for i in 0..25 {
+            fsout.write_int(i * 1_000_003).unwrap();
+        }
+        fsout.flush().unwrap();
+
+        // 16 bytes chunks, so reads at odd offsets regularly span two chunks
+        let file = Arc::new(MmapFile::map(&path, 17, false).unwrap());
+        assert_eq!(file.chunks.len(), 7);
+        let input = MmapIndexInput::from(file);
+        let mut slice = input.slice("shifted", 2, 98).unwrap();
+        let mut bytes = vec![0u8; 98];
+        slice.read_bytes(&mut bytes, 0, 98).unwrap();
+        assert_eq!(&bytes[..6], &[0, 0, 0, 15, 66, 67]);
+        assert!(slice.read_byte().is_err());
+
+        let mut clone = IndexInput::clone(&input).unwrap();
+        clone.seek(12).unwrap();
+        for i in 3..25 {
+            assert_eq!(clone.read_int().unwrap(), i * 1_000_003);
+        }
+        let random_input = input.random_access_slice(1, 99).unwrap();
+        for i in 1..25 {
+            assert_eq!(
+                random_input.read_int(i * 4 - 1).unwrap(),
+                (i * 1_000_003) as i32
+            );
+        }
+        ::std::fs::remove_file(name).unwrap();
+
+        let fsout = FSIndexOutput::new(&path).unwrap();
+        drop(fsout);
+        let mut empty = MmapIndexInput::new(&path).unwrap();
+        assert_eq!(IndexInput::len(&empty), 0);
+        assert!(DataInput::read_byte(&mut empty).is_err());
+        ::std::fs::remove_file(name).unwrap();
+    }
 }
@@ -80,6 +80,14 @@ pub use self::ram_output::*;
 mod rate_limiter;
 pub use self::rate_limiter::*;
 
+mod snapshot_directory;
+pub use self::snapshot_directory::*;
+
+#[cfg(feature = "encryption")]
+mod encrypted_directory;
+#[cfg(feature = "encryption")]
+pub use self::encrypted_directory::*;
+
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct FlushInfo {
     num_docs: u32,
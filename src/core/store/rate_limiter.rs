@@ -13,8 +13,11 @@
 
 use error::Result;
 
-use std::sync::Arc;
-use std::time::{Duration, SystemTime};
+use core::util::Volatile;
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 /// Abstract base class to rate limit IO.  Typically implementations are
 /// shared across multiple IndexInputs or IndexOutputs (for example
@@ -59,35 +62,105 @@ impl RateLimiter for Arc<RateLimiter> {
 
 /// Simple class to rate limit IO.
 pub struct SimpleRateLimiter {
-    _mb_per_sec: f64,
-    _min_pause_check_bytes: u64,
-    _last_ns: SystemTime,
+    mb_per_sec: Volatile<f64>,
+    min_pause_check_bytes: Volatile<u64>,
+    last_time: Mutex<Instant>,
 }
 
+const MIN_PAUSE_CHECK_MSEC: f64 = 5.0;
+
 impl SimpleRateLimiter {
+    /// mb_per_sec is the MB/sec max IO rate
     pub fn new(mb_per_sec: f64) -> Self {
-        SimpleRateLimiter {
-            _mb_per_sec: mb_per_sec,
-            _min_pause_check_bytes: 0,
-            _last_ns: SystemTime::now(),
-        }
+        let limiter = SimpleRateLimiter {
+            mb_per_sec: Volatile::new(0.0),
+            min_pause_check_bytes: Volatile::new(0),
+            last_time: Mutex::new(Instant::now()),
+        };
+        limiter.set_mb_per_sec(mb_per_sec);
+        limiter
     }
 }
 
 impl RateLimiter for SimpleRateLimiter {
-    fn set_mb_per_sec(&self, _mb_per_sec: f64) {
-        unimplemented!()
+    fn set_mb_per_sec(&self, mb_per_sec: f64) {
+        self.mb_per_sec.write(mb_per_sec);
+        // NOTE: casting a float out of the u64 range is undefined, clamp
+        // f64::INFINITY to u64::max_value() by hand
+        let bytes = MIN_PAUSE_CHECK_MSEC / 1000.0 * mb_per_sec * 1024.0 * 1024.0;
+        let bytes = if bytes >= u64::max_value() as f64 {
+            u64::max_value()
+        } else {
+            bytes as u64
+        };
+        self.min_pause_check_bytes.write(bytes);
     }
 
     fn mb_per_sec(&self) -> f64 {
-        unimplemented!()
+        self.mb_per_sec.read()
     }
 
-    fn pause(&self, _bytes: u64) -> Result<Duration> {
-        unimplemented!()
+    /// Pauses, if necessary, to keep the instantaneous IO rate at or below
+    /// the target. Be sure to only call this method when bytes >
+    /// `min_pause_check_bytes`, otherwise it will pause way too long!
+    fn pause(&self, bytes: u64) -> Result<Duration> {
+        let start = Instant::now();
+        let seconds_to_pause = bytes as f64 / 1024.0 / 1024.0 / self.mb_per_sec.read();
+
+        // Time we should sleep until; this is purely instantaneous
+        // rate (just adds seconds onto the last time we had paused to);
+        // maybe we should also offer decayed recent history one?
+        let target_time = {
+            let mut last_time = self.last_time.lock()?;
+            let target_time = *last_time
+                + Duration::new(
+                    seconds_to_pause.trunc() as u64,
+                    (seconds_to_pause.fract() * 1e9) as u32,
+                );
+            if start >= target_time {
+                // OK, current time is already beyond the target sleep time,
+                // no pausing to do.
+
+                // Set to start, not target_time, to enforce the instant rate, not
+                // the "averaged over all history" rate:
+                *last_time = start;
+                return Ok(Duration::default());
+            }
+            *last_time = target_time;
+            target_time
+        };
+
+        let mut cur_time = start;
+        // While loop because sleep doesn't always sleep long enough:
+        while cur_time < target_time {
+            thread::sleep(target_time - cur_time);
+            cur_time = Instant::now();
+        }
+        Ok(cur_time - start)
     }
 
     fn min_pause_check_bytes(&self) -> u64 {
-        unimplemented!()
+        self.min_pause_check_bytes.read()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f64;
+
+    #[test]
+    fn test_simple_rate_limiter() {
+        let limiter = SimpleRateLimiter::new(10.0);
+        assert_eq!(limiter.min_pause_check_bytes(), 52_428);
+
+        // 1MB at 10MB/sec, the second write must wait for the first one
+        let start = Instant::now();
+        limiter.pause(1024 * 1024).unwrap();
+        limiter.pause(1024 * 1024).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        limiter.set_mb_per_sec(f64::INFINITY);
+        assert_eq!(limiter.min_pause_check_bytes(), u64::max_value());
     }
 }
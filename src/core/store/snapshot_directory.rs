@@ -0,0 +1,579 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use byteorder::{BigEndian, ByteOrder};
+
+use core::store::{
+    DataInput, Directory, FSIndexOutput, IOContext, IndexInput, NativeFSLock, RandomAccessInput,
+};
+use error::{
+    ErrorKind::{IllegalArgument, UnexpectedEOF, UnsupportedOperation},
+    Result,
+};
+
+/// The default size of the regions fetched from the blob store.
+pub const DEFAULT_SNAPSHOT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// The default number of bytes of fetched regions kept in memory.
+pub const DEFAULT_SNAPSHOT_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+/// A store of immutable blobs, e.g. a bucket of an object store, an index
+/// snapshot is archived to.
+///
+/// Only ranged reads are needed, so a snapshot can be searched without
+/// downloading its files first.
+pub trait BlobStore: Send + Sync + 'static {
+    /// Reads `buf.len()` bytes of `blob` starting at `offset`.
+    fn read_range(&self, blob: &str, offset: u64, buf: &mut [u8]) -> Result<()>;
+
+    /// Reads a whole blob, used to fetch the snapshot manifest.
+    fn read_all(&self, blob: &str) -> Result<Vec<u8>>;
+}
+
+/// A `BlobStore` over a local directory, each blob being a file, e.g. a
+/// mounted network file system.
+pub struct FSBlobStore {
+    root: PathBuf,
+}
+
+impl FSBlobStore {
+    pub fn new<T: AsRef<Path> + ?Sized>(root: &T) -> FSBlobStore {
+        FSBlobStore {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl BlobStore for FSBlobStore {
+    fn read_range(&self, blob: &str, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let mut file = File::open(self.root.join(blob))?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(buf)?;
+        Ok(())
+    }
+
+    fn read_all(&self, blob: &str) -> Result<Vec<u8>> {
+        let mut bytes = vec![];
+        File::open(self.root.join(blob))?.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// A file of an index snapshot and the blob holding its content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    pub name: String,
+    pub blob: String,
+    pub length: u64,
+}
+
+/// The list of the files of an index snapshot, stored as json along the
+/// blobs of the files.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub files: Vec<SnapshotFile>,
+}
+
+impl SnapshotManifest {
+    /// Builds the manifest of `files` of `directory`, whose blobs are named
+    /// `blob_prefix` followed by the file name. The files of the latest
+    /// commit are `SegmentInfos::read_latest_commit(&dir)?.files(true)`.
+    pub fn from_directory<D: Directory, I: IntoIterator<Item = String>>(
+        directory: &D,
+        files: I,
+        blob_prefix: &str,
+    ) -> Result<SnapshotManifest> {
+        let mut files = files
+            .into_iter()
+            .map(|name| {
+                let length = directory.file_length(&name)? as u64;
+                Ok(SnapshotFile {
+                    blob: format!("{}{}", blob_prefix, name),
+                    name,
+                    length,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(SnapshotManifest { files })
+    }
+
+    pub fn from_json(bytes: &[u8]) -> Result<SnapshotManifest> {
+        Ok(::serde_json::from_slice(bytes)?)
+    }
+
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(::serde_json::to_vec(self)?)
+    }
+}
+
+type BlockKey = (usize, u64);
+
+/// The fetched regions shared by all the inputs of a directory, the oldest
+/// fetched ones are evicted first once `capacity` bytes are cached.
+struct BlockCache {
+    blocks: HashMap<BlockKey, Arc<Vec<u8>>>,
+    order: VecDeque<BlockKey>,
+    size: usize,
+    capacity: usize,
+}
+
+impl BlockCache {
+    fn get(&self, key: &BlockKey) -> Option<Arc<Vec<u8>>> {
+        self.blocks.get(key).cloned()
+    }
+
+    fn put(&mut self, key: BlockKey, block: Arc<Vec<u8>>) {
+        if self.blocks.contains_key(&key) {
+            return;
+        }
+        self.size += block.len();
+        self.blocks.insert(key, block);
+        self.order.push_back(key);
+        while self.size > self.capacity && self.order.len() > 1 {
+            let oldest = self.order.pop_front().unwrap();
+            if let Some(evicted) = self.blocks.remove(&oldest) {
+                self.size -= evicted.len();
+            }
+        }
+    }
+}
+
+struct SnapshotSource<S: BlobStore> {
+    store: Arc<S>,
+    cache: Mutex<BlockCache>,
+    block_size: usize,
+}
+
+impl<S: BlobStore> SnapshotSource<S> {
+    /// Returns the block `block` of the file, fetching it if it isn't cached.
+    fn block(&self, id: usize, file: &SnapshotFile, block: u64) -> Result<Arc<Vec<u8>>> {
+        let key = (id, block);
+        if let Some(bytes) = self.cache.lock()?.get(&key) {
+            return Ok(bytes);
+        }
+        // the lock isn't held while fetching, a block fetched concurrently
+        // by two inputs is only cached once
+        let offset = block * self.block_size as u64;
+        let len = (file.length - offset).min(self.block_size as u64) as usize;
+        let mut bytes = vec![0u8; len];
+        self.store.read_range(&file.blob, offset, &mut bytes)?;
+        let bytes = Arc::new(bytes);
+        self.cache.lock()?.put(key, Arc::clone(&bytes));
+        Ok(bytes)
+    }
+}
+
+/// A read-only `Directory` over an index snapshot archived to a
+/// `BlobStore`, e.g. to search old indexes of a "frozen" tier.
+///
+/// The files are those of the snapshot manifest, their regions are only
+/// fetched once read, by blocks of `block_size` bytes cached in memory.
+/// Nothing is ever written: all the methods creating, deleting or renaming
+/// files fail, so the directory can only be searched, with
+/// `StandardDirectoryReader::open(Arc::new(directory))`.
+pub struct SnapshotDirectory<S: BlobStore> {
+    source: Arc<SnapshotSource<S>>,
+    files: BTreeMap<String, (usize, SnapshotFile)>,
+}
+
+impl<S: BlobStore> SnapshotDirectory<S> {
+    /// Opens the snapshot whose manifest is the blob `manifest_blob`.
+    pub fn open(store: Arc<S>, manifest_blob: &str) -> Result<SnapshotDirectory<S>> {
+        let manifest = SnapshotManifest::from_json(&store.read_all(manifest_blob)?)?;
+        Self::with_manifest(
+            store,
+            manifest,
+            DEFAULT_SNAPSHOT_BLOCK_SIZE,
+            DEFAULT_SNAPSHOT_CACHE_SIZE,
+        )
+    }
+
+    pub fn with_manifest(
+        store: Arc<S>,
+        manifest: SnapshotManifest,
+        block_size: usize,
+        cache_size: usize,
+    ) -> Result<SnapshotDirectory<S>> {
+        if block_size == 0 {
+            bail!(IllegalArgument("block_size must be positive".into()));
+        }
+        let mut files = BTreeMap::new();
+        for (id, file) in manifest.files.into_iter().enumerate() {
+            if files.contains_key(&file.name) {
+                bail!(IllegalArgument(format!(
+                    "duplicate file '{}' in snapshot manifest",
+                    file.name
+                )));
+            }
+            files.insert(file.name.clone(), (id, file));
+        }
+        let source = SnapshotSource {
+            store,
+            cache: Mutex::new(BlockCache {
+                blocks: HashMap::new(),
+                order: VecDeque::new(),
+                size: 0,
+                capacity: cache_size,
+            }),
+            block_size,
+        };
+        Ok(SnapshotDirectory {
+            source: Arc::new(source),
+            files,
+        })
+    }
+
+    /// The number of bytes of the fetched regions currently cached.
+    pub fn cached_bytes(&self) -> usize {
+        self.source.cache.lock().map(|c| c.size).unwrap_or(0)
+    }
+
+    fn file(&self, name: &str) -> Result<&(usize, SnapshotFile)> {
+        self.files.get(name).ok_or_else(|| {
+            IllegalArgument(format!("file '{}' is not in the snapshot", name)).into()
+        })
+    }
+
+    fn read_only<T>(&self, op: &str) -> Result<T> {
+        bail!(UnsupportedOperation(
+            format!("cannot {} in read-only snapshot directory", op).into()
+        ))
+    }
+}
+
+impl<S: BlobStore> Directory for SnapshotDirectory<S> {
+    type LK = NativeFSLock;
+    type IndexOutput = FSIndexOutput;
+    type TempOutput = FSIndexOutput;
+
+    fn list_all(&self) -> Result<Vec<String>> {
+        Ok(self.files.keys().cloned().collect())
+    }
+
+    fn file_length(&self, name: &str) -> Result<i64> {
+        Ok(self.file(name)?.1.length as i64)
+    }
+
+    fn create_output(&self, name: &str, _ctx: &IOContext) -> Result<Self::IndexOutput> {
+        self.read_only(&format!("create '{}'", name))
+    }
+
+    fn open_input(&self, name: &str, _ctx: &IOContext) -> Result<Box<dyn IndexInput>> {
+        let (id, file) = self.file(name)?;
+        Ok(Box::new(SnapshotIndexInput {
+            source: Arc::clone(&self.source),
+            file: Arc::new(file.clone()),
+            id: *id,
+            start: 0,
+            length: file.length,
+            pos: 0,
+            current: None,
+        }))
+    }
+
+    fn obtain_lock(&self, name: &str) -> Result<Self::LK> {
+        self.read_only(&format!("obtain lock '{}'", name))
+    }
+
+    fn create_temp_output(
+        &self,
+        prefix: &str,
+        _suffix: &str,
+        _ctx: &IOContext,
+    ) -> Result<Self::TempOutput> {
+        self.read_only(&format!("create temp file '{}'", prefix))
+    }
+
+    fn delete_file(&self, name: &str) -> Result<()> {
+        self.read_only(&format!("delete '{}'", name))
+    }
+
+    fn sync(&self, _name: &HashSet<String>) -> Result<()> {
+        self.read_only("sync files")
+    }
+
+    fn sync_meta_data(&self) -> Result<()> {
+        self.read_only("sync meta data")
+    }
+
+    fn rename(&self, source: &str, _dest: &str) -> Result<()> {
+        self.read_only(&format!("rename '{}'", source))
+    }
+}
+
+impl<S: BlobStore> fmt::Display for SnapshotDirectory<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SnapshotDirectory({} files)", self.files.len())
+    }
+}
+
+/// An input over a file of a `SnapshotDirectory`, or a slice of it.
+pub struct SnapshotIndexInput<S: BlobStore> {
+    source: Arc<SnapshotSource<S>>,
+    file: Arc<SnapshotFile>,
+    id: usize,
+    // the bounds of this slice in the file
+    start: u64,
+    length: u64,
+    pos: u64,
+    // the last block read sequentially, not to hit the cache for each read
+    current: Option<(u64, Arc<Vec<u8>>)>,
+}
+
+impl<S: BlobStore> SnapshotIndexInput<S> {
+    fn slice_impl(&self, offset: i64, length: i64) -> Result<SnapshotIndexInput<S>> {
+        if offset < 0 || length < 0 || (offset + length) as u64 > self.length {
+            bail!(IllegalArgument(format!(
+                "Illegal (offset, length) slice: ({}, {}) for file of length: {}",
+                offset, length, self.length
+            )));
+        }
+        Ok(SnapshotIndexInput {
+            source: Arc::clone(&self.source),
+            file: Arc::clone(&self.file),
+            id: self.id,
+            start: self.start + offset as u64,
+            length: length as u64,
+            pos: 0,
+            current: self.current.clone(),
+        })
+    }
+
+    /// Copies the bytes at `pos` of this slice to `buf`, returns the number
+    /// of copied bytes which is less than `buf.len()` at the end of the slice.
+    fn read_at(
+        &self,
+        current: &mut Option<(u64, Arc<Vec<u8>>)>,
+        pos: u64,
+        buf: &mut [u8],
+    ) -> Result<usize> {
+        let end = self.length.min(pos + buf.len() as u64);
+        let block_size = self.source.block_size as u64;
+        let mut copied = 0;
+        let mut pos = pos;
+        while pos < end {
+            let file_pos = self.start + pos;
+            let block = file_pos / block_size;
+            let bytes = match current {
+                Some((id, ref bytes)) if *id == block => Arc::clone(bytes),
+                _ => {
+                    let bytes = self.source.block(self.id, &self.file, block)?;
+                    *current = Some((block, Arc::clone(&bytes)));
+                    bytes
+                }
+            };
+            let from = (file_pos - block * block_size) as usize;
+            let len = (bytes.len() - from).min((end - pos) as usize);
+            buf[copied..copied + len].copy_from_slice(&bytes[from..from + len]);
+            copied += len;
+            pos += len as u64;
+        }
+        Ok(copied)
+    }
+
+    fn read_exact_at(&self, pos: i64, buf: &mut [u8]) -> Result<()> {
+        if pos < 0 || pos as u64 + buf.len() as u64 > self.length {
+            bail!(IllegalArgument(format!(
+                "invalid position, expecting 0 < pos < {}, got: {}",
+                self.length, pos
+            )));
+        }
+        let mut current = None;
+        self.read_at(&mut current, pos as u64, buf)?;
+        Ok(())
+    }
+}
+
+impl<S: BlobStore> IndexInput for SnapshotIndexInput<S> {
+    fn clone(&self) -> Result<Box<dyn IndexInput>> {
+        let mut input = self.slice_impl(0, self.length as i64)?;
+        input.pos = self.pos;
+        Ok(Box::new(input))
+    }
+
+    fn file_pointer(&self) -> i64 {
+        self.pos as i64
+    }
+
+    fn seek(&mut self, pos: i64) -> Result<()> {
+        if pos < 0 || pos as u64 > self.length {
+            bail!(IllegalArgument(format!(
+                "invalid position, expecting 0 < pos < {}, got: {}",
+                self.length, pos
+            )));
+        }
+        self.pos = pos as u64;
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.length
+    }
+
+    fn name(&self) -> &str {
+        &self.file.name
+    }
+
+    fn random_access_slice(&self, offset: i64, length: i64) -> Result<Box<dyn RandomAccessInput>> {
+        Ok(Box::new(self.slice_impl(offset, length)?))
+    }
+
+    fn slice(&self, _description: &str, offset: i64, length: i64) -> Result<Box<dyn IndexInput>> {
+        Ok(Box::new(self.slice_impl(offset, length)?))
+    }
+}
+
+impl<S: BlobStore> DataInput for SnapshotIndexInput<S> {
+    fn read_bytes(&mut self, b: &mut [u8], offset: usize, length: usize) -> Result<()> {
+        if self.pos + length as u64 > self.length {
+            bail!(UnexpectedEOF(format!(
+                "read past EOF of '{}': {} + {} > {}",
+                self.file.name, self.pos, length, self.length
+            )));
+        }
+        let mut current = self.current.take();
+        let res = self.read_at(&mut current, self.pos, &mut b[offset..offset + length]);
+        self.current = current;
+        self.pos += res? as u64;
+        Ok(())
+    }
+}
+
+impl<S: BlobStore> Read for SnapshotIndexInput<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut current = self.current.take();
+        let res = self.read_at(&mut current, self.pos, buf);
+        self.current = current;
+        let count = res.map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<S: BlobStore> RandomAccessInput for SnapshotIndexInput<S> {
+    fn read_byte(&self, pos: i64) -> Result<u8> {
+        let mut buf = [0u8; 1];
+        self.read_exact_at(pos, &mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_short(&self, pos: i64) -> Result<i16> {
+        let mut buf = [0u8; 2];
+        self.read_exact_at(pos, &mut buf)?;
+        Ok(BigEndian::read_i16(&buf))
+    }
+
+    fn read_int(&self, pos: i64) -> Result<i32> {
+        let mut buf = [0u8; 4];
+        self.read_exact_at(pos, &mut buf)?;
+        Ok(BigEndian::read_i32(&buf))
+    }
+
+    fn read_long(&self, pos: i64) -> Result<i64> {
+        let mut buf = [0u8; 8];
+        self.read_exact_at(pos, &mut buf)?;
+        Ok(BigEndian::read_i64(&buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    struct MemoryBlobStore {
+        blobs: HashMap<String, Vec<u8>>,
+        fetches: AtomicUsize,
+    }
+
+    impl BlobStore for MemoryBlobStore {
+        fn read_range(&self, blob: &str, offset: u64, buf: &mut [u8]) -> Result<()> {
+            self.fetches.fetch_add(1, Ordering::Relaxed);
+            let bytes = &self.blobs[blob];
+            let offset = offset as usize;
+            buf.copy_from_slice(&bytes[offset..offset + buf.len()]);
+            Ok(())
+        }
+
+        fn read_all(&self, blob: &str) -> Result<Vec<u8>> {
+            Ok(self.blobs[blob].clone())
+        }
+    }
+
+    #[test]
+    fn test_snapshot_directory() {
+        let data: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let manifest = SnapshotManifest {
+            files: vec![SnapshotFile {
+                name: "_0.cfs".into(),
+                blob: "snap/_0.cfs".into(),
+                length: data.len() as u64,
+            }],
+        };
+        let mut blobs = HashMap::new();
+        blobs.insert("snap/_0.cfs".to_string(), data.clone());
+        blobs.insert("snap/manifest".to_string(), manifest.to_json().unwrap());
+        let store = Arc::new(MemoryBlobStore {
+            blobs,
+            fetches: AtomicUsize::new(0),
+        });
+
+        let dir = SnapshotDirectory::open(Arc::clone(&store), "snap/manifest").unwrap();
+        assert_eq!(dir.list_all().unwrap(), vec!["_0.cfs".to_string()]);
+        assert_eq!(dir.file_length("_0.cfs").unwrap(), 1000);
+        // nothing is fetched until read
+        let mut input = dir.open_input("_0.cfs", &IOContext::READ).unwrap();
+        assert_eq!(store.fetches.load(Ordering::Relaxed), 0);
+
+        input.seek(990).unwrap();
+        let mut buf = vec![0u8; 10];
+        input.read_bytes(&mut buf, 0, 10).unwrap();
+        assert_eq!(&buf[..], &data[990..]);
+        assert_eq!(store.fetches.load(Ordering::Relaxed), 1);
+
+        // the region was cached
+        let slice = input.random_access_slice(995, 5).unwrap();
+        assert_eq!(slice.read_byte(4).unwrap(), data[999]);
+        assert_eq!(store.fetches.load(Ordering::Relaxed), 1);
+
+        let dir = SnapshotDirectory::with_manifest(
+            Arc::clone(&store),
+            manifest,
+            16,
+            DEFAULT_SNAPSHOT_CACHE_SIZE,
+        )
+        .unwrap();
+        let input = dir.open_input("_0.cfs", &IOContext::READ).unwrap();
+        let mut slice = input.slice("slice", 10, 100).unwrap();
+        let mut buf = vec![0u8; 100];
+        slice.read_bytes(&mut buf, 0, 100).unwrap();
+        assert_eq!(&buf[..], &data[10..110]);
+        assert!(slice.read_byte().is_err());
+        let slice = input.random_access_slice(30, 8).unwrap();
+        let mut expected = [0u8; 8];
+        expected.copy_from_slice(&data[30..38]);
+        assert_eq!(slice.read_long(0).unwrap(), BigEndian::read_i64(&expected));
+
+        assert!(dir.delete_file("_0.cfs").is_err());
+        assert!(dir.create_output("_1.cfs", &IOContext::Default).is_err());
+        assert!(dir.open_input("_1.cfs", &IOContext::READ).is_err());
+    }
+}
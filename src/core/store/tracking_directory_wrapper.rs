@@ -107,6 +107,14 @@ where
     fn create_files(&self) -> HashSet<String> {
         self.create_file_names.lock().unwrap().clone()
     }
+
+    fn pending_deletions(&self) -> Result<HashSet<String>> {
+        self.directory.pending_deletions()
+    }
+
+    fn delete_pending_files(&self) -> Result<()> {
+        self.directory.delete_pending_files()
+    }
 }
 
 impl<D, T> fmt::Display for TrackingDirectoryWrapper<D, T>
@@ -0,0 +1,622 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::{analyze, AnalyzedToken, Analyzer};
+use core::codec::Codec;
+use core::doc::{
+    BinaryDocValuesField, Field, FieldType, NumericDocValuesField, Word, WordTokenStream,
+};
+use core::index::index_writer_config::IndexWriterConfig;
+use core::index::merge_policy::MergePolicy;
+use core::index::merge_scheduler::MergeScheduler;
+use core::index::{
+    Fieldable, IndexOptions, IndexReader, IndexWriter, LeafReaderContext, NumericDocValuesRef,
+    StandardDirectoryReader, Term,
+};
+use core::search::boolean_query::BooleanQuery;
+use core::search::collector::{
+    Collector, CollectorManager, ParallelLeafCollector, SearchCollector,
+};
+use core::search::searcher::{DefaultIndexSearcher, IndexSearcher};
+use core::search::term_query::TermQuery;
+use core::search::{Query, Scorer};
+use core::store::Directory;
+use core::suggest::{InputEntry, LookupResult};
+use core::util::{DocId, VariantValue};
+
+use error::{ErrorKind::IllegalState, Result};
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+use std::sync::{Arc, RwLock};
+
+/// Field name used for the indexed text.
+pub const TEXT_FIELD_NAME: &str = "text";
+/// Field name used for the indexed text, as a prefix n-grams of each token.
+pub const TEXT_GRAMS_FIELD_NAME: &str = "textgrams";
+/// Field name used for the stored, un-analyzed text.
+pub const EXACT_TEXT_FIELD_NAME: &str = "exacttext";
+/// Field name used for the numeric doc values holding the suggestion weight.
+pub const WEIGHT_FIELD_NAME: &str = "weight";
+/// Field name used for the binary doc values holding the suggestion payload.
+pub const PAYLOAD_FIELD_NAME: &str = "payload";
+
+/// Default minimum number of leading characters before a prefix query is used.
+pub const DEFAULT_MIN_PREFIX_CHARS: usize = 1;
+
+const HIGHLIGHT_PRE_TAG: &str = "<b>";
+const HIGHLIGHT_POST_TAG: &str = "</b>";
+
+/// Analyzes the input text and then suggests matches based on prefix matches
+/// to any tokens in the indexed text. This also highlights the tokens that match.
+///
+/// The suggestions are kept in an auxiliary index held by the suggester. Each
+/// suggestion is analyzed with the given `Analyzer`, and every token is also
+/// indexed as edge n-grams into `TEXT_GRAMS_FIELD_NAME`, so that the last token
+/// of the lookup key can match as a prefix. All other tokens of the key must
+/// match whole tokens. Matches are sorted by their weight (highest first).
+///
+/// Call `refresh` after `add` or `update` to make the new suggestions visible
+/// to `lookup`.
+pub struct AnalyzingInfixSuggester<D, C, MS, MP, A>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+    A: Analyzer,
+{
+    writer: IndexWriter<D, C, MS, MP>,
+    analyzer: A,
+    min_prefix_chars: usize,
+    reader: RwLock<Option<Arc<StandardDirectoryReader<D, C, MS, MP>>>>,
+}
+
+impl<D, C, MS, MP, A> AnalyzingInfixSuggester<D, C, MS, MP, A>
+where
+    D: Directory + Send + Sync + 'static,
+    C: Codec,
+    MS: MergeScheduler,
+    MP: MergePolicy,
+    A: Analyzer,
+{
+    /// Creates a new suggester storing its auxiliary index in `dir`.
+    ///
+    /// `min_prefix_chars` is the minimum number of leading characters of the last
+    /// lookup token before it's matched as a prefix; shorter tokens must match a
+    /// whole indexed token.
+    pub fn new(
+        dir: Arc<D>,
+        config: Arc<IndexWriterConfig<C, MS, MP>>,
+        analyzer: A,
+        min_prefix_chars: usize,
+    ) -> Result<Self> {
+        let writer = IndexWriter::new(dir, config)?;
+        let suggester = AnalyzingInfixSuggester {
+            writer,
+            analyzer,
+            min_prefix_chars: min_prefix_chars.max(1),
+            reader: RwLock::new(None),
+        };
+        if suggester.writer.num_docs() > 0 {
+            suggester.refresh()?;
+        }
+        Ok(suggester)
+    }
+
+    /// Removes all the previous suggestions and builds the suggester from
+    /// the given entries.
+    pub fn build<I: IntoIterator<Item = InputEntry>>(&self, entries: I) -> Result<()> {
+        self.writer.delete_all()?;
+        for entry in entries {
+            self.add(
+                &entry.text,
+                entry.weight,
+                entry.payload.as_ref().map(|p| p.as_slice()),
+            )?;
+        }
+        self.writer.commit()?;
+        self.refresh()
+    }
+
+    /// Adds a new suggestion. Be sure to use `update` instead if you want to
+    /// replace a previous suggestion.
+    pub fn add(&self, text: &str, weight: i64, payload: Option<&[u8]>) -> Result<()> {
+        let doc = self.build_document(text, weight, payload)?;
+        self.writer.add_document(doc)?;
+        Ok(())
+    }
+
+    /// Updates a previous suggestion, matching the exact same text as before.
+    /// Use this to change the weight or payload of an already added suggestion.
+    /// If the suggestion does not exist, it's added.
+    pub fn update(&self, text: &str, weight: i64, payload: Option<&[u8]>) -> Result<()> {
+        let doc = self.build_document(text, weight, payload)?;
+        let term = Term::new(EXACT_TEXT_FIELD_NAME.to_string(), text.as_bytes().to_vec());
+        self.writer.update_document(doc, Some(term))?;
+        Ok(())
+    }
+
+    /// Reopens the underlying searcher; it's best to call this rarely, as it
+    /// may be costly.
+    pub fn refresh(&self) -> Result<()> {
+        let reader = self.writer.get_reader(true, false)?;
+        *self.reader.write()? = Some(Arc::new(reader));
+        Ok(())
+    }
+
+    /// Commits all the pending changes to the auxiliary index.
+    pub fn commit(&self) -> Result<()> {
+        self.writer.commit()?;
+        Ok(())
+    }
+
+    /// Commits the pending changes and closes the auxiliary index.
+    pub fn close(&self) -> Result<()> {
+        self.writer.close()
+    }
+
+    /// Returns the number of suggestions visible to `lookup`.
+    pub fn count(&self) -> Result<i32> {
+        Ok(match *self.reader.read()? {
+            Some(ref reader) => reader.num_docs(),
+            None => 0,
+        })
+    }
+
+    /// Looks up the suggestions matching `key`, sorted by weight.
+    ///
+    /// If `all_terms_required` is true, every token of the key must match,
+    /// otherwise at least one of them must. If `do_highlight` is true, the
+    /// matched fragments of each suggestion are wrapped with `<b>` and `</b>`
+    /// in `LookupResult::highlight_key`.
+    pub fn lookup(
+        &self,
+        key: &str,
+        num: usize,
+        all_terms_required: bool,
+        do_highlight: bool,
+    ) -> Result<Vec<LookupResult>> {
+        let reader = match *self.reader.read()? {
+            Some(ref reader) => Arc::clone(reader),
+            None => bail!(IllegalState("suggester was not built or refreshed".into())),
+        };
+
+        let tokens = analyze(&self.analyzer, TEXT_FIELD_NAME, key)?;
+        if tokens.is_empty() || num == 0 {
+            return Ok(vec![]);
+        }
+
+        // the last token is a prefix, unless the key ends with a separator
+        let last_token_is_prefix = tokens[tokens.len() - 1].end_offset == key.len();
+        let mut matched_terms = HashSet::new();
+        let mut prefix = None;
+        let mut clauses: Vec<Box<dyn Query<C>>> = Vec::with_capacity(tokens.len());
+        for (i, token) in tokens.iter().enumerate() {
+            let term = if i == tokens.len() - 1
+                && last_token_is_prefix
+                && token.term.chars().count() >= self.min_prefix_chars
+            {
+                prefix = Some(token.term.clone());
+                Term::new(
+                    TEXT_GRAMS_FIELD_NAME.to_string(),
+                    token.term.as_bytes().to_vec(),
+                )
+            } else {
+                matched_terms.insert(token.term.clone());
+                Term::new(TEXT_FIELD_NAME.to_string(), token.term.as_bytes().to_vec())
+            };
+            clauses.push(Box::new(TermQuery::new(term, 1.0, None)));
+        }
+        let query = if all_terms_required {
+            BooleanQuery::build(clauses, vec![], vec![])?
+        } else {
+            BooleanQuery::build(vec![], clauses, vec![])?
+        };
+
+        let searcher = DefaultIndexSearcher::new(Arc::clone(&reader));
+        let mut collector = TopWeightCollector::new(num);
+        searcher.search(query.as_ref(), &mut collector)?;
+
+        let fields = [EXACT_TEXT_FIELD_NAME.to_string()];
+        let mut results = Vec::with_capacity(collector.pq.len());
+        for hit in collector.top_hits() {
+            let document = reader.document(hit.doc, &fields)?;
+            let text = document
+                .fields
+                .iter()
+                .find(|f| f.field.name() == EXACT_TEXT_FIELD_NAME)
+                .and_then(|f| f.field.string_value())
+                .unwrap_or("")
+                .to_string();
+            let highlight_key = if do_highlight {
+                Some(self.highlight(&text, &matched_terms, prefix.as_ref())?)
+            } else {
+                None
+            };
+            let payload = Self::payload(reader.as_ref(), hit.doc)?;
+            results.push(LookupResult {
+                key: text,
+                highlight_key,
+                value: hit.weight,
+                payload,
+            });
+        }
+        Ok(results)
+    }
+
+    fn payload(
+        reader: &StandardDirectoryReader<D, C, MS, MP>,
+        doc: DocId,
+    ) -> Result<Option<Vec<u8>>> {
+        let leaf = reader.leaf_reader_for_doc(doc);
+        if leaf.reader.field_info(PAYLOAD_FIELD_NAME).is_none() {
+            return Ok(None);
+        }
+        let payload = leaf
+            .reader
+            .get_binary_doc_values(PAYLOAD_FIELD_NAME)?
+            .get(doc - leaf.doc_base)?;
+        Ok(if payload.is_empty() {
+            None
+        } else {
+            Some(payload)
+        })
+    }
+
+    /// Wraps the tokens of `text` that matched the lookup key with highlight tags.
+    fn highlight(
+        &self,
+        text: &str,
+        matched_terms: &HashSet<String>,
+        prefix: Option<&String>,
+    ) -> Result<String> {
+        let tokens = analyze(&self.analyzer, TEXT_FIELD_NAME, text)?;
+        let mut highlighted = String::with_capacity(text.len() + 16);
+        let mut up_to = 0;
+        for token in &tokens {
+            if token.start_offset < up_to || token.end_offset > text.len() {
+                continue;
+            }
+            highlighted.push_str(&text[up_to..token.start_offset]);
+            let token_text = &text[token.start_offset..token.end_offset];
+            if matched_terms.contains(&token.term) {
+                highlighted.push_str(HIGHLIGHT_PRE_TAG);
+                highlighted.push_str(token_text);
+                highlighted.push_str(HIGHLIGHT_POST_TAG);
+            } else if let Some(prefix) = prefix.filter(|p| token.term.starts_with(p.as_str())) {
+                let prefix_len = token_text
+                    .char_indices()
+                    .nth(prefix.chars().count())
+                    .map_or(token_text.len(), |(i, _)| i);
+                highlighted.push_str(HIGHLIGHT_PRE_TAG);
+                highlighted.push_str(&token_text[..prefix_len]);
+                highlighted.push_str(HIGHLIGHT_POST_TAG);
+                highlighted.push_str(&token_text[prefix_len..]);
+            } else {
+                highlighted.push_str(token_text);
+            }
+            up_to = token.end_offset;
+        }
+        highlighted.push_str(&text[up_to..]);
+        Ok(highlighted)
+    }
+
+    fn build_document(
+        &self,
+        text: &str,
+        weight: i64,
+        payload: Option<&[u8]>,
+    ) -> Result<Vec<Box<dyn Fieldable>>> {
+        let tokens = analyze(&self.analyzer, TEXT_FIELD_NAME, text)?;
+        let grams = self.edge_grams(&tokens);
+
+        let mut doc: Vec<Box<dyn Fieldable>> = Vec::with_capacity(5);
+        doc.push(Box::new(Field::new(
+            TEXT_FIELD_NAME.to_string(),
+            Self::indexed_field_type(IndexOptions::DocsAndFreqsAndPositionsAndOffsets),
+            Some(VariantValue::VString(text.to_string())),
+            Some(self.analyzer.token_stream(TEXT_FIELD_NAME, text)?),
+        )));
+        doc.push(Box::new(Field::new(
+            TEXT_GRAMS_FIELD_NAME.to_string(),
+            Self::indexed_field_type(IndexOptions::Docs),
+            Some(VariantValue::VString(text.to_string())),
+            Some(Box::new(WordTokenStream::new(grams))),
+        )));
+        let mut exact_field_type = Self::indexed_field_type(IndexOptions::Docs);
+        exact_field_type.stored = true;
+        exact_field_type.tokenized = false;
+        doc.push(Box::new(Field::new(
+            EXACT_TEXT_FIELD_NAME.to_string(),
+            exact_field_type,
+            Some(VariantValue::VString(text.to_string())),
+            None,
+        )));
+        doc.push(Box::new(NumericDocValuesField::new(
+            WEIGHT_FIELD_NAME,
+            weight,
+        )));
+        if let Some(payload) = payload {
+            doc.push(Box::new(BinaryDocValuesField::new(
+                PAYLOAD_FIELD_NAME,
+                payload,
+            )));
+        }
+        Ok(doc)
+    }
+
+    /// Builds the prefixes of every token that are at least `min_prefix_chars`
+    /// characters long, the token itself included.
+    fn edge_grams(&self, tokens: &[AnalyzedToken]) -> Vec<Word> {
+        let mut grams = vec![];
+        for token in tokens {
+            let length = token.end_offset - token.start_offset;
+            let mut num_chars = 0;
+            for (i, c) in token.term.char_indices() {
+                num_chars += 1;
+                if num_chars >= self.min_prefix_chars {
+                    let end = i + c.len_utf8();
+                    grams.push(Word::new(&token.term[..end], token.start_offset, length));
+                }
+            }
+        }
+        grams
+    }
+
+    fn indexed_field_type(index_options: IndexOptions) -> FieldType {
+        let mut field_type = FieldType::default();
+        field_type.index_options = index_options;
+        field_type.omit_norms = true;
+        field_type
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct WeightedDoc {
+    weight: i64,
+    doc: DocId,
+}
+
+impl Ord for WeightedDoc {
+    // the "greater" doc is the less competitive one, so that the top of the
+    // `BinaryHeap` is the first hit to evict
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .weight
+            .cmp(&self.weight)
+            .then_with(|| self.doc.cmp(&other.doc))
+    }
+}
+
+impl PartialOrd for WeightedDoc {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn insert_weighted_doc(pq: &mut BinaryHeap<WeightedDoc>, size: usize, hit: WeightedDoc) {
+    if pq.len() < size {
+        pq.push(hit);
+    } else if let Some(mut top) = pq.peek_mut() {
+        if hit < *top {
+            *top = hit;
+        }
+    }
+}
+
+/// Collects the top hits sorted by the value of the weight doc values.
+struct TopWeightCollector {
+    num: usize,
+    pq: BinaryHeap<WeightedDoc>,
+    doc_base: DocId,
+    weights: Option<NumericDocValuesRef>,
+}
+
+impl TopWeightCollector {
+    fn new(num: usize) -> TopWeightCollector {
+        TopWeightCollector {
+            num,
+            pq: BinaryHeap::with_capacity(num),
+            doc_base: 0,
+            weights: None,
+        }
+    }
+
+    fn top_hits(&mut self) -> Vec<WeightedDoc> {
+        ::std::mem::replace(&mut self.pq, BinaryHeap::new()).into_sorted_vec()
+    }
+
+    fn weights<C: Codec>(reader: &LeafReaderContext<'_, C>) -> Result<Option<NumericDocValuesRef>> {
+        if reader.reader.field_info(WEIGHT_FIELD_NAME).is_some() {
+            Ok(Some(
+                reader.reader.get_numeric_doc_values(WEIGHT_FIELD_NAME)?,
+            ))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl SearchCollector for TopWeightCollector {
+    fn set_next_reader<C: Codec>(&mut self, reader: &LeafReaderContext<'_, C>) -> Result<()> {
+        self.doc_base = reader.doc_base;
+        self.weights = Self::weights(reader)?;
+        Ok(())
+    }
+
+    fn support_parallel(&self) -> bool {
+        true
+    }
+}
+
+impl CollectorManager for TopWeightCollector {
+    type Collector = TopWeightLeafCollector;
+
+    fn new_collector<C: Codec>(
+        &mut self,
+        reader: &LeafReaderContext<'_, C>,
+    ) -> Result<TopWeightLeafCollector> {
+        Ok(TopWeightLeafCollector {
+            num: self.num,
+            pq: BinaryHeap::with_capacity(self.num),
+            doc_base: reader.doc_base,
+            weights: Self::weights(reader)?,
+        })
+    }
+
+    fn reduce(&mut self, collectors: Vec<TopWeightLeafCollector>) -> Result<()> {
+        for collector in collectors {
+            for hit in collector.pq.into_vec() {
+                insert_weighted_doc(&mut self.pq, self.num, hit);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Collector for TopWeightCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        let weight = match self.weights {
+            Some(ref weights) => weights.get(doc)?,
+            None => 0,
+        };
+        let hit = WeightedDoc {
+            weight,
+            doc: doc + self.doc_base,
+        };
+        insert_weighted_doc(&mut self.pq, self.num, hit);
+        Ok(())
+    }
+}
+
+struct TopWeightLeafCollector {
+    num: usize,
+    pq: BinaryHeap<WeightedDoc>,
+    doc_base: DocId,
+    weights: Option<NumericDocValuesRef>,
+}
+
+impl Collector for TopWeightLeafCollector {
+    fn needs_scores(&self) -> bool {
+        false
+    }
+
+    fn collect<S: Scorer + ?Sized>(&mut self, doc: DocId, _scorer: &mut S) -> Result<()> {
+        let weight = match self.weights {
+            Some(ref weights) => weights.get(doc)?,
+            None => 0,
+        };
+        let hit = WeightedDoc {
+            weight,
+            doc: doc + self.doc_base,
+        };
+        insert_weighted_doc(&mut self.pq, self.num, hit);
+        Ok(())
+    }
+}
+
+impl ParallelLeafCollector for TopWeightLeafCollector {
+    fn finish_leaf(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::SimpleAnalyzer;
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+
+    #[test]
+    fn test_analyzing_infix_suggester() {
+        let path = ::std::env::temp_dir().join("rucene_analyzing_infix_suggester");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let suggester = AnalyzingInfixSuggester::new(
+            dir,
+            Arc::new(IndexWriterConfig::default()),
+            SimpleAnalyzer::default(),
+            DEFAULT_MIN_PREFIX_CHARS,
+        )
+        .unwrap();
+        assert!(suggester.lookup("lend", 10, true, false).is_err());
+
+        suggester
+            .build(vec![
+                InputEntry::with_payload("lend me your ear", 8, b"foobar".to_vec()),
+                InputEntry::with_payload("a penny saved is a penny earned", 10, b"foobaz".to_vec()),
+                InputEntry::new("earnest money", 3),
+            ])
+            .unwrap();
+        assert_eq!(suggester.count().unwrap(), 3);
+
+        // the last token matches as a prefix, sorted by weight
+        let results = suggester.lookup("ear", 10, true, true).unwrap();
+        let keys: Vec<&str> = results.iter().map(|r| r.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec![
+                "a penny saved is a penny earned",
+                "lend me your ear",
+                "earnest money"
+            ]
+        );
+        assert_eq!(results[0].value, 10);
+        assert_eq!(results[0].payload, Some(b"foobaz".to_vec()));
+        assert_eq!(
+            results[0].highlight_key.as_ref().unwrap(),
+            "a penny saved is a penny <b>ear</b>ned"
+        );
+        assert_eq!(results[2].payload, None);
+
+        // the other tokens must match whole tokens
+        let results = suggester.lookup("pen ear", 10, true, false).unwrap();
+        assert!(results.is_empty());
+        let results = suggester.lookup("penny ear", 10, true, true).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].highlight_key.as_ref().unwrap(),
+            "a <b>penny</b> saved is a <b>penny</b> <b>ear</b>ned"
+        );
+        let results = suggester.lookup("money lend", 10, false, false).unwrap();
+        assert_eq!(results.len(), 2);
+        let results = suggester.lookup("ear", 1, true, false).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].value, 10);
+
+        suggester.update("earnest money", 20, None).unwrap();
+        suggester.refresh().unwrap();
+        assert_eq!(suggester.count().unwrap(), 3);
+        let results = suggester.lookup("ear", 10, true, false).unwrap();
+        assert_eq!(results[0].key, "earnest money");
+        assert_eq!(results[0].value, 20);
+
+        // building again replaces all the previous suggestions
+        suggester
+            .build(vec![InputEntry::new("early bird", 1)])
+            .unwrap();
+        assert_eq!(suggester.count().unwrap(), 1);
+        let results = suggester.lookup("ear", 10, true, false).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].key, "early bird");
+
+        suggester.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
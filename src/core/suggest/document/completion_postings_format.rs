@@ -0,0 +1,323 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::{
+    codec_util, BlockTreeTermsReader, BlockTreeTermsWriter, Codec, FieldReaderRef, FieldsConsumer,
+    FieldsConsumerEnum, FieldsProducer, Lucene50PostingsFormat, Lucene50PostingsWriter,
+    NormsProducer, PostingsFormat,
+};
+use core::index::{
+    segment_file_name, FieldInfos, Fields, SegmentReadState, SegmentWriteState, TermIterator, Terms,
+};
+use core::search::posting_iterator::{PostingIterator, PostingIteratorFlags};
+use core::search::{DocIterator, NO_MORE_DOCS};
+use core::store::{DataInput, DataOutput, Directory, IndexInput, IndexOutput};
+use core::suggest::document::{NRTSuggester, NRTSuggesterBuilder};
+use core::util::{Accountable, RamUsage};
+
+use error::{ErrorKind::CorruptIndex, Result};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Name of the completion postings format.
+pub const COMPLETION_POSTINGS_FORMAT_NAME: &str = "Completion50";
+
+const CODEC_NAME: &str = "completion";
+const VERSION_START: i32 = 0;
+const VERSION_CURRENT: i32 = VERSION_START;
+
+/// Extension of the file holding the per field suggesters.
+const DICT_EXTENSION: &str = "lkp";
+/// Extension of the file holding the file pointers of the suggesters.
+const INDEX_EXTENSION: &str = "cmp";
+
+/// A postings format which, besides the regular postings written by the
+/// `Lucene50PostingsFormat`, builds a FST based `NRTSuggester` for every field
+/// indexed with it.
+///
+/// Fields are indexed with this format through `SuggestField` or
+/// `ContextSuggestField`, the weight and surface form of every completion is
+/// encoded in the position payloads. Suggesters are loaded in memory when the
+/// segment is opened and can be looked up with a `CompletionQuery`.
+#[derive(Default)]
+pub struct CompletionPostingsFormat {
+    delegate: Lucene50PostingsFormat,
+}
+
+impl PostingsFormat for CompletionPostingsFormat {
+    type FieldsProducer = CompletionFieldsProducer;
+
+    fn fields_producer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<Self::FieldsProducer> {
+        let delegate = self.delegate.fields_producer(state)?;
+        CompletionFieldsProducer::new(delegate, state)
+    }
+
+    fn fields_consumer<D: Directory, DW: Directory, C: Codec>(
+        &self,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<FieldsConsumerEnum<D, DW, C>> {
+        let delegate = self.delegate.terms_writer(state)?;
+        Ok(FieldsConsumerEnum::Completion(
+            CompletionFieldsConsumer::new(delegate, state)?,
+        ))
+    }
+
+    fn name(&self) -> &str {
+        COMPLETION_POSTINGS_FORMAT_NAME
+    }
+}
+
+pub struct CompletionFieldsConsumer<O: IndexOutput> {
+    delegate: BlockTreeTermsWriter<Lucene50PostingsWriter<O>, O>,
+    field_infos: FieldInfos,
+    dict_out: O,
+    index_out: O,
+    // field number and file pointer of the suggesters written to `dict_out`
+    entries: Vec<(u32, i64)>,
+    closed: bool,
+}
+
+impl<O: IndexOutput> CompletionFieldsConsumer<O> {
+    fn new<D: Directory, DW: Directory<IndexOutput = O>, C: Codec>(
+        delegate: BlockTreeTermsWriter<Lucene50PostingsWriter<O>, O>,
+        state: &SegmentWriteState<D, DW, C>,
+    ) -> Result<Self> {
+        let dict_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            DICT_EXTENSION,
+        );
+        let mut dict_out = state.directory.create_output(&dict_name, &state.context)?;
+        codec_util::write_index_header(
+            &mut dict_out,
+            CODEC_NAME,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+
+        let index_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            INDEX_EXTENSION,
+        );
+        let mut index_out = state.directory.create_output(&index_name, &state.context)?;
+        codec_util::write_index_header(
+            &mut index_out,
+            CODEC_NAME,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+
+        Ok(CompletionFieldsConsumer {
+            delegate,
+            field_infos: state.field_infos.clone(),
+            dict_out,
+            index_out,
+            entries: vec![],
+            closed: false,
+        })
+    }
+
+    fn write_suggester(&mut self, field: &str, terms: &impl Terms) -> Result<()> {
+        let mut builder = NRTSuggesterBuilder::default();
+        let mut terms_iter = terms.iterator()?;
+        while let Some(term) = terms_iter.next()? {
+            let mut postings = terms_iter.postings_with_flags(PostingIteratorFlags::PAYLOADS)?;
+            loop {
+                let doc = postings.next()?;
+                if doc == NO_MORE_DOCS {
+                    break;
+                }
+                for _ in 0..postings.freq()? {
+                    postings.next_position()?;
+                    let payload = postings.payload()?;
+                    let mut input = payload.as_slice();
+                    let weight = input.read_vlong()?;
+                    builder.add_entry(doc, weight, input);
+                }
+            }
+            builder.finish_term(&term)?;
+        }
+
+        let fp = self.dict_out.file_pointer();
+        if builder.store(&mut self.dict_out)? {
+            let number = match self.field_infos.field_info_by_name(field) {
+                Some(info) => info.number,
+                None => bail!(CorruptIndex(format!("unknown suggest field: {}", field))),
+            };
+            self.entries.push((number, fp));
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.closed = true;
+
+        self.index_out.write_vint(self.entries.len() as i32)?;
+        for &(number, fp) in &self.entries {
+            self.index_out.write_vint(number as i32)?;
+            self.index_out.write_vlong(fp)?;
+        }
+        codec_util::write_footer(&mut self.index_out)?;
+        codec_util::write_footer(&mut self.dict_out)
+    }
+}
+
+impl<O: IndexOutput> FieldsConsumer for CompletionFieldsConsumer<O> {
+    fn write(&mut self, fields: &impl Fields, norms: Option<&dyn NormsProducer>) -> Result<()> {
+        self.delegate.write(fields, norms)?;
+
+        for field in fields.fields() {
+            if let Some(terms) = fields.terms(&field)? {
+                self.write_suggester(&field, &terms)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<O: IndexOutput> Drop for CompletionFieldsConsumer<O> {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            error!("drop CompletionFieldsConsumer failed by '{:?}'", e);
+        }
+    }
+}
+
+/// Reads the postings of the fields written by `CompletionPostingsFormat`, and
+/// keeps their `NRTSuggester` in memory.
+pub struct CompletionFieldsProducer {
+    delegate: BlockTreeTermsReader,
+    suggesters: HashMap<String, Arc<NRTSuggester>>,
+}
+
+impl CompletionFieldsProducer {
+    fn new<D: Directory, DW: Directory, C: Codec>(
+        delegate: BlockTreeTermsReader,
+        state: &SegmentReadState<'_, D, DW, C>,
+    ) -> Result<CompletionFieldsProducer> {
+        let index_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            INDEX_EXTENSION,
+        );
+        let mut index_in = state
+            .directory
+            .open_checksum_input(&index_name, &state.context)?;
+        let version = codec_util::check_index_header(
+            &mut index_in,
+            CODEC_NAME,
+            VERSION_START,
+            VERSION_CURRENT,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        let count = index_in.read_vint()?;
+        let mut entries = Vec::with_capacity(count.max(0) as usize);
+        for _ in 0..count {
+            let number = index_in.read_vint()? as u32;
+            let fp = index_in.read_vlong()?;
+            entries.push((number, fp));
+        }
+        codec_util::check_footer(&mut index_in)?;
+
+        let dict_name = segment_file_name(
+            &state.segment_info.name,
+            &state.segment_suffix,
+            DICT_EXTENSION,
+        );
+        let mut dict_in = state.directory.open_input(&dict_name, &state.context)?;
+        codec_util::check_index_header(
+            dict_in.as_mut(),
+            CODEC_NAME,
+            version,
+            version,
+            state.segment_info.get_id(),
+            &state.segment_suffix,
+        )?;
+        codec_util::retrieve_checksum(dict_in.as_mut())?;
+
+        let mut suggesters = HashMap::with_capacity(entries.len());
+        for (number, fp) in entries {
+            let field = match state.field_infos.field_info_by_number(number) {
+                Some(info) => info.name.clone(),
+                None => bail!(CorruptIndex(format!("invalid field number: {}", number))),
+            };
+            dict_in.seek(fp)?;
+            let suggester = NRTSuggester::load(dict_in.as_mut())?;
+            suggesters.insert(field, Arc::new(suggester));
+        }
+
+        Ok(CompletionFieldsProducer {
+            delegate,
+            suggesters,
+        })
+    }
+}
+
+impl FieldsProducer for CompletionFieldsProducer {
+    fn check_integrity(&self) -> Result<()> {
+        self.delegate.check_integrity()
+    }
+
+    fn suggester(&self, field: &str) -> Option<Arc<NRTSuggester>> {
+        self.suggesters.get(field).cloned()
+    }
+}
+
+impl Accountable for CompletionFieldsProducer {
+    fn ram_bytes_used(&self) -> usize {
+        self.delegate.ram_bytes_used()
+            + self
+                .suggesters
+                .values()
+                .map(|s| s.ram_bytes_used())
+                .sum::<usize>()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        let mut resources = vec![RamUsage::of("delegate", &self.delegate)];
+        for (field, suggester) in &self.suggesters {
+            resources.push(RamUsage::of(format!("suggester '{}'", field), suggester));
+        }
+        resources
+    }
+}
+
+impl Fields for CompletionFieldsProducer {
+    type Terms = FieldReaderRef;
+    fn fields(&self) -> Vec<String> {
+        self.delegate.fields()
+    }
+
+    fn terms(&self, field: &str) -> Result<Option<Self::Terms>> {
+        self.delegate.terms(field)
+    }
+
+    fn size(&self) -> usize {
+        self.delegate.size()
+    }
+
+    fn terms_freq(&self, field: &str) -> usize {
+        self.delegate.terms_freq(field)
+    }
+}
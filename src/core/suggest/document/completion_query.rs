@@ -0,0 +1,243 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::codec::FieldsProducer;
+use core::index::IndexReader;
+use core::suggest::document::SuggestEntry;
+use core::util::DocId;
+
+use error::Result;
+
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// A suggestion returned by a `CompletionQuery`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestScoreDoc {
+    /// the top level doc id of the document the suggestion was indexed in
+    pub doc: DocId,
+    /// the suggestion text
+    pub key: String,
+    /// the context the suggestion matched, for context suggest fields
+    pub context: Option<String>,
+    pub weight: i64,
+}
+
+/// The suggestions of a `CompletionQuery`, sorted by descending weight.
+#[derive(Debug, Clone, Default)]
+pub struct TopSuggestDocs {
+    pub suggest_docs: Vec<SuggestScoreDoc>,
+}
+
+impl TopSuggestDocs {
+    pub fn suggest_docs(&self) -> &[SuggestScoreDoc] {
+        &self.suggest_docs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.suggest_docs.is_empty()
+    }
+}
+
+/// Looks up the completions of a prefix in a field indexed with `SuggestField`
+/// or `ContextSuggestField`.
+///
+/// The completions are looked up in the `NRTSuggester` of every segment, deleted
+/// documents are skipped. For context suggest fields, the completions can be
+/// restricted to a set of contexts; without any context all of them are returned.
+pub struct CompletionQuery {
+    field: String,
+    prefix: String,
+    contexts: Vec<String>,
+}
+
+impl CompletionQuery {
+    pub fn new(field: String, prefix: String) -> CompletionQuery {
+        CompletionQuery {
+            field,
+            prefix,
+            contexts: vec![],
+        }
+    }
+
+    /// Restricts the suggestions to the ones indexed with one of `contexts`.
+    pub fn with_contexts(field: String, prefix: String, contexts: Vec<String>) -> CompletionQuery {
+        CompletionQuery {
+            field,
+            prefix,
+            contexts,
+        }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn contexts(&self) -> &[String] {
+        &self.contexts
+    }
+
+    /// Returns the `num` suggestions with the highest weight. A document
+    /// suggests the same text once, even if it matches multiple contexts.
+    pub fn suggest<R: IndexReader + ?Sized>(
+        &self,
+        reader: &R,
+        num: usize,
+    ) -> Result<TopSuggestDocs> {
+        let contexts: Vec<Vec<u8>> = self
+            .contexts
+            .iter()
+            .map(|c| c.as_bytes().to_vec())
+            .collect();
+
+        let mut suggest_docs = vec![];
+        for leaf in reader.leaves() {
+            let suggester = match leaf.reader.fields()?.suggester(&self.field) {
+                Some(suggester) => suggester,
+                None => {
+                    continue;
+                }
+            };
+            let live_docs = leaf.reader.live_docs();
+            let doc_base = leaf.doc_base;
+            let mut seen = HashSet::new();
+            let entries = suggester.lookup(
+                self.prefix.as_bytes(),
+                &contexts,
+                num,
+                &mut |entry: &SuggestEntry| {
+                    Ok(live_docs.get(entry.doc as usize)?
+                        && seen.insert((entry.doc, entry.surface.clone())))
+                },
+            )?;
+            for entry in entries {
+                suggest_docs.push(SuggestScoreDoc {
+                    doc: doc_base + entry.doc,
+                    key: String::from_utf8(entry.surface)?,
+                    context: match entry.context {
+                        Some(c) => Some(String::from_utf8(c)?),
+                        None => None,
+                    },
+                    weight: entry.weight,
+                });
+            }
+        }
+
+        suggest_docs.sort_by(|a, b| match b.weight.cmp(&a.weight) {
+            Ordering::Equal => a.doc.cmp(&b.doc).then_with(|| a.key.cmp(&b.key)),
+            o => o,
+        });
+        suggest_docs.truncate(num);
+        Ok(TopSuggestDocs { suggest_docs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::doc::{Field, FieldType};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexOptions, IndexWriter, Term};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::suggest::document::{ContextSuggestField, SuggestField};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn id_field(id: &str) -> Box<dyn Fieldable> {
+        let mut field_type = FieldType::default();
+        field_type.tokenized = false;
+        field_type.index_options = IndexOptions::Docs;
+        field_type.omit_norms = true;
+        Box::new(Field::new(
+            "id".into(),
+            field_type,
+            Some(VariantValue::VString(id.to_string())),
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_suggest() {
+        let path = ::std::env::temp_dir().join("rucene_completion_query_suggest");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+
+        let suggestions = [
+            ("apple", 4),
+            ("apricot", 9),
+            ("avocado", 1),
+            ("banana", 10),
+            ("apple pie", 7),
+        ];
+        for (i, &(text, weight)) in suggestions.iter().enumerate() {
+            let doc: Vec<Box<dyn Fieldable>> = vec![
+                id_field(&i.to_string()),
+                Box::new(SuggestField::new("suggest".into(), text.into(), weight).unwrap()),
+            ];
+            writer.add_document(doc).unwrap();
+            // spread the suggestions over several segments
+            if i % 2 == 1 {
+                writer.commit().unwrap();
+            }
+        }
+        writer
+            .delete_documents_by_terms(vec![Term::new("id".into(), b"1".to_vec())])
+            .unwrap();
+        let doc: Vec<Box<dyn Fieldable>> = vec![Box::new(
+            ContextSuggestField::new(
+                "context".into(),
+                "apple".into(),
+                3,
+                vec!["fruit".into(), "tech".into()],
+            )
+            .unwrap(),
+        )];
+        writer.add_document(doc).unwrap();
+
+        let reader = writer.get_reader(true, false).unwrap();
+        let query = CompletionQuery::new("suggest".into(), "a".into());
+        let keys: Vec<(String, i64)> = query
+            .suggest(&reader, 2)
+            .unwrap()
+            .suggest_docs()
+            .iter()
+            .map(|s| (s.key.clone(), s.weight))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![("apple pie".to_string(), 7), ("apple".to_string(), 4)]
+        );
+        assert_eq!(query.suggest(&reader, 10).unwrap().suggest_docs().len(), 3);
+
+        // the same suggestion of a document is only returned once
+        let query = CompletionQuery::new("context".into(), "app".into());
+        let suggest_docs = query.suggest(&reader, 10).unwrap();
+        assert_eq!(suggest_docs.suggest_docs().len(), 1);
+        let query =
+            CompletionQuery::with_contexts("context".into(), "app".into(), vec!["tech".into()]);
+        let suggest_docs = query.suggest(&reader, 10).unwrap();
+        assert_eq!(
+            suggest_docs.suggest_docs()[0].context,
+            Some("tech".to_string())
+        );
+
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
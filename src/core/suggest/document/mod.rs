@@ -0,0 +1,28 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Suggesters built at index time: completions indexed with `SuggestField` or
+//! `ContextSuggestField` are stored as per segment FSTs by the
+//! `CompletionPostingsFormat`, and looked up with a `CompletionQuery`.
+
+mod completion_postings_format;
+pub use self::completion_postings_format::*;
+
+mod completion_query;
+pub use self::completion_query::*;
+
+mod nrt_suggester;
+pub use self::nrt_suggester::*;
+
+mod suggest_field;
+pub use self::suggest_field::*;
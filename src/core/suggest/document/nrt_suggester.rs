@@ -0,0 +1,581 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::store::{DataInput, DataOutput};
+use core::util::fst::fst_builder::FstBuilder;
+use core::util::fst::{
+    Arc as FSTArc, ByteSequenceOutput, ByteSequenceOutputFactory, FSTBytesReader, InputType,
+    Output, END_LABEL, FST,
+};
+use core::util::ints_ref::{to_ints_ref, IntsRefBuilder};
+use core::util::{Accountable, DocId};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Separates the context from the suggestion in the terms of a context
+/// enabled suggest field.
+pub const CONTEXT_SEPARATOR: u8 = 0x1d;
+
+/// The kind of suggest field a `NRTSuggester` was built from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuggestFieldType {
+    /// Every completion of the field is indexed as is.
+    Plain,
+    /// Every completion of the field is indexed prefixed by its contexts.
+    Context,
+}
+
+impl SuggestFieldType {
+    pub fn as_byte(self) -> u8 {
+        match self {
+            SuggestFieldType::Plain => 0,
+            SuggestFieldType::Context => 1,
+        }
+    }
+
+    pub fn from_byte(b: u8) -> Result<SuggestFieldType> {
+        match b {
+            0 => Ok(SuggestFieldType::Plain),
+            1 => Ok(SuggestFieldType::Context),
+            _ => bail!(IllegalArgument(format!(
+                "invalid suggest field type: {}",
+                b
+            ))),
+        }
+    }
+}
+
+/// A completion matched by a `NRTSuggester` lookup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestEntry {
+    /// the segment local doc id
+    pub doc: DocId,
+    pub weight: i64,
+    /// the suggestion as it was indexed, without its context
+    pub surface: Vec<u8>,
+    /// the context the completion was indexed with, for context fields
+    pub context: Option<Vec<u8>>,
+}
+
+/// Builds the per segment FST of a suggest field.
+///
+/// The inputs of the FST are the indexed terms of the field, the output of each
+/// term is the list of `(doc, weight, surface form)` of the documents having it.
+/// Terms must be added in sorted order.
+#[derive(Default)]
+pub struct NRTSuggesterBuilder {
+    field_type: Option<SuggestFieldType>,
+    entries: Vec<(DocId, i64, Vec<u8>)>,
+    // the FST builder keeps pointers to itself, so it can't be stored here and
+    // the encoded terms are only added to it in `store`
+    terms: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl NRTSuggesterBuilder {
+    /// Adds one completion of the current term for the segment local `doc`.
+    pub fn add_entry(&mut self, doc: DocId, weight: i64, surface: &[u8]) {
+        // only keep the best weight of the same completion in one doc
+        if let Some(last) = self.entries.last_mut() {
+            if last.0 == doc && last.2.as_slice() == surface {
+                last.1 = last.1.max(weight);
+                return;
+            }
+        }
+        self.entries.push((doc, weight, surface.to_vec()));
+    }
+
+    /// Adds all the entries collected since the last call under `term`.
+    pub fn finish_term(&mut self, term: &[u8]) -> Result<()> {
+        if self.entries.is_empty() {
+            return Ok(());
+        }
+        let field_type = if term.contains(&CONTEXT_SEPARATOR) {
+            SuggestFieldType::Context
+        } else {
+            SuggestFieldType::Plain
+        };
+        match self.field_type {
+            Some(t) if t != field_type => {
+                bail!(IllegalArgument(
+                    "a field can't have both context and plain suggest fields".into()
+                ));
+            }
+            _ => self.field_type = Some(field_type),
+        }
+
+        // the best entries first, the output starts with the encoded max
+        // weight so that the common prefix of the outputs below an arc bounds
+        // the weights of its completions
+        self.entries
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let mut output = encode_weight(self.entries[0].1).to_vec();
+        output.write_vint(self.entries.len() as i32)?;
+        for (doc, weight, surface) in self.entries.drain(..) {
+            output.write_vint(doc)?;
+            output.write_vlong(weight)?;
+            output.write_vint(surface.len() as i32)?;
+            output.write_bytes(&surface, 0, surface.len())?;
+        }
+        self.terms.push((term.to_vec(), output));
+        Ok(())
+    }
+
+    /// Writes the built suggester, returns false if the field had no completion.
+    pub fn store(&mut self, out: &mut impl DataOutput) -> Result<bool> {
+        let field_type = match self.field_type {
+            Some(t) => t,
+            None => {
+                return Ok(false);
+            }
+        };
+        let mut builder = FstBuilder::new(InputType::Byte1, ByteSequenceOutputFactory::new());
+        builder.init();
+        let mut scratch_ints = IntsRefBuilder::new();
+        for (term, output) in self.terms.drain(..) {
+            let input = to_ints_ref(&term, &mut scratch_ints);
+            builder.add(input, ByteSequenceOutput::new(output))?;
+        }
+        match builder.finish()? {
+            Some(fst) => {
+                out.write_byte(field_type.as_byte())?;
+                fst.save(out)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+const WEIGHT_BYTES: usize = 8;
+
+// encodes the weight so that the bytes of a higher weight sort first
+fn encode_weight(weight: i64) -> [u8; WEIGHT_BYTES] {
+    let cost = !((weight as u64) ^ (1 << 63));
+    let mut bytes = [0u8; WEIGHT_BYTES];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = (cost >> ((WEIGHT_BYTES - 1 - i) * 8)) as u8;
+    }
+    bytes
+}
+
+// the highest weight of the outputs starting with `output`
+fn max_weight(output: &[u8]) -> i64 {
+    let mut cost = 0u64;
+    for i in 0..WEIGHT_BYTES {
+        cost = (cost << 8) | u64::from(output.get(i).cloned().unwrap_or(0));
+    }
+    ((!cost) ^ (1 << 63)) as i64
+}
+
+/// A path of the top N search, ordered by the highest weight of the
+/// completions below it.
+struct SearchPath {
+    max_weight: i64,
+    arc: FSTArc<ByteSequenceOutput>,
+    output: ByteSequenceOutput,
+    context: Option<Vec<u8>>,
+}
+
+impl PartialEq for SearchPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.max_weight == other.max_weight
+    }
+}
+
+impl Eq for SearchPath {}
+
+impl PartialOrd for SearchPath {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SearchPath {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.max_weight.cmp(&other.max_weight)
+    }
+}
+
+// the "greater" entry is the less competitive one, so that the top of the
+// `BinaryHeap` of the results is the first one to evict
+struct ResultEntry(SuggestEntry);
+
+impl PartialEq for ResultEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ResultEntry {}
+
+impl PartialOrd for ResultEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ResultEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .0
+            .weight
+            .cmp(&self.0.weight)
+            .then_with(|| self.0.doc.cmp(&other.0.doc))
+    }
+}
+
+/// The in memory suggester of a suggest field for one segment, loaded by
+/// `CompletionFieldsProducer`. Since it's built when the segment is
+/// flushed, new completions are visible as soon as a near-real-time reader
+/// is opened.
+pub struct NRTSuggester {
+    field_type: SuggestFieldType,
+    fst: FST<ByteSequenceOutputFactory>,
+}
+
+impl Accountable for NRTSuggester {
+    fn ram_bytes_used(&self) -> usize {
+        self.fst.ram_bytes_used()
+    }
+}
+
+impl NRTSuggester {
+    pub fn load<I: DataInput + ?Sized>(input: &mut I) -> Result<NRTSuggester> {
+        let field_type = SuggestFieldType::from_byte(input.read_byte()?)?;
+        let fst = FST::from_input(input, ByteSequenceOutputFactory::new())?;
+        Ok(NRTSuggester { field_type, fst })
+    }
+
+    pub fn field_type(&self) -> SuggestFieldType {
+        self.field_type
+    }
+
+    /// Returns the `num` completions starting with `prefix` with the highest
+    /// weight, only the ones `accept` returns true for are counted.
+    ///
+    /// For context fields, `contexts` restricts the completions to the ones indexed
+    /// with one of the given contexts; if it's empty any context matches. It's
+    /// ignored for plain fields.
+    ///
+    /// The FST is searched best first, the paths which can't beat the
+    /// current top `num` completions are never visited.
+    pub fn lookup(
+        &self,
+        prefix: &[u8],
+        contexts: &[Vec<u8>],
+        num: usize,
+        accept: &mut impl FnMut(&SuggestEntry) -> Result<bool>,
+    ) -> Result<Vec<SuggestEntry>> {
+        if num == 0 {
+            return Ok(vec![]);
+        }
+        let mut reader = self.fst.bytes_reader();
+        let root = self.fst.root_arc();
+        let output = ByteSequenceOutput::empty();
+        let mut queue = BinaryHeap::new();
+        match self.field_type {
+            SuggestFieldType::Plain => {
+                self.start_path(&root, &output, prefix, None, &mut reader, &mut queue)?;
+            }
+            SuggestFieldType::Context if contexts.is_empty() => {
+                let mut context = vec![];
+                self.start_any_context(
+                    &root,
+                    &output,
+                    prefix,
+                    &mut context,
+                    &mut reader,
+                    &mut queue,
+                )?;
+            }
+            SuggestFieldType::Context => {
+                for context in contexts {
+                    let mut key = context.clone();
+                    key.push(CONTEXT_SEPARATOR);
+                    key.extend_from_slice(prefix);
+                    self.start_path(&root, &output, &key, Some(context), &mut reader, &mut queue)?;
+                }
+            }
+        }
+
+        let mut results: BinaryHeap<ResultEntry> = BinaryHeap::with_capacity(num);
+        while let Some(path) = queue.pop() {
+            if results.len() == num && path.max_weight < results.peek().unwrap().0.weight {
+                break;
+            }
+            if path.arc.label == END_LABEL {
+                self.decode(&path, num, accept, &mut results)?;
+                continue;
+            }
+            let mut child = self.fst.read_first_target_arc(&path.arc, &mut reader)?;
+            loop {
+                let output = Self::cat(&path.output, child.output.as_ref());
+                queue.push(SearchPath {
+                    max_weight: max_weight(output.inner()),
+                    arc: child.clone(),
+                    output,
+                    context: path.context.clone(),
+                });
+                if child.is_last() {
+                    break;
+                }
+                self.fst.read_next_arc(&mut child, &mut reader)?;
+            }
+        }
+        Ok(results
+            .into_sorted_vec()
+            .into_iter()
+            .map(|entry| entry.0)
+            .collect())
+    }
+
+    // walk down the context part of the terms, and start a path at the
+    // prefix once the context separator is reached.
+    fn start_any_context(
+        &self,
+        arc: &FSTArc<ByteSequenceOutput>,
+        output: &ByteSequenceOutput,
+        prefix: &[u8],
+        context: &mut Vec<u8>,
+        reader: &mut FSTBytesReader,
+        queue: &mut BinaryHeap<SearchPath>,
+    ) -> Result<()> {
+        let mut child = self.fst.read_first_target_arc(arc, reader)?;
+        loop {
+            if child.label != END_LABEL {
+                let out = Self::cat(output, child.output.as_ref());
+                if child.label == i32::from(CONTEXT_SEPARATOR) {
+                    let ctx = context.clone();
+                    self.start_path(&child, &out, prefix, Some(&ctx), reader, queue)?;
+                } else {
+                    context.push(child.label as u8);
+                    self.start_any_context(&child, &out, prefix, context, reader, queue)?;
+                    context.pop();
+                }
+            }
+            if child.is_last() {
+                break;
+            }
+            self.fst.read_next_arc(&mut child, reader)?;
+        }
+        Ok(())
+    }
+
+    // follow `prefix` from `arc`, the completions below are searched from there
+    fn start_path(
+        &self,
+        arc: &FSTArc<ByteSequenceOutput>,
+        output: &ByteSequenceOutput,
+        prefix: &[u8],
+        context: Option<&Vec<u8>>,
+        reader: &mut FSTBytesReader,
+        queue: &mut BinaryHeap<SearchPath>,
+    ) -> Result<()> {
+        let mut arc = arc.clone();
+        let mut output = output.clone();
+        for &b in prefix {
+            match self.fst.find_target_arc(i32::from(b), &arc, reader)? {
+                Some(next) => {
+                    output = Self::cat(&output, next.output.as_ref());
+                    arc = next;
+                }
+                None => {
+                    return Ok(());
+                }
+            }
+        }
+        queue.push(SearchPath {
+            max_weight: max_weight(output.inner()),
+            arc,
+            output,
+            context: context.cloned(),
+        });
+        Ok(())
+    }
+
+    fn cat(prefix: &ByteSequenceOutput, output: Option<&ByteSequenceOutput>) -> ByteSequenceOutput {
+        match output {
+            Some(o) if !o.is_empty() => prefix.cat(o),
+            _ => prefix.clone(),
+        }
+    }
+
+    fn decode(
+        &self,
+        path: &SearchPath,
+        num: usize,
+        accept: &mut impl FnMut(&SuggestEntry) -> Result<bool>,
+        results: &mut BinaryHeap<ResultEntry>,
+    ) -> Result<()> {
+        let mut bytes = &path.output.inner()[WEIGHT_BYTES..];
+        let count = bytes.read_vint()?;
+        for _ in 0..count {
+            let doc = bytes.read_vint()?;
+            let weight = bytes.read_vlong()?;
+            let len = bytes.read_vint()? as usize;
+            if results.len() == num && weight < results.peek().unwrap().0.weight {
+                // the entries of a term are sorted by weight
+                break;
+            }
+            let mut surface = vec![0u8; len];
+            bytes.read_bytes(&mut surface, 0, len)?;
+            let entry = SuggestEntry {
+                doc,
+                weight,
+                surface,
+                context: path.context.clone(),
+            };
+            if !accept(&entry)? {
+                continue;
+            }
+            let entry = ResultEntry(entry);
+            if results.len() < num {
+                results.push(entry);
+            } else if let Some(mut top) = results.peek_mut() {
+                if entry < *top {
+                    *top = entry;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build(terms: &[(&[u8], DocId, i64, &str)]) -> NRTSuggester {
+        let mut builder = NRTSuggesterBuilder::default();
+        for &(term, doc, weight, surface) in terms {
+            builder.add_entry(doc, weight, surface.as_bytes());
+            builder.finish_term(term).unwrap();
+        }
+        let mut out = vec![];
+        assert!(builder.store(&mut out).unwrap());
+        let mut input = out.as_slice();
+        NRTSuggester::load(&mut input).unwrap()
+    }
+
+    fn lookup(suggester: &NRTSuggester, prefix: &str, contexts: &[&str]) -> Vec<(DocId, String)> {
+        top_n(suggester, prefix, contexts, 10)
+    }
+
+    fn top_n(
+        suggester: &NRTSuggester,
+        prefix: &str,
+        contexts: &[&str],
+        num: usize,
+    ) -> Vec<(DocId, String)> {
+        let contexts: Vec<Vec<u8>> = contexts.iter().map(|c| c.as_bytes().to_vec()).collect();
+        suggester
+            .lookup(prefix.as_bytes(), &contexts, num, &mut |_| Ok(true))
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.doc, String::from_utf8(e.surface).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_encode_weight() {
+        let weights = [i64::min_value(), -5, 0, 1, 300, i64::max_value()];
+        for w in weights.windows(2) {
+            assert!(encode_weight(w[0]) > encode_weight(w[1]));
+        }
+        for &w in &weights {
+            assert_eq!(max_weight(&encode_weight(w)), w);
+        }
+        // a common prefix bounds the weights of all the outputs sharing it
+        assert_eq!(max_weight(&[]), i64::max_value());
+        assert!(max_weight(&encode_weight(300)[..7]) >= 300);
+    }
+
+    #[test]
+    fn test_plain_lookup() {
+        let suggester = build(&[
+            (b"app", 3, 1, "app"),
+            (b"apple", 0, 5, "apple"),
+            (b"banana", 1, 2, "banana"),
+        ]);
+        assert_eq!(suggester.field_type(), SuggestFieldType::Plain);
+        assert_eq!(
+            lookup(&suggester, "app", &[]),
+            vec![(0, "apple".to_string()), (3, "app".to_string())]
+        );
+        assert!(lookup(&suggester, "c", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_context_lookup() {
+        let suggester = build(&[
+            (b"fruit\x1dapple", 0, 5, "apple"),
+            (b"tech\x1dandroid", 2, 5, "android"),
+            (b"tech\x1dapple", 1, 5, "apple"),
+        ]);
+        assert_eq!(suggester.field_type(), SuggestFieldType::Context);
+        assert_eq!(
+            lookup(&suggester, "ap", &["tech"]),
+            vec![(1, "apple".to_string())]
+        );
+        assert_eq!(
+            lookup(&suggester, "a", &[]),
+            vec![
+                (0, "apple".to_string()),
+                (1, "apple".to_string()),
+                (2, "android".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_n_lookup() {
+        let terms: Vec<(Vec<u8>, DocId, i64, String)> = (0..100)
+            .map(|i| {
+                let surface = format!("term{:03}", i);
+                (
+                    surface.as_bytes().to_vec(),
+                    i,
+                    (i * 37 % 100) as i64,
+                    surface,
+                )
+            })
+            .collect();
+        let terms: Vec<(&[u8], DocId, i64, &str)> = terms
+            .iter()
+            .map(|(t, d, w, s)| (t.as_slice(), *d, *w, s.as_str()))
+            .collect();
+        let suggester = build(&terms);
+
+        let mut expected: Vec<(DocId, i64)> = terms.iter().map(|t| (t.1, t.2)).collect();
+        expected.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        let top: Vec<DocId> = top_n(&suggester, "term", &[], 5)
+            .into_iter()
+            .map(|(doc, _)| doc)
+            .collect();
+        let expected_top: Vec<DocId> = expected.iter().take(5).map(|e| e.0).collect();
+        assert_eq!(top, expected_top);
+
+        // rejected completions aren't counted
+        let res = suggester
+            .lookup(b"term", &[], 3, &mut |e| Ok(e.weight % 2 == 0))
+            .unwrap();
+        let weights: Vec<i64> = res.iter().map(|e| e.weight).collect();
+        assert_eq!(weights, vec![98, 96, 94]);
+
+        assert_eq!(top_n(&suggester, "term00", &[], 20).len(), 10);
+        assert!(top_n(&suggester, "term", &[], 0).is_empty());
+    }
+}
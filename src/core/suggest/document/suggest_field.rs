@@ -0,0 +1,296 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::analysis::TokenStream;
+use core::attribute::{BytesTermAttribute, PayloadAttribute, TermToBytesRefAttribute};
+use core::attribute::{OffsetAttribute, PositionIncrementAttribute};
+use core::doc::FieldType;
+use core::index::{Fieldable, IndexOptions};
+use core::store::DataOutput;
+use core::suggest::document::{COMPLETION_POSTINGS_FORMAT_NAME, CONTEXT_SEPARATOR};
+use core::util::{Numeric, VariantValue};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+/// Bytes reserved by the suggest fields, they can't appear in suggestions or contexts.
+const RESERVED_BYTES: [u8; 4] = [0x00, CONTEXT_SEPARATOR, 0x1e, 0x1f];
+
+fn suggest_field_type() -> FieldType {
+    let mut field_type = FieldType::default();
+    field_type.omit_norms = true;
+    field_type.index_options = IndexOptions::DocsAndFreqsAndPositions;
+    field_type.set_postings_format(COMPLETION_POSTINGS_FORMAT_NAME);
+    field_type
+}
+
+fn check_reserved(kind: &str, value: &[u8]) -> Result<()> {
+    if let Some(b) = value.iter().find(|b| RESERVED_BYTES.contains(b)) {
+        bail!(IllegalArgument(format!(
+            "illegal {} {:?}: reserved byte 0x{:x}",
+            kind,
+            String::from_utf8_lossy(value),
+            b
+        )));
+    }
+    Ok(())
+}
+
+// the payload of every completion token: the weight followed by the surface form
+fn completion_payload(value: &str, weight: i64) -> Result<Vec<u8>> {
+    if weight < 0 {
+        bail!(IllegalArgument(format!(
+            "weight must be >= 0, got {}",
+            weight
+        )));
+    }
+    let mut payload = Vec::with_capacity(value.len() + 9);
+    payload.write_vlong(weight)?;
+    payload.extend_from_slice(value.as_bytes());
+    Ok(payload)
+}
+
+/// Field which indexes a weighted suggestion with the `CompletionPostingsFormat`,
+/// the suggestion can then be looked up by prefix with a `CompletionQuery`.
+///
+/// A document can have multiple suggest fields of the same name.
+pub struct SuggestField {
+    name: String,
+    field_type: FieldType,
+    value: String,
+    weight: i64,
+}
+
+impl SuggestField {
+    pub fn new(name: String, value: String, weight: i64) -> Result<SuggestField> {
+        check_reserved("suggestion", value.as_bytes())?;
+        completion_payload(&value, weight)?;
+        Ok(SuggestField {
+            name,
+            field_type: suggest_field_type(),
+            value,
+            weight,
+        })
+    }
+
+    pub fn weight(&self) -> i64 {
+        self.weight
+    }
+}
+
+impl Fieldable for SuggestField {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn field_type(&self) -> &FieldType {
+        &self.field_type
+    }
+
+    fn boost(&self) -> f32 {
+        1.0
+    }
+
+    fn fields_data(&self) -> Option<&VariantValue> {
+        None
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        let payload = completion_payload(&self.value, self.weight)?;
+        let terms = vec![self.value.as_bytes().to_vec()];
+        Ok(Box::new(CompletionTokenStream::new(terms, payload)))
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        Some(&self.value)
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        None
+    }
+}
+
+/// A `SuggestField` whose suggestion is indexed once for each of its contexts,
+/// so that a `CompletionQuery` can restrict the suggestions to some contexts.
+///
+/// A suggestion without any context matches only the queries which don't filter
+/// on contexts. A field can't mix context and plain suggest fields.
+pub struct ContextSuggestField {
+    name: String,
+    field_type: FieldType,
+    value: String,
+    weight: i64,
+    contexts: Vec<String>,
+}
+
+impl ContextSuggestField {
+    pub fn new(
+        name: String,
+        value: String,
+        weight: i64,
+        contexts: Vec<String>,
+    ) -> Result<ContextSuggestField> {
+        check_reserved("suggestion", value.as_bytes())?;
+        for context in &contexts {
+            check_reserved("context", context.as_bytes())?;
+        }
+        completion_payload(&value, weight)?;
+        Ok(ContextSuggestField {
+            name,
+            field_type: suggest_field_type(),
+            value,
+            weight,
+            contexts,
+        })
+    }
+
+    pub fn weight(&self) -> i64 {
+        self.weight
+    }
+
+    pub fn contexts(&self) -> &[String] {
+        &self.contexts
+    }
+}
+
+impl Fieldable for ContextSuggestField {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn field_type(&self) -> &FieldType {
+        &self.field_type
+    }
+
+    fn boost(&self) -> f32 {
+        1.0
+    }
+
+    fn fields_data(&self) -> Option<&VariantValue> {
+        None
+    }
+
+    fn token_stream(&mut self) -> Result<Box<dyn TokenStream>> {
+        let payload = completion_payload(&self.value, self.weight)?;
+        let terms = if self.contexts.is_empty() {
+            let mut term = vec![CONTEXT_SEPARATOR];
+            term.extend_from_slice(self.value.as_bytes());
+            vec![term]
+        } else {
+            self.contexts
+                .iter()
+                .map(|context| {
+                    let mut term = context.as_bytes().to_vec();
+                    term.push(CONTEXT_SEPARATOR);
+                    term.extend_from_slice(self.value.as_bytes());
+                    term
+                })
+                .collect()
+        };
+        Ok(Box::new(CompletionTokenStream::new(terms, payload)))
+    }
+
+    fn binary_value(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn string_value(&self) -> Option<&str> {
+        Some(&self.value)
+    }
+
+    fn numeric_value(&self) -> Option<Numeric> {
+        None
+    }
+}
+
+/// Emits the terms of a suggest field, each of them carrying the completion payload.
+#[derive(Debug)]
+struct CompletionTokenStream {
+    term_attribute: BytesTermAttribute,
+    offset_attribute: OffsetAttribute,
+    position_attribute: PositionIncrementAttribute,
+    payload_attribute: PayloadAttribute,
+    terms: Vec<Vec<u8>>,
+    payload: Vec<u8>,
+    upto: usize,
+}
+
+impl CompletionTokenStream {
+    fn new(terms: Vec<Vec<u8>>, payload: Vec<u8>) -> Self {
+        let upto = terms.len();
+        CompletionTokenStream {
+            term_attribute: BytesTermAttribute::new(),
+            offset_attribute: OffsetAttribute::new(),
+            position_attribute: PositionIncrementAttribute::new(),
+            payload_attribute: PayloadAttribute::new(Vec::with_capacity(0)),
+            terms,
+            payload,
+            upto,
+        }
+    }
+}
+
+impl TokenStream for CompletionTokenStream {
+    fn increment_token(&mut self) -> Result<bool> {
+        if self.upto >= self.terms.len() {
+            return Ok(false);
+        }
+        self.clear_attributes();
+
+        self.term_attribute.set_bytes(&self.terms[self.upto]);
+        self.payload_attribute.set_payload(self.payload.clone());
+        self.upto += 1;
+        Ok(true)
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.end_attributes();
+        Ok(())
+    }
+
+    fn reset(&mut self) -> Result<()> {
+        self.upto = 0;
+        Ok(())
+    }
+
+    fn offset_attribute_mut(&mut self) -> &mut OffsetAttribute {
+        &mut self.offset_attribute
+    }
+
+    fn offset_attribute(&self) -> &OffsetAttribute {
+        &self.offset_attribute
+    }
+
+    fn position_attribute_mut(&mut self) -> &mut PositionIncrementAttribute {
+        &mut self.position_attribute
+    }
+
+    fn payload_attribute_mut(&mut self) -> Option<&mut PayloadAttribute> {
+        Some(&mut self.payload_attribute)
+    }
+
+    fn payload_attribute(&self) -> Option<&PayloadAttribute> {
+        Some(&self.payload_attribute)
+    }
+
+    fn term_bytes_attribute_mut(&mut self) -> &mut TermToBytesRefAttribute {
+        &mut self.term_attribute
+    }
+
+    fn term_bytes_attribute(&self) -> &TermToBytesRefAttribute {
+        &self.term_attribute
+    }
+}
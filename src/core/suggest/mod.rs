@@ -0,0 +1,58 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod document;
+pub mod spell;
+
+mod analyzing_infix_suggester;
+pub use self::analyzing_infix_suggester::*;
+
+/// A single entry to build a suggester from: the suggestion text, its weight
+/// and an optional payload returned along with the suggestion.
+#[derive(Debug, Clone)]
+pub struct InputEntry {
+    pub text: String,
+    pub weight: i64,
+    pub payload: Option<Vec<u8>>,
+}
+
+impl InputEntry {
+    pub fn new(text: &str, weight: i64) -> InputEntry {
+        InputEntry {
+            text: text.to_string(),
+            weight,
+            payload: None,
+        }
+    }
+
+    pub fn with_payload(text: &str, weight: i64, payload: Vec<u8>) -> InputEntry {
+        InputEntry {
+            text: text.to_string(),
+            weight,
+            payload: Some(payload),
+        }
+    }
+}
+
+/// Result of a lookup.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupResult {
+    /// the suggestion text
+    pub key: String,
+    /// the suggestion text with the matched fragments highlighted, if requested
+    pub highlight_key: Option<String>,
+    /// the weight of the suggestion
+    pub value: i64,
+    /// the payload of the suggestion, if any
+    pub payload: Option<Vec<u8>>,
+}
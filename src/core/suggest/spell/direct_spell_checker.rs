@@ -0,0 +1,435 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::index::{get_terms, IndexReader, SeekStatus, Term, TermIterator, Terms};
+use core::suggest::spell::{LevenshteinAutomaton, LevenshteinState, SuggestMode, SuggestWord};
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::char;
+use std::cmp::Ordering;
+use std::str;
+
+/// The maximum number of edits supported by `DirectSpellChecker`.
+pub const MAXIMUM_SUPPORTED_DISTANCE: u32 = 2;
+
+/// Default accuracy a suggestion must reach to be returned.
+pub const DEFAULT_ACCURACY: f32 = 0.5;
+
+/// Simple automaton-based spellchecker.
+///
+/// Candidates are enumerated directly from the term dictionary of the main
+/// index by intersecting it with a Levenshtein automaton of the misspelled
+/// word, so no auxiliary index has to be built or kept in sync.
+///
+/// A suggestion is scored `1 - edits / min(len(word), len(suggestion))`,
+/// suggestions scoring below the configured accuracy are dropped.
+pub struct DirectSpellChecker {
+    max_edits: u32,
+    min_prefix: usize,
+    accuracy: f32,
+    threshold_frequency: f32,
+    min_query_length: usize,
+    max_query_frequency: f32,
+    lower_case_terms: bool,
+}
+
+impl Default for DirectSpellChecker {
+    fn default() -> Self {
+        DirectSpellChecker {
+            max_edits: MAXIMUM_SUPPORTED_DISTANCE,
+            min_prefix: 1,
+            accuracy: DEFAULT_ACCURACY,
+            threshold_frequency: 0.0,
+            min_query_length: 4,
+            max_query_frequency: 0.01,
+            lower_case_terms: true,
+        }
+    }
+}
+
+impl DirectSpellChecker {
+    pub fn max_edits(&self) -> u32 {
+        self.max_edits
+    }
+
+    /// Sets the maximum number of Levenshtein edit-distance to consider when
+    /// enumerating terms, this can only be 1 or 2.
+    pub fn set_max_edits(&mut self, max_edits: u32) -> Result<()> {
+        if max_edits < 1 || max_edits > MAXIMUM_SUPPORTED_DISTANCE {
+            bail!(IllegalArgument(format!(
+                "invalid max_edits {}, must be 1 or 2",
+                max_edits
+            )));
+        }
+        self.max_edits = max_edits;
+        Ok(())
+    }
+
+    pub fn min_prefix(&self) -> usize {
+        self.min_prefix
+    }
+
+    /// Sets the minimal number of initial characters that must match exactly,
+    /// which greatly reduces the number of terms to enumerate.
+    pub fn set_min_prefix(&mut self, min_prefix: usize) {
+        self.min_prefix = min_prefix;
+    }
+
+    pub fn accuracy(&self) -> f32 {
+        self.accuracy
+    }
+
+    /// Sets the minimal accuracy required (from 0-1) for a suggestion match.
+    pub fn set_accuracy(&mut self, accuracy: f32) {
+        self.accuracy = accuracy;
+    }
+
+    pub fn threshold_frequency(&self) -> f32 {
+        self.threshold_frequency
+    }
+
+    /// Sets the minimal threshold of documents a term must appear in for it to
+    /// be suggested. This can be specified either as a fraction of documents
+    /// (less than 1) or as an absolute number of documents (1 or more).
+    pub fn set_threshold_frequency(&mut self, threshold_frequency: f32) -> Result<()> {
+        if threshold_frequency >= 1.0 && threshold_frequency.fract() != 0.0 {
+            bail!(IllegalArgument(
+                "fractional absolute document frequencies are not allowed".into()
+            ));
+        }
+        self.threshold_frequency = threshold_frequency;
+        Ok(())
+    }
+
+    pub fn min_query_length(&self) -> usize {
+        self.min_query_length
+    }
+
+    /// Sets the minimum length of a query term needed to return suggestions.
+    pub fn set_min_query_length(&mut self, min_query_length: usize) {
+        self.min_query_length = min_query_length;
+    }
+
+    pub fn max_query_frequency(&self) -> f32 {
+        self.max_query_frequency
+    }
+
+    /// Sets the maximum threshold of documents a query term can appear in order
+    /// to provide suggestions, more frequent terms are assumed to be spelled
+    /// correctly. This can be specified either as a fraction of documents (less
+    /// than 1) or as an absolute number of documents (1 or more).
+    pub fn set_max_query_frequency(&mut self, max_query_frequency: f32) -> Result<()> {
+        if max_query_frequency >= 1.0 && max_query_frequency.fract() != 0.0 {
+            bail!(IllegalArgument(
+                "fractional absolute document frequencies are not allowed".into()
+            ));
+        }
+        self.max_query_frequency = max_query_frequency;
+        Ok(())
+    }
+
+    pub fn lower_case_terms(&self) -> bool {
+        self.lower_case_terms
+    }
+
+    /// True if the spellchecker should lowercase terms before checking them.
+    pub fn set_lower_case_terms(&mut self, lower_case_terms: bool) {
+        self.lower_case_terms = lower_case_terms;
+    }
+
+    /// Suggest similar words for `term`, at most `num_sug` suggestions are
+    /// returned, best first.
+    pub fn suggest_similar<IR: IndexReader + ?Sized>(
+        &self,
+        term: &Term,
+        num_sug: usize,
+        reader: &IR,
+        mode: SuggestMode,
+    ) -> Result<Vec<SuggestWord>> {
+        let mut text = term.text()?;
+        if self.lower_case_terms {
+            text = text.to_lowercase();
+        }
+        let text_len = text.chars().count();
+        if num_sug == 0 || text_len < self.min_query_length {
+            return Ok(Vec::new());
+        }
+
+        let terms = match get_terms(reader, &term.field)? {
+            Some(terms) => terms,
+            None => return Ok(Vec::new()),
+        };
+        let mut iter = terms.iterator()?;
+
+        let mut doc_freq = if iter.seek_exact(text.as_bytes())? {
+            iter.doc_freq()?
+        } else {
+            0
+        };
+        if mode == SuggestMode::SuggestWhenNotInIndex && doc_freq > 0 {
+            return Ok(Vec::new());
+        }
+
+        let max_doc = reader.max_doc();
+        if self.max_query_frequency >= 1.0 {
+            if doc_freq as f32 > self.max_query_frequency {
+                return Ok(Vec::new());
+            }
+        } else if doc_freq as f32 > self.max_query_frequency * max_doc as f32 {
+            return Ok(Vec::new());
+        }
+
+        if mode != SuggestMode::SuggestMorePopular {
+            doc_freq = 0;
+        }
+        if self.threshold_frequency >= 1.0 {
+            doc_freq = doc_freq.max(self.threshold_frequency as i32);
+        } else if self.threshold_frequency > 0.0 {
+            doc_freq = doc_freq.max((self.threshold_frequency * max_doc as f32) as i32 - 1);
+        }
+
+        let prefix_len = text
+            .char_indices()
+            .nth(self.min_prefix)
+            .map_or(text.len(), |(i, _)| i);
+        let automaton = LevenshteinAutomaton::new(&text[prefix_len..], self.max_edits, true);
+        let mut suggestions =
+            self.collect(&mut iter, &text, prefix_len, text_len, &automaton, doc_freq)?;
+
+        suggestions.sort_by(|a, b| match b.score.partial_cmp(&a.score) {
+            Some(Ordering::Equal) | None => {
+                b.freq.cmp(&a.freq).then_with(|| a.string.cmp(&b.string))
+            }
+            Some(o) => o,
+        });
+        suggestions.truncate(num_sug);
+        Ok(suggestions)
+    }
+
+    /// Intersects the terms sharing the first `prefix_len` bytes of `text` with
+    /// `automaton`, skipping every term below a prefix the automaton rejects.
+    fn collect<T: TermIterator>(
+        &self,
+        iter: &mut T,
+        text: &str,
+        prefix_len: usize,
+        text_len: usize,
+        automaton: &LevenshteinAutomaton,
+        doc_freq: i32,
+    ) -> Result<Vec<SuggestWord>> {
+        let mut suggestions = Vec::new();
+        let prefix = &text.as_bytes()[..prefix_len];
+        if iter.seek_ceil(prefix)? == SeekStatus::End {
+            return Ok(suggestions);
+        }
+
+        let prefix_chars = text[..prefix_len].chars().count();
+        // states[i] is the state after the first i chars of the current term's
+        // suffix, all of them can still match.
+        let mut states: Vec<LevenshteinState> = vec![automaton.start()];
+        let mut chars: Vec<char> = Vec::new();
+        loop {
+            let mut seek_to = None;
+            let mut accepted = false;
+            {
+                let term = iter.term()?;
+                if !term.starts_with(prefix) {
+                    break;
+                }
+                if let Ok(suffix) = str::from_utf8(&term[prefix_len..]) {
+                    let common = chars
+                        .iter()
+                        .zip(suffix.chars())
+                        .take_while(|(a, b)| **a == *b)
+                        .count();
+                    states.truncate(common + 1);
+                    chars.truncate(common);
+                    for c in suffix.chars().skip(common) {
+                        let state = automaton.step(&states[states.len() - 1], c);
+                        if !automaton.can_match(&state) {
+                            seek_to = Some(Self::next_prefix(prefix, &chars, c));
+                            break;
+                        }
+                        states.push(state);
+                        chars.push(c);
+                    }
+                    accepted = seek_to.is_none()
+                        && automaton.is_match(&states[states.len() - 1])
+                        && term != text.as_bytes();
+                }
+            }
+
+            if let Some(Some(target)) = seek_to {
+                if iter.seek_ceil(&target)? == SeekStatus::End {
+                    break;
+                }
+                continue;
+            }
+            if accepted {
+                let freq = iter.doc_freq()?;
+                if freq > doc_freq {
+                    let edits = automaton.distance(&states[states.len() - 1]);
+                    let len = text_len.min(prefix_chars + chars.len());
+                    let score = 1.0 - edits as f32 / len as f32;
+                    if score >= self.accuracy {
+                        suggestions.push(SuggestWord {
+                            string: String::from_utf8(iter.term()?.to_vec())?,
+                            freq,
+                            score,
+                        });
+                    }
+                }
+            }
+            if iter.next()?.is_none() {
+                break;
+            }
+        }
+        Ok(suggestions)
+    }
+
+    /// Returns the smallest term greater than every term starting with
+    /// `prefix + chars + dead`, None if there is no such term.
+    fn next_prefix(prefix: &[u8], chars: &[char], dead: char) -> Option<Vec<u8>> {
+        let next = match dead as u32 + 1 {
+            0xD800 => Some('\u{E000}'),
+            c => char::from_u32(c),
+        }?;
+        let mut target = prefix.to_vec();
+        let mut buf = [0u8; 4];
+        for c in chars.iter().chain(Some(&next)) {
+            target.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+        }
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::{Analyzer, SimpleAnalyzer};
+    use core::doc::{Field, FieldType};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexOptions, IndexWriter};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn document(body: &str) -> Vec<Box<dyn Fieldable>> {
+        let mut body_type = FieldType::default();
+        body_type.index_options = IndexOptions::DocsAndFreqs;
+        let tokens = SimpleAnalyzer::default()
+            .token_stream("body", body)
+            .unwrap();
+        vec![Box::new(Field::new(
+            "body".into(),
+            body_type,
+            Some(VariantValue::VString(body.to_string())),
+            Some(tokens),
+        ))]
+    }
+
+    fn words(suggestions: &[SuggestWord]) -> Vec<(&str, i32)> {
+        suggestions
+            .iter()
+            .map(|s| (s.string.as_str(), s.freq))
+            .collect()
+    }
+
+    #[test]
+    fn test_suggest_similar() {
+        let path = ::std::env::temp_dir().join("rucene_direct_spell_checker");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let bodies = [
+            "lucene search",
+            "lucene index",
+            "lucene fast",
+            "lucent",
+            "licence",
+            "blucene",
+        ];
+        for body in &bodies {
+            writer.add_document(document(body)).unwrap();
+        }
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let term = |text: &str| Term::new("body".into(), text.as_bytes().to_vec());
+
+        // "licence" is 3 edits away and "blucene" doesn't share the first char
+        let mut checker = DirectSpellChecker::default();
+        let suggestions = checker
+            .suggest_similar(&term("lucenx"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert_eq!(words(&suggestions), vec![("lucene", 3), ("lucent", 1)]);
+        assert!((suggestions[0].score - (1.0 - 1.0 / 6.0)).abs() < 1e-6);
+        assert_eq!(suggestions[0].score, suggestions[1].score);
+
+        // without min prefix "blucene" is 2 edits away
+        checker.set_min_prefix(0);
+        let suggestions = checker
+            .suggest_similar(&term("Lucenx"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert_eq!(
+            words(&suggestions),
+            vec![("lucene", 3), ("lucent", 1), ("blucene", 1)]
+        );
+        checker.set_max_edits(1).unwrap();
+        assert!(checker.set_max_edits(3).is_err());
+        let suggestions = checker
+            .suggest_similar(&term("lucenx"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert_eq!(words(&suggestions), vec![("lucene", 3), ("lucent", 1)]);
+
+        // only the terms in more than 2 docs
+        checker.set_threshold_frequency(2.0).unwrap();
+        let suggestions = checker
+            .suggest_similar(&term("lucenx"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert_eq!(words(&suggestions), vec![("lucene", 3)]);
+        assert!(checker.set_threshold_frequency(1.5).is_err());
+
+        // an indexed term only gets suggestions if it's rare enough
+        let mut checker = DirectSpellChecker::default();
+        let suggestions = checker
+            .suggest_similar(&term("lucent"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert!(suggestions.is_empty());
+        checker.set_max_query_frequency(1.0).unwrap();
+        let suggestions = checker
+            .suggest_similar(&term("lucent"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert_eq!(words(&suggestions), vec![("lucene", 3)]);
+        let suggestions = checker
+            .suggest_similar(
+                &term("lucent"),
+                10,
+                &reader,
+                SuggestMode::SuggestWhenNotInIndex,
+            )
+            .unwrap();
+        assert!(suggestions.is_empty());
+
+        // too short
+        let suggestions = checker
+            .suggest_similar(&term("luc"), 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert!(suggestions.is_empty());
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,132 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// A Levenshtein automaton accepting every string within `max_edits` edits of
+/// the query string.
+///
+/// The automaton is not materialized, its states are the rows of the edit
+/// distance matrix which are computed lazily as characters are consumed. This
+/// makes it cheap to intersect with a sorted term dictionary: the states of a
+/// shared prefix are reused, and as soon as a state can no longer reach an
+/// accepting state the whole subtree of terms sharing that prefix is skipped.
+///
+/// When `transpositions` is true, swapping two adjacent characters counts as a
+/// single edit (Damerau-Levenshtein, optimal string alignment).
+pub struct LevenshteinAutomaton {
+    query: Vec<char>,
+    max_edits: u32,
+    transpositions: bool,
+}
+
+/// A state of `LevenshteinAutomaton`.
+#[derive(Debug, Clone)]
+pub struct LevenshteinState {
+    row: Vec<u32>,
+    prev_row: Vec<u32>,
+    last_char: Option<char>,
+}
+
+impl LevenshteinAutomaton {
+    pub fn new(query: &str, max_edits: u32, transpositions: bool) -> Self {
+        LevenshteinAutomaton {
+            query: query.chars().collect(),
+            max_edits,
+            transpositions,
+        }
+    }
+
+    pub fn max_edits(&self) -> u32 {
+        self.max_edits
+    }
+
+    pub fn start(&self) -> LevenshteinState {
+        LevenshteinState {
+            row: (0..=self.query.len() as u32).collect(),
+            prev_row: Vec::with_capacity(0),
+            last_char: None,
+        }
+    }
+
+    /// Returns the state reached after consuming `c` from `state`.
+    pub fn step(&self, state: &LevenshteinState, c: char) -> LevenshteinState {
+        let mut row = Vec::with_capacity(state.row.len());
+        row.push(state.row[0] + 1);
+        for (i, &q) in self.query.iter().enumerate() {
+            let cost = if q == c { 0 } else { 1 };
+            let mut value = (state.row[i] + cost)
+                .min(state.row[i + 1] + 1)
+                .min(row[i] + 1);
+            if self.transpositions && i > 0 {
+                if let Some(last) = state.last_char {
+                    if c == self.query[i - 1] && last == q {
+                        value = value.min(state.prev_row[i - 1] + 1);
+                    }
+                }
+            }
+            row.push(value);
+        }
+        LevenshteinState {
+            row,
+            prev_row: state.row.clone(),
+            last_char: Some(c),
+        }
+    }
+
+    /// Returns the state reached after consuming all characters of `text`.
+    pub fn run(&self, text: &str) -> LevenshteinState {
+        text.chars()
+            .fold(self.start(), |state, c| self.step(&state, c))
+    }
+
+    /// Whether the input consumed so far is accepted.
+    pub fn is_match(&self, state: &LevenshteinState) -> bool {
+        self.distance(state) <= self.max_edits
+    }
+
+    /// Whether some continuation of the input consumed so far may be accepted.
+    pub fn can_match(&self, state: &LevenshteinState) -> bool {
+        state.row.iter().any(|&d| d <= self.max_edits)
+    }
+
+    /// The edit distance between the query and the input consumed so far.
+    pub fn distance(&self, state: &LevenshteinState) -> u32 {
+        state.row[self.query.len()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein_automaton() {
+        let automaton = LevenshteinAutomaton::new("lucene", 1, false);
+        assert!(automaton.is_match(&automaton.run("lucene")));
+        assert!(automaton.is_match(&automaton.run("lucen")));
+        assert!(automaton.is_match(&automaton.run("lucenes")));
+        assert!(automaton.is_match(&automaton.run("lucine")));
+        assert!(!automaton.is_match(&automaton.run("ulcene")));
+        assert!(!automaton.is_match(&automaton.run("lucinex")));
+
+        let state = automaton.run("xy");
+        assert!(!automaton.can_match(&state));
+        let state = automaton.run("lx");
+        assert!(automaton.can_match(&state));
+        assert!(!automaton.is_match(&state));
+
+        let automaton = LevenshteinAutomaton::new("lucene", 1, true);
+        assert_eq!(automaton.distance(&automaton.run("ulcene")), 1);
+        assert_eq!(automaton.distance(&automaton.run("lucnee")), 1);
+        assert_eq!(automaton.distance(&automaton.run("lucnex")), 2);
+    }
+}
@@ -0,0 +1,50 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Spell checking: suggests corrections of query terms from the terms of an index.
+
+mod direct_spell_checker;
+
+pub use self::direct_spell_checker::*;
+
+mod levenshtein_automaton;
+
+pub use self::levenshtein_automaton::*;
+
+mod word_break_spell_checker;
+
+pub use self::word_break_spell_checker::*;
+
+/// Set of strategies for suggesting related terms.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestMode {
+    /// Generate suggestions only for terms not in the index.
+    SuggestWhenNotInIndex,
+    /// Return only suggested words that are as frequent or more frequent than the
+    /// searched word.
+    SuggestMorePopular,
+    /// Always attempt to offer suggestions (however, other parameters may limit
+    /// suggestions. For example, see `DirectSpellChecker::set_max_query_frequency`).
+    SuggestAlways,
+}
+
+/// A spelling correction suggested for a word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestWord {
+    /// the suggested word
+    pub string: String,
+    /// the doc freq of the suggested word
+    pub freq: i32,
+    /// the score of the word, higher is better
+    pub score: f32,
+}
@@ -0,0 +1,488 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::index::{get_terms, IndexReader, Term, TermIterator, Terms};
+use core::suggest::spell::{SuggestMode, SuggestWord};
+
+use error::Result;
+
+use std::cmp::Ordering;
+
+/// Determines the order of the list of word break suggestions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakSuggestionSortMethod {
+    /// Sort by number of changes, then by the sum of the frequencies of the
+    /// broken words.
+    NumChangesThenSummedFrequency,
+    /// Sort by number of changes, then by the frequency of the most frequent
+    /// broken word.
+    NumChangesThenMaxFrequency,
+}
+
+/// A suggestion to replace several consecutive terms of a query by a single
+/// word.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CombineSuggestion {
+    /// the indexes of the combined terms in the original term list
+    pub original_term_indexes: Vec<usize>,
+    /// the replacement, its score is the number of changes
+    pub suggestion: SuggestWord,
+}
+
+/// A spell checker whose suggestions break a term into several words (e.g.
+/// `"lucenesearch"` to `"lucene search"`) or combine consecutive terms into a
+/// single word (e.g. `"lu cene"` to `"lucene"`), using the frequencies of the
+/// terms in the index to decide which words exist.
+///
+/// It complements `DirectSpellChecker` which only suggests words within an
+/// edit distance of a single term.
+pub struct WordBreakSpellChecker {
+    min_suggestion_frequency: i32,
+    max_changes: usize,
+    max_combine_word_length: usize,
+    min_break_word_length: usize,
+    max_evaluations: usize,
+}
+
+impl Default for WordBreakSpellChecker {
+    fn default() -> Self {
+        WordBreakSpellChecker {
+            min_suggestion_frequency: 1,
+            max_changes: 1,
+            max_combine_word_length: 20,
+            min_break_word_length: 1,
+            max_evaluations: 1000,
+        }
+    }
+}
+
+impl WordBreakSpellChecker {
+    pub fn min_suggestion_frequency(&self) -> i32 {
+        self.min_suggestion_frequency
+    }
+
+    /// Sets the minimum frequency a term must have to be included as part of a
+    /// suggestion.
+    pub fn set_min_suggestion_frequency(&mut self, min_suggestion_frequency: i32) {
+        self.min_suggestion_frequency = min_suggestion_frequency;
+    }
+
+    pub fn max_changes(&self) -> usize {
+        self.max_changes
+    }
+
+    /// Sets the maximum number of changes (word breaks or combinations) a
+    /// single suggestion may have.
+    pub fn set_max_changes(&mut self, max_changes: usize) {
+        self.max_changes = max_changes;
+    }
+
+    pub fn max_combine_word_length(&self) -> usize {
+        self.max_combine_word_length
+    }
+
+    /// Sets the maximum length, in chars, of a suggestion made by combining
+    /// one or more original terms.
+    pub fn set_max_combine_word_length(&mut self, max_combine_word_length: usize) {
+        self.max_combine_word_length = max_combine_word_length;
+    }
+
+    pub fn min_break_word_length(&self) -> usize {
+        self.min_break_word_length
+    }
+
+    /// Sets the minimum length, in chars, of a word a term may be broken into.
+    pub fn set_min_break_word_length(&mut self, min_break_word_length: usize) {
+        self.min_break_word_length = min_break_word_length;
+    }
+
+    pub fn max_evaluations(&self) -> usize {
+        self.max_evaluations
+    }
+
+    /// Sets the maximum number of term lookups made for a single call, which
+    /// bounds the cost of breaking long terms.
+    pub fn set_max_evaluations(&mut self, max_evaluations: usize) {
+        self.max_evaluations = max_evaluations;
+    }
+
+    /// Generates a list of suggestions breaking `term` into several words,
+    /// each suggestion being the list of words to replace the term with. At
+    /// most `max_suggestions` suggestions are returned, ordered by
+    /// `sort_method`.
+    pub fn suggest_word_breaks<IR: IndexReader + ?Sized>(
+        &self,
+        term: &Term,
+        max_suggestions: usize,
+        reader: &IR,
+        mode: SuggestMode,
+        sort_method: BreakSuggestionSortMethod,
+    ) -> Result<Vec<Vec<SuggestWord>>> {
+        if max_suggestions == 0 {
+            return Ok(Vec::new());
+        }
+        let terms = match get_terms(reader, &term.field)? {
+            Some(terms) => terms,
+            None => return Ok(Vec::new()),
+        };
+        let mut iter = terms.iterator()?;
+
+        let text = term.text()?;
+        let orig_freq = Self::doc_freq(&mut iter, &text)?;
+        if mode == SuggestMode::SuggestWhenNotInIndex && orig_freq > 0 {
+            return Ok(Vec::new());
+        }
+        let min_freq = if mode == SuggestMode::SuggestMorePopular {
+            orig_freq.max(self.min_suggestion_frequency)
+        } else {
+            self.min_suggestion_frequency
+        };
+
+        let mut suggestions = Vec::new();
+        let mut evaluations = 0;
+        self.generate_breaks(
+            &mut iter,
+            &text,
+            min_freq,
+            &mut Vec::new(),
+            &mut evaluations,
+            &mut suggestions,
+        )?;
+
+        let rank = |words: &[SuggestWord]| match sort_method {
+            BreakSuggestionSortMethod::NumChangesThenSummedFrequency => {
+                words.iter().map(|w| i64::from(w.freq)).sum::<i64>()
+            }
+            BreakSuggestionSortMethod::NumChangesThenMaxFrequency => {
+                words.iter().map(|w| i64::from(w.freq)).max().unwrap_or(0)
+            }
+        };
+        suggestions.sort_by(|a, b| {
+            a.len()
+                .cmp(&b.len())
+                .then_with(|| rank(b).cmp(&rank(a)))
+                .then_with(|| {
+                    let a = a.iter().map(|w| w.string.as_str());
+                    let b = b.iter().map(|w| w.string.as_str());
+                    a.cmp(b)
+                })
+        });
+        suggestions.truncate(max_suggestions);
+        Ok(suggestions)
+    }
+
+    /// Generates a list of suggestions combining two or more consecutive
+    /// `terms` into a single word. At most `max_suggestions` suggestions are
+    /// returned, those with the fewest changes first, then the most frequent.
+    ///
+    /// Only terms of the same field are combined.
+    pub fn suggest_word_combinations<IR: IndexReader + ?Sized>(
+        &self,
+        terms: &[Term],
+        max_suggestions: usize,
+        reader: &IR,
+        mode: SuggestMode,
+    ) -> Result<Vec<CombineSuggestion>> {
+        if max_suggestions == 0 || terms.len() < 2 {
+            return Ok(Vec::new());
+        }
+        let texts = terms
+            .iter()
+            .map(|t| t.text())
+            .collect::<Result<Vec<String>>>()?;
+
+        let mut suggestions = Vec::new();
+        let mut evaluations = 0;
+        'outer: for i in 0..terms.len() {
+            let field_terms = match get_terms(reader, &terms[i].field)? {
+                Some(field_terms) => field_terms,
+                None => continue,
+            };
+            let mut iter = field_terms.iterator()?;
+
+            let mut combined = texts[i].clone();
+            let mut combined_len = combined.chars().count();
+            if combined_len > self.max_combine_word_length {
+                continue;
+            }
+            let mut max_freq = 0;
+            let mut min_freq = i32::max_value();
+            if mode != SuggestMode::SuggestAlways {
+                let freq = Self::doc_freq(&mut iter, &texts[i])?;
+                max_freq = freq;
+                min_freq = freq;
+            }
+
+            for j in i + 1..terms.len().min(i + 1 + self.max_changes) {
+                if terms[j].field != terms[i].field {
+                    break;
+                }
+                combined.push_str(&texts[j]);
+                combined_len += texts[j].chars().count();
+                if combined_len > self.max_combine_word_length {
+                    break;
+                }
+                if mode != SuggestMode::SuggestAlways {
+                    let freq = Self::doc_freq(&mut iter, &texts[j])?;
+                    max_freq = max_freq.max(freq);
+                    min_freq = min_freq.min(freq);
+                }
+
+                let freq = Self::doc_freq(&mut iter, &combined)?;
+                if (mode != SuggestMode::SuggestMorePopular || freq >= max_freq)
+                    && (mode != SuggestMode::SuggestWhenNotInIndex || min_freq == 0)
+                    && freq >= self.min_suggestion_frequency
+                {
+                    suggestions.push(CombineSuggestion {
+                        original_term_indexes: (i..=j).collect(),
+                        suggestion: SuggestWord {
+                            string: combined.clone(),
+                            freq,
+                            score: (j - i) as f32,
+                        },
+                    });
+                }
+
+                evaluations += 1;
+                if evaluations >= self.max_evaluations {
+                    break 'outer;
+                }
+            }
+        }
+
+        suggestions.sort_by(|a, b| {
+            match a.suggestion.score.partial_cmp(&b.suggestion.score) {
+                Some(Ordering::Equal) | None => {}
+                Some(o) => return o,
+            }
+            b.suggestion
+                .freq
+                .cmp(&a.suggestion.freq)
+                .then_with(|| a.original_term_indexes.cmp(&b.original_term_indexes))
+        });
+        suggestions.truncate(max_suggestions);
+        Ok(suggestions)
+    }
+
+    /// Breaks `text` in two at every allowed position, records the breaks whose
+    /// both words are frequent enough and recursively breaks the right word
+    /// while changes are left. `prefix` holds the words already broken off.
+    fn generate_breaks<T: TermIterator>(
+        &self,
+        iter: &mut T,
+        text: &str,
+        min_freq: i32,
+        prefix: &mut Vec<SuggestWord>,
+        evaluations: &mut usize,
+        suggestions: &mut Vec<Vec<SuggestWord>>,
+    ) -> Result<()> {
+        let min_len = self.min_break_word_length.max(1);
+        let text_len = text.chars().count();
+        for (left_len, (i, _)) in text.char_indices().enumerate() {
+            if left_len < min_len {
+                continue;
+            }
+            if text_len - left_len < min_len || *evaluations >= self.max_evaluations {
+                break;
+            }
+            *evaluations += 1;
+
+            let (left, right) = text.split_at(i);
+            let left_freq = Self::doc_freq(iter, left)?;
+            if left_freq < min_freq {
+                continue;
+            }
+            prefix.push(Self::suggest_word(left, left_freq));
+
+            let right_freq = Self::doc_freq(iter, right)?;
+            if right_freq >= min_freq {
+                let mut words = prefix.clone();
+                words.push(Self::suggest_word(right, right_freq));
+                suggestions.push(words);
+            }
+            if prefix.len() < self.max_changes {
+                self.generate_breaks(iter, right, min_freq, prefix, evaluations, suggestions)?;
+            }
+            prefix.pop();
+        }
+        Ok(())
+    }
+
+    fn suggest_word(text: &str, freq: i32) -> SuggestWord {
+        SuggestWord {
+            string: text.to_string(),
+            freq,
+            score: 1.0,
+        }
+    }
+
+    fn doc_freq<T: TermIterator>(iter: &mut T, text: &str) -> Result<i32> {
+        if iter.seek_exact(text.as_bytes())? {
+            iter.doc_freq()
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::analysis::{Analyzer, SimpleAnalyzer};
+    use core::doc::{Field, FieldType};
+    use core::index::index_writer_config::IndexWriterConfig;
+    use core::index::{Fieldable, IndexOptions, IndexWriter};
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use core::util::VariantValue;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn document(body: &str) -> Vec<Box<dyn Fieldable>> {
+        let mut body_type = FieldType::default();
+        body_type.index_options = IndexOptions::DocsAndFreqs;
+        let tokens = SimpleAnalyzer::default()
+            .token_stream("body", body)
+            .unwrap();
+        vec![Box::new(Field::new(
+            "body".into(),
+            body_type,
+            Some(VariantValue::VString(body.to_string())),
+            Some(tokens),
+        ))]
+    }
+
+    fn term(text: &str) -> Term {
+        Term::new("body".into(), text.as_bytes().to_vec())
+    }
+
+    fn words(suggestion: &[SuggestWord]) -> Vec<(&str, i32)> {
+        suggestion
+            .iter()
+            .map(|s| (s.string.as_str(), s.freq))
+            .collect()
+    }
+
+    #[test]
+    fn test_break_and_combine() {
+        let path = ::std::env::temp_dir().join("rucene_word_break_spell_checker");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        let writer = IndexWriter::new(dir, Arc::new(IndexWriterConfig::default())).unwrap();
+        let bodies = [
+            "lucene search",
+            "lucene index",
+            "lucene",
+            "search engine",
+            "luc",
+            "ene",
+        ];
+        for body in &bodies {
+            writer.add_document(document(body)).unwrap();
+        }
+        writer.commit().unwrap();
+        let reader = writer.get_reader(true, false).unwrap();
+        let sort = BreakSuggestionSortMethod::NumChangesThenSummedFrequency;
+
+        let mut checker = WordBreakSpellChecker::default();
+        let breaks = checker
+            .suggest_word_breaks(
+                &term("lucenesearch"),
+                10,
+                &reader,
+                SuggestMode::SuggestAlways,
+                sort,
+            )
+            .unwrap();
+        assert_eq!(breaks.len(), 1);
+        assert_eq!(words(&breaks[0]), vec![("lucene", 3), ("search", 2)]);
+
+        // two changes also break "lucene", with fewer changes first
+        checker.set_max_changes(2);
+        let breaks = checker
+            .suggest_word_breaks(
+                &term("lucenesearch"),
+                10,
+                &reader,
+                SuggestMode::SuggestAlways,
+                sort,
+            )
+            .unwrap();
+        assert_eq!(breaks.len(), 2);
+        assert_eq!(words(&breaks[0]), vec![("lucene", 3), ("search", 2)]);
+        assert_eq!(
+            words(&breaks[1]),
+            vec![("luc", 1), ("ene", 1), ("search", 2)]
+        );
+
+        checker.set_min_break_word_length(4);
+        let breaks = checker
+            .suggest_word_breaks(
+                &term("lucenesearch"),
+                10,
+                &reader,
+                SuggestMode::SuggestAlways,
+                sort,
+            )
+            .unwrap();
+        assert_eq!(breaks.len(), 1);
+
+        // an indexed term isn't broken
+        let breaks = checker
+            .suggest_word_breaks(
+                &term("lucene"),
+                10,
+                &reader,
+                SuggestMode::SuggestWhenNotInIndex,
+                sort,
+            )
+            .unwrap();
+        assert!(breaks.is_empty());
+
+        let checker = WordBreakSpellChecker::default();
+        let terms = [term("luc"), term("ene"), term("search")];
+        let combinations = checker
+            .suggest_word_combinations(&terms, 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert_eq!(
+            combinations,
+            vec![CombineSuggestion {
+                original_term_indexes: vec![0, 1],
+                suggestion: SuggestWord {
+                    string: "lucene".into(),
+                    freq: 3,
+                    score: 1.0,
+                },
+            }]
+        );
+        // "lucene" is more frequent than both of its parts
+        let combinations = checker
+            .suggest_word_combinations(&terms, 10, &reader, SuggestMode::SuggestMorePopular)
+            .unwrap();
+        assert_eq!(combinations.len(), 1);
+        // both parts are indexed
+        let combinations = checker
+            .suggest_word_combinations(&terms, 10, &reader, SuggestMode::SuggestWhenNotInIndex)
+            .unwrap();
+        assert!(combinations.is_empty());
+        // terms of different fields are not combined
+        let terms = [term("luc"), Term::new("other".into(), b"ene".to_vec())];
+        let combinations = checker
+            .suggest_word_combinations(&terms, 10, &reader, SuggestMode::SuggestAlways)
+            .unwrap();
+        assert!(combinations.is_empty());
+
+        drop(reader);
+        writer.close().unwrap();
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::mem;
+use std::sync::Arc;
+
+/// An object whose memory usage can be computed, so that applications can
+/// report and bound the memory used by the readers, the caches and the
+/// indexing buffers.
+///
+/// The usage is an estimate of the heap memory held by the object, the
+/// memory mapped files and the memory shared with other objects, e.g. the
+/// `Arc`ed readers of a segment, are not counted.
+pub trait Accountable {
+    /// The estimated number of heap bytes used by this object.
+    fn ram_bytes_used(&self) -> usize;
+
+    /// The usage of the resources of this object, if it's made of several
+    /// ones, to break down its `ram_bytes_used`, e.g. per field.
+    fn child_resources(&self) -> Vec<RamUsage> {
+        vec![]
+    }
+}
+
+impl<T: Accountable + ?Sized> Accountable for Arc<T> {
+    fn ram_bytes_used(&self) -> usize {
+        self.as_ref().ram_bytes_used()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.as_ref().child_resources()
+    }
+}
+
+impl<T: Accountable + ?Sized> Accountable for Box<T> {
+    fn ram_bytes_used(&self) -> usize {
+        self.as_ref().ram_bytes_used()
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.as_ref().child_resources()
+    }
+}
+
+/// The number of heap bytes allocated by `v`.
+pub fn vec_ram_bytes<T>(v: &Vec<T>) -> usize {
+    v.capacity() * mem::size_of::<T>()
+}
+
+/// A snapshot of the memory usage of a named resource and of its children,
+/// printed as a tree, e.g. `segment _0: 1.2 KB` followed by the indented
+/// usage of its postings and doc values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RamUsage {
+    pub name: String,
+    pub ram_bytes_used: usize,
+    pub children: Vec<RamUsage>,
+}
+
+impl RamUsage {
+    pub fn new<S: Into<String>>(name: S, ram_bytes_used: usize) -> RamUsage {
+        RamUsage {
+            name: name.into(),
+            ram_bytes_used,
+            children: vec![],
+        }
+    }
+
+    /// Takes a snapshot of the usage of `resource` and of its children.
+    pub fn of<S: Into<String>, A: Accountable + ?Sized>(name: S, resource: &A) -> RamUsage {
+        RamUsage {
+            name: name.into(),
+            ram_bytes_used: resource.ram_bytes_used(),
+            children: resource.child_resources(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        for _ in 0..depth {
+            write!(f, "    ")?;
+        }
+        if depth > 0 {
+            write!(f, "|-- ")?;
+        }
+        writeln!(
+            f,
+            "{}: {}",
+            self.name,
+            human_readable_bytes(self.ram_bytes_used)
+        )?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Accountable for RamUsage {
+    fn ram_bytes_used(&self) -> usize {
+        self.ram_bytes_used
+    }
+
+    fn child_resources(&self) -> Vec<RamUsage> {
+        self.children.clone()
+    }
+}
+
+impl fmt::Display for RamUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Formats a number of bytes with the largest unit keeping it above 1,
+/// e.g. `1.5 MB`.
+pub fn human_readable_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Leaf(Vec<u64>);
+
+    impl Accountable for Leaf {
+        fn ram_bytes_used(&self) -> usize {
+            vec_ram_bytes(&self.0)
+        }
+    }
+
+    struct Parent(Vec<Leaf>);
+
+    impl Accountable for Parent {
+        fn ram_bytes_used(&self) -> usize {
+            self.0.iter().map(Accountable::ram_bytes_used).sum()
+        }
+
+        fn child_resources(&self) -> Vec<RamUsage> {
+            self.0
+                .iter()
+                .enumerate()
+                .map(|(i, leaf)| RamUsage::of(format!("leaf {}", i), leaf))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_ram_usage_tree() {
+        let parent = Arc::new(Parent(vec![
+            Leaf(Vec::with_capacity(16)),
+            Leaf(Vec::with_capacity(256)),
+        ]));
+        assert_eq!(parent.ram_bytes_used(), 16 * 8 + 256 * 8);
+
+        let usage = RamUsage::of("parent", &parent);
+        assert_eq!(usage.children.len(), 2);
+        assert_eq!(usage.children[1].ram_bytes_used, 2048);
+        assert_eq!(
+            usage.to_string(),
+            "parent: 2.1 KB\n    |-- leaf 0: 128 bytes\n    |-- leaf 1: 2.0 KB\n"
+        );
+        assert_eq!(human_readable_bytes(3 * 1024 * 1024 / 2), "1.5 MB");
+    }
+}
@@ -15,7 +15,7 @@ use std::sync::Arc;
 
 use core::search::{DocIterator, NO_MORE_DOCS};
 use core::util::bit_util::{self, UnsignedShift};
-use core::util::{Bits, BitsContext, BitsRef};
+use core::util::{vec_ram_bytes, Accountable, Bits, BitsContext, BitsRef};
 
 use error::{ErrorKind, Result};
 
@@ -333,6 +333,12 @@ impl BitSet for FixedBitSet {
     }
 }
 
+impl Accountable for FixedBitSet {
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.bits)
+    }
+}
+
 impl Bits for FixedBitSet {
     #[inline]
     fn get_with_ctx(&self, ctx: BitsContext, index: usize) -> Result<(bool, BitsContext)> {
@@ -17,8 +17,8 @@ use core::store::{ByteArrayDataInput, ByteArrayRef, DataInput, IndexInput};
 use core::util::bkd::DocIdsWriter;
 use core::util::bkd::{
     BKD_CODEC_NAME, BKD_VERSION_COMPRESSED_DOC_IDS, BKD_VERSION_COMPRESSED_VALUES,
-    BKD_VERSION_CURRENT, BKD_VERSION_IMPLICIT_SPLIT_DIM_1D, BKD_VERSION_PACKED_INDEX,
-    BKD_VERSION_START,
+    BKD_VERSION_CURRENT, BKD_VERSION_IMPLICIT_SPLIT_DIM_1D, BKD_VERSION_LEAF_STORES_BOUNDS,
+    BKD_VERSION_PACKED_INDEX, BKD_VERSION_START,
 };
 use core::util::math;
 use core::util::DocId;
@@ -340,6 +340,101 @@ impl BKDReader {
         Ok(())
     }
 
+    /// Estimates the number of points `intersect` would visit, only comparing
+    /// the cells of the inner nodes with the visitor: the points of the leaves
+    /// crossing the query are counted as half full.
+    pub fn estimate_point_count(&self, visitor: &mut impl IntersectVisitor) -> Result<i64> {
+        let mut state = self.create_intersect_state(visitor)?;
+
+        self.estimate_point_count_with_state(
+            &mut state,
+            &self.min_packed_value,
+            &self.max_packed_value,
+        )
+    }
+
+    fn estimate_point_count_with_state<'a, IV: IntersectVisitor + 'a>(
+        &self,
+        state: &mut IntersectState<'a, IV>,
+        cell_min_packed: &[u8],
+        cell_max_packed: &[u8],
+    ) -> Result<i64> {
+        let r = state.visitor.compare(cell_min_packed, cell_max_packed);
+
+        if r == Relation::CellOutsideQuery {
+            Ok(0)
+        } else if r == Relation::CellInsideQuery {
+            let num_leaves = self.num_leaves_of(state.index_tree.node_id());
+            Ok((self.max_points_in_leaf_node * num_leaves) as i64)
+        } else if state.index_tree.is_leaf_node() {
+            Ok((self.max_points_in_leaf_node as i64 + 1) / 2)
+        } else {
+            // same split as `intersect_with_state`
+            let split_dim = state.index_tree.split_dim() as usize;
+            let split_packed_value_idx = state.index_tree.split_packed_value_index();
+            let mut split_dim_value = state.index_tree.split_dim_value();
+            let dim_start = split_dim * self.bytes_per_dim;
+            let dim_end = dim_start + self.bytes_per_dim;
+
+            state.index_tree.set_split_packed_value(
+                split_packed_value_idx,
+                0,
+                &cell_max_packed[0..self.packed_bytes_length],
+            );
+            state.index_tree.set_split_packed_value(
+                split_packed_value_idx,
+                dim_start,
+                &split_dim_value,
+            );
+            let mut split_packed_value = state.index_tree.split_packed_value();
+
+            state.index_tree.push_left()?;
+            let left_cost =
+                self.estimate_point_count_with_state(state, cell_min_packed, &split_packed_value)?;
+            state.index_tree.pop();
+
+            // Restore the split dim value since it may have been overwritten while recursing:
+            split_dim_value[0..self.bytes_per_dim]
+                .copy_from_slice(&split_packed_value[dim_start..dim_end]);
+
+            split_packed_value[0..self.packed_bytes_length]
+                .copy_from_slice(&cell_min_packed[0..self.packed_bytes_length]);
+            split_packed_value[dim_start..dim_end]
+                .copy_from_slice(&split_dim_value[0..self.bytes_per_dim]);
+            state
+                .index_tree
+                .set_split_packed_value(split_packed_value_idx, 0, &split_packed_value);
+            state.index_tree.set_split_dim_value(&split_dim_value);
+
+            state.index_tree.push_right()?;
+            let right_cost =
+                self.estimate_point_count_with_state(state, &split_packed_value, cell_max_packed)?;
+            state.index_tree.pop();
+
+            Ok(left_cost + right_cost)
+        }
+    }
+
+    /// Number of leaves below the given node of the tree, the leaves being
+    /// the nodes from `leaf_node_offset` to `2 * leaf_node_offset`.
+    fn num_leaves_of(&self, node_id: i32) -> usize {
+        let mut left_most_leaf = node_id;
+        while left_most_leaf < self.leaf_node_offset {
+            left_most_leaf *= 2;
+        }
+        let mut right_most_leaf = node_id;
+        while right_most_leaf < self.leaf_node_offset {
+            right_most_leaf = right_most_leaf * 2 + 1;
+        }
+        // in the unbalanced case the right most leaf may be one level up
+        let num_leaves = if right_most_leaf >= left_most_leaf {
+            right_most_leaf - left_most_leaf + 1
+        } else {
+            right_most_leaf - left_most_leaf + 1 + self.leaf_node_offset
+        };
+        num_leaves as usize
+    }
+
     pub fn create_intersect_state<'a, IV: IntersectVisitor + 'a>(
         &self,
         visitor: &'a mut IV,
@@ -478,10 +573,31 @@ impl BKDReader {
         count: usize,
         visitor: &mut impl IntersectVisitor,
     ) -> Result<()> {
-        visitor.grow(count);
-
         self.read_common_prefixes(common_prefix_lengths, scratch_packed_value, input)?;
 
+        if self.num_dims != 1 && self.version >= BKD_VERSION_LEAF_STORES_BOUNDS {
+            let mut min_packed_value = scratch_packed_value.to_vec();
+            let mut max_packed_value = scratch_packed_value.to_vec();
+            self.read_min_max(
+                common_prefix_lengths,
+                &mut min_packed_value,
+                &mut max_packed_value,
+                input,
+            )?;
+            match visitor.compare(&min_packed_value, &max_packed_value) {
+                Relation::CellOutsideQuery => return Ok(()),
+                Relation::CellInsideQuery => {
+                    visitor.grow(count);
+                    for doc in doc_ids.iter().take(count) {
+                        visitor.visit(*doc)?;
+                    }
+                    return Ok(());
+                }
+                Relation::CellCrossesQuery => {}
+            }
+        }
+        visitor.grow(count);
+
         let compressed_dim = if self.version < BKD_VERSION_COMPRESSED_VALUES {
             -1
         } else {
@@ -512,6 +628,22 @@ impl BKDReader {
         Ok(())
     }
 
+    fn read_min_max(
+        &self,
+        common_prefix_lengths: &[i32],
+        min_packed_value: &mut [u8],
+        max_packed_value: &mut [u8],
+        input: &mut dyn IndexInput,
+    ) -> Result<()> {
+        for (dim, length) in common_prefix_lengths.iter().enumerate().take(self.num_dims) {
+            let prefix = *length as usize;
+            let offset = dim * self.bytes_per_dim + prefix;
+            input.read_bytes(min_packed_value, offset, self.bytes_per_dim - prefix)?;
+            input.read_bytes(max_packed_value, offset, self.bytes_per_dim - prefix)?;
+        }
+        Ok(())
+    }
+
     // Just read suffixes for every dimension
     fn visit_raw_doc_values(
         &self,
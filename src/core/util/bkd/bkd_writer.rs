@@ -521,7 +521,7 @@ impl<D: Directory> BKDWriter<D> {
         if self.num_dims == 1 {
             self.write_field_1_dim(out, field_name, reader)
         } else {
-            unimplemented!()
+            self.write_field_n_dims(out, field_name, reader)
         }
     }
 
@@ -794,6 +794,286 @@ impl<D: Directory> BKDWriter<D> {
         one_dim_writer.finish()
     }
 
+    /// Builds the tree of a multi dimensional field in heap, reordering the
+    /// points of `reader` in place.
+    fn write_field_n_dims<O: IndexOutput>(
+        &mut self,
+        out: &mut O,
+        field_name: &str,
+        mut reader: impl MutablePointsReader,
+    ) -> Result<i64> {
+        if self.point_count != 0 {
+            bail!(IllegalState("cannot mix add and write_field".into()));
+        }
+
+        // Catch user silliness:
+        if self.heap_point_writer.is_none() && self.temp_input.is_none() {
+            bail!(IllegalState("already finished".into()));
+        }
+
+        // Mark that we already finished:
+        self.heap_point_writer = None;
+
+        self.point_count = reader.size(field_name)?;
+        let mut count_per_leaf = self.point_count;
+        let mut inner_node_count = 1;
+        while count_per_leaf > self.max_points_in_leaf_node as i64 {
+            count_per_leaf = (count_per_leaf + 1) / 2;
+            inner_node_count *= 2;
+        }
+
+        let num_leaves = inner_node_count;
+        self.check_max_leaf_node_count(num_leaves)?;
+
+        let mut split_packed_values = vec![0u8; num_leaves as usize * (1 + self.bytes_per_dim)];
+        let mut leaf_block_fps = vec![0i64; num_leaves as usize];
+
+        // compute the min/max for this slice
+        let mut min_packed_value = vec![0xffu8; self.packed_bytes_length];
+        let mut max_packed_value = vec![0u8; self.packed_bytes_length];
+        let mut packed_value = vec![0u8; self.packed_bytes_length];
+        for i in 0..self.point_count as i32 {
+            reader.value(i, &mut packed_value);
+            for dim in 0..self.num_dims {
+                let offset = dim * self.bytes_per_dim;
+                let end = offset + self.bytes_per_dim;
+                if packed_value[offset..end] < min_packed_value[offset..end] {
+                    min_packed_value[offset..end].copy_from_slice(&packed_value[offset..end]);
+                }
+                if packed_value[offset..end] > max_packed_value[offset..end] {
+                    max_packed_value[offset..end].copy_from_slice(&packed_value[offset..end]);
+                }
+            }
+            self.docs_seen.set(reader.doc_id(i) as usize);
+        }
+        self.min_packed_value = min_packed_value.clone();
+        self.max_packed_value = max_packed_value.clone();
+
+        let mut parent_splits = vec![0i32; self.num_dims];
+        let mut spare_doc_ids = vec![0 as DocId; self.max_points_in_leaf_node as usize];
+        let to = self.point_count as i32;
+        self.build_in_heap(
+            1,
+            num_leaves,
+            &mut reader,
+            0,
+            to,
+            out,
+            &min_packed_value,
+            &max_packed_value,
+            &mut parent_splits,
+            &mut split_packed_values,
+            &mut leaf_block_fps,
+            &mut spare_doc_ids,
+        )?;
+
+        let index_fp = out.file_pointer();
+        self.write_index(out, &leaf_block_fps, &mut split_packed_values)?;
+
+        Ok(index_fp)
+    }
+
+    /// Recursively reorders the points of `reader` and writes the tree on the fly.
+    #[allow(too_many_arguments)]
+    fn build_in_heap<P: MutablePointsReader>(
+        &mut self,
+        node_id: i32,
+        leaf_node_offset: i32,
+        reader: &mut P,
+        from: i32,
+        to: i32,
+        out: &mut impl IndexOutput,
+        min_packed_value: &[u8],
+        max_packed_value: &[u8],
+        parent_splits: &mut [i32],
+        split_packed_values: &mut [u8],
+        leaf_block_fps: &mut [i64],
+        spare_doc_ids: &mut [DocId],
+    ) -> Result<()> {
+        if node_id >= leaf_node_offset {
+            // Leaf node
+            let count = (to - from) as usize;
+            debug_assert!(count <= self.max_points_in_leaf_node as usize);
+
+            // Compute common prefixes
+            let mut common_prefix_lengths = vec![self.bytes_per_dim; self.num_dims];
+            let mut first_value = vec![0u8; self.packed_bytes_length];
+            let mut value = vec![0u8; self.packed_bytes_length];
+            reader.value(from, &mut first_value);
+            for i in from + 1..to {
+                reader.value(i, &mut value);
+                for dim in 0..self.num_dims {
+                    let offset = dim * self.bytes_per_dim;
+                    for j in 0..common_prefix_lengths[dim] {
+                        if first_value[offset + j] != value[offset + j] {
+                            common_prefix_lengths[dim] = j;
+                            break;
+                        }
+                    }
+                }
+            }
+
+            // Find the dimension that has the least number of unique bytes at
+            // common_prefix_lengths[dim], which makes compression more efficient
+            let mut used_bytes: Vec<Option<FixedBitSet>> = common_prefix_lengths
+                .iter()
+                .map(|&prefix| {
+                    if prefix < self.bytes_per_dim {
+                        Some(FixedBitSet::new(256))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            for i in from..to {
+                for dim in 0..self.num_dims {
+                    if let Some(ref mut bits) = used_bytes[dim] {
+                        let k = dim * self.bytes_per_dim + common_prefix_lengths[dim];
+                        bits.set(reader.byte_at(i, k as i32) as usize);
+                    }
+                }
+            }
+            let mut sorted_dim = 0;
+            let mut sorted_dim_cardinality = usize::max_value();
+            for (dim, bits) in used_bytes.iter().enumerate() {
+                if let Some(bits) = bits {
+                    let cardinality = bits.cardinality();
+                    if cardinality < sorted_dim_cardinality {
+                        sorted_dim = dim;
+                        sorted_dim_cardinality = cardinality;
+                    }
+                }
+            }
+
+            // sort by sorted_dim
+            let prefixes: Vec<i32> = common_prefix_lengths.iter().map(|&p| p as i32).collect();
+            MutablePointsReaderUtils::sort_by_dim(
+                sorted_dim as i32,
+                self.bytes_per_dim as i32,
+                &prefixes,
+                reader,
+                from,
+                to,
+                &mut self.scratch1,
+                &mut self.scratch2,
+            );
+
+            // Save the block file pointer:
+            leaf_block_fps[(node_id - leaf_node_offset) as usize] = out.file_pointer();
+
+            // Write doc IDs
+            for i in from..to {
+                spare_doc_ids[(i - from) as usize] = reader.doc_id(i);
+            }
+            self.write_leaf_block_docs(out, spare_doc_ids, 0, count)?;
+
+            // Write the common prefixes:
+            reader.value(from, &mut first_value);
+            self.write_common_prefixes(out, &common_prefix_lengths, &mut first_value)?;
+
+            // Write the full values:
+            let values_to_store: Vec<Vec<u8>> = (from..to)
+                .map(|i| {
+                    let mut value = vec![0u8; self.packed_bytes_length];
+                    reader.value(i, &mut value);
+                    value
+                })
+                .collect();
+            let mut values: Vec<&[u8]> = values_to_store.iter().map(|v| v.as_slice()).collect();
+            debug_assert!(self.value_in_order_and_bounds(
+                count,
+                sorted_dim,
+                min_packed_value,
+                max_packed_value,
+                &values,
+                spare_doc_ids,
+            ));
+            self.write_leaf_block_packed_values(
+                out,
+                &mut common_prefix_lengths,
+                count,
+                sorted_dim,
+                &mut values,
+            )?;
+        } else {
+            // Inner node: compute the split dimension and partition around it
+            let split_dim = self.split(min_packed_value, max_packed_value, parent_splits);
+            let mid = (from + to + 1) >> 1;
+
+            let offset = split_dim * self.bytes_per_dim;
+            let end = offset + self.bytes_per_dim;
+            let mut common_prefix_len = self.bytes_per_dim;
+            for i in 0..self.bytes_per_dim {
+                if min_packed_value[offset + i] != max_packed_value[offset + i] {
+                    common_prefix_len = i;
+                    break;
+                }
+            }
+
+            // sorting the slice on the split dim leaves the points lower than the
+            // split value on the left of `mid`, and the others on its right
+            let mut prefixes = vec![0i32; self.num_dims];
+            prefixes[split_dim] = common_prefix_len as i32;
+            MutablePointsReaderUtils::sort_by_dim(
+                split_dim as i32,
+                self.bytes_per_dim as i32,
+                &prefixes,
+                reader,
+                from,
+                to,
+                &mut self.scratch1,
+                &mut self.scratch2,
+            );
+
+            // set the split value
+            let mut split_value = vec![0u8; self.packed_bytes_length];
+            reader.value(mid, &mut split_value);
+            let address = node_id as usize * (1 + self.bytes_per_dim);
+            split_packed_values[address] = split_dim as u8;
+            split_packed_values[address + 1..address + 1 + self.bytes_per_dim]
+                .copy_from_slice(&split_value[offset..end]);
+
+            let mut min_split_packed_value = min_packed_value.to_vec();
+            min_split_packed_value[offset..end].copy_from_slice(&split_value[offset..end]);
+            let mut max_split_packed_value = max_packed_value.to_vec();
+            max_split_packed_value[offset..end].copy_from_slice(&split_value[offset..end]);
+
+            // recurse
+            parent_splits[split_dim] += 1;
+            self.build_in_heap(
+                node_id * 2,
+                leaf_node_offset,
+                reader,
+                from,
+                mid,
+                out,
+                min_packed_value,
+                &max_split_packed_value,
+                parent_splits,
+                split_packed_values,
+                leaf_block_fps,
+                spare_doc_ids,
+            )?;
+            self.build_in_heap(
+                node_id * 2 + 1,
+                leaf_node_offset,
+                reader,
+                mid,
+                to,
+                out,
+                &min_split_packed_value,
+                max_packed_value,
+                parent_splits,
+                split_packed_values,
+                leaf_block_fps,
+                spare_doc_ids,
+            )?;
+            parent_splits[split_dim] -= 1;
+        }
+
+        Ok(())
+    }
+
     fn rotate_to_tree(
         &self,
         node_id: i32,
@@ -1427,9 +1707,9 @@ impl<D: Directory> BKDWriter<D> {
                     let mut cardinality = 1;
                     let mut previous = self.scratch1[offset + prefix];
 
-                    for _i in 1..source.count {
+                    for i in 1..source.count {
                         heap_source
-                            .read_packed_value(source.start as usize + 1, &mut self.scratch2);
+                            .read_packed_value((source.start + i) as usize, &mut self.scratch2);
                         let b = self.scratch2[offset + prefix];
                         debug_assert!(previous <= b);
                         if b != previous {
@@ -1505,13 +1785,7 @@ impl<D: Directory> BKDWriter<D> {
         } else {
             // Inner node: partition/recurse
             let split_dim = if self.num_dims > 1 {
-                let min_packed_value = (&self.min_packed_value) as (*const Vec<u8>);
-                let max_packed_value = (&self.max_packed_value) as (*const Vec<u8>);
-                self.split(
-                    unsafe { &(*min_packed_value) },
-                    unsafe { &(*max_packed_value) },
-                    parent_splits,
-                )
+                self.split(min_packed_value, max_packed_value, parent_splits)
             } else {
                 0
             };
@@ -1528,6 +1802,8 @@ impl<D: Directory> BKDWriter<D> {
                 self.mark_right_tree(right_count, split_dim as i32, source, ord_bitset)?;
             let address = node_id as usize * (1 + self.bytes_per_dim);
             split_packed_values[address] = split_dim as u8;
+            split_packed_values[address + 1..address + 1 + self.bytes_per_dim]
+                .copy_from_slice(&split_value[0..self.bytes_per_dim]);
 
             // Partition all PathSlice that are not the split dim into sorted left and right sets,
             // so we can recurse:
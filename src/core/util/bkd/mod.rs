@@ -51,8 +51,10 @@ pub const BKD_VERSION_COMPRESSED_DOC_IDS: i32 = 1;
 pub const BKD_VERSION_COMPRESSED_VALUES: i32 = 2;
 pub const BKD_VERSION_IMPLICIT_SPLIT_DIM_1D: i32 = 3;
 pub const BKD_VERSION_PACKED_INDEX: i32 = 4;
+// written by Lucene 7, the leaves of multi dimensional trees record their bounds
+pub const BKD_VERSION_LEAF_STORES_BOUNDS: i32 = 5;
 pub const BKD_VERSION_START: i32 = 0;
-pub const BKD_VERSION_CURRENT: i32 = BKD_VERSION_PACKED_INDEX;
+pub const BKD_VERSION_CURRENT: i32 = BKD_VERSION_LEAF_STORES_BOUNDS;
 
 #[derive(PartialOrd, PartialEq)]
 pub enum PointType {
@@ -481,7 +483,7 @@ pub struct DimIntroSorter<'a, 'b, P: MutablePointsReader> {
     pivot_doc: i32,
     pivot: &'a mut Vec<u8>,
     scratch2: &'b mut Vec<u8>,
-    reader: P,
+    reader: &'a mut P,
 }
 
 impl<'a, 'b, P: MutablePointsReader> DimIntroSorter<'a, 'b, P> {
@@ -490,7 +492,7 @@ impl<'a, 'b, P: MutablePointsReader> DimIntroSorter<'a, 'b, P> {
         offset: i32,
         scratch1: &'a mut Vec<u8>,
         scratch2: &'b mut Vec<u8>,
-        reader: P,
+        reader: &'a mut P,
     ) -> Self {
         DimIntroSorter {
             num_bytes_to_compare,
@@ -559,7 +561,7 @@ impl MutablePointsReaderUtils {
         sorted_dim: i32,
         bytes_per_dim: i32,
         common_prefix_lengths: &[i32],
-        reader: impl MutablePointsReader,
+        reader: &mut impl MutablePointsReader,
         from: i32,
         to: i32,
         scratch1: &mut Vec<u8>,
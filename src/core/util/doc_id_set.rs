@@ -18,7 +18,7 @@ use core::search::query_cache::{
 };
 use core::search::{DocIdSet, DocIterator, NO_MORE_DOCS};
 use core::util::bit_set::{FixedBitSet, ImmutableBitSet};
-use core::util::DocId;
+use core::util::{vec_ram_bytes, Accountable, DocId};
 use std::sync::Arc;
 
 pub struct BitDocIdSet<T: ImmutableBitSet> {
@@ -40,6 +40,12 @@ impl<T: ImmutableBitSet> BitDocIdSet<T> {
     }
 }
 
+impl<T: ImmutableBitSet + Accountable> Accountable for BitDocIdSet<T> {
+    fn ram_bytes_used(&self) -> usize {
+        self.set.ram_bytes_used()
+    }
+}
+
 impl<T: ImmutableBitSet + 'static> DocIdSet for BitDocIdSet<T> {
     type Iter = BitSetIterator<T>;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
@@ -114,6 +120,12 @@ impl IntArrayDocIdSet {
     }
 }
 
+impl Accountable for IntArrayDocIdSet {
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.docs)
+    }
+}
+
 impl DocIdSet for IntArrayDocIdSet {
     type Iter = IntArrayDocIterator;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
@@ -184,6 +196,17 @@ pub enum DocIdSetEnum {
     BitDocId(BitDocIdSet<FixedBitSet>),
 }
 
+impl Accountable for DocIdSetEnum {
+    fn ram_bytes_used(&self) -> usize {
+        match self {
+            DocIdSetEnum::ShortArray(s) => s.ram_bytes_used(),
+            DocIdSetEnum::IntArray(s) => s.ram_bytes_used(),
+            DocIdSetEnum::NotDocId(s) => s.ram_bytes_used(),
+            DocIdSetEnum::BitDocId(s) => s.ram_bytes_used(),
+        }
+    }
+}
+
 impl DocIdSet for DocIdSetEnum {
     type Iter = DocIdSetDocIterEnum;
     fn iterator(&self) -> Result<Option<Self::Iter>> {
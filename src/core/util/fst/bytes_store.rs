@@ -13,6 +13,7 @@
 
 use core::store::{DataInput, DataOutput, IndexInput, RandomAccessInput};
 use core::util::fst::BytesReader;
+use core::util::{vec_ram_bytes, Accountable};
 
 use error::{ErrorKind, Result};
 
@@ -92,6 +93,12 @@ impl BytesStore {
     }
 }
 
+impl Accountable for BytesStore {
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.blocks) + self.blocks.iter().map(vec_ram_bytes).sum::<usize>()
+    }
+}
+
 impl DataOutput for BytesStore {}
 
 impl Write for BytesStore {
@@ -20,6 +20,7 @@ use core::util::fst::bytes_store::{BytesStore, StoreBytesReader};
 use core::util::fst::fst_builder::{FstBuilder, Node};
 use core::util::fst::DirectionalBytesReader;
 use core::util::fst::{BytesReader, Output, OutputFactory};
+use core::util::{vec_ram_bytes, Accountable};
 use error::{ErrorKind, Result};
 
 const BIT_FINAL_ARC: u8 = 1;
@@ -997,6 +998,15 @@ impl<F: OutputFactory> FST<F> {
 }
 
 // this should only be used for place holder to avoid Option
+impl<F: OutputFactory> Accountable for FST<F> {
+    fn ram_bytes_used(&self) -> usize {
+        // the cached root arcs outputs are small, only their slots are counted
+        self.bytes_store.ram_bytes_used()
+            + vec_ram_bytes(&self.bytes_array)
+            + vec_ram_bytes(&self.cached_root_arcs)
+    }
+}
+
 impl<F: OutputFactory + Default> Default for FST<F> {
     fn default() -> Self {
         FST {
@@ -0,0 +1,147 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Encoding of geo points and distance computations, a point is encoded as
+//! two 32-bit integers so that it fits in a single 64-bit doc value.
+
+use error::ErrorKind::IllegalArgument;
+use error::Result;
+
+use std::f64;
+
+pub const MIN_LAT: f64 = -90.0;
+pub const MAX_LAT: f64 = 90.0;
+pub const MIN_LON: f64 = -180.0;
+pub const MAX_LON: f64 = 180.0;
+
+/// Mean radius of the earth in meters, as defined by the IUGG.
+pub const EARTH_MEAN_RADIUS_METERS: f64 = 6_371_008.771_4;
+
+const LAT_ENCODE: f64 = (1u64 << 32) as f64 / 180.0;
+const LAT_DECODE: f64 = 1.0 / LAT_ENCODE;
+const LON_ENCODE: f64 = (1u64 << 32) as f64 / 360.0;
+const LON_DECODE: f64 = 1.0 / LON_ENCODE;
+
+pub fn check_latitude(latitude: f64) -> Result<()> {
+    if latitude.is_nan() || latitude < MIN_LAT || latitude > MAX_LAT {
+        bail!(IllegalArgument(format!(
+            "invalid latitude {}, must be in [{}, {}]",
+            latitude, MIN_LAT, MAX_LAT
+        )));
+    }
+    Ok(())
+}
+
+pub fn check_longitude(longitude: f64) -> Result<()> {
+    if longitude.is_nan() || longitude < MIN_LON || longitude > MAX_LON {
+        bail!(IllegalArgument(format!(
+            "invalid longitude {}, must be in [{}, {}]",
+            longitude, MIN_LON, MAX_LON
+        )));
+    }
+    Ok(())
+}
+
+// the largest value below `v`, `v` must be positive
+fn next_down(v: f64) -> f64 {
+    f64::from_bits(v.to_bits() - 1)
+}
+
+/// Quantizes `latitude` to a 32-bit integer, rounding down.
+pub fn encode_latitude(latitude: f64) -> Result<i32> {
+    check_latitude(latitude)?;
+    // the maximum value would overflow
+    let latitude = if latitude == MAX_LAT {
+        next_down(latitude)
+    } else {
+        latitude
+    };
+    Ok((latitude * LAT_ENCODE).floor() as i32)
+}
+
+/// Quantizes `longitude` to a 32-bit integer, rounding down.
+pub fn encode_longitude(longitude: f64) -> Result<i32> {
+    check_longitude(longitude)?;
+    let longitude = if longitude == MAX_LON {
+        next_down(longitude)
+    } else {
+        longitude
+    };
+    Ok((longitude * LON_ENCODE).floor() as i32)
+}
+
+pub fn decode_latitude(encoded: i32) -> f64 {
+    f64::from(encoded) * LAT_DECODE
+}
+
+pub fn decode_longitude(encoded: i32) -> f64 {
+    f64::from(encoded) * LON_DECODE
+}
+
+/// Encodes a point in a single long, the latitude in the upper 32 bits.
+pub fn encode_point(latitude: f64, longitude: f64) -> Result<i64> {
+    let lat = encode_latitude(latitude)?;
+    let lon = encode_longitude(longitude)?;
+    Ok((i64::from(lat) << 32) | i64::from(lon as u32))
+}
+
+/// Decodes a point encoded by `encode_point` into `(latitude, longitude)`.
+pub fn decode_point(encoded: i64) -> (f64, f64) {
+    (
+        decode_latitude((encoded >> 32) as i32),
+        decode_longitude(encoded as i32),
+    )
+}
+
+/// The haversine distance in meters between two points given in degrees.
+pub fn haversin_meters(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let sin_lat = (d_lat / 2.0).sin();
+    let sin_lon = (d_lon / 2.0).sin();
+    let h =
+        sin_lat * sin_lat + lat1.to_radians().cos() * lat2.to_radians().cos() * sin_lon * sin_lon;
+    2.0 * EARTH_MEAN_RADIUS_METERS * h.sqrt().min(1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_point() {
+        for &(lat, lon) in &[
+            (0.0, 0.0),
+            (48.8566, 2.3522),
+            (-33.8688, 151.2093),
+            (MAX_LAT, MAX_LON),
+            (MIN_LAT, MIN_LON),
+        ] {
+            let (dlat, dlon) = decode_point(encode_point(lat, lon).unwrap());
+            assert!(dlat <= lat && lat - dlat < 1e-7);
+            assert!(dlon <= lon && lon - dlon < 1e-7);
+        }
+        assert!(encode_point(90.1, 0.0).is_err());
+        assert!(encode_point(0.0, f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_haversin() {
+        assert!(haversin_meters(1.0, 2.0, 1.0, 2.0).abs() < 1e-9);
+        // paris to london, about 343.5 km
+        let d = haversin_meters(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((d - 343_500.0).abs() < 1_000.0);
+        let d = haversin_meters(0.0, 0.0, 0.0, 180.0);
+        assert!((d - f64::consts::PI * EARTH_MEAN_RADIUS_METERS).abs() < 1e-3);
+    }
+}
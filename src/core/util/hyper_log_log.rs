@@ -0,0 +1,292 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use error::{ErrorKind::IllegalArgument, Result};
+
+use std::cmp::max;
+use std::collections::HashMap;
+
+pub const MIN_PRECISION: u8 = 4;
+pub const MAX_PRECISION: u8 = 18;
+pub const DEFAULT_PRECISION: u8 = 14;
+
+/// precision of the hashes of the sparse representation
+const SPARSE_PRECISION: u8 = 25;
+
+/// Estimates below these thresholds, indexed by `precision - 4`, are computed
+/// with linear counting, which is more accurate for small cardinalities.
+const LINEAR_COUNTING_THRESHOLDS: [f64; 15] = [
+    10.0, 20.0, 40.0, 80.0, 220.0, 400.0, 900.0, 1800.0, 3100.0, 6500.0, 11500.0, 20000.0, 50000.0,
+    120_000.0, 350_000.0,
+];
+
+/// A HyperLogLog++ sketch estimating the number of distinct values added to
+/// it, using `2^precision` bytes at most.
+///
+/// As long as few values are added, the sketch keeps a sparse representation
+/// with a much higher precision, so small cardinalities are close to exact.
+/// It switches to the dense registers once the sparse representation would
+/// use more memory than them. Sketches of the same precision can be merged,
+/// which gives the same sketch as adding all the values to a single one.
+///
+/// The empirical bias correction of HyperLogLog++ is not applied, the
+/// relative error is about `1.04 / sqrt(2^precision)`.
+#[derive(Debug, Clone)]
+pub struct HyperLogLogPlusPlus {
+    precision: u8,
+    // max rho per index at `SPARSE_PRECISION`, until converted to dense
+    sparse: Option<HashMap<u32, u8>>,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLogPlusPlus {
+    pub fn new(precision: u8) -> Result<HyperLogLogPlusPlus> {
+        if precision < MIN_PRECISION || precision > MAX_PRECISION {
+            bail!(IllegalArgument(format!(
+                "precision must be in [{}, {}], got {}",
+                MIN_PRECISION, MAX_PRECISION, precision
+            )));
+        }
+        Ok(HyperLogLogPlusPlus {
+            precision,
+            sparse: Some(HashMap::new()),
+            registers: vec![],
+        })
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        self.sparse.is_some()
+    }
+
+    fn num_registers(&self) -> usize {
+        1 << self.precision
+    }
+
+    /// Adds a value given its 64 bits hash, the hash must be uniformly
+    /// distributed.
+    pub fn add_hash(&mut self, hash: u64) {
+        let converted = if let Some(ref mut sparse) = self.sparse {
+            let (index, rho) = Self::index_and_rho(hash, SPARSE_PRECISION);
+            let entry = sparse.entry(index).or_insert(0);
+            *entry = max(*entry, rho);
+            // a sparse entry takes about 8 bytes, a register 1 byte
+            sparse.len() * 8 > self.num_registers()
+        } else {
+            let (index, rho) = Self::index_and_rho(hash, self.precision);
+            let register = &mut self.registers[index as usize];
+            *register = max(*register, rho);
+            false
+        };
+        if converted {
+            self.to_dense();
+        }
+    }
+
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        self.add_hash(murmur_hash64(bytes, 0));
+    }
+
+    pub fn add_i64(&mut self, value: i64) {
+        self.add_hash(murmur_hash64(&value.to_le_bytes(), 0));
+    }
+
+    /// Splits `hash` into the register index, its `precision` highest bits,
+    /// and the position of the first one bit of the remaining bits.
+    fn index_and_rho(hash: u64, precision: u8) -> (u32, u8) {
+        let index = (hash >> (64 - u32::from(precision))) as u32;
+        let rest = hash << precision;
+        let rho = if rest == 0 {
+            64 - precision + 1
+        } else {
+            rest.leading_zeros() as u8 + 1
+        };
+        (index, rho)
+    }
+
+    fn to_dense(&mut self) {
+        if let Some(sparse) = self.sparse.take() {
+            let mut registers = vec![0u8; self.num_registers()];
+            let shift = SPARSE_PRECISION - self.precision;
+            for (sparse_index, sparse_rho) in sparse {
+                let index = (sparse_index >> shift) as usize;
+                // the bits between both precisions are part of the rest of
+                // the hash at the dense precision
+                let low_bits = sparse_index & ((1u32 << shift) - 1);
+                let rho = if low_bits == 0 {
+                    shift + sparse_rho
+                } else {
+                    (low_bits.leading_zeros() - (32 - u32::from(shift))) as u8 + 1
+                };
+                registers[index] = max(registers[index], rho);
+            }
+            self.registers = registers;
+        }
+    }
+
+    /// Adds all the values of `other` to this sketch.
+    pub fn merge(&mut self, other: &HyperLogLogPlusPlus) -> Result<()> {
+        if self.precision != other.precision {
+            bail!(IllegalArgument(format!(
+                "can't merge sketches of precisions {} and {}",
+                self.precision, other.precision
+            )));
+        }
+        match other.sparse {
+            Some(ref other_sparse) if self.sparse.is_some() => {
+                let too_large = {
+                    let sparse = self.sparse.as_mut().unwrap();
+                    for (&index, &rho) in other_sparse {
+                        let entry = sparse.entry(index).or_insert(0);
+                        *entry = max(*entry, rho);
+                    }
+                    sparse.len() * 8 > self.num_registers()
+                };
+                if too_large {
+                    self.to_dense();
+                }
+            }
+            _ => {
+                self.to_dense();
+                let mut other = other.clone();
+                other.to_dense();
+                for (r, &o) in self.registers.iter_mut().zip(other.registers.iter()) {
+                    *r = max(*r, o);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The estimated number of distinct values.
+    pub fn cardinality(&self) -> u64 {
+        if let Some(ref sparse) = self.sparse {
+            let m = f64::from(1u32 << SPARSE_PRECISION);
+            return linear_counting(m, m - sparse.len() as f64).round() as u64;
+        }
+
+        let m = self.num_registers() as f64;
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &r in &self.registers {
+            sum += 1.0 / (1u64 << r) as f64;
+            if r == 0 {
+                zeros += 1;
+            }
+        }
+        if zeros > 0 {
+            let estimate = linear_counting(m, f64::from(zeros));
+            if estimate <= LINEAR_COUNTING_THRESHOLDS[(self.precision - MIN_PRECISION) as usize] {
+                return estimate.round() as u64;
+            }
+        }
+        (alpha(self.num_registers()) * m * m / sum).round() as u64
+    }
+}
+
+fn linear_counting(m: f64, zeros: f64) -> f64 {
+    m * (m / zeros).ln()
+}
+
+fn alpha(m: usize) -> f64 {
+    match m {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / m as f64),
+    }
+}
+
+/// MurmurHash64A by Austin Appleby.
+pub fn murmur_hash64(bytes: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+
+    let mut h = seed ^ (bytes.len() as u64).wrapping_mul(M);
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = chunk
+            .iter()
+            .rev()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b));
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &b) in tail.iter().enumerate() {
+            h ^= u64::from(b) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relative_error(estimate: u64, actual: u64) -> f64 {
+        (estimate as f64 - actual as f64).abs() / actual as f64
+    }
+
+    #[test]
+    fn test_cardinality() {
+        let mut hll = HyperLogLogPlusPlus::new(DEFAULT_PRECISION).unwrap();
+        for i in 0..1000 {
+            hll.add_i64(i);
+            hll.add_i64(i);
+        }
+        assert!(hll.is_sparse());
+        assert!(relative_error(hll.cardinality(), 1000) < 0.01);
+
+        for i in 1000..100_000 {
+            hll.add_i64(i);
+        }
+        assert!(!hll.is_sparse());
+        assert!(relative_error(hll.cardinality(), 100_000) < 0.03);
+
+        assert!(HyperLogLogPlusPlus::new(3).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut a = HyperLogLogPlusPlus::new(10).unwrap();
+        let mut b = HyperLogLogPlusPlus::new(10).unwrap();
+        let mut all = HyperLogLogPlusPlus::new(10).unwrap();
+        for i in 0..20_000 {
+            let value = format!("value{}", i);
+            if i % 3 == 0 {
+                a.add_bytes(value.as_bytes());
+            } else {
+                b.add_bytes(value.as_bytes());
+            }
+            all.add_bytes(value.as_bytes());
+        }
+        a.merge(&b).unwrap();
+        assert_eq!(a.cardinality(), all.cardinality());
+        assert!(relative_error(a.cardinality(), 20_000) < 0.1);
+
+        let c = HyperLogLogPlusPlus::new(12).unwrap();
+        assert!(a.merge(&c).is_err());
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2019 Zhizhesihai (Beijing) Technology Limited.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A sink for the detailed diagnostics of the `IndexWriter`, its flushes and
+/// its merges, see `IndexWriterConfig::set_info_stream`.
+///
+/// The messages are tagged by the component emitting them:
+/// - `IW`: the `IndexWriter`, e.g. commits, merges start and end
+/// - `DW`: the `DocumentsWriter`, e.g. full flushes and deletes
+/// - `DWPT`: the per thread writers flushing their segment
+/// - `DWFC`: the flush control, e.g. stalls and forced flushes
+/// - `MS`: the merge scheduler
+///
+/// Callers check `is_enabled` before formatting a message, so a disabled
+/// component costs nothing.
+pub trait InfoStream: Send + Sync {
+    /// Returns true if the messages of `component` should be emitted.
+    fn is_enabled(&self, component: &str) -> bool;
+
+    /// Emits a message of `component`, only called if it is enabled.
+    fn message(&self, component: &str, message: &str);
+}
+
+/// An `InfoStream` emitting nothing, the default.
+#[derive(Debug, Default)]
+pub struct NoOutputInfoStream;
+
+impl InfoStream for NoOutputInfoStream {
+    fn is_enabled(&self, _component: &str) -> bool {
+        false
+    }
+
+    fn message(&self, _component: &str, _message: &str) {
+        debug_assert!(
+            false,
+            "message() should not be called when is_enabled returns false"
+        );
+    }
+}
+
+/// An `InfoStream` emitting the messages to the `log` crate, at the info
+/// level, with the component as the target.
+#[derive(Debug, Default)]
+pub struct LoggerInfoStream;
+
+impl InfoStream for LoggerInfoStream {
+    fn is_enabled(&self, _component: &str) -> bool {
+        log_enabled!(::log::Level::Info)
+    }
+
+    fn message(&self, component: &str, message: &str) {
+        info!(target: "rucene::info_stream", "{}: {}", component, message);
+    }
+}
+
+/// An `InfoStream` writing one line per message to `W`, e.g. stdout or a
+/// file, in the form `component id [timestamp ms; thread]: message`, where
+/// `id` tells apart the streams writing to the same output.
+pub struct PrintStreamInfoStream<W: Write + Send> {
+    writer: Mutex<W>,
+    message_id: usize,
+}
+
+static MESSAGE_ID: AtomicUsize = AtomicUsize::new(0);
+
+impl<W: Write + Send> PrintStreamInfoStream<W> {
+    pub fn new(writer: W) -> Self {
+        PrintStreamInfoStream {
+            writer: Mutex::new(writer),
+            message_id: MESSAGE_ID.fetch_add(1, Ordering::AcqRel),
+        }
+    }
+}
+
+impl PrintStreamInfoStream<io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W: Write + Send> InfoStream for PrintStreamInfoStream<W> {
+    fn is_enabled(&self, _component: &str) -> bool {
+        true
+    }
+
+    fn message(&self, component: &str, message: &str) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() * 1000 + u64::from(d.subsec_millis()))
+            .unwrap_or(0);
+        let current = thread::current();
+        let thread_name = match current.name() {
+            Some(name) => name.to_string(),
+            None => format!("{:?}", current.id()),
+        };
+        if let Ok(mut writer) = self.writer.lock() {
+            // the diagnostics must never fail the indexing
+            let _ = writeln!(
+                writer,
+                "{} {} [{}; {}]: {}",
+                component, self.message_id, timestamp, thread_name, message
+            );
+        }
+    }
+}
+
+/// Restricts an `InfoStream` to some components, e.g. only `IW` to follow
+/// the merges without the flush details.
+pub struct ComponentFilterInfoStream<S: InfoStream> {
+    stream: S,
+    components: HashSet<String>,
+}
+
+impl<S: InfoStream> ComponentFilterInfoStream<S> {
+    pub fn new(stream: S, components: &[&str]) -> Self {
+        ComponentFilterInfoStream {
+            stream,
+            components: components.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl<S: InfoStream> InfoStream for ComponentFilterInfoStream<S> {
+    fn is_enabled(&self, component: &str) -> bool {
+        self.components.contains(component) && self.stream.is_enabled(component)
+    }
+
+    fn message(&self, component: &str, message: &str) {
+        self.stream.message(component, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_component_filter() {
+        let stream = ComponentFilterInfoStream::new(PrintStreamInfoStream::new(vec![]), &["IW"]);
+        assert!(stream.is_enabled("IW"));
+        assert!(!stream.is_enabled("DWPT"));
+        stream.message("IW", "now flush");
+
+        let written = stream.stream.writer.lock().unwrap().clone();
+        let line = String::from_utf8(written).unwrap();
+        assert!(line.starts_with("IW "));
+        assert!(line.ends_with(": now flush\n"));
+        assert!(!NoOutputInfoStream.is_enabled("IW"));
+    }
+}
@@ -60,6 +60,12 @@ pub use self::context::*;
 mod counter;
 pub use self::counter::*;
 
+mod accountable;
+pub use self::accountable::*;
+
+mod info_stream;
+pub use self::info_stream::*;
+
 mod volatile;
 pub use self::volatile::Volatile;
 
@@ -79,6 +85,8 @@ pub mod bytes_ref_hash;
 pub mod doc_id_set;
 pub mod external;
 pub mod fst;
+pub mod geo;
+pub mod hyper_log_log;
 pub mod int_block_pool;
 pub mod ints_ref;
 pub mod io;
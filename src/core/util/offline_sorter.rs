@@ -11,15 +11,26 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use core::store::{DataInput, DataOutput, Directory, IOContext, IndexInput, IndexOutput};
 use error::{ErrorKind::IllegalArgument, Result};
 
+use std::cmp::Ordering;
+use std::mem;
+use std::sync::Arc;
+
 pub const MAX_TEMP_FILES: i32 = 10;
 pub const MB: i32 = 1024 * 1024;
 pub const GB: i32 = MB * 1024;
 pub const ABSOLUTE_MIN_SORT_BUFFER_SIZE: i32 = MB / 2;
 pub const MIN_BUFFER_SIZE_MSG: &str = "At least 0.5MB RAM buffer is needed";
 
-pub struct BufferSize {}
+// estimated heap overhead of a buffered record
+const RECORD_OVERHEAD_BYTES: usize = 32;
+
+/// The RAM buffer size of an `OfflineSorter`.
+pub struct BufferSize {
+    bytes: usize,
+}
 
 impl BufferSize {
     pub fn new(bytes: i32) -> Result<BufferSize> {
@@ -34,7 +45,9 @@ impl BufferSize {
             )));
         }
 
-        Ok(BufferSize {})
+        Ok(BufferSize {
+            bytes: bytes as usize,
+        })
     }
 
     pub fn megabytes(mb: i32) -> Result<BufferSize> {
@@ -44,28 +57,274 @@ impl BufferSize {
     pub fn automatic() -> Result<BufferSize> {
         BufferSize::megabytes(1024)
     }
+
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
 }
 
-//#[derive(Debug)]
-// pub struct SortInfo {}
-//
-// pub trait BytesRefComparator {}
-//
-// pub trait SortableBytesRefArray {}
-//
-// pub struct OfflineSorter {}
-//
-// impl OfflineSorter {
-//    /// All-details constructor.  If {@code valueLength} is -1 (the default), the length of each
-//    /// value differs; otherwise, all values have the specified length.
-//    pub fn new(
-//        _dir: &Directory,
-//        _temp_file_name_prefix: &str,
-//        _comparator: Box<BytesRefComparator>,
-//        _ram_buffer_size: BufferSize,
-//        _max_temp_files: i32,
-//        _value_length: i32,
-//    ) -> Result<OfflineSorter> {
-//        unimplemented!()
-//    }
-//}
+/// Writes byte sequences to a temp file, each prefixed by its length.
+pub struct ByteSequencesWriter<O: IndexOutput> {
+    output: O,
+}
+
+impl<O: IndexOutput> ByteSequencesWriter<O> {
+    pub fn new(output: O) -> ByteSequencesWriter<O> {
+        ByteSequencesWriter { output }
+    }
+
+    pub fn name(&self) -> &str {
+        self.output.name()
+    }
+
+    pub fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.output.write_vint(bytes.len() as i32)?;
+        self.output.write_bytes(bytes, 0, bytes.len())
+    }
+
+    /// Closes the file, returns its name.
+    pub fn finish(self) -> Result<String> {
+        let name = self.output.name().to_string();
+        // the output is flushed on drop
+        Ok(name)
+    }
+}
+
+/// Reads back the byte sequences written by a `ByteSequencesWriter`.
+pub struct ByteSequencesReader {
+    input: Box<dyn IndexInput>,
+    len: i64,
+}
+
+impl ByteSequencesReader {
+    pub fn new(input: Box<dyn IndexInput>) -> ByteSequencesReader {
+        let len = input.len() as i64;
+        ByteSequencesReader { input, len }
+    }
+
+    pub fn open<D: Directory>(dir: &D, name: &str) -> Result<ByteSequencesReader> {
+        Ok(ByteSequencesReader::new(
+            dir.open_input(name, &IOContext::READ_ONCE)?,
+        ))
+    }
+
+    /// Returns the next sequence, None at the end of the file.
+    pub fn next(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.input.file_pointer() >= self.len {
+            return Ok(None);
+        }
+        let length = self.input.read_vint()?;
+        if length < 0 {
+            bail!(IllegalArgument(format!(
+                "invalid byte sequence length: {}",
+                length
+            )));
+        }
+        let mut bytes = vec![0u8; length as usize];
+        self.input.read_bytes(&mut bytes, 0, length as usize)?;
+        Ok(Some(bytes))
+    }
+}
+
+/// Sorts the byte sequences of a temp file written by a
+/// `ByteSequencesWriter` with a bounded amount of RAM: the sequences are
+/// sorted by chunks of the RAM buffer size into temp files, which are then
+/// merged, at most `max_temp_files` at once.
+pub struct OfflineSorter<D: Directory> {
+    dir: Arc<D>,
+    temp_file_name_prefix: String,
+    comparator: Box<dyn Fn(&[u8], &[u8]) -> Ordering + Send + Sync>,
+    ram_buffer_size: BufferSize,
+    max_temp_files: usize,
+}
+
+impl<D: Directory> OfflineSorter<D> {
+    pub fn new(
+        dir: Arc<D>,
+        temp_file_name_prefix: &str,
+        comparator: Box<dyn Fn(&[u8], &[u8]) -> Ordering + Send + Sync>,
+        ram_buffer_size: BufferSize,
+        max_temp_files: i32,
+    ) -> Result<OfflineSorter<D>> {
+        if max_temp_files < 2 {
+            bail!(IllegalArgument(format!(
+                "max_temp_files must be >= 2, got {}",
+                max_temp_files
+            )));
+        }
+        Ok(OfflineSorter {
+            dir,
+            temp_file_name_prefix: temp_file_name_prefix.to_string(),
+            comparator,
+            ram_buffer_size,
+            max_temp_files: max_temp_files as usize,
+        })
+    }
+
+    pub fn directory(&self) -> &Arc<D> {
+        &self.dir
+    }
+
+    /// Creates a temp file to write the sequences to sort to.
+    pub fn create_input(&self) -> Result<ByteSequencesWriter<D::TempOutput>> {
+        Ok(ByteSequencesWriter::new(self.dir.create_temp_output(
+            &self.temp_file_name_prefix,
+            "unsorted",
+            &IOContext::Default,
+        )?))
+    }
+
+    /// Sorts the sequences of the temp file `input_name`, returns the name of
+    /// the temp file holding them in order. The input file is left alone, the
+    /// caller deletes both files once done.
+    pub fn sort(&self, input_name: &str) -> Result<String> {
+        self.sort_files(&[input_name.to_string()])
+    }
+
+    /// Sorts the sequences of several temp files together, see `sort`.
+    pub fn sort_files(&self, input_names: &[String]) -> Result<String> {
+        let mut runs = vec![];
+        let res = self.sort_runs(input_names, &mut runs);
+        if res.is_err() {
+            self.delete_files(&runs);
+        }
+        res
+    }
+
+    fn sort_runs(&self, input_names: &[String], runs: &mut Vec<String>) -> Result<String> {
+        let mut buffer: Vec<Vec<u8>> = vec![];
+        let mut buffer_bytes = 0;
+        for input_name in input_names {
+            let mut reader = ByteSequencesReader::open(self.dir.as_ref(), input_name)?;
+            while let Some(bytes) = reader.next()? {
+                buffer_bytes += bytes.len() + RECORD_OVERHEAD_BYTES;
+                buffer.push(bytes);
+                if buffer_bytes >= self.ram_buffer_size.bytes {
+                    runs.push(self.write_run(&mut buffer)?);
+                    buffer_bytes = 0;
+                }
+            }
+        }
+        if !buffer.is_empty() || runs.is_empty() {
+            runs.push(self.write_run(&mut buffer)?);
+        }
+
+        while runs.len() > 1 {
+            let count = runs.len().min(self.max_temp_files);
+            let merged = self.merge_runs(&runs[..count])?;
+            let merged_runs: Vec<String> = runs.drain(..count).collect();
+            self.delete_files(&merged_runs);
+            runs.push(merged);
+        }
+        Ok(runs.pop().unwrap())
+    }
+
+    fn write_run(&self, buffer: &mut Vec<Vec<u8>>) -> Result<String> {
+        let mut records = mem::replace(buffer, vec![]);
+        let comparator = &self.comparator;
+        records.sort_by(|a, b| comparator(a, b));
+        let mut writer = self.create_temp("sort")?;
+        for record in &records {
+            writer.write(record)?;
+        }
+        writer.finish()
+    }
+
+    fn merge_runs(&self, runs: &[String]) -> Result<String> {
+        let mut readers = Vec::with_capacity(runs.len());
+        let mut heads = Vec::with_capacity(runs.len());
+        for run in runs {
+            let mut reader = ByteSequencesReader::open(self.dir.as_ref(), run)?;
+            heads.push(reader.next()?);
+            readers.push(reader);
+        }
+        let mut writer = self.create_temp("merge")?;
+        loop {
+            // few runs are merged at once, a linear scan is enough
+            let mut min: Option<usize> = None;
+            for (i, head) in heads.iter().enumerate() {
+                if let Some(ref bytes) = *head {
+                    let smaller = match min {
+                        Some(m) => {
+                            (self.comparator)(bytes, heads[m].as_ref().unwrap()) == Ordering::Less
+                        }
+                        None => true,
+                    };
+                    if smaller {
+                        min = Some(i);
+                    }
+                }
+            }
+            match min {
+                Some(i) => {
+                    writer.write(heads[i].as_ref().unwrap())?;
+                    heads[i] = readers[i].next()?;
+                }
+                None => break,
+            }
+        }
+        writer.finish()
+    }
+
+    fn create_temp(&self, suffix: &str) -> Result<ByteSequencesWriter<D::TempOutput>> {
+        Ok(ByteSequencesWriter::new(self.dir.create_temp_output(
+            &self.temp_file_name_prefix,
+            suffix,
+            &IOContext::Default,
+        )?))
+    }
+
+    fn delete_files(&self, names: &[String]) {
+        for name in names {
+            if let Err(e) = self.dir.delete_file(name) {
+                warn!("failed to delete temp file {}: {:?}", name, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::store::{FSDirectory, NativeFSLockFactory};
+    use std::fs;
+
+    #[test]
+    fn test_sort() {
+        let path = ::std::env::temp_dir().join("rucene_offline_sorter");
+        let _ = fs::remove_dir_all(&path);
+        let dir = Arc::new(FSDirectory::new(&path, NativeFSLockFactory::default()).unwrap());
+        // a tiny buffer and 2 temp files at most to go through several merges
+        let sorter = OfflineSorter::new(
+            Arc::clone(&dir),
+            "test",
+            Box::new(|a: &[u8], b: &[u8]| a.cmp(b)),
+            BufferSize { bytes: 200 },
+            2,
+        )
+        .unwrap();
+
+        let mut input = sorter.create_input().unwrap();
+        let mut expected = vec![];
+        for i in 0..100u32 {
+            let bytes = format!("{}", (i * 37) % 101).into_bytes();
+            input.write(&bytes).unwrap();
+            expected.push(bytes);
+        }
+        let input_name = input.finish().unwrap();
+        let sorted_name = sorter.sort(&input_name).unwrap();
+        expected.sort();
+
+        let mut reader = ByteSequencesReader::open(dir.as_ref(), &sorted_name).unwrap();
+        let mut sorted = vec![];
+        while let Some(bytes) = reader.next().unwrap() {
+            sorted.push(bytes);
+        }
+        assert_eq!(sorted, expected);
+
+        dir.delete_file(&input_name).unwrap();
+        dir.delete_file(&sorted_name).unwrap();
+        assert!(dir.list_all().unwrap().is_empty());
+        fs::remove_dir_all(&path).unwrap();
+    }
+}
@@ -16,6 +16,7 @@ use core::store::IndexInput;
 use core::util::packed::PackedIntsNullReader;
 use core::util::packed_misc::{self, Reader, ReaderEnum};
 use core::util::DocId;
+use core::util::{vec_ram_bytes, Accountable};
 use core::util::{LongValues, LongValuesContext};
 use error::ErrorKind::{CorruptIndex, IllegalArgument};
 use error::Result;
@@ -106,6 +107,19 @@ impl MonotonicBlockPackedReader {
     }
 }
 
+impl Accountable for MonotonicBlockPackedReader {
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.min_values)
+            + vec_ram_bytes(&self.averages)
+            + vec_ram_bytes(&self.sub_readers)
+            + self
+                .sub_readers
+                .iter()
+                .map(Accountable::ram_bytes_used)
+                .sum::<usize>()
+    }
+}
+
 impl LongValues for MonotonicBlockPackedReader {
     fn get64_with_ctx(
         &self,
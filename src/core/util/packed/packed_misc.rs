@@ -15,6 +15,7 @@ use core::codec::codec_util;
 use core::store::{DataInput, DataOutput, IndexInput};
 use core::util::bit_util::{BitsRequired, UnsignedShift, ZigZagEncoding};
 use core::util::packed::packed_ints_null_reader::PackedIntsNullReader;
+use core::util::{vec_ram_bytes, Accountable};
 
 use error::{
     ErrorKind::{IOError, IllegalArgument, UnexpectedEOF},
@@ -134,6 +135,22 @@ pub enum ReaderEnum {
     PackedIntsNull(PackedIntsNullReader),
 }
 
+impl Accountable for ReaderEnum {
+    fn ram_bytes_used(&self) -> usize {
+        match self {
+            ReaderEnum::Direct8(r) => vec_ram_bytes(&r.values),
+            ReaderEnum::Direct16(r) => vec_ram_bytes(&r.values),
+            ReaderEnum::Direct32(r) => vec_ram_bytes(&r.values),
+            ReaderEnum::Direct64(r) => vec_ram_bytes(&r.values),
+            ReaderEnum::Packed8TB(r) => vec_ram_bytes(&r.blocks),
+            ReaderEnum::Packed16TB(r) => vec_ram_bytes(&r.blocks),
+            ReaderEnum::Packed64(r) => vec_ram_bytes(&r.blocks),
+            ReaderEnum::Packed64SB(r) => vec_ram_bytes(&r.blocks),
+            ReaderEnum::PackedIntsNull(_) => 0,
+        }
+    }
+}
+
 impl ReaderEnum {
     pub fn into_mutable(self) -> MutableEnum {
         match self {
@@ -12,7 +12,7 @@
 // limitations under the License.
 
 use core::store::{DataInput, DataOutput, IndexInput};
-use core::util::BytesRef;
+use core::util::{vec_ram_bytes, Accountable, BytesRef};
 
 use error::{
     ErrorKind::{IllegalArgument, IllegalState},
@@ -333,6 +333,12 @@ pub struct PagedBytesReader {
     blocks: Vec<Vec<u8>>,
 }
 
+impl Accountable for PagedBytesReader {
+    fn ram_bytes_used(&self) -> usize {
+        vec_ram_bytes(&self.blocks) + self.blocks.iter().map(vec_ram_bytes).sum::<usize>()
+    }
+}
+
 impl PagedBytesReader {
     pub fn new(paged_bytes: PagedBytes) -> Self {
         let blocks = paged_bytes.blocks;
@@ -13,7 +13,7 @@
 
 use std::{
     mem,
-    sync::{Arc, Mutex, MutexGuard},
+    sync::{Arc, Mutex, MutexGuard, RwLock},
 };
 
 use error::{
@@ -24,7 +24,8 @@ use error::{
 pub struct ReferenceManagerBase<T: ?Sized> {
     lock: Mutex<()>,
     refresh_lock: Mutex<()>,
-    current: Option<Arc<T>>,
+    // read by `acquire` while a refresh may swap it
+    current: RwLock<Option<Arc<T>>>,
 }
 
 impl<T: ?Sized> ReferenceManagerBase<T> {
@@ -32,11 +33,11 @@ impl<T: ?Sized> ReferenceManagerBase<T> {
         Self {
             lock: Mutex::new(()),
             refresh_lock: Mutex::new(()),
-            current: Some(current),
+            current: RwLock::new(Some(current)),
         }
     }
     fn ensure_open(&self) -> Result<()> {
-        if self.current.is_none() {
+        if self.current.read()?.is_none() {
             bail!(AlreadyClosed("this ReferenceManager is closed".into()));
         }
         Ok(())
@@ -54,14 +55,9 @@ pub trait ReferenceManager<T: ?Sized, RL: RefreshListener> {
     fn refresh_listener(&self) -> Option<&RL>;
 
     fn _swap_reference(&self, new_reference: Option<Arc<T>>, _l: &MutexGuard<()>) -> Result<()> {
-        // let _l = base.lock.lock().unwrap();
-        // we use base.lock to make sure this operation is safe
-        let base_ptr =
-            self.base() as *const ReferenceManagerBase<T> as *mut ReferenceManagerBase<T>;
-        unsafe {
-            if let Some(old_ref) = mem::replace(&mut (*base_ptr).current, new_reference) {
-                self.release(old_ref.as_ref())?;
-            }
+        let old_ref = mem::replace(&mut *self.base().current.write()?, new_reference);
+        if let Some(old_ref) = old_ref {
+            self.release(old_ref.as_ref())?;
         }
         Ok(())
     }
@@ -82,7 +78,7 @@ pub trait ReferenceManager<T: ?Sized, RL: RefreshListener> {
     /// reference to None to prevent accidental usage after it has been released.
     fn acquire(&self) -> Result<Arc<T>> {
         loop {
-            if let Some(ref cur) = self.base().current {
+            if let Some(ref cur) = *self.base().current.read()? {
                 if self.try_inc_ref(cur)? {
                     return Ok(Arc::clone(cur));
                 }
@@ -112,7 +108,7 @@ pub trait ReferenceManager<T: ?Sized, RL: RefreshListener> {
     /// {@link AlreadyClosedException}.
     fn close(&self) -> Result<()> {
         let _l = self.base().lock.lock().unwrap();
-        if self.base().current.is_some() {
+        if self.base().current.read()?.is_some() {
             // make sure we can call this more than once
             // closeable javadoc says:
             // if this is already closed then invoking this method has no effect.
@@ -11,6 +11,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+/// The largest value of `SmallFloat::long_to_int4` for an `i32`.
+const MAX_INT4: i32 = 231;
+/// The number of small values `SmallFloat::int_to_byte4` encodes exactly, the
+/// byte values left over by the largest int4 of an `i32`.
+const NUM_FREE_VALUES: i32 = 255 - MAX_INT4;
+
+/// Lossy encodings of numbers into a single byte, e.g. for norms.
 pub struct SmallFloat;
 impl SmallFloat {
     pub fn float_to_byte315(f: f32) -> u8 {
@@ -34,6 +41,61 @@ impl SmallFloat {
             f32::from_bits(bits)
         }
     }
+
+    /// Encodes a non-negative long into 4 bits, keeping its 4 most significant
+    /// bits: values lower than 16 are exact, larger ones are rounded down.
+    pub fn long_to_int4(i: i64) -> i32 {
+        debug_assert!(i >= 0);
+        let num_bits = 64 - i.leading_zeros() as i32;
+        if num_bits < 4 {
+            // subnormal value
+            i as i32
+        } else {
+            // normal value
+            let shift = num_bits - 4;
+            // only keep the 4 most significant bits, the first one is implicit
+            let mut encoded = (i >> shift) as i32 & 0x07;
+            // encode the shift, plus one so that a shift of 0 isn't a subnormal
+            encoded |= (shift + 1) << 3;
+            encoded
+        }
+    }
+
+    /// Decodes a value encoded by `long_to_int4`.
+    pub fn int4_to_long(i: i32) -> i64 {
+        let bits = i64::from(i & 0x07);
+        let shift = (i >> 3) - 1;
+        if shift == -1 {
+            // subnormal value
+            bits
+        } else {
+            // normal value
+            (bits | 0x08) << shift
+        }
+    }
+
+    /// Encodes a non-negative integer, e.g. the length of a field, into a byte
+    /// preserving its order. Small values are exact, larger ones keep their 4
+    /// most significant bits like `long_to_int4`.
+    pub fn int_to_byte4(i: i32) -> u8 {
+        debug_assert!(i >= 0);
+        if i < NUM_FREE_VALUES {
+            i.max(0) as u8
+        } else {
+            (NUM_FREE_VALUES + Self::long_to_int4(i64::from(i - NUM_FREE_VALUES))) as u8
+        }
+    }
+
+    /// Decodes a value encoded by `int_to_byte4`.
+    pub fn byte4_to_int(b: u8) -> i32 {
+        let i = i32::from(b);
+        if i < NUM_FREE_VALUES {
+            i
+        } else {
+            let decoded = i64::from(NUM_FREE_VALUES) + Self::int4_to_long(i - NUM_FREE_VALUES);
+            decoded.min(i64::from(i32::max_value())) as i32
+        }
+    }
 }
 
 #[cfg(test)]
@@ -114,4 +176,41 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_int4() {
+        assert_eq!(
+            MAX_INT4,
+            SmallFloat::long_to_int4(i64::from(i32::max_value()))
+        );
+        for i in 0..16 {
+            assert_eq!(i, SmallFloat::long_to_int4(i64::from(i)));
+        }
+        let mut prev = -1;
+        for i in 0..=MAX_INT4 {
+            let decoded = SmallFloat::int4_to_long(i);
+            assert!(decoded > prev);
+            assert_eq!(i, SmallFloat::long_to_int4(decoded));
+            prev = decoded;
+        }
+        for _ in 0..10_000 {
+            let l = i64::from(rand::random::<u32>() >> 1);
+            let decoded = SmallFloat::int4_to_long(SmallFloat::long_to_int4(l));
+            assert!(decoded <= l);
+            // only the 4 most significant bits are kept
+            assert!(l - decoded <= l >> 3);
+        }
+    }
+
+    #[test]
+    fn test_byte4() {
+        for i in 0..NUM_FREE_VALUES + 16 {
+            assert_eq!(i, SmallFloat::byte4_to_int(SmallFloat::int_to_byte4(i)));
+        }
+        assert_eq!(255, SmallFloat::int_to_byte4(i32::max_value()));
+        for b in 1..256 {
+            let decoded = SmallFloat::byte4_to_int(b as u8);
+            assert!(decoded > SmallFloat::byte4_to_int((b - 1) as u8));
+            assert_eq!(b as u8, SmallFloat::int_to_byte4(decoded));
+        }
+    }
 }
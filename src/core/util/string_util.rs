@@ -32,8 +32,9 @@ pub fn bytes_subtract(bytes_per_dim: usize, dim: usize, a: &[u8], b: &[u8], resu
     let start = dim * bytes_per_dim;
     let end = start + bytes_per_dim;
     let mut borrow = 0;
-    let i = end - 1;
-    while i >= start {
+    let mut i = end;
+    while i > start {
+        i -= 1;
         let mut diff: i32 = (a[i] as i32 & 0xFF) - (b[i] as i32 & 0xFF) - borrow;
         if diff < 0 {
             diff += 256;
@@ -90,4 +91,15 @@ mod tests {
         let strv = id2str(&v[..]);
         assert_eq!("4161047F", strv);
     }
+
+    #[test]
+    fn test_bytes_subtract() {
+        let a = vec![0u8, 0, 1, 0, 9, 9];
+        let b = vec![0u8, 0, 0, 1, 9, 8];
+        let mut result = vec![0u8; 2];
+        bytes_subtract(2, 1, &a, &b, &mut result);
+        assert_eq!(vec![0u8, 255], result);
+        bytes_subtract(2, 2, &a, &b, &mut result);
+        assert_eq!(vec![0u8, 1], result);
+    }
 }
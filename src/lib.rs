@@ -35,6 +35,8 @@ extern crate serde;
 extern crate serde_derive;
 extern crate serde_json;
 
+#[cfg(feature = "encryption")]
+extern crate aes_ctr;
 extern crate byteorder;
 extern crate bytes;
 extern crate crc;
@@ -47,6 +49,8 @@ extern crate num_traits;
 extern crate smallvec;
 extern crate thread_local;
 extern crate unicode_reader;
+#[cfg(feature = "zstd")]
+extern crate zstd;
 
 pub mod core;
 pub mod error;